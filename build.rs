@@ -0,0 +1,44 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+	// Git commit of the source tree, if it is built from a repository
+	let git_commit = Command::new("git")
+		.args(["rev-parse", "--short=12", "HEAD"])
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.and_then(|output| String::from_utf8(output.stdout).ok())
+		.map(|commit| commit.trim().to_string())
+		.filter(|commit| !commit.is_empty())
+		.unwrap_or_else(|| "unknown".to_string());
+	println!("cargo:rustc-env=REDWOOD_GIT_COMMIT={}", git_commit);
+
+	// Honor `SOURCE_DATE_EPOCH` for reproducible builds
+	let build_timestamp = std::env::var("SOURCE_DATE_EPOCH")
+		.ok()
+		.and_then(|epoch| epoch.parse::<u64>().ok())
+		.unwrap_or_else(|| {
+			SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map_or(0, |duration| duration.as_secs())
+		});
+	println!(
+		"cargo:rustc-env=REDWOOD_BUILD_TIMESTAMP={}",
+		build_timestamp
+	);
+
+	let mut features: Vec<String> = std::env::vars()
+		.filter_map(|(key, _)| {
+			key.strip_prefix("CARGO_FEATURE_")
+				.map(|feature| feature.to_lowercase().replace('_', "-"))
+		})
+		.collect();
+	features.sort();
+	println!("cargo:rustc-env=REDWOOD_FEATURES={}", features.join(","));
+
+	println!("cargo:rerun-if-changed=build.rs");
+	println!("cargo:rerun-if-changed=.git/HEAD");
+	println!("cargo:rerun-if-changed=.git/refs/heads");
+	println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
@@ -0,0 +1,707 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use super::auth;
+use super::config::Config;
+use super::database::{Article, CreateArticleError, DbError, ImportedArticle, ItemId};
+use super::db_lock::DbLock;
+use super::dictionary::{self, DictionaryError};
+use super::timestamp::UtcTimestamp;
+
+/// Largest accepted JSON body, as for the preview of an article
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Source articles per page of `/api/links`, unless `limit` is given
+const LINKS_PAGE_SIZE: u32 = 100;
+
+/// Largest accepted `limit` of `/api/links`
+const MAX_LINKS_PAGE_SIZE: u32 = 1000;
+
+/// Largest accepted body of `PUT /api/article/{id}/full`, which holds
+/// the whole history of the article
+const MAX_FULL_BODY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Articles whose JSON with the history is larger are sent as JSON
+/// lines by `GET /api/article/{id}/full`
+const FULL_JSON_LINES_THRESHOLD: usize = 1024 * 1024;
+
+const JSON_LINES: &str = "application/x-ndjson";
+
+/// JSON API for scripts under `/api/`. Every response, including
+/// errors, is JSON: handlers reject with an `ApiError`, which
+/// `handle_rejection` in `main` turns into the response. The other
+/// `/api/` routes (dictionary, favicon, preview) serve the pages of the
+/// wiki and stay in `main`, their errors take the same path.
+pub fn routes(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+	let editor = auth::editor(config.clone());
+	let db = warp::any().map(move || db.clone());
+	let config = warp::any().map(move || config.clone());
+	let article = warp::get()
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path::end())
+		.then(article_get)
+		.and_then(reject_error);
+	let links = warp::get()
+		.and(warp::path("links"))
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::query::<HashMap<String, String>>())
+		.then(links_get)
+		.and_then(reject_error);
+	let article_links = warp::get()
+		.and(warp::path("links"))
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path::end())
+		.then(article_links_get)
+		.and_then(reject_error);
+	let article_full = warp::get()
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path("full"))
+		.and(warp::path::end())
+		.then(article_full_get)
+		.and_then(reject_error);
+	let article_full_replace = warp::put()
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path("full"))
+		.and(warp::path::end())
+		.and(warp::header::optional::<String>("content-type"))
+		.and(
+			warp::body::content_length_limit(MAX_FULL_BODY_SIZE)
+				.and(warp::body::bytes())
+				.or_else(|rejection: warp::Rejection| async move {
+					Err(warp::reject::custom(body_error(
+						&rejection,
+						MAX_FULL_BODY_SIZE,
+					)))
+				}),
+		)
+		.then(article_full_put)
+		.and_then(reject_error);
+	let article_create = warp::post()
+		.and(warp::path("article"))
+		.and(warp::path::end())
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(json_body())
+		.then(article_post)
+		.and_then(reject_error);
+	let article_update = warp::put()
+		.and(warp::path("article"))
+		.and(editor)
+		.and(db)
+		.and(config)
+		.and(warp::path::param::<String>())
+		.and(warp::path::end())
+		.and(json_body())
+		.then(article_put)
+		.and_then(reject_error);
+	warp::path("api").and(
+		article
+			.or(article_full)
+			.unify()
+			.or(article_full_replace)
+			.unify()
+			.or(article_create)
+			.unify()
+			.or(article_update)
+			.unify()
+			.or(links)
+			.unify()
+			.or(article_links)
+			.unify(),
+	)
+}
+
+/// The body of every error response of the API. `code` is one of a
+/// fixed set that programs can rely on, `message` is meant for humans
+/// and may change.
+///
+/// | `code`         | Status                   |
+/// |----------------|--------------------------|
+/// | `not_found`    | 404                      |
+/// | `validation`   | 400, 411 or 415          |
+/// | `conflict`     | 409                      |
+/// | `inconsistent` | 422                      |
+/// | `too_large`    | 413                      |
+/// | `rate_limited` | 429                      |
+/// | `read_only`    | 403                      |
+/// | `unauthorized` | 401                      |
+/// | `internal`     | 500, the cause is logged |
+/// | `unavailable`  | 503, to be retried later |
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+	#[serde(skip)]
+	status: StatusCode,
+	pub code: &'static str,
+	pub message: String,
+	/// Machine-readable specifics, e.g. the offending field
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub details: Option<serde_json::Value>,
+}
+
+impl warp::reject::Reject for ApiError {}
+
+impl ApiError {
+	fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> ApiError {
+		ApiError {
+			status,
+			code,
+			message: message.into(),
+			details: None,
+		}
+	}
+
+	pub fn not_found(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::NOT_FOUND, "not_found", message)
+	}
+
+	pub fn validation(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::BAD_REQUEST, "validation", message)
+	}
+
+	pub fn conflict(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::CONFLICT, "conflict", message)
+	}
+
+	/// The body is well-formed, but does not make sense as a whole
+	pub fn inconsistent(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "inconsistent", message)
+	}
+
+	pub fn too_large(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "too_large", message)
+	}
+
+	pub fn rate_limited(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", message)
+	}
+
+	pub fn read_only(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::FORBIDDEN, "read_only", message)
+	}
+
+	pub fn unauthorized(message: impl Into<String>) -> ApiError {
+		ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+	}
+
+	/// The database is locked by another process, the request can be
+	/// repeated later
+	pub fn unavailable() -> ApiError {
+		ApiError::new(
+			StatusCode::SERVICE_UNAVAILABLE,
+			"unavailable",
+			"the database is busy",
+		)
+	}
+
+	/// The message does not tell what went wrong, log the cause instead
+	pub fn internal() -> ApiError {
+		ApiError::new(
+			StatusCode::INTERNAL_SERVER_ERROR,
+			"internal",
+			"internal error",
+		)
+	}
+
+	pub fn with_details(mut self, details: serde_json::Value) -> ApiError {
+		self.details = Some(details);
+		self
+	}
+
+	/// The error for a rejection of warp with `status`, e.g. for an
+	/// unknown path or a body that is too large
+	pub fn from_status(status: StatusCode, message: &str) -> ApiError {
+		match status {
+			StatusCode::NOT_FOUND => ApiError::not_found(message),
+			StatusCode::CONFLICT => ApiError::conflict(message),
+			StatusCode::UNPROCESSABLE_ENTITY => ApiError::inconsistent(message),
+			StatusCode::PAYLOAD_TOO_LARGE => ApiError::too_large(message),
+			StatusCode::TOO_MANY_REQUESTS => ApiError::rate_limited(message),
+			StatusCode::FORBIDDEN => ApiError::read_only(message),
+			StatusCode::UNAUTHORIZED => ApiError::unauthorized(message),
+			StatusCode::SERVICE_UNAVAILABLE => ApiError::unavailable(),
+			status if status.is_client_error() => ApiError::new(status, "validation", message),
+			_ => ApiError::internal(),
+		}
+	}
+
+	pub fn to_response(&self) -> warp::reply::Response {
+		warp::reply::with_status(warp::reply::json(self), self.status).into_response()
+	}
+}
+
+impl From<DbError> for ApiError {
+	fn from(err: DbError) -> ApiError {
+		match err {
+			DbError::NotFound => ApiError::not_found("not found"),
+			DbError::AlreadyExists => ApiError::conflict(err.to_string()),
+			DbError::Cycle | DbError::NothingToUpdate | DbError::EmptyTitle => {
+				ApiError::validation(err.to_string())
+			}
+			DbError::Busy => ApiError::unavailable(),
+			DbError::DateOutOfRange(_) | DbError::Corrupt | DbError::Sqlite(_) => {
+				log::error!("Database error in the API: {}", err);
+				ApiError::internal()
+			}
+		}
+	}
+}
+
+impl From<DictionaryError> for ApiError {
+	fn from(err: DictionaryError) -> ApiError {
+		match err {
+			DictionaryError::InvalidWord => ApiError::validation(format!(
+				"Invalid word, only letters, digits, _, - and ' are allowed (at most {} characters)",
+				dictionary::MAX_WORD_LENGTH
+			))
+			.with_details(serde_json::json!({ "field": "word" })),
+			DictionaryError::Full => ApiError::conflict(format!(
+				"Dictionary is full ({} words)",
+				dictionary::MAX_WORDS
+			)),
+			DictionaryError::NoFile => ApiError::read_only("No dictionary file is configured"),
+			DictionaryError::Io(err) => {
+				log::error!("Could not write dictionary: {:?}", err);
+				ApiError::internal()
+			}
+		}
+	}
+}
+
+/// Turn the error of a handler into the rejection that is answered
+/// with it
+async fn reject_error(
+	result: Result<warp::reply::Response, ApiError>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	result.map_err(warp::reject::custom)
+}
+
+/// The JSON body of the request, rejected with an `ApiError` if it is
+/// too large or malformed
+fn json_body<T: DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+	warp::body::content_length_limit(MAX_BODY_SIZE)
+		.and(warp::body::json())
+		.or_else(|rejection: warp::Rejection| async move {
+			Err(warp::reject::custom(body_error(&rejection, MAX_BODY_SIZE)))
+		})
+}
+
+/// The error for a body that warp rejected, `limit` is its largest
+/// accepted size
+fn body_error(rejection: &warp::Rejection, limit: u64) -> ApiError {
+	if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+		ApiError::too_large("body too large").with_details(serde_json::json!({ "limit": limit }))
+	} else if rejection.find::<warp::reject::LengthRequired>().is_some() {
+		ApiError::new(
+			StatusCode::LENGTH_REQUIRED,
+			"validation",
+			"content length required",
+		)
+	} else if rejection
+		.find::<warp::reject::UnsupportedMediaType>()
+		.is_some()
+	{
+		ApiError::new(
+			StatusCode::UNSUPPORTED_MEDIA_TYPE,
+			"validation",
+			"expected JSON",
+		)
+	} else {
+		let reason = rejection
+			.find::<warp::body::BodyDeserializeError>()
+			.map(|err| err.to_string());
+		invalid_body(reason)
+	}
+}
+
+fn invalid_body(reason: Option<String>) -> ApiError {
+	let error = ApiError::validation("invalid body");
+	match reason {
+		Some(reason) => error.with_details(serde_json::json!({ "reason": reason })),
+		None => error,
+	}
+}
+
+fn parse_id(id: &str) -> Result<ItemId, ApiError> {
+	id.parse::<ItemId>().map_err(|_| {
+		ApiError::validation("invalid id").with_details(serde_json::json!({ "field": "id" }))
+	})
+}
+
+/// The article with all its fields, deleted articles are not found
+async fn article_get(db: Arc<DbLock>, id: String) -> Result<warp::reply::Response, ApiError> {
+	let id = parse_id(&id)?;
+	match db.lock().await.get_article(id)? {
+		Some(article) => Ok(warp::reply::json(&article).into_response()),
+		None => Err(ApiError::not_found("not found")),
+	}
+}
+
+/// The first line of an article sent as JSON lines
+#[derive(Serialize)]
+struct FullArticleHead<'a> {
+	#[serde(flatten)]
+	article: &'a Article,
+	tags: &'a [String],
+}
+
+/// The article with its tags and all revisions, oldest first, in the
+/// format of an entry of `Database::export_all`. Larger than
+/// `FULL_JSON_LINES_THRESHOLD`, it is sent in chunks as JSON lines
+/// instead: the article with its tags on the first line, then one
+/// revision per line. `PUT` takes both formats.
+async fn article_full_get(db: Arc<DbLock>, id: String) -> Result<warp::reply::Response, ApiError> {
+	let id = parse_id(&id)?;
+	let Some(exported) = db.lock().await.export_article(id)? else {
+		return Err(ApiError::not_found("not found"));
+	};
+	// Serializing the plain fields cannot fail
+	let json = serde_json::to_string(&exported).unwrap_or_default();
+	if json.len() <= FULL_JSON_LINES_THRESHOLD {
+		return Ok(
+			warp::reply::with_header(json, "Content-Type", "application/json").into_response(),
+		);
+	}
+	let head = FullArticleHead {
+		article: &exported.article,
+		tags: &exported.tags,
+	};
+	let lines = std::iter::once(serde_json::to_string(&head).unwrap_or_default())
+		.chain(
+			exported
+				.revisions
+				.iter()
+				.map(|revision| serde_json::to_string(revision).unwrap_or_default()),
+		)
+		.map(|line| Ok::<_, Infallible>(line + "\n"))
+		.collect::<Vec<_>>();
+	let body = warp::hyper::Body::wrap_stream(tokio_stream::iter(lines));
+	Ok(
+		warp::reply::with_header(warp::reply::Response::new(body), "Content-Type", JSON_LINES)
+			.into_response(),
+	)
+}
+
+/// Replace the article with its tags and revision history, e.g. with
+/// the article of another wiki from `GET /api/article/{id}/full`, and
+/// answer with the replaced article. The ID in the body is ignored, it
+/// may differ between wikis. The history must be consistent (see
+/// `ImportedArticle::check_history`), otherwise the first problem is
+/// answered with 422.
+async fn article_full_put(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	id: String,
+	content_type: Option<String>,
+	body: warp::hyper::body::Bytes,
+) -> Result<warp::reply::Response, ApiError> {
+	let id = parse_id(&id)?;
+	if !config.api.article_sync {
+		return Err(ApiError::read_only("article sync is disabled"));
+	}
+	let Ok(body) = std::str::from_utf8(&body) else {
+		return Err(invalid_body(Some("not UTF-8".to_string())));
+	};
+	let is_json_lines = content_type.is_some_and(|content_type| {
+		content_type.split(';').next().map(str::trim) == Some(JSON_LINES)
+	});
+	let article = if is_json_lines {
+		ImportedArticle::from_json_lines(body)
+	} else {
+		serde_json::from_str(body)
+	}
+	.map_err(|err| invalid_body(Some(err.to_string())))?;
+	if let Err(err) = article.check_history() {
+		return Err(ApiError::inconsistent(err.problem)
+			.with_details(serde_json::json!({ "index": err.index })));
+	}
+	let links = |text: &str| super::render::article_refs(text, super::render_options(&config));
+	let mut db = db.lock().await;
+	db.replace_article(id, article, links)?;
+	super::run_save_hooks(&mut db, &config, id);
+	match db.get_article(id)? {
+		Some(article) => Ok(warp::reply::json(&article).into_response()),
+		None => {
+			log::error!("Article {} is gone after replacing it through the API", id);
+			Err(ApiError::internal())
+		}
+	}
+}
+
+/// All links between articles as JSON lines of `{"from", "to",
+/// "kind"}`, ordered by source. A page has the links of up to `limit`
+/// source articles with an ID above `after`, a full page has a `Link`
+/// header to the next one. The last source of a page is the `after` of
+/// the next one, an empty page is the end.
+async fn links_get(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	query: HashMap<String, String>,
+) -> Result<warp::reply::Response, ApiError> {
+	let after = match query.get("after") {
+		Some(after) => Some(after.parse::<ItemId>().map_err(|_| {
+			ApiError::validation("invalid after")
+				.with_details(serde_json::json!({ "field": "after" }))
+		})?),
+		None => None,
+	};
+	let limit = match query.get("limit") {
+		Some(limit) => match limit.parse::<u32>() {
+			Ok(limit @ 1..=MAX_LINKS_PAGE_SIZE) => limit,
+			_ => {
+				return Err(ApiError::validation(format!(
+					"limit must be between 1 and {}",
+					MAX_LINKS_PAGE_SIZE
+				))
+				.with_details(serde_json::json!({ "field": "limit" })))
+			}
+		},
+		None => LINKS_PAGE_SIZE,
+	};
+	let Some(links) = db.lock().await.get_links(after, limit) else {
+		return Err(ApiError::internal());
+	};
+	let mut body = String::new();
+	for link in &links {
+		// Serializing the plain fields cannot fail
+		body.push_str(&serde_json::to_string(link).unwrap_or_default());
+		body.push('\n');
+	}
+	let mut sources = links.iter().map(|link| link.src).collect::<Vec<_>>();
+	sources.dedup();
+	let mut response = warp::reply::with_header(body, "Content-Type", JSON_LINES).into_response();
+	if sources.len() as u32 == limit {
+		if let Some(last) = sources.last() {
+			let next = config.url(&format!("/api/links?after={}&limit={}", last, limit));
+			if let Ok(value) = format!("<{}>; rel=\"next\"", next).parse() {
+				response
+					.headers_mut()
+					.insert(warp::http::header::LINK, value);
+			}
+		}
+	}
+	Ok(response)
+}
+
+/// The outgoing and incoming links of an article, deleted articles are
+/// not found
+async fn article_links_get(db: Arc<DbLock>, id: String) -> Result<warp::reply::Response, ApiError> {
+	let id = parse_id(&id)?;
+	let mut db = db.lock().await;
+	if db.get_article(id)?.is_none() {
+		return Err(ApiError::not_found("not found"));
+	}
+	match db.get_article_links(id) {
+		Some(links) => Ok(warp::reply::json(&links).into_response()),
+		None => Err(ApiError::internal()),
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewArticle {
+	title: String,
+	#[serde(default)]
+	text: String,
+}
+
+/// Create an article, answered with the new article and its location
+async fn article_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	new: NewArticle,
+) -> Result<warp::reply::Response, ApiError> {
+	if new.title.trim().is_empty() {
+		return Err(empty_title());
+	}
+	let mut db = db.lock().await;
+	// Also taken by deleted articles, see `get_title_owner`
+	if db.get_title_owner(&new.title).is_some() {
+		return Err(title_taken());
+	}
+	let new_title = new.title.clone();
+	let created = db.create_article(&Article {
+		id: 0.into(),
+		title: new.title,
+		text: new.text,
+		date_created: UtcTimestamp::now(),
+		date_modified: UtcTimestamp::now(),
+		revision: 0,
+		slug: String::new(), // Generated from the title by the database
+	});
+	let created = match created {
+		Ok(id) => id,
+		Err(CreateArticleError::DuplicateTitle) => return Err(title_taken()),
+		Err(CreateArticleError::EmptyTitle) => return Err(empty_title()),
+		Err(_) => return Err(ApiError::internal()),
+	};
+	let Some(article) = db.get_article(created)? else {
+		log::error!("Could not create article {:?} through the API", new_title);
+		return Err(ApiError::internal());
+	};
+	super::save_links(&mut db, &config, article.id, &article.text);
+	super::run_save_hooks(&mut db, &config, article.id);
+	Ok(warp::reply::with_header(
+		warp::reply::with_status(warp::reply::json(&article), StatusCode::CREATED),
+		warp::http::header::LOCATION,
+		config.url(&format!("/api/article/{}", article.id)),
+	)
+	.into_response())
+}
+
+/// Fields of an article to change, absent fields are left alone
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArticleChanges {
+	title: Option<String>,
+	text: Option<String>,
+	/// Summary of the changes for the history of the article
+	summary: Option<String>,
+}
+
+/// Update the title and/or text of an article, answered with the
+/// updated article
+async fn article_put(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	id: String,
+	changes: ArticleChanges,
+) -> Result<warp::reply::Response, ApiError> {
+	let id = parse_id(&id)?;
+	if changes.title.is_none() && changes.text.is_none() {
+		return Err(ApiError::validation("nothing to update")
+			.with_details(serde_json::json!({ "fields": ["title", "text"] })));
+	}
+	if changes
+		.title
+		.as_deref()
+		.is_some_and(|title| title.trim().is_empty())
+	{
+		return Err(empty_title());
+	}
+	let mut db = db.lock().await;
+	if db.get_article(id)?.is_none() {
+		return Err(ApiError::not_found("not found"));
+	}
+	if let Some(title) = &changes.title {
+		if db.get_title_owner(title).is_some_and(|owner| owner != id) {
+			return Err(title_taken());
+		}
+	}
+	match db.update_article(
+		id,
+		changes.title.as_deref(),
+		changes.text.as_deref(),
+		changes.summary.as_deref(),
+		None,
+	) {
+		Ok(0) => return Err(ApiError::not_found("not found")),
+		Ok(_) => (),
+		Err(err) => return Err(err.into()),
+	}
+	if let Some(text) = &changes.text {
+		super::save_links(&mut db, &config, id, text);
+	}
+	super::run_save_hooks(&mut db, &config, id);
+	match db.get_article(id)? {
+		Some(article) => Ok(warp::reply::json(&article).into_response()),
+		None => {
+			log::error!("Article {} is gone after updating it through the API", id);
+			Err(ApiError::internal())
+		}
+	}
+}
+
+fn empty_title() -> ApiError {
+	ApiError::validation("empty title").with_details(serde_json::json!({ "field": "title" }))
+}
+
+fn title_taken() -> ApiError {
+	ApiError::conflict("title already exists").with_details(serde_json::json!({ "field": "title" }))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn code_and_status(error: ApiError) -> (&'static str, u16) {
+		(error.code, error.status.as_u16())
+	}
+
+	#[test]
+	fn test_error_codes() {
+		assert_eq!(
+			code_and_status(ApiError::from(DbError::NotFound)),
+			("not_found", 404)
+		);
+		assert_eq!(
+			code_and_status(ApiError::from(DbError::AlreadyExists)),
+			("conflict", 409)
+		);
+		assert_eq!(
+			code_and_status(ApiError::from(DbError::Cycle)),
+			("validation", 400)
+		);
+		let sqlite = ApiError::from(DbError::Sqlite(rusqlite::Error::InvalidQuery));
+		assert_eq!(sqlite.message, "internal error");
+		assert_eq!(code_and_status(sqlite), ("internal", 500));
+		assert_eq!(
+			code_and_status(ApiError::from(DbError::Busy)),
+			("unavailable", 503)
+		);
+		assert_eq!(
+			code_and_status(ApiError::from(DbError::Corrupt)),
+			("internal", 500)
+		);
+		assert_eq!(
+			code_and_status(ApiError::from(DictionaryError::NoFile)),
+			("read_only", 403)
+		);
+		assert_eq!(
+			code_and_status(ApiError::from(DictionaryError::Full)),
+			("conflict", 409)
+		);
+
+		for (status, code) in [
+			(StatusCode::NOT_FOUND, "not_found"),
+			(StatusCode::BAD_REQUEST, "validation"),
+			(StatusCode::LENGTH_REQUIRED, "validation"),
+			(StatusCode::UNSUPPORTED_MEDIA_TYPE, "validation"),
+			(StatusCode::CONFLICT, "conflict"),
+			(StatusCode::UNPROCESSABLE_ENTITY, "inconsistent"),
+			(StatusCode::PAYLOAD_TOO_LARGE, "too_large"),
+			(StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+			(StatusCode::FORBIDDEN, "read_only"),
+			(StatusCode::UNAUTHORIZED, "unauthorized"),
+			(StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+			(StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+		] {
+			assert_eq!(
+				code_and_status(ApiError::from_status(status, "message")),
+				(code, status.as_u16())
+			);
+		}
+	}
+}
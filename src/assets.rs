@@ -0,0 +1,351 @@
+use std::sync::OnceLock;
+
+use chrono::NaiveDateTime;
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Filter;
+
+use super::config;
+use super::version::VersionInfo;
+
+/// File embedded into the binary and served under a fixed path
+#[derive(Debug)]
+pub struct Asset {
+	/// URL path, starting with a slash
+	pub path: &'static str,
+	pub content_type: &'static str,
+	pub data: &'static [u8],
+	/// Hash of `data`, computed at compile time
+	pub hash: u64,
+}
+
+impl Asset {
+	/// Contents of a text asset, for inlining into pages
+	pub fn text(&self) -> &'static str {
+		std::str::from_utf8(self.data).expect("text asset is valid UTF-8")
+	}
+
+	pub fn etag(&self) -> String {
+		format!("\"{:016x}\"", self.hash)
+	}
+
+	/// Kind of the asset, which decides how long it is cached
+	pub fn class(&self) -> AssetClass {
+		let content_type = self.content_type;
+		if content_type.starts_with("image/") {
+			AssetClass::Image
+		} else if content_type.starts_with("text/css")
+			|| content_type.starts_with("text/javascript")
+		{
+			AssetClass::Code
+		} else {
+			AssetClass::Document
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetClass {
+	/// Icons and logos
+	Image,
+	/// Styles and scripts
+	Code,
+	/// Everything else, like the starter articles
+	Document,
+}
+
+/// When the assets last changed. They are part of the binary, so this
+/// is the build date, or the start if the build date is unknown.
+pub fn last_modified() -> NaiveDateTime {
+	static LAST_MODIFIED: OnceLock<NaiveDateTime> = OnceLock::new();
+	*LAST_MODIFIED.get_or_init(|| {
+		VersionInfo::get()
+			.build_date
+			.unwrap_or_else(|| chrono::Utc::now().naive_utc())
+	})
+}
+
+/// Date format of HTTP headers (RFC 7231, IMF-fixdate)
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+fn http_date(time: NaiveDateTime) -> String {
+	time.format(HTTP_DATE_FORMAT).to_string()
+}
+
+/// Whether an `If-Modified-Since` header is no earlier than `time`.
+/// Unreadable dates never match.
+fn not_modified_since(if_modified_since: &str, time: NaiveDateTime) -> bool {
+	NaiveDateTime::parse_from_str(if_modified_since.trim(), HTTP_DATE_FORMAT)
+		.is_ok_and(|since| time.and_utc().timestamp() <= since.and_utc().timestamp())
+}
+
+/// 64 bit FNV-1a, usable in constants. Only used to detect changes
+/// of the assets, it does not need to be cryptographically secure.
+pub const fn fnv1a(data: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	let mut i = 0;
+	while i < data.len() {
+		hash ^= data[i] as u64;
+		hash = hash.wrapping_mul(0x0100_0000_01b3);
+		i += 1;
+	}
+	hash
+}
+
+/// Declares a static for every asset and the `ASSETS` table of all of
+/// them. The file is relative to this source file.
+macro_rules! assets {
+	($($name:ident: $path:expr, $file:expr, $content_type:expr;)*) => {
+		$(
+			pub static $name: Asset = Asset {
+				path: $path,
+				content_type: $content_type,
+				data: include_bytes!($file),
+				hash: fnv1a(include_bytes!($file)),
+			};
+		)*
+
+		/// All embedded assets
+		pub static ASSETS: &[&Asset] = &[$(&$name),*];
+	};
+}
+
+assets! {
+	FAVICON_ICO: "/favicon.ico", "favicon/favicon.ico", "image/x-icon";
+	FAVICON_SVG: "/icon.svg", "favicon/icon.svg", "image/svg+xml";
+	WIKI_ICON: "/img/redwood_observatory_medium.png", "wiki_icon/redwood_observatory_medium.png", "image/png";
+	MAIN_STYLE: "/css/main_style.css", "css/main_style.css", "text/css; charset=utf-8";
+	GITHUB_MARKDOWN: "/css/github_markdown.css", "css/github_markdown.css", "text/css; charset=utf-8";
+	EASYMDE_STYLE: "/easymde/easymde.min.css", "easymde/easymde.min.css", "text/css; charset=utf-8";
+	EASYMDE_SCRIPT: "/easymde/easymde.min.js", "easymde/easymde.min.js", "text/javascript; charset=utf-8";
+	EDITOR_PREVIEW: "/js/editor_preview.js", "js/editor_preview.js", "text/javascript; charset=utf-8";
+	EDITOR_DICTIONARY: "/js/editor_dictionary.js", "js/editor_dictionary.js", "text/javascript; charset=utf-8";
+	EDITOR_SECTIONS: "/js/editor_sections.js", "js/editor_sections.js", "text/javascript; charset=utf-8";
+	STARTER_HOME: "/starter/home.md", "starter/home.md", "text/markdown; charset=utf-8";
+	STARTER_COAST_REDWOOD: "/starter/coast_redwood.md", "starter/coast_redwood.md", "text/markdown; charset=utf-8";
+	STARTER_GIANT_SEQUOIA: "/starter/giant_sequoia.md", "starter/giant_sequoia.md", "text/markdown; charset=utf-8";
+}
+
+pub fn find(path: &str) -> Option<&'static Asset> {
+	ASSETS.iter().copied().find(|asset| asset.path == path)
+}
+
+/// Whether an `If-None-Match` header matches the ETag
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+	if_none_match
+		.split(',')
+		.map(|tag| tag.trim())
+		.any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+fn response(
+	asset: &'static Asset,
+	if_none_match: Option<String>,
+	if_modified_since: Option<String>,
+	cache: &config::Assets,
+) -> Response<Body> {
+	let etag = asset.etag();
+	let last_modified = last_modified();
+	let builder = Response::builder()
+		.header(header::ETAG, &etag)
+		.header(header::LAST_MODIFIED, http_date(last_modified))
+		.header(
+			header::CACHE_CONTROL,
+			format!("public, max-age={}", cache.max_age(asset.class())),
+		);
+	// The ETag takes precedence over the date (RFC 7232, section 6)
+	let not_modified = match (if_none_match, if_modified_since) {
+		(Some(tags), _) => etag_matches(&tags, &etag),
+		(None, Some(since)) => not_modified_since(&since, last_modified),
+		(None, None) => false,
+	};
+	if not_modified {
+		return builder
+			.status(StatusCode::NOT_MODIFIED)
+			.body(Body::empty())
+			.unwrap();
+	}
+	builder
+		.status(StatusCode::OK)
+		.header(header::CONTENT_TYPE, asset.content_type)
+		.body(Body::from(asset.data))
+		.unwrap()
+}
+
+/// Serves every registered asset under its path, below the base path
+/// that the surrounding filter already matched
+pub fn route(
+	cache: config::Assets,
+) -> impl Filter<Extract = (Response<Body>,), Error = warp::Rejection> + Clone {
+	// Without a build date, the date is that of the start, not of the
+	// first request
+	last_modified();
+	warp::get()
+		.and(warp::path::tail())
+		.and_then(|tail: warp::path::Tail| async move {
+			find(&format!("/{}", tail.as_str())).ok_or_else(warp::reject::not_found)
+		})
+		.and(warp::header::optional::<String>("if-none-match"))
+		.and(warp::header::optional::<String>("if-modified-since"))
+		.map(move |asset, if_none_match, if_modified_since| {
+			response(asset, if_none_match, if_modified_since, &cache)
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::convert::TryFrom;
+
+	use crate::urls::BasePath;
+
+	#[test]
+	fn test_registry() {
+		for (i, asset) in ASSETS.iter().enumerate() {
+			assert!(asset.path.starts_with('/'), "{}", asset.path);
+			assert!(!asset.data.is_empty(), "{}", asset.path);
+			assert_eq!(asset.hash, fnv1a(asset.data));
+			assert!(
+				ASSETS[..i].iter().all(|other| other.path != asset.path),
+				"duplicate path {}",
+				asset.path
+			);
+			if asset.content_type.starts_with("text/") {
+				asset.text();
+			}
+		}
+		assert_eq!(find("/css/main_style.css").unwrap().path, MAIN_STYLE.path);
+		assert!(find("/css/missing.css").is_none());
+	}
+
+	#[test]
+	fn test_etag_matches() {
+		assert!(etag_matches("\"abc\"", "\"abc\""));
+		assert!(etag_matches("W/\"abc\"", "\"abc\""));
+		assert!(etag_matches("\"x\", \"abc\"", "\"abc\""));
+		assert!(etag_matches("*", "\"abc\""));
+		assert!(!etag_matches("\"abcd\"", "\"abc\""));
+	}
+
+	#[tokio::test]
+	async fn test_last_modified() {
+		let last_modified = last_modified();
+		assert_eq!(Some(last_modified), VersionInfo::get().build_date);
+		let get = |if_modified_since: String, if_none_match: Option<&str>| {
+			let mut request = warp::test::request()
+				.path(FAVICON_SVG.path)
+				.header("if-modified-since", if_modified_since);
+			if let Some(if_none_match) = if_none_match {
+				request = request.header("if-none-match", if_none_match);
+			}
+			async move { request.reply(&route(config::Assets::default())).await }
+		};
+
+		let response = get(http_date(last_modified), None).await;
+		assert_eq!(response.status(), 304);
+		assert_eq!(
+			response.headers()["last-modified"],
+			http_date(last_modified).as_str()
+		);
+		let later = last_modified + chrono::Duration::days(1);
+		assert_eq!(get(http_date(later), None).await.status(), 304);
+		let earlier = last_modified - chrono::Duration::seconds(1);
+		assert_eq!(get(http_date(earlier), None).await.status(), 200);
+		assert_eq!(get("yesterday".to_string(), None).await.status(), 200);
+		// A changed ETag wins over the date
+		let response = get(http_date(later), Some("\"other\"")).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.body().len(), FAVICON_SVG.data.len());
+
+		assert!(http_date(last_modified).ends_with(" GMT"));
+		assert!(not_modified_since(
+			"Sun, 06 Nov 1994 08:49:37 GMT",
+			NaiveDateTime::parse_from_str("1994-11-06 08:49:37", "%Y-%m-%d %H:%M:%S").unwrap()
+		));
+	}
+
+	#[tokio::test]
+	async fn test_cache_control() {
+		let cache = config::Assets {
+			image_max_age: 604800,
+			code_max_age: 3600,
+			document_max_age: 60,
+		};
+		assert_eq!(WIKI_ICON.class(), AssetClass::Image);
+		assert_eq!(FAVICON_SVG.class(), AssetClass::Image);
+		assert_eq!(EASYMDE_SCRIPT.class(), AssetClass::Code);
+		assert_eq!(MAIN_STYLE.class(), AssetClass::Code);
+		assert_eq!(STARTER_HOME.class(), AssetClass::Document);
+		for (asset, expected) in [
+			(&FAVICON_ICO, "public, max-age=604800"),
+			(&MAIN_STYLE, "public, max-age=3600"),
+			(&EDITOR_PREVIEW, "public, max-age=3600"),
+			(&STARTER_HOME, "public, max-age=60"),
+		] {
+			let response = warp::test::request()
+				.path(asset.path)
+				.reply(&route(cache))
+				.await;
+			assert_eq!(response.headers()["cache-control"], expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_serve() {
+		for asset in ASSETS {
+			let response = warp::test::request()
+				.path(asset.path)
+				.reply(&route(config::Assets::default()))
+				.await;
+			assert_eq!(response.status(), 200, "{}", asset.path);
+			assert_eq!(response.headers()["content-type"], asset.content_type);
+			assert_eq!(response.headers()["etag"], asset.etag().as_str());
+			assert_eq!(response.headers()["cache-control"], "public, max-age=86400");
+			assert_eq!(
+				response.headers()["last-modified"],
+				http_date(last_modified()).as_str()
+			);
+			assert_eq!(response.body().len(), asset.data.len());
+
+			let response = warp::test::request()
+				.path(asset.path)
+				.header("if-none-match", asset.etag())
+				.reply(&route(config::Assets::default()))
+				.await;
+			assert_eq!(response.status(), 304, "{}", asset.path);
+			assert!(response.body().is_empty());
+		}
+
+		let response = warp::test::request()
+			.path("/css/missing.css")
+			.reply(&route(config::Assets::default()))
+			.await;
+		assert_eq!(response.status(), 404);
+		let response = warp::test::request()
+			.method("POST")
+			.path(MAIN_STYLE.path)
+			.reply(&route(config::Assets::default()))
+			.await;
+		assert!(response.status().is_client_error());
+	}
+
+	#[tokio::test]
+	async fn test_serve_under_base_path() {
+		let base_path = BasePath::try_from("/wiki".to_string()).unwrap();
+		let routes = base_path.filter().and(route(config::Assets::default()));
+		for asset in ASSETS {
+			let response = warp::test::request()
+				.path(&format!("/wiki{}", asset.path))
+				.reply(&routes)
+				.await;
+			assert_eq!(response.status(), 200, "{}", asset.path);
+			assert_eq!(response.body().len(), asset.data.len());
+		}
+		let response = warp::test::request()
+			.path(MAIN_STYLE.path)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 404);
+	}
+}
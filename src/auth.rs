@@ -0,0 +1,562 @@
+use serde::{Deserialize, Serialize};
+
+use std::convert::{Infallible, TryFrom};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+use warp::http::{header, HeaderValue, StatusCode};
+use warp::Filter;
+
+use super::config::{Auth, Config};
+use super::hmac::hmac_sha1;
+
+/// Cookie of a login session, see `session_cookie`
+pub const SESSION_COOKIE: &str = "redwood_session";
+
+/// The password hash of `auth.password_hash`, Argon2 in the PHC format
+/// (`$argon2id$...`) or bcrypt (`$2b$...`, as written by `htpasswd -B`)
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PasswordHash(String);
+
+impl TryFrom<String> for PasswordHash {
+	type Error = String;
+
+	fn try_from(hash: String) -> Result<PasswordHash, String> {
+		let valid = if hash.starts_with("$argon2") {
+			argon2::PasswordHash::new(&hash)
+				.is_ok_and(|hash| hash.salt.is_some() && hash.hash.is_some())
+		} else {
+			is_bcrypt(&hash)
+		};
+		if valid {
+			Ok(PasswordHash(hash))
+		} else {
+			Err("invalid password hash, expected an Argon2 hash like `$argon2id$v=19$...` or a bcrypt hash like `$2b$12$...`".to_string())
+		}
+	}
+}
+
+impl From<PasswordHash> for String {
+	fn from(hash: PasswordHash) -> String {
+		hash.0
+	}
+}
+
+/// Not shown in the log of the configuration
+impl std::fmt::Debug for PasswordHash {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("PasswordHash(..)")
+	}
+}
+
+/// `$2a$`, `$2b$`, `$2x$` or `$2y$`, the cost and 53 characters of salt
+/// and hash
+fn is_bcrypt(hash: &str) -> bool {
+	let parts: Vec<&str> = hash.split('$').collect();
+	matches!(parts.as_slice(), ["", version, cost, rest]
+		if ["2a", "2b", "2x", "2y"].contains(version)
+			&& cost.len() == 2
+			&& cost.parse::<u32>().is_ok_and(|cost| (4..=31).contains(&cost))
+			&& rest.len() == 53)
+}
+
+impl PasswordHash {
+	fn verify(&self, password: &[u8]) -> bool {
+		if self.0.starts_with("$argon2") {
+			use argon2::PasswordVerifier;
+			argon2::PasswordHash::new(&self.0).is_ok_and(|hash| {
+				argon2::Argon2::default()
+					.verify_password(password, &hash)
+					.is_ok()
+			})
+		} else {
+			bcrypt::verify(password, &self.0).unwrap_or(false)
+		}
+	}
+}
+
+/// The last `Authorization` header that was accepted, as SHA-1 digest.
+/// Browsers send the header with every request, the password hash is
+/// only checked again when it changes.
+#[derive(Default)]
+pub struct Verified(Mutex<Option<[u8; 20]>>);
+
+impl std::fmt::Debug for Verified {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("Verified(..)")
+	}
+}
+
+/// `auth.session_secret`, long enough that it cannot be guessed
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SessionSecret(String);
+
+impl TryFrom<String> for SessionSecret {
+	type Error = String;
+
+	fn try_from(secret: String) -> Result<SessionSecret, String> {
+		if secret.chars().count() >= 32 {
+			Ok(SessionSecret(secret))
+		} else {
+			Err("session secret too short, expected at least 32 characters".to_string())
+		}
+	}
+}
+
+impl From<SessionSecret> for String {
+	fn from(secret: SessionSecret) -> String {
+		secret.0
+	}
+}
+
+/// Not shown in the log of the configuration
+impl std::fmt::Debug for SessionSecret {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("SessionSecret(..)")
+	}
+}
+
+/// Signs the session cookies without `auth.session_secret`. The key is
+/// random and only kept in memory, so a restart ends all sessions.
+pub struct SessionKey([u8; 32]);
+
+impl Default for SessionKey {
+	fn default() -> SessionKey {
+		let mut key = [0; 32];
+		rand::thread_rng().fill_bytes(&mut key);
+		SessionKey(key)
+	}
+}
+
+impl std::fmt::Debug for SessionKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("SessionKey(..)")
+	}
+}
+
+/// Someone who logged in on the login page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+	pub name: String,
+}
+
+/// Rejection of a request without valid credentials, answered with 401
+/// by `handle_rejection`
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Compares every byte, so that the time taken does not tell how many
+/// of the leading bytes match
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether `username` and `password` are the configured credentials.
+/// Both are checked, so that the time does not tell whether the
+/// username is right.
+pub fn verify_credentials(auth: &Auth, username: &[u8], password: &[u8]) -> bool {
+	let username_matches = constant_time_eq(username, auth.username.as_bytes());
+	let password_matches = auth.password_hash.verify(password);
+	username_matches && password_matches
+}
+
+/// Whether the `Authorization` header has the configured credentials
+fn check(config: &Config, authorization: &str) -> bool {
+	let auth = match &config.auth {
+		Some(auth) => auth,
+		None => return true,
+	};
+	let digest: [u8; 20] = Sha1::digest(authorization.as_bytes()).into();
+	let verified =
+		|cached: &Option<[u8; 20]>| cached.is_some_and(|cached| constant_time_eq(&cached, &digest));
+	if verified(
+		&auth
+			.verified
+			.0
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner),
+	) {
+		return true;
+	}
+
+	let credentials = authorization
+		.strip_prefix("Basic ")
+		.and_then(|encoded| {
+			base64::engine::general_purpose::STANDARD
+				.decode(encoded.trim())
+				.ok()
+		})
+		.unwrap_or_default();
+	let (username, password) = match credentials.iter().position(|&byte| byte == b':') {
+		Some(colon) => (&credentials[..colon], &credentials[colon + 1..]),
+		None => return false,
+	};
+	if verify_credentials(auth, username, password) {
+		*auth
+			.verified
+			.0
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner) = Some(digest);
+		true
+	} else {
+		false
+	}
+}
+
+fn session_mac(auth: &Auth, payload: &str) -> String {
+	let key = match &auth.session_secret {
+		Some(secret) => secret.0.as_bytes(),
+		None => &auth.session_key.0,
+	};
+	hmac_sha1(key, format!("session\0{}", payload).as_bytes())
+		.iter()
+		.map(|byte| format!("{:02x}", byte))
+		.collect()
+}
+
+/// Value of a session cookie of `user` that expires after
+/// `auth.session_days`: the expiry, a random id and the name, signed
+/// with an HMAC
+fn session_value(auth: &Auth, user: &User, now: i64) -> String {
+	let mut id = [0; 16];
+	rand::thread_rng().fill_bytes(&mut id);
+	let payload = format!(
+		"{}.{}.{}",
+		now + i64::from(auth.session_days) * 24 * 60 * 60,
+		URL_SAFE_NO_PAD.encode(id),
+		URL_SAFE_NO_PAD.encode(&user.name)
+	);
+	let mac = session_mac(auth, &payload);
+	format!("{}.{}", payload, mac)
+}
+
+/// The user of a session cookie. Cookies that are malformed, expired,
+/// not signed by this wiki or of a user that is no longer configured
+/// are ignored.
+fn session_user(auth: &Auth, value: &str, now: i64) -> Option<User> {
+	let (payload, mac) = value.rsplit_once('.')?;
+	if !constant_time_eq(session_mac(auth, payload).as_bytes(), mac.as_bytes()) {
+		return None;
+	}
+	let mut parts = payload.split('.');
+	let (expiry, _id, name) = (parts.next()?, parts.next()?, parts.next()?);
+	let expiry: i64 = expiry.parse().ok()?;
+	let name = String::from_utf8(URL_SAFE_NO_PAD.decode(name).ok()?).ok()?;
+	if parts.next().is_some() || expiry <= now || name != auth.username {
+		return None;
+	}
+	Some(User { name })
+}
+
+fn now() -> i64 {
+	chrono::Utc::now().timestamp()
+}
+
+fn cookie_attributes(config: &Config) -> String {
+	let https = config.network.tls_cert.is_some()
+		|| config
+			.network
+			.public_url
+			.as_ref()
+			.is_some_and(|url| url.starts_with("https://"));
+	format!(
+		"Path={}; HttpOnly; SameSite=Lax{}",
+		config.url("/"),
+		if https { "; Secure" } else { "" }
+	)
+}
+
+/// `Set-Cookie` header value that logs in `user`
+pub fn session_cookie(config: &Config, auth: &Auth, user: &User) -> String {
+	format!(
+		"{}={}; Max-Age={}; {}",
+		SESSION_COOKIE,
+		session_value(auth, user, now()),
+		u64::from(auth.session_days) * 24 * 60 * 60,
+		cookie_attributes(config)
+	)
+}
+
+/// `Set-Cookie` header value that logs out
+pub fn logout_cookie(config: &Config) -> String {
+	format!(
+		"{}=; Max-Age=0; {}",
+		SESSION_COOKIE,
+		cookie_attributes(config)
+	)
+}
+
+/// The logged in user, `None` for anonymous requests, which includes
+/// those with an expired or tampered session cookie
+pub fn user(
+	config: Arc<Config>,
+) -> impl Filter<Extract = (Option<User>,), Error = Infallible> + Clone {
+	warp::cookie::optional::<String>(SESSION_COOKIE).map(move |cookie: Option<String>| {
+		let auth = config.auth.as_ref()?;
+		session_user(auth, &cookie?, now())
+	})
+}
+
+/// Passes requests of a logged in user or with the credentials of the
+/// `[auth]` section, or all requests without one. The password hash is
+/// slow to check on purpose, so it is checked on a blocking thread.
+pub fn editor(config: Arc<Config>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+	user(config.clone())
+		.and(warp::header::optional::<String>("authorization"))
+		.and_then(move |user: Option<User>, authorization: Option<String>| {
+			let config = config.clone();
+			async move {
+				if config.auth.is_none() || user.is_some() {
+					return Ok(());
+				}
+				let authorization =
+					authorization.ok_or_else(|| warp::reject::custom(Unauthorized))?;
+				let accepted = tokio::task::spawn_blocking(move || check(&config, &authorization))
+					.await
+					.unwrap_or(false);
+				if accepted {
+					Ok(())
+				} else {
+					Err(warp::reject::custom(Unauthorized))
+				}
+			}
+		})
+		.untuple_one()
+}
+
+/// Like `editor`, but only with `auth.protect_reads`
+pub fn reader(config: Arc<Config>) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+	let protect_reads = config.auth.as_ref().is_some_and(|auth| auth.protect_reads);
+	warp::any()
+		.and_then(move || async move {
+			if protect_reads {
+				Err(warp::reject::custom(Unauthorized))
+			} else {
+				Ok(())
+			}
+		})
+		.untuple_one()
+		.or(editor(config))
+		.unify()
+}
+
+/// Keeps shared caches from storing pages, with `[auth]` their menu
+/// shows who is logged in
+pub fn mark_private(config: &Config, response: &mut warp::reply::Response) {
+	let is_html = response
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.is_some_and(|value| value.starts_with("text/html"));
+	if config.auth.is_none() || !is_html {
+		return;
+	}
+	let value = match response
+		.headers()
+		.get(header::CACHE_CONTROL)
+		.map(|value| value.to_str())
+	{
+		None => "private".to_string(),
+		Some(Ok(value)) if !value.contains("private") => format!("private, {}", value),
+		Some(_) => return,
+	};
+	if let Ok(value) = HeaderValue::from_str(&value) {
+		response.headers_mut().insert(header::CACHE_CONTROL, value);
+	}
+}
+
+/// Asks for the credentials with HTTP Basic authentication, for clients
+/// of the API
+pub fn challenge(config: &Config, response: &mut warp::reply::Response) {
+	if response.status() != StatusCode::UNAUTHORIZED {
+		return;
+	}
+	let realm = config.wiki.name.replace(['"', '\\'], "");
+	let value = format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm);
+	if let Ok(value) = HeaderValue::from_str(&value) {
+		response
+			.headers_mut()
+			.insert(header::WWW_AUTHENTICATE, value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Hashes of `correct horse`, with the lowest costs to keep the tests fast
+	const ARGON2: &str =
+		"$argon2id$v=19$m=1024,t=1,p=1$cmVkd29vZC10ZXN0LXNhbHQ$2DznADakk3EejqyT0L6+HEYyD3BSpTB4kRX87hFBO1M";
+	const BCRYPT: &str = "$2b$04$akTib07tXAzyXVLya0Dqb.GY6BdVRMtqzhYavCxE/q.9kUWesvRZ6";
+
+	fn config(hash: &str, protect_reads: bool) -> Arc<Config> {
+		Arc::new(
+			toml::from_str(&format!(
+				"[network]\nip = \"127.0.0.1\"\nport = 0\n\n[auth]\nusername = \"editor\"\npassword_hash = '{}'\nprotect_reads = {}\n",
+				hash, protect_reads
+			))
+			.unwrap(),
+		)
+	}
+
+	fn basic(credentials: &str) -> String {
+		format!(
+			"Basic {}",
+			base64::engine::general_purpose::STANDARD.encode(credentials)
+		)
+	}
+
+	#[test]
+	fn test_password_hash() {
+		for hash in [ARGON2, BCRYPT] {
+			assert!(PasswordHash::try_from(hash.to_string()).is_ok(), "{}", hash);
+		}
+		for hash in ["", "correct horse", "$argon2id$nonsense", "$2b$12$short"] {
+			assert!(
+				PasswordHash::try_from(hash.to_string()).is_err(),
+				"{}",
+				hash
+			);
+		}
+		let hash = PasswordHash::try_from(BCRYPT.to_string()).unwrap();
+		assert_eq!(format!("{:?}", hash), "PasswordHash(..)");
+	}
+
+	#[test]
+	fn test_check() {
+		for hash in [ARGON2, BCRYPT] {
+			let config = config(hash, false);
+			assert!(check(&config, &basic("editor:correct horse")));
+			// Again, from the cache
+			assert!(check(&config, &basic("editor:correct horse")));
+			assert!(!check(&config, &basic("editor:wrong horse")));
+			assert!(!check(&config, &basic("other:correct horse")));
+			assert!(!check(&config, &basic("editor")));
+			assert!(!check(&config, "Basic not-base64"));
+			assert!(!check(&config, "Bearer token"));
+		}
+	}
+
+	#[test]
+	fn test_session() {
+		let first = config(BCRYPT, false);
+		let auth = first.auth.as_ref().unwrap();
+		let user = User {
+			name: "editor".to_string(),
+		};
+		let now = 1_700_000_000;
+		let value = session_value(auth, &user, now);
+		assert_eq!(session_user(auth, &value, now), Some(user.clone()));
+		// Every login is a new session
+		assert_ne!(session_value(auth, &user, now), value);
+
+		// Expired
+		let later = now + 30 * 24 * 60 * 60;
+		assert_eq!(session_user(auth, &value, later - 1), Some(user.clone()));
+		assert_eq!(session_user(auth, &value, later), None);
+
+		// Tampered, with a longer expiry or another name
+		let (payload, mac) = value.rsplit_once('.').unwrap();
+		let (_, rest) = payload.split_once('.').unwrap();
+		let extended = format!("{}.{}.{}", now + 1_000_000_000, rest, mac);
+		assert_eq!(session_user(auth, &extended, now), None);
+		let (rest, _) = payload.rsplit_once('.').unwrap();
+		let renamed = format!("{}.{}.{}", rest, URL_SAFE_NO_PAD.encode("admin"), mac);
+		assert_eq!(session_user(auth, &renamed, now), None);
+		for malformed in ["", ".", "1.2.3.4", "not a cookie", &value[1..]] {
+			assert_eq!(session_user(auth, malformed, now), None, "{:?}", malformed);
+		}
+
+		// Signed with another key, like after a restart
+		let other = config(BCRYPT, false);
+		assert_eq!(
+			session_user(other.auth.as_ref().unwrap(), &value, now),
+			None
+		);
+
+		// A configured secret keeps sessions valid
+		let with_secret = |hash: &str| -> Arc<Config> {
+			Arc::new(
+				toml::from_str(&format!(
+					"[network]\nip = \"127.0.0.1\"\nport = 0\n\n[auth]\nusername = \"editor\"\npassword_hash = '{}'\nsession_secret = \"{}\"\n",
+					hash,
+					"s".repeat(32)
+				))
+				.unwrap(),
+			)
+		};
+		let first = with_secret(BCRYPT);
+		let value = session_value(first.auth.as_ref().unwrap(), &user, now);
+		let second = with_secret(BCRYPT);
+		assert_eq!(
+			session_user(second.auth.as_ref().unwrap(), &value, now),
+			Some(user)
+		);
+		assert_eq!(
+			format!("{:?}", second.auth.as_ref().unwrap().session_secret),
+			"Some(SessionSecret(..))"
+		);
+		assert!(SessionSecret::try_from("too short".to_string()).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_filters() {
+		let status = |config: Arc<Config>, authorization: Option<String>, protect: bool| async move {
+			let route = if protect {
+				editor(config.clone()).map(|| "ok").boxed()
+			} else {
+				reader(config.clone()).map(|| "ok").boxed()
+			};
+			let route = route.recover(move |rejection: warp::Rejection| {
+				let config = config.clone();
+				async move {
+					assert!(rejection.find::<Unauthorized>().is_some());
+					let mut response =
+						warp::reply::with_status("no", StatusCode::UNAUTHORIZED).into_response();
+					challenge(&config, &mut response);
+					Ok::<_, std::convert::Infallible>(response)
+				}
+			});
+			let mut request = warp::test::request();
+			if let Some(authorization) = authorization {
+				request = request.header("authorization", authorization);
+			}
+			let response = request.reply(&route).await;
+			if response.status() == StatusCode::UNAUTHORIZED {
+				assert_eq!(
+					response.headers()["www-authenticate"],
+					"Basic realm=\"Redwood Wiki\", charset=\"UTF-8\""
+				);
+			}
+			response.status()
+		};
+		use warp::Reply;
+
+		let open = config(BCRYPT, false);
+		let good = || Some(basic("editor:correct horse"));
+		assert_eq!(status(open.clone(), None, true).await, 401);
+		assert_eq!(
+			status(open.clone(), Some(basic("editor:no")), true).await,
+			401
+		);
+		assert_eq!(status(open.clone(), good(), true).await, 200);
+		assert_eq!(status(open.clone(), None, false).await, 200);
+
+		let closed = config(BCRYPT, true);
+		assert_eq!(status(closed.clone(), None, false).await, 401);
+		assert_eq!(status(closed.clone(), good(), false).await, 200);
+
+		// Without `[auth]`, everything is open
+		let config: Arc<Config> =
+			Arc::new(toml::from_str("[network]\nip = \"127.0.0.1\"\nport = 0\n").unwrap());
+		assert_eq!(status(config.clone(), None, true).await, 200);
+		assert_eq!(status(config, None, false).await, 200);
+	}
+}
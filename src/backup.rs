@@ -0,0 +1,217 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use super::config::Backup;
+use super::database::{Database, DbError};
+use super::db_lock::DbLock;
+
+/// Backups are named `wiki_db-<date>.sqlite`, the date sorts like the
+/// time of the backup
+const FILE_PREFIX: &str = "wiki_db-";
+const FILE_SUFFIX: &str = ".sqlite";
+/// Written under this name first, a backup interrupted by a crash is
+/// never mistaken for a complete one
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Back up the database every `backup.interval()`, starting one interval
+/// after startup. Runs until the process ends, failures are logged and
+/// retried at the next interval.
+pub async fn run_scheduled(db: std::sync::Arc<DbLock>, backup: Backup) {
+	let interval = backup.interval();
+	let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+	// After a suspended machine wakes up, one backup is enough
+	ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+	loop {
+		ticks.tick().await;
+		match run_once(&db, &backup, Utc::now()).await {
+			Ok((path, pruned)) => log::info!(
+				"Backed up the database to {}, deleted {} old backups",
+				path.display(),
+				pruned
+			),
+			Err(err) => log::error!(
+				"Could not back up the database to {}: {}",
+				backup.directory.display(),
+				err
+			),
+		}
+	}
+}
+
+/// Write a backup taken at `now` and delete the ones beyond
+/// `backup.keep_count`. Returns the new backup and how many were deleted.
+async fn run_once(
+	db: &DbLock,
+	backup: &Backup,
+	now: DateTime<Utc>,
+) -> Result<(PathBuf, usize), String> {
+	let (path, partial) = prepare(backup, now)?;
+	// Only the copy itself needs the database
+	let result = db.lock().await.backup_to(&partial);
+	complete(backup, result, &partial, &path)
+}
+
+/// `run_once` without the server, for `redwood-wiki backup`
+pub fn run_now(
+	db: &mut Database,
+	backup: &Backup,
+	now: DateTime<Utc>,
+) -> Result<(PathBuf, usize), String> {
+	let (path, partial) = prepare(backup, now)?;
+	let result = db.backup_to(&partial);
+	complete(backup, result, &partial, &path)
+}
+
+/// The file of the backup taken at `now` and the one to write it to first
+fn prepare(backup: &Backup, now: DateTime<Utc>) -> Result<(PathBuf, PathBuf), String> {
+	std::fs::create_dir_all(&backup.directory).map_err(|err| err.to_string())?;
+	let name = format!(
+		"{}{}{}",
+		FILE_PREFIX,
+		now.format("%Y%m%d-%H%M%S"),
+		FILE_SUFFIX
+	);
+	let path = backup.directory.join(&name);
+	if path.exists() {
+		return Err(format!("{} already exists", path.display()));
+	}
+	let partial = backup.directory.join(name + PARTIAL_SUFFIX);
+	// Left behind by a crash during the previous backup
+	let _ = std::fs::remove_file(&partial);
+	Ok((path, partial))
+}
+
+/// Give the written backup its name and delete the old ones
+fn complete(
+	backup: &Backup,
+	result: Result<(), DbError>,
+	partial: &Path,
+	path: &Path,
+) -> Result<(PathBuf, usize), String> {
+	if let Err(err) = result {
+		let _ = std::fs::remove_file(partial);
+		return Err(err.to_string());
+	}
+	std::fs::rename(partial, path).map_err(|err| err.to_string())?;
+
+	let pruned = prune(&backup.directory, backup.keep_count).map_err(|err| {
+		format!(
+			"the backup was written, but old backups could not be deleted: {}",
+			err
+		)
+	})?;
+	Ok((path.to_path_buf(), pruned))
+}
+
+/// Delete all but the newest `keep_count` backups in `directory`, other
+/// files are left alone. Returns how many were deleted.
+fn prune(directory: &Path, keep_count: usize) -> io::Result<usize> {
+	let mut backups = Vec::new();
+	for entry in std::fs::read_dir(directory)? {
+		let name = entry?.file_name();
+		let Some(name) = name.to_str() else {
+			continue;
+		};
+		if name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX) {
+			backups.push(name.to_string());
+		}
+	}
+	backups.sort_unstable();
+	let excess = backups.len().saturating_sub(keep_count);
+	for name in &backups[..excess] {
+		std::fs::remove_file(directory.join(name))?;
+	}
+	Ok(excess)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use chrono::TimeZone;
+
+	use super::super::database::{Article, DatabaseConnection, OpenMode};
+	use super::super::timestamp::UtcTimestamp;
+
+	fn temp_dir(name: &str) -> PathBuf {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!(
+			"redwood-wiki-test-backup-{}-{}",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_dir_all(&dir);
+		dir
+	}
+
+	fn file_names(dir: &Path) -> Vec<String> {
+		let mut names: Vec<String> = std::fs::read_dir(dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect();
+		names.sort();
+		names
+	}
+
+	#[tokio::test]
+	async fn test_backups_are_rotated() {
+		let dir = temp_dir("rotated");
+		let mut db = DatabaseConnection::new(Path::new(""), OpenMode::InMemory)
+			.unwrap()
+			.init()
+			.unwrap();
+		let id = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let db = DbLock::new(db, std::time::Duration::from_millis(100));
+		let backup = Backup {
+			directory: dir.clone(),
+			interval_hours: 1,
+			keep_count: 2,
+		};
+
+		let at = |hour| Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap();
+		let (first, pruned) = run_once(&db, &backup, at(1)).await.unwrap();
+		assert_eq!(first, dir.join("wiki_db-20240301-010000.sqlite"));
+		assert_eq!(pruned, 0);
+		std::fs::write(dir.join("notes.txt"), "kept").unwrap();
+		std::fs::write(dir.join("wiki_db-20240301-020000.sqlite.partial"), "").unwrap();
+		run_once(&db, &backup, at(2)).await.unwrap();
+		let (_, pruned) = run_once(&db, &backup, at(3)).await.unwrap();
+		assert_eq!(pruned, 1);
+		assert_eq!(
+			file_names(&dir),
+			vec![
+				"notes.txt",
+				"wiki_db-20240301-020000.sqlite",
+				"wiki_db-20240301-030000.sqlite"
+			]
+		);
+
+		// A backup is a complete database
+		let mut restored = DatabaseConnection::new(
+			&dir.join("wiki_db-20240301-030000.sqlite"),
+			OpenMode::OpenExisting,
+		)
+		.unwrap()
+		.init()
+		.unwrap();
+		assert_eq!(restored.get_article(id).unwrap().unwrap().text, "Tall");
+		drop(restored);
+
+		// Existing backups are not overwritten
+		assert!(run_once(&db, &backup, at(3)).await.is_err());
+		assert_eq!(file_names(&dir).len(), 3);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
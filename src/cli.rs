@@ -0,0 +1,1130 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use regex::Regex;
+
+use super::backup;
+use super::config::{self, Backup, Overrides};
+use super::config_template;
+use super::database::{
+	ArticleOrder, ConnectionSettings, Database, DatabaseConnection, DatabaseInitError, ExportError,
+	ImportConflict, ImportError, ImportSummary, ItemId, OpenMode,
+};
+use super::markdown_import;
+use super::render::{self, RenderOptions};
+use super::timestamp::{DateWindow, UtcTimestamp};
+
+pub const USAGE: &str = "Usage:
+	redwood-wiki [<options>] [serve] [--resume-migration] [--listen <ip:port>]
+	redwood-wiki [<options>] check
+	redwood-wiki [<options>] backup [<file>]
+	redwood-wiki [<options>] export
+	redwood-wiki [<options>] import <file> [--on-conflict skip|overwrite|rename]
+	redwood-wiki [<options>] article show <id>
+	redwood-wiki [<options>] article list [--sort id|title|modified]
+	redwood-wiki [<options>] article import <directory>
+	redwood-wiki [<options>] grep <pattern>
+	redwood-wiki [<options>] db stats
+	redwood-wiki [<options>] db verify-dates [--fix]
+	redwood-wiki init-config [<file>] [--force]
+	redwood-wiki --version
+
+Options:
+	--config <path>     Configuration file, instead of `wiki-config.toml` in
+	                    the working directory or next to the executable
+	--data-dir <path>   Directory of the database, instead of
+	                    `database.storage_location` of the configuration
+	--db <path>         Database file for the commands, the configuration is
+	                    not read
+
+Without a command or with `serve`, the wiki server is started, `--listen`
+replaces the address of the configuration. The other commands work on the
+database, also while the server is running or cannot start. `check` verifies
+the database file and its dates. `backup` writes a copy of the database to
+the file, or without one, to the backups of the configuration (`backup`
+section). `export` prints the whole wiki as JSON document, like
+`/admin/export.json` of the server. `import` adds the articles of an export,
+articles whose title is taken are skipped (the default), overwrite the
+existing article or are renamed. `article import` creates an article from
+every Markdown file in the directory and turns the links between the files
+into references. `db verify-dates --fix` sets the implausible dates it
+reports to the current time. Only the imports and `--fix` write to the
+database. Without a configuration file, `--data-dir` works like `--db`.
+`init-config` writes a configuration file with every setting and its
+default, to `wiki-config.toml` if no file is given. It does not replace
+an existing file unless `--force` is given.";
+
+/// Number of articles `article list` reads at once
+const LIST_CHUNK: u32 = 1000;
+
+/// What the command line asks for
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+	/// Start the wiki server, the default
+	Serve {
+		/// Deal with an interrupted migration and exit
+		resume_migration: bool,
+	},
+	/// Print the usage
+	Help,
+	Version,
+	/// Write a configuration file with the defaults
+	InitConfig {
+		file: PathBuf,
+		/// Replace an existing file
+		force: bool,
+	},
+	/// Run a command on the database instead of the server
+	Run(Command),
+}
+
+/// Command on the database, run instead of the server
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+	/// Integrity of the database file and plausibility of the dates
+	Check,
+	/// Copy of the database to the file, or to the configured backups
+	Backup(Option<PathBuf>),
+	/// Metadata and Markdown of an article
+	ShowArticle(ItemId),
+	ListArticles(ArticleOrder),
+	/// One article per Markdown file in the directory
+	ImportMarkdown(PathBuf),
+	/// Lines of titles and texts matching a regular expression
+	Grep(String),
+	Stats,
+	/// Dates outside of the plausible ones, set to now with `fix`
+	VerifyDates {
+		fix: bool,
+	},
+	/// The whole wiki as JSON document
+	Export,
+	/// Add the articles of an export
+	Import {
+		file: PathBuf,
+		on_conflict: ImportConflict,
+	},
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Invocation {
+	pub overrides: Overrides,
+	/// Database file instead of the one of the configuration
+	pub db: Option<PathBuf>,
+	pub action: Action,
+}
+
+/// The action given on the command line (without the program name).
+/// Options take their value as next argument or after `=`, arguments
+/// after `--` are never options.
+pub fn parse_args(args: &[String]) -> Result<Invocation, String> {
+	let mut words = Vec::new();
+	let mut overrides = Overrides::default();
+	let mut db = None;
+	let mut sort = None;
+	let mut fix = false;
+	let mut force = false;
+	let mut on_conflict = None;
+	let mut resume_migration = false;
+	let mut help = false;
+	let mut version = false;
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		let (name, mut inline) = match arg.split_once('=') {
+			Some((name, value)) if arg.starts_with("--") => (name, Some(value)),
+			_ => (arg.as_str(), None),
+		};
+		match name {
+			"--db" => {
+				db = Some(PathBuf::from(value(
+					name,
+					&mut inline,
+					&mut args,
+					"a path",
+				)?))
+			}
+			"--config" => {
+				overrides.config = Some(PathBuf::from(value(
+					name,
+					&mut inline,
+					&mut args,
+					"a path",
+				)?))
+			}
+			"--data-dir" => {
+				overrides.data_dir = Some(PathBuf::from(value(
+					name,
+					&mut inline,
+					&mut args,
+					"a path",
+				)?))
+			}
+			"--listen" => {
+				let address = value(
+					name,
+					&mut inline,
+					&mut args,
+					"an address like `127.0.0.1:8080`",
+				)?;
+				let address = address.parse().map_err(|_| {
+					format!("`{}` is not an address like `127.0.0.1:8080`", address)
+				})?;
+				overrides.listen = Some(address);
+			}
+			"--sort" => {
+				let order =
+					match value(name, &mut inline, &mut args, "`id`, `title` or `modified`")? {
+						"id" => ArticleOrder::Id,
+						"title" => ArticleOrder::Title,
+						"modified" => ArticleOrder::Modified,
+						_ => return Err("`--sort` needs `id`, `title` or `modified`".to_string()),
+					};
+				sort = Some(order);
+			}
+			"--on-conflict" => {
+				on_conflict = Some(parse_conflict(value(
+					name,
+					&mut inline,
+					&mut args,
+					"`skip`, `overwrite` or `rename`",
+				)?)?);
+			}
+			"--fix" => fix = true,
+			"--force" => force = true,
+			"--resume-migration" => resume_migration = true,
+			"--help" | "-h" => help = true,
+			"--version" | "-V" => version = true,
+			"--" => words.extend(args.by_ref().map(String::as_str)),
+			name if name.starts_with('-') => return Err(format!("Unknown option `{}`", name)),
+			_ => words.push(arg.as_str()),
+		}
+		if inline.is_some() {
+			return Err(format!("`{}` takes no value", name));
+		}
+	}
+	if help || version {
+		let action = if help { Action::Help } else { Action::Version };
+		return Ok(Invocation {
+			overrides,
+			db,
+			action,
+		});
+	}
+	let init_config = match words[..] {
+		["init-config"] => Some(PathBuf::from("wiki-config.toml")),
+		["init-config", file] => Some(PathBuf::from(file)),
+		_ => None,
+	};
+	if let Some(file) = init_config {
+		if db.is_some() || overrides != Overrides::default() {
+			return Err("`init-config` takes no options but `--force`".to_string());
+		}
+		return Ok(Invocation {
+			overrides,
+			db,
+			action: Action::InitConfig { file, force },
+		});
+	}
+	if force {
+		return Err("`--force` only goes with `init-config`".to_string());
+	}
+	let command = match words[..] {
+		[] | ["serve"] => None,
+		["check"] => Some(Command::Check),
+		["backup"] => Some(Command::Backup(None)),
+		["backup", file] => Some(Command::Backup(Some(PathBuf::from(file)))),
+		["export"] => Some(Command::Export),
+		["import", file] => Some(Command::Import {
+			file: PathBuf::from(file),
+			on_conflict: on_conflict.take().unwrap_or(ImportConflict::Skip),
+		}),
+		["article", "show", id] => {
+			Some(Command::ShowArticle(id.parse().map_err(|_| {
+				format!("'{}' is not a valid article id", id)
+			})?))
+		}
+		["article", "list"] => Some(Command::ListArticles(
+			sort.take().unwrap_or(ArticleOrder::Id),
+		)),
+		["article", "import", dir] => Some(Command::ImportMarkdown(PathBuf::from(dir))),
+		["grep", pattern] => Some(Command::Grep(pattern.to_string())),
+		["db", "stats"] => Some(Command::Stats),
+		["db", "verify-dates"] => Some(Command::VerifyDates {
+			fix: std::mem::take(&mut fix),
+		}),
+		_ => return Err(format!("Unknown command `{}`", words.join(" "))),
+	};
+	if sort.is_some() {
+		return Err("`--sort` only goes with `article list`".to_string());
+	}
+	if fix {
+		return Err("`--fix` only goes with `db verify-dates`".to_string());
+	}
+	if on_conflict.is_some() {
+		return Err("`--on-conflict` only goes with `import`".to_string());
+	}
+	let action = match command {
+		None if db.is_some() => {
+			return Err("`--db` needs a command, the server uses `--data-dir`".to_string())
+		}
+		None => Action::Serve { resume_migration },
+		Some(_) if resume_migration => {
+			return Err("`--resume-migration` only goes with `serve`".to_string())
+		}
+		Some(_) if overrides.listen.is_some() => {
+			return Err("`--listen` only goes with `serve`".to_string())
+		}
+		Some(command) => Action::Run(command),
+	};
+	Ok(Invocation {
+		overrides,
+		db,
+		action,
+	})
+}
+
+/// The value of the option `name`, after `=` or as next argument
+fn value<'a>(
+	name: &str,
+	inline: &mut Option<&'a str>,
+	args: &mut impl Iterator<Item = &'a String>,
+	what: &str,
+) -> Result<&'a str, String> {
+	inline
+		.take()
+		.or_else(|| args.next().map(String::as_str))
+		.ok_or_else(|| format!("`{}` needs {}", name, what))
+}
+
+fn parse_conflict(value: &str) -> Result<ImportConflict, String> {
+	match value {
+		"skip" => Ok(ImportConflict::Skip),
+		"overwrite" => Ok(ImportConflict::Overwrite),
+		"rename" => Ok(ImportConflict::Rename),
+		_ => Err("`--on-conflict` needs `skip`, `overwrite` or `rename`".to_string()),
+	}
+}
+
+/// Write the configuration template to `file`, returns the exit code
+pub fn init_config(file: &Path, force: bool) -> i32 {
+	match config_template::write(file, force) {
+		Ok(()) => {
+			println!("Wrote {}", file.display());
+			0
+		}
+		Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+			eprintln!(
+				"{} already exists, use `--force` to replace it",
+				file.display()
+			);
+			1
+		}
+		Err(err) => {
+			eprintln!("Could not write {}: {}", file.display(), err);
+			1
+		}
+	}
+}
+
+/// Run the command and return the exit code of the process
+pub fn run(command: Command, db: Option<PathBuf>, overrides: &Overrides) -> i32 {
+	// The output of the commands goes to stdout, only problems are logged
+	fern::Dispatch::new()
+		.format(|out, message, record| out.finish(format_args!("{}: {}", record.level(), message)))
+		.level(log::LevelFilter::Warn)
+		.chain(std::io::stderr())
+		.apply()
+		.unwrap();
+
+	let (path, window, settings, backup) = match (db, overrides.load()) {
+		(Some(path), _) => (
+			path,
+			DateWindow::default(),
+			ConnectionSettings::default(),
+			Backup::default(),
+		),
+		(None, Ok(config)) => (
+			config.database.path(),
+			config.database.dates.window(),
+			config.database.connection.settings(),
+			config.backup.unwrap_or_default(),
+		),
+		(None, Err(err)) => match &overrides.data_dir {
+			// No configuration file was found, the defaults apply
+			Some(dir) if err.kind() == io::ErrorKind::NotFound && overrides.config.is_none() => (
+				config::database_file(dir),
+				DateWindow::default(),
+				ConnectionSettings::default(),
+				Backup::default(),
+			),
+			_ => {
+				eprintln!("Could not read the configuration: {}", err);
+				eprintln!("Use `--db <path>` to read a database without configuration.");
+				return 2;
+			}
+		},
+	};
+	let writable = matches!(
+		command,
+		Command::VerifyDates { fix: true } | Command::Import { .. } | Command::ImportMarkdown(_)
+	);
+	let mut db = match open(&path, writable, &settings) {
+		Ok(db) => db,
+		Err(message) => {
+			eprintln!("{}", message);
+			return 1;
+		}
+	};
+	db.set_date_window(window);
+	let stdout = io::stdout();
+	match execute(&command, &mut db, &backup, &mut stdout.lock()) {
+		Ok(true) => 0,
+		Ok(false) => 1,
+		Err(message) => {
+			eprintln!("{}", message);
+			1
+		}
+	}
+}
+
+/// Open the database for reading, and for writing if `writable`.
+/// Databases that are being migrated are refused, their tables may be
+/// half-way between two layouts.
+fn open(path: &Path, writable: bool, settings: &ConnectionSettings) -> Result<Database, String> {
+	if !path.is_file() {
+		return Err(format!("There is no database at {}", path.display()));
+	}
+	let mode = if writable {
+		OpenMode::OpenExisting
+	} else {
+		OpenMode::ReadOnly
+	};
+	let connection = DatabaseConnection::with_settings(path, mode, settings)
+		.map_err(|err| format!("Could not open {}: {}", path.display(), err))?;
+	connection.inspect().map_err(|err| match err {
+		DatabaseInitError::MigrationInProgress { .. } => format!(
+			"In {}, {}. If no other process is migrating the database, start the wiki with `--resume-migration`, it completes the migration or tells whether to restore the database from a backup.",
+			path.display(),
+			err
+		),
+		DatabaseInitError::MigrationNeeded { .. } => format!(
+			"In {}, {}. Start the wiki once to migrate it.",
+			path.display(),
+			err
+		),
+		err => format!("Could not read {}: {}.", path.display(), err),
+	})
+}
+
+/// Write the output of the command to `out`. Returns whether anything
+/// was found, like grep, a failed search ends with exit code 1.
+fn execute(
+	command: &Command,
+	db: &mut Database,
+	backup: &Backup,
+	out: &mut impl Write,
+) -> Result<bool, String> {
+	let result = match command {
+		Command::Check => check(db, UtcTimestamp::now(), out),
+		Command::Backup(file) => write_backup(db, file.as_deref(), backup, out),
+		Command::ShowArticle(id) => show_article(db, *id, out),
+		Command::ListArticles(order) => list_articles(db, *order, out),
+		Command::ImportMarkdown(dir) => import_markdown(db, dir, out),
+		Command::Grep(pattern) => {
+			let regex = Regex::new(pattern).map_err(|err| format!("Invalid pattern: {}", err))?;
+			grep(db, &regex, out)
+		}
+		Command::Stats => stats(db, out),
+		Command::VerifyDates { fix } => verify_dates(db, *fix, UtcTimestamp::now(), out),
+		Command::Export => export(db, out),
+		Command::Import { file, on_conflict } => import(db, file, *on_conflict, out),
+	};
+	match result {
+		Ok(found) => Ok(found),
+		// Output piped into `head`, for example
+		Err(Error::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe => Ok(true),
+		Err(Error::Io(err)) => Err(format!("Could not write the output: {}", err)),
+		Err(Error::Message(message)) => Err(message),
+	}
+}
+
+enum Error {
+	Io(io::Error),
+	Message(String),
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Error {
+		Error::Io(err)
+	}
+}
+
+const READ_FAILED: &str = "Could not read the database, see the log above";
+
+/// Succeeds if SQLite finds no problems in the database file and all
+/// dates are plausible
+fn check(db: &mut Database, now: UtcTimestamp, out: &mut impl Write) -> Result<bool, Error> {
+	let problems = db
+		.check_integrity()
+		.map_err(|err| Error::Message(format!("Could not check the database: {}", err)))?;
+	if problems.is_empty() {
+		writeln!(out, "Integrity: ok")?;
+	}
+	for problem in &problems {
+		writeln!(out, "Integrity: {}", problem)?;
+	}
+	let bad = db
+		.find_bad_dates(now)
+		.ok_or_else(|| Error::Message(READ_FAILED.to_string()))?;
+	if bad.is_empty() {
+		writeln!(out, "Dates:     ok")?;
+	} else {
+		writeln!(
+			out,
+			"Dates:     {} implausible, see `db verify-dates`",
+			bad.len()
+		)?;
+	}
+	Ok(problems.is_empty() && bad.is_empty())
+}
+
+/// Back up to `file`, or without one, like the scheduled backups
+fn write_backup(
+	db: &mut Database,
+	file: Option<&Path>,
+	settings: &Backup,
+	out: &mut impl Write,
+) -> Result<bool, Error> {
+	let Some(file) = file else {
+		let (path, pruned) = backup::run_now(db, settings, Utc::now()).map_err(|err| {
+			Error::Message(format!(
+				"Could not back up the database to {}: {}",
+				settings.directory.display(),
+				err
+			))
+		})?;
+		writeln!(
+			out,
+			"Backed up the database to {}, deleted {} old backups",
+			path.display(),
+			pruned
+		)?;
+		return Ok(true);
+	};
+	db.backup_to(file).map_err(|err| {
+		Error::Message(format!(
+			"Could not back up the database to {}: {}",
+			file.display(),
+			err
+		))
+	})?;
+	writeln!(out, "Backed up the database to {}", file.display())?;
+	Ok(true)
+}
+
+fn show_article(db: &mut Database, id: ItemId, out: &mut impl Write) -> Result<bool, Error> {
+	let Some(article) = db
+		.get_article(id)
+		.map_err(|err| Error::Message(format!("Could not read the database: {}", err)))?
+	else {
+		return Err(Error::Message(format!("Article #{} does not exist", id)));
+	};
+	writeln!(out, "Title:    {}", article.title)?;
+	writeln!(out, "ID:       {}", article.id)?;
+	writeln!(
+		out,
+		"Path:     {}",
+		super::urls::article_path(id, &article.slug)
+	)?;
+	writeln!(out, "Created:  {}", article.date_created)?;
+	writeln!(out, "Modified: {}", article.date_modified)?;
+	writeln!(out, "Revision: {}", article.revision)?;
+	let tags = db.get_tags(id).unwrap_or_default();
+	if !tags.is_empty() {
+		writeln!(out, "Tags:     {}", tags.join(", "))?;
+	}
+	if let Some(category) = db.get_article_category(id) {
+		let path = db.get_category_path(category).unwrap_or_default();
+		let names: Vec<&str> = path.iter().map(|category| category.name.as_str()).collect();
+		writeln!(out, "Category: {}", names.join(" / "))?;
+	}
+	writeln!(out)?;
+	out.write_all(article.text.as_bytes())?;
+	if !article.text.is_empty() && !article.text.ends_with('\n') {
+		writeln!(out)?;
+	}
+	Ok(true)
+}
+
+/// One line per article: ID, time of the last change and title,
+/// separated by tabs
+fn list_articles(
+	db: &mut Database,
+	order: ArticleOrder,
+	out: &mut impl Write,
+) -> Result<bool, Error> {
+	let mut offset = 0;
+	loop {
+		let (articles, total) = db
+			.get_articles_page(offset, LIST_CHUNK, order)
+			.map_err(|err| Error::Message(format!("Could not read the database: {}", err)))?;
+		for article in &articles {
+			writeln!(
+				out,
+				"{}\t{}\t{}",
+				article.id, article.date_modified, article.title
+			)?;
+		}
+		offset += articles.len() as u32;
+		if articles.is_empty() || offset >= total {
+			return Ok(offset > 0);
+		}
+	}
+}
+
+/// Print `id:title:line` for every matching line of the texts. An
+/// article whose title matches, but none of its lines, is printed as
+/// `id:title:`.
+fn grep(db: &mut Database, regex: &Regex, out: &mut impl Write) -> Result<bool, Error> {
+	let mut found = false;
+	let mut result = Ok(());
+	let scanned = db.scan_articles(|article| {
+		if result.is_err() {
+			return;
+		}
+		let mut matched = false;
+		for line in article.text.lines().filter(|line| regex.is_match(line)) {
+			matched = true;
+			result = writeln!(out, "{}:{}:{}", article.id, article.title, line);
+			if result.is_err() {
+				return;
+			}
+		}
+		if !matched && regex.is_match(&article.title) {
+			matched = true;
+			result = writeln!(out, "{}:{}:", article.id, article.title);
+		}
+		found |= matched;
+	});
+	result?;
+	scanned.ok_or_else(|| Error::Message(READ_FAILED.to_string()))?;
+	Ok(found)
+}
+
+fn export(db: &mut Database, out: &mut impl Write) -> Result<bool, Error> {
+	match db.export_all(out) {
+		Ok(_) => Ok(true),
+		Err(ExportError::Write(err)) => Err(Error::Io(err)),
+		Err(err) => Err(Error::Message(format!(
+			"Could not export the wiki: {}",
+			err
+		))),
+	}
+}
+
+/// Import the export in `file`, succeeds if no article failed
+fn import(
+	db: &mut Database,
+	file: &Path,
+	on_conflict: ImportConflict,
+	out: &mut impl Write,
+) -> Result<bool, Error> {
+	let reader = std::fs::File::open(file)
+		.map(io::BufReader::new)
+		.map_err(|err| Error::Message(format!("Could not open {}: {}", file.display(), err)))?;
+	let links = |text: &str| render::article_refs(text, RenderOptions::default());
+	let summary = match db.import(reader, on_conflict, links) {
+		Ok(summary) => summary,
+		Err(ImportError::Damaged { err, partial }) => {
+			write_summary(&partial, out)?;
+			return Err(Error::Message(format!(
+				"Could not import {}, the import stopped at the damage: {}",
+				file.display(),
+				err
+			)));
+		}
+		Err(err) => {
+			return Err(Error::Message(format!(
+				"Could not import {}: {}",
+				file.display(),
+				err
+			)))
+		}
+	};
+	write_summary(&summary, out)?;
+	Ok(summary.failed == 0)
+}
+
+fn import_markdown(db: &mut Database, dir: &Path, out: &mut impl Write) -> Result<bool, Error> {
+	let report = markdown_import::import_dir(db, dir)
+		.map_err(|err| Error::Message(format!("Could not import {}: {}", dir.display(), err)))?;
+	writeln!(out, "Imported: {} files", report.imported.len())?;
+	for (file, title) in &report.renamed {
+		writeln!(
+			out,
+			"Renamed:  {} (title taken, imported as {:?})",
+			file.display(),
+			title
+		)?;
+	}
+	for (file, reason) in &report.skipped {
+		writeln!(out, "Skipped:  {} ({})", file.display(), reason)?;
+	}
+	Ok(true)
+}
+
+fn write_summary(summary: &ImportSummary, out: &mut impl Write) -> io::Result<()> {
+	writeln!(out, "Imported: {}", summary.imported)?;
+	writeln!(out, "Skipped:  {}", summary.skipped)?;
+	writeln!(out, "Failed:   {}", summary.failed)
+}
+
+fn stats(db: &mut Database, out: &mut impl Write) -> Result<bool, Error> {
+	let stats = db
+		.get_stats()
+		.ok_or_else(|| Error::Message(READ_FAILED.to_string()))?;
+	writeln!(out, "Articles:        {}", stats.articles)?;
+	writeln!(out, "In the trash:    {}", stats.deleted_articles)?;
+	writeln!(out, "Texts:           {}", format_size(stats.text_bytes))?;
+	writeln!(out, "Tags:            {}", stats.tags)?;
+	writeln!(out, "Categories:      {}", stats.categories)?;
+	writeln!(out, "Links:           {}", stats.links)?;
+	writeln!(out, "Title redirects: {}", stats.title_redirects)?;
+	writeln!(out, "Cached favicons: {}", stats.favicons)?;
+	writeln!(
+		out,
+		"Database:        {} ({} pages of {} bytes, {} unused)",
+		format_size(stats.pages * stats.page_size),
+		stats.pages,
+		stats.page_size,
+		stats.free_pages
+	)?;
+	Ok(true)
+}
+
+/// One line per implausible date: table, row, column and the value as
+/// stored, separated by tabs. Succeeds if there are none, or if they
+/// were set to `now` with `fix`.
+fn verify_dates(
+	db: &mut Database,
+	fix: bool,
+	now: UtcTimestamp,
+	out: &mut impl Write,
+) -> Result<bool, Error> {
+	let bad = db
+		.find_bad_dates(now)
+		.ok_or_else(|| Error::Message(READ_FAILED.to_string()))?;
+	for date in &bad {
+		writeln!(
+			out,
+			"{}\t{}\t{}\t{}",
+			date.table, date.key, date.column, date.value
+		)?;
+	}
+	if !fix || bad.is_empty() {
+		return Ok(bad.is_empty());
+	}
+	let fixed = db
+		.fix_bad_dates(now)
+		.map_err(|err| Error::Message(format!("Could not fix the dates: {}", err)))?;
+	writeln!(out, "Set {} dates to {}", fixed, now)?;
+	Ok(true)
+}
+
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+	if bytes < 1024 {
+		return format!("{} bytes", bytes);
+	}
+	let mut size = bytes as f64 / 1024.0;
+	let mut unit = 0;
+	while size >= 1024.0 && unit + 1 < UNITS.len() {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::database::Article;
+	use crate::timestamp::UtcTimestamp;
+
+	fn args(line: &str) -> Vec<String> {
+		line.split_whitespace().map(str::to_string).collect()
+	}
+
+	fn output(command: Command, db: &mut Database) -> (Result<bool, String>, String) {
+		let mut out = Vec::new();
+		let result = execute(&command, db, &Backup::default(), &mut out);
+		(result, String::from_utf8(out).unwrap())
+	}
+
+	fn command(line: &str) -> Command {
+		match parse_args(&args(line)).unwrap().action {
+			Action::Run(command) => command,
+			action => panic!("Not a command: {:?}", action),
+		}
+	}
+
+	#[test]
+	fn test_parse_args() {
+		assert_eq!(
+			parse_args(&args("")),
+			Ok(Invocation {
+				overrides: Overrides::default(),
+				db: None,
+				action: Action::Serve {
+					resume_migration: false
+				},
+			})
+		);
+		assert_eq!(
+			parse_args(&args("--resume-migration")).unwrap().action,
+			Action::Serve {
+				resume_migration: true
+			}
+		);
+		assert_eq!(
+			parse_args(&args(
+				"serve --config /etc/wiki.toml --data-dir=/var/lib/wiki --listen [::1]:8080"
+			)),
+			Ok(Invocation {
+				overrides: Overrides {
+					config: Some(PathBuf::from("/etc/wiki.toml")),
+					data_dir: Some(PathBuf::from("/var/lib/wiki")),
+					listen: Some("[::1]:8080".parse().unwrap()),
+				},
+				db: None,
+				action: Action::Serve {
+					resume_migration: false
+				},
+			})
+		);
+		assert_eq!(
+			parse_args(&args("--version")).unwrap().action,
+			Action::Version
+		);
+		assert_eq!(parse_args(&args("grep x -h")).unwrap().action, Action::Help);
+		assert_eq!(
+			parse_args(&args("article show 5")),
+			Ok(Invocation {
+				overrides: Overrides::default(),
+				db: None,
+				action: Action::Run(Command::ShowArticle(5.into())),
+			})
+		);
+		assert_eq!(
+			parse_args(&args("--db /tmp/wiki.sqlite article list --sort modified")),
+			Ok(Invocation {
+				overrides: Overrides::default(),
+				db: Some(PathBuf::from("/tmp/wiki.sqlite")),
+				action: Action::Run(Command::ListArticles(ArticleOrder::Modified)),
+			})
+		);
+		assert_eq!(
+			parse_args(&args("grep redwood --db x.sqlite")),
+			Ok(Invocation {
+				overrides: Overrides::default(),
+				db: Some(PathBuf::from("x.sqlite")),
+				action: Action::Run(Command::Grep("redwood".to_string())),
+			})
+		);
+		assert_eq!(
+			command("grep -- --redwood"),
+			Command::Grep("--redwood".to_string())
+		);
+		assert_eq!(command("db stats"), Command::Stats);
+		assert_eq!(command("check"), Command::Check);
+		assert_eq!(command("backup"), Command::Backup(None));
+		assert_eq!(
+			command("backup /tmp/copy.sqlite"),
+			Command::Backup(Some(PathBuf::from("/tmp/copy.sqlite")))
+		);
+		assert!(parse_args(&args("article show five")).is_err());
+		assert!(parse_args(&args("article list --sort size")).is_err());
+		assert!(parse_args(&args("grep x --sort title")).is_err());
+		assert!(parse_args(&args("--db")).is_err());
+		assert!(parse_args(&args("--db x.sqlite")).is_err());
+		assert!(parse_args(&args("article delete 5")).is_err());
+		assert!(parse_args(&args("db stats --resume-migration")).is_err());
+		assert!(parse_args(&args("check --listen 127.0.0.1:80")).is_err());
+		assert!(parse_args(&args("--listen 127.0.0.1")).is_err());
+		assert!(parse_args(&args("--fix=yes db verify-dates")).is_err());
+		assert_eq!(
+			parse_args(&args("--verbose")),
+			Err("Unknown option `--verbose`".to_string())
+		);
+		assert_eq!(
+			parse_args(&args("-x")),
+			Err("Unknown option `-x`".to_string())
+		);
+		assert_eq!(
+			command("db verify-dates --fix"),
+			Command::VerifyDates { fix: true }
+		);
+		assert_eq!(
+			command("db verify-dates"),
+			Command::VerifyDates { fix: false }
+		);
+		assert_eq!(command("export"), Command::Export);
+		assert_eq!(
+			command("import wiki.json --on-conflict=rename"),
+			Command::Import {
+				file: PathBuf::from("wiki.json"),
+				on_conflict: ImportConflict::Rename
+			}
+		);
+		assert_eq!(
+			command("import --on-conflict overwrite wiki.json"),
+			Command::Import {
+				file: PathBuf::from("wiki.json"),
+				on_conflict: ImportConflict::Overwrite
+			}
+		);
+		assert_eq!(
+			command("import wiki.json"),
+			Command::Import {
+				file: PathBuf::from("wiki.json"),
+				on_conflict: ImportConflict::Skip
+			}
+		);
+		assert_eq!(
+			command("article import notes"),
+			Command::ImportMarkdown(PathBuf::from("notes"))
+		);
+		assert!(parse_args(&args("import wiki.json --on-conflict=merge")).is_err());
+		assert!(parse_args(&args("export --on-conflict skip")).is_err());
+		assert!(parse_args(&args("db stats --fix")).is_err());
+		assert!(parse_args(&args("--fix")).is_err());
+		assert_eq!(
+			parse_args(&args("init-config")).unwrap().action,
+			Action::InitConfig {
+				file: PathBuf::from("wiki-config.toml"),
+				force: false
+			}
+		);
+		assert_eq!(
+			parse_args(&args("init-config --force /etc/wiki.toml"))
+				.unwrap()
+				.action,
+			Action::InitConfig {
+				file: PathBuf::from("/etc/wiki.toml"),
+				force: true
+			}
+		);
+		assert!(parse_args(&args("init-config --data-dir x")).is_err());
+		assert!(parse_args(&args("check --force")).is_err());
+	}
+
+	#[test]
+	fn test_commands() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-cli-{}.sqlite",
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let mut create = |title: &str, text: &str| {
+			db.create_article(&Article {
+				id: 0.into(),
+				title: title.to_string(),
+				text: text.to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap()
+		};
+		let redwood = create(
+			"Coast redwood",
+			"# Coast redwood\n\nTallest tree.\nGrows in fog.",
+		);
+		let sequoia = create("Giant sequoia", "Largest tree by volume.\n");
+		let trashed = create("Trashed tree", "Tree in the trash.");
+		db.add_tag(redwood, "conifer").unwrap();
+		db.add_tag(redwood, "tree").unwrap();
+		db.delete_article(trashed).unwrap();
+		drop(db);
+
+		let mut db = open(&path, false, &ConnectionSettings::default()).unwrap();
+		let (result, out) = output(Command::ShowArticle(redwood), &mut db);
+		assert_eq!(result, Ok(true));
+		let lines: Vec<&str> = out.lines().collect();
+		assert_eq!(lines[0], "Title:    Coast redwood");
+		assert_eq!(lines[1], format!("ID:       {}", redwood));
+		assert_eq!(
+			lines[2],
+			format!("Path:     /article/{}/coast-redwood", redwood)
+		);
+		assert!(lines[3].starts_with("Created:  ") && lines[3].ends_with(" UTC"));
+		assert_eq!(lines[5], "Revision: 0");
+		assert_eq!(lines[6], "Tags:     conifer, tree");
+		assert!(out.ends_with("\n\n# Coast redwood\n\nTallest tree.\nGrows in fog.\n"));
+		assert_eq!(
+			output(Command::ShowArticle(trashed), &mut db).0,
+			Err(format!("Article #{} does not exist", trashed))
+		);
+
+		let (result, out) = output(Command::ListArticles(ArticleOrder::Title), &mut db);
+		assert_eq!(result, Ok(true));
+		let titles: Vec<&str> = out
+			.lines()
+			.map(|line| line.rsplit('\t').next().unwrap())
+			.collect();
+		assert_eq!(titles, ["Coast redwood", "Giant sequoia"]);
+		assert!(out.starts_with(&format!("{}\t", redwood)));
+
+		let (result, out) = output(Command::Grep("(?i)tree".to_string()), &mut db);
+		assert_eq!(result, Ok(true));
+		assert_eq!(
+			out,
+			format!(
+				"{}:Coast redwood:Tallest tree.\n{}:Giant sequoia:Largest tree by volume.\n",
+				redwood, sequoia
+			)
+		);
+		let (result, out) = output(Command::Grep("^Giant".to_string()), &mut db);
+		assert_eq!(result, Ok(true));
+		assert_eq!(out, format!("{}:Giant sequoia:\n", sequoia));
+		assert_eq!(
+			output(Command::Grep("palm".to_string()), &mut db),
+			(Ok(false), String::new())
+		);
+		assert!(output(Command::Grep("(".to_string()), &mut db).0.is_err());
+
+		let (result, out) = output(Command::Stats, &mut db);
+		assert_eq!(result, Ok(true));
+		assert!(out.starts_with("Articles:        2\nIn the trash:    1\n"));
+		assert!(out.contains("\nTags:            2\n"));
+		let (result, out) = output(Command::Export, &mut db);
+		assert_eq!(result, Ok(true));
+		let export_path = path.with_extension("json");
+		std::fs::write(&export_path, &out).unwrap();
+		let mut copy = DatabaseConnection::new(Path::new(""), OpenMode::InMemory)
+			.unwrap()
+			.init()
+			.unwrap();
+		let import = Command::Import {
+			file: export_path.clone(),
+			on_conflict: ImportConflict::Skip,
+		};
+		assert_eq!(
+			output(import, &mut copy),
+			(
+				Ok(true),
+				"Imported: 2\nSkipped:  0\nFailed:   0\n".to_string()
+			)
+		);
+		assert_eq!(copy.get_tags(redwood).unwrap(), ["conifer", "tree"]);
+		std::fs::remove_file(&export_path).unwrap();
+		let export: serde_json::Value = serde_json::from_str(&out).unwrap();
+		assert_eq!(export["articles"][0]["title"], "Coast redwood");
+		assert_eq!(export["articles"][1]["title"], "Giant sequoia");
+		assert_eq!(export["articles"].as_array().unwrap().len(), 2);
+		assert_eq!(
+			output(Command::VerifyDates { fix: false }, &mut db),
+			(Ok(true), String::new())
+		);
+		assert_eq!(
+			output(Command::Check, &mut db),
+			(Ok(true), "Integrity: ok\nDates:     ok\n".to_string())
+		);
+		let backup_path = path.with_extension("backup.sqlite");
+		let _ = std::fs::remove_file(&backup_path);
+		let (result, out) = output(Command::Backup(Some(backup_path.clone())), &mut db);
+		assert_eq!(result, Ok(true));
+		assert_eq!(
+			out,
+			format!("Backed up the database to {}\n", backup_path.display())
+		);
+		let mut copy = open(&backup_path, false, &ConnectionSettings::default()).unwrap();
+		assert_eq!(copy.get_tags(redwood).unwrap(), ["conifer", "tree"]);
+		drop(copy);
+		// Existing files are not overwritten
+		assert!(output(Command::Backup(Some(backup_path.clone())), &mut db)
+			.0
+			.is_err());
+		std::fs::remove_file(&backup_path).unwrap();
+		drop(db);
+
+		// An import from a machine with a wrong clock
+		rusqlite::Connection::open(&path)
+			.unwrap()
+			.execute(
+				"UPDATE article SET date_modified = '1970-01-01 00:00:00' WHERE id = ?",
+				[sequoia],
+			)
+			.unwrap();
+		let mut db = open(&path, false, &ConnectionSettings::default()).unwrap();
+		let (result, out) = output(Command::VerifyDates { fix: false }, &mut db);
+		assert_eq!(result, Ok(false));
+		assert_eq!(
+			out,
+			format!("article\t{}\tdate_modified\t1970-01-01 00:00:00\n", sequoia)
+		);
+		assert_eq!(
+			output(Command::Check, &mut db),
+			(
+				Ok(false),
+				"Integrity: ok\nDates:     1 implausible, see `db verify-dates`\n".to_string()
+			)
+		);
+		drop(db);
+		let mut db = open(&path, true, &ConnectionSettings::default()).unwrap();
+		let now = UtcTimestamp::now();
+		let mut out = Vec::new();
+		assert!(matches!(
+			verify_dates(&mut db, true, now, &mut out),
+			Ok(true)
+		));
+		assert!(String::from_utf8(out)
+			.unwrap()
+			.ends_with(&format!("\nSet 1 dates to {}\n", now)));
+		assert_eq!(db.get_article(sequoia).unwrap().unwrap().date_modified, now);
+		assert_eq!(
+			output(Command::VerifyDates { fix: false }, &mut db),
+			(Ok(true), String::new())
+		);
+		drop(db);
+
+		// Nothing to read while a migration is in progress
+		let set_layout = |sql: &str| {
+			rusqlite::Connection::open(&path)
+				.unwrap()
+				.execute(sql, [])
+				.unwrap();
+		};
+		set_layout("UPDATE table_layout SET migrating_to_version = '1000.0.0'");
+		assert!(open(&path, false, &ConnectionSettings::default())
+			.err()
+			.unwrap()
+			.contains(", a migration to layout 1000.0.0 is in progress (started at"));
+		// Nor from a newer version of the wiki
+		set_layout("UPDATE table_layout SET migrating_to_version = NULL, version = '1000.0.0'");
+		let message = open(&path, false, &ConnectionSettings::default())
+			.err()
+			.unwrap();
+		assert!(message.starts_with("Could not read "));
+		assert!(message.contains(
+			": the database was created by a newer version of redwood-wiki (layout 1000.0.0), this build supports "
+		));
+		std::fs::remove_file(&path).unwrap();
+		assert!(open(&path, false, &ConnectionSettings::default()).is_err());
+	}
+
+	#[test]
+	fn test_format_size() {
+		assert_eq!(format_size(1000), "1000 bytes");
+		assert_eq!(format_size(1536), "1.5 KiB");
+		assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+	}
+}
@@ -1,16 +1,160 @@
 use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag};
 
+use serde::{Deserialize, Serialize};
+use syntect::highlighting::{Theme, ThemeSet};
 use syntect::html::{ClassStyle, ClassedHTMLGenerator};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
 
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
+/// Code blocks in these languages are not meant to be highlighted, so
+/// they get no note that there is no syntax for them
+const UNHIGHLIGHTED_LANGUAGES: [&str; 1] = ["mermaid"];
+
+/// The syntaxes code blocks are highlighted with: those of syntect and
+/// the `.sublime-syntax` files of `[render] extra_syntaxes_dir`. Without
+/// extra syntaxes, the defaults are only loaded on first use.
+#[derive(Default)]
+pub struct Syntaxes(OnceLock<SyntaxSet>);
+
+impl std::fmt::Debug for Syntaxes {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("Syntaxes")
+			.field(&self.0.get().map(|set| set.syntaxes().len()))
+			.finish()
+	}
+}
+
+impl Syntaxes {
+	/// The default syntaxes and those of the `.sublime-syntax` files in
+	/// `dir`, along with the files that could not be loaded and why.
+	/// An unreadable directory is an error, the defaults are used then.
+	pub fn load(dir: &Path) -> std::io::Result<(Syntaxes, Vec<(String, String)>)> {
+		let mut files: Vec<_> = std::fs::read_dir(dir)?
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| path.extension().is_some_and(|ext| ext == "sublime-syntax"))
+			.collect();
+		files.sort();
+
+		let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+		let mut problems = Vec::new();
+		for file in files {
+			let name = file.file_name().unwrap_or_default().to_string_lossy();
+			let definition = std::fs::read_to_string(&file)
+				.map_err(|err| err.to_string())
+				.and_then(|text| {
+					let fallback_name = file.file_stem().and_then(|stem| stem.to_str());
+					SyntaxDefinition::load_from_str(&text, true, fallback_name)
+						.map_err(|err| err.to_string())
+				});
+			match definition {
+				Ok(definition) => builder.add(definition),
+				Err(err) => problems.push((name.into_owned(), err)),
+			}
+		}
+		Ok((Syntaxes(OnceLock::from(builder.build())), problems))
+	}
+
+	pub fn get(&self) -> &SyntaxSet {
+		self.0.get_or_init(SyntaxSet::load_defaults_newlines)
+	}
+}
+
+/// Theme of highlighted code if `appearance.code_theme` is not set
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// `appearance.code_theme`: the name of one of syntect's themes, or a
+/// `.tmTheme` file. Names are checked when the configuration is read.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum CodeTheme {
+	Builtin(String),
+	File(PathBuf),
+}
+
+impl Default for CodeTheme {
+	fn default() -> CodeTheme {
+		CodeTheme::Builtin(DEFAULT_THEME.to_string())
+	}
+}
+
+impl TryFrom<String> for CodeTheme {
+	type Error = String;
+
+	fn try_from(name: String) -> Result<CodeTheme, String> {
+		if name.ends_with(".tmTheme") {
+			return Ok(CodeTheme::File(PathBuf::from(name)));
+		}
+		let themes = ThemeSet::load_defaults().themes;
+		if themes.contains_key(&name) {
+			Ok(CodeTheme::Builtin(name))
+		} else {
+			let names: Vec<String> = themes.keys().map(|name| format!("{:?}", name)).collect();
+			Err(format!(
+				"unknown code theme {:?}, expected one of {} or a `.tmTheme` file",
+				name,
+				names.join(", ")
+			))
+		}
+	}
+}
+
+impl From<CodeTheme> for String {
+	fn from(theme: CodeTheme) -> String {
+		match theme {
+			CodeTheme::Builtin(name) => name,
+			CodeTheme::File(path) => path.to_string_lossy().into_owned(),
+		}
+	}
+}
+
+/// CSS for the classes the syntax highlighting emits
+pub fn theme_css(theme: &Theme) -> String {
+	syntect::html::css_for_theme_with_class_style(theme, ClassStyle::Spaced).unwrap()
+}
+
+/// CSS of the code theme, generated on first use. A `.tmTheme` file is
+/// loaded by `startup::init_optional`, so that a broken file shows up
+/// as a startup problem.
+#[derive(Default)]
+pub struct ThemeCss(OnceLock<String>);
+
+impl std::fmt::Debug for ThemeCss {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("ThemeCss")
+			.field(&self.0.get().map(String::len))
+			.finish()
+	}
+}
+
+impl ThemeCss {
+	pub fn load(path: &Path) -> Result<ThemeCss, syntect::LoadingError> {
+		let theme = ThemeSet::get_theme(path)?;
+		Ok(ThemeCss(OnceLock::from(theme_css(&theme))))
+	}
+
+	/// The CSS of `theme`, of the default theme if its file cannot be read
+	pub fn get(&self, theme: &CodeTheme) -> &str {
+		self.0.get_or_init(|| {
+			let mut themes = ThemeSet::load_defaults().themes;
+			let loaded = match theme {
+				CodeTheme::Builtin(name) => themes.remove(name),
+				CodeTheme::File(path) => ThemeSet::get_theme(path).ok(),
+			};
+			theme_css(&loaded.unwrap_or_else(|| themes.remove(DEFAULT_THEME).unwrap()))
+		})
+	}
+}
+
 // To use the SyntaxHighlightStream, prior text merging is
 // required to prevent confusing the syntect parser with
 // events that only contain partial lines
 
 pub struct SyntaxHighlightStream<'a, 'syn_set, I> {
 	iter: I,
+	syntax_set: &'syn_set SyntaxSet,
 	inject_event: Option<Event<'a>>,
 	html_generator: Option<ClassedHTMLGenerator<'syn_set>>,
 }
@@ -19,9 +163,10 @@ impl<'a, 'syn_set, I> SyntaxHighlightStream<'a, 'syn_set, I>
 where
 	I: Iterator<Item = Event<'a>>,
 {
-	pub fn new(iter: I) -> Self {
+	pub fn new(iter: I, syntax_set: &'syn_set SyntaxSet) -> Self {
 		Self {
 			iter,
+			syntax_set,
 			inject_event: None,
 			html_generator: None,
 		}
@@ -43,24 +188,40 @@ where
 
 		match self.iter.next() {
 			Some(Event::Start(Tag::CodeBlock(language))) => {
-				static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
-				let syntax_set: &SyntaxSet =
-					SYNTAX_SET.get_or_init(|| SyntaxSet::load_defaults_newlines());
-
-				let syntax = if let CodeBlockKind::Fenced(lang_str) = &language {
-					syntax_set.find_syntax_by_token(&lang_str)
-				} else {
-					None
-				}
-				.unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+				let syntax_set = self.syntax_set;
+				let (syntax, unknown_language) = match &language {
+					CodeBlockKind::Fenced(lang_str) if !lang_str.is_empty() => {
+						match syntax_set.find_syntax_by_token(lang_str) {
+							Some(syntax) => (syntax, None),
+							None => (
+								syntax_set.find_syntax_plain_text(),
+								Some(lang_str.clone()).filter(|lang| {
+									!UNHIGHLIGHTED_LANGUAGES.contains(&lang.as_ref())
+								}),
+							),
+						}
+					}
+					_ => (syntax_set.find_syntax_plain_text(), None),
+				};
 
 				self.html_generator = Some(ClassedHTMLGenerator::new_with_class_style(
-					&syntax,
+					syntax,
 					syntax_set,
 					ClassStyle::Spaced,
 				));
 
-				Some(Event::Start(Tag::CodeBlock(language)))
+				match unknown_language {
+					Some(lang) => {
+						// Rather than looking like a highlighting bug
+						self.inject_event = Some(Event::Start(Tag::CodeBlock(language)));
+						let mut html =
+							String::from("<div class=\"code_note\">No highlighting for “");
+						pulldown_cmark::escape::escape_html(&mut html, &lang).unwrap();
+						html.push_str("”</div>\n");
+						Some(Event::Html(CowStr::Boxed(html.into_boxed_str())))
+					}
+					None => Some(Event::Start(Tag::CodeBlock(language))),
+				}
 			}
 			next_event @ Some(Event::End(Tag::CodeBlock(_))) => {
 				let mut local_html_gen = None;
@@ -89,3 +250,85 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::render::{Pipeline, RenderOptions};
+
+	fn render(text: &str, syntaxes: &Syntaxes) -> String {
+		Pipeline::new(RenderOptions::default())
+			.with_syntax_highlight("", syntaxes.get())
+			.render_html(text)
+			.html
+	}
+
+	#[test]
+	fn test_extra_syntaxes() {
+		let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata/syntaxes");
+		let (syntaxes, problems) = Syntaxes::load(&dir).unwrap();
+		assert!(problems.is_empty());
+		assert_eq!(
+			render("```rwtest\nthe tree \"in the grove\"\n```", &syntaxes),
+			"<pre><code class=\"language-rwtest\"><span class=\"source rwtest\">the <span class=\"keyword other rwtest\">tree</span> <span class=\"string quoted double rwtest\">&quot;in the grove&quot;</span>\n</span></code></pre>\n"
+		);
+		// The defaults are still there
+		assert!(
+			render("```rust\nfn main() {}\n```", &syntaxes).contains("storage type function rust")
+		);
+		assert!(Syntaxes::load(&dir.join("missing")).is_err());
+	}
+
+	#[test]
+	fn test_unknown_language() {
+		let syntaxes = Syntaxes::default();
+		let html = render("```rwtest\ntree\n```", &syntaxes);
+		assert_eq!(
+			html,
+			"<div class=\"code_note\">No highlighting for “rwtest”</div>\n<pre><code class=\"language-rwtest\"><span class=\"text plain\">tree\n</span></code></pre>\n"
+		);
+		assert!(render("```<b>\nx\n```", &syntaxes).contains("No highlighting for “&lt;b&gt;”"));
+		// No note without a language, or for diagrams
+		for text in ["```\ntree\n```", "    tree", "```mermaid\nA-->B\n```"] {
+			assert!(!render(text, &syntaxes).contains("code_note"), "{}", text);
+		}
+	}
+
+	#[test]
+	fn test_code_theme() {
+		let parse = |name: &str| CodeTheme::try_from(name.to_string());
+		assert_eq!(parse(DEFAULT_THEME), Ok(CodeTheme::default()));
+		assert_eq!(
+			parse("Solarized (dark)"),
+			Ok(CodeTheme::Builtin("Solarized (dark)".to_string()))
+		);
+		assert_eq!(
+			parse("themes/forest.tmTheme"),
+			Ok(CodeTheme::File(PathBuf::from("themes/forest.tmTheme")))
+		);
+		let err = parse("Forest").unwrap_err();
+		assert!(err.contains("\"InspiredGitHub\", "), "{}", err);
+
+		let css = |theme: &CodeTheme| ThemeCss::default().get(theme).to_string();
+		let themes = ThemeSet::load_defaults().themes;
+		assert_eq!(
+			css(&CodeTheme::default()),
+			theme_css(&themes[DEFAULT_THEME])
+		);
+		assert_eq!(
+			css(&parse("base16-ocean.dark").unwrap()),
+			theme_css(&themes["base16-ocean.dark"])
+		);
+		assert_ne!(
+			css(&parse("base16-ocean.dark").unwrap()),
+			css(&CodeTheme::default())
+		);
+		// A missing file falls back to the default
+		assert_eq!(
+			css(&parse("missing.tmTheme").unwrap()),
+			css(&CodeTheme::default())
+		);
+		assert!(ThemeCss::load(Path::new("missing.tmTheme")).is_err());
+	}
+}
@@ -0,0 +1,318 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use warp::http::header::{self, HeaderValue};
+use warp::http::StatusCode;
+use warp::hyper::{self, Body, Response};
+
+use super::config;
+
+/// Content types worth compressing. Images other than SVG are already
+/// compressed.
+const COMPRESSIBLE_TYPES: [&str; 5] = [
+	"application/json",
+	"application/javascript",
+	"application/xml",
+	"application/atom+xml",
+	"image/svg+xml",
+];
+
+/// Quality of Brotli compression, from 0 to 11. The pages are
+/// compressed for every request, higher levels take much longer for
+/// little gain.
+const BROTLI_QUALITY: u32 = 5;
+
+/// Whether the client accepts `coding`, according to `Accept-Encoding`
+fn accepts(accept_encoding: &str, coding: &str) -> bool {
+	accept_encoding.split(',').any(|accepted| {
+		let mut params = accepted.split(';').map(str::trim);
+		let name = params.next().unwrap_or("");
+		let refused = params.any(|param| {
+			param
+				.strip_prefix("q=")
+				.and_then(|q| q.parse::<f32>().ok())
+				.is_some_and(|q| q == 0.0)
+		});
+		(name.eq_ignore_ascii_case(coding) || name == "*") && !refused
+	})
+}
+
+fn accepts_gzip(accept_encoding: &str) -> bool {
+	accepts(accept_encoding, "gzip")
+}
+
+fn accepts_brotli(accept_encoding: &str) -> bool {
+	accepts(accept_encoding, "br")
+}
+
+/// Encodings the wiki can compress with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+	Brotli,
+	Gzip,
+}
+
+impl Encoding {
+	/// Brotli compresses better, it is used if the client accepts both
+	fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+		if accepts_brotli(accept_encoding) {
+			Some(Encoding::Brotli)
+		} else if accepts_gzip(accept_encoding) {
+			Some(Encoding::Gzip)
+		} else {
+			None
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Encoding::Brotli => "br",
+			Encoding::Gzip => "gzip",
+		}
+	}
+
+	fn encode(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+		match self {
+			Encoding::Brotli => {
+				let mut encoder =
+					brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, 22);
+				encoder.write_all(data)?;
+				encoder.flush()?;
+				Ok(encoder.into_inner())
+			}
+			Encoding::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(data)?;
+				encoder.finish()
+			}
+		}
+	}
+}
+
+fn is_compressible(response: &Response<Body>) -> bool {
+	let Some(content_type) = response
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+	else {
+		return false;
+	};
+	let mime = content_type.split(';').next().unwrap_or("").trim();
+	mime.starts_with("text/") || COMPRESSIBLE_TYPES.contains(&mime)
+}
+
+/// Compress the body of `response` with Brotli or gzip if the client
+/// accepts it and the content type and size are worth it. The responses
+/// of the wiki are generated in memory, so the body is read completely;
+/// it gets a `Content-Length` whether it is compressed or not.
+pub async fn compress(
+	response: Response<Body>,
+	accept_encoding: Option<&str>,
+	settings: &config::Compression,
+) -> Response<Body> {
+	if !settings.enabled
+		|| response.status() == StatusCode::NOT_MODIFIED
+		|| response.status() == StatusCode::NO_CONTENT
+		|| response.headers().contains_key(header::CONTENT_ENCODING)
+		|| !is_compressible(&response)
+	{
+		return response;
+	}
+	let (mut parts, body) = response.into_parts();
+	// Caches must not serve the compressed variant to other clients
+	parts
+		.headers
+		.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+	let body = match hyper::body::to_bytes(body).await {
+		Ok(body) => body,
+		Err(err) => {
+			log::error!("Could not read the response to compress it: {}", err);
+			parts.status = StatusCode::INTERNAL_SERVER_ERROR;
+			parts.headers.remove(header::CONTENT_LENGTH);
+			return Response::from_parts(parts, Body::empty());
+		}
+	};
+	let encoding = accept_encoding.and_then(Encoding::negotiate);
+	let body = match encoding.filter(|_| body.len() >= settings.min_size_bytes) {
+		Some(encoding) => match encoding.encode(&body) {
+			Ok(compressed) => {
+				parts.headers.insert(
+					header::CONTENT_ENCODING,
+					HeaderValue::from_static(encoding.name()),
+				);
+				// The compressed bytes differ, the ETag no longer is strong
+				if let Some(etag) = parts.headers.get(header::ETAG) {
+					if !etag.as_bytes().starts_with(b"W/") {
+						let mut weak = b"W/".to_vec();
+						weak.extend_from_slice(etag.as_bytes());
+						if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+							parts.headers.insert(header::ETAG, weak);
+						}
+					}
+				}
+				compressed.into()
+			}
+			Err(err) => {
+				log::error!("Could not compress the response: {}", err);
+				body
+			}
+		},
+		None => body,
+	};
+	parts
+		.headers
+		.insert(header::CONTENT_LENGTH, body.len().into());
+	parts.headers.remove(header::TRANSFER_ENCODING);
+	Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use flate2::read::GzDecoder;
+	use std::io::Read;
+
+	fn settings(enabled: bool, min_size_bytes: usize) -> config::Compression {
+		config::Compression {
+			enabled,
+			min_size_bytes,
+		}
+	}
+
+	fn response(content_type: &str, body: &str) -> Response<Body> {
+		Response::builder()
+			.header(header::CONTENT_TYPE, content_type)
+			.header(header::ETAG, "\"abc\"")
+			.body(Body::from(body.to_string()))
+			.unwrap()
+	}
+
+	async fn body(response: Response<Body>) -> Vec<u8> {
+		hyper::body::to_bytes(response.into_body())
+			.await
+			.unwrap()
+			.to_vec()
+	}
+
+	#[test]
+	fn test_accepts_gzip() {
+		assert!(accepts_gzip("gzip"));
+		assert!(accepts_gzip("deflate, GZIP;q=0.5, br"));
+		assert!(accepts_gzip("*"));
+		assert!(!accepts_gzip("gzip;q=0"));
+		assert!(!accepts_gzip("gzip; q=0.0, br"));
+		assert!(!accepts_gzip("deflate, br"));
+		assert!(!accepts_gzip("identity"));
+		assert!(!accepts_gzip(""));
+	}
+
+	#[test]
+	fn test_accepts_brotli() {
+		assert!(accepts_brotli("br"));
+		assert!(accepts_brotli("gzip, deflate, BR;q=0.5"));
+		assert!(accepts_brotli("*"));
+		assert!(!accepts_brotli("br;q=0"));
+		assert!(!accepts_brotli("gzip, br; q=0.0"));
+		assert!(!accepts_brotli("gzip, deflate"));
+		assert!(!accepts_brotli("brotli"));
+		assert!(!accepts_brotli(""));
+	}
+
+	#[test]
+	fn test_negotiate() {
+		assert_eq!(Encoding::negotiate("gzip, br"), Some(Encoding::Brotli));
+		assert_eq!(
+			Encoding::negotiate("br;q=0.1, gzip"),
+			Some(Encoding::Brotli)
+		);
+		assert_eq!(Encoding::negotiate("*"), Some(Encoding::Brotli));
+		assert_eq!(Encoding::negotiate("gzip, br;q=0"), Some(Encoding::Gzip));
+		assert_eq!(Encoding::negotiate("deflate"), None);
+	}
+
+	#[tokio::test]
+	async fn test_compress_brotli() {
+		let text = "<p>Coast redwood</p>".repeat(100);
+		let compressed = compress(
+			response("text/html; charset=utf-8", &text),
+			Some("gzip, deflate, br"),
+			&settings(true, 1024),
+		)
+		.await;
+		let headers = compressed.headers().clone();
+		assert_eq!(headers["content-encoding"], "br");
+		assert_eq!(headers["vary"], "accept-encoding");
+		assert_eq!(headers["etag"], "W/\"abc\"");
+		let data = body(compressed).await;
+		assert_eq!(headers["content-length"], data.len().to_string().as_str());
+		assert!(data.len() < text.len() / 10);
+		let mut decoded = String::new();
+		brotli::Decompressor::new(&data[..], 4096)
+			.read_to_string(&mut decoded)
+			.unwrap();
+		assert_eq!(decoded, text);
+	}
+
+	#[tokio::test]
+	async fn test_compress() {
+		let text = "<p>Coast redwood</p>".repeat(100);
+		let compressed = compress(
+			response("text/html; charset=utf-8", &text),
+			Some("gzip, deflate"),
+			&settings(true, 1024),
+		)
+		.await;
+		let headers = compressed.headers().clone();
+		assert_eq!(headers["content-encoding"], "gzip");
+		assert_eq!(headers["vary"], "accept-encoding");
+		assert_eq!(headers["etag"], "W/\"abc\"");
+		let data = body(compressed).await;
+		assert_eq!(headers["content-length"], data.len().to_string().as_str());
+		assert!(data.len() < text.len() / 10);
+		let mut decoded = String::new();
+		GzDecoder::new(&data[..])
+			.read_to_string(&mut decoded)
+			.unwrap();
+		assert_eq!(decoded, text);
+
+		// Not compressed, but with the length
+		for (content_type, accept, settings) in [
+			("text/html", None, settings(true, 1024)),
+			("text/html", Some("deflate"), settings(true, 1024)),
+			("text/html", Some("gzip"), settings(true, 4096)),
+			("text/html", Some("br"), settings(true, 4096)),
+			(
+				"application/json",
+				Some("gzip;q=0, br;q=0"),
+				settings(true, 1024),
+			),
+		] {
+			let response = compress(response(content_type, &text), accept, &settings).await;
+			assert!(response.headers().get("content-encoding").is_none());
+			assert_eq!(response.headers()["vary"], "accept-encoding");
+			assert_eq!(response.headers()["etag"], "\"abc\"");
+			assert_eq!(response.headers()["content-length"], "2000");
+			assert_eq!(body(response).await, text.as_bytes());
+		}
+
+		// Left alone entirely
+		for (content_type, settings) in [
+			("image/png", settings(true, 1024)),
+			("image/x-icon", settings(true, 1024)),
+			("text/html", settings(false, 1024)),
+		] {
+			let response = compress(response(content_type, &text), Some("gzip"), &settings).await;
+			assert!(response.headers().get("content-encoding").is_none());
+			assert!(response.headers().get("vary").is_none());
+			assert!(response.headers().get("content-length").is_none());
+		}
+		let svg = compress(
+			response("image/svg+xml", &text),
+			Some("gzip"),
+			&settings(true, 1024),
+		)
+		.await;
+		assert_eq!(svg.headers()["content-encoding"], "gzip");
+	}
+}
@@ -1,35 +1,885 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use std::io::ErrorKind;
-use std::net::IpAddr;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Deserialize, Debug)]
+use super::assets::AssetClass;
+use super::auth::{PasswordHash, SessionKey, SessionSecret, Verified};
+use super::codeblock_syntax_highlight::{CodeTheme, Syntaxes, ThemeCss};
+use super::custom_css::{CustomCss, Stylesheet};
+use super::database::{ConnectionSettings, JournalMode, Synchronous};
+use super::timestamp::{DateFormat, DateWindow, SiteTimezone, UtcTimestamp};
+use super::unix_socket::SocketMode;
+use super::urls::BasePath;
+
+#[derive(Deserialize, Serialize, Debug, Default)]
 pub struct Config {
+	/// The file this configuration was read from
+	#[serde(skip)]
+	pub file_path: Option<PathBuf>,
 	pub network: Network,
+	#[serde(default)]
+	pub wiki: Wiki,
+	#[serde(default)]
 	pub database: Database,
+	#[serde(default)]
+	pub search: Search,
+	#[serde(default)]
+	pub editor: Editor,
+	#[serde(default)]
+	pub render: Render,
+	#[serde(default)]
+	pub markdown: Markdown,
+	#[serde(default)]
+	pub listing: Listing,
+	#[serde(default)]
+	pub appearance: Appearance,
+	/// Daily notes, enabled if the section is present
+	pub journal: Option<Journal>,
+	/// Scheduled backups of the database, enabled if the section is present
+	pub backup: Option<Backup>,
+	/// Credentials for editing, enabled if the section is present
+	pub auth: Option<Auth>,
+	#[serde(default)]
+	pub hooks: Hooks,
+	#[serde(default)]
+	pub api: Api,
+	#[serde(default)]
+	pub site: Site,
+	#[serde(default)]
+	pub log: Log,
+	#[serde(default)]
+	pub limits: Limits,
+	#[serde(default)]
+	pub compression: Compression,
+	#[serde(default)]
+	pub assets: Assets,
+	/// Problems of optional features found at startup, these features
+	/// are disabled (see `startup::init_optional`)
+	#[serde(skip)]
+	pub startup_problems: Vec<String>,
+}
+
+impl Config {
+	/// Whether external links get the icon of the target site
+	pub fn external_link_icons(&self) -> bool {
+		self.render.external_link_icons && self.network.outbound_requests
+	}
+
+	/// Link target of `path` on this site, under `network.base_path`
+	pub fn url(&self, path: &str) -> String {
+		self.network.base_path.url(path)
+	}
+
+	/// `time` as shown on pages, in the time zone `appearance.timezone`
+	pub fn format_timestamp(&self, time: UtcTimestamp) -> String {
+		self.appearance
+			.date_format
+			.format(time, self.appearance.timezone)
+	}
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Network {
-	pub ip: IpAddr,
-	pub port: u16,
+	/// Address of the TCP listener, unless the wiki listens on `unix_socket`
+	#[serde(default)]
+	pub ip: Option<IpAddr>,
+	#[serde(default)]
+	pub port: Option<u16>,
+	/// Addresses to listen on instead of `ip` and `port`, one like
+	/// `"127.0.0.1:8080"` or a list of them
+	#[serde(default)]
+	pub listen: Option<ListenAddresses>,
+	/// Unix domain socket to listen on instead of `ip` and `port`
+	#[serde(default)]
+	pub unix_socket: Option<PathBuf>,
+	/// Permissions of the socket file, by default they follow the umask
+	#[serde(default)]
+	pub socket_mode: Option<SocketMode>,
+	/// Send a `Server` header with the wiki version in every response
+	#[serde(default)]
+	pub server_header: bool,
+	/// URL under which the wiki is publicly reachable, used for absolute links
+	#[serde(default)]
+	pub public_url: Option<String>,
+	/// Path the wiki is served under behind a reverse proxy, e.g. `/wiki`
+	#[serde(default, skip_serializing_if = "BasePath::is_root")]
+	pub base_path: BasePath,
+	/// Allow the wiki to make requests to other servers
+	#[serde(default)]
+	pub outbound_requests: bool,
+	/// PEM certificate chain, the wiki serves HTTPS with `tls_key`
+	#[serde(default)]
+	pub tls_cert: Option<PathBuf>,
+	/// PEM private key of `tls_cert`
+	#[serde(default)]
+	pub tls_key: Option<PathBuf>,
+}
+
+/// The addresses of `network.listen`, written as one string or a list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenAddresses(pub Vec<SocketAddr>);
+
+impl Serialize for ListenAddresses {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq(self.0.iter().map(|address| address.to_string()))
+	}
+}
+
+impl<'de> Deserialize<'de> for ListenAddresses {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		use serde::de::{Error, SeqAccess, Visitor};
+
+		fn parse<E: Error>(address: &str) -> Result<SocketAddr, E> {
+			address.parse().map_err(|_| {
+				E::custom(format!(
+					"invalid address {:?}, expected `ip:port` like `127.0.0.1:8080` or `[::1]:8080`",
+					address
+				))
+			})
+		}
+
+		struct AddressesVisitor;
+
+		impl<'de> Visitor<'de> for AddressesVisitor {
+			type Value = ListenAddresses;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("an address like \"127.0.0.1:8080\" or a list of them")
+			}
+
+			fn visit_str<E: Error>(self, address: &str) -> Result<ListenAddresses, E> {
+				Ok(ListenAddresses(vec![parse(address)?]))
+			}
+
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ListenAddresses, A::Error> {
+				let mut addresses = Vec::new();
+				while let Some(address) = seq.next_element::<String>()? {
+					addresses.push(parse(&address)?);
+				}
+				Ok(ListenAddresses(addresses))
+			}
+		}
+
+		deserializer.deserialize_any(AddressesVisitor)
+	}
+}
+
+/// Where the server accepts connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listener {
+	Tcp(Vec<SocketAddr>),
+	Unix {
+		path: PathBuf,
+		mode: Option<SocketMode>,
+	},
 }
 
-#[derive(Deserialize, Debug)]
+/// Error of `Network::listener` without anything to listen on
+const NO_LISTENER: &str = "`network.ip` and `network.port` are required unless the wiki listens on `network.listen` or `network.unix_socket`";
+
+impl Network {
+	/// One of `ip` and `port`, `listen` or `unix_socket`. The wiki listens
+	/// on every address of `listen`, but not on TCP and the socket.
+	pub fn listener(&self) -> Result<Listener, &'static str> {
+		if self.socket_mode.is_some() && self.unix_socket.is_none() {
+			return Err("`network.socket_mode` only goes with `network.unix_socket`");
+		}
+		let tcp = match (self.ip, self.port, &self.listen) {
+			(Some(ip), Some(port), None) => Some(vec![SocketAddr::from((ip, port))]),
+			(None, None, Some(listen)) if listen.0.is_empty() => {
+				return Err("`network.listen` has no address");
+			}
+			(None, None, Some(listen)) => Some(listen.0.clone()),
+			(None, None, None) => None,
+			(_, _, Some(_)) => {
+				return Err("`network.listen` replaces `network.ip` and `network.port`");
+			}
+			_ => return Err(NO_LISTENER),
+		};
+		match (tcp, &self.unix_socket) {
+			(Some(addresses), None) => Ok(Listener::Tcp(addresses)),
+			(None, Some(_)) if cfg!(not(unix)) => {
+				Err("`network.unix_socket` is not supported on this platform")
+			}
+			(None, Some(path)) => Ok(Listener::Unix {
+				path: path.clone(),
+				mode: self.socket_mode,
+			}),
+			(Some(_), Some(_)) => Err(
+				"`network.unix_socket` replaces `network.ip`, `network.port` and `network.listen`, the wiki listens either on TCP or on the socket",
+			),
+			(None, None) => Err(NO_LISTENER),
+		}
+	}
+
+	/// The certificate and key file if the wiki serves HTTPS, an error
+	/// if only one of them is set
+	pub fn tls_files(&self) -> Result<Option<(&Path, &Path)>, &'static str> {
+		match (&self.tls_cert, &self.tls_key) {
+			(Some(cert), Some(key)) => Ok(Some((cert, key))),
+			(None, None) => Ok(None),
+			_ => Err("`network.tls_cert` and `network.tls_key` must be set together"),
+		}
+	}
+}
+
+impl Default for Network {
+	fn default() -> Self {
+		Network {
+			ip: Some(IpAddr::from([127, 0, 0, 1])),
+			port: Some(3030),
+			listen: None,
+			unix_socket: None,
+			socket_mode: None,
+			server_header: false,
+			public_url: None,
+			base_path: BasePath::default(),
+			outbound_requests: false,
+			tls_cert: None,
+			tls_key: None,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Wiki {
+	/// Shown in the page titles and the header of the menu
+	pub name: String,
+}
+
+impl Default for Wiki {
+	fn default() -> Self {
+		Wiki {
+			name: "Redwood Wiki".to_string(),
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Database {
+	/// Directory of the database file, the directory of the executable
+	/// by default
+	#[serde(default = "default_storage_location")]
 	pub storage_location: PathBuf,
+	#[serde(default)]
+	pub dates: Dates,
+	#[serde(default)]
+	pub lock: Lock,
+	#[serde(default)]
+	pub connection: Connection,
+}
+
+impl Default for Database {
+	fn default() -> Self {
+		Database {
+			storage_location: default_storage_location(),
+			dates: Dates::default(),
+			lock: Lock::default(),
+			connection: Connection::default(),
+		}
+	}
+}
+
+impl Database {
+	/// The database file in `storage_location`
+	pub fn path(&self) -> PathBuf {
+		database_file(&self.storage_location)
+	}
+
+	/// Create `storage_location` if it does not exist yet
+	pub fn create_storage_location(&self) -> std::io::Result<()> {
+		std::fs::create_dir_all(&self.storage_location)
+	}
+}
+
+/// The directory of the executable, the working directory if it cannot
+/// be determined
+fn default_storage_location() -> PathBuf {
+	std::env::current_exe()
+		.and_then(|path| path.canonicalize())
+		.ok()
+		.and_then(|path| path.parent().map(Path::to_path_buf))
+		.unwrap_or_default()
+}
+
+/// The database file in the directory `storage_location`
+pub fn database_file(storage_location: &Path) -> PathBuf {
+	storage_location.join("wiki_db.sqlite")
+}
+
+/// Window of plausible dates, see `DateWindow`
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Dates {
+	pub earliest_year: i32,
+	pub max_future_days: u32,
+}
+
+impl Default for Dates {
+	fn default() -> Self {
+		Dates {
+			earliest_year: 2000,
+			max_future_days: 1,
+		}
+	}
+}
+
+impl Dates {
+	pub fn window(&self) -> DateWindow {
+		DateWindow::new(self.earliest_year, self.max_future_days)
+	}
 }
 
-pub fn parse_config() -> std::io::Result<Config> {
+/// Monitoring of the database lock, see `DbLock`
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Lock {
+	/// Holding the lock longer than this is logged with the caller
+	pub slow_hold_ms: u64,
+}
+
+impl Default for Lock {
+	fn default() -> Self {
+		Lock { slow_hold_ms: 100 }
+	}
+}
+
+impl Lock {
+	pub fn slow_hold(&self) -> Duration {
+		Duration::from_millis(self.slow_hold_ms)
+	}
+}
+
+/// SQLite settings of the database connection, see `ConnectionSettings`
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Connection {
+	pub journal_mode: JournalMode,
+	pub synchronous: Synchronous,
+	pub foreign_keys: bool,
+	pub busy_timeout_ms: u64,
+}
+
+impl Default for Connection {
+	fn default() -> Self {
+		let settings = ConnectionSettings::default();
+		Connection {
+			journal_mode: settings.journal_mode,
+			synchronous: settings.synchronous,
+			foreign_keys: settings.foreign_keys,
+			busy_timeout_ms: settings.busy_timeout.as_millis() as u64,
+		}
+	}
+}
+
+impl Connection {
+	pub fn settings(&self) -> ConnectionSettings {
+		ConnectionSettings {
+			journal_mode: self.journal_mode,
+			synchronous: self.synchronous,
+			foreign_keys: self.foreign_keys,
+			busy_timeout: Duration::from_millis(self.busy_timeout_ms),
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Search {
+	/// Maximum number of results shown per match category
+	pub max_results_per_category: usize,
+}
+
+impl Default for Search {
+	fn default() -> Self {
+		Search {
+			max_results_per_category: 200,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Editor {
+	/// Enable the spell checker of the browser in the article editor
+	pub spellcheck: bool,
+	/// File with words the spell checker must not flag, one per line
+	pub dictionary: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Render {
+	/// Rendering of an article stops after this many Markdown elements
+	pub max_events: usize,
+	/// Show the icon of the target site in front of external links,
+	/// requires `outbound_requests`
+	pub external_link_icons: bool,
+	/// Folder of `.sublime-syntax` files for code block languages that
+	/// syntect does not know
+	pub extra_syntaxes_dir: Option<PathBuf>,
+	/// The syntaxes, with those of `extra_syntaxes_dir` once they are
+	/// loaded by `startup::init_optional`
+	#[serde(skip)]
+	pub syntaxes: Syntaxes,
+}
+
+impl Default for Render {
+	fn default() -> Self {
+		Render {
+			max_events: 200_000,
+			external_link_icons: false,
+			extra_syntaxes_dir: None,
+			syntaxes: Syntaxes::default(),
+		}
+	}
+}
+
+/// Markdown extensions of the article text, see
+/// https://docs.rs/pulldown-cmark/latest/pulldown_cmark/struct.Options.html
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Markdown {
+	/// `[^1]` references and `[^1]: ...` definitions
+	pub footnotes: bool,
+	/// Typographic quotes and dashes for `"..."`, `--` and `---`
+	pub smart_punctuation: bool,
+	pub tables: bool,
+	/// `~~strikethrough~~`
+	pub strikethrough: bool,
+	/// `- [ ]` and `- [x]`
+	pub tasklists: bool,
+}
+
+impl Default for Markdown {
+	fn default() -> Self {
+		Markdown {
+			footnotes: false,
+			smart_punctuation: false,
+			tables: true,
+			strikethrough: true,
+			tasklists: true,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Appearance {
+	/// Stylesheet added after the built-in styles of every page
+	pub custom_css: Option<PathBuf>,
+	/// How times are shown, see `Config::format_timestamp`
+	pub date_format: DateFormat,
+	/// Time zone of the times shown and of the journal's today, the
+	/// database always stores UTC
+	pub timezone: SiteTimezone,
+	/// Colors of highlighted code
+	pub code_theme: CodeTheme,
+	/// The stylesheet of `custom_css`, first read by
+	/// `startup::init_optional`
+	#[serde(skip)]
+	pub stylesheet: CustomCss,
+	/// The CSS of `code_theme`, a file is loaded by
+	/// `startup::init_optional`
+	#[serde(skip)]
+	pub theme_css: ThemeCss,
+}
+
+impl Appearance {
+	/// The custom stylesheet, `None` if there is none or it cannot be read
+	pub fn custom_css(&self) -> Option<Stylesheet> {
+		let path = self.custom_css.as_ref()?;
+		self.stylesheet.get(path)
+	}
+
+	/// CSS for highlighted code in the colors of `code_theme`
+	pub fn code_theme_css(&self) -> &str {
+		self.theme_css.get(&self.code_theme)
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Listing {
+	/// Mark articles changed since the last visit of the article list.
+	/// The time of the visit is only stored in a cookie in the browser.
+	pub new_markers: bool,
+	/// Articles per page of the article list, unless the page is
+	/// requested with `?per_page=`
+	pub per_page: u32,
+}
+
+impl Default for Listing {
+	fn default() -> Self {
+		Listing {
+			new_markers: true,
+			per_page: 100,
+		}
+	}
+}
+
+/// The JSON API under `/api/`, see `api::routes`
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Api {
+	/// Allow `PUT /api/article/{id}/full`, which replaces an article
+	/// together with its revision history, e.g. to sync two wikis
+	pub article_sync: bool,
+}
+
+/// Largest accepted request bodies in bytes, larger requests are
+/// answered with 413
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Limits {
+	/// Forms with the text of an article (saving and creating articles)
+	pub article_body_bytes: u64,
+	/// The search form
+	pub search_body_bytes: u64,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			article_body_bytes: 1024 * 1024,
+			search_body_bytes: 4 * 1024,
+		}
+	}
+}
+
+/// How long browsers may use the bundled icons, styles and scripts
+/// without asking again, in seconds. They only change with a new build.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Assets {
+	pub image_max_age: u32,
+	pub code_max_age: u32,
+	pub document_max_age: u32,
+}
+
+impl Default for Assets {
+	fn default() -> Self {
+		Assets {
+			image_max_age: 86400,
+			code_max_age: 86400,
+			document_max_age: 86400,
+		}
+	}
+}
+
+impl Assets {
+	pub fn max_age(&self, class: AssetClass) -> u32 {
+		match class {
+			AssetClass::Image => self.image_max_age,
+			AssetClass::Code => self.code_max_age,
+			AssetClass::Document => self.document_max_age,
+		}
+	}
+}
+
+/// Brotli or gzip compression of responses, see `compression::compress`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Compression {
+	pub enabled: bool,
+	/// Smaller responses are sent uncompressed
+	pub min_size_bytes: usize,
+}
+
+impl Default for Compression {
+	fn default() -> Self {
+		Compression {
+			enabled: true,
+			min_size_bytes: 1024,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Log {
+	/// Also write the log to this file. It is reopened on `SIGUSR1` and
+	/// on `POST /admin/rotate-logs`, so it can be rotated by moving it.
+	pub file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Journal {
+	/// Format of the date in the title of a journal entry, see
+	/// https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+	pub date_format: String,
+	/// Text in front of the date in the title, e.g. `Journal `
+	pub title_prefix: String,
+	/// ID of the article whose text new journal entries start with
+	pub template_article: Option<u32>,
+}
+
+impl Default for Journal {
+	fn default() -> Self {
+		Journal {
+			date_format: "%Y-%m-%d".to_string(),
+			title_prefix: String::new(),
+			template_article: None,
+		}
+	}
+}
+
+/// Credentials for the login page and HTTP Basic authentication, see
+/// `auth::editor`
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Auth {
+	pub username: String,
+	pub password_hash: PasswordHash,
+	/// Ask for the credentials on every page, not only for editing
+	#[serde(default)]
+	pub protect_reads: bool,
+	/// Signs the session cookies, so that logins survive a restart
+	#[serde(default)]
+	pub session_secret: Option<SessionSecret>,
+	/// How long a login lasts
+	#[serde(default = "default_session_days")]
+	pub session_days: u32,
+	#[serde(skip)]
+	pub verified: Verified,
+	#[serde(skip)]
+	pub session_key: SessionKey,
+}
+
+fn default_session_days() -> u32 {
+	30
+}
+
+/// Copies of the database written by `backup::run_scheduled`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Backup {
+	pub directory: PathBuf,
+	pub interval_hours: u64,
+	/// Older backups beyond this many are deleted
+	pub keep_count: usize,
+}
+
+impl Default for Backup {
+	fn default() -> Self {
+		Backup {
+			directory: PathBuf::from("backups"),
+			interval_hours: 24,
+			keep_count: 7,
+		}
+	}
+}
+
+impl Backup {
+	pub fn interval(&self) -> Duration {
+		Duration::from_secs(self.interval_hours * 60 * 60)
+	}
+}
+
+/// Requests to other servers after an article was saved, they require
+/// `outbound_requests`. In the URLs, `{path}` is replaced by the path
+/// of the article and `{url}` by its URL-encoded canonical URL.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Hooks {
+	/// `PURGE` request, e.g. to the caching proxy in front of the wiki
+	pub purge_url: Option<String>,
+	/// `GET` request, e.g. a sitemap ping of a search engine
+	pub ping_url: Option<String>,
+	/// Attempts per request before giving up
+	pub attempts: u32,
+	/// Timeout of a single attempt in seconds
+	pub timeout: u64,
+}
+
+impl Default for Hooks {
+	fn default() -> Self {
+		Hooks {
+			purge_url: None,
+			ping_url: None,
+			attempts: 3,
+			timeout: 5,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Site {
+	/// Additional links in the sidebar menu, shown in this order
+	pub nav_links: Vec<NavLink>,
+	/// Days after renaming an article during which its page tells the
+	/// former title, 0 to never tell
+	pub rename_notice_days: u32,
+}
+
+impl Default for Site {
+	fn default() -> Self {
+		Site {
+			nav_links: Vec::new(),
+			rename_notice_days: 14,
+		}
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct NavLink {
+	pub label: String,
+	/// Absolute `http://` or `https://` URL, or a path on this site
+	/// starting with `/`
+	pub url: String,
+	/// Menu section of the link: `Navigation` (the default), `Wiki` or
+	/// any other name, which adds a section of that name to the menu
+	#[serde(default)]
+	pub section: Option<String>,
+}
+
+impl NavLink {
+	pub fn section(&self) -> &str {
+		self.section.as_deref().unwrap_or("Navigation")
+	}
+
+	/// Whether the link leads to another site
+	pub fn is_external(&self) -> bool {
+		self.url.starts_with("http://") || self.url.starts_with("https://")
+	}
+
+	/// Link target, paths are on this site under `base_path`
+	pub fn href(&self, base_path: &BasePath) -> String {
+		if self.is_external() {
+			self.url.clone()
+		} else {
+			base_path.url(&self.url)
+		}
+	}
+
+	/// Reason why the link cannot be shown, if any
+	pub fn check(&self) -> Result<(), &'static str> {
+		if self.label.trim().is_empty() {
+			return Err("the label is empty");
+		}
+		if self.section().trim().is_empty() {
+			return Err("the section name is empty");
+		}
+		let site_relative = self.url.starts_with('/') && !self.url.starts_with("//");
+		let absolute = ["http://", "https://"]
+			.iter()
+			.any(|scheme| self.url.len() > scheme.len() && self.url.starts_with(scheme));
+		if !site_relative && !absolute {
+			return Err("the URL is neither an http(s) URL nor a path starting with `/`");
+		}
+		Ok(())
+	}
+}
+
+/// Settings given on the command line, they take precedence over the
+/// configuration file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Overrides {
+	/// The configuration file, instead of `wiki-config.toml` in the
+	/// working directory or next to the executable
+	pub config: Option<PathBuf>,
+	/// Replaces `database.storage_location`
+	pub data_dir: Option<PathBuf>,
+	/// Replaces `network.ip` and `network.port`, or `network.unix_socket`
+	pub listen: Option<SocketAddr>,
+}
+
+impl Overrides {
+	/// Read the configuration file and apply the overrides
+	pub fn load(&self) -> std::io::Result<Config> {
+		let mut config = parse_config(self.config.as_deref())?;
+		self.apply(&mut config);
+		Ok(config)
+	}
+
+	fn apply(&self, config: &mut Config) {
+		if let Some(data_dir) = &self.data_dir {
+			config.database.storage_location = data_dir.clone();
+		}
+		if let Some(listen) = self.listen {
+			config.network.ip = Some(listen.ip());
+			config.network.port = Some(listen.port());
+			config.network.listen = None;
+			config.network.unix_socket = None;
+			config.network.socket_mode = None;
+		}
+	}
+}
+
+/// Read the configuration from `file`, or without one, from
+/// `wiki-config.toml` in the working directory or next to the executable
+pub fn parse_config(file: Option<&Path>) -> std::io::Result<Config> {
+	if let Some(file) = file {
+		// Unlike the files that are looked for, this one must exist
+		return match parse_config_internal(vec![file.to_path_buf()]) {
+			Err(err) if err.kind() == ErrorKind::NotFound => Err(std::io::Error::new(
+				ErrorKind::NotFound,
+				format!("{} does not exist", file.display()),
+			)),
+			result => result,
+		};
+	}
 	let mut exe_path = std::env::current_exe()?.canonicalize()?;
 	exe_path.pop();
 	exe_path.push("wiki-config.toml");
 	parse_config_internal(vec![PathBuf::from("wiki-config.toml"), exe_path])
 }
 
-fn toml_err_to_io_err(err: toml::de::Error) -> std::io::Error {
-	std::io::Error::new(ErrorKind::InvalidData, err)
+/// Parse the configuration, errors name the field that failed
+fn parse_toml(toml_content: &str) -> std::io::Result<Config> {
+	toml::from_str(toml_content).map_err(|err| toml_err_to_io_err(err, toml_content))
+}
+
+fn toml_err_to_io_err(err: toml::de::Error, toml_content: &str) -> std::io::Error {
+	let message = match err.span().map(|span| field_at(toml_content, span.start)) {
+		Some((field, line)) if !field.is_empty() => {
+			format!("`{}` (line {}): {}", field, line, err.message())
+		}
+		Some((_, line)) => format!("line {}: {}", line, err.message()),
+		None => err.to_string(),
+	};
+	std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// The dotted name of the field at `offset`, like `network.port`, and
+/// its line number. Only the table if the offset is not at a key.
+fn field_at(toml_content: &str, offset: usize) -> (String, usize) {
+	let before = toml_content.get(..offset).unwrap_or(toml_content);
+	let line_number = before.matches('\n').count() + 1;
+	let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+	let line = toml_content[line_start..]
+		.lines()
+		.next()
+		.unwrap_or_default();
+	let table = |line: &str| {
+		let line = line.trim();
+		line.strip_prefix('[')
+			.and_then(|line| line.split_once(']'))
+			.map(|(name, _)| name.trim_matches(['[', ' ']).to_string())
+	};
+	if let Some(table) = table(line) {
+		return (table, line_number);
+	}
+	let key = line
+		.split_once('=')
+		.map(|(key, _)| key.trim().trim_matches('"'))
+		.unwrap_or_default();
+	let table = toml_content[..line_start]
+		.lines()
+		.rev()
+		.find_map(table)
+		.unwrap_or_default();
+	let field = match (table.is_empty(), key.is_empty()) {
+		(false, false) => format!("{}.{}", table, key),
+		(true, _) => key.to_string(),
+		(false, true) => table,
+	};
+	(field, line_number)
 }
 
 pub fn parse_config_internal(files: Vec<PathBuf>) -> std::io::Result<Config> {
@@ -38,7 +888,10 @@ pub fn parse_config_internal(files: Vec<PathBuf>) -> std::io::Result<Config> {
 		let toml_content = std::fs::read_to_string(&file);
 		match toml_content {
 			Ok(toml_content) => {
-				let config: Config = toml::from_str(&toml_content).map_err(toml_err_to_io_err)?;
+				let mut config = parse_toml(&toml_content).map_err(|err| {
+					std::io::Error::new(err.kind(), format!("{}: {}", file.display(), err))
+				})?;
+				config.file_path = Some(file.clone());
 				log::info!("Config file: {:?}", file);
 				log::info!("Config contents: {:?}", config);
 				return Ok(config);
@@ -69,3 +922,237 @@ pub fn parse_config_internal(files: Vec<PathBuf>) -> std::io::Result<Config> {
 		  ip = "192.168.0.1"
 		  "#;
 */
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::convert::TryFrom;
+
+	#[test]
+	fn test_database_section() {
+		let config = parse_toml(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 8080
+
+			[database]
+			storage_location = "/var/lib/wiki"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(
+			config.database.path(),
+			Path::new("/var/lib/wiki/wiki_db.sqlite")
+		);
+
+		// Without the table or the field, next to the executable
+		let exe_dir = std::env::current_exe()
+			.unwrap()
+			.canonicalize()
+			.unwrap()
+			.parent()
+			.unwrap()
+			.to_path_buf();
+		let config = parse_toml(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 8080
+			"#,
+		)
+		.unwrap();
+		assert_eq!(config.database.storage_location, exe_dir);
+		assert_eq!(config.database.lock.slow_hold_ms, 100);
+		let config = parse_toml(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 8080
+
+			[database.lock]
+			slow_hold_ms = 50
+			"#,
+		)
+		.unwrap();
+		assert_eq!(config.database.storage_location, exe_dir);
+		assert_eq!(config.database.lock.slow_hold_ms, 50);
+	}
+
+	#[test]
+	fn test_timezone() {
+		let time = |text: &str| {
+			UtcTimestamp::from_naive_utc(
+				chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap(),
+			)
+		};
+		// Without the field, UTC
+		let config = parse_toml("[network]\nip = \"127.0.0.1\"\nport = 8080\n").unwrap();
+		assert_eq!(config.appearance.timezone, SiteTimezone::default());
+		assert_eq!(
+			config.format_timestamp(time("2024-03-31 00:30")),
+			"2024-03-31 00:30"
+		);
+
+		// Summer time in Zurich starts on 31 March 2024 at 01:00 UTC
+		let config = parse_toml(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 8080
+
+			[appearance]
+			timezone = "Europe/Zurich"
+			date_format = "%Y-%m-%d %H:%M %:z"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(
+			config.format_timestamp(time("2024-03-31 00:30")),
+			"2024-03-31 01:30 +01:00"
+		);
+		assert_eq!(
+			config.format_timestamp(time("2024-03-31 01:30")),
+			"2024-03-31 03:30 +02:00"
+		);
+	}
+
+	#[test]
+	fn test_errors_name_the_field() {
+		let error = |toml_content: &str| parse_toml(toml_content).unwrap_err().to_string();
+		assert_eq!(
+			error("[network]\nip = \"127.0.0.1\"\nport = \"http\"\n"),
+			"`network.port` (line 3): invalid type: string \"http\", expected u16"
+		);
+		assert_eq!(
+			error("[network]\nip = \"127.0.0.1\"\nport = 80\n\n[database]\nstorage_location = 5\n"),
+			"`database.storage_location` (line 6): invalid type: integer `5`, expected path string"
+		);
+		assert!(error("[network\n").starts_with("line 1: "));
+		assert_eq!(
+			error("[network]\nlisten = [\"127.0.0.1:80\", \"::1:80\"]\n"),
+			"`network.listen` (line 2): invalid address \"::1:80\", expected `ip:port` like `127.0.0.1:8080` or `[::1]:8080`"
+		);
+		assert_eq!(
+			error("[network]\nlisten = 8080\n"),
+			"`network.listen` (line 2): invalid type: integer `8080`, expected an address like \"127.0.0.1:8080\" or a list of them"
+		);
+		assert!(error("[appearance]\ntimezone = \"Europe/Atlantis\"\n")
+			.starts_with("`appearance.timezone` (line 2): invalid time zone \"Europe/Atlantis\""));
+		assert!(error("[appearance]\ndate_format = \"%Q\"\n")
+			.starts_with("`appearance.date_format` (line 2): invalid date format \"%Q\""));
+	}
+
+	#[test]
+	fn test_listener() {
+		let network = |toml_content: &str| {
+			parse_toml(&format!("[network]\n{}", toml_content))
+				.unwrap()
+				.network
+				.listener()
+		};
+		assert_eq!(
+			network("ip = \"::1\"\nport = 8080\n"),
+			Ok(Listener::Tcp(vec!["[::1]:8080".parse().unwrap()]))
+		);
+		assert_eq!(
+			network("listen = \"[::1]:8080\"\n"),
+			Ok(Listener::Tcp(vec!["[::1]:8080".parse().unwrap()]))
+		);
+		assert_eq!(
+			network("listen = [\"127.0.0.1:8080\", \"[::1]:8080\"]\n"),
+			Ok(Listener::Tcp(vec![
+				"127.0.0.1:8080".parse().unwrap(),
+				"[::1]:8080".parse().unwrap()
+			]))
+		);
+		#[cfg(unix)]
+		assert_eq!(
+			network("unix_socket = \"/run/wiki.sock\"\nsocket_mode = \"660\"\n"),
+			Ok(Listener::Unix {
+				path: PathBuf::from("/run/wiki.sock"),
+				mode: Some(SocketMode::try_from("660".to_string()).unwrap()),
+			})
+		);
+		assert_eq!(network(""), Err(NO_LISTENER));
+		assert_eq!(network("ip = \"127.0.0.1\"\n"), Err(NO_LISTENER));
+		assert_eq!(
+			network("listen = []\n"),
+			Err("`network.listen` has no address")
+		);
+		assert_eq!(
+			network("port = 80\nlisten = \"[::1]:8080\"\n"),
+			Err("`network.listen` replaces `network.ip` and `network.port`")
+		);
+		for tcp in [
+			"ip = \"127.0.0.1\"\nport = 80\n",
+			"listen = \"[::1]:8080\"\n",
+		] {
+			assert_eq!(
+				network(&format!("{}unix_socket = \"/run/wiki.sock\"\n", tcp)),
+				Err("`network.unix_socket` replaces `network.ip`, `network.port` and `network.listen`, the wiki listens either on TCP or on the socket")
+			);
+		}
+		assert_eq!(
+			network("ip = \"127.0.0.1\"\nport = 80\nsocket_mode = \"660\"\n"),
+			Err("`network.socket_mode` only goes with `network.unix_socket`")
+		);
+
+		// `--listen` replaces the socket
+		let overrides = Overrides {
+			listen: Some("127.0.0.1:8080".parse().unwrap()),
+			..Overrides::default()
+		};
+		let mut config = parse_toml("[network]\nunix_socket = \"/run/wiki.sock\"\n").unwrap();
+		overrides.apply(&mut config);
+		assert_eq!(
+			config.network.listener(),
+			Ok(Listener::Tcp(vec!["127.0.0.1:8080".parse().unwrap()]))
+		);
+	}
+
+	#[test]
+	fn test_tls_files() {
+		let network = |tls: &str| {
+			parse_toml(&format!(
+				"[network]\nip = \"127.0.0.1\"\nport = 443\n{}",
+				tls
+			))
+			.unwrap()
+			.network
+		};
+		assert_eq!(network("").tls_files(), Ok(None));
+		assert_eq!(
+			network("tls_cert = \"cert.pem\"\ntls_key = \"key.pem\"\n").tls_files(),
+			Ok(Some((Path::new("cert.pem"), Path::new("key.pem"))))
+		);
+		for tls in ["tls_cert = \"cert.pem\"\n", "tls_key = \"key.pem\"\n"] {
+			assert_eq!(
+				network(tls).tls_files(),
+				Err("`network.tls_cert` and `network.tls_key` must be set together")
+			);
+		}
+	}
+
+	#[test]
+	fn test_create_storage_location() {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!(
+			"redwood-wiki-test-storage-location-{}",
+			std::process::id()
+		));
+		let _ = std::fs::remove_dir_all(&dir);
+		let mut database = Database {
+			storage_location: dir.join("data/wiki"),
+			..Database::default()
+		};
+		database.create_storage_location().unwrap();
+		assert!(database.storage_location.is_dir());
+		// Existing directories are fine, files are not
+		database.create_storage_location().unwrap();
+		std::fs::write(dir.join("file"), "").unwrap();
+		database.storage_location = dir.join("file");
+		assert!(database.create_storage_location().is_err());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,282 @@
+use super::config::Config;
+use super::database::{Database, ItemId};
+use super::urls;
+
+/// How a configuration value names an article
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArticleRef {
+	Id(ItemId),
+	/// Found like `/wiki/{title}`, so former titles still work
+	Title(String),
+}
+
+/// What an `ArticleRef` leads to at the moment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+	Found(ItemId),
+	/// Found through a former title, `title` is the current one
+	Renamed {
+		id: ItemId,
+		title: String,
+	},
+	/// No such article, or it is deleted
+	Missing,
+}
+
+impl Resolution {
+	pub fn id(&self) -> Option<ItemId> {
+		match self {
+			Resolution::Found(id) | Resolution::Renamed { id, .. } => Some(*id),
+			Resolution::Missing => None,
+		}
+	}
+}
+
+/// Article reference of a configuration value
+#[derive(Debug)]
+pub struct ConfigRef {
+	/// Key of the value in the configuration file, e.g.
+	/// `journal.template_article`
+	pub key: String,
+	pub article: ArticleRef,
+	pub resolution: Resolution,
+}
+
+/// All article references of the configuration, resolved against the
+/// database. Features break silently if these articles are deleted or
+/// renamed, so the references are checked at startup and before an
+/// article is deleted.
+#[derive(Debug, Default)]
+pub struct Registry {
+	pub refs: Vec<ConfigRef>,
+}
+
+impl Registry {
+	pub fn resolve(config: &Config, db: &mut Database) -> Registry {
+		let refs = collect(config)
+			.into_iter()
+			.map(|(key, article)| {
+				let resolution = match &article {
+					ArticleRef::Id(id) => match db.get_article_title(*id) {
+						Some(_) => Resolution::Found(*id),
+						None => Resolution::Missing,
+					},
+					ArticleRef::Title(title) => match db.resolve_title(title) {
+						Ok(Some((id, false))) => Resolution::Found(id),
+						Ok(Some((id, true))) => match db.get_article_title(id) {
+							Some(title) => Resolution::Renamed { id, title },
+							None => Resolution::Missing,
+						},
+						Ok(None) | Err(_) => Resolution::Missing,
+					},
+				};
+				ConfigRef {
+					key,
+					article,
+					resolution,
+				}
+			})
+			.collect();
+		Registry { refs }
+	}
+
+	/// References that do not lead to an article, or only through a
+	/// former title, as messages naming the configuration key
+	pub fn problems(&self) -> Vec<String> {
+		self.refs
+			.iter()
+			.filter_map(|config_ref| {
+				let target = match &config_ref.article {
+					ArticleRef::Id(id) => format!("article #{}", id),
+					ArticleRef::Title(title) => format!("the article titled {:?}", title),
+				};
+				match &config_ref.resolution {
+					Resolution::Found(_) => None,
+					Resolution::Renamed { title, .. } => Some(format!(
+						"`{}` refers to {}, which was renamed to {:?}",
+						config_ref.key, target, title
+					)),
+					Resolution::Missing => Some(format!(
+						"`{}` refers to {}, which does not exist",
+						config_ref.key, target
+					)),
+				}
+			})
+			.collect()
+	}
+
+	/// References that lead to the article `id`
+	pub fn refs_to(&self, id: ItemId) -> impl Iterator<Item = &ConfigRef> {
+		self.refs
+			.iter()
+			.filter(move |config_ref| config_ref.resolution.id() == Some(id))
+	}
+}
+
+/// Article references of the configuration values with their keys
+fn collect(config: &Config) -> Vec<(String, ArticleRef)> {
+	let mut refs = Vec::new();
+	if let Some(id) = config
+		.journal
+		.as_ref()
+		.and_then(|journal| journal.template_article)
+	{
+		refs.push((
+			"journal.template_article".to_string(),
+			ArticleRef::Id(id.into()),
+		));
+	}
+	for (i, link) in config.site.nav_links.iter().enumerate() {
+		if let Some(article) = parse_path(&link.url) {
+			refs.push((format!("site.nav_links[{}].url", i), article));
+		}
+	}
+	refs
+}
+
+/// Article a path on this site leads to, `/article/{id}` (with or
+/// without slug) or `/wiki/{title}`
+fn parse_path(url: &str) -> Option<ArticleRef> {
+	if !url.starts_with('/') || url.starts_with("//") {
+		return None;
+	}
+	let path = url.split(['?', '#']).next().unwrap_or_default();
+	let path = urls::normalize_path(path);
+	if let Some(rest) = path.strip_prefix("/article/") {
+		let id = rest.split('/').next().unwrap_or_default();
+		return id.parse().ok().map(ArticleRef::Id);
+	}
+	let title = path.strip_prefix("/wiki/")?;
+	let title = percent_encoding::percent_decode_str(title).decode_utf8_lossy();
+	Some(ArticleRef::Title(title.into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::database::{Article, DatabaseConnection, OpenMode};
+	use crate::timestamp::UtcTimestamp;
+
+	fn config(extra: &str) -> Config {
+		toml::from_str(&format!(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+			{}
+			"#,
+			extra
+		))
+		.unwrap()
+	}
+
+	fn create(db: &mut Database, title: &str) -> ItemId {
+		db.create_article(&Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn test_parse_path() {
+		assert_eq!(parse_path("/article/5"), Some(ArticleRef::Id(5.into())));
+		assert_eq!(
+			parse_path("//article//5/redwood?x=1"),
+			None,
+			"protocol-relative URLs lead to other sites"
+		);
+		assert_eq!(
+			parse_path("/article/5/redwood/?x=1#top"),
+			Some(ArticleRef::Id(5.into()))
+		);
+		assert_eq!(
+			parse_path("/wiki/Coast%20redwood"),
+			Some(ArticleRef::Title("Coast redwood".to_string()))
+		);
+		assert_eq!(parse_path("/article/x"), None);
+		assert_eq!(parse_path("/articles"), None);
+		assert_eq!(parse_path("https://example.com/article/5"), None);
+	}
+
+	#[test]
+	fn test_registry() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-config-refs-{}.sqlite",
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let template = create(&mut db, "Template");
+		let sequoia = create(&mut db, "Sequoia");
+		db.update_article(sequoia, Some("Giant sequoia"), None, None, None)
+			.unwrap();
+		let config = config(&format!(
+			r#"
+			[journal]
+			template_article = {}
+
+			[[site.nav_links]]
+			label = "Template"
+			url = "/wiki/Template"
+
+			[[site.nav_links]]
+			label = "Sequoia"
+			url = "/wiki/Sequoia"
+
+			[[site.nav_links]]
+			label = "Missing"
+			url = "/article/1000/missing"
+
+			[[site.nav_links]]
+			label = "Other"
+			url = "https://example.com/wiki/Template"
+			"#,
+			template
+		));
+
+		let registry = Registry::resolve(&config, &mut db);
+		let keys: Vec<&str> = registry
+			.refs_to(template)
+			.map(|config_ref| config_ref.key.as_str())
+			.collect();
+		assert_eq!(keys, ["journal.template_article", "site.nav_links[0].url"]);
+		assert_eq!(
+			registry.problems(),
+			[
+				"`site.nav_links[1].url` refers to the article titled \"Sequoia\", which was renamed to \"Giant sequoia\"",
+				"`site.nav_links[2].url` refers to article #1000, which does not exist",
+			]
+		);
+		assert_eq!(registry.refs_to(sequoia).count(), 1);
+
+		// Deleted articles are missing
+		db.delete_article(template).unwrap();
+		let registry = Registry::resolve(&config, &mut db);
+		assert_eq!(registry.refs_to(template).count(), 0);
+		assert_eq!(
+			registry.problems()[0],
+			format!(
+				"`journal.template_article` refers to article #{}, which does not exist",
+				template
+			)
+		);
+		assert_eq!(registry.problems().len(), 4);
+
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,506 @@
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::net::{Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use super::auth::{PasswordHash, SessionSecret};
+use super::config::{Auth, Backup, Config, Journal, ListenAddresses, NavLink};
+use super::unix_socket::SocketMode;
+use super::urls::BasePath;
+
+const HEADER: &str = "\
+# Configuration of redwood-wiki, written by `redwood-wiki init-config`.
+# The values are the defaults. Settings that are commented out are off
+# or have no default, remove the `#` to use them.
+";
+
+/// A table or key of the template, with its comment
+struct Item {
+	/// Dotted path, like `network.port`
+	path: &'static str,
+	comment: &'static str,
+}
+
+const fn item(path: &'static str, comment: &'static str) -> Item {
+	Item { path, comment }
+}
+
+/// Every table and key of `Config`, in the order of the template. A
+/// table comes before its keys, subtables after them.
+const ITEMS: &[Item] = &[
+	item("network", "Address the server listens on"),
+	item("network.ip", ""),
+	item("network.port", ""),
+	item(
+		"network.listen",
+		"Listen on these addresses instead of `ip` and `port`, e.g. on IPv4 and\n\
+		 IPv6. The wiki does not start unless it can listen on all of them.",
+	),
+	item(
+		"network.unix_socket",
+		"Listen on this Unix domain socket instead, e.g. for a reverse proxy on\n\
+		 the same host. Remove `ip` and `port` then, the wiki does not listen on\n\
+		 both. The socket file is removed on shutdown.",
+	),
+	item(
+		"network.socket_mode",
+		"Permissions of the socket file in octal, by default they follow the umask",
+	),
+	item(
+		"network.server_header",
+		"Send the wiki version in the `Server` response header",
+	),
+	item(
+		"network.public_url",
+		"URL under which the wiki is reachable, used for link previews",
+	),
+	item(
+		"network.base_path",
+		"Path the wiki is served under behind a reverse proxy that forwards\n\
+		 the whole path, e.g. https://www.example.com/wiki/... with `public_url`\n\
+		 https://www.example.com/wiki",
+	),
+	item(
+		"network.outbound_requests",
+		"Allow requests to other servers, e.g. for the icons of external links",
+	),
+	item(
+		"network.tls_cert",
+		"Serve HTTPS with this PEM certificate chain and the private key in\n\
+		 `tls_key`, without a reverse proxy. Set both or neither.",
+	),
+	item(
+		"network.tls_key",
+		"PEM private key of `tls_cert`, PKCS#8 or RSA",
+	),
+	item("wiki", ""),
+	item(
+		"wiki.name",
+		"Shown in the page titles and the header of the menu",
+	),
+	item(
+		"database",
+		"Directory of the database, created if it does not exist. Without it,\n\
+		 the database is stored next to the executable.",
+	),
+	item("database.storage_location", ""),
+	item(
+		"database.dates",
+		"Dates before `earliest_year` or more than `max_future_days` ahead are\n\
+		 implausible. The wiki refuses to save while the clock of the server\n\
+		 is that wrong, and `redwood-wiki db verify-dates` finds such dates in\n\
+		 the database.",
+	),
+	item("database.dates.earliest_year", ""),
+	item("database.dates.max_future_days", ""),
+	item(
+		"database.lock",
+		"Requests are served one database access at a time. Accesses holding\n\
+		 the database longer than `slow_hold_ms` are logged as warnings with\n\
+		 the code location, the about page shows how long accesses waited and\n\
+		 held it.",
+	),
+	item("database.lock.slow_hold_ms", ""),
+	item(
+		"database.connection",
+		"SQLite settings applied when the database is opened. The write-ahead\n\
+		 log lets readers continue while an article is saved. Accesses that\n\
+		 find the database locked by another process retry for\n\
+		 `busy_timeout_ms` before answering 503 Service Unavailable.",
+	),
+	item(
+		"database.connection.journal_mode",
+		"`delete`, `truncate`, `persist`, `memory`, `wal` or `off`",
+	),
+	item(
+		"database.connection.synchronous",
+		"`off`, `normal`, `full` or `extra`",
+	),
+	item("database.connection.foreign_keys", ""),
+	item("database.connection.busy_timeout_ms", ""),
+	item("log", ""),
+	item(
+		"log.file",
+		"Also write the log to this file. After moving it away (e.g. with\n\
+		 logrotate), send SIGUSR1 or POST to /admin/rotate-logs to start a new one.",
+	),
+	item(
+		"limits",
+		"Largest accepted form submissions in bytes, larger ones are refused\n\
+		 with 413 Payload Too Large",
+	),
+	item("limits.article_body_bytes", ""),
+	item("limits.search_body_bytes", ""),
+	item(
+		"compression",
+		"Compress HTML, CSS, scripts and JSON with Brotli or gzip for clients\n\
+		 that accept it, unless they are smaller than `min_size_bytes`. Brotli\n\
+		 is preferred when a client accepts both.",
+	),
+	item("compression.enabled", ""),
+	item("compression.min_size_bytes", ""),
+	item(
+		"assets",
+		"Seconds for which browsers use the bundled icons (`image`), styles and\n\
+		 scripts (`code`) and other files (`document`) without asking again.\n\
+		 They only change with a new build of the wiki.",
+	),
+	item("assets.image_max_age", ""),
+	item("assets.code_max_age", ""),
+	item("assets.document_max_age", ""),
+	item("search", ""),
+	item(
+		"search.max_results_per_category",
+		"Maximum number of results shown per match category",
+	),
+	item("editor", ""),
+	item(
+		"editor.spellcheck",
+		"Enable the spell checker of the browser in the article editor",
+	),
+	item(
+		"editor.dictionary",
+		"Words the spell checker must not flag, one per line",
+	),
+	item("render", ""),
+	item(
+		"render.max_events",
+		"Rendering of an article stops after this many Markdown elements",
+	),
+	item(
+		"render.external_link_icons",
+		"Show the icon of the target site in front of external links,\n\
+		 requires `outbound_requests`",
+	),
+	item(
+		"render.extra_syntaxes_dir",
+		"Folder of `.sublime-syntax` files loaded at startup, for code block\n\
+		 languages the built-in syntaxes do not cover",
+	),
+	item("markdown", "Markdown extensions of the article text"),
+	item(
+		"markdown.footnotes",
+		"`[^1]` references and `[^1]: ...` definitions",
+	),
+	item(
+		"markdown.smart_punctuation",
+		"Typographic quotes and dashes for `\"...\"`, `--` and `---`",
+	),
+	item("markdown.tables", ""),
+	item("markdown.strikethrough", "`~~strikethrough~~`"),
+	item("markdown.tasklists", "`- [ ]` and `- [x]`"),
+	item("appearance", ""),
+	item(
+		"appearance.custom_css",
+		"Stylesheet added to every page after the built-in styles, served at\n\
+		 `/css/custom.css`. It is read again when it changes or on SIGHUP.",
+	),
+	item(
+		"appearance.code_theme",
+		"Colors of highlighted code: InspiredGitHub, Solarized (dark),\n\
+		 Solarized (light), base16-eighties.dark, base16-mocha.dark,\n\
+		 base16-ocean.dark, base16-ocean.light, or a `.tmTheme` file",
+	),
+	item(
+		"appearance.date_format",
+		"How times are shown, in the time zone of `timezone`, see\n\
+		 https://docs.rs/chrono/latest/chrono/format/strftime/index.html",
+	),
+	item(
+		"appearance.timezone",
+		"Time zone of the times shown and of the journal's today: a name like\n\
+		 `Europe/Zurich` that follows daylight saving time, `UTC`, `local` for\n\
+		 the time zone of the server, or a fixed offset like `+02:00`. The\n\
+		 database always stores UTC.",
+	),
+	item("listing", ""),
+	item(
+		"listing.new_markers",
+		"Mark articles changed since the last visit of the article list. The\n\
+		 time of the visit is stored in a cookie, set to false to not use it.",
+	),
+	item("listing.per_page", "Articles per page of the article list"),
+	item(
+		"journal",
+		"Daily notes at `/today`, enabled if this section is present.\n\
+		 Dates are in the time zone of `appearance.timezone`.",
+	),
+	item("journal.date_format", ""),
+	item("journal.title_prefix", ""),
+	item(
+		"journal.template_article",
+		"ID of the article new entries are created from",
+	),
+	item(
+		"backup",
+		"Backups of the database every `interval_hours`, enabled if this\n\
+		 section is present. Only the newest `keep_count` files named\n\
+		 `wiki_db-<date>.sqlite` in `directory` are kept, other files there\n\
+		 are left alone. A backup is a complete SQLite database, restore it by\n\
+		 copying it over `wiki_db.sqlite` while the wiki is stopped.",
+	),
+	item("backup.directory", ""),
+	item("backup.interval_hours", ""),
+	item("backup.keep_count", ""),
+	item(
+		"auth",
+		"Login for editing, creating, deleting, restoring and the admin pages,\n\
+		 enabled if this section is present. Log in on the login page or with\n\
+		 HTTP Basic authentication. Use it with HTTPS, the session cookie and\n\
+		 Basic authentication are sent with every request.",
+	),
+	item("auth.username", ""),
+	item(
+		"auth.password_hash",
+		"Argon2 hash (`$argon2id$...`) or bcrypt hash of the password, e.g. from\n\
+		 `htpasswd -nbB '' <password> | cut -d: -f2`",
+	),
+	item(
+		"auth.protect_reads",
+		"Ask for the credentials on every page, not only for editing",
+	),
+	item(
+		"auth.session_secret",
+		"Signs the session cookies, at least 32 characters. Without it, a\n\
+		 random key is used and a restart logs everyone out.",
+	),
+	item("auth.session_days", "Days until a login expires"),
+	item(
+		"hooks",
+		"Requests to other servers after an article was saved, they require\n\
+		 `outbound_requests`. `{path}` is replaced by the path of the article,\n\
+		 `{url}` by its URL-encoded canonical URL (requires `public_url`).\n\
+		 Only http:// and https:// URLs are supported.",
+	),
+	item("hooks.purge_url", ""),
+	item("hooks.ping_url", ""),
+	item("hooks.attempts", ""),
+	item("hooks.timeout", "Timeout of a single attempt in seconds"),
+	item("api", "The JSON API under /api/"),
+	item(
+		"api.article_sync",
+		"Allow `PUT /api/article/{id}/full`, which replaces an article with its\n\
+		 tags and whole revision history, e.g. to sync from another wiki. It\n\
+		 requires the login of `auth` if that section is present.",
+	),
+	item("site", ""),
+	item(
+		"site.rename_notice_days",
+		"Days after renaming an article during which its page shows the former\n\
+		 title, 0 to never show it",
+	),
+	item(
+		"site.nav_links",
+		"Additional links in the sidebar menu, shown in this order. The URL is\n\
+		 an http(s) URL or a path on this wiki starting with `/`. `section` is\n\
+		 `Navigation` (the default), `Wiki` or the name of a new section.",
+	),
+	item("site.nav_links.label", ""),
+	item("site.nav_links.url", ""),
+	item("site.nav_links.section", ""),
+];
+
+/// The defaults, with examples for the settings without default value.
+/// These are commented out in the template.
+fn example_config() -> Config {
+	let mut config = Config::default();
+	config.network.public_url = Some("https://www.example.com".to_string());
+	config.network.base_path = BasePath::try_from("/wiki".to_string()).expect("valid base path");
+	config.network.listen = Some(ListenAddresses(vec![
+		SocketAddr::from(([127, 0, 0, 1], 8080)),
+		SocketAddr::from((Ipv6Addr::LOCALHOST, 8080)),
+	]));
+	config.network.unix_socket = Some(PathBuf::from("/run/redwood-wiki.sock"));
+	config.network.socket_mode =
+		Some(SocketMode::try_from("660".to_string()).expect("valid socket mode"));
+	config.network.tls_cert = Some(PathBuf::from("cert.pem"));
+	config.network.tls_key = Some(PathBuf::from("key.pem"));
+	config.log.file = Some(PathBuf::from("/var/log/redwood-wiki.log"));
+	config.editor.dictionary = Some(PathBuf::from("dictionary.txt"));
+	config.render.extra_syntaxes_dir = Some(PathBuf::from("syntaxes"));
+	config.appearance.custom_css = Some(PathBuf::from("custom.css"));
+	config.journal = Some(Journal {
+		template_article: Some(1),
+		..Journal::default()
+	});
+	config.backup = Some(Backup::default());
+	config.auth = Some(Auth {
+		username: "editor".to_string(),
+		password_hash: PasswordHash::try_from(
+			"$2y$05$Rk5h0Z8s2n0cXGcWcJ9ZbuJ6tbB8Xq0bVqzJ1lQqYjJY2lJzQ4y2a".to_string(),
+		)
+		.expect("valid password hash"),
+		protect_reads: false,
+		session_secret: Some(
+			SessionSecret::try_from("change this to a long random string".to_string())
+				.expect("valid session secret"),
+		),
+		session_days: 30,
+		verified: Default::default(),
+		session_key: Default::default(),
+	});
+	config.hooks.purge_url = Some("http://127.0.0.1:6081{path}".to_string());
+	config.hooks.ping_url = Some("http://search.example.com/ping?url={url}".to_string());
+	config.site.nav_links = vec![NavLink {
+		label: "Issue tracker".to_string(),
+		url: "https://issues.example.com".to_string(),
+		section: Some("Team".to_string()),
+	}];
+	config
+}
+
+fn to_table(config: &Config) -> Table {
+	Table::try_from(config).expect("The configuration is valid TOML")
+}
+
+/// The value at `path`, the first element of arrays of tables
+fn lookup<'a>(table: &'a Table, path: &str) -> Option<&'a Value> {
+	let mut segments = path.split('.');
+	let mut value = table.get(segments.next()?)?;
+	for segment in segments {
+		value = match value {
+			Value::Array(tables) => tables.first()?.get(segment)?,
+			value => value.get(segment)?,
+		};
+	}
+	Some(value)
+}
+
+/// Whether the value is in the configuration, empty arrays of tables
+/// are not
+fn is_set(value: Option<&Value>) -> bool {
+	match value {
+		Some(Value::Array(values)) => !values.is_empty(),
+		value => value.is_some(),
+	}
+}
+
+/// A commented configuration file with every setting. With `uncomment`,
+/// the settings without default are not commented out.
+fn render(uncomment: bool) -> String {
+	let defaults = to_table(&Config::default());
+	let examples = to_table(&example_config());
+	let mut out = String::from(HEADER);
+	for item in ITEMS {
+		let value = lookup(&examples, item.path).expect("Items are settings of `Config`");
+		let prefix = if uncomment || is_set(lookup(&defaults, item.path)) {
+			""
+		} else {
+			"#"
+		};
+		let is_table_array =
+			matches!(value, Value::Array(values) if values.iter().all(Value::is_table));
+		if value.is_table() || is_table_array {
+			out.push('\n');
+		}
+		for line in item.comment.lines() {
+			out.push_str("# ");
+			out.push_str(line);
+			out.push('\n');
+		}
+		if value.is_table() {
+			out.push_str(&format!("{}[{}]\n", prefix, item.path));
+		} else if is_table_array {
+			out.push_str(&format!("{}[[{}]]\n", prefix, item.path));
+		} else {
+			let key = item.path.rsplit('.').next().unwrap_or_default();
+			out.push_str(&format!("{}{} = {}\n", prefix, key, value));
+		}
+	}
+	out
+}
+
+/// A commented `wiki-config.toml` with every setting and its default
+pub fn template() -> String {
+	render(false)
+}
+
+/// Write the template to `path`. An existing file is only replaced
+/// with `force`.
+pub fn write(path: &Path, force: bool) -> io::Result<()> {
+	let mut options = std::fs::OpenOptions::new();
+	options.write(true);
+	if force {
+		options.create(true).truncate(true);
+	} else {
+		options.create_new(true);
+	}
+	options.open(path)?.write_all(template().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Dotted paths of all tables and keys
+	fn collect_paths(table: &Table, prefix: &str, paths: &mut Vec<String>) {
+		for (key, value) in table {
+			let path = if prefix.is_empty() {
+				key.clone()
+			} else {
+				format!("{}.{}", prefix, key)
+			};
+			paths.push(path.clone());
+			match value {
+				Value::Table(table) => collect_paths(table, &path, paths),
+				Value::Array(values) => {
+					if let Some(Value::Table(table)) = values.first() {
+						collect_paths(table, &path, paths);
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+
+	#[test]
+	fn test_items_cover_config() {
+		let mut settings = Vec::new();
+		collect_paths(&to_table(&example_config()), "", &mut settings);
+		settings.sort();
+		let mut items: Vec<String> = ITEMS.iter().map(|item| item.path.to_string()).collect();
+		items.sort();
+		assert_eq!(items, settings);
+	}
+
+	#[test]
+	fn test_template_is_config() {
+		let template = template();
+		assert!(template.starts_with(HEADER));
+		assert!(template.contains("\n[network]\nip = \"127.0.0.1\"\nport = 3030\n"));
+		assert!(template.contains(
+			"\n# Daily notes at `/today`, enabled if this section is present.\n# Dates are in the time zone of `appearance.timezone`.\n#[journal]\n#date_format = "
+		));
+		assert!(template.contains("\n[log]\n# Also write"));
+		assert!(template.contains("\n#file = \"/var/log/redwood-wiki.log\"\n"));
+		assert!(template.contains("\n#[[site.nav_links]]\n#label = \"Issue tracker\"\n"));
+
+		// Read back, the template is the defaults
+		let config: Config = toml::from_str(&template).unwrap();
+		assert_eq!(to_table(&config), to_table(&Config::default()));
+		let config: Config = toml::from_str(&render(true)).unwrap();
+		assert_eq!(to_table(&config), to_table(&example_config()));
+	}
+
+	#[test]
+	fn test_write() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-config-template-{}.toml",
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		write(&path, false).unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), template());
+
+		std::fs::write(&path, "[network]").unwrap();
+		let err = write(&path, false).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "[network]");
+		write(&path, true).unwrap();
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), template());
+		std::fs::remove_file(&path).unwrap();
+	}
+}
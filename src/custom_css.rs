@@ -0,0 +1,215 @@
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Filter;
+
+use super::assets;
+use super::config::Config;
+
+/// URL path of the custom stylesheet, pages link it with `?v=<hash>`
+/// so a changed file is fetched again despite caching
+pub const PATH: &str = "/css/custom.css";
+
+/// The stylesheet of `appearance.custom_css`. It is read again when
+/// the modification time of the file changes, or on `reload` (SIGHUP).
+/// Without a readable file, the pages only have the built-in styles.
+#[derive(Default)]
+pub struct CustomCss {
+	loaded: Mutex<Option<Loaded>>,
+}
+
+struct Loaded {
+	modified: Option<SystemTime>,
+	stylesheet: Option<Stylesheet>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stylesheet {
+	pub text: Arc<str>,
+	pub hash: u64,
+}
+
+impl Stylesheet {
+	/// URL of this version of the stylesheet
+	pub fn url(&self) -> String {
+		format!("{}?v={:016x}", PATH, self.hash)
+	}
+
+	fn etag(&self) -> String {
+		format!("\"{:016x}\"", self.hash)
+	}
+}
+
+impl std::fmt::Debug for CustomCss {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+		f.debug_tuple("CustomCss")
+			.field(
+				&loaded
+					.as_ref()
+					.and_then(|loaded| loaded.stylesheet.as_ref())
+					.map(|css| css.text.len()),
+			)
+			.finish()
+	}
+}
+
+impl CustomCss {
+	/// The stylesheet in `path`, read again if the file changed
+	pub fn get(&self, path: &Path) -> Option<Stylesheet> {
+		let modified = modified(path);
+		let mut loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+		match &*loaded {
+			Some(current) if current.modified == modified => current.stylesheet.clone(),
+			_ => {
+				let stylesheet = match read(path) {
+					Ok(stylesheet) => {
+						log::info!("Loaded the custom stylesheet {}", path.display());
+						Some(stylesheet)
+					}
+					Err(err) => {
+						log::warn!(
+							"Could not read the custom stylesheet {}, the built-in styles are used: {}",
+							path.display(),
+							err
+						);
+						None
+					}
+				};
+				*loaded = Some(Loaded {
+					modified,
+					stylesheet: stylesheet.clone(),
+				});
+				stylesheet
+			}
+		}
+	}
+
+	/// Read the file again even if its modification time is the same.
+	/// If it cannot be read, the built-in styles are used until it
+	/// changes.
+	pub fn reload(&self, path: &Path) -> io::Result<()> {
+		let modified = modified(path);
+		let result = read(path);
+		*self.loaded.lock().unwrap_or_else(PoisonError::into_inner) = Some(Loaded {
+			modified,
+			stylesheet: result.as_ref().ok().cloned(),
+		});
+		result.map(|_| ())
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path)
+		.and_then(|metadata| metadata.modified())
+		.ok()
+}
+
+fn read(path: &Path) -> io::Result<Stylesheet> {
+	let text = std::fs::read_to_string(path)?;
+	Ok(Stylesheet {
+		hash: assets::fnv1a(text.as_bytes()),
+		text: text.into(),
+	})
+}
+
+/// Serves the custom stylesheet at `PATH`, like the built-in ones
+pub fn route(
+	config: Arc<Config>,
+) -> impl Filter<Extract = (Response<Body>,), Error = warp::Rejection> + Clone {
+	warp::get()
+		.and(warp::path!("css" / "custom.css"))
+		.and(warp::header::optional::<String>("if-none-match"))
+		.and_then(move |if_none_match: Option<String>| {
+			let config = config.clone();
+			async move {
+				let stylesheet = config
+					.appearance
+					.custom_css()
+					.ok_or_else(warp::reject::not_found)?;
+				Ok::<_, warp::Rejection>(response(&stylesheet, if_none_match, &config))
+			}
+		})
+}
+
+fn response(
+	stylesheet: &Stylesheet,
+	if_none_match: Option<String>,
+	config: &Config,
+) -> Response<Body> {
+	let etag = stylesheet.etag();
+	let builder = Response::builder().header(header::ETAG, &etag).header(
+		header::CACHE_CONTROL,
+		format!(
+			"public, max-age={}",
+			config.assets.max_age(assets::AssetClass::Code)
+		),
+	);
+	if if_none_match.is_some_and(|tags| assets::etag_matches(&tags, &etag)) {
+		return builder
+			.status(StatusCode::NOT_MODIFIED)
+			.body(Body::empty())
+			.unwrap();
+	}
+	builder
+		.status(StatusCode::OK)
+		.header(header::CONTENT_TYPE, "text/css; charset=utf-8")
+		.body(Body::from(stylesheet.text.to_string()))
+		.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_reread_on_change() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-custom-css-{}.css",
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		let css = CustomCss::default();
+		assert!(css.get(&path).is_none());
+
+		std::fs::write(&path, "body { color: red; }").unwrap();
+		let first = css.get(&path).unwrap();
+		assert_eq!(&*first.text, "body { color: red; }");
+		assert!(first.url().starts_with("/css/custom.css?v="));
+
+		// Same modification time, as within the resolution of the
+		// file system: only a reload notices
+		let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+		std::fs::write(&path, "body { color: blue; }").unwrap();
+		std::fs::File::options()
+			.write(true)
+			.open(&path)
+			.unwrap()
+			.set_modified(modified)
+			.unwrap();
+		assert_eq!(&*css.get(&path).unwrap().text, "body { color: red; }");
+		css.reload(&path).unwrap();
+		let second = css.get(&path).unwrap();
+		assert_eq!(&*second.text, "body { color: blue; }");
+		assert_ne!(second.url(), first.url());
+
+		// A changed modification time is enough
+		std::fs::write(&path, "body { color: green; }").unwrap();
+		std::fs::File::options()
+			.write(true)
+			.open(&path)
+			.unwrap()
+			.set_modified(modified + std::time::Duration::from_secs(1))
+			.unwrap();
+		assert_eq!(&*css.get(&path).unwrap().text, "body { color: green; }");
+
+		std::fs::remove_file(&path).unwrap();
+		assert!(css.get(&path).is_none());
+		assert!(css.reload(&path).is_err());
+	}
+}
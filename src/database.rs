@@ -1,16 +1,22 @@
 use std::path::Path;
 
-use chrono;
 use chrono::Utc;
 
+use super::favicon::Icon;
+use super::render;
+use super::slug;
+use super::sql_util;
+use super::tags;
+use super::timestamp::{DateWindow, UtcTimestamp};
+
 use std::convert::TryFrom;
 
 use rusqlite::{
 	params, types::FromSql, types::FromSqlError, types::ToSqlOutput, types::ValueRef, Connection,
-	OpenFlags, ToSql,
+	OpenFlags, ToSql, TransactionBehavior,
 };
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ItemId {
 	value: u32,
 }
@@ -29,14 +35,19 @@ impl TryFrom<i64> for ItemId {
 	}
 }
 
+/// Strict parsing of the numbers in IDs and versions: Only ASCII
+/// digits, so no sign (we don't want versions like `+1.+3.+9`) and no
+/// whitespace, and no leading zeros except for `0` itself, so every
+/// number has exactly one representation.
 fn parse_u32(in_str: &str) -> Option<u32> {
-	if in_str.starts_with("+") {
-		// Do not accept strings like `+10`
-		// We don't want versions like `+1.+3.+9`
-		None
-	} else {
-		in_str.parse::<u32>().ok()
+	if in_str.is_empty()
+		|| !in_str.bytes().all(|b| b.is_ascii_digit())
+		|| (in_str.len() > 1 && in_str.starts_with('0'))
+	{
+		return None;
 	}
+	// Only fails on overflow now
+	in_str.parse::<u32>().ok()
 }
 
 impl std::str::FromStr for ItemId {
@@ -67,29 +78,572 @@ impl ToSql for ItemId {
 	}
 }
 
-#[derive(Debug)]
+impl serde::Serialize for ItemId {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u32(self.value)
+	}
+}
+
+#[derive(Debug, serde::Serialize)]
+#[allow(dead_code)]
 pub struct Article {
 	pub id: ItemId,
 	pub title: String,
 	pub text: String,
-	pub date_created: chrono::NaiveDateTime,
-	pub date_modified: chrono::NaiveDateTime,
+	pub date_created: UtcTimestamp,
+	pub date_modified: UtcTimestamp,
+	pub revision: i64,
+	/// Cosmetic part of the article URL, generated from the title
+	/// on creation and only changed on explicit request
+	pub slug: String,
+}
+
+/// Article without its text, for lists of articles
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct ArticleSummary {
+	pub id: ItemId,
+	pub title: String,
+	pub date_created: UtcTimestamp,
+	pub date_modified: UtcTimestamp,
+	pub slug: String,
+}
+
+/// A saved edit of an article, or its creation as revision 0, see
+/// `Database::get_revisions`
+#[derive(Debug)]
+pub struct Revision {
+	pub revision: i64,
+	pub date_created: UtcTimestamp,
+	/// Empty if the editor gave none
+	pub summary: String,
+	/// If the edit changed the title
+	pub title_change: Option<TitleChange>,
+	/// Size of the text in bytes, `None` for revisions saved before the
+	/// texts were kept
+	pub size: Option<i64>,
+}
+
+/// Kind of an edge between two articles in `article_link`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+	/// An `[article:N]` reference
+	ArticleRef,
+}
+
+impl LinkKind {
+	fn as_sql(self) -> &'static str {
+		match self {
+			LinkKind::ArticleRef => "article_ref",
+		}
+	}
+}
+
+impl FromSql for LinkKind {
+	fn column_result(val_ref: ValueRef<'_>) -> Result<Self, FromSqlError> {
+		match val_ref.as_str()? {
+			"article_ref" => Ok(LinkKind::ArticleRef),
+			_ => Err(FromSqlError::InvalidType),
+		}
+	}
+}
+
+/// An edge of the link graph: `src` links to `dst`, which may not
+/// exist (yet)
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ArticleLink {
+	#[serde(rename = "from")]
+	pub src: ItemId,
+	#[serde(rename = "to")]
+	pub dst: ItemId,
+	pub kind: LinkKind,
+}
+
+/// The edges of one article, see `Database::get_article_links`
+#[derive(Debug, serde::Serialize)]
+pub struct ArticleLinks {
+	/// Ordered by target
+	pub outgoing: Vec<ArticleLink>,
+	/// Ordered by source, without deleted sources
+	pub incoming: Vec<ArticleLink>,
+}
+
+/// Version of the document written by `Database::export_all`, raised
+/// whenever the format changes incompatibly
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// An article with everything stored about it, one entry of the
+/// `articles` of an export, see also `Database::export_article`
+#[derive(serde::Serialize)]
+pub struct ExportedArticle {
+	#[serde(flatten)]
+	pub article: Article,
+	pub tags: Vec<String>,
+	/// Oldest first
+	pub revisions: Vec<ExportedRevision>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ExportedRevision {
 	pub revision: i64,
+	pub date_created: UtcTimestamp,
+	pub summary: String,
+	pub old_title: Option<String>,
+	pub new_title: Option<String>,
+	/// `None` for revisions saved before the texts were kept
+	pub text: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+	Database(DbError),
+	Write(std::io::Error),
+}
+
+impl From<rusqlite::Error> for ExportError {
+	fn from(err: rusqlite::Error) -> ExportError {
+		ExportError::Database(DbError::from(err))
+	}
+}
+
+impl From<std::io::Error> for ExportError {
+	fn from(err: std::io::Error) -> ExportError {
+		ExportError::Write(err)
+	}
+}
+
+impl From<serde_json::Error> for ExportError {
+	fn from(err: serde_json::Error) -> ExportError {
+		ExportError::Write(err.into())
+	}
+}
+
+impl std::fmt::Display for ExportError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ExportError::Database(err) => write!(f, "could not read the database: {}", err),
+			ExportError::Write(err) => write!(f, "could not write the export: {}", err),
+		}
+	}
+}
+
+impl std::error::Error for ExportError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			ExportError::Database(err) => Some(err),
+			ExportError::Write(err) => Some(err),
+		}
+	}
+}
+
+/// What `Database::import` does with an article whose title is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportConflict {
+	/// Keep the existing article, leave out the imported one
+	Skip,
+	/// Replace the existing article, its ID and slug stay
+	Overwrite,
+	/// Import the article as `Title (2)`, `Title (3)`, ...
+	Rename,
+}
+
+/// Outcome of `Database::import`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+	pub imported: u32,
+	/// Titles that were taken, with `ImportConflict::Skip`
+	pub skipped: u32,
+	/// Articles that could not be stored, see the log
+	pub failed: u32,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+	/// The document is no export or damaged. The articles before the
+	/// damage were imported.
+	Damaged {
+		err: serde_json::Error,
+		partial: ImportSummary,
+	},
+	/// Written by a different version of `Database::export_all`
+	UnsupportedFormat(u32),
+	/// Exported from a database with a newer layout than this build
+	/// supports
+	UnsupportedLayout {
+		found: WikiSemVer,
+		supported: WikiSemVer,
+	},
+}
+
+impl std::fmt::Display for ImportError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ImportError::Damaged { err, .. } => write!(f, "the document is damaged: {}", err),
+			ImportError::UnsupportedFormat(version) => write!(
+				f,
+				"the export format {} is not supported, this build reads format {}",
+				version, EXPORT_FORMAT_VERSION
+			),
+			ImportError::UnsupportedLayout { found, supported } => write!(
+				f,
+				"the export was made by a newer version of redwood-wiki (layout {}), this build supports {}",
+				found, supported
+			),
+		}
+	}
+}
+
+impl std::error::Error for ImportError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			ImportError::Damaged { err, .. } => Some(err),
+			_ => None,
+		}
+	}
+}
+
+/// An entry of the `articles` of an export, or the body of
+/// `Database::replace_article`
+#[derive(serde::Deserialize)]
+pub struct ImportedArticle {
+	id: Option<u32>,
+	title: String,
+	text: String,
+	date_created: UtcTimestamp,
+	date_modified: UtcTimestamp,
+	revision: i64,
+	#[serde(default)]
+	slug: String,
+	#[serde(default)]
+	tags: Vec<String>,
+	#[serde(default)]
+	revisions: Vec<ImportedRevision>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportedRevision {
+	revision: i64,
+	date_created: UtcTimestamp,
+	#[serde(default)]
+	summary: String,
+	old_title: Option<String>,
+	new_title: Option<String>,
+	text: Option<String>,
+}
+
+/// The first problem `ImportedArticle::check_history` found
+#[derive(Debug, PartialEq, Eq)]
+pub struct HistoryError {
+	/// Position in `revisions`, `None` if the article does not match
+	/// the history as a whole
+	pub index: Option<usize>,
+	pub problem: &'static str,
+}
+
+impl ImportedArticle {
+	/// Read an article as JSON lines: the article with its tags but
+	/// without `revisions` on the first line, then one revision per
+	/// line, oldest first
+	pub fn from_json_lines(text: &str) -> serde_json::Result<ImportedArticle> {
+		let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+		let mut article: ImportedArticle = serde_json::from_str(lines.next().unwrap_or_default())?;
+		for line in lines {
+			article.revisions.push(serde_json::from_str(line)?);
+		}
+		Ok(article)
+	}
+
+	/// Check that the revisions could have been saved one after the
+	/// other: the numbers increase and the dates do not decrease, all
+	/// within the dates of the article, and the last revision is the
+	/// current one. An empty history is consistent, it is replaced by
+	/// the current revision.
+	pub fn check_history(&self) -> Result<(), HistoryError> {
+		let at = |index, problem| {
+			Err(HistoryError {
+				index: Some(index),
+				problem,
+			})
+		};
+		let mut previous: Option<&ImportedRevision> = None;
+		for (index, revision) in self.revisions.iter().enumerate() {
+			if revision.revision < 0 {
+				return at(index, "revision numbers must not be negative");
+			}
+			if revision.date_created < self.date_created {
+				return at(index, "revision is older than the article");
+			}
+			if revision.date_created > self.date_modified {
+				return at(
+					index,
+					"revision is newer than the last change of the article",
+				);
+			}
+			if let Some(previous) = previous {
+				if revision.revision <= previous.revision {
+					return at(index, "revision numbers must increase");
+				}
+				if revision.date_created < previous.date_created {
+					return at(index, "revision is older than the one before");
+				}
+			}
+			previous = Some(revision);
+		}
+		let whole = |problem| {
+			Err(HistoryError {
+				index: None,
+				problem,
+			})
+		};
+		if self.date_modified < self.date_created {
+			return whole("the article was changed before it was created");
+		}
+		match previous {
+			Some(last) if last.revision != self.revision => {
+				whole("the last revision must be the revision of the article")
+			}
+			Some(last) if last.date_created != self.date_modified => {
+				whole("the last revision must be dated like the last change of the article")
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Reads an export while importing it, one article at a time
+struct Importer<'a, L> {
+	db: &'a mut Database,
+	on_conflict: ImportConflict,
+	links: L,
+	summary: ImportSummary,
+	/// Why the document was refused, the error of the deserializer
+	/// only says that it was
+	refused: Option<ImportError>,
+}
+
+impl<'de, L: Fn(&str) -> Vec<ItemId>> serde::de::DeserializeSeed<'de> for &mut Importer<'_, L> {
+	type Value = ();
+
+	fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		deserializer.deserialize_map(self)
+	}
+}
+
+impl<'de, L: Fn(&str) -> Vec<ItemId>> serde::de::Visitor<'de> for &mut Importer<'_, L> {
+	type Value = ();
+
+	fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "a wiki export")
+	}
+
+	fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+		use serde::de::Error;
+
+		let mut format_version = None;
+		while let Some(key) = map.next_key::<String>()? {
+			match key.as_str() {
+				"format_version" => {
+					let version = map.next_value()?;
+					if version != EXPORT_FORMAT_VERSION {
+						self.refused = Some(ImportError::UnsupportedFormat(version));
+						return Err(A::Error::custom("unsupported format"));
+					}
+					format_version = Some(version);
+				}
+				"layout_version" => {
+					let version: String = map.next_value()?;
+					let found: WikiSemVer = version.parse().map_err(|_| {
+						A::Error::custom(format!("invalid layout_version {:?}", version))
+					})?;
+					if found > LAYOUT_VERSION {
+						self.refused = Some(ImportError::UnsupportedLayout {
+							found,
+							supported: LAYOUT_VERSION,
+						});
+						return Err(A::Error::custom("unsupported layout"));
+					}
+				}
+				"articles" => {
+					if format_version.is_none() {
+						return Err(A::Error::custom(
+							"`format_version` must come before `articles`",
+						));
+					}
+					map.next_value_seed(ImportedArticles(&mut *self))?;
+				}
+				_ => {
+					map.next_value::<serde::de::IgnoredAny>()?;
+				}
+			}
+		}
+		match format_version {
+			Some(_) => Ok(()),
+			None => Err(A::Error::missing_field("format_version")),
+		}
+	}
+}
+
+struct ImportedArticles<'i, 'a, L>(&'i mut Importer<'a, L>);
+
+impl<'de, L: Fn(&str) -> Vec<ItemId>> serde::de::DeserializeSeed<'de>
+	for ImportedArticles<'_, '_, L>
+{
+	type Value = ();
+
+	fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		deserializer.deserialize_seq(self)
+	}
+}
+
+impl<'de, L: Fn(&str) -> Vec<ItemId>> serde::de::Visitor<'de> for ImportedArticles<'_, '_, L> {
+	type Value = ();
+
+	fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "a list of articles")
+	}
+
+	fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+		let importer = self.0;
+		while let Some(article) = seq.next_element::<ImportedArticle>()? {
+			let title = article.title.clone();
+			match importer
+				.db
+				.import_article(article, importer.on_conflict, &importer.links)
+			{
+				Ok(Some(_)) => importer.summary.imported += 1,
+				Ok(None) => importer.summary.skipped += 1,
+				Err(err) => {
+					log::error!("Could not import article {:?}: {}", title, err);
+					importer.summary.failed += 1;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Title of an article before and after an edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleChange {
+	pub old_title: String,
+	pub new_title: String,
+}
+
+/// A stored date outside of the plausible dates or not readable at all,
+/// see `Database::find_bad_dates`
+#[derive(Debug, PartialEq, Eq)]
+pub struct BadDate {
+	pub table: &'static str,
+	/// The row, like the ID of the article
+	pub key: String,
+	pub column: &'static str,
+	/// As stored
+	pub value: String,
+}
+
+/// Columns with the dates of the content, as table, SQL expression of
+/// the key of a row and column. Dates of the bookkeeping (layout,
+/// favicon cache) are left out.
+const DATE_COLUMNS: &[(&str, &str, &str)] = &[
+	("article", "id", "date_created"),
+	("article", "id", "date_modified"),
+	("article", "id", "date_deleted"),
+	(
+		"article_revision",
+		"article_id || ' revision ' || revision",
+		"date_created",
+	),
+	("title_redirect", "old_title", "date_created"),
+];
+
+/// What the URL of an article is built from, see `Database::get_article_locations`
+#[derive(Debug)]
+pub struct ArticleLocation {
+	pub id: ItemId,
+	pub slug: String,
+	pub date_modified: UtcTimestamp,
+}
+
+/// See `Database::get_stats`
+#[derive(Debug)]
+pub struct DatabaseStats {
+	pub articles: u64,
+	/// In the trash
+	pub deleted_articles: u64,
+	/// Size of all article texts in UTF-8, including the trash
+	pub text_bytes: u64,
+	/// Distinct tags
+	pub tags: u64,
+	pub categories: u64,
+	pub links: u64,
+	pub title_redirects: u64,
+	pub favicons: u64,
+	pub page_size: u64,
+	pub pages: u64,
+	/// Unused pages, `VACUUM` gives them back
+	pub free_pages: u64,
+}
+
+/// Order of `Database::get_articles_page`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArticleOrder {
+	/// Oldest article first
+	Id,
+	/// Alphabetical, ignoring ASCII case
+	Title,
+	/// Most recently changed first
+	Modified,
+}
+
+impl ArticleOrder {
+	fn sql(self) -> &'static str {
+		// The ID makes the order total, so pages do not overlap
+		match self {
+			ArticleOrder::Id => "id",
+			ArticleOrder::Title => "title COLLATE NOCASE, id",
+			ArticleOrder::Modified => "date_modified DESC, id",
+		}
+	}
+}
+
+/// Article found by the full-text search
+#[derive(Debug)]
+pub struct SearchHit {
+	pub article: Article,
+	/// BM25 score, lower is more relevant
+	pub rank: f64,
+}
+
+/// Node in the tree of categories
+#[derive(Debug)]
+pub struct Category {
+	pub id: ItemId,
+	pub name: String,
+	/// `None` for top-level categories
+	pub parent: Option<ItemId>,
 }
 
-#[derive(Debug, std::cmp::PartialEq)]
+#[derive(
+	Debug, Copy, Clone, std::cmp::PartialEq, std::cmp::Eq, std::cmp::PartialOrd, std::cmp::Ord,
+)]
 pub struct WikiSemVer {
 	major: u32,
 	minor: u32,
 	patch: u32,
 }
 
+impl std::fmt::Display for WikiSemVer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+		write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+	}
+}
+
 impl ToSql for WikiSemVer {
 	fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
-		Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(format!(
-			"{}.{}.{}",
-			self.major, self.minor, self.patch
-		))))
+		Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(
+			self.to_string(),
+		)))
 	}
 }
 
@@ -97,7 +651,14 @@ impl FromSql for WikiSemVer {
 	fn column_result(
 		val_ref: rusqlite::types::ValueRef<'_>,
 	) -> rusqlite::types::FromSqlResult<Self> {
-		let version_str = val_ref.as_str()?;
+		val_ref.as_str()?.parse()
+	}
+}
+
+impl std::str::FromStr for WikiSemVer {
+	type Err = rusqlite::types::FromSqlError;
+
+	fn from_str(version_str: &str) -> Result<Self, Self::Err> {
 		let mut version_iter = version_str.split('.');
 
 		match (
@@ -125,13 +686,625 @@ pub struct TableLayout {
 	id: ItemId,
 	version: WikiSemVer,
 	migrating_to_version: Option<WikiSemVer>,
-	date_created: chrono::NaiveDateTime,
-	date_migration_begin: Option<chrono::NaiveDateTime>,
-	date_migration_complete: Option<chrono::NaiveDateTime>,
+	date_created: UtcTimestamp,
+	date_migration_begin: Option<UtcTimestamp>,
+	date_migration_complete: Option<UtcTimestamp>,
+}
+
+impl TableLayout {
+	pub fn version(&self) -> WikiSemVer {
+		self.version
+	}
+}
+
+/// The table layout version this build of redwood-wiki operates on
+const LAYOUT_VERSION: WikiSemVer = WikiSemVer {
+	major: 0,
+	minor: 14,
+	patch: 0,
+};
+
+/// One step in the chain of database migrations. `apply` runs inside
+/// the migration transaction and must only touch the schema and data,
+/// the `table_layout` bookkeeping is done by the migration runner.
+pub struct Migration {
+	from: WikiSemVer,
+	to: WikiSemVer,
+	apply: fn(&rusqlite::Transaction) -> rusqlite::Result<()>,
+}
+
+/// All known migrations, ordered from oldest to newest layout version
+const MIGRATIONS: &[Migration] = &[
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 1,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 2,
+			patch: 0,
+		},
+		apply: migrate_0_1_0_to_0_2_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 2,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 3,
+			patch: 0,
+		},
+		apply: migrate_0_2_0_to_0_3_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 3,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 4,
+			patch: 0,
+		},
+		apply: migrate_0_3_0_to_0_4_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 4,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 5,
+			patch: 0,
+		},
+		apply: migrate_0_4_0_to_0_5_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 5,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 6,
+			patch: 0,
+		},
+		apply: migrate_0_5_0_to_0_6_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 6,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 7,
+			patch: 0,
+		},
+		apply: migrate_0_6_0_to_0_7_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 7,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 8,
+			patch: 0,
+		},
+		apply: migrate_0_7_0_to_0_8_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 8,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 9,
+			patch: 0,
+		},
+		apply: migrate_0_8_0_to_0_9_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 9,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 10,
+			patch: 0,
+		},
+		apply: migrate_0_9_0_to_0_10_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 10,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 11,
+			patch: 0,
+		},
+		apply: migrate_0_10_0_to_0_11_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 11,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 12,
+			patch: 0,
+		},
+		apply: migrate_0_11_0_to_0_12_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 12,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 13,
+			patch: 0,
+		},
+		apply: migrate_0_12_0_to_0_13_0,
+	},
+	Migration {
+		from: WikiSemVer {
+			major: 0,
+			minor: 13,
+			patch: 0,
+		},
+		to: WikiSemVer {
+			major: 0,
+			minor: 14,
+			patch: 0,
+		},
+		apply: migrate_0_13_0_to_0_14_0,
+	},
+];
+
+/// Cache of the icons of external sites, keyed by host name.
+/// Failed fetches are cached as well, with `data` set to NULL.
+const CREATE_FAVICON_TABLE: &str = "CREATE TABLE favicon (
+	host          TEXT PRIMARY KEY,
+	content_type  TEXT,
+	data          BLOB,
+	date_fetched  DATETIME NOT NULL
+)";
+
+/// Former titles of renamed articles. A redirect points to the article
+/// itself, not to its new title, so following one is always a single
+/// hop and redirects cannot form chains or cycles.
+const CREATE_TITLE_REDIRECT_TABLE: &str = "CREATE TABLE title_redirect (
+	old_title     TEXT PRIMARY KEY,
+	article_id    INTEGER NOT NULL,
+	date_created  DATETIME NOT NULL
+)";
+
+/// Tags of articles, stored normalized (see `tags::normalize`). A tag
+/// exists as long as an article has it, there is no separate list of
+/// tags that would need to be cleaned up.
+const CREATE_ARTICLE_TAG_TABLE: &str = "CREATE TABLE article_tag (
+	article_id    INTEGER NOT NULL,
+	tag           TEXT NOT NULL,
+	PRIMARY KEY (article_id, tag)
+)";
+
+/// Finds the articles with a tag
+const CREATE_ARTICLE_TAG_INDEX: &str = "CREATE INDEX article_tag_tag ON article_tag (tag)";
+
+/// Links between articles, `kind` is a `LinkKind`. The links of an
+/// article are replaced whenever it is saved (see
+/// `Database::set_article_links`). The target may not exist (yet).
+const CREATE_ARTICLE_LINK_TABLE: &str = "CREATE TABLE article_link (
+	src           INTEGER NOT NULL,
+	dst           INTEGER NOT NULL,
+	kind          TEXT NOT NULL,
+	PRIMARY KEY (src, dst, kind)
+)";
+
+/// Finds the articles linking to an article
+const CREATE_ARTICLE_LINK_INDEX: &str = "CREATE INDEX article_link_dst ON article_link (dst)";
+
+/// One row per saved edit of an article, with the summary of the
+/// changes given by the editor (empty if none). `revision` is the
+/// revision of the article after the edit and `text` its text, `NULL`
+/// for the edits before layout 0.12.0 except the current revision.
+/// `old_title` and `new_title` are only set if the edit changed the
+/// title.
+const CREATE_ARTICLE_REVISION_TABLE: &str = "CREATE TABLE article_revision (
+	article_id    INTEGER NOT NULL,
+	revision      INTEGER NOT NULL,
+	date_created  DATETIME NOT NULL,
+	summary       TEXT NOT NULL DEFAULT '',
+	old_title     TEXT,
+	new_title     TEXT,
+	text          TEXT,
+	PRIMARY KEY (article_id, revision)
+)";
+
+/// Titles are unique regardless of the case of ASCII letters, like
+/// the lookup in `Database::get_article_id_by_title`. Databases from
+/// before layout 0.13.0 may lack the index, see `normalize_titles`.
+const CREATE_ARTICLE_TITLE_INDEX: &str =
+	"CREATE UNIQUE INDEX article_title_nocase ON article (title COLLATE NOCASE)";
+
+/// Most characters of an edit summary, longer ones are cut off
+pub const MAX_EDIT_SUMMARY_LENGTH: usize = 200;
+
+/// Tree of categories. `move_category` keeps cycles out of the parent
+/// chains.
+const CREATE_CATEGORY_TABLE: &str = "CREATE TABLE category (
+	id            INTEGER PRIMARY KEY AUTOINCREMENT,
+	name          TEXT NOT NULL,
+	parent_id     INTEGER REFERENCES category (id)
+)";
+
+/// The category of an article, an article is in at most one category
+const CREATE_ARTICLE_CATEGORY_TABLE: &str = "CREATE TABLE article_category (
+	article_id    INTEGER PRIMARY KEY,
+	category_id   INTEGER NOT NULL
+)";
+
+/// Finds the articles in a category
+const CREATE_ARTICLE_CATEGORY_INDEX: &str =
+	"CREATE INDEX article_category_category ON article_category (category_id)";
+
+/// Categories from the given one up to the top level, with their
+/// distance from it. The depth limit ends the recursion even if the
+/// parent chains of the database contain a cycle.
+const CATEGORY_ANCESTORS: &str = "WITH RECURSIVE ancestor (id, name, parent_id, depth) AS (
+		SELECT id, name, parent_id, 0 FROM category WHERE id = ?
+		UNION ALL
+		SELECT category.id, category.name, category.parent_id, ancestor.depth + 1
+			FROM category JOIN ancestor ON category.id = ancestor.parent_id
+			WHERE ancestor.depth < 1000
+	)";
+
+/// Full-text index of the titles and texts of the articles. It only
+/// stores the index, the text is read from the `article` table, and
+/// the triggers keep it up to date on every change of an article.
+const CREATE_ARTICLE_FTS: &str = "
+	CREATE VIRTUAL TABLE article_fts USING fts5(
+		title,
+		text,
+		content = 'article',
+		content_rowid = 'id'
+	);
+	CREATE TRIGGER article_fts_insert AFTER INSERT ON article BEGIN
+		INSERT INTO article_fts (rowid, title, text) VALUES (new.id, new.title, new.text);
+	END;
+	CREATE TRIGGER article_fts_delete AFTER DELETE ON article BEGIN
+		INSERT INTO article_fts (article_fts, rowid, title, text) VALUES ('delete', old.id, old.title, old.text);
+	END;
+	CREATE TRIGGER article_fts_update AFTER UPDATE OF title, text ON article BEGIN
+		INSERT INTO article_fts (article_fts, rowid, title, text) VALUES ('delete', old.id, old.title, old.text);
+		INSERT INTO article_fts (rowid, title, text) VALUES (new.id, new.title, new.text);
+	END;
+";
+
+/// Create the full-text index. Without FTS5 support in SQLite, the
+/// wiki keeps working with the slower `LIKE` search (see
+/// `Database::has_fts`).
+fn create_article_fts(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+	match conn.execute_batch(CREATE_ARTICLE_FTS) {
+		Err(err) if err.to_string().contains("no such module: fts5") => {
+			log::warn!("SQLite lacks FTS5, the search falls back to LIKE");
+			Ok(())
+		}
+		result => result,
+	}
+}
+
+/// Add the `slug` column and backfill it for existing articles.
+/// Articles are processed in the order of their IDs, so on
+/// colliding slugs the older article gets the plain slug.
+fn migrate_0_1_0_to_0_2_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"ALTER TABLE article ADD COLUMN slug TEXT NOT NULL DEFAULT ''",
+		params![],
+	)?;
+	let articles = {
+		let mut stmt = tx.prepare("SELECT id, title FROM article ORDER BY id")?;
+		let rows = stmt.query_map(params![], |row| {
+			Ok((row.get::<_, ItemId>(0)?, row.get::<_, String>(1)?))
+		})?;
+		rows.collect::<rusqlite::Result<Vec<_>>>()?
+	};
+	for (id, title) in articles {
+		let slug = unique_slug(tx, &title, Some(id))?;
+		tx.execute(
+			"UPDATE article SET slug = ? WHERE id = ?",
+			params![slug, id],
+		)?;
+	}
+	tx.execute(
+		"CREATE UNIQUE INDEX article_slug ON article (slug)",
+		params![],
+	)?;
+	Ok(())
+}
+
+/// Add the `favicon` cache table
+fn migrate_0_2_0_to_0_3_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(CREATE_FAVICON_TABLE, params![])?;
+	Ok(())
+}
+
+/// Add the `date_deleted` column for soft deletion
+fn migrate_0_3_0_to_0_4_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"ALTER TABLE article ADD COLUMN date_deleted DATETIME",
+		params![],
+	)?;
+	Ok(())
+}
+
+/// Add the `title_redirect` table. Renames before this version were
+/// not recorded, so it starts out empty.
+fn migrate_0_4_0_to_0_5_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(CREATE_TITLE_REDIRECT_TABLE, params![])?;
+	Ok(())
+}
+
+/// Add the `article_tag` table
+fn migrate_0_5_0_to_0_6_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(CREATE_ARTICLE_TAG_TABLE, params![])?;
+	tx.execute(CREATE_ARTICLE_TAG_INDEX, params![])?;
+	Ok(())
+}
+
+/// Add the `category` and `article_category` tables
+fn migrate_0_6_0_to_0_7_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(CREATE_CATEGORY_TABLE, params![])?;
+	tx.execute(CREATE_ARTICLE_CATEGORY_TABLE, params![])?;
+	tx.execute(CREATE_ARTICLE_CATEGORY_INDEX, params![])?;
+	Ok(())
+}
+
+/// Add the full-text index and fill it with the existing articles
+fn migrate_0_7_0_to_0_8_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	create_article_fts(tx)?;
+	if has_table(tx, "article_fts")? {
+		tx.execute(
+			"INSERT INTO article_fts (article_fts) VALUES ('rebuild')",
+			params![],
+		)?;
+	}
+	Ok(())
+}
+
+/// Add the `article_link` table and fill it from the texts of the
+/// existing articles
+fn migrate_0_8_0_to_0_9_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"CREATE TABLE article_link (
+			src           INTEGER NOT NULL,
+			dst           INTEGER NOT NULL,
+			PRIMARY KEY (src, dst)
+		)",
+		params![],
+	)?;
+	tx.execute(CREATE_ARTICLE_LINK_INDEX, params![])?;
+	let articles: Vec<(ItemId, String)> = tx
+		.prepare("SELECT id, text FROM article")?
+		.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+		.collect::<rusqlite::Result<_>>()?;
+	let mut insert = tx.prepare("INSERT OR IGNORE INTO article_link (src, dst) VALUES (?, ?)")?;
+	for (id, text) in articles {
+		for dst in render::article_refs(&text, render::RenderOptions::default()) {
+			if dst != id {
+				insert.execute(params![id, dst])?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Add the `article_revision` table. Edits before the migration have
+/// no rows.
+fn migrate_0_9_0_to_0_10_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"CREATE TABLE article_revision (
+			article_id    INTEGER NOT NULL,
+			revision      INTEGER NOT NULL,
+			date_created  DATETIME NOT NULL,
+			summary       TEXT NOT NULL DEFAULT '',
+			PRIMARY KEY (article_id, revision)
+		)",
+		params![],
+	)?;
+	Ok(())
+}
+
+/// Add the title changes to the revisions. Renames before the
+/// migration are not known.
+fn migrate_0_10_0_to_0_11_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"ALTER TABLE article_revision ADD COLUMN old_title TEXT",
+		params![],
+	)?;
+	tx.execute(
+		"ALTER TABLE article_revision ADD COLUMN new_title TEXT",
+		params![],
+	)?;
+	Ok(())
+}
+
+/// Keep the text of every revision. Of the edits before the migration
+/// only the text of the current revision is known.
+fn migrate_0_11_0_to_0_12_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"ALTER TABLE article_revision ADD COLUMN text TEXT",
+		params![],
+	)?;
+	tx.execute(
+		"UPDATE article_revision SET text = (SELECT text FROM article
+			WHERE article.id = article_revision.article_id AND article.revision = article_revision.revision)",
+		params![],
+	)?;
+	Ok(())
+}
+
+/// Normalize the titles of the existing articles and add the index
+/// that keeps titles unique regardless of case
+fn migrate_0_12_0_to_0_13_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	normalize_titles(tx)?;
+	Ok(())
+}
+
+/// Add the kind of the links. The key of `article_link` changes, so
+/// the table is copied, all existing links are `[article:N]`
+/// references.
+fn migrate_0_13_0_to_0_14_0(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+	tx.execute(
+		"ALTER TABLE article_link RENAME TO article_link_old",
+		params![],
+	)?;
+	tx.execute("DROP INDEX article_link_dst", params![])?;
+	tx.execute(CREATE_ARTICLE_LINK_TABLE, params![])?;
+	tx.execute(CREATE_ARTICLE_LINK_INDEX, params![])?;
+	tx.execute(
+		"INSERT INTO article_link (src, dst, kind) SELECT src, dst, ? FROM article_link_old",
+		params![LinkKind::ArticleRef.as_sql()],
+	)?;
+	tx.execute("DROP TABLE article_link_old", params![])?;
+	Ok(())
+}
+
+/// Normalize the stored titles like new ones, see
+/// `Database::normalize_title`, and create the case-insensitive title
+/// index. Titles that would then be the same as another one are left
+/// alone and returned, grouped and ordered by ID. The index is only
+/// created without such conflicts, they have to be renamed first.
+fn normalize_titles(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Vec<(ItemId, String)>>> {
+	let articles: Vec<(ItemId, String)> = conn
+		.prepare("SELECT id, title FROM article ORDER BY id")?
+		.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+		.collect::<rusqlite::Result<_>>()?;
+	let mut groups: std::collections::BTreeMap<String, Vec<(ItemId, String)>> =
+		std::collections::BTreeMap::new();
+	for (id, title) in articles {
+		let key = Database::normalize_title(&title).to_ascii_lowercase();
+		groups.entry(key).or_default().push((id, title));
+	}
+	let mut conflicts = Vec::new();
+	for (_, group) in groups {
+		if group.len() > 1 {
+			conflicts.push(group);
+			continue;
+		}
+		let (id, title) = &group[0];
+		let normalized = Database::normalize_title(title);
+		if normalized != *title {
+			conn.execute(
+				"UPDATE article SET title = ? WHERE id = ?",
+				params![normalized, id],
+			)?;
+		}
+	}
+	conflicts.sort_by_key(|group| group[0].0.value);
+	if conflicts.is_empty() {
+		conn.execute(CREATE_ARTICLE_TITLE_INDEX, params![])?;
+	}
+	Ok(conflicts)
+}
+
+fn article_link_from_row(row: &rusqlite::Row) -> rusqlite::Result<ArticleLink> {
+	Ok(ArticleLink {
+		src: row.get(0)?,
+		dst: row.get(1)?,
+		kind: row.get(2)?,
+	})
+}
+
+fn has_table(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<bool> {
+	conn.query_row(
+		"SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+		params![name],
+		|row| row.get(0),
+	)
+}
+
+fn has_index(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<bool> {
+	conn.query_row(
+		"SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?)",
+		params![name],
+		|row| row.get(0),
+	)
+}
+
+/// Slug for `title` that no article other than `id` uses yet
+fn unique_slug(
+	conn: &rusqlite::Connection,
+	title: &str,
+	id: Option<ItemId>,
+) -> rusqlite::Result<String> {
+	let mut stmt =
+		conn.prepare("SELECT EXISTS(SELECT 1 FROM article WHERE slug = ? AND id IS NOT ?)")?;
+	for candidate in slug::candidates(&slug::slugify(title)) {
+		if !stmt.query_row(params![candidate, id], |row| row.get::<_, bool>(0))? {
+			return Ok(candidate);
+		}
+	}
+	unreachable!("The slug candidates are endless");
 }
 
 pub struct Database {
 	conn: rusqlite::Connection,
+	/// When the connection was opened, distinguishes the render epochs
+	/// of different runs (see `render_epoch`)
+	opened: chrono::NaiveDateTime,
+	/// Writes are refused while the clock is outside of it
+	date_window: DateWindow,
+}
+
+/// Changes whenever the database changes, see `Database::render_epoch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderEpoch {
+	opened: chrono::NaiveDateTime,
+	changes: u64,
+	/// Changes with the commits of other connections, e.g. of an import
+	/// through the command line while the server runs
+	data_version: i64,
+}
+
+impl std::fmt::Display for RenderEpoch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}.{}.{}",
+			self.opened
+				.and_utc()
+				.timestamp_nanos_opt()
+				.unwrap_or_default(),
+			self.changes,
+			self.data_version
+		)
+	}
 }
 
 #[allow(dead_code)]
@@ -139,6 +1312,11 @@ pub enum OpenMode {
 	CreateNew,
 	OpenExisting,
 	OpenOrCreate,
+	/// For inspecting the database, see `DatabaseConnection::inspect`
+	ReadOnly,
+	/// A new, empty database that only lives as long as the connection,
+	/// the path is ignored
+	InMemory,
 }
 
 #[derive(Debug)]
@@ -146,17 +1324,237 @@ pub enum DatabaseConnectError {
 	AlreadyExists,
 	CannotOpen,
 	CouldNotCreate,
+	/// A connection setting could not be applied, see `ConnectionSettings`
+	CouldNotConfigure {
+		setting: &'static str,
+		reason: String,
+	},
 	Unknown,
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-pub enum DatabaseInitError {
-	CouldNotReadLayout,
-	UnsupportedLayout,
-	MigrationNeeded,
-}
-
+impl std::fmt::Display for DatabaseConnectError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DatabaseConnectError::AlreadyExists => write!(f, "the database file already exists"),
+			DatabaseConnectError::CannotOpen => write!(f, "the database file cannot be opened"),
+			DatabaseConnectError::CouldNotCreate => {
+				write!(f, "the database file could not be created")
+			}
+			DatabaseConnectError::CouldNotConfigure { setting, reason } => {
+				write!(
+					f,
+					"could not set the {} of the database: {}",
+					setting, reason
+				)
+			}
+			DatabaseConnectError::Unknown => write!(f, "unknown error, see the log above"),
+		}
+	}
+}
+
+impl std::error::Error for DatabaseConnectError {}
+
+/// `PRAGMA journal_mode` values
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+	Delete,
+	Truncate,
+	Persist,
+	Memory,
+	Wal,
+	Off,
+}
+
+impl JournalMode {
+	fn as_sql(self) -> &'static str {
+		match self {
+			JournalMode::Delete => "delete",
+			JournalMode::Truncate => "truncate",
+			JournalMode::Persist => "persist",
+			JournalMode::Memory => "memory",
+			JournalMode::Wal => "wal",
+			JournalMode::Off => "off",
+		}
+	}
+}
+
+/// `PRAGMA synchronous` values
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+	Off,
+	Normal,
+	Full,
+	Extra,
+}
+
+impl Synchronous {
+	fn as_sql(self) -> &'static str {
+		match self {
+			Synchronous::Off => "OFF",
+			Synchronous::Normal => "NORMAL",
+			Synchronous::Full => "FULL",
+			Synchronous::Extra => "EXTRA",
+		}
+	}
+}
+
+/// Settings applied to the SQLite connection right after opening it
+#[derive(Debug, Clone)]
+pub struct ConnectionSettings {
+	pub journal_mode: JournalMode,
+	pub synchronous: Synchronous,
+	pub foreign_keys: bool,
+	/// How long to retry when another connection holds a lock before
+	/// failing with `DbError::Busy`
+	pub busy_timeout: std::time::Duration,
+}
+
+impl Default for ConnectionSettings {
+	fn default() -> Self {
+		ConnectionSettings {
+			journal_mode: JournalMode::Wal,
+			synchronous: Synchronous::Normal,
+			foreign_keys: true,
+			busy_timeout: std::time::Duration::from_millis(5000),
+		}
+	}
+}
+
+impl ConnectionSettings {
+	/// Apply the settings to `conn`. Without `set_journal_mode`, the
+	/// connection keeps the journal mode it has: Read-only connections
+	/// cannot change the mode of the database file, in-memory databases
+	/// always use the memory journal.
+	fn apply(&self, conn: &Connection, set_journal_mode: bool) -> Result<(), DatabaseConnectError> {
+		fn failed(setting: &'static str) -> impl Fn(rusqlite::Error) -> DatabaseConnectError {
+			move |err| DatabaseConnectError::CouldNotConfigure {
+				setting,
+				reason: err.to_string(),
+			}
+		}
+
+		conn.busy_timeout(self.busy_timeout)
+			.map_err(failed("busy timeout"))?;
+		conn.pragma_update(None, "synchronous", self.synchronous.as_sql())
+			.map_err(failed("synchronous mode"))?;
+		conn.pragma_update(None, "foreign_keys", self.foreign_keys)
+			.map_err(failed("foreign key enforcement"))?;
+		let journal_mode: String = if set_journal_mode {
+			// Setting the journal mode answers with the mode in effect
+			// afterwards, which differs from the requested one if SQLite
+			// refused the change
+			conn.query_row(
+				&format!("PRAGMA journal_mode = {}", self.journal_mode.as_sql()),
+				[],
+				|row| row.get(0),
+			)
+			.map_err(failed("journal mode"))?
+		} else {
+			conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))
+				.map_err(failed("journal mode"))?
+		};
+		if set_journal_mode && !journal_mode.eq_ignore_ascii_case(self.journal_mode.as_sql()) {
+			return Err(DatabaseConnectError::CouldNotConfigure {
+				setting: "journal mode",
+				reason: format!(
+					"requested {}, SQLite kept {}",
+					self.journal_mode.as_sql(),
+					journal_mode
+				),
+			});
+		}
+		log::info!("SQLite journal mode: {}", journal_mode);
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum DatabaseInitError {
+	CouldNotReadLayout,
+	/// The layout is newer than the one of this build, or too old to be
+	/// migrated
+	UnsupportedLayout {
+		found: WikiSemVer,
+		supported: WikiSemVer,
+	},
+	/// Only for `DatabaseConnection::inspect`, `init` migrates instead
+	MigrationNeeded {
+		found: WikiSemVer,
+		supported: WikiSemVer,
+	},
+	/// `migrating_to_version` is set: Either another process is migrating
+	/// the database right now or a previous migration was interrupted.
+	MigrationInProgress {
+		to: WikiSemVer,
+		since: Option<UtcTimestamp>,
+	},
+	/// An interrupted migration cannot be rolled forward because
+	/// no known migration step leads from `from` to `to`.
+	MigrationUnrecoverable {
+		from: WikiSemVer,
+		to: WikiSemVer,
+	},
+	MigrationFailed(rusqlite::Error),
+}
+
+impl std::fmt::Display for DatabaseInitError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DatabaseInitError::CouldNotReadLayout => {
+				write!(f, "the table layout of the database cannot be read")
+			}
+			DatabaseInitError::UnsupportedLayout { found, supported } if found > supported => {
+				write!(
+					f,
+					"the database was created by a newer version of redwood-wiki (layout {}), this build supports {}",
+					found, supported
+				)
+			}
+			DatabaseInitError::UnsupportedLayout { found, supported } => write!(
+				f,
+				"the database has the layout {}, which this build cannot migrate to its layout {}",
+				found, supported
+			),
+			DatabaseInitError::MigrationNeeded { found, supported } => write!(
+				f,
+				"the database has the older layout {}, it needs a migration to {}",
+				found, supported
+			),
+			DatabaseInitError::MigrationInProgress { to, since } => write!(
+				f,
+				"a migration to layout {} is in progress (started at {})",
+				to,
+				since.map_or_else(|| "unknown time".to_string(), |since| since.to_string())
+			),
+			DatabaseInitError::MigrationUnrecoverable { from, to } => write!(
+				f,
+				"the interrupted migration from layout {} to {} cannot be resumed",
+				from, to
+			),
+			DatabaseInitError::MigrationFailed(err) => write!(f, "the migration failed: {}", err),
+		}
+	}
+}
+
+impl std::error::Error for DatabaseInitError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			DatabaseInitError::MigrationFailed(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<rusqlite::Error> for DatabaseInitError {
+	fn from(sqlite_error: rusqlite::Error) -> DatabaseInitError {
+		log::error!("SQLite error during migration: {:?}", sqlite_error);
+		DatabaseInitError::MigrationFailed(sqlite_error)
+	}
+}
+
 impl From<rusqlite::Error> for DatabaseConnectError {
 	fn from(sqlite_error: rusqlite::Error) -> DatabaseConnectError {
 		log::error!("SQLite error: {:?}", sqlite_error);
@@ -171,19 +1569,153 @@ impl From<rusqlite::Error> for DatabaseConnectError {
 	}
 }
 
+#[derive(Debug)]
+pub enum DbError {
+	NotFound,
+	/// The change would make a category its own ancestor
+	Cycle,
+	/// The new name is already taken
+	AlreadyExists,
+	/// A change without anything to change
+	NothingToUpdate,
+	/// A title that is empty, or only whitespace
+	EmptyTitle,
+	/// The clock of the server is outside of the plausible dates
+	DateOutOfRange(UtcTimestamp),
+	/// Another process holds a lock on the database file for longer
+	/// than the busy timeout, trying again later may succeed
+	Busy,
+	/// The database file is damaged or no SQLite database at all
+	Corrupt,
+	Sqlite(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for DbError {
+	fn from(err: rusqlite::Error) -> DbError {
+		match &err {
+			rusqlite::Error::SqliteFailure(inner, _) => match inner.code {
+				rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked => {
+					DbError::Busy
+				}
+				rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase => {
+					DbError::Corrupt
+				}
+				_ => DbError::Sqlite(err),
+			},
+			_ => DbError::Sqlite(err),
+		}
+	}
+}
+
+impl std::fmt::Display for DbError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DbError::NotFound => write!(f, "not found"),
+			DbError::Cycle => write!(f, "a category cannot be moved into itself"),
+			DbError::AlreadyExists => write!(f, "the name already exists"),
+			DbError::NothingToUpdate => write!(f, "there is nothing to change"),
+			DbError::EmptyTitle => write!(f, "the title is empty"),
+			DbError::DateOutOfRange(time) => {
+				write!(f, "the clock of the server is wrong, it is {}", time)
+			}
+			DbError::Busy => write!(f, "the database is busy"),
+			DbError::Corrupt => write!(f, "the database file is damaged"),
+			DbError::Sqlite(err) => write!(f, "{}", err),
+		}
+	}
+}
+
+impl std::error::Error for DbError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			DbError::Sqlite(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+/// Why `Database::create_article` failed
+#[derive(Debug)]
+pub enum CreateArticleError {
+	/// Another article has the title, possibly one in the trash
+	DuplicateTitle,
+	EmptyTitle,
+	/// The clock of the server is outside of the plausible dates
+	DateOutOfRange(UtcTimestamp),
+	Database(rusqlite::Error),
+}
+
+impl std::fmt::Display for CreateArticleError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CreateArticleError::DuplicateTitle => write!(f, "the title already exists"),
+			CreateArticleError::EmptyTitle => write!(f, "the title is empty"),
+			CreateArticleError::DateOutOfRange(time) => {
+				write!(f, "the clock of the server is wrong, it is {}", time)
+			}
+			CreateArticleError::Database(err) => write!(f, "{}", err),
+		}
+	}
+}
+
+impl std::error::Error for CreateArticleError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			CreateArticleError::Database(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<rusqlite::Error> for CreateArticleError {
+	fn from(err: rusqlite::Error) -> CreateArticleError {
+		match &err {
+			rusqlite::Error::SqliteFailure(inner, _)
+				if inner.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE =>
+			{
+				// The slug is chosen to be unique, so it is the title
+				CreateArticleError::DuplicateTitle
+			}
+			_ => CreateArticleError::Database(err),
+		}
+	}
+}
+
+impl From<CreateArticleError> for DbError {
+	fn from(err: CreateArticleError) -> DbError {
+		match err {
+			CreateArticleError::DuplicateTitle => DbError::AlreadyExists,
+			CreateArticleError::EmptyTitle => DbError::EmptyTitle,
+			CreateArticleError::DateOutOfRange(time) => DbError::DateOutOfRange(time),
+			CreateArticleError::Database(err) => DbError::from(err),
+		}
+	}
+}
+
 pub struct DatabaseConnection {
 	database: Database,
 }
 
 impl DatabaseConnection {
+	/// Open the database with the default `ConnectionSettings`
+	#[cfg(test)]
 	pub fn new(
 		database_path: &Path,
 		open_mode: OpenMode,
+	) -> Result<DatabaseConnection, DatabaseConnectError> {
+		DatabaseConnection::with_settings(database_path, open_mode, &ConnectionSettings::default())
+	}
+
+	pub fn with_settings(
+		database_path: &Path,
+		open_mode: OpenMode,
+		settings: &ConnectionSettings,
 	) -> Result<DatabaseConnection, DatabaseConnectError> {
 		log::info!("SQLite version: {}", rusqlite::version());
 
 		pub fn create_new(
 			database_path: &Path,
+			settings: &ConnectionSettings,
 		) -> Result<DatabaseConnection, DatabaseConnectError> {
 			// Note: Here, SQLite forces us to open the database
 			// with a racy file exists check. The reason for that
@@ -199,6 +1731,14 @@ impl DatabaseConnection {
 				return Err(DatabaseConnectError::AlreadyExists);
 			}
 
+			// A write-ahead log left behind by a deleted database would
+			// be replayed into the new one
+			for suffix in &["-wal", "-shm"] {
+				let mut side_file = database_path.as_os_str().to_owned();
+				side_file.push(suffix);
+				let _ = std::fs::remove_file(side_file);
+			}
+
 			let conn_result = Connection::open_with_flags(
 				database_path,
 				OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
@@ -222,35 +1762,87 @@ impl DatabaseConnection {
 			}
 
 			let conn = conn_result?;
-			let mut database = Database { conn };
-			database.init_tables();
+			settings.apply(&conn, true)?;
+			let mut database = Database {
+				conn,
+				opened: Utc::now().naive_utc(),
+				date_window: DateWindow::default(),
+			};
+			if let Err(err) = database.init_tables() {
+				log::error!("Could not create the tables: {}", err);
+				return Err(DatabaseConnectError::CouldNotCreate);
+			}
 			let dbc = DatabaseConnection { database };
 			Ok(dbc)
 		}
 
 		pub fn open_existing(
 			database_path: &Path,
+			settings: &ConnectionSettings,
 		) -> Result<DatabaseConnection, DatabaseConnectError> {
 			let conn =
 				Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+			settings.apply(&conn, true)?;
+
+			let database = Database {
+				conn,
+				opened: Utc::now().naive_utc(),
+				date_window: DateWindow::default(),
+			};
+			let dbc = DatabaseConnection { database };
+			Ok(dbc)
+		}
+
+		pub fn open_read_only(
+			database_path: &Path,
+			settings: &ConnectionSettings,
+		) -> Result<DatabaseConnection, DatabaseConnectError> {
+			let conn =
+				Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+			settings.apply(&conn, false)?;
+
+			let database = Database {
+				conn,
+				opened: Utc::now().naive_utc(),
+				date_window: DateWindow::default(),
+			};
+			let dbc = DatabaseConnection { database };
+			Ok(dbc)
+		}
+
+		pub fn open_in_memory(
+			settings: &ConnectionSettings,
+		) -> Result<DatabaseConnection, DatabaseConnectError> {
+			let conn = Connection::open_in_memory()?;
+			settings.apply(&conn, false)?;
 
-			let database = Database { conn };
+			let mut database = Database {
+				conn,
+				opened: Utc::now().naive_utc(),
+				date_window: DateWindow::default(),
+			};
+			if let Err(err) = database.init_tables() {
+				log::error!("Could not create the tables: {}", err);
+				return Err(DatabaseConnectError::CouldNotCreate);
+			}
 			let dbc = DatabaseConnection { database };
-			return Ok(dbc);
+			Ok(dbc)
 		}
 
 		let dbc = match open_mode {
-			OpenMode::CreateNew => create_new(database_path),
-			OpenMode::OpenExisting => open_existing(database_path),
+			OpenMode::CreateNew => create_new(database_path, settings),
+			OpenMode::InMemory => open_in_memory(settings),
+			OpenMode::OpenExisting => open_existing(database_path, settings),
+			OpenMode::ReadOnly => open_read_only(database_path, settings),
 			OpenMode::OpenOrCreate => {
 				// Note: This check is racy, but once `create_new`
 				// becomes atomic, the worst consequence is that we try
 				// to create a database that already exists and fail
 				// without causing any harm or undefined states.
 				if database_path.exists() {
-					open_existing(database_path)
+					open_existing(database_path, settings)
 				} else {
-					create_new(database_path)
+					create_new(database_path, settings)
 				}
 			}
 		}?;
@@ -258,26 +1850,148 @@ impl DatabaseConnection {
 		Ok(dbc)
 	}
 
-	pub fn init(mut self) -> Result<Database, DatabaseInitError> {
-		let layout = self.database.get_table_layout();
-		if let Some(layout) = layout {
-			if layout.version
-				== (WikiSemVer {
-					major: 0,
-					minor: 1,
-					patch: 0,
-				}) {
-				Ok(self.database)
+	/// Check the table layout and bring the database up to the
+	/// layout version of this build by running all pending migrations.
+	///
+	/// Refuses to open the database while `migrating_to_version` is set,
+	/// see `resume_migration` to deal with interrupted migrations.
+	pub fn init(self) -> Result<Database, DatabaseInitError> {
+		self.init_internal(MIGRATIONS, LAYOUT_VERSION, false)
+	}
+
+	/// Inspect an interrupted migration and roll it forward if possible.
+	///
+	/// The actual schema changes of a migration step are done in a single
+	/// transaction, so if `migrating_to_version` is still set, that transaction
+	/// was never committed and the step can safely be run again. If no known
+	/// migration step matches the recorded versions, the database is left
+	/// untouched and the caller has to restore a backup.
+	pub fn resume_migration(self) -> Result<Database, DatabaseInitError> {
+		self.init_internal(MIGRATIONS, LAYOUT_VERSION, true)
+	}
+
+	/// Check that the table layout is the one of this build without
+	/// changing anything, for reading a database opened with
+	/// `OpenMode::ReadOnly`. Refuses databases that are being migrated
+	/// or still need a migration.
+	pub fn inspect(mut self) -> Result<Database, DatabaseInitError> {
+		let layout = self
+			.database
+			.get_table_layout()
+			.map_err(|_| DatabaseInitError::CouldNotReadLayout)?;
+		if let Some(migrating_to_version) = layout.migrating_to_version {
+			return Err(DatabaseInitError::MigrationInProgress {
+				to: migrating_to_version,
+				since: layout.date_migration_begin,
+			});
+		}
+		if layout.version == LAYOUT_VERSION {
+			Ok(self.database)
+		} else if MIGRATIONS.iter().any(|m| m.from == layout.version) {
+			Err(DatabaseInitError::MigrationNeeded {
+				found: layout.version,
+				supported: LAYOUT_VERSION,
+			})
+		} else {
+			Err(DatabaseInitError::UnsupportedLayout {
+				found: layout.version,
+				supported: LAYOUT_VERSION,
+			})
+		}
+	}
+
+	fn init_internal(
+		mut self,
+		migrations: &[Migration],
+		target: WikiSemVer,
+		resume: bool,
+	) -> Result<Database, DatabaseInitError> {
+		let layout = self
+			.database
+			.get_table_layout()
+			.map_err(|_| DatabaseInitError::CouldNotReadLayout)?;
+
+		if let Some(migrating_to_version) = layout.migrating_to_version {
+			if !resume {
+				return Err(DatabaseInitError::MigrationInProgress {
+					to: migrating_to_version,
+					since: layout.date_migration_begin,
+				});
+			}
+			let step = migrations
+				.iter()
+				.find(|m| m.from == layout.version && m.to == migrating_to_version)
+				.ok_or(DatabaseInitError::MigrationUnrecoverable {
+					from: layout.version,
+					to: migrating_to_version,
+				})?;
+			log::warn!(
+				"Resuming interrupted migration from {} to {}",
+				step.from,
+				step.to
+			);
+			self.database.apply_migration(step)?;
+		}
+
+		let mut version = self
+			.database
+			.get_table_layout()
+			.map_err(|_| DatabaseInitError::CouldNotReadLayout)?
+			.version;
+		while version != target {
+			if let Some(step) = migrations.iter().find(|m| m.from == version) {
+				if self.database.begin_migration(step)? {
+					self.database.apply_migration(step)?;
+					version = step.to;
+				} else {
+					version = self
+						.database
+						.get_table_layout()
+						.map_err(|_| DatabaseInitError::CouldNotReadLayout)?
+						.version;
+				}
 			} else {
-				Err(DatabaseInitError::UnsupportedLayout)
+				return Err(DatabaseInitError::UnsupportedLayout {
+					found: version,
+					supported: target,
+				});
 			}
-		} else {
-			Err(DatabaseInitError::CouldNotReadLayout)
 		}
+		self.database.check_title_index();
+		Ok(self.database)
 	}
 }
-
 impl Database {
+	/// Changes with every write to the database, failed ones included.
+	/// A rendered article depends on more than its own row (titles of
+	/// referenced articles, tags, categories, links to it), so anything
+	/// derived from the database, like the ETag of an article page, is
+	/// only valid for one epoch. Since SQLite counts the changes, no
+	/// write can forget to start a new epoch. `total_changes` only
+	/// counts the writes of this connection, `data_version` those of
+	/// the others.
+	pub fn render_epoch(&mut self) -> Option<RenderEpoch> {
+		let result = self
+			.conn
+			.query_row("SELECT total_changes()", params![], |row| row.get(0))
+			.and_then(|changes| {
+				self.conn
+					.query_row("PRAGMA data_version", params![], |row| row.get(0))
+					.map(|data_version| (changes, data_version))
+			});
+		match result {
+			Ok((changes, data_version)) => Some(RenderEpoch {
+				opened: self.opened,
+				changes,
+				data_version,
+			}),
+			Err(err) => {
+				log::error!("Could not get the render epoch: {:?}", err);
+				None
+			}
+		}
+	}
+
 	/// Filter illegal characters that
 	/// shall be prevented from entering the
 	/// database
@@ -322,24 +2036,114 @@ impl Database {
 		)
 	}
 
-	pub fn init_tables(&mut self) {
+	/// Title as stored: without illegal characters, leading and trailing
+	/// whitespace, and with runs of whitespace inside as one space
+	fn normalize_title(s: &str) -> String {
+		Database::filter_chars(s)
+			.split_whitespace()
+			.collect::<Vec<_>>()
+			.join(" ")
+	}
+
+	/// Titles that keep the case-insensitive index from being created by
+	/// the migration to layout 0.13.0 are reported on every start, until
+	/// they are renamed and the index can be created after all. Until
+	/// then, `create_article` and `update_article` still refuse new
+	/// conflicts, only the existing ones remain.
+	fn check_title_index(&mut self) {
+		let result = has_index(&self.conn, "article_title_nocase").and_then(|exists| {
+			if exists {
+				return Ok(());
+			}
+			let tx = self.conn.transaction()?;
+			let conflicts = normalize_titles(&tx)?;
+			tx.commit()?;
+			if conflicts.is_empty() {
+				log::info!("Created the index of the titles, they are unique now");
+			}
+			for group in conflicts {
+				log::warn!(
+					"Titles only differ in case or whitespace, rename all but one of them: {}",
+					group
+						.iter()
+						.map(|(id, title)| format!("#{} {:?}", id, title))
+						.collect::<Vec<_>>()
+						.join(", ")
+				);
+			}
+			Ok(())
+		});
+		if let Err(err) = result {
+			log::error!("Could not check the index of the titles: {:?}", err);
+		}
+	}
+
+	/// Article other than `id` whose title equals `title` except for
+	/// the case of ASCII letters
+	fn title_taken(
+		conn: &rusqlite::Connection,
+		title: &str,
+		id: Option<ItemId>,
+	) -> rusqlite::Result<bool> {
+		conn.query_row(
+			"SELECT EXISTS(SELECT 1 FROM article WHERE title = ? COLLATE NOCASE AND id IS NOT ?)",
+			params![title, id],
+			|row| row.get(0),
+		)
+	}
+
+	/// Edit summary as stored: on one line, without illegal characters
+	/// and at most `MAX_EDIT_SUMMARY_LENGTH` characters long
+	fn normalize_edit_summary(s: &str) -> String {
+		let s = Database::filter_chars(s);
+		let words: Vec<&str> = s.split_whitespace().collect();
+		words
+			.join(" ")
+			.chars()
+			.take(MAX_EDIT_SUMMARY_LENGTH)
+			.collect::<String>()
+			.trim_end()
+			.to_string()
+	}
+
+	pub fn init_tables(&mut self) -> Result<(), DbError> {
 		// Note: SQLite does not have a DATETIME type
 		// Therefore, we implement datetime types as
-		// TEXT with ISO 8601 format.
+		// TEXT with ISO 8601 format. They are always in
+		// UTC and have no offset, see `UtcTimestamp`.
 
-		self.conn
-			.execute(
-				"CREATE TABLE article (
+		self.conn.execute(
+			"CREATE TABLE article (
 					id            INTEGER PRIMARY KEY AUTOINCREMENT,
 					title         TEXT NOT NULL UNIQUE,
 					text          TEXT NOT NULL,
 					date_created  DATETIME NOT NULL,
 					date_modified DATETIME NOT NULL,
-					revision      INTEGER NOT NULL
+					revision      INTEGER NOT NULL,
+					slug          TEXT NOT NULL DEFAULT '',
+					date_deleted  DATETIME
 				)",
-				params![],
-			)
-			.unwrap();
+			params![],
+		)?;
+		self.conn.execute(
+			"CREATE UNIQUE INDEX article_slug ON article (slug)",
+			params![],
+		)?;
+		self.conn.execute(CREATE_ARTICLE_TITLE_INDEX, params![])?;
+		self.conn.execute(CREATE_FAVICON_TABLE, params![])?;
+		self.conn.execute(CREATE_TITLE_REDIRECT_TABLE, params![])?;
+		self.conn.execute(CREATE_ARTICLE_TAG_TABLE, params![])?;
+		self.conn.execute(CREATE_ARTICLE_TAG_INDEX, params![])?;
+		create_article_fts(&self.conn)?;
+		self.conn.execute(CREATE_CATEGORY_TABLE, params![])?;
+		self.conn
+			.execute(CREATE_ARTICLE_CATEGORY_TABLE, params![])?;
+		self.conn
+			.execute(CREATE_ARTICLE_CATEGORY_INDEX, params![])?;
+		self.conn.execute(CREATE_ARTICLE_LINK_TABLE, params![])?;
+		self.conn.execute(CREATE_ARTICLE_LINK_INDEX, params![])?;
+		self.conn
+			.execute(CREATE_ARTICLE_REVISION_TABLE, params![])?;
 
 		// The following table MUST only ever have one row.
 		// Note that this table MUST always be present and
@@ -373,9 +2177,8 @@ impl Database {
 		// untouched. Additionally, `migrating_to_version` is set
 		// to NULL again, atomically in the same transaction.
 
-		self.conn
-			.execute(
-				"CREATE TABLE table_layout (
+		self.conn.execute(
+			"CREATE TABLE table_layout (
 					id                        INTEGER PRIMARY KEY AUTOINCREMENT,
 					version                   TEXT NOT NULL,
 					migrating_to_version      TEXT,
@@ -383,19 +2186,14 @@ impl Database {
 					date_migration_begin      DATETIME,
 					date_migration_complete   DATETIME
 				)",
-				params![],
-			)
-			.unwrap();
+			params![],
+		)?;
 
 		let layout = TableLayout {
 			id: 1.into(),
-			version: WikiSemVer {
-				major: 0,
-				minor: 1,
-				patch: 0,
-			},
+			version: LAYOUT_VERSION,
 			migrating_to_version: None,
-			date_created: Utc::now().naive_utc(),
+			date_created: UtcTimestamp::now(),
 			date_migration_begin: None,
 			date_migration_complete: None,
 		};
@@ -404,21 +2202,18 @@ impl Database {
 			.execute(
 				"INSERT INTO table_layout (id, version, migrating_to_version, date_created, date_migration_begin, date_migration_complete) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
 				params![layout.id, layout.version, layout.migrating_to_version, layout.date_created, layout.date_migration_begin, layout.date_migration_complete],
-			)
-			.unwrap();
+			)?;
 
 		log::debug!("Table layout: {:?}", self.get_table_layout());
+		Ok(())
 	}
 
-	pub fn get_table_layout(&mut self) -> Option<TableLayout> {
-		let mut stmt = self
-			.conn
-			.prepare(
-				"SELECT id, version, migrating_to_version, date_created, date_migration_begin, date_migration_complete FROM table_layout WHERE id = ?",
-			)
-			.unwrap();
-		let mut table_layout_iter = stmt
-			.query_map(params![1], |row| {
+	/// Read the table layout, returning the error if it cannot be read
+	pub fn read_table_layout(&mut self) -> rusqlite::Result<TableLayout> {
+		self.conn.query_row(
+			"SELECT id, version, migrating_to_version, date_created, date_migration_begin, date_migration_complete FROM table_layout WHERE id = ?",
+			params![1],
+			|row| {
 				Ok(TableLayout {
 					id: row.get(0)?,
 					version: row.get(1)?,
@@ -427,60 +2222,181 @@ impl Database {
 					date_migration_begin: row.get(4)?,
 					date_migration_complete: row.get(5)?,
 				})
-			})
-			.unwrap();
+			},
+		)
+	}
 
-		if let Some(table_layout_result) = table_layout_iter.next() {
-			match table_layout_result {
-				Ok(table_layout) => Some(table_layout),
-				Err(err) => {
-					log::error!("Could not parse table layout: {:?}", err);
-					None
-				}
+	pub fn get_table_layout(&mut self) -> Result<TableLayout, DbError> {
+		match self.read_table_layout() {
+			Ok(table_layout) => Ok(table_layout),
+			Err(rusqlite::Error::QueryReturnedNoRows) => {
+				log::error!("Could not find table layout!");
+				Err(DbError::NotFound)
+			}
+			Err(err) => {
+				log::error!("Could not read table layout: {:?}", err);
+				Err(DbError::from(err))
 			}
-		} else {
-			log::error!("Could not find table layout!");
-			None
 		}
 	}
 
-	pub fn create_article(&mut self, article: &Article) -> Option<ItemId> {
-		let now = Utc::now().naive_utc();
-		if article.title.is_empty() {
-			None
-		} else if let Ok(1) = self.conn
-			.execute(
-				"INSERT INTO article (title, text, date_created, date_modified, revision) VALUES (?1, ?2, ?3, ?4, ?5)",
-				params![Database::filter_chars(&article.title), Database::filter_chars(&article.text), now, now, article.revision],
-			) {
-			ItemId::try_from(self.conn.last_insert_rowid()).ok()
+	/// Mark the start of a migration step in the `table_layout` row.
+	///
+	/// This is a check-and-set in an immediate transaction, so two
+	/// processes racing each other cannot both begin a migration.
+	/// Returns `false` without starting anything if the layout is no
+	/// longer `step.from`, because another process completed the step
+	/// in the meantime.
+	fn begin_migration(&mut self, step: &Migration) -> Result<bool, DatabaseInitError> {
+		let tx = self
+			.conn
+			.transaction_with_behavior(TransactionBehavior::Immediate)?;
+		let (version, migrating_to_version, date_migration_begin): (
+			WikiSemVer,
+			Option<WikiSemVer>,
+			Option<UtcTimestamp>,
+		) = tx.query_row(
+			"SELECT version, migrating_to_version, date_migration_begin FROM table_layout WHERE id = ?",
+			params![1],
+			|row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+		)?;
+		if let Some(migrating_to_version) = migrating_to_version {
+			return Err(DatabaseInitError::MigrationInProgress {
+				to: migrating_to_version,
+				since: date_migration_begin,
+			});
+		}
+		if version != step.from {
+			log::info!(
+				"Migration from {} to {} skipped, the layout is already {}",
+				step.from,
+				step.to,
+				version
+			);
+			return Ok(false);
+		}
+		tx.execute(
+			"UPDATE table_layout SET migrating_to_version = ?, date_migration_begin = ?, date_migration_complete = NULL WHERE id = ?",
+			params![step.to, UtcTimestamp::now(), 1],
+		)?;
+		tx.commit()?;
+		log::info!("Migration from {} to {} started", step.from, step.to);
+		Ok(true)
+	}
+
+	/// Run the migration step and complete it in one immediate transaction.
+	/// `begin_migration` must have been called for the same step before.
+	fn apply_migration(&mut self, step: &Migration) -> Result<(), DatabaseInitError> {
+		let tx = self
+			.conn
+			.transaction_with_behavior(TransactionBehavior::Immediate)?;
+		(step.apply)(&tx)?;
+		tx.execute(
+			"UPDATE table_layout SET version = ?, migrating_to_version = NULL, date_migration_complete = ? WHERE id = ?",
+			params![step.to, UtcTimestamp::now(), 1],
+		)?;
+		tx.commit()?;
+		log::info!("Migration from {} to {} complete", step.from, step.to);
+		Ok(())
+	}
+
+	/// Set the dates that are plausible for writes, see `write_time`
+	pub fn set_date_window(&mut self, window: DateWindow) {
+		self.date_window = window;
+	}
+
+	/// The current time for the dates of a write. A clock outside of
+	/// the plausible dates (reset to 1970 without a battery, say) would
+	/// store dates that mess up the order of the articles, so the write
+	/// is refused instead.
+	fn write_time(&self) -> Result<UtcTimestamp, DbError> {
+		let now = UtcTimestamp::now();
+		if self.date_window.contains(now, now) {
+			Ok(now)
 		} else {
-			None
+			log::error!("Refusing to write at implausible time {}", now);
+			Err(DbError::DateOutOfRange(now))
 		}
 	}
 
-	#[allow(dead_code)]
-	pub fn test_tables(&mut self) {
-		let art1 = Article {
-			id: 0.into(),
-			title: "TITLE_x".to_string(),
-			text: "TEXT_x".to_string(),
-			date_created: Utc::now().naive_utc(),
-			date_modified: Utc::now().naive_utc(),
-			revision: 0,
+	/// Create an article, its text is kept as the first revision
+	pub fn create_article(&mut self, article: &Article) -> Result<ItemId, CreateArticleError> {
+		let now = match self.write_time() {
+			Ok(now) => now,
+			Err(DbError::DateOutOfRange(now)) => {
+				return Err(CreateArticleError::DateOutOfRange(now))
+			}
+			Err(DbError::Sqlite(err)) => return Err(CreateArticleError::Database(err)),
+			Err(err) => unreachable!("Unexpected error of the clock: {}", err),
 		};
-		self.conn
-			.execute(
-				"INSERT INTO article (title, text) VALUES (?1, ?2)",
-				params![art1.title, art1.text],
-			)
-			.unwrap();
-		let mut stmt = self
-			.conn
-			.prepare("SELECT id, title, text, date_created, date_modified, revision FROM article")
-			.unwrap();
-		let article_iter = stmt
-			.query_map(params![], |row| {
+		let title = Database::normalize_title(&article.title);
+		if title.is_empty() {
+			return Err(CreateArticleError::EmptyTitle);
+		}
+		let result = self.conn.transaction().and_then(|tx| {
+			// Also checked without the index, see `check_title_index`
+			if Database::title_taken(&tx, &title, None)? {
+				return Ok(Err(CreateArticleError::DuplicateTitle));
+			}
+			let slug = unique_slug(&tx, &title, None)?;
+			tx.execute(
+				"INSERT INTO article (title, text, date_created, date_modified, revision, slug) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+				params![title, Database::filter_chars(&article.text), now, now, article.revision, slug],
+			)?;
+			let id = tx.last_insert_rowid();
+			tx.execute(
+				"INSERT INTO article_revision (article_id, revision, date_created, text)
+					SELECT id, revision, date_created, text FROM article WHERE id = ?",
+				params![id],
+			)?;
+			tx.commit()?;
+			Ok(Ok(id))
+		});
+		match result
+			.map_err(CreateArticleError::from)
+			.and_then(|result| result)
+		{
+			Ok(id) => ItemId::try_from(id).map_err(|_| {
+				CreateArticleError::Database(rusqlite::Error::IntegralValueOutOfRange(0, id))
+			}),
+			Err(CreateArticleError::DuplicateTitle) => Err(CreateArticleError::DuplicateTitle),
+			Err(err) => {
+				log::error!("Could not create article: {}", err);
+				Err(err)
+			}
+		}
+	}
+
+	/// Write a consistent copy of the database to the new file `path`
+	/// with `VACUUM INTO`, other connections can continue to read
+	/// meanwhile. Fails if `path` exists.
+	pub fn backup_to(&mut self, path: &Path) -> Result<(), DbError> {
+		let path = path
+			.to_str()
+			.ok_or_else(|| DbError::Sqlite(rusqlite::Error::InvalidPath(path.to_path_buf())))?;
+		self.conn.execute("VACUUM INTO ?", params![path])?;
+		Ok(())
+	}
+
+	/// Problems SQLite finds in the database file with
+	/// `PRAGMA integrity_check`, empty if there are none
+	pub fn check_integrity(&mut self) -> Result<Vec<String>, DbError> {
+		let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+		let problems = stmt
+			.query_map([], |row| row.get::<_, String>(0))?
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(problems
+			.into_iter()
+			.filter(|problem| problem != "ok")
+			.collect())
+	}
+
+	/// The article, `None` if it does not exist or is deleted
+	pub fn get_article(&mut self, id: ItemId) -> Result<Option<Article>, DbError> {
+		let result = self.conn.query_row(
+			"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE id = ? AND date_deleted IS NULL",
+			params![id],
+			|row| {
 				Ok(Article {
 					id: row.get(0)?,
 					title: row.get(1)?,
@@ -488,24 +2404,77 @@ impl Database {
 					date_created: row.get(3)?,
 					date_modified: row.get(4)?,
 					revision: row.get(5)?,
+					slug: row.get(6)?,
 				})
-			})
-			.unwrap();
-
-		for article in article_iter {
-			log::debug!("Found article {:?}", article.unwrap());
+			},
+		);
+		match result {
+			Ok(article) => Ok(Some(article)),
+			Err(rusqlite::Error::QueryReturnedNoRows) => {
+				log::debug!("Could not find article with id {}", id);
+				Ok(None)
+			}
+			Err(err) => {
+				log::error!("Could not get article {}: {:?}", id, err);
+				Err(DbError::from(err))
+			}
 		}
 	}
-
-	pub fn get_article(&mut self, id: ItemId) -> Option<Article> {
-		let mut stmt = self
+	/// Get all existing articles
+	///
+	/// Note: This dumps the entire database. Depending on the
+	/// number of articles, this might be slow and use a lot of RAM.
+	/// Lists of articles should use `get_articles_page`.
+	#[allow(dead_code)]
+	pub fn get_all_articles(&mut self) -> Result<Vec<Article>, DbError> {
+		let result = self
 			.conn
 			.prepare(
-				"SELECT id, title, text, date_created, date_modified, revision FROM article WHERE id = ?",
+				"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE date_deleted IS NULL",
 			)
-			.unwrap();
-		let mut article_iter = stmt
-			.query_map(params![id], |row| {
+			.and_then(|mut stmt| {
+				stmt.query_map(params![], |row| {
+					Ok(Article {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						text: row.get(2)?,
+						date_created: row.get(3)?,
+						date_modified: row.get(4)?,
+						revision: row.get(5)?,
+						slug: row.get(6)?,
+					})
+				})?
+				.collect()
+			});
+		result.map_err(|err| {
+			log::error!("Could not get all articles: {:?}", err);
+			DbError::from(err)
+		})
+	}
+
+	/// IDs and titles of all articles, in no particular order
+	pub fn get_all_titles(&mut self) -> Result<Vec<(ItemId, String)>, DbError> {
+		let result = self
+			.conn
+			.prepare("SELECT id, title FROM article WHERE date_deleted IS NULL")
+			.and_then(|mut stmt| {
+				stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+					.collect()
+			});
+		result.map_err(|err| {
+			log::error!("Could not get the titles: {:?}", err);
+			DbError::from(err)
+		})
+	}
+
+	/// The `limit` most recently modified articles with their text
+	pub fn get_recently_modified(&mut self, limit: u32) -> Option<Vec<Article>> {
+		let result = self.conn.prepare(&format!(
+			"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE date_deleted IS NULL ORDER BY {} LIMIT ?",
+			ArticleOrder::Modified.sql()
+		))
+		.and_then(|mut stmt| {
+			stmt.query_map(params![limit], |row| {
 				Ok(Article {
 					id: row.get(0)?,
 					title: row.get(1)?,
@@ -513,154 +2482,3751 @@ impl Database {
 					date_created: row.get(3)?,
 					date_modified: row.get(4)?,
 					revision: row.get(5)?,
+					slug: row.get(6)?,
 				})
-			})
-			.unwrap();
-
-		if let Some(article_result) = article_iter.next() {
-			match article_result {
-				Ok(article) => Some(article),
-				Err(err) => {
-					log::error!("Could not parse article: {:?}", err);
-					None
-				}
+			})?
+			.collect::<rusqlite::Result<Vec<_>>>()
+		});
+		match result {
+			Ok(articles) => Some(articles),
+			Err(err) => {
+				log::error!("Could not get recently modified articles: {:?}", err);
+				None
 			}
-		} else {
-			log::debug!("Could not find article with id {}", id);
-			None
 		}
 	}
-	/// Get all existing articles
-	///
-	/// Note: This dumps the entire database. Depending on the
-	/// number of articles, this might be slow and use a lot of RAM
-	pub fn get_all_articles(&mut self) -> Option<Vec<Article>> {
-		let mut stmt = self
+
+	/// Call `visit` with every article in the order of their IDs. The
+	/// articles are read one at a time, so unlike `get_all_articles`,
+	/// this does not hold the whole wiki in memory. Returns the number
+	/// of articles.
+	pub fn scan_articles(&mut self, mut visit: impl FnMut(Article)) -> Option<u32> {
+		let result = self
 			.conn
-			.prepare("SELECT id, title, text, date_created, date_modified, revision FROM article")
-			.unwrap();
-		let article_iter = stmt
-			.query_map(params![], |row| {
-				Ok(Article {
-					id: row.get(0)?,
-					title: row.get(1)?,
-					text: row.get(2)?,
-					date_created: row.get(3)?,
-					date_modified: row.get(4)?,
-					revision: row.get(5)?,
+			.prepare(
+				"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE date_deleted IS NULL ORDER BY id",
+			)
+			.and_then(|mut stmt| {
+				let mut rows = stmt.query(params![])?;
+				let mut count = 0;
+				while let Some(row) = rows.next()? {
+					visit(Article {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						text: row.get(2)?,
+						date_created: row.get(3)?,
+						date_modified: row.get(4)?,
+						revision: row.get(5)?,
+						slug: row.get(6)?,
+					});
+					count += 1;
+				}
+				Ok(count)
+			});
+		match result {
+			Ok(count) => Some(count),
+			Err(err) => {
+				log::error!("Could not scan articles: {:?}", err);
+				None
+			}
+		}
+	}
+
+	/// Write the whole wiki as JSON document to `writer`: The
+	/// `format_version` of the document, the `layout_version` of the
+	/// database and the `articles` in the order of their IDs, each with
+	/// its tags and revisions. Like `scan_articles`, the articles are
+	/// read and written one at a time, one per line. Deleted articles
+	/// are left out. Returns the number of articles.
+	pub fn export_all(&mut self, mut writer: impl std::io::Write) -> Result<u32, ExportError> {
+		let layout = self.get_table_layout().map_err(ExportError::Database)?;
+		write!(
+			writer,
+			"{{\"format_version\":{},\"layout_version\":{},\"date_exported\":{},\"articles\":[",
+			EXPORT_FORMAT_VERSION,
+			serde_json::to_string(&layout.version.to_string())?,
+			serde_json::to_string(&UtcTimestamp::now())?
+		)?;
+
+		let mut articles = self.conn.prepare(
+			"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE date_deleted IS NULL ORDER BY id",
+		)?;
+		let mut rows = articles.query(params![])?;
+		let mut count = 0;
+		while let Some(row) = rows.next()? {
+			let article = Article {
+				id: row.get(0)?,
+				title: row.get(1)?,
+				text: row.get(2)?,
+				date_created: row.get(3)?,
+				date_modified: row.get(4)?,
+				revision: row.get(5)?,
+				slug: row.get(6)?,
+			};
+			let exported = Database::exported_article(&self.conn, article)?;
+			writer.write_all(if count == 0 { b"\n" } else { b",\n" })?;
+			serde_json::to_writer(&mut writer, &exported)?;
+			count += 1;
+		}
+		writer.write_all(b"\n]}\n")?;
+		writer.flush()?;
+		Ok(count)
+	}
+
+	/// The article `id` with its tags and revisions as in `export_all`,
+	/// `None` if it does not exist or is deleted
+	pub fn export_article(&mut self, id: ItemId) -> Result<Option<ExportedArticle>, DbError> {
+		let result = (|| -> rusqlite::Result<Option<ExportedArticle>> {
+			let article = self
+				.conn
+				.query_row(
+					"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE id = ? AND date_deleted IS NULL",
+					params![id],
+					|row| {
+						Ok(Article {
+							id: row.get(0)?,
+							title: row.get(1)?,
+							text: row.get(2)?,
+							date_created: row.get(3)?,
+							date_modified: row.get(4)?,
+							revision: row.get(5)?,
+							slug: row.get(6)?,
+						})
+					},
+				)
+				.map(Some)
+				.or_else(|err| match err {
+					rusqlite::Error::QueryReturnedNoRows => Ok(None),
+					err => Err(err),
+				})?;
+			match article {
+				Some(article) => Database::exported_article(&self.conn, article).map(Some),
+				None => Ok(None),
+			}
+		})();
+		result.map_err(|err| {
+			log::error!("Could not export article {}: {:?}", id, err);
+			DbError::from(err)
+		})
+	}
+
+	/// `article` with its tags and revisions
+	fn exported_article(
+		conn: &rusqlite::Connection,
+		article: Article,
+	) -> rusqlite::Result<ExportedArticle> {
+		let mut tags =
+			conn.prepare_cached("SELECT tag FROM article_tag WHERE article_id = ? ORDER BY tag")?;
+		let mut revisions = conn.prepare_cached(
+			"SELECT revision, date_created, summary, old_title, new_title, text FROM article_revision
+				WHERE article_id = ? ORDER BY revision",
+		)?;
+		let tags = tags
+			.query_map(params![article.id], |row| row.get(0))?
+			.collect::<rusqlite::Result<_>>()?;
+		let revisions = revisions
+			.query_map(params![article.id], |row| {
+				Ok(ExportedRevision {
+					revision: row.get(0)?,
+					date_created: row.get(1)?,
+					summary: row.get(2)?,
+					old_title: row.get(3)?,
+					new_title: row.get(4)?,
+					text: row.get(5)?,
 				})
-			})
-			.unwrap();
+			})?
+			.collect::<rusqlite::Result<_>>()?;
+		Ok(ExportedArticle {
+			article,
+			tags,
+			revisions,
+		})
+	}
+
+	/// Import the articles of a document written by `export_all`, each
+	/// in its own transaction. `links` gives the articles a text
+	/// references (see `set_article_links`). Articles keep their dates,
+	/// revision, tags and revision history, and their ID and slug if
+	/// they are free. References between the articles only stay
+	/// correct if the IDs were free, e.g. when importing into a new
+	/// wiki. Like the export, the document is read one article at a
+	/// time.
+	pub fn import(
+		&mut self,
+		reader: impl std::io::Read,
+		on_conflict: ImportConflict,
+		links: impl Fn(&str) -> Vec<ItemId>,
+	) -> Result<ImportSummary, ImportError> {
+		use serde::de::DeserializeSeed;
+
+		let mut importer = Importer {
+			db: self,
+			on_conflict,
+			links,
+			summary: ImportSummary::default(),
+			refused: None,
+		};
+		let mut deserializer = serde_json::Deserializer::from_reader(reader);
+		let result = (&mut importer)
+			.deserialize(&mut deserializer)
+			.and_then(|()| deserializer.end());
+		match (result, importer.refused) {
+			(_, Some(refused)) => Err(refused),
+			(Err(err), None) => Err(ImportError::Damaged {
+				err,
+				partial: importer.summary,
+			}),
+			(Ok(()), None) => Ok(importer.summary),
+		}
+	}
 
-		let mut articles = Vec::new();
-		for article in article_iter {
-			articles.push(article.unwrap());
+	/// Store one article of an import, `None` if it was skipped
+	fn import_article(
+		&mut self,
+		article: ImportedArticle,
+		on_conflict: ImportConflict,
+		links: &impl Fn(&str) -> Vec<ItemId>,
+	) -> Result<Option<ItemId>, DbError> {
+		let title = Database::normalize_title(&article.title);
+		if title.is_empty() {
+			return Err(DbError::EmptyTitle);
 		}
+		let text = Database::filter_chars(&article.text);
+		let refs = links(&text);
+		let result = self.conn.transaction().and_then(|tx| {
+			let owner: Option<ItemId> = tx
+				.query_row(
+					"SELECT id FROM article WHERE title = ? COLLATE NOCASE",
+					params![title],
+					|row| row.get(0),
+				)
+				.or_else(|err| match err {
+					rusqlite::Error::QueryReturnedNoRows => Ok(None),
+					err => Err(err),
+				})?;
+			let (owner, title) = match (owner, on_conflict) {
+				(None, _) => (None, title),
+				(Some(_), ImportConflict::Skip) => return Ok(None),
+				(Some(owner), ImportConflict::Overwrite) => (Some(owner), title),
+				(Some(_), ImportConflict::Rename) => {
+					let mut suffix = 2;
+					let title = loop {
+						let candidate = format!("{} ({})", title, suffix);
+						if !Database::title_taken(&tx, &candidate, None)? {
+							break candidate;
+						}
+						suffix += 1;
+					};
+					(None, title)
+				}
+			};
+
+			let id = match owner {
+				Some(id) => {
+					tx.execute(
+						"UPDATE article SET title = ?, text = ?, date_created = ?, date_modified = ?, revision = ?, date_deleted = NULL WHERE id = ?",
+						params![title, text, article.date_created, article.date_modified, article.revision, id],
+					)?;
+					tx.execute("DELETE FROM article_tag WHERE article_id = ?", params![id])?;
+					tx.execute(
+						"DELETE FROM article_revision WHERE article_id = ?",
+						params![id],
+					)?;
+					id
+				}
+				None => {
+					let id_free = match article.id {
+						Some(id) => !tx.query_row(
+							"SELECT EXISTS(SELECT 1 FROM article WHERE id = ?)",
+							params![id],
+							|row| row.get::<_, bool>(0),
+						)?,
+						None => false,
+					};
+					let slug_free = !article.slug.is_empty()
+						&& slug::slugify(&article.slug) == article.slug
+						&& !tx.query_row(
+							"SELECT EXISTS(SELECT 1 FROM article WHERE slug = ?)",
+							params![article.slug],
+							|row| row.get::<_, bool>(0),
+						)?;
+					let slug = if slug_free {
+						article.slug
+					} else {
+						unique_slug(&tx, &title, None)?
+					};
+					tx.execute(
+						"INSERT INTO article (id, title, text, date_created, date_modified, revision, slug) VALUES (?, ?, ?, ?, ?, ?, ?)",
+						params![
+							article.id.filter(|_| id_free),
+							title,
+							text,
+							article.date_created,
+							article.date_modified,
+							article.revision,
+							slug
+						],
+					)?;
+					ItemId::try_from(tx.last_insert_rowid())
+						.map_err(|_| rusqlite::Error::IntegralValueOutOfRange(0, tx.last_insert_rowid()))?
+				}
+			};
 
-		Some(articles)
+			Database::store_imported(&tx, id, &article.tags, &article.revisions, &refs)?;
+			tx.commit()?;
+			Ok(Some(id))
+		});
+		result.map_err(DbError::from)
 	}
 
-	/// Search article
-	pub fn search_articles(&mut self, search_term: &str) -> Option<Vec<Article>> {
-		let search_term = format!(
-			"%{}%",
-			str::replace(search_term, "^", "^^")
-				.replace("%", "^%")
-				.replace("_", "^_")
-		);
-		let mut stmt = self
+	/// Store the tags, revisions and links of an imported article.
+	/// The old ones of the tags and revisions must be deleted before.
+	fn store_imported(
+		tx: &rusqlite::Transaction,
+		id: ItemId,
+		article_tags: &[String],
+		revisions: &[ImportedRevision],
+		refs: &[ItemId],
+	) -> rusqlite::Result<()> {
+		for tag in article_tags {
+			if let Some(tag) = tags::normalize(&Database::filter_chars(tag)) {
+				tx.execute(
+					"INSERT OR IGNORE INTO article_tag (article_id, tag) VALUES (?, ?)",
+					params![id, tag],
+				)?;
+			}
+		}
+		if revisions.is_empty() {
+			tx.execute(
+				"INSERT INTO article_revision (article_id, revision, date_created, text)
+					SELECT id, revision, date_modified, text FROM article WHERE id = ?",
+				params![id],
+			)?;
+		}
+		for revision in revisions {
+			tx.execute(
+				"INSERT OR REPLACE INTO article_revision (article_id, revision, date_created, summary, old_title, new_title, text)
+					VALUES (?, ?, ?, ?, ?, ?, ?)",
+				params![
+					id,
+					revision.revision,
+					revision.date_created,
+					Database::normalize_edit_summary(&revision.summary),
+					revision.old_title,
+					revision.new_title,
+					revision.text
+				],
+			)?;
+		}
+		tx.execute("DELETE FROM article_link WHERE src = ?", params![id])?;
+		for dst in refs.iter().filter(|&&dst| dst != id) {
+			tx.execute(
+				"INSERT OR IGNORE INTO article_link (src, dst, kind) VALUES (?, ?, ?)",
+				params![id, dst, LinkKind::ArticleRef.as_sql()],
+			)?;
+		}
+		Ok(())
+	}
+
+	/// Replace the article `id` with `article`, including its tags and
+	/// revision history, in one transaction. The ID and slug stay, the
+	/// ID in `article` is ignored. Like `import`, `links` gives the
+	/// articles the text references. The history is stored as it is,
+	/// check it with `ImportedArticle::check_history` before.
+	pub fn replace_article(
+		&mut self,
+		id: ItemId,
+		article: ImportedArticle,
+		links: impl Fn(&str) -> Vec<ItemId>,
+	) -> Result<(), DbError> {
+		let title = Database::normalize_title(&article.title);
+		if title.is_empty() {
+			return Err(DbError::EmptyTitle);
+		}
+		let text = Database::filter_chars(&article.text);
+		let refs = links(&text);
+		let result = self.conn.transaction().and_then(|tx| {
+			if Database::title_taken(&tx, &title, Some(id))? {
+				return Ok(Err(DbError::AlreadyExists));
+			}
+			let updated = tx.execute(
+				"UPDATE article SET title = ?, text = ?, date_created = ?, date_modified = ?, revision = ? WHERE id = ? AND date_deleted IS NULL",
+				params![title, text, article.date_created, article.date_modified, article.revision, id],
+			)?;
+			if updated == 0 {
+				return Ok(Err(DbError::NotFound));
+			}
+			tx.execute("DELETE FROM article_tag WHERE article_id = ?", params![id])?;
+			tx.execute(
+				"DELETE FROM article_revision WHERE article_id = ?",
+				params![id],
+			)?;
+			Database::store_imported(&tx, id, &article.tags, &article.revisions, &refs)?;
+			tx.commit()?;
+			Ok(Ok(()))
+		});
+		match result {
+			Ok(result) => result,
+			Err(err) => {
+				log::error!("Could not replace article {}: {:?}", id, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Up to `limit` articles starting at `offset` in the order of
+	/// their IDs, only what is needed to link to them
+	pub fn get_article_locations(
+		&mut self,
+		offset: u32,
+		limit: u32,
+	) -> Option<Vec<ArticleLocation>> {
+		let result = self
 			.conn
-			.prepare("SELECT id, title, text, date_created, date_modified, revision FROM article WHERE title LIKE ? ESCAPE '^' OR text LIKE ? ESCAPE '^'")
-			.unwrap();
-		let article_iter = stmt
-			.query_map(params![search_term, search_term], |row| {
-				Ok(Article {
+			.prepare(
+				"SELECT id, slug, date_modified FROM article WHERE date_deleted IS NULL ORDER BY id LIMIT ? OFFSET ?",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![limit, offset], |row| {
+					Ok(ArticleLocation {
+						id: row.get(0)?,
+						slug: row.get(1)?,
+						date_modified: row.get(2)?,
+					})
+				})?
+				.collect::<rusqlite::Result<Vec<_>>>()
+			});
+		match result {
+			Ok(locations) => Some(locations),
+			Err(err) => {
+				log::error!("Could not get article locations: {:?}", err);
+				None
+			}
+		}
+	}
+
+	/// Numbers of rows and the size of the database file
+	pub fn get_stats(&mut self) -> Option<DatabaseStats> {
+		let count = |conn: &Connection, sql: &str| -> rusqlite::Result<u64> {
+			conn.query_row(sql, params![], |row| row.get(0))
+		};
+		let result = (|| -> rusqlite::Result<DatabaseStats> {
+			Ok(DatabaseStats {
+				articles: count(
+					&self.conn,
+					"SELECT COUNT(*) FROM article WHERE date_deleted IS NULL",
+				)?,
+				deleted_articles: count(
+					&self.conn,
+					"SELECT COUNT(*) FROM article WHERE date_deleted IS NOT NULL",
+				)?,
+				text_bytes: count(
+					&self.conn,
+					"SELECT COALESCE(SUM(LENGTH(CAST(text AS BLOB))), 0) FROM article",
+				)?,
+				tags: count(&self.conn, "SELECT COUNT(DISTINCT tag) FROM article_tag")?,
+				categories: count(&self.conn, "SELECT COUNT(*) FROM category")?,
+				links: count(&self.conn, "SELECT COUNT(*) FROM article_link")?,
+				title_redirects: count(&self.conn, "SELECT COUNT(*) FROM title_redirect")?,
+				favicons: count(&self.conn, "SELECT COUNT(*) FROM favicon")?,
+				page_size: count(&self.conn, "PRAGMA page_size")?,
+				pages: count(&self.conn, "PRAGMA page_count")?,
+				free_pages: count(&self.conn, "PRAGMA freelist_count")?,
+			})
+		})();
+		match result {
+			Ok(stats) => Some(stats),
+			Err(err) => {
+				log::error!("Could not get database statistics: {:?}", err);
+				None
+			}
+		}
+	}
+
+	/// Up to `limit` articles starting at `offset`, without their text,
+	/// and the total number of articles
+	pub fn get_articles_page(
+		&mut self,
+		offset: u32,
+		limit: u32,
+		order: ArticleOrder,
+	) -> Result<(Vec<ArticleSummary>, u32), DbError> {
+		let result = self.conn.prepare(&format!(
+			"SELECT id, title, date_created, date_modified, slug FROM article WHERE date_deleted IS NULL ORDER BY {} LIMIT ? OFFSET ?",
+			order.sql()
+		))
+		.and_then(|mut stmt| {
+			stmt.query_map(params![limit, offset], |row| {
+				Ok(ArticleSummary {
 					id: row.get(0)?,
 					title: row.get(1)?,
-					text: row.get(2)?,
-					date_created: row.get(3)?,
-					date_modified: row.get(4)?,
-					revision: row.get(5)?,
+					date_created: row.get(2)?,
+					date_modified: row.get(3)?,
+					slug: row.get(4)?,
 				})
-			})
-			.unwrap();
+			})?
+			.collect::<rusqlite::Result<Vec<_>>>()
+		});
+		match result {
+			Ok(articles) => Ok((articles, self.count_articles()?)),
+			Err(err) => {
+				log::error!("Could not get page of articles: {:?}", err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Search article
+	pub fn search_articles(&mut self, search_term: &str) -> Result<Vec<Article>, DbError> {
+		let pattern = sql_util::escape_like(search_term).contains();
+		let result = self
+			.conn
+			.prepare(&format!(
+				"SELECT id, title, text, date_created, date_modified, revision, slug FROM article WHERE {} AND date_deleted IS NULL",
+				sql_util::like_any(&["title", "text"], 1)
+			))
+			.and_then(|mut stmt| {
+				stmt.query_map(params![pattern], |row| {
+					Ok(Article {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						text: row.get(2)?,
+						date_created: row.get(3)?,
+						date_modified: row.get(4)?,
+						revision: row.get(5)?,
+						slug: row.get(6)?,
+					})
+				})?
+				.collect()
+			});
+		result.map_err(|err| {
+			log::error!("Could not search articles: {:?}", err);
+			DbError::from(err)
+		})
+	}
+
+	/// Whether the full-text index exists, otherwise only
+	/// `search_articles` can be used
+	pub fn has_fts(&mut self) -> bool {
+		has_table(&self.conn, "article_fts").unwrap_or_else(|err| {
+			log::error!("Could not check for the full-text index: {:?}", err);
+			false
+		})
+	}
+
+	/// Full-text search, most relevant articles first. Matches in the
+	/// title weigh more than matches in the text. The search term is
+	/// taken literally (see `sql_util::fts_query`), a term without words
+	/// finds nothing.
+	pub fn search_articles_fts(&mut self, search_term: &str) -> Result<Vec<SearchHit>, DbError> {
+		let Some(query) = sql_util::fts_query(search_term) else {
+			return Ok(Vec::new());
+		};
+		let result: rusqlite::Result<Vec<SearchHit>> = self
+			.conn
+			.prepare(
+				"SELECT article.id, article.title, article.text, date_created, date_modified, revision, slug, bm25(article_fts, 10.0, 1.0) AS rank
+					FROM article_fts JOIN article ON article.id = article_fts.rowid
+					WHERE article_fts MATCH ? AND date_deleted IS NULL
+					ORDER BY rank, article.id",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![query], |row| {
+					Ok(SearchHit {
+						article: Article {
+							id: row.get(0)?,
+							title: row.get(1)?,
+							text: row.get(2)?,
+							date_created: row.get(3)?,
+							date_modified: row.get(4)?,
+							revision: row.get(5)?,
+							slug: row.get(6)?,
+						},
+						rank: row.get(7)?,
+					})
+				})?
+				.collect()
+			});
+		result.map_err(|err| {
+			log::error!("Full-text search for {:?} failed: {:?}", query, err);
+			DbError::from(err)
+		})
+	}
 
-		let mut articles = Vec::new();
-		for article in article_iter {
-			articles.push(article.unwrap());
+	/// Move an article to the trash. Deleted articles are hidden from
+	/// all lookups except the `*_include_deleted` ones, but keep their
+	/// ID and title, so they can be restored with `restore_article`.
+	pub fn delete_article(&mut self, id: ItemId) -> Result<(), DbError> {
+		let now = self.write_time()?;
+		match self.conn.execute(
+			"UPDATE article SET date_deleted = ? WHERE id = ? AND date_deleted IS NULL",
+			params![now, id],
+		) {
+			Ok(0) => Err(DbError::NotFound),
+			Ok(_) => {
+				log::info!("Article {} deleted", id);
+				Ok(())
+			}
+			Err(err) => {
+				log::error!("Could not delete article {}: {:?}", id, err);
+				Err(DbError::from(err))
+			}
 		}
+	}
 
-		Some(articles)
+	/// Take an article out of the trash
+	pub fn restore_article(&mut self, id: ItemId) -> Result<(), DbError> {
+		match self.conn.execute(
+			"UPDATE article SET date_deleted = NULL WHERE id = ? AND date_deleted IS NOT NULL",
+			params![id],
+		) {
+			Ok(0) => Err(DbError::NotFound),
+			Ok(_) => {
+				log::info!("Article {} restored", id);
+				Ok(())
+			}
+			Err(err) => {
+				log::error!("Could not restore article {}: {:?}", id, err);
+				Err(DbError::from(err))
+			}
+		}
 	}
 
-	pub fn get_article_title(&mut self, id: ItemId) -> Option<String> {
-		let mut stmt = self
+	/// Deleted articles as `(id, title, date_deleted)`, most recently
+	/// deleted first
+	pub fn get_deleted_articles(&mut self) -> Option<Vec<(ItemId, String, UtcTimestamp)>> {
+		let result: rusqlite::Result<Vec<_>> = self
 			.conn
-			.prepare("SELECT title FROM article WHERE id = ?")
-			.unwrap();
-		let mut article_iter = stmt.query_map(params![id], |row| Ok(row.get(0)?)).unwrap();
+			.prepare(
+				"SELECT id, title, date_deleted FROM article WHERE date_deleted IS NOT NULL ORDER BY date_deleted DESC",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+					.collect()
+			});
+		match result {
+			Ok(articles) => Some(articles),
+			Err(err) => {
+				log::error!("Could not get deleted articles: {:?}", err);
+				None
+			}
+		}
+	}
 
-		if let Some(Ok(title)) = article_iter.next() {
-			Some(title)
-		} else {
-			log::debug!("Could not get tile for article with id {}", id);
-			None
+	/// Number of articles, cheaper than `get_all_articles`
+	pub fn count_articles(&mut self) -> Result<u32, DbError> {
+		let result = self.conn.query_row(
+			"SELECT COUNT(*) FROM article WHERE date_deleted IS NULL",
+			params![],
+			|row| row.get(0),
+		);
+		result.map_err(|err| {
+			log::error!("Could not count articles: {:?}", err);
+			DbError::from(err)
+		})
+	}
+
+	/// Number of articles changed after `time`
+	pub fn count_articles_modified_after(&mut self, time: UtcTimestamp) -> Option<u32> {
+		let result = self.conn.query_row(
+			"SELECT COUNT(*) FROM article WHERE date_modified > ? AND date_deleted IS NULL",
+			params![time],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(count) => Some(count),
+			Err(err) => {
+				log::error!("Could not count modified articles: {:?}", err);
+				None
+			}
 		}
 	}
 
-	pub fn update_article(
-		&mut self,
-		id: ItemId,
-		title: Option<&str>,
-		text: Option<&str>,
-	) -> Result<usize, ()> {
-		let title = title.map(|s| Database::filter_chars(s));
-		let text = text.map(|s| Database::filter_chars(s));
+	/// Title of an article that is not deleted
+	pub fn get_article_title(&mut self, id: ItemId) -> Option<String> {
+		match self.get_article_title_include_deleted(id) {
+			Some((title, false)) => Some(title),
+			_ => None,
+		}
+	}
 
-		let mut query = "UPDATE article SET".to_string();
+	/// Title of an article and whether it is deleted, for the views
+	/// that show deleted articles as well
+	pub fn get_article_title_include_deleted(&mut self, id: ItemId) -> Option<(String, bool)> {
+		let result = self.conn.query_row(
+			"SELECT title, date_deleted IS NOT NULL FROM article WHERE id = ?",
+			params![id],
+			|row| Ok((row.get(0)?, row.get(1)?)),
+		);
+		match result {
+			Ok(title) => Some(title),
+			Err(rusqlite::Error::QueryReturnedNoRows) => {
+				log::debug!("Could not get title for article with id {}", id);
+				None
+			}
+			Err(err) => {
+				log::error!("Could not get title of article {}: {:?}", id, err);
+				None
+			}
+		}
+	}
 
-		let now = Utc::now().naive_utc();
-		let mut arguments: Vec<Box<dyn rusqlite::ToSql>> = vec![];
+	/// Article with exactly the given title, or otherwise the oldest one
+	/// whose title only differs in case. Like the title sort order, case
+	/// is only ignored for ASCII letters.
+	pub fn get_article_id_by_title(&mut self, title: &str) -> Result<Option<ItemId>, DbError> {
+		let result = self.conn.query_row(
+			"SELECT id FROM article WHERE title = ?1 COLLATE NOCASE AND date_deleted IS NULL
+				ORDER BY title = ?1 DESC, id LIMIT 1",
+			params![Database::normalize_title(title)],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(id) => Ok(Some(id)),
+			Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+			Err(err) => {
+				log::error!("Could not look up article by title: {:?}", err);
+				Err(DbError::from(err))
+			}
+		}
+	}
 
-		let mut need_delim = false;
-		let delim = ',';
+	/// Article that has the title, including deleted articles, which keep
+	/// their title until they are restored. No other article can be
+	/// created with it or renamed to it, nor with a variant that only
+	/// differs in case or whitespace.
+	pub fn get_title_owner(&mut self, title: &str) -> Option<ItemId> {
+		let result = self.conn.query_row(
+			"SELECT id FROM article WHERE title = ?1 COLLATE NOCASE ORDER BY title = ?1 DESC, id LIMIT 1",
+			params![Database::normalize_title(title)],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(id) => Some(id),
+			Err(rusqlite::Error::QueryReturnedNoRows) => None,
+			Err(err) => {
+				log::error!("Could not look up owner of title: {:?}", err);
+				None
+			}
+		}
+	}
 
-		for (param, sql_str) in &[(&title, " title = ? "), (&text, " text = ? ")] {
-			if let Some(param) = param {
-				// Only update the SQL column if parameter is not None
-				// otherwise let it keep its original value
-				arguments.push(Box::new(param.to_sql().unwrap()));
-				if need_delim {
-					query.push(delim);
-				} else {
-					need_delim = true
-				}
-				query.push_str(sql_str);
+	/// Article with the given title, or that had it before it was
+	/// renamed. The bool is true if the article was found through a
+	/// former title. A current title always takes precedence over a
+	/// former one, and deleted articles are never found.
+	pub fn resolve_title(&mut self, title: &str) -> Result<Option<(ItemId, bool)>, DbError> {
+		if let Some(id) = self.get_article_id_by_title(title)? {
+			return Ok(Some((id, false)));
+		}
+		let result = self.conn.query_row(
+			"SELECT article.id FROM title_redirect
+				JOIN article ON article.id = title_redirect.article_id
+				WHERE title_redirect.old_title = ? AND article.date_deleted IS NULL",
+			params![title],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(id) => Ok(Some((id, true))),
+			Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+			Err(err) => {
+				log::error!("Could not look up title redirect: {:?}", err);
+				Err(DbError::from(err))
 			}
 		}
+	}
 
-		arguments.push(Box::new(now.to_sql().unwrap()));
-		query.push(delim);
-		query.push_str(" date_modified = ? ");
+	/// Add a tag to an article, it is normalized first (see
+	/// `tags::normalize`). Adding a tag the article already has, or a
+	/// tag that is empty after normalization, does nothing.
+	pub fn add_tag(&mut self, id: ItemId, tag: &str) -> Result<(), DbError> {
+		let Some(tag) = tags::normalize(&Database::filter_chars(tag)) else {
+			return Ok(());
+		};
+		let result = self.conn.execute(
+			"INSERT OR IGNORE INTO article_tag (article_id, tag) SELECT id, ? FROM article WHERE id = ? AND date_deleted IS NULL",
+			params![tag, id],
+		);
+		match result {
+			Ok(0) if self.get_article_title(id).is_none() => Err(DbError::NotFound),
+			Ok(_) => Ok(()),
+			Err(err) => {
+				log::error!("Could not add tag {:?} to article {}: {:?}", tag, id, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
 
-		query.push(delim);
-		query.push_str(" revision = revision + 1 ");
+	/// Remove a tag from an article. Once no article has the tag
+	/// anymore, it is gone entirely.
+	pub fn remove_tag(&mut self, id: ItemId, tag: &str) -> Result<(), DbError> {
+		let Some(tag) = tags::normalize(&Database::filter_chars(tag)) else {
+			return Err(DbError::NotFound);
+		};
+		match self.conn.execute(
+			"DELETE FROM article_tag WHERE article_id = ? AND tag = ?",
+			params![id, tag],
+		) {
+			Ok(0) => Err(DbError::NotFound),
+			Ok(_) => Ok(()),
+			Err(err) => {
+				log::error!(
+					"Could not remove tag {:?} from article {}: {:?}",
+					tag,
+					id,
+					err
+				);
+				Err(DbError::from(err))
+			}
+		}
+	}
 
-		arguments.push(Box::new(id.to_sql().unwrap()));
-		query.push_str("WHERE id = ?");
+	/// All tags in alphabetical order, with the number of articles that
+	/// have them. Articles in the trash count as well, they keep their
+	/// tags.
+	pub fn get_tag_counts(&mut self) -> Option<Vec<(String, u32)>> {
+		let result: rusqlite::Result<Vec<(String, u32)>> = self
+			.conn
+			.prepare("SELECT tag, COUNT(*) FROM article_tag GROUP BY tag ORDER BY tag")
+			.and_then(|mut stmt| {
+				stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+					.collect()
+			});
+		match result {
+			Ok(tags) => Some(tags),
+			Err(err) => {
+				log::error!("Could not count tags: {:?}", err);
+				None
+			}
+		}
+	}
 
-		match self
+	/// Normalized names of a tag rename or merge, `None` if one of them
+	/// is empty
+	fn tag_pair(from: &str, to: &str) -> Option<(String, String)> {
+		let from = tags::normalize(&Database::filter_chars(from))?;
+		let to = tags::normalize(&Database::filter_chars(to))?;
+		Some((from, to))
+	}
+
+	fn tag_exists(tx: &rusqlite::Transaction, tag: &str) -> rusqlite::Result<bool> {
+		tx.query_row(
+			"SELECT EXISTS(SELECT 1 FROM article_tag WHERE tag = ?)",
+			params![tag],
+			|row| row.get(0),
+		)
+	}
+
+	/// Give all articles with the tag `from` the tag `to` instead. Fails
+	/// with `DbError::AlreadyExists` if `to` is in use, such tags have
+	/// to be merged with `merge_tag`. As tags are stored case-folded,
+	/// a rename that only changes the case leaves the tag as it is.
+	pub fn rename_tag(&mut self, from: &str, to: &str) -> Result<(), DbError> {
+		let Some((from, to)) = Database::tag_pair(from, to) else {
+			return Err(DbError::NotFound);
+		};
+		let result = self.conn.transaction().and_then(|tx| {
+			if !Database::tag_exists(&tx, &from)? {
+				return Ok(Err(DbError::NotFound));
+			}
+			if from == to {
+				return Ok(Ok(0));
+			}
+			if Database::tag_exists(&tx, &to)? {
+				return Ok(Err(DbError::AlreadyExists));
+			}
+			let renamed = tx.execute(
+				"UPDATE article_tag SET tag = ? WHERE tag = ?",
+				params![to, from],
+			)?;
+			tx.commit()?;
+			Ok(Ok(renamed))
+		});
+		match result {
+			Ok(Ok(renamed)) => {
+				log::info!("Tag {:?} renamed to {:?} on {} articles", from, to, renamed);
+				Ok(())
+			}
+			Ok(Err(err)) => Err(err),
+			Err(err) => {
+				log::error!("Could not rename tag {:?}: {:?}", from, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Give all articles with the tag `from` the tag `into` instead,
+	/// articles that already have `into` keep it once. Afterwards, no
+	/// article has `from` anymore.
+	pub fn merge_tag(&mut self, from: &str, into: &str) -> Result<(), DbError> {
+		let Some((from, into)) = Database::tag_pair(from, into) else {
+			return Err(DbError::NotFound);
+		};
+		let result = self.conn.transaction().and_then(|tx| {
+			if !Database::tag_exists(&tx, &from)? {
+				return Ok(Err(DbError::NotFound));
+			}
+			if from == into {
+				return Ok(Ok(()));
+			}
+			tx.execute(
+				"INSERT OR IGNORE INTO article_tag (article_id, tag) SELECT article_id, ? FROM article_tag WHERE tag = ?",
+				params![into, from],
+			)?;
+			tx.execute("DELETE FROM article_tag WHERE tag = ?", params![from])?;
+			tx.commit()?;
+			Ok(Ok(()))
+		});
+		match result {
+			Ok(Ok(())) => {
+				log::info!("Tag {:?} merged into {:?}", from, into);
+				Ok(())
+			}
+			Ok(Err(err)) => Err(err),
+			Err(err) => {
+				log::error!("Could not merge tag {:?}: {:?}", from, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Replace the articles `src` references with `dsts`, in one
+	/// transaction so no stale link survives. References of an article
+	/// to itself are left out.
+	pub fn set_article_links(&mut self, src: ItemId, dsts: &[ItemId]) -> Result<(), DbError> {
+		let kind = LinkKind::ArticleRef.as_sql();
+		let result = self.conn.transaction().and_then(|tx| {
+			tx.execute(
+				"DELETE FROM article_link WHERE src = ? AND kind = ?",
+				params![src, kind],
+			)?;
+			{
+				let mut insert = tx.prepare(
+					"INSERT OR IGNORE INTO article_link (src, dst, kind) VALUES (?, ?, ?)",
+				)?;
+				for &dst in dsts.iter().filter(|&&dst| dst != src) {
+					insert.execute(params![src, dst, kind])?;
+				}
+			}
+			tx.commit()
+		});
+		result.map_err(|err| {
+			log::error!("Could not store the links of article {}: {:?}", src, err);
+			DbError::from(err)
+		})
+	}
+
+	/// Dates in the content that are outside of the plausible dates at
+	/// `now`, or that are not dates this wiki writes
+	pub fn find_bad_dates(&mut self, now: UtcTimestamp) -> Option<Vec<BadDate>> {
+		let window = self.date_window;
+		let mut bad = Vec::new();
+		for &(table, key, column) in DATE_COLUMNS {
+			let result = self
+				.conn
+				.prepare(&format!(
+					"SELECT CAST({key} AS TEXT), {column} FROM {table} WHERE {column} IS NOT NULL ORDER BY rowid"
+				))
+				.and_then(|mut stmt| {
+					stmt.query_map(params![], |row| {
+						let time = UtcTimestamp::column_result(row.get_ref(1)?).ok();
+						let value = match row.get_ref(1)? {
+							rusqlite::types::ValueRef::Text(text) => {
+								String::from_utf8_lossy(text).into_owned()
+							}
+							other => format!("{:?}", other),
+						};
+						Ok((row.get::<_, String>(0)?, time, value))
+					})?
+					.collect::<rusqlite::Result<Vec<_>>>()
+				});
+			let rows = match result {
+				Ok(rows) => rows,
+				Err(err) => {
+					log::error!("Could not check the dates of {}: {:?}", table, err);
+					return None;
+				}
+			};
+			for (key, time, value) in rows {
+				if !time.is_some_and(|time| window.contains(time, now)) {
+					bad.push(BadDate {
+						table,
+						key,
+						column,
+						value,
+					});
+				}
+			}
+		}
+		Some(bad)
+	}
+
+	/// Set the dates `find_bad_dates` finds to `now`, as if the content
+	/// had been written now. Returns the number of dates changed.
+	pub fn fix_bad_dates(&mut self, now: UtcTimestamp) -> Result<usize, DbError> {
+		let window = self.date_window;
+		if !window.contains(now, now) {
+			return Err(DbError::DateOutOfRange(now));
+		}
+		let result = self.conn.transaction().and_then(|tx| {
+			let mut fixed = 0;
+			for &(table, _, column) in DATE_COLUMNS {
+				let rowids: Vec<i64> = tx
+					.prepare(&format!(
+						"SELECT rowid, {column} FROM {table} WHERE {column} IS NOT NULL"
+					))?
+					.query_map(params![], |row| {
+						let time = UtcTimestamp::column_result(row.get_ref(1)?).ok();
+						Ok((row.get(0)?, time))
+					})?
+					.filter_map(|row| match row {
+						Ok((_, Some(time))) if window.contains(time, now) => None,
+						Ok((rowid, _)) => Some(Ok(rowid)),
+						Err(err) => Some(Err(err)),
+					})
+					.collect::<rusqlite::Result<_>>()?;
+				for rowid in rowids {
+					fixed += tx.execute(
+						&format!("UPDATE {table} SET {column} = ? WHERE rowid = ?"),
+						params![now, rowid],
+					)?;
+				}
+			}
+			tx.commit()?;
+			Ok(fixed)
+		});
+		match result {
+			Ok(fixed) => {
+				log::info!("Set {} implausible dates to {}", fixed, now);
+				Ok(fixed)
+			}
+			Err(err) => {
+				log::error!("Could not fix dates: {:?}", err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// The recorded edits of an article, newest first. The last one is
+	/// the creation as revision 0, unless the article is older than the
+	/// recording of it.
+	pub fn get_revisions(&mut self, id: ItemId) -> Option<Vec<Revision>> {
+		let result: rusqlite::Result<Vec<Revision>> = self
 			.conn
-			.execute(&query, rusqlite::params_from_iter(arguments.iter()))
-		{
-			Ok(updated) => {
-				log::debug!("Article update: {} row successfully updated", updated);
-				Ok(updated)
+			.prepare(
+				"SELECT revision, date_created, summary, old_title, new_title, length(CAST(text AS BLOB)) FROM article_revision
+					WHERE article_id = ? ORDER BY revision DESC",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![id], Database::revision_row)?
+					.collect()
+			});
+		match result {
+			Ok(revisions) => Some(revisions),
+			Err(err) => {
+				log::error!("Could not get revisions of article {}: {:?}", id, err);
+				None
 			}
+		}
+	}
+
+	/// Title changes of an article since `since`, oldest first
+	pub fn get_title_changes(&mut self, id: ItemId, since: UtcTimestamp) -> Option<Vec<Revision>> {
+		let result: rusqlite::Result<Vec<Revision>> = self
+			.conn
+			.prepare(
+				"SELECT revision, date_created, summary, old_title, new_title, length(CAST(text AS BLOB)) FROM article_revision
+					WHERE article_id = ? AND date_created >= ? AND new_title IS NOT NULL
+					ORDER BY revision",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![id, since], Database::revision_row)?
+					.collect()
+			});
+		match result {
+			Ok(changes) => Some(changes),
 			Err(err) => {
-				log::error!("Article update failed: {:?}", err);
-				Err(())
+				log::error!("Could not get title changes of article {}: {:?}", id, err);
+				None
 			}
 		}
 	}
+
+	fn revision_row(row: &rusqlite::Row) -> rusqlite::Result<Revision> {
+		let old_title: Option<String> = row.get(3)?;
+		let new_title: Option<String> = row.get(4)?;
+		Ok(Revision {
+			revision: row.get(0)?,
+			date_created: row.get(1)?,
+			summary: row.get(2)?,
+			title_change: old_title
+				.zip(new_title)
+				.map(|(old_title, new_title)| TitleChange {
+					old_title,
+					new_title,
+				}),
+			size: row.get(5)?,
+		})
+	}
+
+	/// Text of an article as it was in `revision`, `None` if the
+	/// revision does not exist or its text was not kept
+	pub fn get_revision_text(&mut self, id: ItemId, revision: i64) -> Option<String> {
+		let result = self.conn.query_row(
+			"SELECT text FROM article_revision WHERE article_id = ? AND revision = ?",
+			params![id, revision],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(text) => text,
+			Err(rusqlite::Error::QueryReturnedNoRows) => None,
+			Err(err) => {
+				log::error!(
+					"Could not get revision {} of article {}: {:?}",
+					revision,
+					id,
+					err
+				);
+				None
+			}
+		}
+	}
+
+	/// Title of an article in `revision`, as far as the recorded renames
+	/// tell. Renames before they were recorded are not known.
+	pub fn get_revision_title(&mut self, id: ItemId, revision: i64) -> Option<String> {
+		let result = self.conn.query_row(
+			"SELECT COALESCE(
+				(SELECT new_title FROM article_revision WHERE article_id = ?1 AND revision <= ?2
+					AND new_title IS NOT NULL ORDER BY revision DESC LIMIT 1),
+				(SELECT old_title FROM article_revision WHERE article_id = ?1 AND revision > ?2
+					AND old_title IS NOT NULL ORDER BY revision LIMIT 1),
+				(SELECT title FROM article WHERE id = ?1))",
+			params![id, revision],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(title) => title,
+			Err(err) => {
+				log::error!(
+					"Could not get title of article {} in revision {}: {:?}",
+					id,
+					revision,
+					err
+				);
+				None
+			}
+		}
+	}
+
+	/// Summary of the edit that produced `revision` of an article, empty
+	/// if there is none or the edit was not recorded
+	pub fn get_edit_summary(&mut self, id: ItemId, revision: i64) -> Option<String> {
+		let result = self
+			.conn
+			.query_row(
+				"SELECT summary FROM article_revision WHERE article_id = ? AND revision = ?",
+				params![id, revision],
+				|row| row.get(0),
+			)
+			.or_else(|err| match err {
+				rusqlite::Error::QueryReturnedNoRows => Ok(String::new()),
+				err => Err(err),
+			});
+		match result {
+			Ok(summary) => Some(summary),
+			Err(err) => {
+				log::error!("Could not get edit summary of article {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Articles that reference `dst`, in alphabetical order. Deleted
+	/// articles keep their links for when they are restored, but are
+	/// not listed.
+	pub fn get_backlinks(&mut self, dst: ItemId) -> Option<Vec<ArticleSummary>> {
+		let result: rusqlite::Result<Vec<ArticleSummary>> = self
+			.conn
+			.prepare(
+				"SELECT article.id, title, date_created, date_modified, slug FROM article
+					JOIN article_link ON article_link.src = article.id
+					WHERE article_link.dst = ? AND date_deleted IS NULL ORDER BY title COLLATE NOCASE, article.id",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![dst], |row| {
+					Ok(ArticleSummary {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						date_created: row.get(2)?,
+						date_modified: row.get(3)?,
+						slug: row.get(4)?,
+					})
+				})?
+				.collect()
+			});
+		match result {
+			Ok(articles) => Some(articles),
+			Err(err) => {
+				log::error!("Could not get links to article {}: {:?}", dst, err);
+				None
+			}
+		}
+	}
+
+	/// The links of up to `sources` articles with an ID above `after`,
+	/// ordered by source, target and kind. Pages always end with all
+	/// links of a source, so the last source is the `after` of the next
+	/// page. Links from deleted articles are left out as for
+	/// `get_backlinks`.
+	pub fn get_links(&mut self, after: Option<ItemId>, sources: u32) -> Option<Vec<ArticleLink>> {
+		let result: rusqlite::Result<Vec<ArticleLink>> = self
+			.conn
+			.prepare(
+				"SELECT src, dst, kind FROM article_link WHERE src IN (
+					SELECT DISTINCT src FROM article_link JOIN article ON article.id = article_link.src
+						WHERE (?1 IS NULL OR src > ?1) AND date_deleted IS NULL ORDER BY src LIMIT ?2
+				) ORDER BY src, dst, kind",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![after, sources], article_link_from_row)?
+					.collect()
+			});
+		match result {
+			Ok(links) => Some(links),
+			Err(err) => {
+				log::error!("Could not get links after {:?}: {:?}", after, err);
+				None
+			}
+		}
+	}
+
+	/// The links from and to `id`
+	pub fn get_article_links(&mut self, id: ItemId) -> Option<ArticleLinks> {
+		let result = (|| -> rusqlite::Result<ArticleLinks> {
+			let outgoing = self
+				.conn
+				.prepare(
+					"SELECT src, dst, kind FROM article_link WHERE src = ? ORDER BY dst, kind",
+				)?
+				.query_map(params![id], article_link_from_row)?
+				.collect::<rusqlite::Result<_>>()?;
+			let incoming = self
+				.conn
+				.prepare(
+					"SELECT src, dst, kind FROM article_link JOIN article ON article.id = article_link.src
+						WHERE dst = ? AND date_deleted IS NULL ORDER BY src, kind",
+				)?
+				.query_map(params![id], article_link_from_row)?
+				.collect::<rusqlite::Result<_>>()?;
+			Ok(ArticleLinks { outgoing, incoming })
+		})();
+		match result {
+			Ok(links) => Some(links),
+			Err(err) => {
+				log::error!("Could not get the links of article {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Number of articles `get_backlinks` lists
+	pub fn count_backlinks(&mut self, dst: ItemId) -> Option<u32> {
+		let result = self.conn.query_row(
+			"SELECT COUNT(*) FROM article_link JOIN article ON article.id = article_link.src
+				WHERE article_link.dst = ? AND date_deleted IS NULL",
+			params![dst],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(count) => Some(count),
+			Err(err) => {
+				log::error!("Could not count links to article {}: {:?}", dst, err);
+				None
+			}
+		}
+	}
+
+	/// Tags of an article in alphabetical order
+	pub fn get_tags(&mut self, id: ItemId) -> Option<Vec<String>> {
+		let result: rusqlite::Result<Vec<String>> = self
+			.conn
+			.prepare("SELECT tag FROM article_tag WHERE article_id = ? ORDER BY tag")
+			.and_then(|mut stmt| stmt.query_map(params![id], |row| row.get(0))?.collect());
+		match result {
+			Ok(tags) => Some(tags),
+			Err(err) => {
+				log::error!("Could not get tags of article {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Articles with a tag, the tag is normalized first. Deleted
+	/// articles keep their tags for when they are restored, but are
+	/// not listed.
+	pub fn get_articles_by_tag(&mut self, tag: &str) -> Option<Vec<ArticleSummary>> {
+		let Some(tag) = tags::normalize(tag) else {
+			return Some(Vec::new());
+		};
+		let result: rusqlite::Result<Vec<ArticleSummary>> = self
+			.conn
+			.prepare(
+				"SELECT article.id, title, date_created, date_modified, slug FROM article
+					JOIN article_tag ON article_tag.article_id = article.id
+					WHERE article_tag.tag = ? AND date_deleted IS NULL ORDER BY article.id",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![tag], |row| {
+					Ok(ArticleSummary {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						date_created: row.get(2)?,
+						date_modified: row.get(3)?,
+						slug: row.get(4)?,
+					})
+				})?
+				.collect()
+			});
+		match result {
+			Ok(articles) => Some(articles),
+			Err(err) => {
+				log::error!("Could not get articles with tag {:?}: {:?}", tag, err);
+				None
+			}
+		}
+	}
+
+	fn category_exists(&mut self, id: ItemId) -> rusqlite::Result<bool> {
+		self.conn.query_row(
+			"SELECT EXISTS(SELECT 1 FROM category WHERE id = ?)",
+			params![id],
+			|row| row.get(0),
+		)
+	}
+
+	/// Create a category below `parent`, or at the top level
+	pub fn create_category(
+		&mut self,
+		name: &str,
+		parent: Option<ItemId>,
+	) -> Result<ItemId, DbError> {
+		let result: rusqlite::Result<_> = (|| {
+			if let Some(parent) = parent {
+				if !self.category_exists(parent)? {
+					return Ok(None);
+				}
+			}
+			self.conn.execute(
+				"INSERT INTO category (name, parent_id) VALUES (?, ?)",
+				params![Database::filter_chars(name.trim()), parent],
+			)?;
+			Ok(ItemId::try_from(self.conn.last_insert_rowid()).ok())
+		})();
+		match result {
+			Ok(Some(id)) => {
+				log::info!("Category {} created", id);
+				Ok(id)
+			}
+			Ok(None) => Err(DbError::NotFound),
+			Err(err) => {
+				log::error!("Could not create category {:?}: {:?}", name, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Move a category with everything in it below `parent`, or to the
+	/// top level. Moving a category below itself or one of its
+	/// descendants is rejected with `DbError::Cycle`.
+	pub fn move_category(&mut self, id: ItemId, parent: Option<ItemId>) -> Result<(), DbError> {
+		let result: rusqlite::Result<_> = (|| {
+			if !self.category_exists(id)? {
+				return Ok(Err(DbError::NotFound));
+			}
+			if let Some(parent) = parent {
+				let ancestors = self.category_path(parent)?;
+				if ancestors.is_empty() {
+					return Ok(Err(DbError::NotFound));
+				}
+				if ancestors.iter().any(|category| category.id == id) {
+					return Ok(Err(DbError::Cycle));
+				}
+			}
+			self.conn.execute(
+				"UPDATE category SET parent_id = ? WHERE id = ?",
+				params![parent, id],
+			)?;
+			Ok(Ok(()))
+		})();
+		match result {
+			Ok(result) => result,
+			Err(err) => {
+				log::error!("Could not move category {}: {:?}", id, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	pub fn get_category(&mut self, id: ItemId) -> Option<Category> {
+		let result = self.conn.query_row(
+			"SELECT id, name, parent_id FROM category WHERE id = ?",
+			params![id],
+			|row| {
+				Ok(Category {
+					id: row.get(0)?,
+					name: row.get(1)?,
+					parent: row.get(2)?,
+				})
+			},
+		);
+		match result {
+			Ok(category) => Some(category),
+			Err(rusqlite::Error::QueryReturnedNoRows) => None,
+			Err(err) => {
+				log::error!("Could not get category {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Direct sub-categories of `parent`, or the top-level categories,
+	/// ordered by name
+	pub fn get_categories(&mut self, parent: Option<ItemId>) -> Option<Vec<Category>> {
+		let result: rusqlite::Result<Vec<Category>> = self
+			.conn
+			.prepare(
+				"SELECT id, name, parent_id FROM category WHERE parent_id IS ? ORDER BY name, id",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![parent], |row| {
+					Ok(Category {
+						id: row.get(0)?,
+						name: row.get(1)?,
+						parent: row.get(2)?,
+					})
+				})?
+				.collect()
+			});
+		match result {
+			Ok(categories) => Some(categories),
+			Err(err) => {
+				log::error!("Could not list categories: {:?}", err);
+				None
+			}
+		}
+	}
+
+	fn category_path(&mut self, id: ItemId) -> rusqlite::Result<Vec<Category>> {
+		let mut stmt = self.conn.prepare(&format!(
+			"{} SELECT id, name, parent_id FROM ancestor ORDER BY depth DESC",
+			CATEGORY_ANCESTORS
+		))?;
+		let categories = stmt
+			.query_map(params![id], |row| {
+				Ok(Category {
+					id: row.get(0)?,
+					name: row.get(1)?,
+					parent: row.get(2)?,
+				})
+			})?
+			.collect();
+		categories
+	}
+
+	/// The category and its ancestors, starting at the top level.
+	/// Empty if the category does not exist.
+	pub fn get_category_path(&mut self, id: ItemId) -> Option<Vec<Category>> {
+		match self.category_path(id) {
+			Ok(path) => Some(path),
+			Err(err) => {
+				log::error!("Could not get the ancestors of category {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Put an article into a category, or take it out of its category
+	/// with `None`
+	pub fn set_article_category(
+		&mut self,
+		article: ItemId,
+		category: Option<ItemId>,
+	) -> Result<(), DbError> {
+		let result: rusqlite::Result<_> = (|| {
+			if self.get_article_title(article).is_none() {
+				return Ok(Err(DbError::NotFound));
+			}
+			match category {
+				Some(category) => {
+					if !self.category_exists(category)? {
+						return Ok(Err(DbError::NotFound));
+					}
+					self.conn.execute(
+						"INSERT OR REPLACE INTO article_category (article_id, category_id) VALUES (?, ?)",
+						params![article, category],
+					)?;
+				}
+				None => {
+					self.conn.execute(
+						"DELETE FROM article_category WHERE article_id = ?",
+						params![article],
+					)?;
+				}
+			}
+			Ok(Ok(()))
+		})();
+		match result {
+			Ok(result) => result,
+			Err(err) => {
+				log::error!("Could not set category of article {}: {:?}", article, err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+
+	/// Category of an article, `None` if it is in none
+	pub fn get_article_category(&mut self, article: ItemId) -> Option<ItemId> {
+		let result = self.conn.query_row(
+			"SELECT category_id FROM article_category WHERE article_id = ?",
+			params![article],
+			|row| row.get(0),
+		);
+		match result {
+			Ok(category) => Some(category),
+			Err(rusqlite::Error::QueryReturnedNoRows) => None,
+			Err(err) => {
+				log::error!("Could not get category of article {}: {:?}", article, err);
+				None
+			}
+		}
+	}
+
+	/// Articles directly in a category, not in its sub-categories
+	pub fn get_articles_in_category(&mut self, id: ItemId) -> Option<Vec<ArticleSummary>> {
+		let result: rusqlite::Result<Vec<ArticleSummary>> = self
+			.conn
+			.prepare(
+				"SELECT article.id, title, date_created, date_modified, slug FROM article
+					JOIN article_category ON article_category.article_id = article.id
+					WHERE article_category.category_id = ? AND date_deleted IS NULL ORDER BY title",
+			)
+			.and_then(|mut stmt| {
+				stmt.query_map(params![id], |row| {
+					Ok(ArticleSummary {
+						id: row.get(0)?,
+						title: row.get(1)?,
+						date_created: row.get(2)?,
+						date_modified: row.get(3)?,
+						slug: row.get(4)?,
+					})
+				})?
+				.collect()
+			});
+		match result {
+			Ok(articles) => Some(articles),
+			Err(err) => {
+				log::error!("Could not get articles of category {}: {:?}", id, err);
+				None
+			}
+		}
+	}
+
+	/// Generate a new slug from the current title of the article,
+	/// returns the new slug
+	pub fn regenerate_slug(&mut self, id: ItemId) -> Option<String> {
+		let result: rusqlite::Result<_> = self.get_article_title(id).map_or(Ok(None), |title| {
+			let slug = unique_slug(&self.conn, &title, Some(id))?;
+			self.conn.execute(
+				"UPDATE article SET slug = ? WHERE id = ?",
+				params![slug, id],
+			)?;
+			Ok(Some(slug))
+		});
+		match result {
+			Ok(slug) => slug,
+			Err(err) => {
+				log::error!("Could not regenerate slug: {:?}", err);
+				None
+			}
+		}
+	}
+
+	/// Cached icon of `host` if it was fetched after `fetched_after`.
+	/// `Some(None)` means the last fetch failed.
+	pub fn get_favicon(&mut self, host: &str, fetched_after: UtcTimestamp) -> Option<Option<Icon>> {
+		let result = self.conn.query_row(
+			"SELECT content_type, data FROM favicon WHERE host = ? AND date_fetched > ?",
+			params![host, fetched_after],
+			|row| {
+				let content_type: Option<String> = row.get(0)?;
+				let data: Option<Vec<u8>> = row.get(1)?;
+				Ok(content_type
+					.zip(data)
+					.map(|(content_type, data)| Icon { content_type, data }))
+			},
+		);
+		match result {
+			Ok(icon) => Some(icon),
+			Err(rusqlite::Error::QueryReturnedNoRows) => None,
+			Err(err) => {
+				log::error!("Could not read favicon cache: {:?}", err);
+				None
+			}
+		}
+	}
+
+	/// Store the result of fetching the icon of `host` (`None` on
+	/// failure) and drop the oldest entries beyond `max_entries`
+	pub fn store_favicon(&mut self, host: &str, icon: Option<&Icon>, max_entries: usize) {
+		let result = self.conn.execute(
+			"INSERT OR REPLACE INTO favicon (host, content_type, data, date_fetched) VALUES (?1, ?2, ?3, ?4)",
+			params![
+				host,
+				icon.map(|icon| &icon.content_type),
+				icon.map(|icon| &icon.data),
+				UtcTimestamp::now()
+			],
+		).and_then(|_| self.conn.execute(
+			"DELETE FROM favicon WHERE host NOT IN (SELECT host FROM favicon ORDER BY date_fetched DESC LIMIT ?)",
+			params![max_entries as i64],
+		));
+		if let Err(err) = result {
+			log::error!("Could not write favicon cache: {:?}", err);
+		}
+	}
+
+	/// Change the title and/or text of an article and record the edit
+	/// with its `summary` in the revision history. With
+	/// `expected_revision`, the article is only changed if it still is
+	/// in that revision, otherwise nothing is updated. Either the title
+	/// or the text must be given.
+	pub fn update_article(
+		&mut self,
+		id: ItemId,
+		title: Option<&str>,
+		text: Option<&str>,
+		summary: Option<&str>,
+		expected_revision: Option<i64>,
+	) -> Result<usize, DbError> {
+		if title.is_none() && text.is_none() {
+			return Err(DbError::NothingToUpdate);
+		}
+		let title = title.map(Database::normalize_title);
+		if title.as_deref() == Some("") {
+			return Err(DbError::EmptyTitle);
+		}
+		let text = text.map(Database::filter_chars);
+		let summary = Database::normalize_edit_summary(summary.unwrap_or_default());
+
+		let mut query = "UPDATE article SET".to_string();
+
+		let now = self.write_time()?;
+		let mut arguments: Vec<Box<dyn rusqlite::ToSql>> = vec![];
+
+		let mut need_delim = false;
+		let delim = ',';
+
+		for (param, sql_str) in &[(&title, " title = ? "), (&text, " text = ? ")] {
+			if let Some(param) = param {
+				// Only update the SQL column if parameter is not None
+				// otherwise let it keep its original value
+				arguments.push(Box::new(param.to_sql().unwrap()));
+				if need_delim {
+					query.push(delim);
+				} else {
+					need_delim = true
+				}
+				query.push_str(sql_str);
+			}
+		}
+
+		arguments.push(Box::new(now.to_sql().unwrap()));
+		query.push(delim);
+		query.push_str(" date_modified = ? ");
+
+		query.push(delim);
+		query.push_str(" revision = revision + 1 ");
+
+		arguments.push(Box::new(id.to_sql().unwrap()));
+		query.push_str("WHERE id = ? AND date_deleted IS NULL");
+		if let Some(revision) = expected_revision {
+			arguments.push(Box::new(revision));
+			query.push_str(" AND revision = ?");
+		}
+
+		// The update and the redirect of the old title must not get
+		// out of sync, so both happen in one transaction
+		let result = self.conn.transaction().and_then(|tx| {
+			if let Some(title) = &title {
+				if Database::title_taken(&tx, title, Some(id))? {
+					return Ok(Err(DbError::AlreadyExists));
+				}
+			}
+			let old_title: Option<String> = match &title {
+				Some(_) => tx
+					.query_row(
+						"SELECT title FROM article WHERE id = ? AND date_deleted IS NULL",
+						params![id],
+						|row| row.get(0),
+					)
+					.map(Some)
+					.or_else(|err| match err {
+						rusqlite::Error::QueryReturnedNoRows => Ok(None),
+						err => Err(err),
+					})?,
+				None => None,
+			};
+			let renamed = match (&old_title, &title) {
+				(Some(old_title), Some(new_title)) if old_title != new_title => {
+					Some((old_title, new_title))
+				}
+				_ => None,
+			};
+			let updated = tx.execute(&query, rusqlite::params_from_iter(arguments.iter()))?;
+			if updated > 0 {
+				tx.execute(
+					"INSERT OR REPLACE INTO article_revision (article_id, revision, date_created, summary, old_title, new_title, text)
+						SELECT id, revision, ?, ?, ?, ?, text FROM article WHERE id = ?",
+					params![
+						now,
+						summary,
+						renamed.map(|(old_title, _)| old_title),
+						renamed.map(|(_, new_title)| new_title),
+						id
+					],
+				)?;
+			}
+			if let Some((old_title, new_title)) = renamed {
+				tx.execute(
+					"INSERT OR REPLACE INTO title_redirect (old_title, article_id, date_created) VALUES (?, ?, ?)",
+					params![old_title, id, now],
+				)?;
+				// The new title is current again, so it must no
+				// longer redirect anywhere
+				tx.execute(
+					"DELETE FROM title_redirect WHERE old_title = ?",
+					params![new_title],
+				)?;
+			}
+			tx.commit()?;
+			Ok(Ok(updated))
+		});
+		match result {
+			Ok(Ok(updated)) => {
+				log::debug!("Article update: {} row successfully updated", updated);
+				Ok(updated)
+			}
+			Ok(Err(err)) => Err(err),
+			Err(err) => {
+				log::error!("Article update failed: {:?}", err);
+				Err(DbError::from(err))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::path::PathBuf;
+
+	fn temp_database_path(name: &str) -> PathBuf {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-{}-{}.sqlite",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	/// Layout version after the current one, for testing the migration runner
+	const VERSION_NEXT: WikiSemVer = WikiSemVer {
+		major: LAYOUT_VERSION.major,
+		minor: LAYOUT_VERSION.minor + 1,
+		patch: 0,
+	};
+
+	fn migrate_test(tx: &rusqlite::Transaction) -> rusqlite::Result<()> {
+		tx.execute(
+			"CREATE TABLE migration_test (id INTEGER PRIMARY KEY)",
+			params![],
+		)?;
+		Ok(())
+	}
+
+	const TEST_MIGRATIONS: &[Migration] = &[Migration {
+		from: LAYOUT_VERSION,
+		to: VERSION_NEXT,
+		apply: migrate_test,
+	}];
+
+	/// Leave the database in the state of a migration that
+	/// was marked as started but never completed
+	fn interrupt_migration(path: &Path, to: &str) {
+		let conn = Connection::open(path).unwrap();
+		conn.execute(
+			"UPDATE table_layout SET migrating_to_version = ?, date_migration_begin = ? WHERE id = 1",
+			params![to, UtcTimestamp::now()],
+		)
+		.unwrap();
+	}
+
+	fn open(path: &Path) -> DatabaseConnection {
+		DatabaseConnection::new(path, OpenMode::OpenExisting).unwrap()
+	}
+
+	/// New database that claims the older layout `version`, with the
+	/// case-insensitive title index of layout 0.13.0 removed
+	fn open_in_layout(path: &Path, version: &str) -> Database {
+		let _ = std::fs::remove_file(path);
+		let db = DatabaseConnection::new(path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		db.conn
+			.execute_batch(&format!(
+				"DROP INDEX article_title_nocase;
+				UPDATE table_layout SET version = '{}';",
+				version
+			))
+			.unwrap();
+		db
+	}
+
+	#[test]
+	fn test_parse_u32() {
+		for (input, expected) in [
+			("0", Some(0)),
+			("7", Some(7)),
+			("10", Some(10)),
+			("4294967295", Some(u32::MAX)),
+			("4294967296", None),
+			("99999999999999999999", None),
+			("", None),
+			("+5", None),
+			("-5", None),
+			("-0", None),
+			("00", None),
+			("05", None),
+			(" 5", None),
+			("5 ", None),
+			("5a", None),
+			("abc", None),
+			("1.0", None),
+			("٣", None),
+			("５", None),
+		] {
+			assert_eq!(parse_u32(input), expected, "{:?}", input);
+			assert_eq!(
+				input.parse::<ItemId>().ok().map(|id| id.value),
+				expected,
+				"{:?}",
+				input
+			);
+		}
+	}
+
+	#[test]
+	fn test_migration_chain() {
+		let path = temp_database_path("migration-chain");
+		DatabaseConnection::new(&path, OpenMode::CreateNew).unwrap();
+
+		let mut db = open(&path)
+			.init_internal(TEST_MIGRATIONS, VERSION_NEXT, false)
+			.unwrap();
+		let layout = db.get_table_layout().unwrap();
+		assert_eq!(layout.version, VERSION_NEXT);
+		assert_eq!(layout.migrating_to_version, None);
+		assert!(layout.date_migration_begin.is_some());
+		assert!(layout.date_migration_complete.is_some());
+
+		// Without a migration path, the newer layout is not supported by this build
+		assert!(matches!(
+			open(&path).init(),
+			Err(DatabaseInitError::UnsupportedLayout { found, supported })
+				if found == VERSION_NEXT && supported == LAYOUT_VERSION
+		));
+		assert_eq!(
+			open(&path).init().err().unwrap().to_string(),
+			format!(
+				"the database was created by a newer version of redwood-wiki (layout {}), this build supports {}",
+				VERSION_NEXT, LAYOUT_VERSION
+			)
+		);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_interrupted_migration_refused() {
+		let path = temp_database_path("migration-refused");
+		DatabaseConnection::new(&path, OpenMode::CreateNew).unwrap();
+		interrupt_migration(&path, &VERSION_NEXT.to_string());
+
+		assert!(matches!(
+			open(&path).init_internal(TEST_MIGRATIONS, VERSION_NEXT, false),
+			Err(DatabaseInitError::MigrationInProgress {
+				to: VERSION_NEXT,
+				since: Some(_)
+			})
+		));
+
+		// A second runner must not be able to start the same migration
+		let mut db = open(&path).database;
+		assert!(matches!(
+			db.begin_migration(&TEST_MIGRATIONS[0]),
+			Err(DatabaseInitError::MigrationInProgress { .. })
+		));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_migration_done_by_other_process() {
+		let path = temp_database_path("migration-done-by-other");
+		DatabaseConnection::new(&path, OpenMode::CreateNew).unwrap();
+		// Read the layout before the other process migrates
+		let mut db = open(&path).database;
+		assert_eq!(db.get_table_layout().unwrap().version, LAYOUT_VERSION);
+		open(&path)
+			.init_internal(TEST_MIGRATIONS, VERSION_NEXT, false)
+			.unwrap();
+
+		assert!(!db.begin_migration(&TEST_MIGRATIONS[0]).unwrap());
+		let layout = db.get_table_layout().unwrap();
+		assert_eq!(layout.version, VERSION_NEXT);
+		assert_eq!(layout.migrating_to_version, None);
+		assert!(layout.date_migration_complete.is_some());
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_interrupted_migration_resumed() {
+		let path = temp_database_path("migration-resumed");
+		DatabaseConnection::new(&path, OpenMode::CreateNew).unwrap();
+		interrupt_migration(&path, &VERSION_NEXT.to_string());
+
+		let mut db = open(&path)
+			.init_internal(TEST_MIGRATIONS, VERSION_NEXT, true)
+			.unwrap();
+		let layout = db.get_table_layout().unwrap();
+		assert_eq!(layout.version, VERSION_NEXT);
+		assert_eq!(layout.migrating_to_version, None);
+		assert!(layout.date_migration_complete.is_some());
+		db.conn
+			.execute("INSERT INTO migration_test (id) VALUES (1)", params![])
+			.unwrap();
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_interrupted_migration_unrecoverable() {
+		let path = temp_database_path("migration-unrecoverable");
+		DatabaseConnection::new(&path, OpenMode::CreateNew).unwrap();
+		interrupt_migration(&path, "99.0.0");
+
+		assert!(matches!(
+			open(&path).init_internal(TEST_MIGRATIONS, VERSION_NEXT, true),
+			Err(DatabaseInitError::MigrationUnrecoverable { .. })
+		));
+		// The layout row must be left untouched for inspection
+		let layout = open(&path).database.get_table_layout().unwrap();
+		assert_eq!(layout.version, LAYOUT_VERSION);
+		assert!(layout.migrating_to_version.is_some());
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_slug_backfill_migration() {
+		let path = temp_database_path("slug-backfill");
+		{
+			// Layout 0.1.0, before the slug column existed
+			let conn = Connection::open(&path).unwrap();
+			conn.execute_batch(
+				"CREATE TABLE article (
+					id            INTEGER PRIMARY KEY AUTOINCREMENT,
+					title         TEXT NOT NULL UNIQUE,
+					text          TEXT NOT NULL,
+					date_created  DATETIME NOT NULL,
+					date_modified DATETIME NOT NULL,
+					revision      INTEGER NOT NULL
+				);
+				CREATE TABLE table_layout (
+					id                        INTEGER PRIMARY KEY AUTOINCREMENT,
+					version                   TEXT NOT NULL,
+					migrating_to_version      TEXT,
+					date_created              DATETIME NOT NULL,
+					date_migration_begin      DATETIME,
+					date_migration_complete   DATETIME
+				);
+				INSERT INTO table_layout (id, version, date_created) VALUES (1, '0.1.0', '2023-01-01T00:00:00');",
+			)
+			.unwrap();
+			for (title, text) in [
+				("Redwood tree", ""),
+				("Redwood Tree!", ""),
+				("redwood-tree", ""),
+				("???", "See [article:1] and [article:4]"),
+			] {
+				conn.execute(
+					"INSERT INTO article (title, text, date_created, date_modified, revision) VALUES (?, ?, '2023-01-01T00:00:00', '2023-01-01T00:00:00', 0)",
+					params![title, text],
+				)
+				.unwrap();
+			}
+		}
+
+		let mut db = open(&path).init().unwrap();
+		assert_eq!(db.get_table_layout().unwrap().version, LAYOUT_VERSION);
+		let slugs: Vec<String> = db
+			.get_all_articles()
+			.unwrap()
+			.into_iter()
+			.map(|article| article.slug)
+			.collect();
+		assert_eq!(
+			slugs,
+			vec![
+				"redwood-tree",
+				"redwood-tree-2",
+				"redwood-tree-3",
+				"article"
+			]
+		);
+		// Links are taken from the existing texts
+		let backlinks = db.get_backlinks(1.into()).unwrap();
+		assert_eq!(backlinks.len(), 1);
+		assert_eq!(backlinks[0].title, "???");
+		assert_eq!(db.count_backlinks(4.into()), Some(0));
+
+		// New articles continue with the next free suffix
+		let id = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood: tree".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		assert_eq!(db.get_article(id).unwrap().unwrap().slug, "redwood-tree-4");
+
+		// Renaming keeps the slug until it is regenerated
+		db.update_article(id, Some("Giant sequoia"), None, None, None)
+			.unwrap();
+		assert_eq!(db.get_article(id).unwrap().unwrap().slug, "redwood-tree-4");
+		assert_eq!(db.regenerate_slug(id).unwrap(), "giant-sequoia");
+		// Regenerating must not collide with the article itself
+		assert_eq!(db.regenerate_slug(id).unwrap(), "giant-sequoia");
+		assert_eq!(db.regenerate_slug(1000.into()), None);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_title_index_migration() {
+		let path = temp_database_path("title-index");
+		let db = open_in_layout(&path, "0.12.0");
+		let titles = [
+			"Redwood",
+			" Giant  sequoia",
+			"redwood ",
+			"Fir",
+			"FIR",
+			"Giant sequoia",
+		];
+		for (i, title) in titles.iter().enumerate() {
+			db.conn
+				.execute(
+					"INSERT INTO article (title, text, date_created, date_modified, revision, slug) VALUES (?, '', '2023-01-01T00:00:00', '2023-01-01T00:00:00', 0, ?)",
+					params![title, format!("article-{}", i)],
+				)
+				.unwrap();
+		}
+		assert_eq!(
+			normalize_titles(&db.conn).unwrap(),
+			[
+				vec![
+					(1.into(), "Redwood".to_string()),
+					(3.into(), "redwood ".to_string())
+				],
+				vec![
+					(2.into(), " Giant  sequoia".to_string()),
+					(6.into(), "Giant sequoia".to_string())
+				],
+				vec![(4.into(), "Fir".to_string()), (5.into(), "FIR".to_string())],
+			]
+		);
+		drop(db);
+
+		// The conflicts keep the index from being created, but not the
+		// database from being opened
+		let mut db = open(&path).init().unwrap();
+		assert_eq!(db.get_table_layout().unwrap().version, LAYOUT_VERSION);
+		assert!(!has_index(&db.conn, "article_title_nocase").unwrap());
+		// New conflicts are refused all the same
+		let new = |title: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		assert!(matches!(
+			db.create_article(&new("fir")),
+			Err(CreateArticleError::DuplicateTitle)
+		));
+		db.update_article(3.into(), Some("Coast redwood"), None, None, None)
+			.unwrap();
+		db.update_article(5.into(), Some("Douglas fir"), None, None, None)
+			.unwrap();
+		db.delete_article(6.into()).unwrap();
+		drop(db);
+
+		// Deleted articles keep their title, so one conflict is left
+		let mut db = open(&path).init().unwrap();
+		assert!(!has_index(&db.conn, "article_title_nocase").unwrap());
+		db.update_article(2.into(), Some("Giant sequoia 2"), None, None, None)
+			.unwrap();
+		drop(db);
+		let mut db = open(&path).init().unwrap();
+		assert!(has_index(&db.conn, "article_title_nocase").unwrap());
+		let titles: Vec<String> = (1..=6)
+			.map(|id| db.get_article_title_include_deleted(id.into()).unwrap().0)
+			.collect();
+		assert_eq!(
+			titles,
+			[
+				"Redwood",
+				"Giant sequoia 2",
+				"Coast redwood",
+				"Fir",
+				"Douglas fir",
+				"Giant sequoia"
+			]
+		);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_link_kind_migration() {
+		let path = temp_database_path("link-kind");
+		let db = open_in_layout(&path, "0.13.0");
+		db.conn
+			.execute_batch(
+				"DROP TABLE article_link;
+				CREATE TABLE article_link (
+					src           INTEGER NOT NULL,
+					dst           INTEGER NOT NULL,
+					PRIMARY KEY (src, dst)
+				);
+				CREATE INDEX article_link_dst ON article_link (dst);
+				INSERT INTO article (title, text, date_created, date_modified, revision, slug)
+					VALUES ('Redwood', '', '2023-01-01T00:00:00', '2023-01-01T00:00:00', 0, 'redwood');
+				INSERT INTO article_link (src, dst) VALUES (1, 2), (1, 3);",
+			)
+			.unwrap();
+		db.conn
+			.execute(CREATE_ARTICLE_TITLE_INDEX, params![])
+			.unwrap();
+		drop(db);
+
+		let mut db = open(&path).init().unwrap();
+		assert_eq!(db.get_table_layout().unwrap().version, LAYOUT_VERSION);
+		assert_eq!(
+			db.get_links(None, 10).unwrap(),
+			[
+				ArticleLink {
+					src: 1.into(),
+					dst: 2.into(),
+					kind: LinkKind::ArticleRef
+				},
+				ArticleLink {
+					src: 1.into(),
+					dst: 3.into(),
+					kind: LinkKind::ArticleRef
+				}
+			]
+		);
+		assert_eq!(db.count_backlinks(3.into()), Some(1));
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_db_errors() {
+		let path = temp_database_path("db-errors");
+		// Readers never wait in WAL mode
+		let settings = ConnectionSettings {
+			journal_mode: JournalMode::Delete,
+			..ConnectionSettings::default()
+		};
+		let mut db = DatabaseConnection::with_settings(&path, OpenMode::CreateNew, &settings)
+			.unwrap()
+			.init()
+			.unwrap();
+		assert!(matches!(db.get_article(1.into()), Ok(None)));
+
+		// Another process holds the database, without waiting for it
+		db.conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+		let other = Connection::open(&path).unwrap();
+		other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+		assert!(matches!(db.get_article(1.into()), Err(DbError::Busy)));
+		assert!(matches!(db.get_all_articles(), Err(DbError::Busy)));
+		assert!(matches!(db.search_articles("tree"), Err(DbError::Busy)));
+		assert!(matches!(db.search_articles_fts("tree"), Err(DbError::Busy)));
+		assert!(matches!(
+			db.get_articles_page(0, 10, ArticleOrder::Title),
+			Err(DbError::Busy)
+		));
+		assert!(matches!(db.count_articles(), Err(DbError::Busy)));
+		assert!(matches!(db.get_all_titles(), Err(DbError::Busy)));
+		assert!(matches!(
+			db.get_article_id_by_title("Tree"),
+			Err(DbError::Busy)
+		));
+		assert!(matches!(db.resolve_title("Tree"), Err(DbError::Busy)));
+		assert!(matches!(db.get_table_layout(), Err(DbError::Busy)));
+		other.execute_batch("ROLLBACK").unwrap();
+		assert!(db.get_all_articles().unwrap().is_empty());
+
+		let failure = |code| {
+			DbError::from(rusqlite::Error::SqliteFailure(
+				rusqlite::ffi::Error::new(code),
+				None,
+			))
+		};
+		assert!(matches!(
+			failure(rusqlite::ffi::SQLITE_CORRUPT),
+			DbError::Corrupt
+		));
+		assert!(matches!(
+			failure(rusqlite::ffi::SQLITE_NOTADB),
+			DbError::Corrupt
+		));
+		assert!(matches!(
+			failure(rusqlite::ffi::SQLITE_LOCKED),
+			DbError::Busy
+		));
+		assert!(matches!(
+			failure(rusqlite::ffi::SQLITE_IOERR),
+			DbError::Sqlite(_)
+		));
+		drop((db, other));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_connection_settings() {
+		let pragma = |db: &Database, name: &str| -> String {
+			db.conn
+				.query_row(&format!("PRAGMA {}", name), [], |row| {
+					row.get::<_, rusqlite::types::Value>(0)
+				})
+				.map(|value| match value {
+					rusqlite::types::Value::Integer(i) => i.to_string(),
+					rusqlite::types::Value::Text(t) => t,
+					other => format!("{:?}", other),
+				})
+				.unwrap()
+		};
+
+		let path = temp_database_path("connection-settings");
+		let db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		assert_eq!(pragma(&db, "journal_mode"), "wal");
+		// NORMAL
+		assert_eq!(pragma(&db, "synchronous"), "1");
+		assert_eq!(pragma(&db, "foreign_keys"), "1");
+		assert_eq!(pragma(&db, "busy_timeout"), "5000");
+
+		// The read-only connection keeps the journal mode of the file
+		let read_only = DatabaseConnection::new(&path, OpenMode::ReadOnly)
+			.unwrap()
+			.inspect()
+			.unwrap();
+		assert_eq!(pragma(&read_only, "journal_mode"), "wal");
+		drop((db, read_only));
+
+		let config: crate::config::Connection = toml::from_str(
+			r#"
+			journal_mode = "delete"
+			synchronous = "full"
+			foreign_keys = false
+			busy_timeout_ms = 250
+			"#,
+		)
+		.unwrap();
+		let db =
+			DatabaseConnection::with_settings(&path, OpenMode::OpenExisting, &config.settings())
+				.unwrap()
+				.init()
+				.unwrap();
+		assert_eq!(pragma(&db, "journal_mode"), "delete");
+		assert_eq!(pragma(&db, "synchronous"), "2");
+		assert_eq!(pragma(&db, "foreign_keys"), "0");
+		assert_eq!(pragma(&db, "busy_timeout"), "250");
+		drop(db);
+
+		let settings = ConnectionSettings {
+			journal_mode: JournalMode::Wal,
+			..ConnectionSettings::default()
+		};
+		// SQLite keeps in-memory databases in memory journal mode
+		let conn = Connection::open_in_memory().unwrap();
+		let err = settings.apply(&conn, true).unwrap_err();
+		assert!(matches!(
+			err,
+			DatabaseConnectError::CouldNotConfigure {
+				setting: "journal mode",
+				..
+			}
+		));
+		assert_eq!(
+			err.to_string(),
+			"could not set the journal mode of the database: requested wal, SQLite kept memory"
+		);
+
+		assert!(toml::from_str::<crate::config::Connection>(r#"journal_mode = "fast""#).is_err());
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_delete_article() {
+		let path = temp_database_path("delete");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let article = |title: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let first = db.create_article(&article("First")).unwrap();
+		let second = db.create_article(&article("Second")).unwrap();
+
+		db.delete_article(first).unwrap();
+		assert!(db.get_article(first).unwrap().is_none());
+		assert!(db.get_article_title(first).is_none());
+		assert_eq!(
+			db.get_article_title_include_deleted(first),
+			Some(("First".to_string(), true))
+		);
+		assert!(db.get_article_id_by_title("First").unwrap().is_none());
+		assert_eq!(db.get_article_id_by_title("second").unwrap(), Some(second));
+		assert_eq!(db.get_all_articles().unwrap().len(), 1);
+		assert_eq!(db.count_articles().unwrap(), 1);
+		assert!(db.get_article(second).unwrap().is_some());
+		assert!(matches!(db.delete_article(first), Err(DbError::NotFound)));
+		let deleted = db.get_deleted_articles().unwrap();
+		assert_eq!(deleted.len(), 1);
+		assert_eq!((deleted[0].0.value, deleted[0].1.as_str()), (1, "First"));
+		// Deleted articles cannot be edited
+		assert!(matches!(
+			db.update_article(first, None, Some("x"), None, None),
+			Ok(0)
+		));
+
+		// The ID is not reused
+		let third = db.create_article(&article("Third")).unwrap();
+		assert_eq!(third.value, 3);
+
+		db.restore_article(first).unwrap();
+		assert_eq!(db.get_article_title(first).as_deref(), Some("First"));
+		assert!(db.get_deleted_articles().unwrap().is_empty());
+		assert!(matches!(db.restore_article(first), Err(DbError::NotFound)));
+		assert!(matches!(
+			db.restore_article(99.into()),
+			Err(DbError::NotFound)
+		));
+
+		// An exact match is preferred to the older article. Such titles
+		// can only be left from before the title index.
+		assert!(matches!(
+			db.create_article(&article("FIRST")),
+			Err(CreateArticleError::DuplicateTitle)
+		));
+		db.conn
+			.execute_batch(
+				"DROP INDEX article_title_nocase;
+				INSERT INTO article (title, text, date_created, date_modified, revision, slug)
+					VALUES ('FIRST', '', '2023-01-01T00:00:00', '2023-01-01T00:00:00', 0, 'first-2');",
+			)
+			.unwrap();
+		let upper = db.get_title_owner("FIRST").unwrap();
+		assert_ne!(upper, first);
+		assert_eq!(db.get_article_id_by_title("FIRST").unwrap(), Some(upper));
+		assert_eq!(db.get_article_id_by_title("first").unwrap(), Some(first));
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_revisions() {
+		let path = temp_database_path("revisions");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let id = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let created = db.get_revisions(id).unwrap();
+		assert_eq!(created.len(), 1);
+		assert_eq!((created[0].revision, created[0].size), (0, Some(0)));
+
+		db.update_article(id, None, Some("Tall"), Some("First\r\n  draft\u{7}"), None)
+			.unwrap();
+		db.update_article(id, Some("Coast redwood"), None, None, None)
+			.unwrap();
+		let long = "x".repeat(MAX_EDIT_SUMMARY_LENGTH + 10);
+		db.update_article(id, None, Some("Taller"), Some(&long), None)
+			.unwrap();
+		let revisions = db.get_revisions(id).unwrap();
+		let summaries: Vec<(i64, &str)> = revisions
+			.iter()
+			.map(|r| (r.revision, r.summary.as_str()))
+			.collect();
+		assert_eq!(
+			summaries,
+			[
+				(3, &long[..MAX_EDIT_SUMMARY_LENGTH]),
+				(2, ""),
+				(1, "First draft"),
+				(0, "")
+			]
+		);
+		let sizes: Vec<Option<i64>> = revisions.iter().map(|r| r.size).collect();
+		assert_eq!(sizes, [Some(6), Some(4), Some(4), Some(0)]);
+		let article = db.get_article(id).unwrap().unwrap();
+		assert_eq!(article.revision, 3);
+		assert_eq!(db.get_edit_summary(id, 1).as_deref(), Some("First draft"));
+		assert_eq!(db.get_edit_summary(id, 9).as_deref(), Some(""));
+		assert_eq!(db.get_revision_text(id, 1).as_deref(), Some("Tall"));
+		assert_eq!(db.get_revision_text(id, 3).as_deref(), Some("Taller"));
+		assert_eq!(db.get_revision_text(id, 0).as_deref(), Some(""));
+		assert_eq!(db.get_revision_text(id, 9), None);
+
+		assert!(matches!(
+			db.update_article(id, None, None, Some("Nothing"), None),
+			Err(DbError::NothingToUpdate)
+		));
+
+		// Only saved if the article still is in the expected revision
+		assert!(matches!(
+			db.update_article(id, None, Some("x"), None, Some(2)),
+			Ok(0)
+		));
+		assert_eq!(db.get_article(id).unwrap().unwrap().text, "Taller");
+		assert!(matches!(
+			db.update_article(id, None, Some("Tallest"), None, Some(3)),
+			Ok(1)
+		));
+
+		// Edits of deleted articles are not recorded
+		db.delete_article(id).unwrap();
+		assert!(matches!(
+			db.update_article(id, None, Some("x"), Some("Gone"), None),
+			Ok(0)
+		));
+		assert_eq!(db.get_revisions(id).unwrap().len(), 5);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_title_changes() {
+		let path = temp_database_path("title-changes");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let id = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		for (title, expected) in [
+			("Redwood", "DuplicateTitle"),
+			("REDWOOD", "DuplicateTitle"),
+			(" redwood\t", "DuplicateTitle"),
+			("", "EmptyTitle"),
+			(" \n ", "EmptyTitle"),
+		] {
+			let err = db
+				.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: String::new(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap_err();
+			assert_eq!(format!("{:?}", err), expected);
+		}
+		let before = UtcTimestamp::now() - chrono::Duration::seconds(10);
+		let change = |old_title: &str, new_title: &str| {
+			Some(TitleChange {
+				old_title: old_title.to_string(),
+				new_title: new_title.to_string(),
+			})
+		};
+
+		db.update_article(id, Some("Coast redwood"), None, Some("More precise"), None)
+			.unwrap();
+		// Unchanged titles and text edits are no title changes
+		db.update_article(id, Some("Coast redwood"), Some("Tall"), None, None)
+			.unwrap();
+		db.update_article(id, Some("Redwood"), None, None, None)
+			.unwrap();
+		let revisions = db.get_revisions(id).unwrap();
+		let changes: Vec<_> = revisions.iter().map(|r| r.title_change.clone()).collect();
+		assert_eq!(
+			changes,
+			[
+				change("Coast redwood", "Redwood"),
+				None,
+				change("Redwood", "Coast redwood"),
+				None
+			]
+		);
+		assert_eq!(revisions[2].summary, "More precise");
+
+		let titles: Vec<Option<String>> = (0..5)
+			.map(|revision| db.get_revision_title(id, revision))
+			.collect();
+		assert_eq!(
+			titles,
+			[
+				Some("Redwood".to_string()),
+				Some("Coast redwood".to_string()),
+				Some("Coast redwood".to_string()),
+				Some("Redwood".to_string()),
+				Some("Redwood".to_string())
+			]
+		);
+		assert_eq!(db.get_revision_title(99.into(), 0), None);
+
+		let changes: Vec<(i64, Option<TitleChange>)> = db
+			.get_title_changes(id, before)
+			.unwrap()
+			.into_iter()
+			.map(|r| (r.revision, r.title_change))
+			.collect();
+		assert_eq!(
+			changes,
+			[
+				(1, change("Redwood", "Coast redwood")),
+				(3, change("Coast redwood", "Redwood"))
+			]
+		);
+		let later = UtcTimestamp::now() + chrono::Duration::seconds(10);
+		assert!(db.get_title_changes(id, later).unwrap().is_empty());
+		assert_eq!(db.resolve_title("Redwood").unwrap(), Some((id, false)));
+		assert_eq!(db.resolve_title("Coast redwood").unwrap(), Some((id, true)));
+
+		// Titles are stored normalized, and unique regardless of case
+		let other = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "  Giant \t sequoia ".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		assert_eq!(
+			db.get_article(other).unwrap().unwrap().title,
+			"Giant sequoia"
+		);
+		assert_eq!(db.get_title_owner("giant  SEQUOIA"), Some(other));
+		assert_eq!(
+			db.get_article_id_by_title(" Giant sequoia").unwrap(),
+			Some(other)
+		);
+		assert!(matches!(
+			db.update_article(other, Some("redwood "), None, None, None),
+			Err(DbError::AlreadyExists)
+		));
+		assert!(matches!(
+			db.update_article(other, Some("   "), None, None, None),
+			Err(DbError::EmptyTitle)
+		));
+		db.update_article(other, Some("Giant Sequoia"), None, None, None)
+			.unwrap();
+		assert_eq!(
+			db.get_article(other).unwrap().unwrap().title,
+			"Giant Sequoia"
+		);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_bad_dates() {
+		let path = temp_database_path("bad-dates");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let mut ids = Vec::new();
+		for title in ["Good", "Old", "Future", "Offset", "Renamed"] {
+			ids.push(
+				db.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: String::new(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap(),
+			);
+		}
+		db.update_article(ids[4], Some("Renamed again"), None, None, None)
+			.unwrap();
+		db.delete_article(ids[0]).unwrap();
+		let seed = |sql: &str, id: ItemId| {
+			Connection::open(&path)
+				.unwrap()
+				.execute(sql, params![id])
+				.unwrap();
+		};
+		// Just inside and just outside of the default window
+		seed(
+			"UPDATE article SET date_created = '2000-01-01 00:00:00' WHERE id = ?",
+			ids[0],
+		);
+		seed(
+			"UPDATE article SET date_modified = '1970-01-01 00:00:00', date_created = '1999-12-31 23:59:59' WHERE id = ?",
+			ids[1],
+		);
+		seed(
+			"UPDATE article SET date_modified = '2999-01-01 00:00:00' WHERE id = ?",
+			ids[2],
+		);
+		seed(
+			"UPDATE article SET date_modified = '2024-05-12T08:30:00Z' WHERE id = ?",
+			ids[3],
+		);
+		seed(
+			"UPDATE article_revision SET date_created = 5 WHERE article_id = ? AND revision = 1",
+			ids[4],
+		);
+		seed(
+			"UPDATE title_redirect SET date_created = '0001-01-01 00:00:00' WHERE article_id = ?",
+			ids[4],
+		);
+
+		let now = UtcTimestamp::now();
+		let bad = db.find_bad_dates(now).unwrap();
+		let found: Vec<(&str, &str, &str, &str)> = bad
+			.iter()
+			.map(|date| {
+				(
+					date.table,
+					date.key.as_str(),
+					date.column,
+					date.value.as_str(),
+				)
+			})
+			.collect();
+		let key = |id: ItemId| id.to_string();
+		let revision = format!("{} revision 1", ids[4]);
+		assert_eq!(
+			found,
+			[
+				(
+					"article",
+					&key(ids[1])[..],
+					"date_created",
+					"1999-12-31 23:59:59"
+				),
+				(
+					"article",
+					&key(ids[1])[..],
+					"date_modified",
+					"1970-01-01 00:00:00"
+				),
+				(
+					"article",
+					&key(ids[2])[..],
+					"date_modified",
+					"2999-01-01 00:00:00"
+				),
+				(
+					"article",
+					&key(ids[3])[..],
+					"date_modified",
+					"2024-05-12T08:30:00Z"
+				),
+				(
+					"article_revision",
+					&revision[..],
+					"date_created",
+					"Integer(5)"
+				),
+				(
+					"title_redirect",
+					"Renamed",
+					"date_created",
+					"0001-01-01 00:00:00"
+				),
+			]
+		);
+
+		assert_eq!(db.fix_bad_dates(now).unwrap(), 6);
+		assert!(db.find_bad_dates(now).unwrap().is_empty());
+		let old = db.get_article(ids[1]).unwrap().unwrap();
+		assert_eq!((old.date_created, old.date_modified), (now, now));
+		assert_eq!(db.get_article(ids[3]).unwrap().unwrap().date_modified, now);
+		assert_eq!(db.get_revisions(ids[4]).unwrap()[0].date_created, now);
+		// Good dates are left alone
+		let deleted = db.get_deleted_articles().unwrap();
+		assert_ne!(deleted[0].2, now);
+
+		// With a clock before the window, nothing is written
+		db.set_date_window(DateWindow::new(3000, 1));
+		let article = Article {
+			id: 0.into(),
+			title: "Too early".to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		assert!(matches!(
+			db.create_article(&article),
+			Err(CreateArticleError::DateOutOfRange(_))
+		));
+		assert!(matches!(
+			db.update_article(ids[1], None, Some("x"), None, None),
+			Err(DbError::DateOutOfRange(_))
+		));
+		assert!(matches!(
+			db.delete_article(ids[1]),
+			Err(DbError::DateOutOfRange(_))
+		));
+		assert!(matches!(
+			db.fix_bad_dates(UtcTimestamp::now()),
+			Err(DbError::DateOutOfRange(_))
+		));
+		assert_eq!(db.get_article(ids[1]).unwrap().unwrap().revision, 0);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_title_redirects() {
+		let path = temp_database_path("title-redirects");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let article = |title: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let tree = db.create_article(&article("Redwood")).unwrap();
+		let resolved = |db: &mut Database, title: &str| {
+			db.resolve_title(title)
+				.unwrap()
+				.map(|(id, redirected)| (id.value, redirected))
+		};
+		assert_eq!(resolved(&mut db, "Redwood"), Some((tree.value, false)));
+
+		// Saving the text only does not add a redirect
+		db.update_article(tree, None, Some("text"), None, None)
+			.unwrap();
+		db.update_article(tree, Some("Redwood"), None, None, None)
+			.unwrap();
+		assert_eq!(resolved(&mut db, "Redwood"), Some((tree.value, false)));
+
+		db.update_article(tree, Some("Coast redwood"), None, None, None)
+			.unwrap();
+		db.update_article(tree, Some("Sequoia sempervirens"), None, None, None)
+			.unwrap();
+		assert_eq!(resolved(&mut db, "Redwood"), Some((tree.value, true)));
+		assert_eq!(resolved(&mut db, "Coast redwood"), Some((tree.value, true)));
+		assert_eq!(
+			resolved(&mut db, "Sequoia sempervirens"),
+			Some((tree.value, false))
+		);
+		assert_eq!(resolved(&mut db, "Giant sequoia"), None);
+
+		// Renaming back makes the title current again instead of
+		// redirecting the article to itself
+		db.update_article(tree, Some("Redwood"), None, None, None)
+			.unwrap();
+		assert_eq!(resolved(&mut db, "Redwood"), Some((tree.value, false)));
+		assert_eq!(
+			resolved(&mut db, "Sequoia sempervirens"),
+			Some((tree.value, true))
+		);
+
+		// A current title wins over a former title of another article
+		let other = db.create_article(&article("Coast redwood")).unwrap();
+		assert_eq!(
+			resolved(&mut db, "Coast redwood"),
+			Some((other.value, false))
+		);
+		// A former title moves on to the article that had it last
+		db.update_article(other, Some("Other"), None, None, None)
+			.unwrap();
+		assert_eq!(
+			resolved(&mut db, "Coast redwood"),
+			Some((other.value, true))
+		);
+
+		// Redirects to deleted articles do not resolve
+		db.delete_article(tree).unwrap();
+		assert_eq!(resolved(&mut db, "Sequoia sempervirens"), None);
+		db.restore_article(tree).unwrap();
+		assert_eq!(
+			resolved(&mut db, "Sequoia sempervirens"),
+			Some((tree.value, true))
+		);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_tags() {
+		let path = temp_database_path("tags");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let article = |title: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let titles = |db: &mut Database, tag: &str| -> Vec<String> {
+			db.get_articles_by_tag(tag)
+				.unwrap()
+				.into_iter()
+				.map(|article| article.title)
+				.collect()
+		};
+		let first = db.create_article(&article("First")).unwrap();
+		let second = db.create_article(&article("Second")).unwrap();
+		assert!(db.get_tags(first).unwrap().is_empty());
+
+		db.add_tag(first, " Trees ").unwrap();
+		db.add_tag(first, "trees").unwrap();
+		db.add_tag(first, "Conifer").unwrap();
+		db.add_tag(first, "  ").unwrap();
+		db.add_tag(second, "TREES").unwrap();
+		assert_eq!(db.get_tags(first).unwrap(), ["conifer", "trees"]);
+		assert_eq!(db.get_tags(second).unwrap(), ["trees"]);
+		assert_eq!(titles(&mut db, "Trees"), ["First", "Second"]);
+		assert_eq!(titles(&mut db, "conifer"), ["First"]);
+		assert!(matches!(
+			db.add_tag(99.into(), "trees"),
+			Err(DbError::NotFound)
+		));
+
+		// Deleted articles keep their tags but are not listed
+		db.delete_article(second).unwrap();
+		assert_eq!(titles(&mut db, "trees"), ["First"]);
+		assert!(matches!(db.add_tag(second, "new"), Err(DbError::NotFound)));
+		db.restore_article(second).unwrap();
+		assert_eq!(titles(&mut db, "trees"), ["First", "Second"]);
+
+		db.remove_tag(first, "CONIFER").unwrap();
+		assert!(matches!(
+			db.remove_tag(first, "conifer"),
+			Err(DbError::NotFound)
+		));
+		// Nothing is left of a tag no article has anymore
+		assert!(titles(&mut db, "conifer").is_empty());
+		let rows: u32 = db
+			.conn
+			.query_row(
+				"SELECT COUNT(*) FROM article_tag WHERE tag = 'conifer'",
+				params![],
+				|row| row.get(0),
+			)
+			.unwrap();
+		assert_eq!(rows, 0);
+		assert_eq!(db.get_tags(first).unwrap(), ["trees"]);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_rename_and_merge_tags() {
+		let path = temp_database_path("tag-rename");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let article = |title: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: String::new(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let first = db.create_article(&article("First")).unwrap();
+		let second = db.create_article(&article("Second")).unwrap();
+		let third = db.create_article(&article("Third")).unwrap();
+		db.add_tag(first, "linux").unwrap();
+		db.add_tag(second, "Linux").unwrap();
+		db.add_tag(second, "gnu-linux").unwrap();
+		db.add_tag(third, "gnu-linux").unwrap();
+		db.add_tag(third, "kernel").unwrap();
+		let counts = |db: &mut Database| db.get_tag_counts().unwrap();
+		assert_eq!(
+			counts(&mut db),
+			[
+				("gnu-linux".to_string(), 2),
+				("kernel".to_string(), 1),
+				("linux".to_string(), 2)
+			]
+		);
+
+		db.rename_tag("Kernel", " Core ").unwrap();
+		assert_eq!(db.get_tags(third).unwrap(), ["core", "gnu-linux"]);
+		// The target exists, the tags have to be merged instead
+		assert!(matches!(
+			db.rename_tag("gnu-linux", "LINUX"),
+			Err(DbError::AlreadyExists)
+		));
+		assert!(matches!(
+			db.rename_tag("missing", "other"),
+			Err(DbError::NotFound)
+		));
+		assert!(matches!(db.rename_tag("core", " "), Err(DbError::NotFound)));
+		// Tags are case-folded, so a case-only rename keeps the tag
+		db.rename_tag("core", "CORE").unwrap();
+		assert_eq!(db.get_tags(third).unwrap(), ["core", "gnu-linux"]);
+
+		// The second article has both tags and keeps one of them
+		db.merge_tag("gnu-linux", "linux").unwrap();
+		assert_eq!(
+			counts(&mut db),
+			[("core".to_string(), 1), ("linux".to_string(), 3)]
+		);
+		assert_eq!(db.get_tags(second).unwrap(), ["linux"]);
+		assert_eq!(db.get_tags(third).unwrap(), ["core", "linux"]);
+		assert!(matches!(
+			db.merge_tag("gnu-linux", "linux"),
+			Err(DbError::NotFound)
+		));
+		db.merge_tag("Linux", "linux").unwrap();
+		assert_eq!(db.get_articles_by_tag("linux").unwrap().len(), 3);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_get_articles_page() {
+		let path = temp_database_path("articles-page");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let start = UtcTimestamp::now();
+		for (i, title) in ["cedar", "Beech", "alder", "Dogwood", "elm"]
+			.iter()
+			.enumerate()
+		{
+			let date = start + chrono::Duration::seconds(i as i64);
+			db.create_article(&Article {
+				id: 0.into(),
+				title: title.to_string(),
+				text: "Bark".to_string(),
+				date_created: date,
+				date_modified: date,
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		}
+		let deleted = db.get_article_id_by_title("elm").unwrap().unwrap();
+		db.delete_article(deleted).unwrap();
+		let mut titles = |offset, limit, order| {
+			let (articles, total) = db.get_articles_page(offset, limit, order).unwrap();
+			assert_eq!(total, 4);
+			articles
+				.into_iter()
+				.map(|article| article.title)
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(titles(0, 2, ArticleOrder::Id), ["cedar", "Beech"]);
+		assert_eq!(titles(2, 2, ArticleOrder::Id), ["alder", "Dogwood"]);
+		assert_eq!(
+			titles(0, 3, ArticleOrder::Title),
+			["alder", "Beech", "cedar"]
+		);
+		assert_eq!(titles(3, 3, ArticleOrder::Title), ["Dogwood"]);
+		assert_eq!(titles(0, 1, ArticleOrder::Modified), ["Dogwood"]);
+		assert!(titles(4, 2, ArticleOrder::Id).is_empty());
+		assert!(titles(u32::MAX, u32::MAX, ArticleOrder::Id).is_empty());
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_article_links() {
+		let path = temp_database_path("article-links");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let mut create = |title: &str| {
+			db.create_article(&Article {
+				id: 0.into(),
+				title: title.to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap()
+		};
+		let redwood = create("Redwood");
+		let sequoia = create("sequoia");
+		let oak = create("Oak");
+		let titles = |db: &mut Database, id: ItemId| -> Vec<String> {
+			db.get_backlinks(id)
+				.unwrap()
+				.into_iter()
+				.map(|article| article.title)
+				.collect()
+		};
+
+		db.set_article_links(sequoia, &[redwood, oak, redwood, sequoia])
+			.unwrap();
+		db.set_article_links(oak, &[redwood]).unwrap();
+		assert_eq!(titles(&mut db, redwood), ["Oak", "sequoia"]);
+		assert_eq!(db.count_backlinks(redwood), Some(2));
+		// No links to itself
+		assert!(titles(&mut db, sequoia).is_empty());
+
+		// Saving again replaces the old links
+		db.set_article_links(sequoia, &[oak]).unwrap();
+		assert_eq!(titles(&mut db, redwood), ["Oak"]);
+		assert_eq!(titles(&mut db, oak), ["sequoia"]);
+		db.set_article_links(oak, &[]).unwrap();
+		assert!(titles(&mut db, redwood).is_empty());
+
+		// Links from deleted articles are hidden until they are restored
+		db.delete_article(sequoia).unwrap();
+		assert!(titles(&mut db, oak).is_empty());
+		assert_eq!(db.count_backlinks(oak), Some(0));
+		db.restore_article(sequoia).unwrap();
+		assert_eq!(titles(&mut db, oak), ["sequoia"]);
+
+		// Links to articles that do not exist yet are kept
+		db.set_article_links(oak, &[1000.into()]).unwrap();
+		assert_eq!(db.count_backlinks(1000.into()), Some(1));
+
+		let link = |src: ItemId, dst: ItemId| ArticleLink {
+			src,
+			dst,
+			kind: LinkKind::ArticleRef,
+		};
+		let links = db.get_article_links(oak).unwrap();
+		assert_eq!(links.outgoing, [link(oak, 1000.into())]);
+		assert_eq!(links.incoming, [link(sequoia, oak)]);
+		let links = db.get_article_links(redwood).unwrap();
+		assert!(links.outgoing.is_empty() && links.incoming.is_empty());
+
+		// Pages hold all links of their sources
+		db.set_article_links(redwood, &[oak, sequoia]).unwrap();
+		assert_eq!(
+			db.get_links(None, 1).unwrap(),
+			[link(redwood, sequoia), link(redwood, oak)]
+		);
+		assert_eq!(
+			db.get_links(Some(redwood), 5).unwrap(),
+			[link(sequoia, oak), link(oak, 1000.into())]
+		);
+		assert!(db.get_links(Some(oak), 5).unwrap().is_empty());
+		db.delete_article(sequoia).unwrap();
+		assert_eq!(
+			db.get_links(Some(redwood), 1).unwrap(),
+			[link(oak, 1000.into())]
+		);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_render_epoch() {
+		let path = temp_database_path("render-epoch");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let start = db.render_epoch().unwrap();
+		let id = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let created = db.render_epoch().unwrap();
+		assert_ne!(start, created);
+		// Reading does not change it
+		db.get_article(id).unwrap().unwrap();
+		db.get_backlinks(id).unwrap();
+		assert_eq!(db.render_epoch().unwrap(), created);
+		db.add_tag(id, "tree").unwrap();
+		let tagged = db.render_epoch().unwrap();
+		assert_ne!(tagged, created);
+		db.delete_article(id).unwrap();
+		let deleted = db.render_epoch().unwrap();
+		assert_ne!(deleted, tagged);
+
+		// Writes of another connection, e.g. an import from the command
+		// line while the server runs
+		let mut other = DatabaseConnection::new(&path, OpenMode::OpenExisting)
+			.unwrap()
+			.init()
+			.unwrap();
+		other.restore_article(id).unwrap();
+		assert_ne!(db.render_epoch().unwrap(), deleted);
+		drop(other);
+		drop(db);
+
+		// A new run starts over, with different epochs
+		let mut db = DatabaseConnection::new(&path, OpenMode::OpenExisting)
+			.unwrap()
+			.init()
+			.unwrap();
+		assert_ne!(db.render_epoch().unwrap(), start);
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_search_articles_fts() {
+		let path = temp_database_path("fts");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let article = |title: &str, text: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: text.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let titles = |db: &mut Database, term: &str| -> Vec<String> {
+			db.search_articles_fts(term)
+				.unwrap()
+				.into_iter()
+				.map(|hit| hit.article.title)
+				.collect()
+		};
+		assert!(db.has_fts());
+		let mention = db
+			.create_article(&article("Forest", "A redwood grows here"))
+			.unwrap();
+		db.create_article(&article("Redwood", "Tall tree")).unwrap();
+		db.create_article(&article("Oak", "Broad tree")).unwrap();
+
+		// The title match ranks first
+		assert_eq!(titles(&mut db, "redwood"), ["Redwood", "Forest"]);
+		assert_eq!(titles(&mut db, "RED"), ["Redwood", "Forest"]);
+		assert_eq!(titles(&mut db, "tall tree"), ["Redwood"]);
+		let hits = db.search_articles_fts("tree").unwrap();
+		assert_eq!(hits.len(), 2);
+		assert!(hits.iter().all(|hit| hit.rank < 0.0));
+		// Not a syntax error
+		assert!(titles(&mut db, "\"redwood OR (").is_empty());
+		assert_eq!(titles(&mut db, "\"redwood"), ["Redwood", "Forest"]);
+		assert!(titles(&mut db, "  ").is_empty());
+
+		// The index follows changes and deletions
+		db.update_article(mention, Some("Woods"), Some("Only oaks"), None, None)
+			.unwrap();
+		assert_eq!(titles(&mut db, "redwood"), ["Redwood"]);
+		assert_eq!(titles(&mut db, "woods"), ["Woods"]);
+		db.delete_article(mention).unwrap();
+		assert!(titles(&mut db, "woods").is_empty());
+		db.restore_article(mention).unwrap();
+		assert_eq!(titles(&mut db, "woods"), ["Woods"]);
+
+		// Databases without the index have to use `search_articles`
+		db.conn
+			.execute_batch(
+				"DROP TRIGGER article_fts_insert; DROP TRIGGER article_fts_delete;
+				DROP TRIGGER article_fts_update; DROP TABLE article_fts;",
+			)
+			.unwrap();
+		assert!(!db.has_fts());
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_categories() {
+		let path = temp_database_path("categories");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let names = |categories: Vec<Category>| -> Vec<String> {
+			categories
+				.into_iter()
+				.map(|category| category.name)
+				.collect()
+		};
+		let projects = db.create_category("Projects", None).unwrap();
+		let home = db.create_category(" Home ", Some(projects)).unwrap();
+		let garden = db.create_category("Garden", Some(home)).unwrap();
+		let archive = db.create_category("Archive", None).unwrap();
+		assert!(matches!(
+			db.create_category("Orphan", Some(99.into())),
+			Err(DbError::NotFound)
+		));
+		assert_eq!(
+			names(db.get_categories(None).unwrap()),
+			["Archive", "Projects"]
+		);
+		assert_eq!(names(db.get_categories(Some(projects)).unwrap()), ["Home"]);
+		assert_eq!(
+			names(db.get_category_path(garden).unwrap()),
+			["Projects", "Home", "Garden"]
+		);
+		assert!(db.get_category_path(99.into()).unwrap().is_empty());
+		assert_eq!(db.get_category(garden).unwrap().parent, Some(home));
+
+		// Cycles are rejected
+		for parent in [projects, home, garden] {
+			assert!(matches!(
+				db.move_category(projects, Some(parent)),
+				Err(DbError::Cycle)
+			));
+		}
+		assert!(matches!(
+			db.move_category(projects, Some(99.into())),
+			Err(DbError::NotFound)
+		));
+		assert!(matches!(
+			db.move_category(99.into(), None),
+			Err(DbError::NotFound)
+		));
+		db.move_category(home, Some(archive)).unwrap();
+		assert_eq!(
+			names(db.get_category_path(garden).unwrap()),
+			["Archive", "Home", "Garden"]
+		);
+		db.move_category(home, None).unwrap();
+		assert_eq!(
+			names(db.get_category_path(garden).unwrap()),
+			["Home", "Garden"]
+		);
+
+		let article = db
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tomatoes".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		assert_eq!(db.get_article_category(article), None);
+		db.set_article_category(article, Some(garden)).unwrap();
+		assert_eq!(db.get_article_category(article), Some(garden));
+		assert_eq!(db.get_articles_in_category(garden).unwrap().len(), 1);
+		assert!(db.get_articles_in_category(home).unwrap().is_empty());
+		assert!(matches!(
+			db.set_article_category(article, Some(99.into())),
+			Err(DbError::NotFound)
+		));
+		assert!(matches!(
+			db.set_article_category(99.into(), Some(garden)),
+			Err(DbError::NotFound)
+		));
+		db.delete_article(article).unwrap();
+		assert!(db.get_articles_in_category(garden).unwrap().is_empty());
+		db.restore_article(article).unwrap();
+		db.set_article_category(article, None).unwrap();
+		assert_eq!(db.get_article_category(article), None);
+		assert!(db.get_articles_in_category(garden).unwrap().is_empty());
+		drop(db);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_favicon_cache() {
+		let path = temp_database_path("favicon-cache");
+		let mut db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		let long_ago = UtcTimestamp::now() - chrono::Duration::days(1);
+		let icon = Icon {
+			content_type: "image/png".to_string(),
+			data: vec![1, 2, 3],
+		};
+
+		assert!(db.get_favicon("a.example", long_ago).is_none());
+		db.store_favicon("a.example", Some(&icon), 2);
+		db.store_favicon("b.example", None, 2);
+		let cached = db.get_favicon("a.example", long_ago).unwrap().unwrap();
+		assert_eq!(cached.content_type, "image/png");
+		assert_eq!(cached.data, vec![1, 2, 3]);
+		assert!(db.get_favicon("b.example", long_ago).unwrap().is_none());
+
+		// Expired entries are not returned
+		let future = UtcTimestamp::now() + chrono::Duration::days(1);
+		assert!(db.get_favicon("a.example", future).is_none());
+
+		// The oldest entry is dropped beyond the size cap
+		db.store_favicon("c.example", None, 2);
+		assert!(db.get_favicon("a.example", long_ago).is_none());
+		assert!(db.get_favicon("c.example", long_ago).is_some());
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	fn memory_db() -> Database {
+		DatabaseConnection::new(Path::new(""), OpenMode::InMemory)
+			.unwrap()
+			.init()
+			.unwrap()
+	}
+
+	fn new_article(title: &str, text: &str) -> Article {
+		Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: text.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		}
+	}
+
+	#[test]
+	fn test_in_memory_articles() {
+		let mut db = memory_db();
+		assert_eq!(db.get_table_layout().unwrap().version, LAYOUT_VERSION);
+		assert!(db.get_all_articles().unwrap().is_empty());
+
+		let id = db
+			.create_article(&new_article("Coast redwood", "Tallest tree"))
+			.unwrap();
+		let article = db.get_article(id).unwrap().unwrap();
+		assert_eq!(article.title, "Coast redwood");
+		assert_eq!(article.text, "Tallest tree");
+		assert_eq!(article.slug, "coast-redwood");
+		assert!(db.get_article(999.into()).unwrap().is_none());
+
+		assert_eq!(
+			db.update_article(
+				id,
+				None,
+				Some("Tallest tree on earth"),
+				None,
+				Some(article.revision)
+			)
+			.unwrap(),
+			1
+		);
+		// Stale revisions change nothing
+		assert_eq!(
+			db.update_article(id, None, Some("Short"), None, Some(article.revision))
+				.unwrap(),
+			0
+		);
+		let updated = db.get_article(id).unwrap().unwrap();
+		assert_eq!(updated.text, "Tallest tree on earth");
+		assert_eq!(updated.revision, article.revision + 1);
+
+		let other = db
+			.create_article(&new_article("Douglas fir", "Not a redwood"))
+			.unwrap();
+		let mut hits: Vec<ItemId> = db
+			.search_articles("redwood")
+			.unwrap()
+			.into_iter()
+			.map(|article| article.id)
+			.collect();
+		hits.sort_by_key(|id| id.value);
+		assert_eq!(hits, vec![id, other]);
+		let hits = db.search_articles("earth").unwrap();
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].id, id);
+		assert!(db.search_articles("sequoia").unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_in_memory_duplicate_title() {
+		let mut db = memory_db();
+		let id = db.create_article(&new_article("Redwood", "")).unwrap();
+		assert!(matches!(
+			db.create_article(&new_article("Redwood", "")),
+			Err(CreateArticleError::DuplicateTitle)
+		));
+		assert!(matches!(
+			db.create_article(&new_article(" REDWOOD ", "")),
+			Err(CreateArticleError::DuplicateTitle)
+		));
+		assert!(matches!(
+			db.create_article(&new_article("  ", "")),
+			Err(CreateArticleError::EmptyTitle)
+		));
+
+		let other = db.create_article(&new_article("Fir", "")).unwrap();
+		assert!(matches!(
+			db.update_article(other, Some("redwood"), None, None, None),
+			Err(DbError::AlreadyExists)
+		));
+		assert_eq!(db.get_article(other).unwrap().unwrap().title, "Fir");
+		assert_eq!(db.get_all_articles().unwrap().len(), 2);
+
+		// Each in-memory database starts empty
+		assert!(memory_db().get_article(id).unwrap().is_none());
+	}
+
+	#[test]
+	fn test_export_all() {
+		let mut db = memory_db();
+		let mut json = Vec::new();
+		assert_eq!(db.export_all(&mut json).unwrap(), 0);
+		let export: serde_json::Value = serde_json::from_slice(&json).unwrap();
+		assert_eq!(export["format_version"], EXPORT_FORMAT_VERSION);
+		assert_eq!(export["layout_version"], LAYOUT_VERSION.to_string());
+		assert!(export["date_exported"].is_string());
+		assert_eq!(export["articles"], serde_json::json!([]));
+
+		let redwood = db
+			.create_article(&new_article("Redwood", "Tall \"tree\""))
+			.unwrap();
+		db.update_article(redwood, Some("Coast redwood"), None, Some("Rename"), None)
+			.unwrap();
+		db.add_tag(redwood, "tree").unwrap();
+		db.add_tag(redwood, "conifer").unwrap();
+		let fir = db.create_article(&new_article("Fir", "")).unwrap();
+		let trashed = db.create_article(&new_article("Trashed", "")).unwrap();
+		db.delete_article(trashed).unwrap();
+
+		let mut json = Vec::new();
+		assert_eq!(db.export_all(&mut json).unwrap(), 2);
+		let json = String::from_utf8(json).unwrap();
+		// One line per article
+		assert_eq!(json.lines().count(), 4);
+		let export: serde_json::Value = serde_json::from_str(&json).unwrap();
+		let articles = export["articles"].as_array().unwrap();
+		assert_eq!(articles.len(), 2);
+		let article = &articles[0];
+		assert_eq!(article["id"], redwood.value);
+		assert_eq!(article["title"], "Coast redwood");
+		assert_eq!(article["slug"], "redwood");
+		assert_eq!(article["text"], "Tall \"tree\"");
+		assert_eq!(article["revision"], 1);
+		assert_eq!(article["tags"], serde_json::json!(["conifer", "tree"]));
+		let revisions = article["revisions"].as_array().unwrap();
+		assert_eq!(revisions.len(), 2);
+		assert_eq!(revisions[0]["revision"], 0);
+		assert_eq!(revisions[1]["summary"], "Rename");
+		assert_eq!(revisions[1]["old_title"], "Redwood");
+		assert_eq!(revisions[1]["new_title"], "Coast redwood");
+		assert_eq!(articles[1]["id"], fir.value);
+		assert_eq!(articles[1]["tags"], serde_json::json!([]));
+	}
+
+	#[test]
+	fn test_import() {
+		let mut source = memory_db();
+		let redwood = source
+			.create_article(&new_article("Redwood", "See [article:3]"))
+			.unwrap();
+		source
+			.update_article(redwood, Some("Coast redwood"), None, Some("Rename"), None)
+			.unwrap();
+		source.add_tag(redwood, "conifer").unwrap();
+		let trashed = source.create_article(&new_article("Trashed", "")).unwrap();
+		source.delete_article(trashed).unwrap();
+		let fir = source
+			.create_article(&new_article("Fir", "Not tall"))
+			.unwrap();
+		let mut export = Vec::new();
+		source.export_all(&mut export).unwrap();
+		let original = source.get_article(redwood).unwrap().unwrap();
+
+		// Into an empty wiki, everything stays as it was
+		let mut db = memory_db();
+		let links = |text: &str| {
+			if text.contains("[article:3]") {
+				vec![fir]
+			} else {
+				Vec::new()
+			}
+		};
+		let summary = db.import(&export[..], ImportConflict::Skip, links).unwrap();
+		assert_eq!(
+			summary,
+			ImportSummary {
+				imported: 2,
+				skipped: 0,
+				failed: 0
+			}
+		);
+		let imported = db.get_article(redwood).unwrap().unwrap();
+		assert_eq!(imported.title, "Coast redwood");
+		assert_eq!(imported.slug, original.slug);
+		assert_eq!(imported.revision, 1);
+		assert_eq!(
+			imported.date_created.rfc3339(),
+			original.date_created.rfc3339()
+		);
+		assert_eq!(
+			imported.date_modified.rfc3339(),
+			original.date_modified.rfc3339()
+		);
+		assert_eq!(db.get_tags(redwood).unwrap(), ["conifer"]);
+		let revisions = db.get_revisions(redwood).unwrap();
+		assert_eq!(revisions.len(), 2);
+		assert_eq!(revisions[0].summary, "Rename");
+		assert_eq!(
+			revisions[0].title_change.as_ref().unwrap().old_title,
+			"Redwood"
+		);
+		assert_eq!(db.get_article(fir).unwrap().unwrap().text, "Not tall");
+		assert_eq!(db.get_backlinks(fir).unwrap()[0].id, redwood);
+
+		// Taken titles
+		db.update_article(fir, None, Some("Changed"), None, None)
+			.unwrap();
+		let skipped = db.import(&export[..], ImportConflict::Skip, links).unwrap();
+		assert_eq!((skipped.imported, skipped.skipped), (0, 2));
+		assert_eq!(db.get_article(fir).unwrap().unwrap().text, "Changed");
+		let overwritten = db
+			.import(&export[..], ImportConflict::Overwrite, links)
+			.unwrap();
+		assert_eq!((overwritten.imported, overwritten.skipped), (2, 0));
+		let restored = db.get_article(fir).unwrap().unwrap();
+		assert_eq!(restored.text, "Not tall");
+		assert_eq!(restored.revision, 0);
+		assert_eq!(db.get_revisions(fir).unwrap().len(), 1);
+		let renamed = db
+			.import(&export[..], ImportConflict::Rename, links)
+			.unwrap();
+		assert_eq!(renamed.imported, 2);
+		let id = db
+			.get_article_id_by_title("Coast redwood (2)")
+			.unwrap()
+			.unwrap();
+		let copy = db.get_article(id).unwrap().unwrap();
+		assert_ne!(copy.id, redwood);
+		assert_eq!(copy.slug, "coast-redwood-2");
+		assert!(db.get_article_id_by_title("Fir (2)").unwrap().is_some());
+		assert_eq!(db.get_all_articles().unwrap().len(), 4);
+	}
+
+	#[test]
+	fn test_check_history() {
+		let article = |revisions: serde_json::Value| -> ImportedArticle {
+			serde_json::from_value(serde_json::json!({
+				"title": "Redwood",
+				"text": "Tall",
+				"date_created": "2024-01-01T00:00:00Z",
+				"date_modified": "2024-01-03T00:00:00Z",
+				"revision": 2,
+				"revisions": revisions,
+			}))
+			.unwrap()
+		};
+		let revision = |revision: i64, day: u32| {
+			serde_json::json!({
+				"revision": revision,
+				"date_created": format!("2024-01-{:02}T00:00:00Z", day),
+			})
+		};
+		let problem = |index, problem| {
+			Err(HistoryError {
+				index: Some(index),
+				problem,
+			})
+		};
+		assert_eq!(article(serde_json::json!([])).check_history(), Ok(()));
+		assert_eq!(
+			article(serde_json::json!([
+				revision(0, 1),
+				revision(1, 1),
+				revision(2, 3)
+			]))
+			.check_history(),
+			Ok(())
+		);
+		// Numbers may have gaps, e.g. from an older wiki
+		assert_eq!(
+			article(serde_json::json!([revision(0, 1), revision(2, 3)])).check_history(),
+			Ok(())
+		);
+		assert_eq!(
+			article(serde_json::json!([
+				revision(0, 1),
+				revision(0, 2),
+				revision(2, 3)
+			]))
+			.check_history(),
+			problem(1, "revision numbers must increase")
+		);
+		assert_eq!(
+			article(serde_json::json!([
+				revision(0, 2),
+				revision(1, 1),
+				revision(2, 3)
+			]))
+			.check_history(),
+			problem(1, "revision is older than the one before")
+		);
+		assert_eq!(
+			article(serde_json::json!([revision(-1, 1), revision(2, 3)])).check_history(),
+			problem(0, "revision numbers must not be negative")
+		);
+		assert_eq!(
+			article(serde_json::json!([revision(2, 4)])).check_history(),
+			problem(0, "revision is newer than the last change of the article")
+		);
+		assert_eq!(
+			article(serde_json::json!([revision(0, 1), revision(1, 2)])).check_history(),
+			Err(HistoryError {
+				index: None,
+				problem: "the last revision must be the revision of the article",
+			})
+		);
+		assert_eq!(
+			article(serde_json::json!([revision(0, 1), revision(2, 2)])).check_history(),
+			Err(HistoryError {
+				index: None,
+				problem: "the last revision must be dated like the last change of the article",
+			})
+		);
+
+		let lines = ImportedArticle::from_json_lines(
+			"{\"title\": \"Redwood\", \"text\": \"Tall\", \"date_created\": \"2024-01-01T00:00:00Z\", \"date_modified\": \"2024-01-03T00:00:00Z\", \"revision\": 2, \"tags\": [\"tree\"]}\n\
+			{\"revision\": 0, \"date_created\": \"2024-01-01T00:00:00Z\"}\n\
+			\n\
+			{\"revision\": 2, \"date_created\": \"2024-01-03T00:00:00Z\", \"summary\": \"Taller\"}\n",
+		)
+		.unwrap();
+		assert_eq!(lines.tags, ["tree"]);
+		assert_eq!(lines.revisions.len(), 2);
+		assert_eq!(lines.revisions[1].summary, "Taller");
+		assert_eq!(lines.check_history(), Ok(()));
+		assert!(ImportedArticle::from_json_lines("").is_err());
+		assert!(ImportedArticle::from_json_lines(
+			"{\"title\": \"Redwood\", \"text\": \"\", \"date_created\": \"2024-01-01T00:00:00Z\", \"date_modified\": \"2024-01-01T00:00:00Z\", \"revision\": 0}\n[]"
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn test_import_refused() {
+		let mut db = memory_db();
+		let import = |db: &mut Database, json: &str| {
+			db.import(json.as_bytes(), ImportConflict::Skip, |_| Vec::new())
+		};
+		let article = r#"{"id":1,"title":"Fir","text":"","date_created":"2024-03-01T10:00:00Z","date_modified":"2024-03-01T10:00:00Z","revision":0}"#;
+
+		assert!(matches!(
+			import(&mut db, r#"{"format_version":2,"articles":[]}"#),
+			Err(ImportError::UnsupportedFormat(2))
+		));
+		let err = import(
+			&mut db,
+			r#"{"format_version":1,"layout_version":"99.0.0","articles":[]}"#,
+		)
+		.unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			format!(
+				"the export was made by a newer version of redwood-wiki (layout 99.0.0), this build supports {}",
+				LAYOUT_VERSION
+			)
+		);
+		assert!(matches!(
+			import(&mut db, r#"{"articles":[]}"#),
+			Err(ImportError::Damaged { .. })
+		));
+		assert!(matches!(
+			import(&mut db, "[]"),
+			Err(ImportError::Damaged { .. })
+		));
+
+		// The articles before the damage stay
+		let damaged = format!(r#"{{"format_version":1,"articles":[{},{{"id":2"#, article);
+		match import(&mut db, &damaged) {
+			Err(ImportError::Damaged { partial, .. }) => assert_eq!(partial.imported, 1),
+			other => panic!("Unexpected result {:?}", other),
+		}
+		assert!(db.get_article_id_by_title("Fir").unwrap().is_some());
+
+		// Articles that cannot be stored are counted
+		let empty_title = article.replace("Fir", " ");
+		let summary = import(
+			&mut db,
+			&format!(r#"{{"format_version":1,"articles":[{}]}}"#, empty_title),
+		)
+		.unwrap();
+		assert_eq!(summary.failed, 1);
+	}
 }
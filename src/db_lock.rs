@@ -0,0 +1,260 @@
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::database::Database;
+
+/// Receives the timings of the database lock
+pub trait LockMetrics: Send + Sync {
+	/// How long a caller waited for the lock
+	fn record_wait(&self, wait: Duration);
+	/// How long the lock was held
+	fn record_hold(&self, hold: Duration);
+	/// A hold longer than the threshold, by the code at `caller`
+	fn record_slow_hold(&self, hold: Duration, caller: &'static Location<'static>);
+}
+
+/// The database behind a mutex that measures how long callers wait for
+/// it and how long they hold it. The lock is the bottleneck when the
+/// wiki is slow under load, holds longer than `slow_hold` are logged
+/// with the code location of the caller, which tells the handler.
+pub struct DbLock<M: LockMetrics = LockHistograms> {
+	db: Mutex<Database>,
+	metrics: M,
+	slow_hold: Duration,
+}
+
+impl DbLock {
+	pub fn new(db: Database, slow_hold: Duration) -> DbLock {
+		DbLock::with_metrics(db, slow_hold, LockHistograms::default())
+	}
+}
+
+impl<M: LockMetrics> DbLock<M> {
+	pub fn with_metrics(db: Database, slow_hold: Duration, metrics: M) -> DbLock<M> {
+		DbLock {
+			db: Mutex::new(db),
+			metrics,
+			slow_hold,
+		}
+	}
+
+	pub fn metrics(&self) -> &M {
+		&self.metrics
+	}
+
+	/// Wait for the database. `#[track_caller]` needs a plain function,
+	/// so the caller is taken before the future is created.
+	#[track_caller]
+	pub fn lock(&self) -> impl Future<Output = DbGuard<'_, M>> {
+		let caller = Location::caller();
+		async move {
+			let start = Instant::now();
+			let guard = self.db.lock().await;
+			let locked = Instant::now();
+			self.metrics.record_wait(locked - start);
+			DbGuard {
+				guard,
+				lock: self,
+				locked,
+				caller,
+			}
+		}
+	}
+}
+
+/// Access to the database, the hold time is measured until it is dropped
+pub struct DbGuard<'a, M: LockMetrics = LockHistograms> {
+	guard: MutexGuard<'a, Database>,
+	lock: &'a DbLock<M>,
+	locked: Instant,
+	caller: &'static Location<'static>,
+}
+
+impl<M: LockMetrics> Deref for DbGuard<'_, M> {
+	type Target = Database;
+
+	fn deref(&self) -> &Database {
+		&self.guard
+	}
+}
+
+impl<M: LockMetrics> DerefMut for DbGuard<'_, M> {
+	fn deref_mut(&mut self) -> &mut Database {
+		&mut self.guard
+	}
+}
+
+impl<M: LockMetrics> Drop for DbGuard<'_, M> {
+	fn drop(&mut self) {
+		let hold = self.locked.elapsed();
+		let metrics = &self.lock.metrics;
+		metrics.record_hold(hold);
+		if hold > self.lock.slow_hold {
+			log::warn!(
+				"Database lock held for {} ms by {}",
+				hold.as_millis(),
+				self.caller
+			);
+			metrics.record_slow_hold(hold, self.caller);
+		}
+	}
+}
+
+/// Upper bounds of the buckets of a `Histogram` in milliseconds, the
+/// last bucket has no bound
+pub const BUCKETS_MS: [u64; 8] = [1, 5, 10, 50, 100, 500, 1000, 5000];
+
+/// Number of durations per bucket of `BUCKETS_MS`
+#[derive(Default)]
+pub struct Histogram {
+	counts: [AtomicU64; BUCKETS_MS.len() + 1],
+}
+
+impl Histogram {
+	pub fn record(&self, duration: Duration) {
+		let millis = duration.as_millis();
+		let bucket = BUCKETS_MS
+			.iter()
+			.position(|&bound| millis <= bound.into())
+			.unwrap_or(BUCKETS_MS.len());
+		self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// The counts of the buckets, the last one for durations beyond the
+	/// last bound
+	pub fn counts(&self) -> [u64; BUCKETS_MS.len() + 1] {
+		std::array::from_fn(|i| self.counts[i].load(Ordering::Relaxed))
+	}
+}
+
+/// The timings of the lock since the start, shown on the about page
+#[derive(Default)]
+pub struct LockHistograms {
+	pub wait: Histogram,
+	pub hold: Histogram,
+	slow_holds: AtomicU64,
+}
+
+impl LockHistograms {
+	pub fn slow_holds(&self) -> u64 {
+		self.slow_holds.load(Ordering::Relaxed)
+	}
+}
+
+impl LockMetrics for LockHistograms {
+	fn record_wait(&self, wait: Duration) {
+		self.wait.record(wait);
+	}
+
+	fn record_hold(&self, hold: Duration) {
+		self.hold.record(hold);
+	}
+
+	fn record_slow_hold(&self, _: Duration, _: &'static Location<'static>) {
+		self.slow_holds.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::sync::Arc;
+
+	use crate::database::{DatabaseConnection, OpenMode};
+
+	#[derive(Default)]
+	struct MockMetrics {
+		waits: std::sync::Mutex<Vec<Duration>>,
+		holds: std::sync::Mutex<Vec<Duration>>,
+		slow_holds: std::sync::Mutex<Vec<(Duration, u32)>>,
+	}
+
+	impl LockMetrics for MockMetrics {
+		fn record_wait(&self, wait: Duration) {
+			self.waits.lock().unwrap().push(wait);
+		}
+
+		fn record_hold(&self, hold: Duration) {
+			self.holds.lock().unwrap().push(hold);
+		}
+
+		fn record_slow_hold(&self, hold: Duration, caller: &'static Location<'static>) {
+			assert_eq!(caller.file(), file!());
+			self.slow_holds.lock().unwrap().push((hold, caller.line()));
+		}
+	}
+
+	fn database(name: &str) -> (std::path::PathBuf, Database) {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-db-lock-{}-{}.sqlite",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		let db = DatabaseConnection::new(&path, OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		(path, db)
+	}
+
+	#[tokio::test]
+	async fn test_lock_metrics() {
+		let (path, db) = database("metrics");
+		let lock = Arc::new(DbLock::with_metrics(
+			db,
+			Duration::from_millis(100),
+			MockMetrics::default(),
+		));
+
+		// A quick critical section, through the guard like a handler
+		assert_eq!(lock.lock().await.count_articles().unwrap(), 0);
+		assert!(lock.metrics().slow_holds.lock().unwrap().is_empty());
+
+		// A slow one, while another caller waits
+		let guard = lock.lock().await;
+		let slow_line = line!() - 1;
+		let waiting = tokio::spawn({
+			let lock = lock.clone();
+			async move {
+				lock.lock().await.count_articles().unwrap();
+			}
+		});
+		tokio::time::sleep(Duration::from_millis(250)).await;
+		drop(guard);
+		waiting.await.unwrap();
+
+		let metrics = lock.metrics();
+		assert_eq!(metrics.waits.lock().unwrap().len(), 3);
+		assert!(metrics.waits.lock().unwrap()[2] >= Duration::from_millis(200));
+		let holds = metrics.holds.lock().unwrap();
+		assert_eq!(holds.len(), 3);
+		assert!(holds[1] >= Duration::from_millis(250));
+		let slow_holds = metrics.slow_holds.lock().unwrap();
+		assert_eq!(slow_holds.len(), 1);
+		assert_eq!(slow_holds[0], (holds[1], slow_line));
+		drop((holds, slow_holds));
+		drop(lock);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_histograms() {
+		let histograms = LockHistograms::default();
+		for millis in [0, 1, 2, 700, 6000] {
+			histograms.record_hold(Duration::from_millis(millis));
+		}
+		histograms.record_wait(Duration::from_micros(1500));
+		histograms.record_slow_hold(Duration::from_millis(6000), Location::caller());
+		assert_eq!(histograms.hold.counts(), [2, 1, 0, 0, 0, 0, 1, 0, 1]);
+		assert_eq!(histograms.wait.counts(), [1, 0, 0, 0, 0, 0, 0, 0, 0]);
+		assert_eq!(histograms.slow_holds(), 1);
+	}
+}
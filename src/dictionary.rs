@@ -0,0 +1,178 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of words in the custom dictionary
+pub const MAX_WORDS: usize = 10_000;
+/// Maximum length of a single word in characters
+pub const MAX_WORD_LENGTH: usize = 64;
+
+/// Custom dictionary of words the spell checker of the editor
+/// must not flag. It is stored as a plain text file with one
+/// word per line, so it can also be edited by hand.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+	path: Option<PathBuf>,
+	words: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub enum DictionaryError {
+	/// The word is empty, too long or contains characters other than
+	/// letters, digits, `_`, `-` and `'`
+	InvalidWord,
+	/// The dictionary already contains `MAX_WORDS` words
+	Full,
+	/// No dictionary file is configured, so words cannot be persisted
+	NoFile,
+	Io(std::io::Error),
+}
+
+impl From<std::io::Error> for DictionaryError {
+	fn from(err: std::io::Error) -> Self {
+		DictionaryError::Io(err)
+	}
+}
+
+pub fn is_valid_word(word: &str) -> bool {
+	!word.is_empty()
+		&& word.chars().count() <= MAX_WORD_LENGTH
+		&& word
+			.chars()
+			.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '\'')
+}
+
+impl Dictionary {
+	/// Load the dictionary from the given file. A missing file is
+	/// treated as an empty dictionary, it is created when the first
+	/// word is added. Invalid lines and duplicates are skipped.
+	pub fn load(path: &Path) -> std::io::Result<Dictionary> {
+		let mut dictionary = Dictionary {
+			path: Some(path.to_path_buf()),
+			words: BTreeSet::new(),
+		};
+		let content = match std::fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				log::info!("Dictionary {:?} does not exist yet", path);
+				return Ok(dictionary);
+			}
+			Err(err) => return Err(err),
+		};
+		for line in content
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty())
+		{
+			if !is_valid_word(line) {
+				log::warn!("Skipping invalid dictionary word: {:?}", line);
+			} else if dictionary.words.len() >= MAX_WORDS {
+				log::warn!("Dictionary exceeds {} words, skipping the rest", MAX_WORDS);
+				break;
+			} else {
+				dictionary.words.insert(line.to_string());
+			}
+		}
+		log::info!("Loaded {} dictionary words", dictionary.words.len());
+		Ok(dictionary)
+	}
+
+	pub fn words(&self) -> impl Iterator<Item = &str> {
+		self.words.iter().map(String::as_str)
+	}
+
+	/// Add a word and append it to the dictionary file. Returns
+	/// `false` if the word was already in the dictionary.
+	pub fn add(&mut self, word: &str) -> Result<bool, DictionaryError> {
+		if !is_valid_word(word) {
+			return Err(DictionaryError::InvalidWord);
+		}
+		if self.words.contains(word) {
+			return Ok(false);
+		}
+		if self.words.len() >= MAX_WORDS {
+			return Err(DictionaryError::Full);
+		}
+		let path = self.path.as_ref().ok_or(DictionaryError::NoFile)?;
+		let mut file = std::fs::OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(path)?;
+		writeln!(file, "{}", word)?;
+		self.words.insert(word.to_string());
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_dictionary_path(name: &str) -> PathBuf {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-dictionary-{}-{}.txt",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn test_valid_words() {
+		assert!(is_valid_word("rusqlite"));
+		assert!(is_valid_word("Schrödinger's"));
+		assert!(is_valid_word("pull-down_cmark"));
+		assert!(!is_valid_word(""));
+		assert!(!is_valid_word("two words"));
+		assert!(!is_valid_word("<script>"));
+		assert!(!is_valid_word(&"a".repeat(MAX_WORD_LENGTH + 1)));
+	}
+
+	#[test]
+	fn test_persistence_and_dedup() {
+		let path = temp_dictionary_path("persistence");
+		std::fs::write(&path, "warp\n\nrusqlite\nwarp\nnot valid\n").unwrap();
+
+		let mut dictionary = Dictionary::load(&path).unwrap();
+		assert_eq!(
+			dictionary.words().collect::<Vec<_>>(),
+			vec!["rusqlite", "warp"]
+		);
+		assert!(dictionary.add("syntect").unwrap());
+		assert!(!dictionary.add("syntect").unwrap());
+		assert!(matches!(
+			dictionary.add("not valid"),
+			Err(DictionaryError::InvalidWord)
+		));
+
+		let dictionary = Dictionary::load(&path).unwrap();
+		assert_eq!(
+			dictionary.words().collect::<Vec<_>>(),
+			vec!["rusqlite", "syntect", "warp"]
+		);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_limits() {
+		let path = temp_dictionary_path("limits");
+		let words: String = (0..MAX_WORDS + 5).map(|i| format!("word{}\n", i)).collect();
+		std::fs::write(&path, words).unwrap();
+
+		let mut dictionary = Dictionary::load(&path).unwrap();
+		assert_eq!(dictionary.words().count(), MAX_WORDS);
+		assert!(matches!(
+			dictionary.add("another"),
+			Err(DictionaryError::Full)
+		));
+		std::fs::remove_file(&path).unwrap();
+
+		let mut dictionary = Dictionary::default();
+		assert!(matches!(
+			dictionary.add("word"),
+			Err(DictionaryError::NoFile)
+		));
+	}
+}
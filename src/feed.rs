@@ -0,0 +1,224 @@
+use super::database::Article;
+use super::markdown_utils;
+use super::timestamp::UtcTimestamp;
+use super::urls::{self, BasePath};
+
+/// Path of the feed
+pub const PATH: &str = "/feed.xml";
+
+/// Number of articles in the feed
+pub const ENTRIES: u32 = 20;
+
+/// Length of the summary of an entry, in characters
+const SUMMARY_LENGTH: usize = 300;
+
+/// An article in the feed with the summary of its last edit (empty if
+/// the editor gave none)
+pub struct Entry {
+	pub article: Article,
+	pub edit_summary: String,
+}
+
+/// Atom feed (RFC 4287) of the given articles, most recently modified
+/// first, titled with the name of the wiki. Without a public URL, the links are paths on this site
+/// under `base_path` and the IDs are URNs, both relative to the wiki.
+pub fn atom(
+	entries: &[Entry],
+	wiki_name: &str,
+	public_url: Option<&str>,
+	base_path: &BasePath,
+	now: UtcTimestamp,
+) -> String {
+	let url =
+		|path: &str| urls::canonical_url(public_url, path).unwrap_or_else(|| base_path.url(path));
+	// The feed changes when its newest entry does
+	let updated = entries
+		.iter()
+		.map(|entry| entry.article.date_modified)
+		.max()
+		.unwrap_or(now);
+
+	let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+	out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+	element(&mut out, "title", wiki_name);
+	out.push_str("\t<subtitle>Recently changed articles</subtitle>\n");
+	element(
+		&mut out,
+		"id",
+		&urls::canonical_url(public_url, PATH)
+			.unwrap_or_else(|| "urn:redwood-wiki:feed".to_string()),
+	);
+	link(&mut out, "self", &url(PATH));
+	link(&mut out, "alternate", &url("/"));
+	element(&mut out, "updated", &updated.rfc3339());
+	out.push_str("\t<author>\n");
+	element(&mut out, "name", wiki_name);
+	out.push_str("\t</author>\n");
+	for Entry {
+		article,
+		edit_summary,
+	} in entries
+	{
+		let path = urls::article_path(article.id, &article.slug);
+		out.push_str("\t<entry>\n");
+		element(&mut out, "title", &article.title);
+		element(
+			&mut out,
+			"id",
+			&urls::canonical_url(public_url, &format!("/article/{}", article.id))
+				.unwrap_or_else(|| format!("urn:redwood-wiki:article:{}", article.id)),
+		);
+		link(&mut out, "alternate", &url(&path));
+		element(&mut out, "published", &article.date_created.rfc3339());
+		element(&mut out, "updated", &article.date_modified.rfc3339());
+		// The summary of the edit tells readers what changed, before the
+		// beginning of the text
+		let excerpt = markdown_utils::plain_text_summary(&article.text, SUMMARY_LENGTH);
+		let summary = match (edit_summary.is_empty(), excerpt.is_empty()) {
+			(true, _) => excerpt,
+			(false, true) => edit_summary.clone(),
+			(false, false) => format!("{} \u{2014} {}", edit_summary, excerpt),
+		};
+		if !summary.is_empty() {
+			element(&mut out, "summary", &summary);
+		}
+		out.push_str("\t</entry>\n");
+	}
+	out.push_str("</feed>\n");
+	out
+}
+
+fn element(out: &mut String, name: &str, text: &str) {
+	out.push_str("\t<");
+	out.push_str(name);
+	out.push('>');
+	escape_xml(out, text);
+	out.push_str("</");
+	out.push_str(name);
+	out.push_str(">\n");
+}
+
+fn link(out: &mut String, rel: &str, href: &str) {
+	out.push_str("\t<link rel=\"");
+	out.push_str(rel);
+	out.push_str("\" href=\"");
+	escape_xml(out, href);
+	out.push_str("\"/>\n");
+}
+
+/// Escape text for element content and attribute values. Characters
+/// XML 1.0 does not allow at all are dropped.
+pub fn escape_xml(out: &mut String, text: &str) {
+	for c in text.chars() {
+		match c {
+			'&' => out.push_str("&amp;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			'"' => out.push_str("&quot;"),
+			'\'' => out.push_str("&apos;"),
+			'\t' | '\n' | '\r' => out.push(c),
+			c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+			c => out.push(c),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::convert::TryFrom;
+
+	fn utc(text: &str) -> UtcTimestamp {
+		UtcTimestamp::from_naive_utc(
+			chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap(),
+		)
+	}
+
+	fn entry(id: u32, title: &str, text: &str, modified: &str) -> Entry {
+		let modified = utc(modified);
+		let article = Article {
+			id: id.into(),
+			title: title.to_string(),
+			text: text.to_string(),
+			date_created: modified - chrono::Duration::days(1),
+			date_modified: modified,
+			revision: 0,
+			slug: format!("slug-{}", id),
+		};
+		Entry {
+			article,
+			edit_summary: String::new(),
+		}
+	}
+
+	#[test]
+	fn test_atom() {
+		let now = utc("2024-05-01 00:00:00");
+		let mut articles = [
+			entry(
+				2,
+				"Trees & <shrubs>",
+				"# Tall\n\n*Very* tall \"trees\".",
+				"2024-03-02 10:20:30",
+			),
+			entry(1, "Empty", "", "2024-03-01 08:00:00"),
+		];
+		let feed = atom(
+			&articles,
+			"Redwood Wiki",
+			Some("https://wiki.example.com/"),
+			&BasePath::default(),
+			now,
+		);
+		assert!(feed.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n"));
+		assert!(feed.contains("\t<id>https://wiki.example.com/feed.xml</id>\n\t<link rel=\"self\" href=\"https://wiki.example.com/feed.xml\"/>\n"));
+		assert!(feed.contains(
+			"<feed xmlns=\"http://www.w3.org/2005/Atom\">\n\t<title>Redwood Wiki</title>\n"
+		));
+		assert!(feed.contains("\t<updated>2024-03-02T10:20:30Z</updated>\n\t<author>\n\t<name>Redwood Wiki</name>\n\t</author>\n"));
+		assert!(feed.contains(
+			"\t<entry>\n\t<title>Trees &amp; &lt;shrubs&gt;</title>\n\t<id>https://wiki.example.com/article/2</id>\n\t<link rel=\"alternate\" href=\"https://wiki.example.com/article/2/slug-2\"/>\n\t<published>2024-03-01T10:20:30Z</published>\n\t<updated>2024-03-02T10:20:30Z</updated>\n\t<summary>Tall Very tall &quot;trees&quot;.</summary>\n\t</entry>\n"
+		));
+		// No empty summary
+		assert!(feed.contains("<updated>2024-03-01T08:00:00Z</updated>\n\t</entry>\n</feed>\n"));
+
+		let feed = atom(
+			&articles[1..],
+			"Redwood Wiki",
+			None,
+			&BasePath::default(),
+			now,
+		);
+		assert!(feed.contains("<id>urn:redwood-wiki:feed</id>"));
+		assert!(feed.contains("<id>urn:redwood-wiki:article:1</id>"));
+		assert!(feed.contains("<link rel=\"alternate\" href=\"/article/1/slug-1\"/>"));
+		let base_path = BasePath::try_from("/wiki".to_string()).unwrap();
+		let feed = atom(&articles[1..], "Redwood Wiki", None, &base_path, now);
+		assert!(feed.contains("<link rel=\"self\" href=\"/wiki/feed.xml\"/>"));
+		assert!(feed.contains("<link rel=\"alternate\" href=\"/wiki\"/>"));
+		assert!(feed.contains("<link rel=\"alternate\" href=\"/wiki/article/1/slug-1\"/>"));
+		assert!(feed.contains("<id>urn:redwood-wiki:article:1</id>"));
+
+		// With a summary of the last edit
+		articles[0].edit_summary = "Fix <typo>".to_string();
+		articles[1].edit_summary = "Blank".to_string();
+		let feed = atom(&articles, "Redwood & Co", None, &BasePath::default(), now);
+		assert!(feed.contains("\t<title>Redwood &amp; Co</title>\n"));
+		assert!(feed.contains(
+			"<summary>Fix &lt;typo&gt; \u{2014} Tall Very tall &quot;trees&quot;.</summary>"
+		));
+		assert!(feed.contains("<summary>Blank</summary>"));
+
+		// Without entries, the feed is as new as the request
+		assert!(atom(&[], "Redwood Wiki", None, &BasePath::default(), now)
+			.contains("<updated>2024-05-01T00:00:00Z</updated>"));
+	}
+
+	#[test]
+	fn test_escape_xml() {
+		let mut out = String::new();
+		escape_xml(&mut out, "a\u{1}b\u{FFFF}\t'<&>\"");
+		assert_eq!(out, "ab\t&apos;&lt;&amp;&gt;&quot;");
+	}
+}
@@ -0,0 +1,63 @@
+use sha1::{Digest, Sha1};
+
+/// HMAC (RFC 2104) with SHA-1
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+	const BLOCK_SIZE: usize = 64;
+	let mut block = [0; BLOCK_SIZE];
+	if key.len() > BLOCK_SIZE {
+		block[..20].copy_from_slice(&Sha1::digest(key));
+	} else {
+		block[..key.len()].copy_from_slice(key);
+	}
+	let pad = |byte: u8| block.map(|b| b ^ byte);
+	let inner = Sha1::new()
+		.chain_update(pad(0x36))
+		.chain_update(message)
+		.finalize();
+	Sha1::new()
+		.chain_update(pad(0x5c))
+		.chain_update(inner)
+		.finalize()
+		.into()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hex(digest: [u8; 20]) -> String {
+		digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+	}
+
+	/// Test cases of RFC 2202
+	#[test]
+	fn test_hmac_sha1() {
+		assert_eq!(
+			hex(hmac_sha1(&[0x0b; 20], b"Hi There")),
+			"b617318655057264e28bc0b6fb378c8ef146be00"
+		);
+		assert_eq!(
+			hex(hmac_sha1(b"Jefe", b"what do ya want for nothing?")),
+			"effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+		);
+		assert_eq!(
+			hex(hmac_sha1(&[0xaa; 20], &[0xdd; 50])),
+			"125d7342b9ac11cd91a39af48aa17b4f63f175d3"
+		);
+		// Keys longer than a block are hashed first
+		assert_eq!(
+			hex(hmac_sha1(
+				&[0xaa; 80],
+				b"Test Using Larger Than Block-Size Key - Hash Key First"
+			)),
+			"aa4ae5e15272d00e95705637ce8a3b55ed402112"
+		);
+		assert_eq!(
+			hex(hmac_sha1(
+				&[0xaa; 80],
+				b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data"
+			)),
+			"e8e99d0f45237d786d6bbaa7965c7808bbff1a91"
+		);
+	}
+}
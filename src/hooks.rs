@@ -0,0 +1,451 @@
+use std::time::Duration;
+
+use warp::hyper;
+
+use super::config;
+
+/// Request sent to another server after an article was saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hook {
+	pub method: hyper::Method,
+	/// Target URL, see `expand_url` for the placeholders
+	pub url_template: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	/// Attempts per request, including the first one
+	pub attempts: u32,
+	/// Delay before the first retry, doubled for every further retry
+	pub initial_delay: Duration,
+	/// Timeout of a single attempt
+	pub timeout: Duration,
+}
+
+/// Hooks of the configuration, none if outbound requests are disabled
+pub fn from_config(config: &config::Config) -> (Vec<Hook>, RetryPolicy) {
+	let hooks = &config.hooks;
+	let policy = RetryPolicy {
+		attempts: hooks.attempts.max(1),
+		initial_delay: Duration::from_secs(1),
+		timeout: Duration::from_secs(hooks.timeout),
+	};
+	if !config.network.outbound_requests {
+		return (Vec::new(), policy);
+	}
+	let purge = hooks.purge_url.iter().map(|url| Hook {
+		method: hyper::Method::from_bytes(b"PURGE").expect("PURGE is a valid method"),
+		url_template: url.clone(),
+	});
+	let ping = hooks.ping_url.iter().map(|url| Hook {
+		method: hyper::Method::GET,
+		url_template: url.clone(),
+	});
+	(purge.chain(ping).collect(), policy)
+}
+
+/// Fill in the placeholders of a hook URL: `{path}` is replaced by the
+/// canonical path of the article (already URL-encoded), `{url}` by the
+/// URL-encoded canonical URL. Returns `None` if `{url}` is used but
+/// there is no canonical URL because `public_url` is not configured.
+pub fn expand_url(template: &str, path: &str, url: Option<&str>) -> Option<String> {
+	let mut expanded = template.replace("{path}", path);
+	if expanded.contains("{url}") {
+		let url = percent_encoding::utf8_percent_encode(url?, percent_encoding::NON_ALPHANUMERIC)
+			.to_string();
+		expanded = expanded.replace("{url}", &url);
+	}
+	Some(expanded)
+}
+
+/// Check a hook URL with example values for the placeholders, so that
+/// configuration mistakes show up at startup and not on the first save
+pub fn check_url_template(template: &str) -> Result<(), HookError> {
+	let url = expand_url(
+		template,
+		"/article/1/example",
+		Some("http://wiki.example/article/1/example"),
+	)
+	.ok_or(HookError::InvalidUrl)?;
+	let uri: hyper::Uri = url.parse().map_err(|_| HookError::InvalidUrl)?;
+	if default_port(&uri).is_none() || uri.host().is_none() {
+		return Err(HookError::InvalidUrl);
+	}
+	Ok(())
+}
+
+/// Port of the scheme of `uri`, `None` unless it is `http` or `https`
+fn default_port(uri: &hyper::Uri) -> Option<u16> {
+	match uri.scheme_str() {
+		Some("http") => Some(80),
+		Some("https") => Some(443),
+		_ => None,
+	}
+}
+
+#[derive(Debug)]
+pub enum HookError {
+	/// Not an absolute `http` or `https` URL
+	InvalidUrl,
+	Timeout,
+	Status(u16),
+	Io(std::io::Error),
+	Http(hyper::Error),
+}
+
+impl std::fmt::Display for HookError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			HookError::InvalidUrl => {
+				write!(f, "invalid URL, only http:// and https:// are supported")
+			}
+			HookError::Timeout => write!(f, "timeout"),
+			HookError::Status(status) => write!(f, "HTTP status {}", status),
+			HookError::Io(err) => write!(f, "{}", err),
+			HookError::Http(err) => write!(f, "{}", err),
+		}
+	}
+}
+
+impl From<std::io::Error> for HookError {
+	fn from(err: std::io::Error) -> Self {
+		HookError::Io(err)
+	}
+}
+
+impl From<hyper::Error> for HookError {
+	fn from(err: hyper::Error) -> Self {
+		HookError::Http(err)
+	}
+}
+
+async fn send_once(method: &hyper::Method, url: &str) -> Result<u16, HookError> {
+	let uri: hyper::Uri = url.parse().map_err(|_| HookError::InvalidUrl)?;
+	let default_port = default_port(&uri).ok_or(HookError::InvalidUrl)?;
+	let host = uri.host().ok_or(HookError::InvalidUrl)?;
+	let port = uri.port_u16().unwrap_or(default_port);
+	// Lookup failures may be temporary, they are retried like
+	// connection errors
+	let address = tokio::net::lookup_host((host, port))
+		.await?
+		.next()
+		.ok_or_else(|| {
+			std::io::Error::new(
+				std::io::ErrorKind::NotFound,
+				format!("no address for {}", host),
+			)
+		})?;
+	let host_header = match uri.port() {
+		Some(port) => format!("{}:{}", host, port),
+		None => host.to_string(),
+	};
+	let path = uri.path_and_query().map_or("/", |path| path.as_str());
+	let request = hyper::Request::builder()
+		.method(method.clone())
+		.uri(path)
+		.body(hyper::Body::empty())
+		.map_err(|_| HookError::InvalidUrl)?;
+	let response = if uri.scheme_str() == Some("https") {
+		super::outbound::send_tls::<HookError>(address, &host_header, request).await?
+	} else {
+		super::outbound::send::<HookError>(address, &host_header, request).await?
+	};
+	Ok(response.status().as_u16())
+}
+
+/// Send the request, retrying on connection errors, timeouts and
+/// server errors (5xx). Returns the status of the successful attempt.
+pub async fn execute(
+	method: &hyper::Method,
+	url: &str,
+	policy: &RetryPolicy,
+) -> Result<u16, HookError> {
+	let mut delay = policy.initial_delay;
+	let mut attempt = 1;
+	loop {
+		let result = tokio::time::timeout(policy.timeout, send_once(method, url))
+			.await
+			.unwrap_or(Err(HookError::Timeout));
+		let error = match result {
+			Ok(status) if (200..300).contains(&status) => return Ok(status),
+			// Client errors will not go away by retrying
+			Ok(status) if status < 500 => return Err(HookError::Status(status)),
+			Ok(status) => HookError::Status(status),
+			Err(HookError::InvalidUrl) => return Err(HookError::InvalidUrl),
+			Err(err) => err,
+		};
+		if attempt >= policy.attempts {
+			return Err(error);
+		}
+		log::debug!(
+			"Hook {} {} failed ({}), retrying in {:?}",
+			method,
+			url,
+			error,
+			delay
+		);
+		tokio::time::sleep(delay).await;
+		delay *= 2;
+		attempt += 1;
+	}
+}
+
+/// Run all hooks for the article with the given canonical path and URL
+/// concurrently and log the outcomes. Meant to be spawned, so that slow
+/// or failing hooks never delay the response to the editor.
+pub async fn run_article_saved(
+	hooks: Vec<Hook>,
+	policy: RetryPolicy,
+	path: String,
+	url: Option<String>,
+) {
+	let mut tasks = Vec::new();
+	for hook in hooks {
+		let Some(target) = expand_url(&hook.url_template, &path, url.as_deref()) else {
+			log::warn!(
+				"Hook {} needs `public_url` to be configured",
+				hook.url_template
+			);
+			continue;
+		};
+		let policy = policy.clone();
+		tasks.push(tokio::spawn(async move {
+			match execute(&hook.method, &target, &policy).await {
+				Ok(status) => log::info!("Hook {} {}: {}", hook.method, target, status),
+				Err(err) => log::warn!("Hook {} {} failed: {}", hook.method, target, err),
+			}
+		}));
+	}
+	for task in tasks {
+		let _ = task.await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::sync::{Arc, Mutex};
+
+	use warp::Filter;
+
+	#[test]
+	fn test_expand_url() {
+		assert_eq!(
+			expand_url("http://cache.local{path}", "/article/5/redwoods", None).as_deref(),
+			Some("http://cache.local/article/5/redwoods")
+		);
+		assert_eq!(
+			expand_url(
+				"http://search.example/ping?url={url}",
+				"/article/5/redwoods",
+				Some("https://wiki.example/article/5/redwoods")
+			)
+			.as_deref(),
+			Some("http://search.example/ping?url=https%3A%2F%2Fwiki%2Eexample%2Farticle%2F5%2Fredwoods")
+		);
+		assert_eq!(
+			expand_url("http://search.example/ping?url={url}", "/a", None),
+			None
+		);
+		assert_eq!(
+			expand_url("http://search.example/ping", "/a", None).as_deref(),
+			Some("http://search.example/ping")
+		);
+	}
+
+	#[test]
+	fn test_check_url_template() {
+		assert!(check_url_template("http://cache.local{path}").is_ok());
+		assert!(check_url_template("http://search.example/ping?url={url}").is_ok());
+		assert!(check_url_template("https://search.example/ping?url={url}").is_ok());
+		for template in [
+			"ftp://cache.local{path}",
+			"cache.local{path}",
+			"{path}",
+			"http://cache local/",
+		] {
+			assert!(
+				matches!(check_url_template(template), Err(HookError::InvalidUrl)),
+				"{}",
+				template
+			);
+		}
+	}
+
+	#[test]
+	fn test_from_config() {
+		let config = |outbound: bool| -> config::Config {
+			toml::from_str(&format!(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				outbound_requests = {}
+
+				[database]
+				storage_location = "."
+
+				[hooks]
+				purge_url = "http://cache.local{{path}}"
+				attempts = 0
+				"#,
+				outbound
+			))
+			.unwrap()
+		};
+		let (hooks, policy) = from_config(&config(true));
+		assert_eq!(
+			hooks,
+			vec![Hook {
+				method: hyper::Method::from_bytes(b"PURGE").unwrap(),
+				url_template: "http://cache.local{path}".to_string(),
+			}]
+		);
+		assert_eq!(policy.attempts, 1);
+		assert_eq!(policy.timeout, Duration::from_secs(5));
+		assert!(from_config(&config(false)).0.is_empty());
+	}
+
+	/// Local server answering with the given statuses in turn (the last
+	/// one repeatedly), recording method and path of each request
+	async fn test_server(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let recorded = requests.clone();
+		let route = warp::method()
+			.and(warp::path::full())
+			.and(warp::query::raw().or(warp::any().map(String::new)).unify())
+			.map(
+				move |method: hyper::Method, path: warp::path::FullPath, query: String| {
+					let mut requests = recorded.lock().unwrap();
+					requests.push(format!("{} {}?{}", method, path.as_str(), query));
+					let status = statuses[(requests.len() - 1).min(statuses.len() - 1)];
+					warp::reply::with_status("", warp::http::StatusCode::from_u16(status).unwrap())
+				},
+			);
+		let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+		tokio::spawn(server);
+		(format!("http://{}", address), requests)
+	}
+
+	fn policy() -> RetryPolicy {
+		RetryPolicy {
+			attempts: 3,
+			initial_delay: Duration::from_millis(1),
+			timeout: Duration::from_secs(2),
+		}
+	}
+
+	#[tokio::test]
+	async fn test_execute_retries_server_errors() {
+		let (base, requests) = test_server(vec![503, 500, 200]).await;
+		let purge = hyper::Method::from_bytes(b"PURGE").unwrap();
+		let status = execute(&purge, &format!("{}/article/1/x", base), &policy())
+			.await
+			.unwrap();
+		assert_eq!(status, 200);
+		assert_eq!(*requests.lock().unwrap(), vec!["PURGE /article/1/x?"; 3]);
+	}
+
+	#[tokio::test]
+	async fn test_execute_gives_up() {
+		// Server errors until the attempts are used up
+		let (base, requests) = test_server(vec![503]).await;
+		assert!(matches!(
+			execute(&hyper::Method::GET, &base, &policy()).await,
+			Err(HookError::Status(503))
+		));
+		assert_eq!(requests.lock().unwrap().len(), 3);
+
+		// Client errors are not retried
+		let (base, requests) = test_server(vec![404]).await;
+		assert!(matches!(
+			execute(
+				&hyper::Method::GET,
+				&format!("{}/ping?a=b", base),
+				&policy()
+			)
+			.await,
+			Err(HookError::Status(404))
+		));
+		assert_eq!(*requests.lock().unwrap(), vec!["GET /ping?a=b"]);
+
+		// Nothing listens on the port any more
+		let port = std::net::TcpListener::bind("127.0.0.1:0")
+			.unwrap()
+			.local_addr()
+			.unwrap()
+			.port();
+		assert!(matches!(
+			execute(
+				&hyper::Method::GET,
+				&format!("http://127.0.0.1:{}/", port),
+				&policy()
+			)
+			.await,
+			Err(HookError::Io(_))
+		));
+
+		// https:// goes over TLS, the self-signed certificate of the
+		// tests is refused like any connection error
+		let (address, server) = warp::serve(warp::any().map(|| ""))
+			.tls()
+			.cert_path("src/testdata/tls/cert.pem")
+			.key_path("src/testdata/tls/key.pem")
+			.bind_ephemeral(([127, 0, 0, 1], 0));
+		tokio::spawn(server);
+		let result = execute(
+			&hyper::Method::GET,
+			&format!("https://localhost:{}/", address.port()),
+			&policy(),
+		)
+		.await;
+		assert!(
+			matches!(&result, Err(HookError::Io(err)) if err.to_string().contains("certificate")),
+			"{:?}",
+			result
+		);
+
+		for url in ["ftp://example.com/", "not a url", "/relative"] {
+			assert!(matches!(
+				execute(&hyper::Method::GET, url, &policy()).await,
+				Err(HookError::InvalidUrl)
+			));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_run_article_saved() {
+		let (base, requests) = test_server(vec![200]).await;
+		let hooks = vec![
+			Hook {
+				method: hyper::Method::from_bytes(b"PURGE").unwrap(),
+				url_template: format!("{}{{path}}", base),
+			},
+			Hook {
+				method: hyper::Method::GET,
+				url_template: format!("{}/ping?url={{url}}", base),
+			},
+		];
+		run_article_saved(
+			hooks.clone(),
+			policy(),
+			"/article/5/redwoods".to_string(),
+			Some("https://wiki.example/article/5/redwoods".to_string()),
+		)
+		.await;
+		let mut recorded = requests.lock().unwrap().clone();
+		recorded.sort();
+		assert_eq!(
+			recorded,
+			vec![
+				"GET /ping?url=https%3A%2F%2Fwiki%2Eexample%2Farticle%2F5%2Fredwoods",
+				"PURGE /article/5/redwoods?",
+			]
+		);
+
+		// Without public URL, only the hook using `{path}` runs
+		run_article_saved(hooks, policy(), "/article/6/x".to_string(), None).await;
+		assert_eq!(requests.lock().unwrap().len(), 3);
+	}
+}
@@ -0,0 +1,210 @@
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+
+/// Language tag of the fenced code block that defines an infobox
+pub const INFOBOX_LANGUAGE: &str = "infobox";
+
+pub fn is_infobox(kind: &CodeBlockKind<'_>) -> bool {
+	matches!(kind, CodeBlockKind::Fenced(lang) if lang.as_ref() == INFOBOX_LANGUAGE)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Infobox {
+	/// `key: value` entries in the order of the block
+	pub entries: Vec<(String, String)>,
+	/// Lines that could not be parsed
+	pub warnings: Vec<String>,
+}
+
+/// Parse the contents of an infobox block. Every non-empty line is a
+/// `key: value` entry, split at the first colon so values may contain
+/// colons themselves (e.g. URLs).
+pub fn parse(block: &str) -> Infobox {
+	let mut infobox = Infobox {
+		entries: Vec::new(),
+		warnings: Vec::new(),
+	};
+	for line in block.lines().map(str::trim).filter(|line| !line.is_empty()) {
+		match line.split_once(':') {
+			Some((key, value)) if !key.trim().is_empty() => {
+				infobox
+					.entries
+					.push((key.trim().to_string(), value.trim().to_string()));
+			}
+			_ => infobox.warnings.push(format!(
+				"Infobox line is not of the form `key: value`: {}",
+				line
+			)),
+		}
+	}
+	infobox
+}
+
+/// Contents of all infobox blocks in the document, in document order
+pub fn extract(text: &str, options: Options) -> Vec<String> {
+	// Cheap check to avoid parsing the document twice in the common case
+	if !text.contains(INFOBOX_LANGUAGE) {
+		return Vec::new();
+	}
+	let mut blocks = Vec::new();
+	let mut current: Option<String> = None;
+	for event in Parser::new_ext(text, options) {
+		match event {
+			Event::Start(Tag::CodeBlock(kind)) if is_infobox(&kind) => {
+				current = Some(String::new());
+			}
+			Event::Text(text) => {
+				if let Some(block) = &mut current {
+					block.push_str(&text);
+				}
+			}
+			Event::End(Tag::CodeBlock(_)) => {
+				blocks.extend(current.take());
+			}
+			_ => {}
+		}
+	}
+	blocks
+}
+
+/// Removes all infobox blocks from the stream and places the rendered
+/// infobox `html` in front of the first paragraph (or at the end if
+/// there is no paragraph)
+pub struct InfoboxStream<'a, I> {
+	iter: I,
+	html: Option<String>,
+	inject_event: Option<Event<'a>>,
+	inside_infobox: bool,
+}
+
+impl<'a, I> InfoboxStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	pub fn new(iter: I, html: Option<String>) -> Self {
+		Self {
+			iter,
+			html,
+			inject_event: None,
+			inside_infobox: false,
+		}
+	}
+
+	fn take_html(&mut self) -> Option<Event<'a>> {
+		self.html
+			.take()
+			.map(|html| Event::Html(CowStr::Boxed(html.into_boxed_str())))
+	}
+}
+
+impl<'a, I> Iterator for InfoboxStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	type Item = Event<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(event) = self.inject_event.take() {
+			return Some(event);
+		}
+		loop {
+			match self.iter.next() {
+				Some(Event::Start(Tag::CodeBlock(kind))) if is_infobox(&kind) => {
+					self.inside_infobox = true;
+				}
+				Some(Event::End(Tag::CodeBlock(_))) if self.inside_infobox => {
+					self.inside_infobox = false;
+				}
+				Some(_) if self.inside_infobox => {}
+				Some(event @ Event::Start(Tag::Paragraph)) => {
+					return match self.take_html() {
+						Some(html) => {
+							self.inject_event = Some(event);
+							Some(html)
+						}
+						None => Some(event),
+					};
+				}
+				Some(event) => return Some(event),
+				None => return self.take_html(),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use pulldown_cmark::html;
+
+	#[test]
+	fn test_parse() {
+		let infobox = parse(
+			"Status: active\n\n  Owner :  Jane  \nWebsite: https://example.com:8080/a?b=c\nTime: 12:30\nno colon\n: no key\n",
+		);
+		assert_eq!(
+			infobox.entries,
+			vec![
+				("Status".to_string(), "active".to_string()),
+				("Owner".to_string(), "Jane".to_string()),
+				(
+					"Website".to_string(),
+					"https://example.com:8080/a?b=c".to_string()
+				),
+				("Time".to_string(), "12:30".to_string()),
+			]
+		);
+		assert_eq!(infobox.warnings.len(), 2);
+		assert!(infobox.warnings[0].ends_with("no colon"));
+
+		assert_eq!(
+			parse("Empty:").entries,
+			vec![("Empty".to_string(), String::new())]
+		);
+	}
+
+	#[test]
+	fn test_extract() {
+		let text = "# Title\n\n```infobox\nA: 1\nB: 2\n```\n\ntext\n\n```rust\nfn x() {}\n```\n\n~~~infobox\nC: 3\n~~~\n";
+		assert_eq!(
+			extract(text, Options::empty()),
+			vec!["A: 1\nB: 2\n", "C: 3\n"]
+		);
+		assert!(extract("No box here", Options::empty()).is_empty());
+		// Mentioning the word is not enough
+		assert!(extract("An `infobox` block", Options::empty()).is_empty());
+	}
+
+	fn place(text: &str) -> String {
+		let mut out = String::new();
+		html::push_html(
+			&mut out,
+			InfoboxStream::new(Parser::new(text), Some("<aside>BOX</aside>".to_string())),
+		);
+		out
+	}
+
+	#[test]
+	fn test_placement() {
+		// Before the first paragraph, even if the block comes later
+		assert_eq!(
+			place("# Title\n\nFirst\n\nSecond\n\n```infobox\nA: 1\n```\n"),
+			"<h1>Title</h1>\n<aside>BOX</aside>\n<p>First</p>\n<p>Second</p>\n"
+		);
+		// All blocks are removed, the box is placed once
+		assert_eq!(
+			place("```infobox\nA: 1\n```\n\n```infobox\nB: 2\n```\n\nText"),
+			"<aside>BOX</aside>\n<p>Text</p>\n"
+		);
+		// Without paragraph, at the end
+		assert_eq!(
+			place("# Title\n\n```infobox\nA: 1\n```\n"),
+			"<h1>Title</h1>\n<aside>BOX</aside>"
+		);
+		// Other code blocks are untouched
+		assert_eq!(
+			place("```rust\nA: 1\n```\n"),
+			"<pre><code class=\"language-rust\">A: 1\n</code></pre>\n<aside>BOX</aside>"
+		);
+	}
+}
@@ -0,0 +1,65 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, NaiveDate, TimeZone};
+
+use super::config::Journal;
+
+/// Title of the journal entry of `date`. `None` if the configured
+/// date format is invalid or the title would be empty.
+pub fn entry_title(journal: &Journal, date: NaiveDate) -> Option<String> {
+	let mut title = journal.title_prefix.clone();
+	// Formatting fails on invalid format specifiers
+	write!(title, "{}", date.format(&journal.date_format)).ok()?;
+	let title = title.trim().to_string();
+	if title.is_empty() {
+		None
+	} else {
+		Some(title)
+	}
+}
+
+/// The date of `now` in its time zone, i.e. the day a user of the
+/// wiki in that time zone considers today
+pub fn date_in<Tz: TimeZone>(now: DateTime<Tz>) -> NaiveDate {
+	now.date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use chrono::Utc;
+
+	fn journal(date_format: &str, title_prefix: &str) -> Journal {
+		Journal {
+			date_format: date_format.to_string(),
+			title_prefix: title_prefix.to_string(),
+			template_article: None,
+		}
+	}
+
+	#[test]
+	fn test_entry_title() {
+		let date = NaiveDate::from_ymd_opt(2024, 5, 12).unwrap();
+		assert_eq!(
+			entry_title(&Journal::default(), date).as_deref(),
+			Some("2024-05-12")
+		);
+		assert_eq!(
+			entry_title(&journal("%d.%m.%Y", "Journal "), date).as_deref(),
+			Some("Journal 12.05.2024")
+		);
+		assert_eq!(entry_title(&journal("%Q", ""), date), None);
+		assert_eq!(entry_title(&journal("", " "), date), None);
+	}
+
+	#[test]
+	fn test_date_in_time_zone() {
+		let now = Utc.with_ymd_and_hms(2024, 5, 12, 23, 30, 0).unwrap();
+		assert_eq!(date_in(now), NaiveDate::from_ymd_opt(2024, 5, 12).unwrap());
+		assert_eq!(
+			date_in(now.with_timezone(&chrono_tz::Europe::Zurich)),
+			NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()
+		);
+	}
+}
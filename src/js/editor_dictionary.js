@@ -0,0 +1,52 @@
+// Custom dictionary for the native spell checker of the browser.
+// Dictionary words are marked by a CodeMirror overlay and their spans
+// get `spellcheck="false"`, so the browser does not flag them.
+// Selecting a word and opening the context menu offers to add it.
+function redwoodEditorDictionary(easyMDE, words) {
+	var cm = easyMDE.codemirror;
+	var dictionary = new Set(words.map(function (word) { return word.toLowerCase(); }));
+	var wordRegex = /^[\p{L}\p{N}_'-]+/u;
+
+	var overlay = {
+		token: function (stream) {
+			if (stream.match(wordRegex)) {
+				return dictionary.has(stream.current().toLowerCase()) ? "dictionary-word" : null;
+			}
+			stream.next();
+			return null;
+		}
+	};
+	cm.addOverlay(overlay);
+
+	cm.on("renderLine", function (cm, line, element) {
+		element.querySelectorAll(".cm-dictionary-word").forEach(function (span) {
+			span.spellcheck = false;
+		});
+	});
+
+	cm.on("contextmenu", function (cm, event) {
+		var word = cm.getSelection().trim();
+		var match = word.match(wordRegex);
+		if (!match || match[0] !== word || dictionary.has(word.toLowerCase())) {
+			return;
+		}
+		if (!window.confirm("Add \"" + word + "\" to the dictionary?")) {
+			return;
+		}
+		event.preventDefault();
+		fetch(document.documentElement.dataset.basePath + "/api/dictionary", {
+			method: "POST",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ word: word })
+		}).then(function (response) {
+			if (response.ok) {
+				dictionary.add(word.toLowerCase());
+				// Re-tokenize so the new word is marked
+				cm.removeOverlay(overlay);
+				cm.addOverlay(overlay);
+			} else {
+				response.json().then(function (error) { window.alert(error.message); });
+			}
+		});
+	});
+}
@@ -0,0 +1,49 @@
+// Side-by-side preview rendered by the server, so that references,
+// syntax highlighting etc. look exactly like on the saved page.
+// Returns the `previewRender` function for EasyMDE. Requests are
+// debounced, on errors the last good render stays visible.
+function redwoodPreviewRender(articleId) {
+	var timer = null;
+	var latest = null;
+	var lastGood = null;
+
+	return function (plainText, preview) {
+		if (timer !== null) {
+			clearTimeout(timer);
+		}
+		preview.classList.add("preview_loading");
+		timer = setTimeout(function () {
+			timer = null;
+			var request = fetch(document.documentElement.dataset.basePath + "/api/preview", {
+				method: "POST",
+				headers: { "Content-Type": "application/json" },
+				body: JSON.stringify({ text: plainText, article: articleId })
+			}).then(function (response) {
+				if (!response.ok) {
+					throw new Error(response.status + " " + response.statusText);
+				}
+				return response.json();
+			}).then(function (result) {
+				if (request !== latest) {
+					return; // Superseded by a newer request
+				}
+				lastGood = result.html;
+				preview.innerHTML = result.html;
+				preview.classList.remove("preview_loading");
+			}).catch(function (error) {
+				if (request !== latest) {
+					return;
+				}
+				var message = document.createElement("div");
+				message.className = "render_error";
+				message.textContent = "Preview failed: " + error.message;
+				preview.innerHTML = lastGood !== null ? lastGood : "";
+				preview.appendChild(message);
+				preview.classList.remove("preview_loading");
+			});
+			latest = request;
+		}, 300);
+		// Keep the current content until the server answered
+		return lastGood === null ? "<p>Loading preview…</p>" : null;
+	};
+}
@@ -0,0 +1,22 @@
+// Jump to the section given in the URL fragment (`#section-...`), e.g.
+// from the edit links next to the headings of an article. The lines of
+// the section headings are provided by the server as JSON.
+function redwoodEditorSections(easyMDE, linesElementId) {
+	var lines = JSON.parse(document.getElementById(linesElementId).textContent);
+
+	function jump() {
+		var id = decodeURIComponent(window.location.hash.slice(1));
+		if (!Object.prototype.hasOwnProperty.call(lines, id)) {
+			return;
+		}
+		var codemirror = easyMDE.codemirror;
+		var position = { line: lines[id] - 1, ch: 0 };
+		codemirror.setCursor(position);
+		// Heading at the top of the editor
+		codemirror.scrollTo(null, codemirror.charCoords(position, "local").top);
+		codemirror.focus();
+	}
+
+	window.addEventListener("hashchange", jump);
+	jump();
+}
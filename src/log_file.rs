@@ -0,0 +1,199 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The log file of the process, chained into the logger once at startup
+pub static LOG_FILE: LogFile = LogFile::new();
+
+/// Log file that can be reopened under the same path while logging
+/// continues, so logrotate can move it away and have the wiki start a
+/// new one (`SIGUSR1` or `POST /admin/rotate-logs`). Writing through
+/// `&LogFile` takes the file behind a lock, reopening swaps it, so the
+/// logger never has to be rebuilt. Until a file is opened, everything
+/// written is discarded.
+pub struct LogFile {
+	file: Mutex<Option<OpenFile>>,
+}
+
+struct OpenFile {
+	path: PathBuf,
+	file: File,
+}
+
+impl LogFile {
+	pub const fn new() -> LogFile {
+		LogFile {
+			file: Mutex::new(None),
+		}
+	}
+
+	fn lock(&self) -> MutexGuard<'_, Option<OpenFile>> {
+		// A panic while writing leaves nothing half-done that matters
+		self.file.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Start writing to the file at `path`, appending if it exists
+	pub fn open(&self, path: &Path) -> io::Result<()> {
+		let file = open_append(path)?;
+		self.swap(path.to_path_buf(), file);
+		Ok(())
+	}
+
+	/// Open the file under its path again, creating it if it was moved
+	/// away. A marker line is written to both the old and the new file.
+	/// The old file stays in use if the new one cannot be opened.
+	///
+	/// Must not be called while logging, the logger writes to this file.
+	pub fn reopen(&self) -> io::Result<PathBuf> {
+		let path = match &*self.lock() {
+			Some(open) => open.path.clone(),
+			None => {
+				return Err(io::Error::new(
+					io::ErrorKind::NotFound,
+					"no log file is configured",
+				))
+			}
+		};
+		let file = open_append(&path)?;
+		self.swap(path.clone(), file);
+		Ok(path)
+	}
+
+	fn swap(&self, path: PathBuf, mut file: File) {
+		let mut current = self.lock();
+		if let Some(old) = &mut *current {
+			// Best effort, the old file may be on a full disk
+			let _ = writeln!(
+				old.file,
+				"{}",
+				marker("Log file closed, continued in a new file")
+			);
+			let _ = old.file.flush();
+			let _ = writeln!(file, "{}", marker("Log file reopened"));
+		}
+		*current = Some(OpenFile { path, file });
+	}
+}
+
+impl Default for LogFile {
+	fn default() -> Self {
+		LogFile::new()
+	}
+}
+
+impl Write for &LogFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match &mut *self.lock() {
+			Some(open) => open.file.write(buf),
+			None => Ok(buf.len()),
+		}
+	}
+
+	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+		// One lock for the whole line, so lines of threads never mix
+		match &mut *self.lock() {
+			Some(open) => open.file.write_all(buf),
+			None => Ok(()),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match &mut *self.lock() {
+			Some(open) => open.file.flush(),
+			None => Ok(()),
+		}
+	}
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+	OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Line in the format of the logger (see `main`)
+fn marker(message: &str) -> String {
+	format!(
+		"{}[{}][INFO] {}",
+		chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+		module_path!(),
+		message
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_path(name: &str) -> PathBuf {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-log-{}-{}.log",
+			name,
+			std::process::id()
+		));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn test_write_swap_write() {
+		let path = temp_path("rotate");
+		let rotated = temp_path("rotate-1");
+		let log = LogFile::new();
+		// Discarded, no file yet
+		writeln!(&log, "before").unwrap();
+		assert!(log.reopen().is_err());
+
+		log.open(&path).unwrap();
+		writeln!(&log, "first").unwrap();
+		// What logrotate does before signalling
+		std::fs::rename(&path, &rotated).unwrap();
+		writeln!(&log, "second").unwrap();
+		assert_eq!(log.reopen().unwrap(), path);
+		writeln!(&log, "third").unwrap();
+		(&log).flush().unwrap();
+
+		let old = std::fs::read_to_string(&rotated).unwrap();
+		let new = std::fs::read_to_string(&path).unwrap();
+		let old_lines: Vec<&str> = old.lines().collect();
+		let new_lines: Vec<&str> = new.lines().collect();
+		assert_eq!(old_lines.len(), 3);
+		assert_eq!(old_lines[..2], ["first", "second"]);
+		assert!(old_lines[2]
+			.ends_with("[redwood_wiki::log_file][INFO] Log file closed, continued in a new file"));
+		assert_eq!(new_lines.len(), 2);
+		assert!(new_lines[0].ends_with("[INFO] Log file reopened"));
+		assert_eq!(new_lines[1], "third");
+
+		std::fs::remove_file(&path).unwrap();
+		std::fs::remove_file(&rotated).unwrap();
+	}
+
+	#[test]
+	fn test_reopen_appends() {
+		let path = temp_path("append");
+		let log = LogFile::new();
+		log.open(&path).unwrap();
+		writeln!(&log, "first").unwrap();
+		// Not rotated: the same file is opened again
+		log.reopen().unwrap();
+		writeln!(&log, "second").unwrap();
+		let lines: Vec<String> = std::fs::read_to_string(&path)
+			.unwrap()
+			.lines()
+			.map(str::to_string)
+			.collect();
+		assert_eq!(lines.len(), 4);
+		assert_eq!(lines[0], "first");
+		assert_eq!(lines[3], "second");
+
+		// A file that cannot be created leaves the old one in use
+		let missing_dir = temp_path("missing-dir");
+		log.open(&missing_dir.join("wiki.log")).unwrap_err();
+		writeln!(&log, "third").unwrap();
+		assert!(std::fs::read_to_string(&path)
+			.unwrap()
+			.ends_with("second\nthird\n"));
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -1,45 +1,133 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
-
-use chrono;
-use chrono::Utc;
+// The nested filter types of `routes` exceed the default limit
+#![recursion_limit = "256"]
 
-use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
 use warp::{Filter, Reply};
 
 mod database;
-use database::{Article, Database, DatabaseConnection, ItemId};
+mod db_lock;
+
+mod backup;
+use database::{
+	Article, ArticleOrder, ArticleSummary, CreateArticleError, Database, DatabaseConnection, ItemId,
+};
+use db_lock::DbLock;
 
 mod config;
-use config::parse_config;
+use config::Config;
+
+mod config_refs;
+
+mod config_template;
 
 mod markdown_utils;
-use markdown_utils::{LinkHighlightStream, TextMergeStream, UnknownRefHandlingStream};
+
+mod markdown_import;
 
 mod codeblock_syntax_highlight;
-use codeblock_syntax_highlight::SyntaxHighlightStream;
 
 mod regex_utils;
-use regex::RegexBuilder;
-use regex_utils::{DoPartition, Part};
+
+mod sql_util;
+
+mod search;
+use search::{MatchCategory, SearchView};
+
+mod urls;
+
+mod slug;
+
+mod infobox;
+
+mod sections;
+
+mod outbound;
+
+mod favicon;
+
+mod hooks;
+
+mod journal;
+
+mod render;
+use render::{Pipeline, RenderOptions};
+
+mod dictionary;
+use dictionary::Dictionary;
+
+mod version;
+use version::VersionInfo;
+
+mod assets;
+
+mod starter;
+
+mod visits;
+
+mod startup;
+
+mod log_file;
+
+mod tags;
+
+mod feed;
+
+mod cli;
+
+mod sitemap;
+
+mod timestamp;
+use timestamp::UtcTimestamp;
+
+mod api;
+
+mod hmac;
+
+mod similar_titles;
+
+mod compression;
+
+mod custom_css;
+
+mod tls;
+
+mod unix_socket;
+
+mod auth;
 
 struct HtmlDocument {
 	title: String,
 	style: String,
 	styles: Vec<&'static str>,
 	scripts: Vec<&'static str>,
+	/// Additional elements in `<head>`, e.g. metadata for link previews
+	head_extra: String,
+	/// URL of the custom stylesheet, after the built-in styles
+	custom_css: Option<String>,
+	/// `network.base_path`, the scripts read it from the `<html>` element
+	base_path: String,
 	body: String,
 }
 impl HtmlDocument {
-	fn new() -> HtmlDocument {
+	/// Document titled with the name of the wiki
+	fn new(config: &Config) -> HtmlDocument {
 		HtmlDocument {
-			title: "Redwood-wiki".to_string(),
+			title: config.wiki.name.clone(),
 			style: String::new(),
 			styles: vec![],
 			scripts: vec![],
+			head_extra: String::new(),
+			custom_css: config
+				.appearance
+				.custom_css()
+				.map(|stylesheet| config.url(&stylesheet.url())),
+			base_path: config.network.base_path.as_str().to_string(),
 			body: String::new(),
 		}
 	}
@@ -48,33 +136,43 @@ impl HtmlDocument {
 		format!(
 			r####"
 <!DOCTYPE html>
-<html>
+<html lang="en" data-base-path="{base}">
 	<head>
 		<meta charset=utf-8>
 		<meta name=viewport content="width=device-width, initial-scale=1.0">
 		<meta name="description" content="">
 		<title>{}</title>
-		<link rel="icon" href="/favicon.ico" sizes="any"><!-- 32×32 -->
-		<link rel="icon" href="/icon.svg" type="image/svg+xml">
+		<link rel="icon" href="{base}/favicon.ico" sizes="any"><!-- 32×32 -->
+		<link rel="icon" href="{base}/icon.svg" type="image/svg+xml">
+		<link rel="alternate" type="application/atom+xml" title="Recently changed articles" href="{base}/feed.xml">{}
 		<style>
 {}
 
 {}
-		</style>
+		</style>{}
 		<script>
 {}
 		</script>
 	</head>
 	<body>
+		<a href="#main_content" class="skip_link">Skip to content</a>
 		{}
 	</body>
 </html>
 "####,
-			self.title,
+			html_escape(&self.title),
+			self.head_extra,
 			self.style,
 			self.styles.join("\n\n"),
+			self.custom_css
+				.as_ref()
+				.map_or_else(String::new, |url| format!(
+					"\n\t\t<link rel=\"stylesheet\" href=\"{}\">",
+					html_escape(url)
+				)),
 			self.scripts.join("\n\n"),
 			self.body,
+			base = html_escape(&self.base_path),
 		)
 	}
 }
@@ -87,6 +185,9 @@ impl HtmlDocument {
 // The last part of the URL (`Title-of-fifth-article`) is purely cosmetic.
 // The number (`5`) is the unique ID that is relevant for the database lookup.
 // The article URL name is always encoded as `[id]/[title]` where the latter part is cosmetic.
+// The cosmetic part is the slug stored with the article (see `slug::slugify`). It is generated
+// when the article is created and only regenerated on request, so renaming an article does not
+// break external links. Requests with an outdated slug are redirected to the stored one.
 // So it could also be accessed with
 // `https://www.example.com/article/5`
 // or
@@ -94,14 +195,79 @@ impl HtmlDocument {
 // (these "wrong" titles might later trigger a redirect to the URL with the proper titles)
 // Editing articles would be:
 // `https://www.example.com/edit/article/1/Title-of-first-article`
+// Editing a single section jumps to its heading in the editor:
+// `https://www.example.com/edit/article/1#section-habitat`
+// The anchor is `section-` followed by the slug of the heading text, with `-2`, `-3`, ...
+// appended to repeated headings (see `sections::heading_anchors`). The article page uses
+// the same anchors as IDs of its headings, so `/article/1#section-habitat` links to the section.
+// Deleting articles (after a confirmation page) would be:
+// `https://www.example.com/delete/article/1`
+// Articles can also be looked up by title:
+// `https://www.example.com/wiki/Title of first article`
+// This redirects to the article with that title, or to the article that had it before being
+// renamed, in which case the article page notes the old title.
+// Articles with a tag are listed at
+// `https://www.example.com/tag/conifer`
+// All tags are listed at `https://www.example.com/admin/tags`, with forms that POST to
+// `/admin/tags/rename` and `/admin/tags/merge`.
+// Categories form a tree, the top-level categories are listed at
+// `https://www.example.com/categories`
+// and a category with its sub-categories and articles at
+// `https://www.example.com/category/1`
+// New categories are a POST to `/create/category`, moving one is a POST to `/move/category/1`.
+// Deleted articles are kept in the trash at `https://www.example.com/trash`,
+// restoring them is a POST to `https://www.example.com/restore/article/1`
+// The same POST with a `revision` form field saves the text of that revision as a new one.
+// The stored Markdown of an article, as a text file, is at
+// `https://www.example.com/raw/article/1`
+// The saved edits of an article, with their summaries, are listed at
+// `https://www.example.com/history/article/1`
+// The most recently changed articles are an Atom feed at `https://www.example.com/feed.xml`
+// With `network.public_url` configured, all articles are listed in `https://www.example.com/sitemap.xml`,
+// which becomes an index of `/sitemap/1.xml`, `/sitemap/2.xml`, ... for very large wikis.
+// Scripts can read articles as JSON from `https://www.example.com/api/article/1`,
+// create them with a POST to `/api/article` and update them with a PUT to `/api/article/1`,
+// see the `api` module.
+// With a log file configured, a POST to `/admin/rotate-logs` (or SIGUSR1) reopens it.
+// The whole wiki can be downloaded as JSON document from `https://www.example.com/admin/export.json`,
+// `redwood-wiki export` writes the same document without the server.
 // Previewing a pending edit would be
 // `https://www.example.com/preview/article/1/Title-of-first-article`
 // The idea is that the URL is always composed of
 // `/[verb]/[item-type]/[item-id]`, except for plain showing articles, which can simply omit the verb.
 // So, `/edit/article/1/Title-of-first-article` but `/article/1/Title-of-first-article` for showing.
+// Paths never end with a slash (except the root `/`) and never contain empty segments.
+// Requests like `/article/1/` or `//article//1` are redirected to `/article/1`,
+// see `urls::normalize_path`.
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let invocation = match cli::parse_args(&args) {
+		Ok(invocation) => invocation,
+		Err(message) => {
+			eprintln!("{}\n\n{}", message, cli::USAGE);
+			std::process::exit(2);
+		}
+	};
+	let resume_migration = match invocation.action {
+		cli::Action::Serve { resume_migration } => resume_migration,
+		cli::Action::Help => {
+			println!("{}", cli::USAGE);
+			return;
+		}
+		cli::Action::Version => {
+			println!("{}", VersionInfo::get());
+			return;
+		}
+		cli::Action::InitConfig { file, force } => {
+			std::process::exit(cli::init_config(&file, force))
+		}
+		cli::Action::Run(command) => {
+			std::process::exit(cli::run(command, invocation.db, &invocation.overrides))
+		}
+	};
+
 	fern::Dispatch::new()
 		// Perform allocation-free log formatting
 		.format(|out, message, record| {
@@ -116,741 +282,8994 @@ async fn main() {
 		.level(log::LevelFilter::Warn)
 		.level_for("redwood_wiki", log::LevelFilter::Trace)
 		.chain(std::io::stdout())
-		//.chain(fern::log_file("output.log").unwrap())
+		// Opened once the configuration is read, see `startup::init_optional`
+		.chain(Box::new(&log_file::LOG_FILE) as Box<dyn std::io::Write + Send>)
 		// Apply globally
 		.apply()
 		.unwrap();
 
-	log::info!("Starting Redwood-Wiki!");
+	log::info!("Starting {}", VersionInfo::get());
 
-	let config = parse_config().unwrap();
+	let mut config = match invocation.overrides.load() {
+		Ok(config) => config,
+		Err(err) => {
+			log::error!("Could not read the configuration: {}", err);
+			std::process::exit(1);
+		}
+	};
+	let listener = match config.network.listener() {
+		Ok(listener) => listener,
+		Err(err) => {
+			log::error!("Could not read the configuration: {}", err);
+			std::process::exit(1);
+		}
+	};
+	let tls = match config.network.tls_files() {
+		Ok(Some(_)) if matches!(listener, config::Listener::Unix { .. }) => {
+			log::error!(
+				"Could not read the configuration: HTTPS is not supported on `network.unix_socket`, the reverse proxy serves it"
+			);
+			std::process::exit(1);
+		}
+		Ok(Some((cert, key))) => match tls::Identity::load(cert, key) {
+			Ok(identity) => Some(identity),
+			Err(err) => {
+				log::error!("Could not set up HTTPS: {}", err);
+				std::process::exit(1);
+			}
+		},
+		Ok(None) => None,
+		Err(err) => {
+			log::error!("Could not read the configuration: {}", err);
+			std::process::exit(1);
+		}
+	};
 
-	let db = DatabaseConnection::new(
-		&config.database.storage_location.join("wiki_db.sqlite"),
+	if let Err(err) = config.database.create_storage_location() {
+		log::error!(
+			"Could not create the database directory {}: {}",
+			config.database.storage_location.display(),
+			err
+		);
+		std::process::exit(1);
+	}
+	let db_path = config.database.path();
+	let db_connection = match DatabaseConnection::with_settings(
+		&db_path,
 		database::OpenMode::OpenOrCreate,
-	)
-	.unwrap()
-	.init()
-	.unwrap();
+		&config.database.connection.settings(),
+	) {
+		Ok(db_connection) => db_connection,
+		Err(err) => {
+			log::error!(
+				"Could not open the database at {}: {}",
+				db_path.display(),
+				err
+			);
+			std::process::exit(1);
+		}
+	};
+
+	if resume_migration {
+		// Administrative path: Deal with an interrupted migration and exit
+		match db_connection.resume_migration() {
+			Ok(_) => {
+				log::info!("Database migration state is consistent, no action needed anymore.");
+				return;
+			}
+			Err(err @ database::DatabaseInitError::MigrationUnrecoverable { .. }) => {
+				log::error!(
+					"In the database at {}, {}. Please restore the database from a backup.",
+					db_path.display(),
+					err
+				);
+				std::process::exit(1);
+			}
+			Err(err) => {
+				log::error!(
+					"Could not resume the migration of the database at {}: {}",
+					db_path.display(),
+					err
+				);
+				std::process::exit(1);
+			}
+		}
+	}
+
+	let db = match db_connection.init() {
+		Ok(db) => db,
+		Err(err @ database::DatabaseInitError::MigrationInProgress { .. }) => {
+			log::error!(
+				"In the database at {}, {}. If no other process is migrating the database, run with `--resume-migration`, it completes the migration or tells whether to restore the database from a backup.",
+				db_path.display(),
+				err
+			);
+			std::process::exit(1);
+		}
+		Err(err) => {
+			log::error!(
+				"Could not use the database at {}: {}.",
+				db_path.display(),
+				err
+			);
+			std::process::exit(1);
+		}
+	};
+
+	let dictionary = startup::init_optional(&mut config);
+	let mut db = db;
+	db.set_date_window(config.database.dates.window());
+	startup::check_article_refs(&mut config, &mut db);
+
+	#[cfg(unix)]
+	if config.log.file.is_some() {
+		tokio::spawn(rotate_logs_on_signal());
+	}
+
+	let db = Arc::new(DbLock::new(db, config.database.lock.slow_hold()));
+	if let Some(backup) = config.backup.clone() {
+		tokio::spawn(backup::run_scheduled(db.clone(), backup));
+	}
+	let config = Arc::new(config);
+	#[cfg(unix)]
+	if config.appearance.custom_css.is_some() {
+		tokio::spawn(reload_css_on_signal(config.clone()));
+	}
+	let dictionary = Arc::new(Mutex::new(dictionary));
+	let base_path = config.network.base_path.as_str().to_string();
+	match (listener, tls) {
+		(config::Listener::Tcp(addresses), tls) => {
+			let scheme = if tls.is_some() { "https" } else { "http" };
+			let servers = match bind_servers(db, config, dictionary, &addresses, tls.as_ref()) {
+				Ok(servers) => servers,
+				Err((address, err)) => {
+					log::error!("Could not listen on {}: {}", address, err);
+					std::process::exit(1);
+				}
+			};
+			// The bound address, with the port picked by the system for port 0
+			for (address, _) in &servers {
+				log::info!("Listening on {}://{}{}/", scheme, address, base_path);
+			}
+			let servers: Vec<_> = servers
+				.into_iter()
+				.map(|(_, server)| tokio::spawn(server))
+				.collect();
+			for server in servers {
+				if let Err(err) = server.await {
+					log::error!("Server failed: {}", err);
+				}
+			}
+		}
+		#[cfg(unix)]
+		(config::Listener::Unix { path, mode }, _) => {
+			let (incoming, _socket_file) = match unix_socket::bind(&path, mode) {
+				Ok(bound) => bound,
+				Err(err) => {
+					log::error!("Could not listen on {}: {}", path.display(), err);
+					std::process::exit(1);
+				}
+			};
+			log::info!("Listening on {}, path {}/", path.display(), base_path);
+			warp::serve(routes(db, config, dictionary))
+				.serve_incoming_with_graceful_shutdown(incoming, unix_socket::shutdown_signal())
+				.await
+		}
+		#[cfg(not(unix))]
+		(config::Listener::Unix { .. }, _) => unreachable!("rejected by `Network::listener`"),
+	}
+}
+
+/// A server bound to one of the addresses of `network.listen`
+type ServerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Bind a server to each address and return the bound addresses, port 0
+/// is replaced by the port the system picked. Every address is bound
+/// before any is served, so that the wiki does not start if it cannot
+/// listen on all of them. The error names the address that failed.
+fn bind_servers(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	dictionary: Arc<Mutex<Dictionary>>,
+	addresses: &[std::net::SocketAddr],
+	tls: Option<&tls::Identity>,
+) -> Result<Vec<(std::net::SocketAddr, ServerFuture)>, (std::net::SocketAddr, warp::Error)> {
+	let routes = routes(db, config, dictionary);
+	let mut servers = Vec::new();
+	for &address in addresses {
+		let server = warp::serve(routes.clone());
+		let bound = match tls {
+			Some(identity) => server
+				.tls()
+				.cert(&identity.cert)
+				.key(&identity.key)
+				.try_bind_with_graceful_shutdown(address, std::future::pending())
+				.map(|(address, server)| (address, Box::pin(server) as ServerFuture)),
+			None => server
+				.try_bind_with_graceful_shutdown(address, std::future::pending())
+				.map(|(address, server)| (address, Box::pin(server) as ServerFuture)),
+		};
+		servers.push(bound.map_err(|err| (address, err))?);
+	}
+	Ok(servers)
+}
+
+/// Reopen the log file on `SIGUSR1`, which logrotate can send after
+/// moving the file away
+#[cfg(unix)]
+async fn rotate_logs_on_signal() {
+	use tokio::signal::unix::{signal, SignalKind};
+	let mut signals = match signal(SignalKind::user_defined1()) {
+		Ok(signals) => signals,
+		Err(err) => {
+			log::error!(
+				"Cannot handle SIGUSR1, the log file cannot be rotated: {}",
+				err
+			);
+			return;
+		}
+	};
+	while signals.recv().await.is_some() {
+		let _ = rotate_logs();
+	}
+}
+
+/// Read the custom stylesheet again on `SIGHUP`, for changes that keep
+/// the modification time of the file
+#[cfg(unix)]
+async fn reload_css_on_signal(config: Arc<Config>) {
+	use tokio::signal::unix::{signal, SignalKind};
+	let mut signals = match signal(SignalKind::hangup()) {
+		Ok(signals) => signals,
+		Err(err) => {
+			log::error!(
+				"Cannot handle SIGHUP, the custom stylesheet is only read again when it changes: {}",
+				err
+			);
+			return;
+		}
+	};
+	let Some(path) = &config.appearance.custom_css else {
+		return;
+	};
+	while signals.recv().await.is_some() {
+		match config.appearance.stylesheet.reload(path) {
+			Ok(()) => log::info!("Reloaded the custom stylesheet {}", path.display()),
+			Err(err) => log::warn!(
+				"Could not read the custom stylesheet {}, the built-in styles are used: {}",
+				path.display(),
+				err
+			),
+		}
+	}
+}
+
+/// Reopen the log file under its configured path
+fn rotate_logs() -> std::io::Result<std::path::PathBuf> {
+	let result = log_file::LOG_FILE.reopen();
+	match &result {
+		Ok(path) => log::info!("Reopened log file {}", path.display()),
+		Err(err) => log::error!("Could not reopen the log file: {}", err),
+	}
+	result
+}
 
-	let db = Arc::new(Mutex::new(db));
+fn routes(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	dictionary: Arc<Mutex<Dictionary>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = std::convert::Infallible> + Clone {
+	let server_header = config.network.server_header;
+	let base_path = config.network.base_path.filter();
+	let rejection_config = config.clone();
+	let article_body_limit = config.limits.article_body_bytes;
+	let search_body_limit = config.limits.search_body_bytes;
+	let assets_cache = config.assets;
+	let custom_css_route = custom_css::route(config.clone());
+	let api_routes = api::routes(db.clone(), config.clone());
+	// Editing, deleting and administration need the credentials of the
+	// `[auth]` section, if there is one
+	let editor = auth::editor(config.clone());
+	let reader = auth::reader(config.clone());
+	let user = auth::user(config.clone());
+	let rejection_user = user.clone();
 	let db = warp::any().map(move || db.clone());
+	let config = warp::any().map(move || config.clone());
+	let dictionary = warp::any().map(move || dictionary.clone());
+	let confirm_key = Arc::new(similar_titles::ConfirmKey::random());
+	let favicon_limiter = Arc::new(favicon::RateLimiter::new(
+		30,
+		std::time::Duration::from_secs(60),
+	));
+	let favicon_limiter = warp::any().map(move || favicon_limiter.clone());
 
-	let index_path = warp::path::end().and(db.clone()).and_then(index_page);
-	let favicon_ico_path_get = warp::get()
-		.and(warp::path("favicon.ico"))
-		.and(warp::path::end())
-		.and_then(favicon_ico_page);
-	let favicon_svg_path_get = warp::get()
-		.and(warp::path("icon.svg"))
-		.and(warp::path::end())
-		.and_then(favicon_svg_page);
-	let wiki_icon_page_get = warp::get()
-		.and(warp::path("img"))
-		.and(warp::path("redwood_observatory_medium.png"))
+	let last_visit = warp::cookie::optional::<String>(visits::COOKIE_NAME);
+	let index_path = warp::path::end()
+		.and(db.clone())
+		.and(config.clone())
+		.and(last_visit)
+		.and(user.clone())
+		.and_then(index_page);
+	let setup_path_post = warp::post()
+		.and(warp::path("setup"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
 		.and(warp::path::end())
-		.and_then(wiki_icon_page);
+		.and(warp::body::content_length_limit(1024))
+		.and(warp::body::form())
+		.and_then(setup_post);
 	let article_path_post = warp::post()
 		.and(warp::path("article"))
+		.and(editor.clone())
 		.and(db.clone())
-		.and(warp::path::param::<ItemId>())
+		.and(config.clone())
+		.and(urls::item_id())
 		.and(warp::path::end())
-		.and(warp::body::form()) //This does not have a default size limit, it would be wise to use one to prevent a overly large request from using too much memory.
-		//.and(warp::body::content_length_limit(1024 * 32))
+		.and(form_body(article_body_limit))
+		.and(user.clone())
 		.and_then(article_page_post);
 	let article_path_get = warp::get()
 		.and(warp::path("article"))
 		.and(db.clone())
-		.and(warp::path::param::<ItemId>())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(urls::optional_slug())
+		.and(warp::path::end())
+		.and(warp::query::<HashMap<String, String>>())
+		.and(warp::header::optional::<String>("if-none-match"))
+		.and(user.clone())
+		.and_then(article_page_get);
+	let wiki_path = warp::get()
+		.and(warp::path("wiki"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::tail())
+		.and(user.clone())
+		.and_then(wiki_page);
+	let tag_path = warp::get()
+		.and(warp::path("tag"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(tag_page);
+	let admin_tags_path = warp::get()
+		.and(warp::path("admin"))
+		.and(warp::path("tags"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(user.clone())
+		.and_then(admin_tags_page);
+	let admin_tags_rename_path = warp::post()
+		.and(warp::path("admin"))
+		.and(warp::path("tags"))
+		.and(warp::path("rename"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::body::content_length_limit(4 * 1024))
+		.and(warp::body::form())
+		.and(user.clone())
+		.and_then(admin_tags_rename_post);
+	let admin_tags_merge_path = warp::post()
+		.and(warp::path("admin"))
+		.and(warp::path("tags"))
+		.and(warp::path("merge"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::body::content_length_limit(4 * 1024))
+		.and(warp::body::form())
+		.and(user.clone())
+		.and_then(admin_tags_merge_post);
+	let rotate_logs_path = warp::post()
+		.and(warp::path("admin"))
+		.and(warp::path("rotate-logs"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(config.clone())
+		.and_then(rotate_logs_post);
+	let export_path = warp::get()
+		.and(warp::path("admin"))
+		.and(warp::path("export.json"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and_then(export_get);
+	let categories_path = warp::get()
+		.and(warp::path("categories"))
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(user.clone())
+		.and_then(categories_page);
+	let category_path = warp::get()
+		.and(warp::path("category"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(category_page);
+	let category_create_path = warp::post()
+		.and(warp::path("create"))
+		.and(warp::path("category"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::body::content_length_limit(4 * 1024))
+		.and(warp::body::form())
+		.and(user.clone())
+		.and_then(category_create_post);
+	let category_move_path = warp::post()
+		.and(warp::path("move"))
+		.and(warp::path("category"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
 		.and(warp::path::end())
-		.and_then(article_page);
+		.and(warp::body::content_length_limit(1024))
+		.and(warp::body::form())
+		.and(user.clone())
+		.and_then(category_move_post);
 	let search_path_post = warp::post()
 		.and(warp::path("search"))
 		.and(warp::path("article"))
 		.and(db.clone())
+		.and(config.clone())
 		.and(warp::path::end())
-		.and(warp::body::form()) //This does not have a default size limit, it would be wise to use one to prevent a overly large request from using too much memory.
-		//.and(warp::body::content_length_limit(1024 * 32))
+		.and(form_body(search_body_limit))
+		.and(user.clone())
 		.and_then(search_page_post);
 	let search_path_get = warp::get()
 		.and(warp::path("search"))
 		.and(warp::path("article"))
 		.and(db.clone())
+		.and(config.clone())
 		.and(warp::path::end())
+		.and(warp::query::<HashMap<String, String>>())
+		.and(user.clone())
 		.and_then(search_page_get);
 	let article_edit_path = warp::path("edit")
 		.and(warp::path("article"))
+		.and(editor.clone())
 		.and(db.clone())
-		.and(warp::path::param::<ItemId>())
+		.and(config.clone())
+		.and(dictionary.clone())
+		.and(urls::item_id())
+		.and(urls::optional_slug())
 		.and(warp::path::end())
+		.and(user.clone())
 		.and_then(article_edit_page);
+	let article_delete_get_path = warp::get()
+		.and(warp::path("delete"))
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(article_delete_page);
+	let article_delete_post_path = warp::post()
+		.and(warp::path("delete"))
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(article_delete_page_post);
+	let trash_path = warp::get()
+		.and(warp::path("trash"))
+		.and(editor.clone())
+		.and(warp::path::end())
+		.and(db.clone())
+		.and(config.clone())
+		.and(user.clone())
+		.and_then(trash_page);
+	// Without the `revision` field, the request restores the article
+	// from the trash
+	let revision_restore_path = warp::post()
+		.and(warp::path("restore"))
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(warp::body::content_length_limit(1024))
+		.and(warp::body::form())
+		.and(user.clone())
+		.and_then(revision_restore_post);
+	let article_restore_path = warp::post()
+		.and(warp::path("restore"))
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(article_restore_page_post);
+	let backlinks_path = warp::get()
+		.and(warp::path("links"))
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(backlinks_page);
+	let article_preview_path = warp::get()
+		.and(warp::path("preview"))
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(urls::optional_slug())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(article_preview_page);
+	let article_preview_post_path = warp::post()
+		.and(warp::path("preview"))
+		.and(warp::path("article"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(form_body(article_body_limit))
+		.and_then(article_preview_post);
+	let raw_path = warp::get()
+		.and(warp::path("raw"))
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(warp::header::optional::<String>("if-none-match"))
+		.and_then(raw_article_page);
+	let history_path = warp::get()
+		.and(warp::path("history"))
+		.and(warp::path("article"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(urls::item_id())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(history_page);
 	let article_create_get_path = warp::get()
 		.and(warp::path("create"))
 		.and(warp::path("article"))
+		.and(editor.clone())
 		.and(db.clone())
+		.and(config.clone())
 		.and(warp::path::end())
+		.and(user.clone())
 		.and_then(article_create_page);
 	let article_create_post_path = warp::post()
 		.and(warp::path("create"))
 		.and(warp::path("article"))
+		.and(editor.clone())
 		.and(db.clone())
+		.and(config.clone())
+		.and(warp::any().map(move || confirm_key.clone()))
 		.and(warp::path::end())
-		.and(warp::body::form()) //This does not have a default size limit, it would be wise to use one to prevent a overly large request from using too much memory.
-		//.and(warp::body::content_length_limit(1024 * 32))
+		.and(form_body(article_body_limit))
+		.and(user.clone())
 		.and_then(article_create_page_post);
 	let articles_path = warp::get()
 		.and(warp::path("articles"))
 		.and(db.clone())
+		.and(config.clone())
 		.and(warp::path::end())
+		.and(last_visit)
+		.and(warp::query::<HashMap<String, String>>())
+		.and(user.clone())
 		.and_then(articles_page);
-	let routes = index_path
-		.or(favicon_ico_path_get)
-		.or(favicon_svg_path_get)
-		.or(wiki_icon_page_get)
+	let dictionary_get_path = warp::get()
+		.and(warp::path("api"))
+		.and(warp::path("dictionary"))
+		.and(dictionary.clone())
+		.and(warp::path::end())
+		.and_then(dictionary_get);
+	let dictionary_post_path = warp::post()
+		.and(warp::path("api"))
+		.and(warp::path("dictionary"))
+		.and(editor.clone())
+		.and(dictionary.clone())
+		.and(warp::path::end())
+		.and(warp::body::content_length_limit(1024))
+		.and(warp::body::json())
+		.and_then(dictionary_post);
+	let favicon_api_path = warp::get()
+		.and(warp::path("api"))
+		.and(warp::path("favicon"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(favicon_limiter)
+		.and(warp::path::end())
+		.and(warp::query::<HashMap<String, String>>())
+		.and_then(favicon_api);
+	let today_path = warp::get()
+		.and(warp::path("today"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::end())
+		.and_then(today_page);
+	let preview_path = warp::post()
+		.and(warp::path("api"))
+		.and(warp::path("preview"))
+		.and(editor.clone())
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::end())
+		.and(warp::body::content_length_limit(article_body_limit))
+		.and(warp::body::json())
+		.and_then(preview_post);
+	let about_path = warp::get()
+		.and(warp::path("about"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and_then(about_page);
+	let feed_path = warp::get()
+		.and(warp::path("feed.xml"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::end())
+		.and_then(feed_page);
+	let sitemap_path = warp::get()
+		.and(warp::path("sitemap.xml"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::end())
+		.and_then(sitemap_page);
+	let login_get_path = warp::get()
+		.and(warp::path("login"))
+		.and(config.clone())
+		.and(warp::path::end())
+		.and(user.clone())
+		.and(warp::query::<HashMap<String, String>>())
+		.and_then(login_page);
+	let login_post_path = warp::post()
+		.and(warp::path("login"))
+		.and(config.clone())
+		.and(warp::path::end())
+		.and(warp::body::content_length_limit(4096))
+		.and(warp::body::form())
+		.and_then(login_post);
+	let logout_path = warp::post()
+		.and(warp::path("logout"))
+		.and(config.clone())
+		.and(warp::path::end())
+		.and_then(logout_post);
+	let sitemap_part_path = warp::get()
+		.and(warp::path("sitemap"))
+		.and(db.clone())
+		.and(config.clone())
+		.and(warp::path::param::<String>())
+		.and(warp::path::end())
+		.and_then(sitemap_part_page);
+
+	let mut headers = warp::http::HeaderMap::new();
+	if server_header {
+		if let Ok(value) = warp::http::HeaderValue::from_str(&VersionInfo::get().short()) {
+			headers.insert(warp::http::header::SERVER, value);
+		}
+	}
+
+	let routes = urls::normalize_path_filter()
+		.or(index_path)
+		.or(setup_path_post)
+		.or(assets::route(assets_cache))
+		.or(custom_css_route)
 		.or(article_edit_path)
 		.or(article_path_get)
+		.or(wiki_path)
+		.or(tag_path)
+		.or(admin_tags_path)
+		.or(admin_tags_rename_path)
+		.or(admin_tags_merge_path)
+		.or(rotate_logs_path)
+		.or(export_path)
+		.or(categories_path)
+		.or(category_path)
+		.or(category_create_path)
+		.or(category_move_path)
 		.or(article_path_post)
 		.or(search_path_get)
 		.or(search_path_post)
+		.or(article_delete_get_path)
+		.or(article_delete_post_path)
+		.or(trash_path)
+		.or(backlinks_path)
+		.or(history_path)
+		.or(raw_path)
+		.or(article_preview_path)
+		.or(article_preview_post_path)
+		.or(revision_restore_path)
+		.or(article_restore_path)
 		.or(article_create_get_path)
 		.or(article_create_post_path)
-		.or(articles_path);
-	warp::serve(routes)
-		.run((config.network.ip, config.network.port))
-		.await;
+		.or(articles_path)
+		.or(dictionary_get_path)
+		.or(dictionary_post_path)
+		.or(favicon_api_path)
+		.or(preview_path)
+		.or(about_path)
+		.or(today_path)
+		.or(feed_path)
+		.or(api_routes)
+		.or(sitemap_path)
+		.or(sitemap_part_path);
+	// Requests outside of the base path are not found. Logging in must
+	// work when reading needs it too.
+	let routes = base_path
+		.and(
+			login_get_path
+				.or(login_post_path)
+				.or(logout_path)
+				.map(warp::Reply::into_response)
+				.or(reader.and(routes).map(warp::Reply::into_response))
+				.unify(),
+		)
+		.map(Ok)
+		// The rejection handler needs the path to answer API requests with
+		// JSON, `recover` does not provide it
+		.or_else(|rejection| async move { Ok::<_, std::convert::Infallible>((Err(rejection),)) });
+	warp::path::full()
+		.and(warp::header::headers_cloned())
+		.and(rejection_user)
+		.and(routes)
+		.then(
+			move |path: warp::path::FullPath,
+			      headers: warp::http::HeaderMap,
+			      user: Option<auth::User>,
+			      result| {
+				let config = rejection_config.clone();
+				async move {
+					let mut response = match result {
+						Ok(response) => response,
+						Err(rejection) => {
+							handle_rejection(&config, user.as_ref(), path.as_str(), rejection)
+						}
+					};
+					auth::mark_private(&config, &mut response);
+					let accept_encoding = headers
+						.get(warp::http::header::ACCEPT_ENCODING)
+						.and_then(|value| value.to_str().ok());
+					compression::compress(response, accept_encoding, &config.compression).await
+				}
+			},
+		)
+		.with(warp::reply::with::headers(headers))
+}
+
+/// Rejection of a form larger than the configured limit
+#[derive(Debug)]
+struct BodyTooLarge {
+	limit: u64,
+}
+
+impl warp::reject::Reject for BodyTooLarge {}
+
+/// A failed database query ends the request with an error page, see
+/// `handle_rejection`
+impl warp::reject::Reject for database::DbError {}
+
+/// The form in the body, if it is at most `limit` bytes large
+fn form_body<T: serde::de::DeserializeOwned + Send>(
+	limit: u64,
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+	warp::body::content_length_limit(limit)
+		.or_else(move |rejection: warp::Rejection| async move {
+			if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+				Err(warp::reject::custom(BodyTooLarge { limit }))
+			} else {
+				Err(rejection)
+			}
+		})
+		.and(warp::body::form())
+}
+
+/// Answers requests that no route accepted with an error page, or with
+/// a JSON error for the API. Rejections that are not known to be the
+/// fault of the request are logged and answered with a 500 that does
+/// not tell what went wrong.
+fn handle_rejection(
+	config: &Config,
+	user: Option<&auth::User>,
+	path: &str,
+	rejection: warp::Rejection,
+) -> warp::reply::Response {
+	use warp::http::StatusCode;
+	use warp::reject;
+
+	// Errors of the handlers of the API, already with status and body
+	if let Some(error) = rejection.find::<api::ApiError>() {
+		return error.to_response();
+	}
+
+	let invalid_id = rejection.find::<urls::InvalidItemId>();
+	let too_large = rejection.find::<BodyTooLarge>();
+	let db_error = rejection.find::<database::DbError>();
+	let (status, message) = if invalid_id.is_some() {
+		(StatusCode::BAD_REQUEST, "invalid article id")
+	} else if rejection.find::<auth::Unauthorized>().is_some() {
+		(StatusCode::UNAUTHORIZED, "authentication required")
+	} else if let Some(database::DbError::Busy) = db_error {
+		(StatusCode::SERVICE_UNAVAILABLE, "the database is busy")
+	} else if let Some(err) = db_error {
+		log::error!("Database error in {}: {}", path, err);
+		(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+	} else if too_large.is_some() || rejection.find::<reject::PayloadTooLarge>().is_some() {
+		(StatusCode::PAYLOAD_TOO_LARGE, "body too large")
+	} else if rejection.find::<reject::LengthRequired>().is_some() {
+		(StatusCode::LENGTH_REQUIRED, "content length required")
+	} else if rejection.find::<reject::UnsupportedMediaType>().is_some() {
+		(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported media type")
+	} else if rejection.find::<reject::InvalidQuery>().is_some()
+		|| rejection.find::<reject::InvalidHeader>().is_some()
+		|| rejection.find::<reject::MissingHeader>().is_some()
+		|| rejection
+			.find::<warp::body::BodyDeserializeError>()
+			.is_some()
+	{
+		(StatusCode::BAD_REQUEST, "bad request")
+	} else if rejection.is_not_found() || rejection.find::<reject::MethodNotAllowed>().is_some() {
+		// Routes filter by method before the path, so warp reports an
+		// unknown path as a wrong method
+		(StatusCode::NOT_FOUND, "not found")
+	} else {
+		log::error!("Unhandled rejection of {}: {:?}", path, rejection);
+		(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+	};
+
+	let path = config.network.base_path.strip(path).unwrap_or(path);
+	if path == "/api" || path.starts_with("/api/") {
+		let mut response = api::ApiError::from_status(status, message).to_response();
+		auth::challenge(config, &mut response);
+		return response;
+	}
+
+	let text = match (status, invalid_id) {
+		(_, Some(invalid)) => format!(
+			"'{}' is not a valid article id.",
+			html_escape(&invalid.segment)
+		),
+		(StatusCode::PAYLOAD_TOO_LARGE, _) => match too_large {
+			Some(too_large) => format!(
+				"The submitted form is larger than the maximum of {} set in the configuration of the wiki.",
+				cli::format_size(too_large.limit)
+			),
+			None => "The request is too large.".to_string(),
+		},
+		(StatusCode::NOT_FOUND, _) => "There is no page at this address.".to_string(),
+		(StatusCode::UNAUTHORIZED, _) => format!(
+			r#"This page needs the username and password of the wiki, <a href="{}?next={}">log in</a> first."#,
+			html_escape(&config.url("/login")),
+			percent_encoding::utf8_percent_encode(path, percent_encoding::NON_ALPHANUMERIC)
+		),
+		(StatusCode::SERVICE_UNAVAILABLE, _) => {
+			"The database is busy, try again in a moment.".to_string()
+		}
+		(StatusCode::INTERNAL_SERVER_ERROR, _) => {
+			"Something went wrong. The error has been logged.".to_string()
+		}
+		_ => "The request could not be processed.".to_string(),
+	};
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::GITHUB_MARKDOWN.text());
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">{} {}</h2>
+				<p>{}</p>
+				<p>Go to the <a href="{}">index</a> or <a href="{}">search</a> the wiki.</p>
+			</div>
+		</main>
+"#,
+		generate_menu(config, user, None, None),
+		status.as_u16(),
+		status.canonical_reason().unwrap_or("Error"),
+		text,
+		html_escape(&config.url("/")),
+		html_escape(&config.url("/search/article")),
+	);
+	warp::reply::with_status(warp::reply::html(doc.to_html()), status).into_response()
 }
 
 async fn article_edit_page(
-	db: Arc<Mutex<Database>>,
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	dictionary: Arc<Mutex<Dictionary>>,
 	article_number: ItemId,
-) -> Result<impl warp::Reply, warp::Rejection> {
+	slug: Option<String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	if let Some(redirect) = slug_redirect(&db, &config, "/edit", article_number, slug).await {
+		return Ok(redirect);
+	}
 	let mut db = db.lock().await;
 
-	if let Some(article) = db.get_article(article_number) {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(GITHUB_MARKDOWN);
-		doc.styles.push(MAIN_STYLE);
-		doc.styles.push(include_str!("easymde/easymde.min.css"));
-		doc.scripts.push(include_str!("easymde/easymde.min.js"));
+	if let Some(article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	{
+		let category = db.get_article_category(article_number);
+		let category_options = category_options(&mut db, category, None);
+		// References by ID are not affected by renaming
+		let config_refs = config_refs::Registry::resolve(&config, &mut db);
+		let config_note = config_refs_note(
+			config_refs.refs_to(article_number).filter(|config_ref| {
+				matches!(config_ref.article, config_refs::ArticleRef::Title(_))
+			}),
+			"After renaming, the old title keeps working through a redirect, but the configuration should be updated.",
+		);
+		let mut doc = HtmlDocument::new(&config);
+		doc.styles.push(assets::GITHUB_MARKDOWN.text());
+		doc.styles.push(assets::MAIN_STYLE.text());
+		doc.styles.push(assets::EASYMDE_STYLE.text());
+		doc.scripts.push(assets::EASYMDE_SCRIPT.text());
+		doc.scripts.push(assets::EDITOR_PREVIEW.text());
+		doc.scripts.push(assets::EDITOR_SECTIONS.text());
+		if config.editor.spellcheck {
+			doc.scripts.push(assets::EDITOR_DICTIONARY.text());
+		}
+		// The preview contains highlighted code
+		doc.style = config.appearance.code_theme_css().to_string();
 		doc.body = format!(
 			r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<ul class="menu">
-					<li><a href="../../preview/article/{}" class="menu_current">Preview</a></li>
-				</ul>
+				<nav aria-label="Article">
+					<ul class="menu">
+						<li><a href="{base}/preview/article/{}" class="menu_current">Preview</a></li>
+					</ul>
+				</nav>
 
 				<p>Article {}</p>
-
-				<p>
-					<form action="../../article/{}" method="post">
-						<label for="article_title">Title:</label><input type="text" id="article_title" name="article_title" class="editor_input" value="{}"><br>
-						<label for="article_text">Text:</label><br>
-						<textarea id="article_text" name="article_text" class="editor_textarea">{}</textarea><br>
-						<input type="submit" class="editor_submit" value="Save">
-					</form>
-				</p>
+{}				<form action="{base}/article/{}" method="post">
+					<label for="article_title">Title:</label><input type="text" id="article_title" name="article_title" class="editor_input" value="{}"><br>
+					<label for="article_tags">Tags:</label><input type="text" id="article_tags" name="article_tags" class="editor_input" value="{}" placeholder="comma-separated"><br>
+					<label for="article_category">Category:</label><select id="article_category" name="article_category"><option value="">(none)</option>{}</select><br>
+					<label for="article_text">Text:</label><br>
+					<textarea id="article_text" name="article_text" class="editor_textarea">{}</textarea><br>
+					<label for="edit_summary">Summary of changes:</label><input type="text" id="edit_summary" name="edit_summary" class="editor_input" maxlength="{}" placeholder="optional"><br>
+					<input type="checkbox" id="regenerate_slug" name="regenerate_slug" value="1"><label for="regenerate_slug">Regenerate URL from title (<code>{}</code>), old links keep working</label><br>
+					<input type="hidden" name="base_revision" value="{}">
+					<input type="submit" class="editor_submit" value="Save">
+				</form>
 				
-				<script>
-				var easyMDE = new EasyMDE({{
-					autoDownloadFontAwesome: false,
-					lineNumbers: true,
-					spellChecker: false,
-					toolbar: false,
-					element: document.getElementById('article_text')
-				}});
-				</script>
+				<script>{}</script>
+				<script type="application/json" id="section_lines">{}</script>
+				<script>redwoodEditorSections(easyMDE, "section_lines");</script>
 			</div>
-		</div>
+		</main>
 "####,
-			generate_menu(Some(article_number)),
+			generate_menu(
+				&config,
+				user.as_ref(),
+				Some(article_number),
+				Some(MenuItem::EditArticle)
+			),
 			article_number,
 			article_number,
+			config_note,
 			article_number,
-			&article.title,
-			&article.text
+			attribute_escape(&article.title),
+			attribute_escape(&db.get_tags(article_number).unwrap_or_default().join(", ")),
+			category_options,
+			textarea_escape(&article.text),
+			database::MAX_EDIT_SUMMARY_LENGTH,
+			html_escape(&config.url(&urls::article_path(article_number, &article.slug))),
+			article.revision,
+			editor_script(&config.editor, &*dictionary.lock().await, article_number),
+			section_lines_json(&config, &article.text)
 		);
-		Ok(warp::reply::html(doc.to_html()))
+		Ok(warp::reply::html(doc.to_html()).into_response())
 	} else {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(GITHUB_MARKDOWN);
-		doc.styles.push(MAIN_STYLE);
+		let mut doc = HtmlDocument::new(&config);
+		doc.styles.push(assets::GITHUB_MARKDOWN.text());
+		doc.styles.push(assets::MAIN_STYLE.text());
 		doc.body = format!(
 			r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
 				<ul class="menu">
 				</ul>
 
 				<p>Could not find article with id {}</p>
 			</div>
-		</div>
+		</main>
 "####,
-			generate_menu(None),
+			generate_menu(&config, user.as_ref(), None, None),
 			article_number
 		);
-		Ok(warp::reply::html(doc.to_html()))
-	}
-}
-
-async fn article_page_post(
-	db: Arc<Mutex<Database>>,
-	article_number: ItemId,
-	param_map: HashMap<String, String>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-	{
-		let mut db = db.lock().await;
-		log::trace!("Article update post request: {:?}", param_map);
-		db.update_article(
-			article_number,
-			param_map.get("article_title").map(|a| -> &str { a }),
-			param_map.get("article_text").map(|a| -> &str { a }),
-		)
-		.unwrap(); //TODO: Two None parameters here lead to error, handle it
+		Ok(warp::reply::html(doc.to_html()).into_response())
 	}
-	article_page(db, article_number).await
 }
 
-fn handle_unknown_ref<'a>(
-	db: &mut Database,
-	inject_event: &mut VecDeque<Event<'a>>,
-	_link_url: &str,
-	_link_title: &str,
-	link_text: &str,
-) {
-	//println!("Unknown ref: {} {} {}", link_url, link_title, link_text);
-	if let Some(article_str) = link_text.strip_prefix("article:") {
-		let mut article_iter = article_str.split('|');
-
-		if let Some(id_str) = article_iter.next() {
-			if let Ok(id) = id_str.parse::<ItemId>() {
-				let dest_url = "../../article/".to_owned() + id_str;
-				if let Some(title) = db.get_article_title(id) {
-					let displayed_title = article_iter
-						.next()
-						.map_or_else(|| title.to_string(), |s| s.to_string());
-					inject_event.push_back(Event::Start(Tag::Link(
-						LinkType::Autolink,
-						CowStr::Boxed(dest_url.to_string().into_boxed_str()),
-						CowStr::Boxed(title.to_string().into_boxed_str()),
-					)));
-					inject_event
-						.push_back(Event::Text(CowStr::Boxed(displayed_title.into_boxed_str())));
-					inject_event.push_back(Event::End(Tag::Link(
-						LinkType::Autolink,
-						CowStr::Boxed(dest_url.to_string().into_boxed_str()),
-						CowStr::Boxed(title.to_string().into_boxed_str()),
-					)));
-					return;
-				}
-			}
-		} else {
-			unreachable!();
-		}
-	}
-
-	// Does not match any wiki commands... Just emit as text.
-	inject_event.push_back(Event::Text(CowStr::Boxed(
-		format!("[{}]", link_text).into_boxed_str(),
-	)));
+/// Line of each section heading in the article text by its anchor, so
+/// the editor can jump to the section in the URL fragment
+fn section_lines_json(config: &Config, text: &str) -> String {
+	let lines: serde_json::Map<String, serde_json::Value> =
+		sections::heading_anchors(text, render_options(config).parser_options())
+			.into_iter()
+			.map(|anchor| (anchor.id, anchor.line.into()))
+			.collect();
+	// The IDs are slugs, but never allow the script element to be closed
+	serde_json::Value::Object(lines)
+		.to_string()
+		.replace('<', "\\u003c")
 }
 
-async fn article_page(
-	db: Arc<Mutex<Database>>,
-	article_number: ItemId,
-) -> Result<impl warp::Reply, warp::Rejection> {
-	let mut db = db.lock().await;
-
-	if let Some(article) = db.get_article(article_number) {
-		let mut css_str = String::new();
-		let ts = syntect::highlighting::ThemeSet::load_defaults();
-		for (_key, theme) in ts.themes {
-			let css = syntect::html::css_for_theme_with_class_style(
-				&theme,
-				syntect::html::ClassStyle::Spaced,
-			)
-			.unwrap();
-			//println!("{}.css - {}", _key, css);
-			css_str = css;
-			break;
-		}
-
-		// Markdown handling
-		let mut options = Options::empty();
-		options.insert(Options::ENABLE_TABLES); // https://www.tablesgenerator.com/markdown_tables
-										//options.insert(Options::ENABLE_FOOTNOTES); // https://www.markdownguide.org/extended-syntax/#footnotes
-		options.insert(Options::ENABLE_STRIKETHROUGH); // `~~strikethrough~~`
-		options.insert(Options::ENABLE_TASKLISTS); // `- [ ]` or `- [x]` or `- [X]`
-										   //options.insert(Options::ENABLE_SMART_PUNCTUATION); // creates em-dashes for `--` and nice quotes for `"Hello."` or `'thing'`
-										   //For smart punctuation, also see spec: https://github.com/raphlinus/pulldown-cmark/blob/d99667b3a8843744494366799025dcea614ff866/third_party/CommonMark/smart_punct.txt
-
-		let mut broken_link_callback = |_link: pulldown_cmark::BrokenLink<'_>| {
-			//println!("{:?}", link.reference);
-
-			// Returns Option<link_url, hover_description>
-			// Because we need deeper modifications (in particular, the
-			// text of the link itself, we just return empty strings here
-			// and modify the ShortcutUnknown Link events.
-			Some((CowStr::Borrowed(""), CowStr::Borrowed("")))
-		};
-
-		let mut unknown_ref_callback = |inject_event: &mut VecDeque<Event>,
-		                                link_url: &str,
-		                                link_title: &str,
-		                                link_text: &str| {
-			// Capture the locked database `db` in the closure here
-			handle_unknown_ref(&mut db, inject_event, link_url, link_title, link_text);
-		};
-
-		let parser = UnknownRefHandlingStream::new(
-			TextMergeStream::new(Parser::new_with_broken_link_callback(
-				&article.text,
-				options,
-				Some(&mut broken_link_callback),
-			)),
-			&mut unknown_ref_callback,
+/// Script that sets up EasyMDE on the article text area, with the
+/// preview rendered by the server side by side
+fn editor_script(editor: &config::Editor, dictionary: &Dictionary, article: ItemId) -> String {
+	if !editor.spellcheck {
+		return format!(
+			r#"
+				var easyMDE = new EasyMDE({{
+					autoDownloadFontAwesome: false,
+					lineNumbers: true,
+					spellChecker: false,
+					toolbar: false,
+					sideBySideFullscreen: false,
+					previewClass: ["editor-preview", "markdown"],
+					previewRender: redwoodPreviewRender({}),
+					element: document.getElementById('article_text')
+				}});
+				EasyMDE.toggleSideBySide(easyMDE);
+				"#,
+			article
 		);
+	}
+	// The built-in spell checker of EasyMDE downloads its dictionary from a CDN,
+	// so the native spell checker of the browser is used instead
+	let words = serde_json::to_string(&dictionary.words().collect::<Vec<_>>())
+		.unwrap()
+		.replace("</", "<\\/");
+	format!(
+		r#"
+				var easyMDE = new EasyMDE({{
+					autoDownloadFontAwesome: false,
+					lineNumbers: true,
+					spellChecker: false,
+					nativeSpellcheck: true,
+					inputStyle: "contenteditable",
+					toolbar: false,
+					sideBySideFullscreen: false,
+					previewClass: ["editor-preview", "markdown"],
+					previewRender: redwoodPreviewRender({}),
+					element: document.getElementById('article_text')
+				}});
+				EasyMDE.toggleSideBySide(easyMDE);
+				redwoodEditorDictionary(easyMDE, {});
+				"#,
+		article, words
+	)
+}
 
-		let parser = LinkHighlightStream::new(SyntaxHighlightStream::new(parser.into_iter()));
+#[derive(serde::Deserialize)]
+struct PreviewRequest {
+	text: String,
+	/// The article being edited, if any
+	article: Option<u32>,
+}
 
-		// Write to String buffer.
-		let mut html_output = String::new();
-		html::push_html(&mut html_output, parser);
+#[derive(serde::Serialize)]
+struct PreviewResponse {
+	html: String,
+	warnings: Vec<String>,
+}
 
-		if html_output == "" {
-			html_output = format!("[This article is empty. Click <a href='../../edit/article/{}'>here</a> to edit it.]", article.id);
+/// Render text exactly like the article page, for the editor preview.
+/// The HTML is meant to be shown on the edit page of the article, the
+/// links of references are relative to it.
+async fn preview_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	request: PreviewRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let rendered = render_article(&mut *db.lock().await, &config, &request.text, None);
+	if let Some(article) = request.article {
+		for warning in &rendered.warnings {
+			log::trace!("Preview of article {}: {}", article, warning);
 		}
-
-		let mut doc = HtmlDocument::new();
-		doc.style = css_str;
-		doc.styles.push(GITHUB_MARKDOWN);
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r####"
-		{}
-		<div class="main_content">
-			<div class="content markdown">
-				<h1>{} <span style="color: #BBBBBB;">#{}</span> <a href='../../edit/article/{}'>[edit]</a></h1>
-
-				{}
-				
-			</div>
-		</div>
-"####,
-			generate_menu(Some(article_number)),
-			&article.title,
-			article_number,
-			article_number,
-			html_output
-		);
-		Ok(warp::reply::html(doc.to_html()))
-	} else {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(GITHUB_MARKDOWN);
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r####"
-		{}
-		<div class="main_content">
-			<div class="content markdown">
-				<p>Could not find article #{}!</p>
-			</div>
-		</div>
-"####,
-			generate_menu(None),
-			article_number
-		);
-		Ok(warp::reply::html(doc.to_html()))
 	}
+	Ok(warp::reply::json(&PreviewResponse {
+		html: rendered.html,
+		warnings: rendered.warnings,
+	}))
 }
 
-async fn search_page_post(
-	db: Arc<Mutex<Database>>,
+/// Render the title and text of the edit form without saving them, as
+/// an HTML fragment for a preview pane on the edit page. References
+/// resolve like on the article page.
+async fn article_preview_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
 	param_map: HashMap<String, String>,
-) -> Result<impl warp::Reply, warp::Rejection> {
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let (Some(title), Some(text)) = (
+		param_map.get("article_title"),
+		param_map.get("article_text"),
+	) else {
+		return Ok(warp::reply::with_status(
+			"The form needs `article_title` and `article_text`\n",
+			warp::http::StatusCode::BAD_REQUEST,
+		)
+		.into_response());
+	};
 	let mut db = db.lock().await;
-	log::trace!("Article update post request: {:?}", param_map);
+	if db.get_article_title(article_number).is_none() {
+		return Ok(warp::reply::with_status(
+			format!("Could not find article #{}\n", article_number),
+			warp::http::StatusCode::NOT_FOUND,
+		)
+		.into_response());
+	}
+	let rendered = render_article(&mut db, &config, text, None);
+	drop(db);
+	for warning in &rendered.warnings {
+		log::trace!("Preview of article {}: {}", article_number, warning);
+	}
+	let fragment = format!(
+		"<h1>{} <span style=\"color: #BBBBBB;\">#{}</span></h1>\n{}",
+		html_escape(title),
+		article_number,
+		rendered.html
+	);
+	Ok(
+		warp::reply::with_header(warp::reply::html(fragment), "Cache-Control", "no-store")
+			.into_response(),
+	)
+}
 
-	let empty_string = String::new();
-	let search_term = param_map.get("search_term_plain").unwrap_or(&empty_string);
-	let articles = db.search_articles(search_term);
+async fn dictionary_get(
+	dictionary: Arc<Mutex<Dictionary>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let dictionary = dictionary.lock().await;
+	Ok(warp::reply::json(&dictionary.words().collect::<Vec<_>>()))
+}
 
-	if let Some(articles) = articles {
-		use std::fmt::Write;
-		let search_regex = RegexBuilder::new(&regex::escape(search_term))
-			.case_insensitive(true)
-			.build()
-			.expect("Invalid Regex");
-
-		let mut exact_list_html = "<br>\nExact matches:<br>\n".to_string();
-		let mut title_list_html = "<br>\nTitle matches:<br>\n".to_string();
-		let mut text_list_html = "<br>\nText matches:<br>\n".to_string();
-		let mut exact_match_cnt = 0;
-		let mut title_match_cnt = 0;
-		let mut text_match_cnt = 0;
-		for article in &articles {
-			let mut title_match = false;
-			let mut title = String::new();
-			for part in search_regex.partition(&article.title) {
-				match part {
-					Part::NoMatch(text) => {
-						Write::write_str(&mut title, text).unwrap();
-					}
-					Part::Match(text) => {
-						title_match = true;
-						write!(title, "<b style=\"color:red;\">{}</b>", text).unwrap();
-					}
-				}
-			}
+#[derive(serde::Deserialize)]
+struct DictionaryWord {
+	word: String,
+}
 
-			//TODO: Unify with generate_articles_list elsewhere. Have one unique way to show article lists.
+async fn dictionary_post(
+	dictionary: Arc<Mutex<Dictionary>>,
+	word: DictionaryWord,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	use warp::http::StatusCode;
+	let mut dictionary = dictionary.lock().await;
+	let word = word.word.trim();
+	match dictionary.add(word) {
+		Ok(true) => Ok(warp::reply::with_status("Word added", StatusCode::CREATED)),
+		Ok(false) => Ok(warp::reply::with_status(
+			"Word already in dictionary",
+			StatusCode::OK,
+		)),
+		Err(err) => Err(warp::reject::custom(api::ApiError::from(err))),
+	}
+}
 
-			if article.title.to_lowercase() == search_term.to_lowercase() {
-				exact_match_cnt += 1;
-				write!(
-					exact_list_html,
-					"<a href=\"/article/{}\">{}</a> <span style=\"color: #BBBBBB;\">#{}</span><br>\n",
-					article.id, title, article.id
-				)
-				.unwrap();
-			} else if title_match {
-				title_match_cnt += 1;
-				write!(
-					title_list_html,
-					"<a href=\"/article/{}\">{}</a> <span style=\"color: #BBBBBB;\">#{}</span><br>\n",
-					article.id, title, article.id
-				)
-				.unwrap();
-			} else {
-				text_match_cnt += 1;
-				write!(
-					text_list_html,
-					"<a href=\"/article/{}\">{}</a> <span style=\"color: #BBBBBB;\">#{}</span><br>\n",
-					article.id, title, article.id
-				)
-				.unwrap();
+async fn article_page_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	log::trace!("Article update post request: {:?}", param_map);
+	const FIELDS: [&str; 5] = [
+		"article_title",
+		"article_text",
+		"article_tags",
+		"article_category",
+		"regenerate_slug",
+	];
+	if !FIELDS.iter().any(|field| param_map.contains_key(*field)) {
+		return Ok(bad_form_page(
+			&config,
+			user.as_ref(),
+			&format!(
+				"The form changes nothing, it needs one of the fields {}.",
+				FIELDS.join(", ")
+			),
+		));
+	}
+	let title = param_map.get("article_title").map(|a| -> &str { a });
+	if title.is_some_and(|title| title.trim().is_empty()) {
+		return Ok(bad_form_page(
+			&config,
+			user.as_ref(),
+			"The title must not be empty.",
+		));
+	}
+	let text = param_map.get("article_text").map(|a| -> &str { a });
+	let summary = param_map.get("edit_summary").map(|a| -> &str { a });
+	let mut db = db.lock().await;
+	// The revision the editor started from. If the article was saved
+	// since, nothing is saved and the conflict is shown instead.
+	let base_revision = param_map
+		.get("base_revision")
+		.and_then(|revision| revision.parse::<i64>().ok());
+	// A form that only changes the tags leaves the article itself alone
+	if title.is_some() || text.is_some() {
+		let updated = match db.update_article(article_number, title, text, summary, base_revision) {
+			Ok(updated) => updated,
+			Err(database::DbError::AlreadyExists) => {
+				return Ok(bad_form_page(
+					&config,
+					user.as_ref(),
+					"Another article already has this title, titles must differ in more than case and whitespace.",
+				));
+			}
+			Err(_) => return Ok(save_error_page(&config, user.as_ref(), article_number)),
+		};
+		if updated == 0 && base_revision.is_some() {
+			if let Some(article) = db
+				.get_article(article_number)
+				.map_err(warp::reject::custom)?
+			{
+				return Ok(edit_conflict_page(
+					&config,
+					user.as_ref(),
+					&article,
+					&param_map,
+				));
 			}
-			//log::info!("{:?}", title);
-			//titles.push_str(&format!("<a href=\"https://foo\">{}</a>", title));
-		}
-
-		if exact_match_cnt == 0 {
-			exact_list_html.clear();
-		}
-
-		if title_match_cnt == 0 {
-			title_list_html.clear();
 		}
-
-		if text_match_cnt == 0 {
-			text_list_html.clear();
+	}
+	if let Some(text) = text {
+		save_links(&mut db, &config, article_number, text);
+	}
+	if param_map.contains_key("regenerate_slug") {
+		db.regenerate_slug(article_number);
+	}
+	if let Some(list) = param_map.get("article_tags") {
+		save_tags(&mut db, article_number, &tags::parse_list(list));
+	}
+	if let Some(category) = param_map.get("article_category") {
+		// An unknown category leaves the article where it is
+		if let Ok(category) = parse_optional_id(category) {
+			let _ = db.set_article_category(article_number, category);
 		}
+	}
+	run_save_hooks(&mut db, &config, article_number);
+	// Redirect, so that reloading the page does not send the form again
+	let path = match db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	{
+		Some(article) => urls::article_path(article.id, &article.slug),
+		None => format!("/article/{}", article_number),
+	};
+	Ok(warp::redirect::see_other(site_uri(&config, &path)).into_response())
+}
 
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r#"
+/// Page about a submitted form that lacks fields or has invalid ones
+fn bad_form_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	message: &str,
+) -> warp::reply::Response {
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<h2 style="margin-top: 0px;">Articles</h2>
-				<p>
-				{}{}{}
-				</p>
+				<p>{}</p>
+				<p>Go back to correct it.</p>
 			</div>
-		</div>
-"#,
-			generate_menu(None),
-			exact_list_html,
-			title_list_html,
-			text_list_html
-		);
-		Ok(warp::reply::html(doc.to_html()))
-	} else {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r#"
+		</main>
+"####,
+		generate_menu(config, user, None, None),
+		html_escape(message)
+	);
+	warp::reply::with_status(
+		warp::reply::html(doc.to_html()),
+		warp::http::StatusCode::BAD_REQUEST,
+	)
+	.into_response()
+}
+
+/// Page about an edit that could not be saved
+fn save_error_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number: ItemId,
+) -> warp::reply::Response {
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<p>
-					Could not fetch the articles.
-				</p>
+				<p>Could not save article #{}.</p>
 			</div>
-		</div>
-"#,
-			generate_menu(None)
-		);
-		Ok(warp::reply::html(doc.to_html()))
-	}
-}
-
-async fn search_page_get(db: Arc<Mutex<Database>>) -> Result<impl warp::Reply, warp::Rejection> {
-	let mut _db = db.lock().await;
-	//TODO: Add search page
-	Ok(warp::reply::html(format!(
-		"Search page not yet implemented"
-	)))
+		</main>
+"####,
+		generate_menu(config, user, Some(article_number), None),
+		article_number
+	);
+	warp::reply::with_status(
+		warp::reply::html(doc.to_html()),
+		warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+	)
+	.into_response()
 }
 
-//<div contenteditable="true"></div>
-//<style type=text/css>body { max-width: 800px; margin: auto; }</style>
-
-async fn index_page(db: Arc<Mutex<Database>>) -> Result<impl warp::Reply, warp::Rejection> {
-	let _db = db.lock().await;
-	let mut doc = HtmlDocument::new();
-	doc.styles.push(MAIN_STYLE);
+/// Page shown instead of saving an edit of an article that was saved
+/// by someone else in the meantime. It shows the submitted text, so
+/// that it is not lost, and the stored one. Saving the submitted form
+/// again with the current revision overwrites the other edit.
+fn edit_conflict_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	article: &Article,
+	param_map: &HashMap<String, String>,
+) -> warp::reply::Response {
+	let base = config.network.base_path.as_str();
+	let mut fields: Vec<_> = param_map
+		.iter()
+		.filter(|(name, _)| *name != "article_text" && *name != "base_revision")
+		.collect();
+	fields.sort();
+	let mut hidden = String::new();
+	for (name, value) in fields {
+		hidden.push_str(&format!(
+			"\t\t\t\t\t<input type=\"hidden\" name=\"{}\" value=\"{}\">\n",
+			attribute_escape(name),
+			attribute_escape(value)
+		));
+	}
+	let submitted = param_map.get("article_text").map_or_else(String::new, |text| {
+		format!(
+			"\t\t\t\t\t<label for=\"article_text\">Your text:</label><br>\n\t\t\t\t\t<textarea id=\"article_text\" name=\"article_text\" class=\"editor_textarea\">{}</textarea><br>\n",
+			textarea_escape(text)
+		)
+	});
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
 	doc.body = format!(
-		r#"
+		r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<h2 style="margin-top: 0px;">Redwood Wiki</h2>
+				<h2 style="margin-top: 0px;">Edit conflict</h2>
+				<p>The article <a href="{}">{}</a> was saved by someone else while you edited it. Your changes were not saved.</p>
+				<form action="{base}/article/{}" method="post">
+					<input type="hidden" name="base_revision" value="{}">
+{}{}					<input type="submit" class="editor_submit" value="Overwrite anyway">
+				</form>
+				<p><a href="{base}/edit/article/{}">Back to editing</a> the current text, which is:</p>
+				<textarea class="editor_textarea" readonly aria-label="Current text">{}</textarea>
 			</div>
-		</div>
-"#,
-		generate_menu(None)
+		</main>
+"####,
+		generate_menu(config, user, Some(article.id), None),
+		html_escape(&config.url(&urls::article_path(article.id, &article.slug))),
+		html_escape(&article.title),
+		article.id,
+		article.revision,
+		hidden,
+		submitted,
+		article.id,
+		textarea_escape(&article.text)
 	);
-	Ok(warp::reply::html(doc.to_html()))
+	warp::reply::with_status(
+		warp::reply::html(doc.to_html()),
+		warp::http::StatusCode::CONFLICT,
+	)
+	.into_response()
 }
 
-// TODO: Perhaps use the following crate?
-// https://stackoverflow.com/questions/77257503/serve-static-files-in-warp-that-are-bundled-in-the-executable
-// https://crates.io/crates/static_dir
-// Pictures are only cached with
-// Last-Modified (https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Last-Modified) or
-// ETag (https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag)
-
-use warp::http::response::Response;
-
-const FAVICON_ICO: &'static [u8] = include_bytes!("favicon/favicon.ico");
-
-async fn favicon_ico_page() -> Result<impl warp::Reply, warp::Rejection> {
-	let response = Response::builder()
-		.status(200)
-		.header("Content-Type", "image/x-icon")
-		.body(FAVICON_ICO)
-		.unwrap();
-	Ok(response)
+/// Start the configured requests after an article was saved
+fn run_save_hooks(db: &mut Database, config: &Config, article_number: ItemId) {
+	let (hooks, policy) = hooks::from_config(config);
+	if let (false, Some(article)) = (
+		hooks.is_empty(),
+		db.get_article(article_number).ok().flatten(),
+	) {
+		let path = urls::article_path(article.id, &article.slug);
+		let url = urls::canonical_url(config.network.public_url.as_deref(), &path);
+		// The path as requested through a proxy in front of the wiki
+		tokio::spawn(hooks::run_article_saved(
+			hooks,
+			policy,
+			config.url(&path),
+			url,
+		));
+	}
 }
 
-const FAVICON_SVG: &'static [u8] = include_bytes!("favicon/icon.svg");
+/// Give the article exactly the tags in `new_tags`
+fn save_tags(db: &mut Database, article_number: ItemId, new_tags: &[String]) {
+	let old_tags = db.get_tags(article_number).unwrap_or_default();
+	for tag in old_tags.iter().filter(|tag| !new_tags.contains(tag)) {
+		let _ = db.remove_tag(article_number, tag);
+	}
+	for tag in new_tags.iter().filter(|tag| !old_tags.contains(tag)) {
+		let _ = db.add_tag(article_number, tag);
+	}
+}
 
-async fn favicon_svg_page() -> Result<impl warp::Reply, warp::Rejection> {
-	let response = Response::builder()
-		.status(200)
-		.header("Content-Type", "image/svg+xml")
-		.body(FAVICON_SVG)
-		.unwrap();
-	Ok(response)
+/// Store the articles `text` references as the links of the article
+fn save_links(db: &mut Database, config: &Config, article_number: ItemId, text: &str) {
+	let refs = render::article_refs(text, render_options(config));
+	let _ = db.set_article_links(article_number, &refs);
 }
 
-const WIKI_ICON: &'static [u8] = include_bytes!("wiki_icon/redwood_observatory_medium.png");
+/// Length of the article summary in link previews, in characters
+const SUMMARY_LENGTH: usize = 200;
 
-async fn wiki_icon_page() -> Result<impl warp::Reply, warp::Rejection> {
-	let response = Response::builder()
-		.status(200)
-		.header("Content-Type", "image/png")
-		.header("last-modified", "Sun, 31 Dec 2023 16:36:16 GMT") // TODO: Get last time modified from actual file
-		.body(WIKI_ICON)
+/// Open Graph tags and JSON-LD block describing the article, so that
+/// links to it get a preview in chat apps and the like
+fn article_metadata(article: &Article, config: &Config) -> String {
+	use std::fmt::Write;
+	let description = markdown_utils::plain_text_summary(&article.text, SUMMARY_LENGTH);
+	let path = urls::article_path(article.id, &article.slug);
+	let url = urls::canonical_url(config.network.public_url.as_deref(), &path);
+	let modified = article.date_modified.rfc3339();
+	let created = article.date_created.rfc3339();
+
+	let mut out = String::new();
+	let mut meta = |property: &str, content: &str| {
+		write!(
+			out,
+			"\n\t\t<meta property=\"{}\" content=\"{}\">",
+			property,
+			html_escape(content)
+		)
 		.unwrap();
-	Ok(response)
-}
+	};
+	meta("og:type", "article");
+	meta("og:title", &article.title);
+	meta("og:description", &description);
+	if let Some(url) = &url {
+		meta("og:url", url);
+	}
+	meta("article:modified_time", &modified);
+	write!(
+		out,
+		"\n\t\t<link rel=\"canonical\" href=\"{}\">",
+		html_escape(&url.clone().unwrap_or_else(|| config.url(&path)))
+	)
+	.unwrap();
 
-async fn articles_page(db: Arc<Mutex<Database>>) -> Result<impl warp::Reply, warp::Rejection> {
-	let mut db = db.lock().await;
-	let articles = db.get_all_articles();
+	let mut json_ld = serde_json::json!({
+		"@context": "https://schema.org",
+		"@type": "Article",
+		"headline": article.title,
+		"description": description,
+		"dateCreated": created,
+		"dateModified": modified,
+	});
+	if let Some(url) = url {
+		json_ld["url"] = url.into();
+	}
+	// `<` is escaped so the text cannot close the script element
+	write!(
+		out,
+		"\n\t\t<script type=\"application/ld+json\">{}</script>",
+		json_ld.to_string().replace('<', "\\u003c")
+	)
+	.unwrap();
+	out
+}
 
-	fn generate_articles_list(articles: Vec<Article>) -> String {
-		let mut accumulator = String::new();
-		for article in &articles {
-			use std::fmt::Write;
-			write!(
-				accumulator,
-				"<a href=\"/article/{}\">{}</a> <span style=\"color: #BBBBBB;\">#{}</span><br>\n",
-				article.id, article.title, article.id
-			)
-			.unwrap();
+/// Show an article under `/article/{id}` or `/article/{id}/{slug}`.
+/// The article is looked up by ID, a slug that is not the stored
+/// one leads to a redirect to the canonical URL. The `redirected_from`
+/// query parameter is set by `wiki_page` when the article was found
+/// through a former title. With `rev`, the text of that revision is
+/// shown instead, or the current one if the revision is not known.
+async fn article_page_get(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	slug: Option<String>,
+	query: HashMap<String, String>,
+	if_none_match: Option<String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	if let Some(redirect) = slug_redirect(&db, &config, "", article_number, slug).await {
+		return Ok(redirect);
+	}
+	let redirected_from = query.get("redirected_from").map(String::as_str);
+	let revision = query.get("rev").and_then(|rev| rev.parse::<i64>().ok());
+	let (epoch, current, renamed) = {
+		let mut db = db.lock().await;
+		let renamed = rename_notice(&mut db, &config, article_number, UtcTimestamp::now());
+		let current = db
+			.get_article(article_number)
+			.ok()
+			.flatten()
+			.map(|article| (article.revision, article.date_modified));
+		(db.render_epoch(), current, renamed)
+	};
+	// The notice disappears with time, not with a change of the database
+	let etag = epoch.map(|epoch| {
+		article_etag(
+			epoch,
+			&config,
+			user.as_ref(),
+			article_number,
+			current,
+			redirected_from,
+			revision,
+			renamed.is_some(),
+		)
+	});
+	if let (Some(etag), Some(if_none_match)) = (&etag, &if_none_match) {
+		if assets::etag_matches(if_none_match, etag) {
+			return Ok(warp::http::Response::builder()
+				.status(warp::http::StatusCode::NOT_MODIFIED)
+				.header(warp::http::header::ETAG, etag)
+				.body(warp::hyper::Body::empty())
+				.unwrap());
 		}
-		accumulator
 	}
-
-	if let Some(articles) = articles {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r#"
-		{}
-		<div class="main_content">
-			<div class="content markdown">
-				<h2 style="margin-top: 0px;">Articles</h2>
-				<p>
-				{}
-				</p>
-			</div>
-		</div>
-"#,
-			generate_menu(None),
-			generate_articles_list(articles)
+	let mut response =
+		article_page_redirected(db, config, article_number, redirected_from, revision, user)
+			.await?
+			.into_response();
+	if let Some(etag) = etag {
+		let headers = response.headers_mut();
+		headers.insert(warp::http::header::ETAG, etag.parse().unwrap());
+		// Revalidated on every use, the page changes with other articles
+		headers.insert(
+			warp::http::header::CACHE_CONTROL,
+			warp::http::HeaderValue::from_static("no-cache"),
 		);
-		Ok(warp::reply::html(doc.to_html()))
-	} else {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r#"
-		{}
-		<div class="main_content">
-			<div class="content markdown">
-				<p>
-					Could not fetch the articles.
-				</p>
-			</div>
-		</div>
-"#,
-			generate_menu(None)
-		);
-		Ok(warp::reply::html(doc.to_html()))
 	}
+	Ok(response)
 }
 
-async fn article_create_page_post(
-	db: Arc<Mutex<Database>>,
-	param_map: HashMap<String, String>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-	let mut db = db.lock().await;
+/// ETag of an article page. The page shows data of other articles, so
+/// the whole database state counts, not just the revision of the
+/// article; the build is included for changes of the rendering. The
+/// menu shows who is logged in, the custom stylesheet is linked with
+/// its hash. The current revision and modification date of the
+/// article are included as well, in case the epoch misses a write.
+#[allow(clippy::too_many_arguments)]
+fn article_etag(
+	epoch: database::RenderEpoch,
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number: ItemId,
+	current: Option<(i64, UtcTimestamp)>,
+	redirected_from: Option<&str>,
+	revision: Option<i64>,
+	rename_notice: bool,
+) -> String {
+	let key = format!(
+		"{}\n{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}\n{:?}\n{}",
+		VersionInfo::get(),
+		epoch,
+		config.wiki.name,
+		config.markdown,
+		config.appearance.code_theme,
+		config.appearance.custom_css().map(|css| css.hash),
+		user.map(|user| user.name.as_str()),
+		article_number,
+		current,
+		redirected_from,
+		revision,
+		rename_notice
+	);
+	format!("\"{:016x}\"", assets::fnv1a(key.as_bytes()))
+}
 
-	log::trace!("Article create post request: {:?}", param_map);
+/// Former title of an article renamed in the last `rename_notice_days`
+/// and the date of the last rename. The former title is the one before
+/// the first of these renames, if the article got it back there is
+/// nothing to tell.
+fn rename_notice(
+	db: &mut Database,
+	config: &Config,
+	article_number: ItemId,
+	now: UtcTimestamp,
+) -> Option<(String, UtcTimestamp)> {
+	let days = config.site.rename_notice_days;
+	if days == 0 {
+		return None;
+	}
+	let since = now - chrono::Duration::days(days.into());
+	let changes = db.get_title_changes(article_number, since)?;
+	let first = changes.first()?.title_change.as_ref()?;
+	let last = changes.last()?;
+	let current = last.title_change.as_ref()?;
+	if first.old_title == current.new_title {
+		return None;
+	}
+	Some((first.old_title.clone(), last.date_created))
+}
 
-	let art = Article {
-		id: 0.into(),
-		title: param_map.get("article_title").unwrap().to_string(), //TODO: Dangerous unwrap here, can crash server!
-		text: "".to_string(),
-		date_created: Utc::now().naive_utc(),
-		date_modified: Utc::now().naive_utc(),
-		revision: 0,
+/// Show the article with the title given in the path, which may also
+/// be a former title of the article
+async fn wiki_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	tail: warp::path::Tail,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let title = percent_encoding::percent_decode_str(tail.as_str())
+		.decode_utf8_lossy()
+		.into_owned();
+	let resolved = {
+		let mut db = db.lock().await;
+		let resolved = db.resolve_title(&title).map_err(warp::reject::custom)?;
+		resolved.map(|(id, redirected)| {
+			let slug = db
+				.get_article(id)
+				.ok()
+				.flatten()
+				.map(|article| article.slug);
+			(id, slug.unwrap_or_default(), redirected)
+		})
 	};
-
-	let create_result = db.create_article(&art);
-	if let Some(id) = create_result {
-		Ok(
-			warp::redirect(warp::http::Uri::from_maybe_shared(format!("/article/{}", id)).unwrap())
-				.into_response(),
-		)
-	} else {
-		let mut doc = HtmlDocument::new();
-		doc.styles.push(MAIN_STYLE);
-		doc.body = format!(
-			r####"
+	match resolved {
+		Some((id, slug, redirected)) => {
+			let path = urls::article_path(id, &slug);
+			let target = if redirected {
+				format!(
+					"{}?redirected_from={}",
+					path,
+					percent_encoding::utf8_percent_encode(
+						&title,
+						percent_encoding::NON_ALPHANUMERIC
+					)
+				)
+			} else {
+				path
+			};
+			Ok(warp::redirect::see_other(site_uri(&config, &target)).into_response())
+		}
+		None => {
+			let mut doc = HtmlDocument::new(&config);
+			doc.styles.push(assets::MAIN_STYLE.text());
+			doc.body = format!(
+				r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<p>
-					Could not create article. Title already existing?
-				</p>
+				<p>There is no article titled "{}". Create it?</p>
+				{}
 			</div>
-		</div>
+		</main>
 "####,
-			generate_menu(None)
-		);
-		Ok(warp::reply::html(doc.to_html()).into_response())
+				generate_menu(&config, user.as_ref(), None, None),
+				html_escape(&title),
+				create_article_form(&config, &title)
+			);
+			Ok(warp::reply::with_status(
+				warp::reply::html(doc.to_html()),
+				warp::http::StatusCode::NOT_FOUND,
+			)
+			.into_response())
+		}
 	}
 }
 
-async fn article_create_page(
-	_db: Arc<Mutex<Database>>,
-) -> Result<impl warp::Reply, warp::Rejection> {
-	let mut doc = HtmlDocument::new();
-	doc.styles.push(MAIN_STYLE);
+fn render_options(config: &Config) -> RenderOptions {
+	RenderOptions {
+		tables: config.markdown.tables,
+		strikethrough: config.markdown.strikethrough,
+		tasklists: config.markdown.tasklists,
+		footnotes: config.markdown.footnotes,
+		smart_punctuation: config.markdown.smart_punctuation,
+		max_events: config.render.max_events,
+		// Pages about an article may have its slug in the path, so links
+		// relative to them would not work
+		article_link_prefix: config.url("/article/"),
+		..RenderOptions::default()
+	}
+}
+
+/// Render the text of an article the way the article page shows it.
+/// With `section_edit`, the headings get links to edit their section
+/// of that article.
+fn render_article(
+	db: &mut Database,
+	config: &Config,
+	text: &str,
+	section_edit: Option<ItemId>,
+) -> render::RenderResult {
+	let mut pipeline = Pipeline::new(render_options(config))
+		.with_syntax_highlight(
+			config.appearance.code_theme_css(),
+			config.render.syntaxes.get(),
+		)
+		.with_link_highlight()
+		.with_word_breaks();
+	if let Some(id) = section_edit {
+		pipeline =
+			pipeline.with_heading_anchors(Some(config.url(&format!("/edit/article/{}", id))));
+	}
+	if config.external_link_icons() {
+		pipeline = pipeline.with_link_icons(config.url("/api/favicon"));
+	}
+	pipeline.with_db_refs(db).render_html(text)
+}
+
+/// Article page that notes the former title the article was found by
+async fn article_page_redirected(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	redirected_from: Option<&str>,
+	revision: Option<i64>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Html<String>, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let mut db = db.lock().await;
+
+	if let Some(mut article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	{
+		let current_path = config.url(&urls::article_path(article_number, &article.slug));
+		let old_text = revision
+			.and_then(|revision| Some((revision, db.get_revision_text(article_number, revision)?)));
+		// The sections of an older text cannot be edited
+		let revision_note = match old_text {
+			Some((revision, text)) => {
+				article.text = text;
+				format!(
+					"\t\t\t\t<p class=\"revision_note\">You are viewing revision {}. <a href=\"{}\">View the current version</a>.</p>\n",
+					revision,
+					html_escape(&current_path)
+				)
+			}
+			None => String::new(),
+		};
+		let rendered = render_article_html(&mut db, &config, &article, revision_note.is_empty());
+		let tags = db.get_tags(article_number).unwrap_or_default();
+		let backlinks = db.count_backlinks(article_number).map_or_else(String::new, |count| {
+			format!(
+				r#" <a href="{base}/links/article/{}" class="backlink_count" aria-label="What links here">[{} {} here]</a>"#,
+				article_number,
+				count,
+				if count == 1 { "link" } else { "links" }
+			)
+		});
+		let breadcrumb = db
+			.get_article_category(article_number)
+			.and_then(|category| db.get_category_path(category))
+			.map_or_else(String::new, |path| {
+				category_breadcrumb(&config, &path, true)
+			});
+		let rename_note = rename_notice(&mut db, &config, article_number, UtcTimestamp::now())
+			.map_or_else(String::new, |(old_title, date)| {
+				format!(
+					"\t\t\t\t<p class=\"rename_note\">Renamed from \u{201c}{}\u{201d} on {}</p>\n",
+					html_escape(&old_title),
+					config.format_timestamp(date)
+				)
+			});
+
+		let mut doc = HtmlDocument::new(&config);
+		doc.title = format!("{} — {}", article.title, config.wiki.name);
+		doc.style = rendered.style;
+		doc.styles.push(assets::GITHUB_MARKDOWN.text());
+		doc.styles.push(assets::MAIN_STYLE.text());
+		doc.head_extra = article_metadata(&article, &config);
+		if !revision_note.is_empty() {
+			doc.head_extra
+				.push_str("\n\t\t<meta name=\"robots\" content=\"noindex\">");
+		}
+		doc.body = format!(
+			r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+{}				<h1>{} <span style="color: #BBBBBB;">#{}</span> <a href="{base}/edit/article/{}" aria-label="Edit this article">[edit]</a>{}</h1>
+{}{}{}{}
+				{}
+				
+			</div>
+		</main>
+"####,
+			generate_menu(&config, user.as_ref(), Some(article_number), None),
+			breadcrumb,
+			html_escape(&article.title),
+			article_number,
+			article_number,
+			backlinks,
+			redirected_from.map_or_else(String::new, |title| format!(
+				"\t\t\t\t<p class=\"redirect_note\">(redirected from {})</p>\n",
+				html_escape(title)
+			)),
+			rename_note,
+			revision_note,
+			tag_links(&config, &tags),
+			rendered.html
+		);
+		Ok(warp::reply::html(doc.to_html()))
+	} else {
+		Ok(article_not_found_page(
+			&config,
+			user.as_ref(),
+			article_number,
+		))
+	}
+}
+
+/// A permanent redirect to the canonical path if `slug` is given but is
+/// not the slug of the article, e.g. after it was renamed. `page` is
+/// the prefix of the path, see `urls::article_page_path`.
+async fn slug_redirect(
+	db: &DbLock,
+	config: &Config,
+	page: &str,
+	article_number: ItemId,
+	slug: Option<String>,
+) -> Option<warp::reply::Response> {
+	let slug = slug?;
+	let stored_slug = db.lock().await.get_article(article_number).ok()??.slug;
+	let slug = percent_encoding::percent_decode_str(&slug).decode_utf8_lossy();
+	if slug == stored_slug {
+		return None;
+	}
+	let path = urls::article_page_path(page, article_number, &stored_slug);
+	Some(warp::redirect(site_uri(config, &path)).into_response())
+}
+
+/// The text of an article rendered for the article page and its
+/// preview, with edit links on the sections if `section_edit`
+fn render_article_html(
+	db: &mut Database,
+	config: &Config,
+	article: &Article,
+	section_edit: bool,
+) -> render::RenderResult {
+	let base = config.network.base_path.as_str();
+	let mut rendered = render_article(
+		db,
+		config,
+		&article.text,
+		section_edit.then_some(article.id),
+	);
+	for warning in &rendered.warnings {
+		log::debug!("Article {}: {}", article.id, warning);
+	}
+	if rendered.html.is_empty() {
+		rendered.html = format!(
+			"[This article is empty. Click <a href='{base}/edit/article/{}'>here</a> to edit it.]",
+			article.id
+		);
+	}
+	rendered
+}
+
+/// The stored text of an article rendered like the article page, but
+/// marked as a preview and without links to edit it
+async fn article_preview_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	slug: Option<String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	if let Some(redirect) = slug_redirect(&db, &config, "/preview", article_number, slug).await {
+		return Ok(redirect);
+	}
+	let mut db = db.lock().await;
+	let Some(article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	else {
+		return Ok(article_not_found_page(&config, user.as_ref(), article_number).into_response());
+	};
+	let rendered = render_article_html(&mut db, &config, &article, false);
+	let mut doc = HtmlDocument::new(&config);
+	doc.style = rendered.style;
+	doc.styles.push(assets::GITHUB_MARKDOWN.text());
+	doc.styles.push(assets::MAIN_STYLE.text());
+	// Search engines should find the article page instead
+	doc.head_extra = "<meta name=\"robots\" content=\"noindex\">".to_string();
 	doc.body = format!(
 		r####"
 		{}
-		<div class="main_content">
+		<main id="main_content" class="main_content">
 			<div class="content markdown">
-				<p>
-					<form action="/create/article" method="post">
-						<label for="article_title">Title:</label><input type="text" id="article_title" name="article_title" class="editor_input" value="">
-						<input type="submit" class="editor_submit" value="Create">
-					</form>
-				</p>
+				<p class="preview_note">Preview of the stored text, <a href="{base}/article/{}">view the article</a>.</p>
+				<h1>{} <span style="color: #BBBBBB;">#{}</span></h1>
+				{}
 			</div>
-		</div>
+		</main>
 "####,
-		generate_menu(None)
+		generate_menu(&config, user.as_ref(), Some(article_number), None),
+		article_number,
+		html_escape(&article.title),
+		article_number,
+		rendered.html
 	);
-	Ok(warp::reply::html(doc.to_html()))
+	Ok(warp::reply::html(doc.to_html()).into_response())
 }
 
-fn generate_menu(article_number_opt: Option<ItemId>) -> String {
-	if let Some(article_number) = article_number_opt {
-		format!(
-			r#"<div class="side_content">
-			<div class="content">
-				{} Redwood wiki
-				<p>
-					Search:
-					<form action="/search/article" method="post">
-						<input type="text" id="search_term_plain" name="search_term_plain" value=""><input type="submit" class="editor_submit" value="Search">
-					</form>
-				</p>
-				<p>
-					Navigation:
-					<ul>
-						<li><a href="/">Home</a></li>
-						<li><a href="/articles">All articles</a></li>
-					</ul>
-				</p>
-				<p>
-					Wiki:
-					<ul>
-						<li><a href="/create/article">Create article</a></li>
-					</ul>
-				</p>
-				<p>
-					Current article:
-					<ul>
-						<li><a href="/edit/article/{}">Edit</a></li>
-					</ul>
-				</p>
+/// Articles that reference an article
+async fn backlinks_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let mut db = db.lock().await;
+	let Some(title) = db.get_article_title(article_number) else {
+		return Ok(article_not_found_page(
+			&config,
+			user.as_ref(),
+			article_number,
+		));
+	};
+	let content = match db.get_backlinks(article_number) {
+		Some(articles) if articles.is_empty() => "<p>No article links here.</p>".to_string(),
+		Some(articles) => generate_articles_list(&config, &articles, None),
+		None => "<p>Could not fetch the links.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">What links here</h2>
+				<p>Articles that reference <a href="{base}/article/{}">{}</a>:</p>
+				{}
 			</div>
-		</div>"#,
-			REDWOOD_OBS, article_number
-		)
+		</main>
+"#,
+		generate_menu(
+			&config,
+			user.as_ref(),
+			Some(article_number),
+			Some(MenuItem::WhatLinksHere)
+		),
+		article_number,
+		html_escape(&title),
+		content
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// The Markdown of an article as stored, for backups and external
+/// editors
+async fn raw_article_page(
+	db: Arc<DbLock>,
+	article_number: ItemId,
+	if_none_match: Option<String>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let article = db
+		.lock()
+		.await
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?;
+	// Unlike the article page, the text depends on nothing but the
+	// article, so its revision identifies it
+	let etag = article
+		.as_ref()
+		.map(|article| format!("\"{}-{}\"", article.id, article.revision));
+	if let (Some(etag), Some(if_none_match)) = (&etag, &if_none_match) {
+		if assets::etag_matches(if_none_match, etag) {
+			return Ok(Response::builder()
+				.status(warp::http::StatusCode::NOT_MODIFIED)
+				.header(warp::http::header::ETAG, etag)
+				.body(warp::hyper::Body::empty())
+				.unwrap());
+		}
+	}
+	let response = Response::builder()
+		.header("Content-Type", "text/plain; charset=utf-8")
+		.header("X-Content-Type-Options", "nosniff");
+	let response = match (article, etag) {
+		(Some(article), Some(etag)) => response
+			.header(warp::http::header::ETAG, etag)
+			.header(warp::http::header::CACHE_CONTROL, "no-cache")
+			.header(
+				"Content-Disposition",
+				urls::content_disposition(
+					&format!("{}.md", article.slug),
+					&format!("article-{}.md", article.id),
+				),
+			)
+			.body(article.text.into()),
+		_ => response
+			.status(warp::http::StatusCode::NOT_FOUND)
+			.body(format!("Article #{} does not exist\n", article_number).into()),
+	};
+	Ok(response.unwrap())
+}
+
+/// Edit summary for display, an em dash if the editor gave none
+fn edit_summary_html(summary: &str) -> String {
+	if summary.is_empty() {
+		"\u{2014}".to_string()
 	} else {
-		format!(
-			r#"<div class="side_content">
-			<div class="content">
-				{} Redwood wiki
-				<p>
-					Search:
-					<form action="/search/article" method="post">
-						<input type="text" id="search_term_plain" name="search_term_plain" value=""><input type="submit" class="editor_submit" value="Search">
-					</form>
-				</p>
-				<p>
-					Navigation:
-					<ul>
-						<li><a href="/">Home</a></li>
-						<li><a href="/articles">All articles</a></li>
-					</ul>
-				</p>
-				<p>
-					Wiki:
-					<ul>
-						<li><a href="/create/article">Create article</a></li>
-					</ul>
-				</p>
+		html_escape(summary)
+	}
+}
+
+/// The saved edits of an article with their summaries, newest first
+async fn history_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let mut db = db.lock().await;
+	let Some(article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	else {
+		return Ok(article_not_found_page(
+			&config,
+			user.as_ref(),
+			article_number,
+		));
+	};
+	let content = match db.get_revisions(article_number) {
+		Some(revisions) => {
+			let mut list = String::from("<ul>\n");
+			for (i, revision) in revisions.iter().enumerate() {
+				// Revisions with a kept text link to it, with the change
+				// of the size since the revision before
+				let name = match revision.revision {
+					0 => "Created".to_string(),
+					n => format!("Revision {}", n),
+				};
+				let name = if revision.size.is_some() {
+					format!(
+						"<a href=\"{base}/article/{}?rev={}\">{}</a>",
+						article_number, revision.revision, name
+					)
+				} else {
+					name
+				};
+				let previous_size = match revisions.get(i + 1) {
+					Some(previous) => previous.size,
+					None if revision.revision == 0 => Some(0),
+					None => None,
+				};
+				let delta = revision.size.zip(previous_size).map_or_else(
+					String::new,
+					|(size, previous)| {
+						format!(
+							" <span class=\"size_delta\">({:+} bytes)</span>",
+							size - previous
+						)
+					},
+				);
+				// Older revisions can be saved again as a new one
+				let restore = if revision.size.is_some() && revision.revision != article.revision {
+					format!(
+						r#" <form action="{base}/restore/article/{}" method="post" class="inline_form"><input type="hidden" name="revision" value="{}"><input type="submit" class="editor_submit" value="Restore"></form>"#,
+						article_number, revision.revision
+					)
+				} else {
+					String::new()
+				};
+				if revision.revision == 0 {
+					list.push_str(&format!(
+						"<li>{} <span class=\"history_date\">{}</span>{}{}</li>\n",
+						name,
+						config.format_timestamp(revision.date_created),
+						delta,
+						restore
+					));
+					continue;
+				}
+				let change = match &revision.title_change {
+					Some(change) => format!(
+						"<span class=\"badge_renamed\">renamed</span> \u{201c}{}\u{201d} \u{2192} \u{201c}{}\u{201d}{}",
+						html_escape(&change.old_title),
+						html_escape(&change.new_title),
+						if revision.summary.is_empty() {
+							String::new()
+						} else {
+							format!(" {}", html_escape(&revision.summary))
+						}
+					),
+					None => format!(
+						"<span class=\"badge_edited\">edited</span> {}",
+						edit_summary_html(&revision.summary)
+					),
+				};
+				list.push_str(&format!(
+					"<li>{} <span class=\"history_date\">{}</span>{} {}{}</li>\n",
+					name,
+					config.format_timestamp(revision.date_created),
+					delta,
+					change,
+					restore
+				));
+			}
+			// Articles older than the recording of the creation
+			if revisions
+				.last()
+				.is_none_or(|revision| revision.revision != 0)
+			{
+				list.push_str(&format!(
+					"<li>Created <span class=\"history_date\">{}</span></li>\n",
+					config.format_timestamp(article.date_created)
+				));
+			}
+			list.push_str("</ul>");
+			list
+		}
+		None => "<p>Could not fetch the history.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">History</h2>
+				<p>Edits of <a href="{base}/article/{}">{}</a>, newest first:</p>
+				{}
 			</div>
-		</div>"#,
-			REDWOOD_OBS
+		</main>
+"#,
+		generate_menu(
+			&config,
+			user.as_ref(),
+			Some(article_number),
+			Some(MenuItem::History)
+		),
+		article_number,
+		html_escape(&article.title),
+		content
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// Reopen the log file, for log rotation by scripts
+async fn rotate_logs_post(config: Arc<Config>) -> Result<warp::reply::Response, warp::Rejection> {
+	if config.log.file.is_none() {
+		return Ok(warp::reply::with_status(
+			"No log file is configured\n",
+			warp::http::StatusCode::NOT_FOUND,
+		)
+		.into_response());
+	}
+	match rotate_logs() {
+		Ok(_) => Ok(
+			warp::reply::with_status("Log file reopened\n", warp::http::StatusCode::OK)
+				.into_response(),
+		),
+		Err(err) => Ok(warp::reply::with_status(
+			format!("Could not reopen the log file: {}\n", err),
+			warp::http::StatusCode::INTERNAL_SERVER_ERROR,
 		)
+		.into_response()),
+	}
+}
+
+/// The whole wiki as JSON document, see `Database::export_all`
+async fn export_get(db: Arc<DbLock>) -> Result<warp::reply::Response, warp::Rejection> {
+	let mut json = Vec::new();
+	let result = db.lock().await.export_all(&mut json);
+	match result {
+		Ok(_) => Ok(Response::builder()
+			.header("Content-Type", "application/json")
+			.header(
+				"Content-Disposition",
+				"attachment; filename=\"redwood-wiki-export.json\"",
+			)
+			.body(warp::hyper::Body::from(json))
+			.unwrap()),
+		Err(database::ExportError::Database(err)) => Err(warp::reject::custom(err)),
+		Err(err) => {
+			log::error!("Export failed: {}", err);
+			Ok(warp::reply::with_status(
+				format!("Export failed: {}\n", err),
+				warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			)
+			.into_response())
+		}
+	}
+}
+
+/// All tags with their usage and forms to rename or merge them
+async fn admin_tags_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let tags = db.lock().await.get_tag_counts();
+	let content = match tags {
+		Some(tags) if tags.is_empty() => "<p>No article has tags yet.</p>".to_string(),
+		Some(tags) => {
+			let rows: Vec<String> = tags
+				.iter()
+				.map(|(tag, count)| {
+					let escaped = html_escape(tag);
+					format!(
+						r#"<tr>
+						<td><a href="{path}">{tag}</a></td>
+						<td>{count}</td>
+						<td><form action="{base}/admin/tags/rename" method="post" class="inline_form"><input type="hidden" name="from" value="{tag}"><input type="text" name="to" value="{tag}" aria-label="New name of {tag}" required><input type="submit" class="editor_submit" value="Rename"></form></td>
+						<td><form action="{base}/admin/tags/merge" method="post" class="inline_form"><input type="hidden" name="from" value="{tag}"><input type="text" name="into" list="tag_names" aria-label="Tag to merge {tag} into" required><input type="submit" class="editor_submit" value="Merge"></form></td>
+					</tr>"#,
+						path = html_escape(&config.url(&urls::tag_path(tag))),
+						tag = escaped,
+						count = count
+					)
+				})
+				.collect();
+			let names: String = tags
+				.iter()
+				.map(|(tag, _)| format!(r#"<option value="{}">"#, html_escape(tag)))
+				.collect();
+			format!(
+				r#"<table>
+					<thead><tr><th>Tag</th><th>Articles</th><th>Rename</th><th>Merge into</th></tr></thead>
+					<tbody>
+					{}
+					</tbody>
+				</table>
+				<datalist id="tag_names">{}</datalist>"#,
+				rows.join("\n\t\t\t\t\t"),
+				names
+			)
+		}
+		None => "<p>Could not fetch the tags.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Tags</h2>
+				{}
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, None),
+		content
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// Page about a failed tag change, `extra` is HTML shown below the
+/// message
+fn tag_error_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	message: &str,
+	extra: &str,
+	status: warp::http::StatusCode,
+) -> warp::reply::Response {
+	let base = config.network.base_path.as_str();
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>{}</p>
+				{}
+				<p><a href="{base}/admin/tags">Back to the tags</a></p>
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, None, None),
+		html_escape(message),
+		extra
+	);
+	warp::reply::with_status(warp::reply::html(doc.to_html()), status).into_response()
+}
+
+async fn admin_tags_rename_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let from = param_map.get("from").map_or("", String::as_str);
+	let to = param_map.get("to").map_or("", String::as_str);
+	let result = db.lock().await.rename_tag(from, to);
+	match result {
+		Ok(()) => Ok(warp::redirect::see_other(site_uri(&config, "/admin/tags")).into_response()),
+		Err(database::DbError::AlreadyExists) => {
+			let to = tags::normalize(to).unwrap_or_default();
+			let merge_form = format!(
+				r#"<form action="{base}/admin/tags/merge" method="post">
+					<input type="hidden" name="from" value="{}">
+					<input type="hidden" name="into" value="{}">
+					<input type="submit" class="editor_submit" value="Merge &quot;{}&quot; into &quot;{}&quot;">
+				</form>"#,
+				html_escape(from),
+				html_escape(&to),
+				html_escape(from),
+				html_escape(&to)
+			);
+			Ok(tag_error_page(
+				&config,
+				user.as_ref(),
+				&format!(
+					"The tag \"{}\" already exists. Merge the tags to give all articles the tag \"{}\".",
+					to, to
+				),
+				&merge_form,
+				warp::http::StatusCode::CONFLICT,
+			))
+		}
+		Err(database::DbError::NotFound) => Ok(tag_error_page(
+			&config,
+			user.as_ref(),
+			&format!("There is no tag \"{}\", or the new name is empty.", from),
+			"",
+			warp::http::StatusCode::NOT_FOUND,
+		)),
+		Err(_) => Ok(tag_error_page(
+			&config,
+			user.as_ref(),
+			&format!("Could not rename the tag \"{}\".", from),
+			"",
+			warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+		)),
+	}
+}
+
+async fn admin_tags_merge_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let from = param_map.get("from").map_or("", String::as_str);
+	let into = param_map.get("into").map_or("", String::as_str);
+	let result = db.lock().await.merge_tag(from, into);
+	match result {
+		Ok(()) => Ok(warp::redirect::see_other(site_uri(&config, "/admin/tags")).into_response()),
+		Err(database::DbError::NotFound) => Ok(tag_error_page(
+			&config,
+			user.as_ref(),
+			&format!("There is no tag \"{}\", or the target is empty.", from),
+			"",
+			warp::http::StatusCode::NOT_FOUND,
+		)),
+		Err(_) => Ok(tag_error_page(
+			&config,
+			user.as_ref(),
+			&format!("Could not merge the tag \"{}\".", from),
+			"",
+			warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+		)),
+	}
+}
+
+/// Parse a form field that is either empty or an ID
+fn parse_optional_id(value: &str) -> Result<Option<ItemId>, ()> {
+	if value.is_empty() {
+		Ok(None)
+	} else {
+		value.parse().map(Some)
+	}
+}
+
+/// Links to the categories of `path` (see `Database::get_category_path`).
+/// Without `link_last`, the last category is the current page and not
+/// a link.
+fn category_breadcrumb(config: &Config, path: &[database::Category], link_last: bool) -> String {
+	let base = config.network.base_path.as_str();
+	if path.is_empty() {
+		return String::new();
+	}
+	let parts: Vec<String> = path
+		.iter()
+		.enumerate()
+		.map(|(i, category)| {
+			if i + 1 == path.len() && !link_last {
+				format!(
+					r#"<span aria-current="page">{}</span>"#,
+					html_escape(&category.name)
+				)
+			} else {
+				format!(
+					r#"<a href="{base}/category/{}">{}</a>"#,
+					category.id,
+					html_escape(&category.name)
+				)
+			}
+		})
+		.collect();
+	format!(
+		"\t\t\t\t<nav aria-label=\"Category\" class=\"breadcrumb\"><a href=\"{base}/categories\">Categories</a> › {}</nav>\n",
+		parts.join(" › ")
+	)
+}
+
+/// `<option>` elements for all categories, depth-first with their full
+/// path as label. The category `exclude` and its sub-categories are
+/// left out, they are not valid parents when moving it.
+fn category_options(
+	db: &mut Database,
+	selected: Option<ItemId>,
+	exclude: Option<ItemId>,
+) -> String {
+	let mut options = String::new();
+	// Categories still to list, with the path of their parent
+	let mut stack: Vec<(database::Category, String)> = Vec::new();
+	let push_children = |db: &mut Database,
+	                     stack: &mut Vec<(database::Category, String)>,
+	                     parent: Option<ItemId>,
+	                     prefix: &str| {
+		let children = db.get_categories(parent).unwrap_or_default();
+		stack.extend(
+			children
+				.into_iter()
+				.rev()
+				.filter(|category| Some(category.id) != exclude)
+				.map(|category| (category, prefix.to_string())),
+		);
+	};
+	push_children(db, &mut stack, None, "");
+	while let Some((category, prefix)) = stack.pop() {
+		let label = format!("{}{}", prefix, category.name);
+		options.push_str(&format!(
+			r#"<option value="{}"{}>{}</option>"#,
+			category.id,
+			if Some(category.id) == selected {
+				" selected"
+			} else {
+				""
+			},
+			html_escape(&label)
+		));
+		push_children(db, &mut stack, Some(category.id), &format!("{} / ", label));
 	}
+	options
+}
+
+/// Page with a message about a failed category change
+fn category_error_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	message: &str,
+	status: warp::http::StatusCode,
+) -> warp::reply::Response {
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>{}</p>
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, None, Some(MenuItem::Categories)),
+		html_escape(message)
+	);
+	warp::reply::with_status(warp::reply::html(doc.to_html()), status).into_response()
+}
+
+/// Form to create a category below `parent`
+fn category_create_form(config: &Config, parent: Option<ItemId>) -> String {
+	let base = config.network.base_path.as_str();
+	format!(
+		r#"<form action="{base}/create/category" method="post">
+					<input type="hidden" name="parent" value="{}">
+					<label for="category_name">New category:</label><input type="text" id="category_name" name="name" class="editor_input" required><input type="submit" class="editor_submit" value="Create">
+				</form>"#,
+		parent.map_or_else(String::new, |id| id.to_string())
+	)
+}
+
+/// List of links to categories
+fn category_list(config: &Config, categories: &[database::Category]) -> String {
+	let base = config.network.base_path.as_str();
+	let items: Vec<String> = categories
+		.iter()
+		.map(|category| {
+			format!(
+				r#"<li><a href="{base}/category/{}">{}</a></li>"#,
+				category.id,
+				html_escape(&category.name)
+			)
+		})
+		.collect();
+	format!("<ul class=\"category_list\">\n{}\n</ul>", items.join("\n"))
+}
+
+/// The top-level categories
+async fn categories_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let categories = db.lock().await.get_categories(None);
+	let content = match categories {
+		Some(categories) if categories.is_empty() => {
+			"<p>There are no categories yet.</p>".to_string()
+		}
+		Some(categories) => category_list(&config, &categories),
+		None => "<p>Could not fetch the categories.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Categories</h2>
+				{}
+				{}
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, Some(MenuItem::Categories)),
+		content,
+		category_create_form(&config, None)
+	);
+	Ok(warp::reply::html(doc.to_html()))
 }
 
-const MAIN_STYLE: &str = include_str!("css/main_style.css");
-const GITHUB_MARKDOWN: &str = include_str!("css/github_markdown.css");
+/// A category with its sub-categories and articles
+async fn category_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	id: ItemId,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let mut db = db.lock().await;
+	let Some(category) = db.get_category(id) else {
+		return Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			&format!("Could not find category #{}!", id),
+			warp::http::StatusCode::NOT_FOUND,
+		));
+	};
+	let path = db.get_category_path(id).unwrap_or_default();
+	let subcategories = match db.get_categories(Some(id)) {
+		Some(categories) if categories.is_empty() => String::new(),
+		Some(categories) => format!(
+			"<h3>Sub-categories</h3>\n{}",
+			category_list(&config, &categories)
+		),
+		None => "<p>Could not fetch the sub-categories.</p>".to_string(),
+	};
+	let articles = match db.get_articles_in_category(id) {
+		Some(articles) if articles.is_empty() => String::new(),
+		Some(articles) => format!(
+			"<h3>Articles</h3>\n{}",
+			generate_articles_list(&config, &articles, None)
+		),
+		None => "<p>Could not fetch the articles.</p>".to_string(),
+	};
+	let parent_options = category_options(&mut db, category.parent, Some(id));
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+{}				<h2 style="margin-top: 0px;">{}</h2>
+				{}
+				{}
+				{}
+				<form action="{base}/move/category/{}" method="post">
+					<label for="category_parent">Move into:</label><select id="category_parent" name="parent"><option value="">(top level)</option>{}</select><input type="submit" class="editor_submit" value="Move">
+				</form>
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, Some(MenuItem::Categories)),
+		category_breadcrumb(&config, &path, false),
+		html_escape(&category.name),
+		subcategories,
+		articles,
+		category_create_form(&config, Some(id)),
+		id,
+		parent_options
+	);
+	Ok(warp::reply::html(doc.to_html()).into_response())
+}
 
-const REDWOOD_OBS: &str = r#"<img style="width: 112px; height: 112px;" src="/img/redwood_observatory_medium.png" alt="Redwood Observatory">"#;
+async fn category_create_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let name = param_map.get("name").map_or("", |name| name.trim());
+	if name.is_empty() {
+		return Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			"The name of a category must not be empty.",
+			warp::http::StatusCode::BAD_REQUEST,
+		));
+	}
+	let Ok(parent) = parse_optional_id(param_map.get("parent").map_or("", String::as_str)) else {
+		return Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			"The parent category is not valid.",
+			warp::http::StatusCode::BAD_REQUEST,
+		));
+	};
+	let result = db.lock().await.create_category(name, parent);
+	match result {
+		Ok(id) => Ok(
+			warp::redirect::see_other(site_uri(&config, &format!("/category/{}", id)))
+				.into_response(),
+		),
+		Err(err) => Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			&format!("Could not create the category: {}.", err),
+			match err {
+				database::DbError::NotFound => warp::http::StatusCode::NOT_FOUND,
+				_ => warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			},
+		)),
+	}
+}
+
+async fn category_move_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	id: ItemId,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let Ok(parent) = parse_optional_id(param_map.get("parent").map_or("", String::as_str)) else {
+		return Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			"The parent category is not valid.",
+			warp::http::StatusCode::BAD_REQUEST,
+		));
+	};
+	let result = db.lock().await.move_category(id, parent);
+	match result {
+		Ok(()) => Ok(
+			warp::redirect::see_other(site_uri(&config, &format!("/category/{}", id)))
+				.into_response(),
+		),
+		Err(err) => Ok(category_error_page(
+			&config,
+			user.as_ref(),
+			&format!("Could not move the category: {}.", err),
+			match err {
+				database::DbError::NotFound => warp::http::StatusCode::NOT_FOUND,
+				database::DbError::Cycle => warp::http::StatusCode::CONFLICT,
+				_ => warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			},
+		)),
+	}
+}
+
+/// Line with links to the listing pages of the tags, empty without tags
+fn tag_links(config: &Config, tags: &[String]) -> String {
+	if tags.is_empty() {
+		return String::new();
+	}
+	let links: Vec<String> = tags
+		.iter()
+		.map(|tag| {
+			format!(
+				r#"<a href="{}" class="tag">{}</a>"#,
+				html_escape(&config.url(&urls::tag_path(tag))),
+				html_escape(tag)
+			)
+		})
+		.collect();
+	format!(
+		"\t\t\t\t<p class=\"article_tags\">Tags: {}</p>\n",
+		links.join(" ")
+	)
+}
+
+/// List the articles with a tag
+async fn tag_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	tag: String,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let tag = percent_encoding::percent_decode_str(&tag)
+		.decode_utf8_lossy()
+		.into_owned();
+	let tag = tags::normalize(&tag).unwrap_or_default();
+	let articles = db.lock().await.get_articles_by_tag(&tag);
+	let content = match articles {
+		Some(articles) if articles.is_empty() => {
+			format!("<p>No articles are tagged \"{}\".</p>", html_escape(&tag))
+		}
+		Some(articles) => generate_articles_list(&config, &articles, None),
+		None => "<p>Could not fetch the articles.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Tag "{}"</h2>
+				{}
+				<p><a href="{base}/admin/tags">All tags</a></p>
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, None),
+		html_escape(&tag),
+		content
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+fn article_not_found_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number: ItemId,
+) -> warp::reply::Html<String> {
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::GITHUB_MARKDOWN.text());
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>Could not find article #{}!</p>
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, None, None),
+		article_number
+	);
+	warp::reply::html(doc.to_html())
+}
+
+/// Ask for confirmation before deleting an article
+async fn article_delete_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let mut db = db.lock().await;
+	let Some(article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	else {
+		return Ok(article_not_found_page(
+			&config,
+			user.as_ref(),
+			article_number,
+		));
+	};
+	let config_refs = config_refs::Registry::resolve(&config, &mut db);
+	let config_note = config_refs_note(
+		config_refs.refs_to(article_number),
+		"Features using it stop working while it is deleted.",
+	);
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Delete "{}"?</h2>
+				<p>The article #{} will be moved to the <a href="{base}/trash">trash</a>, where it can be restored. Until then, references to it are shown as deleted.</p>
+{}				<form action="{base}/delete/article/{}" method="post">
+					<input type="submit" class="editor_submit" value="Delete article">
+				</form>
+			</div>
+		</main>
+"####,
+		generate_menu(
+			&config,
+			user.as_ref(),
+			Some(article_number),
+			Some(MenuItem::DeleteArticle)
+		),
+		html_escape(&article.title),
+		article_number,
+		config_note,
+		article_number
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// Warning that the configuration refers to an article, listing the
+/// configuration keys, empty if it does not
+fn config_refs_note<'a>(
+	refs: impl Iterator<Item = &'a config_refs::ConfigRef>,
+	consequence: &str,
+) -> String {
+	let keys: Vec<String> = refs
+		.map(|config_ref| format!("<code>{}</code>", html_escape(&config_ref.key)))
+		.collect();
+	if keys.is_empty() {
+		return String::new();
+	}
+	format!(
+		"\t\t\t\t<p class=\"config_note\">This article is configured as {}. {}</p>\n",
+		keys.join(", "),
+		consequence
+	)
+}
+
+async fn article_delete_page_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let result = db.lock().await.delete_article(article_number);
+	match result {
+		Ok(()) => Ok(warp::redirect::see_other(site_uri(&config, "/articles")).into_response()),
+		Err(database::DbError::NotFound) => {
+			Ok(article_not_found_page(&config, user.as_ref(), article_number).into_response())
+		}
+		Err(_) => {
+			let mut doc = HtmlDocument::new(&config);
+			doc.styles.push(assets::MAIN_STYLE.text());
+			doc.body = format!(
+				r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>
+					Could not delete article #{}.
+				</p>
+			</div>
+		</main>
+"####,
+				generate_menu(&config, user.as_ref(), Some(article_number), None),
+				article_number
+			);
+			Ok(warp::reply::with_status(
+				warp::reply::html(doc.to_html()),
+				warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			)
+			.into_response())
+		}
+	}
+}
+
+/// List the deleted articles, each with a button to restore it
+async fn trash_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let base = config.network.base_path.as_str();
+	let deleted = db.lock().await.get_deleted_articles();
+	let content = match deleted {
+		Some(deleted) if deleted.is_empty() => "<p>The trash is empty.</p>".to_string(),
+		Some(deleted) => {
+			let items: Vec<String> = deleted
+				.iter()
+				.map(|(id, title, date_deleted)| {
+					format!(
+						r#"<li>{} <span class="trash_date">deleted {}</span> <form action="{base}/restore/article/{}" method="post" class="inline_form"><input type="submit" class="editor_submit" value="Restore"></form></li>"#,
+						html_escape(title),
+						config.format_timestamp(*date_deleted),
+						id
+					)
+				})
+				.collect();
+			format!("<ul>\n{}\n</ul>", items.join("\n"))
+		}
+		None => "<p>Could not fetch the deleted articles.</p>".to_string(),
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Trash</h2>
+				{}
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, Some(MenuItem::Trash)),
+		content
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+async fn article_restore_page_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let result = db.lock().await.restore_article(article_number);
+	match result {
+		Ok(()) => Ok(warp::redirect::see_other(site_uri(
+			&config,
+			&format!("/article/{}", article_number),
+		))
+		.into_response()),
+		Err(database::DbError::NotFound) => {
+			Ok(article_not_found_page(&config, user.as_ref(), article_number).into_response())
+		}
+		Err(_) => {
+			let mut doc = HtmlDocument::new(&config);
+			doc.styles.push(assets::MAIN_STYLE.text());
+			doc.body = format!(
+				r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>
+					Could not restore article #{}.
+				</p>
+			</div>
+		</main>
+"####,
+				generate_menu(&config, user.as_ref(), None, None),
+				article_number
+			);
+			Ok(warp::reply::with_status(
+				warp::reply::html(doc.to_html()),
+				warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			)
+			.into_response())
+		}
+	}
+}
+
+#[derive(serde::Deserialize)]
+struct RevisionRestoreForm {
+	revision: String,
+}
+
+/// Save the title and text of an older revision as a new revision
+async fn revision_restore_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	article_number: ItemId,
+	form: RevisionRestoreForm,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	use warp::http::StatusCode;
+
+	let Ok(revision) = form.revision.parse::<i64>() else {
+		return Ok(revision_error_page(
+			&config,
+			user.as_ref(),
+			article_number,
+			&format!("\"{}\" is no revision number.", form.revision),
+			StatusCode::BAD_REQUEST,
+		));
+	};
+	let mut db = db.lock().await;
+	let Some(article) = db
+		.get_article(article_number)
+		.map_err(warp::reject::custom)?
+	else {
+		return Ok(article_not_found_page(&config, user.as_ref(), article_number).into_response());
+	};
+	if revision == article.revision {
+		return Ok(revision_error_page(
+			&config,
+			user.as_ref(),
+			article_number,
+			&format!("Revision {} is the current one already.", revision),
+			StatusCode::CONFLICT,
+		));
+	}
+	let (Some(title), Some(text)) = (
+		db.get_revision_title(article_number, revision),
+		db.get_revision_text(article_number, revision),
+	) else {
+		return Ok(revision_error_page(
+			&config,
+			user.as_ref(),
+			article_number,
+			&format!("The text of revision {} is not known.", revision),
+			StatusCode::NOT_FOUND,
+		));
+	};
+	// Another article may have taken the old title since
+	if let Some(owner) = db.get_title_owner(&title) {
+		if owner != article_number {
+			return Ok(revision_error_page(
+				&config,
+				user.as_ref(),
+				article_number,
+				&format!(
+					"The title \"{}\" of revision {} belongs to article #{} now.",
+					title, revision, owner
+				),
+				StatusCode::CONFLICT,
+			));
+		}
+	}
+	let summary = format!("Restored revision {}", revision);
+	let title = (title != article.title).then_some(title.as_str());
+	if db
+		.update_article(article_number, title, Some(&text), Some(&summary), None)
+		.is_err()
+	{
+		return Ok(revision_error_page(
+			&config,
+			user.as_ref(),
+			article_number,
+			&format!("Could not restore revision {}.", revision),
+			warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+		));
+	}
+	save_links(&mut db, &config, article_number, &text);
+	run_save_hooks(&mut db, &config, article_number);
+	Ok(warp::redirect::see_other(site_uri(
+		&config,
+		&urls::article_path(article_number, &article.slug),
+	))
+	.into_response())
+}
+
+/// Page about a revision that could not be restored
+fn revision_error_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number: ItemId,
+	message: &str,
+	status: warp::http::StatusCode,
+) -> warp::reply::Response {
+	let base = config.network.base_path.as_str();
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>{}</p>
+				<p><a href="{base}/history/article/{}">Back to the history</a></p>
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, Some(article_number), None),
+		html_escape(message),
+		article_number
+	);
+	warp::reply::with_status(warp::reply::html(doc.to_html()), status).into_response()
+}
+
+async fn search_page_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	log::trace!("Article search post request: {:?}", param_map);
+
+	let empty_string = String::new();
+	let search_term = param_map.get("search_term_plain").unwrap_or(&empty_string);
+	let view = SearchView::Overview {
+		limit: config.search.max_results_per_category,
+	};
+	Ok(warp::reply::html(
+		search_results_page(db, &config, search_term, view, user).await?,
+	))
+}
+
+async fn search_page_get(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let limit = config.search.max_results_per_category;
+	if let Some(search_term) = param_map.get("search_term_plain") {
+		let view = match param_map
+			.get("category")
+			.and_then(|name| MatchCategory::from_query_name(name))
+		{
+			Some(category) => SearchView::Category {
+				category,
+				page: param_map
+					.get("page")
+					.and_then(|page| page.parse().ok())
+					.unwrap_or(1),
+				limit,
+			},
+			None => SearchView::Overview { limit },
+		};
+		Ok(warp::reply::html(
+			search_results_page(db, &config, search_term, view, user).await?,
+		))
+	} else {
+		//TODO: Add search page
+		Ok(warp::reply::html(
+			"Search page not yet implemented".to_string(),
+		))
+	}
+}
+
+async fn search_results_page(
+	db: Arc<DbLock>,
+	config: &Config,
+	search_term: &str,
+	view: SearchView,
+	user: Option<auth::User>,
+) -> Result<String, warp::Rejection> {
+	let articles: Vec<Article> = {
+		let mut db = db.lock().await;
+		// The full-text index ranks the results, the LIKE search is the
+		// fallback for databases without it and for terms without words
+		if db.has_fts() && sql_util::fts_query(search_term).is_some() {
+			db.search_articles_fts(search_term)
+				.map_err(warp::reject::custom)?
+				.into_iter()
+				.map(|hit| {
+					log::trace!("Search hit {} with rank {}", hit.article.id, hit.rank);
+					hit.article
+				})
+				.collect()
+		} else {
+			db.search_articles(search_term)
+				.map_err(warp::reject::custom)?
+		}
+	};
+
+	//TODO: Unify with generate_articles_list elsewhere. Have one unique way to show article lists.
+	let results = search::categorize(&articles, search_term);
+
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Articles</h2>
+				{}
+			</div>
+		</main>
+"#,
+		generate_menu(config, user.as_ref(), None, None),
+		search::render_results(&config.network.base_path, &results, search_term, view)
+	);
+	Ok(doc.to_html())
+}
+
+//<div contenteditable="true"></div>
+//<style type=text/css>body { max-width: 800px; margin: auto; }</style>
+
+async fn index_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	last_visit_cookie: Option<String>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let mut db = db.lock().await;
+	if db.count_articles().map_err(warp::reject::custom)? == 0 {
+		return Ok(setup_page(&config, user.as_ref()));
+	}
+	let new_articles = last_visit(&config, last_visit_cookie.as_deref())
+		.and_then(|visit| db.count_articles_modified_after(visit));
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">{}</h2>
+			</div>
+		</main>
+"#,
+		generate_menu_with_new(
+			&config,
+			user.as_ref(),
+			None,
+			Some(MenuItem::Home),
+			new_articles
+		),
+		html_escape(&config.wiki.name)
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// Time of the last visit of the article list, if new articles are
+/// marked at all
+fn last_visit(config: &Config, cookie: Option<&str>) -> Option<UtcTimestamp> {
+	if !config.listing.new_markers {
+		return None;
+	}
+	cookie.and_then(|cookie| visits::parse_cookie(cookie, UtcTimestamp::now()))
+}
+
+/// Shown instead of the index page as long as there are no articles
+fn setup_page(config: &Config, user: Option<&auth::User>) -> warp::reply::Html<String> {
+	let base = config.network.base_path.as_str();
+	let config_path = config.file_path.as_ref().map_or_else(
+		|| "No configuration file is in use.".to_string(),
+		|path| {
+			format!(
+				"The configuration is read from <code>{}</code>.",
+				html_escape(&path.display().to_string())
+			)
+		},
+	);
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Welcome to {}</h2>
+				<p>This wiki does not have any articles yet. Start with a home article created from a template that shows the basics of writing articles.</p>
+				<form action="{base}/setup" method="post">
+					<input type="checkbox" id="examples" name="examples" value="on" checked>
+					<label for="examples">Also create a few example articles</label>
+					<input type="submit" class="editor_submit" value="Create home article">
+				</form>
+				<p>{}</p>
+			</div>
+		</main>
+"#,
+		generate_menu(config, user, None, Some(MenuItem::Home)),
+		html_escape(&config.wiki.name),
+		config_path
+	);
+	warp::reply::html(doc.to_html())
+}
+
+/// Create the starter articles, only if there are no articles yet
+async fn setup_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let mut db = db.lock().await;
+	if db.count_articles().map_err(warp::reject::custom)? != 0 {
+		return Ok(warp::redirect::see_other(site_uri(&config, "/")));
+	}
+	let with_examples = param_map.get("examples").map(String::as_str) == Some("on");
+	match starter::create(&mut db, with_examples) {
+		Some(id) => Ok(warp::redirect::see_other(site_uri(
+			&config,
+			&format!("/article/{}", id),
+		))),
+		None => Err(warp::reject::not_found()),
+	}
+}
+
+/// Form to log in with the credentials of the `[auth]` section
+async fn login_page(
+	config: Arc<Config>,
+	user: Option<auth::User>,
+	query: HashMap<String, String>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	if config.auth.is_none() {
+		return Err(warp::reject::not_found());
+	}
+	let next = login_target(&config, query.get("next"));
+	Ok(login_form(
+		&config,
+		user.as_ref(),
+		&next,
+		None,
+		warp::http::StatusCode::OK,
+	))
+}
+
+/// Where to go after logging in, only paths on this wiki so that the
+/// login page cannot send anyone to another site
+fn login_target(config: &Config, next: Option<&String>) -> String {
+	match next {
+		Some(next)
+			if next.starts_with('/')
+				&& !next.starts_with("//")
+				&& !next.contains('\\')
+				&& config.url(next).parse::<warp::http::Uri>().is_ok() =>
+		{
+			next.clone()
+		}
+		_ => "/".to_string(),
+	}
+}
+
+fn login_form(
+	config: &Config,
+	user: Option<&auth::User>,
+	next: &str,
+	error: Option<&str>,
+	status: warp::http::StatusCode,
+) -> warp::reply::Response {
+	let base = config.network.base_path.as_str();
+	let note = match (error, user) {
+		(Some(error), _) => format!("<p><strong>{}</strong></p>", html_escape(error)),
+		(None, Some(user)) => format!("<p>You are logged in as {}.</p>", html_escape(&user.name)),
+		(None, None) => String::new(),
+	};
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Login</h2>
+				{}
+				<form action="{base}/login" method="post">
+					<input type="hidden" name="next" value="{}">
+					<p><label for="username">Username:</label> <input type="text" id="username" name="username" autocomplete="username" required autofocus></p>
+					<p><label for="password">Password:</label> <input type="password" id="password" name="password" autocomplete="current-password" required></p>
+					<input type="submit" class="editor_submit" value="Login">
+				</form>
+			</div>
+		</main>
+"#,
+		generate_menu(config, user, None, Some(MenuItem::Login)),
+		note,
+		html_escape(next)
+	);
+	warp::reply::with_status(warp::reply::html(doc.to_html()), status).into_response()
+}
+
+/// Start a session with the credentials of the login form. The password
+/// hash is slow to check on purpose, so it is checked on a blocking
+/// thread.
+async fn login_post(
+	config: Arc<Config>,
+	param_map: HashMap<String, String>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let auth = config.auth.as_ref().ok_or_else(warp::reject::not_found)?;
+	let next = login_target(&config, param_map.get("next"));
+	let username = param_map.get("username").cloned().unwrap_or_default();
+	let password = param_map.get("password").cloned().unwrap_or_default();
+	let accepted = {
+		let config = config.clone();
+		let username = username.clone();
+		tokio::task::spawn_blocking(move || {
+			config.auth.as_ref().is_some_and(|auth| {
+				auth::verify_credentials(auth, username.as_bytes(), password.as_bytes())
+			})
+		})
+		.await
+		.unwrap_or(false)
+	};
+	if !accepted {
+		log::info!("Failed login as {:?}", username);
+		return Ok(login_form(
+			&config,
+			None,
+			&next,
+			Some("Wrong username or password."),
+			warp::http::StatusCode::UNAUTHORIZED,
+		));
+	}
+	let cookie = auth::session_cookie(&config, auth, &auth::User { name: username });
+	Ok(warp::reply::with_header(
+		warp::redirect::see_other(site_uri(&config, &next)),
+		"Set-Cookie",
+		cookie,
+	)
+	.into_response())
+}
+
+/// End the session by replacing the cookie with an expired one
+async fn logout_post(config: Arc<Config>) -> Result<impl warp::Reply, warp::Rejection> {
+	Ok(warp::reply::with_header(
+		warp::redirect::see_other(site_uri(&config, "/")),
+		"Set-Cookie",
+		auth::logout_cookie(&config),
+	))
+}
+
+use warp::http::response::Response;
+
+fn favicon_response(icon: Option<favicon::Icon>) -> warp::reply::Response {
+	let (content_type, body) = match icon {
+		Some(icon) => (icon.content_type, icon.data),
+		None => (
+			"image/svg+xml".to_string(),
+			favicon::GENERIC_ICON.as_bytes().to_vec(),
+		),
+	};
+	Response::builder()
+		.status(200)
+		.header("Content-Type", content_type)
+		.header("Cache-Control", "public, max-age=86400")
+		.header("X-Content-Type-Options", "nosniff")
+		.header("Content-Security-Policy", "default-src 'none'")
+		.body(body.into())
+		.unwrap()
+}
+
+/// Serve the icon of an external site, fetched once and then cached.
+/// Any failure results in the generic icon.
+async fn favicon_api(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	limiter: Arc<favicon::RateLimiter>,
+	query: HashMap<String, String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	if !config.external_link_icons() {
+		return Err(warp::reject::not_found());
+	}
+	let host = match query.get("host").map(|host| favicon::parse_host(host)) {
+		Some(Ok(host)) => host,
+		_ => return Ok(favicon_response(None)),
+	};
+	// `example.com:443` is fetched over HTTPS, it may have another icon
+	let key = host.to_string();
+
+	let fetched_after = UtcTimestamp::now() - chrono::Duration::days(favicon::CACHE_TTL_DAYS);
+	if let Some(icon) = db.lock().await.get_favicon(&key, fetched_after) {
+		return Ok(favicon_response(icon));
+	}
+	if !limiter.try_acquire() {
+		log::debug!("Favicon rate limit reached, not fetching {}", key);
+		return Ok(favicon_response(None));
+	}
+
+	// The database is not locked while fetching
+	let icon = match favicon::fetch(&host).await {
+		Ok(icon) => Some(icon),
+		Err(err) => {
+			log::debug!("Could not fetch favicon of {}: {}", key, err);
+			None
+		}
+	};
+	db.lock()
+		.await
+		.store_favicon(&key, icon.as_ref(), favicon::MAX_CACHE_ENTRIES);
+	Ok(favicon_response(icon))
+}
+
+/// Upper limit of `?per_page=` on the article list
+const MAX_ARTICLES_PER_PAGE: u32 = 1000;
+
+/// Page of the article list, from the query parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ArticlesPage {
+	/// Starting at 1
+	page: u32,
+	per_page: u32,
+	order: ArticleOrder,
+}
+
+impl ArticlesPage {
+	/// Invalid parameters fall back to the defaults
+	fn from_query(query: &HashMap<String, String>, config: &Config) -> ArticlesPage {
+		let number = |name: &str| query.get(name).and_then(|value| value.parse::<u32>().ok());
+		ArticlesPage {
+			page: number("page").unwrap_or(1).max(1),
+			per_page: number("per_page")
+				.unwrap_or(config.listing.per_page)
+				.clamp(1, MAX_ARTICLES_PER_PAGE),
+			order: match query.get("order").map(String::as_str) {
+				Some("title") => ArticleOrder::Title,
+				Some("modified") => ArticleOrder::Modified,
+				_ => ArticleOrder::Id,
+			},
+		}
+	}
+
+	fn offset(&self) -> u32 {
+		(self.page - 1).saturating_mul(self.per_page)
+	}
+
+	/// Link to this page, leaving out parameters that have their
+	/// default value
+	fn url(&self, config: &Config) -> String {
+		use std::fmt::Write;
+		let mut url = config.url(&format!("/articles?page={}", self.page));
+		if self.per_page != config.listing.per_page {
+			write!(url, "&amp;per_page={}", self.per_page).unwrap();
+		}
+		match self.order {
+			ArticleOrder::Id => {}
+			ArticleOrder::Title => url.push_str("&amp;order=title"),
+			ArticleOrder::Modified => url.push_str("&amp;order=modified"),
+		}
+		url
+	}
+}
+
+/// Links to the other orders of the article list, and to the previous
+/// and next page
+fn articles_page_navigation(
+	config: &Config,
+	current: ArticlesPage,
+	total: u32,
+) -> (String, String) {
+	use std::fmt::Write;
+	let orders = [
+		(ArticleOrder::Id, "by ID"),
+		(ArticleOrder::Title, "by title"),
+		(ArticleOrder::Modified, "recently changed"),
+	]
+	.iter()
+	.map(|&(order, label)| {
+		if order == current.order {
+			format!("<b>{}</b>", label)
+		} else {
+			let page = ArticlesPage {
+				page: 1,
+				order,
+				..current
+			};
+			format!("<a href=\"{}\">{}</a>", page.url(config), label)
+		}
+	})
+	.collect::<Vec<_>>()
+	.join(" | ");
+	let orders = format!("<p class=\"list_order\">Order: {}</p>", orders);
+
+	let page_count = total.div_ceil(current.per_page).max(1);
+	if page_count == 1 {
+		return (orders, String::new());
+	}
+	let mut pages = String::from("<nav aria-label=\"Pages\" class=\"pagination\">");
+	if current.page > 1 {
+		let previous = ArticlesPage {
+			page: (current.page - 1).min(page_count),
+			..current
+		};
+		write!(
+			pages,
+			"<a href=\"{}\" rel=\"prev\">&larr; Previous</a> ",
+			previous.url(config)
+		)
+		.unwrap();
+	}
+	write!(pages, "Page {} of {}", current.page, page_count).unwrap();
+	if current.page < page_count {
+		let next = ArticlesPage {
+			page: current.page + 1,
+			..current
+		};
+		write!(
+			pages,
+			" <a href=\"{}\" rel=\"next\">Next &rarr;</a>",
+			next.url(config)
+		)
+		.unwrap();
+	}
+	pages.push_str("</nav>");
+	(orders, pages)
+}
+
+async fn articles_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	last_visit_cookie: Option<String>,
+	query: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	// Taken before reading the articles, so changes made while the
+	// list is rendered are still new on the next visit
+	let now = UtcTimestamp::now();
+	let last_visit = last_visit(&config, last_visit_cookie.as_deref());
+	let page = ArticlesPage::from_query(&query, &config);
+	let mut db = db.lock().await;
+	let (articles, total) = db
+		.get_articles_page(page.offset(), page.per_page, page.order)
+		.map_err(warp::reject::custom)?;
+	let new_articles = last_visit.and_then(|visit| db.count_articles_modified_after(visit));
+	let (orders, pages) = articles_page_navigation(&config, page, total);
+	let list = if !articles.is_empty() {
+		generate_articles_list(&config, &articles, last_visit)
+	} else if total == 0 {
+		"<p>There are no articles yet.</p>".to_string()
+	} else {
+		format!(
+			"<p>There are no articles on page {}. <a href=\"{}\">Go to the first page</a></p>",
+			page.page,
+			ArticlesPage { page: 1, ..page }.url(&config)
+		)
+	};
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Articles</h2>
+				{}
+				{}
+				{}
+			</div>
+		</main>
+"#,
+		generate_menu_with_new(
+			&config,
+			user.as_ref(),
+			None,
+			Some(MenuItem::AllArticles),
+			new_articles
+		),
+		orders,
+		list,
+		pages
+	);
+	let reply = warp::reply::html(doc.to_html());
+	if config.listing.new_markers {
+		Ok(warp::reply::with_header(reply, "Set-Cookie", visits::cookie(now)).into_response())
+	} else {
+		Ok(reply.into_response())
+	}
+}
+
+async fn article_create_page_post(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	confirm_key: Arc<similar_titles::ConfirmKey>,
+	param_map: HashMap<String, String>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let mut db = db.lock().await;
+
+	log::trace!("Article create post request: {:?}", param_map);
+
+	let title = match param_map.get("article_title") {
+		Some(title) if !title.trim().is_empty() => title.trim(),
+		Some(_) => {
+			return Ok(bad_form_page(
+				&config,
+				user.as_ref(),
+				"The title of the new article is empty.",
+			));
+		}
+		None => {
+			return Ok(bad_form_page(
+				&config,
+				user.as_ref(),
+				"The form lacks the field article_title with the title of the new article.",
+			));
+		}
+	};
+	// Also taken by deleted articles, see `Database::get_title_owner`
+	if db.get_title_owner(title).is_some() {
+		return Ok(duplicate_title_page(&mut db, user.as_ref(), &config, title).into_response());
+	}
+	// Resubmitted from the page of similar titles
+	let confirmed = param_map
+		.get("confirm")
+		.is_some_and(|token| confirm_key.verify(title, token));
+	if !confirmed {
+		let titles = db.get_all_titles().unwrap_or_default();
+		let candidates = similar_titles::candidates(title, &titles);
+		if !candidates.is_empty() {
+			return Ok(similar_titles_page(
+				&config,
+				user.as_ref(),
+				&confirm_key,
+				title,
+				&candidates,
+			)
+			.into_response());
+		}
+	}
+
+	let art = Article {
+		id: 0.into(),
+		title: title.to_string(),
+		text: "".to_string(),
+		date_created: UtcTimestamp::now(),
+		date_modified: UtcTimestamp::now(),
+		revision: 0,
+		slug: String::new(), // Generated from the title by the database
+	};
+
+	match db.create_article(&art) {
+		Ok(id) => {
+			Ok(warp::redirect(site_uri(&config, &format!("/article/{}", id))).into_response())
+		}
+		Err(CreateArticleError::DuplicateTitle) => {
+			Ok(duplicate_title_page(&mut db, user.as_ref(), &config, title).into_response())
+		}
+		Err(err) => {
+			let mut doc = HtmlDocument::new(&config);
+			doc.styles.push(assets::MAIN_STYLE.text());
+			doc.body = format!(
+				r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<p>
+					Could not create the article: {}.
+				</p>
+			</div>
+		</main>
+"####,
+				generate_menu(&config, user.as_ref(), None, Some(MenuItem::CreateArticle)),
+				html_escape(&err.to_string())
+			);
+			Ok(warp::reply::with_status(
+				warp::reply::html(doc.to_html()),
+				warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+			)
+			.into_response())
+		}
+	}
+}
+
+/// The title of a new article belongs to an existing one, which is
+/// linked. The form is shown again to pick another title.
+fn duplicate_title_page(
+	db: &mut Database,
+	user: Option<&auth::User>,
+	config: &Config,
+	title: &str,
+) -> warp::reply::WithStatus<warp::reply::Html<String>> {
+	let base = config.network.base_path.as_str();
+	let existing = db.get_title_owner(title).and_then(|id| {
+		let (title, deleted) = db.get_article_title_include_deleted(id)?;
+		Some(if deleted {
+			format!(
+				"The article <a href=\"{base}/trash\">{}</a> in the trash already has this title.",
+				html_escape(&title)
+			)
+		} else {
+			format!(
+				"The article <a href=\"{}\">{}</a> already has this title.",
+				html_escape(&config.url(&urls::article_path(id, &slug::slugify(&title)))),
+				html_escape(&title)
+			)
+		})
+	});
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">The title "{}" is taken</h2>
+				<p>{}</p>
+				{}
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, None, Some(MenuItem::CreateArticle)),
+		html_escape(title),
+		existing.unwrap_or_else(|| "Another article already has this title.".to_string()),
+		create_article_form(config, title)
+	);
+	warp::reply::with_status(
+		warp::reply::html(doc.to_html()),
+		warp::http::StatusCode::CONFLICT,
+	)
+}
+
+/// Asks whether one of the articles with a title similar to `title` was
+/// meant, with a button to create the article anyway
+fn similar_titles_page(
+	config: &Config,
+	user: Option<&auth::User>,
+	confirm_key: &similar_titles::ConfirmKey,
+	title: &str,
+	candidates: &[&(ItemId, String)],
+) -> warp::reply::Html<String> {
+	let base = config.network.base_path.as_str();
+	let list: String = candidates
+		.iter()
+		.map(|(id, title)| {
+			format!(
+				"<li><a href=\"{}\">{}</a></li>\n",
+				html_escape(&config.url(&urls::article_path(*id, &slug::slugify(title)))),
+				html_escape(title)
+			)
+		})
+		.collect();
+	let mut doc = HtmlDocument::new(config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">Did you mean one of these existing articles?</h2>
+				<p>Their titles are similar to "{}":</p>
+				<ul>
+{}				</ul>
+				<form action="{base}/create/article" method="post">
+					<input type="hidden" name="article_title" value="{}">
+					<input type="hidden" name="confirm" value="{}">
+					<input type="submit" class="editor_submit" value="Create &quot;{}&quot; anyway">
+				</form>
+			</div>
+		</main>
+"####,
+		generate_menu(config, user, None, Some(MenuItem::CreateArticle)),
+		html_escape(title),
+		list,
+		attribute_escape(title),
+		confirm_key.token(title),
+		attribute_escape(title)
+	);
+	warp::reply::html(doc.to_html())
+}
+
+/// Redirect to the journal entry of today, creating it from the
+/// template article first if it does not exist yet
+async fn today_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let journal = config
+		.journal
+		.as_ref()
+		.ok_or_else(warp::reject::not_found)?;
+	let today = journal::date_in(config.appearance.timezone.local_time(UtcTimestamp::now()));
+	let title = journal::entry_title(journal, today).ok_or_else(warp::reject::not_found)?;
+	let redirect =
+		|id: ItemId| warp::redirect::see_other(site_uri(&config, &format!("/article/{}", id)));
+
+	let mut db = db.lock().await;
+	if let Some(id) = db
+		.get_article_id_by_title(&title)
+		.map_err(warp::reject::custom)?
+	{
+		return Ok(redirect(id));
+	}
+	let text = journal
+		.template_article
+		.and_then(|id| db.get_article(id.into()).ok().flatten())
+		.map(|article| article.text)
+		.unwrap_or_default();
+	let article = Article {
+		id: 0.into(),
+		title,
+		text,
+		date_created: UtcTimestamp::now(),
+		date_modified: UtcTimestamp::now(),
+		revision: 0,
+		slug: String::new(), // Generated from the title by the database
+	};
+	match db.create_article(&article) {
+		Ok(id) => {
+			save_links(&mut db, &config, id, &article.text);
+			run_save_hooks(&mut db, &config, id);
+			Ok(redirect(id))
+		}
+		// Another process created the entry in the meantime
+		Err(err) => match db
+			.get_article_id_by_title(&article.title)
+			.map_err(warp::reject::custom)?
+		{
+			Some(id) => Ok(redirect(id)),
+			None => {
+				log::error!(
+					"Could not create journal entry {:?}: {}",
+					article.title,
+					err
+				);
+				Err(warp::reject::custom(database::DbError::from(err)))
+			}
+		},
+	}
+}
+
+async fn article_create_page(
+	_db: Arc<DbLock>,
+	config: Arc<Config>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r####"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				{}
+			</div>
+		</main>
+"####,
+		generate_menu(&config, user.as_ref(), None, Some(MenuItem::CreateArticle)),
+		create_article_form(&config, "")
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// The form that creates an article, with the title filled in
+fn create_article_form(config: &Config, title: &str) -> String {
+	let base = config.network.base_path.as_str();
+	format!(
+		r#"<form action="{base}/create/article" method="post">
+					<label for="article_title">Title:</label><input type="text" id="article_title" name="article_title" class="editor_input" value="{}">
+					<input type="submit" class="editor_submit" value="Create">
+				</form>"#,
+		html_escape(title)
+	)
+}
+
+/// Atom feed of the most recently modified articles
+async fn feed_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let entries = {
+		let mut db = db.lock().await;
+		db.get_recently_modified(feed::ENTRIES).map(|articles| {
+			articles
+				.into_iter()
+				.map(|article| feed::Entry {
+					edit_summary: db
+						.get_edit_summary(article.id, article.revision)
+						.unwrap_or_default(),
+					article,
+				})
+				.collect::<Vec<_>>()
+		})
+	};
+	let Some(entries) = entries else {
+		return Ok(warp::reply::with_status(
+			"Could not read the articles\n",
+			warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+		)
+		.into_response());
+	};
+	let feed = feed::atom(
+		&entries,
+		&config.wiki.name,
+		config.network.public_url.as_deref(),
+		&config.network.base_path,
+		UtcTimestamp::now(),
+	);
+	Ok(warp::reply::with_header(
+		feed,
+		warp::http::header::CONTENT_TYPE,
+		"application/atom+xml",
+	)
+	.into_response())
+}
+
+/// Sitemap of all articles, or the sitemap index if there are more
+/// than fit into one sitemap
+async fn sitemap_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let Some(public_url) = config.network.public_url.as_deref() else {
+		return Ok(sitemap_unavailable());
+	};
+	let mut db = db.lock().await;
+	let Ok(count) = db.count_articles() else {
+		return Ok(sitemap_error());
+	};
+	let parts = sitemap::parts(count);
+	if parts > 1 {
+		return Ok(sitemap_response(sitemap::index(parts, public_url)));
+	}
+	match db.get_article_locations(0, sitemap::MAX_URLS) {
+		Some(articles) => Ok(sitemap_response(sitemap::urlset(&articles, public_url))),
+		None => Ok(sitemap_error()),
+	}
+}
+
+/// One of the sitemaps listed in the sitemap index
+async fn sitemap_part_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	segment: String,
+) -> Result<warp::reply::Response, warp::Rejection> {
+	let Some(public_url) = config.network.public_url.as_deref() else {
+		return Ok(sitemap_unavailable());
+	};
+	let Some(part) = sitemap::parse_part(&segment) else {
+		return Ok(sitemap_not_found());
+	};
+	let mut db = db.lock().await;
+	let Ok(count) = db.count_articles() else {
+		return Ok(sitemap_error());
+	};
+	if part > sitemap::parts(count) {
+		return Ok(sitemap_not_found());
+	}
+	match db.get_article_locations((part - 1) * sitemap::MAX_URLS, sitemap::MAX_URLS) {
+		Some(articles) => Ok(sitemap_response(sitemap::urlset(&articles, public_url))),
+		None => Ok(sitemap_error()),
+	}
+}
+
+fn sitemap_response(xml: String) -> warp::reply::Response {
+	warp::reply::with_header(xml, warp::http::header::CONTENT_TYPE, "application/xml")
+		.into_response()
+}
+
+/// Sitemaps need absolute URLs
+fn sitemap_unavailable() -> warp::reply::Response {
+	warp::reply::with_status(
+		"The sitemap needs `network.public_url` in the configuration\n",
+		warp::http::StatusCode::NOT_FOUND,
+	)
+	.into_response()
+}
+
+fn sitemap_not_found() -> warp::reply::Response {
+	warp::reply::with_status("No such sitemap\n", warp::http::StatusCode::NOT_FOUND).into_response()
+}
+
+fn sitemap_error() -> warp::reply::Response {
+	warp::reply::with_status(
+		"Could not read the articles\n",
+		warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+	)
+	.into_response()
+}
+
+async fn about_page(
+	db: Arc<DbLock>,
+	config: Arc<Config>,
+	user: Option<auth::User>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+	let version_info = VersionInfo::get();
+	// Must not fail if the database is broken, the page is used for diagnosis
+	let layout_version = match db.lock().await.read_table_layout() {
+		Ok(table_layout) => table_layout.version().to_string(),
+		Err(err) => format!("unreadable ({})", err),
+	};
+	let config_path = config
+		.file_path
+		.as_ref()
+		.map_or_else(|| "none".to_string(), |path| path.display().to_string());
+
+	let startup_problems = if config.startup_problems.is_empty() {
+		String::new()
+	} else {
+		let items: Vec<String> = config
+			.startup_problems
+			.iter()
+			.map(|problem| format!("<li>{}</li>", html_escape(problem)))
+			.collect();
+		format!(
+			"\n\t\t\t\t<h3>Startup problems</h3>\n\t\t\t\t<ul class=\"render_error\">\n\t\t\t\t\t{}\n\t\t\t\t</ul>",
+			items.join("\n\t\t\t\t\t")
+		)
+	};
+
+	let mut doc = HtmlDocument::new(&config);
+	doc.styles.push(assets::MAIN_STYLE.text());
+	doc.body = format!(
+		r#"
+		{}
+		<main id="main_content" class="main_content">
+			<div class="content markdown">
+				<h2 style="margin-top: 0px;">About</h2>
+				<table>
+					<tr><th>Version</th><td>{}</td></tr>
+					<tr><th>Git commit</th><td>{}</td></tr>
+					<tr><th>Build date</th><td>{}</td></tr>
+					<tr><th>Features</th><td>{}</td></tr>
+					<tr><th>SQLite version</th><td>{}</td></tr>
+					<tr><th>Database layout</th><td>{}</td></tr>
+					<tr><th>Config file</th><td>{}</td></tr>
+				</table>{}{}
+			</div>
+		</main>
+"#,
+		generate_menu(&config, user.as_ref(), None, Some(MenuItem::About)),
+		version_info.version,
+		version_info.git_commit,
+		version_info.build_date.map_or_else(
+			|| "unknown".to_string(),
+			|date| date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+		),
+		if version_info.features.is_empty() {
+			"none".to_string()
+		} else {
+			version_info.features.join(", ")
+		},
+		rusqlite::version(),
+		html_escape(&layout_version),
+		html_escape(&config_path),
+		startup_problems,
+		lock_metrics_html(db.metrics(), config.database.lock.slow_hold_ms)
+	);
+	Ok(warp::reply::html(doc.to_html()))
+}
+
+/// How long requests waited for the database and held it since the start
+fn lock_metrics_html(metrics: &db_lock::LockHistograms, slow_hold_ms: u64) -> String {
+	let header: Vec<String> = db_lock::BUCKETS_MS
+		.iter()
+		.map(|bound| format!("<th>≤ {} ms</th>", bound))
+		.chain(std::iter::once("<th>more</th>".to_string()))
+		.collect();
+	let row = |name: &str, histogram: &db_lock::Histogram| {
+		let counts: Vec<String> = histogram
+			.counts()
+			.iter()
+			.map(|count| format!("<td>{}</td>", count))
+			.collect();
+		format!("<tr><th>{}</th>{}</tr>", name, counts.concat())
+	};
+	format!(
+		"\n\t\t\t\t<h3>Database lock</h3>\n\t\t\t\t<table class=\"lock_metrics\">\n\t\t\t\t\t<tr><th></th>{}</tr>\n\t\t\t\t\t{}\n\t\t\t\t\t{}\n\t\t\t\t</table>\n\t\t\t\t<p>Held longer than {} ms: {}</p>",
+		header.concat(),
+		row("Wait", &metrics.wait),
+		row("Hold", &metrics.hold),
+		slow_hold_ms,
+		metrics.slow_holds()
+	)
+}
+
+/// Entries of the sidebar menu that can be marked as the current page
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MenuItem {
+	Home,
+	AllArticles,
+	CreateArticle,
+	EditArticle,
+	WhatLinksHere,
+	History,
+	DeleteArticle,
+	Categories,
+	Trash,
+	About,
+	Today,
+	Login,
+}
+
+fn generate_menu(
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number_opt: Option<ItemId>,
+	current: Option<MenuItem>,
+) -> String {
+	generate_menu_with_new(config, user, article_number_opt, current, None)
+}
+
+/// Menu that shows how many articles changed since the last visit of
+/// the article list
+fn generate_menu_with_new(
+	config: &Config,
+	user: Option<&auth::User>,
+	article_number_opt: Option<ItemId>,
+	current: Option<MenuItem>,
+	new_articles: Option<u32>,
+) -> String {
+	let base = config.network.base_path.as_str();
+	let menu_link = |item: MenuItem, path: &str, label: &str| -> String {
+		let href = config.url(path);
+		if current == Some(item) {
+			format!(
+				r#"<li><a href="{}" aria-current="page">{}</a></li>"#,
+				href, label
+			)
+		} else {
+			format!(r#"<li><a href="{}">{}</a></li>"#, href, label)
+		}
+	};
+
+	let current_article = if let Some(article_number) = article_number_opt {
+		format!(
+			r#"
+				<div class="menu_section">
+					Current article:
+					<ul>
+						{}
+						{}
+						{}
+						{}
+					</ul>
+				</div>"#,
+			menu_link(
+				MenuItem::EditArticle,
+				&format!("/edit/article/{}", article_number),
+				"Edit"
+			),
+			menu_link(
+				MenuItem::WhatLinksHere,
+				&format!("/links/article/{}", article_number),
+				"What links here"
+			),
+			menu_link(
+				MenuItem::History,
+				&format!("/history/article/{}", article_number),
+				"History"
+			),
+			menu_link(
+				MenuItem::DeleteArticle,
+				&format!("/delete/article/{}", article_number),
+				"Delete"
+			)
+		)
+	} else {
+		String::new()
+	};
+
+	// Links from the configuration, added to the end of their section
+	let nav_links = |section: &str| -> String {
+		config
+			.site
+			.nav_links
+			.iter()
+			.filter(|link| link.section() == section)
+			.map(|link| {
+				format!(
+					r#"
+						<li><a href="{}"{}>{}</a></li>"#,
+					html_escape(&link.href(&config.network.base_path)),
+					if link.is_external() {
+						r#" rel="noopener""#
+					} else {
+						""
+					},
+					html_escape(&link.label)
+				)
+			})
+			.collect()
+	};
+	let mut custom_sections: Vec<&str> = Vec::new();
+	for link in &config.site.nav_links {
+		let section = link.section();
+		if !["Navigation", "Wiki"].contains(&section) && !custom_sections.contains(&section) {
+			custom_sections.push(section);
+		}
+	}
+	let custom_sections: String = custom_sections
+		.iter()
+		.map(|section| {
+			format!(
+				r#"
+				<div class="menu_section">
+					{}:
+					<ul>{}
+					</ul>
+				</div>"#,
+				html_escape(section),
+				nav_links(section)
+			)
+		})
+		.collect();
+
+	let today = if config.journal.is_some() {
+		format!(
+			"\n\t\t\t\t\t\t{}",
+			menu_link(MenuItem::Today, "/today", "Today's note")
+		)
+	} else {
+		String::new()
+	};
+
+	// Only with credentials in the configuration
+	let account = match (&config.auth, user) {
+		(None, _) => String::new(),
+		(Some(_), None) => format!(
+			r#"
+				<div class="menu_section">
+					Account:
+					<ul>
+						{}
+					</ul>
+				</div>"#,
+			menu_link(MenuItem::Login, "/login", "Login")
+		),
+		(Some(_), Some(user)) => format!(
+			r#"
+				<div class="menu_section">
+					Account:
+					<ul>
+						<li>Logged in as {}</li>
+						<li><form action="{base}/logout" method="post"><input type="submit" class="editor_submit" value="Logout"></form></li>
+					</ul>
+				</div>"#,
+			html_escape(&user.name)
+		),
+	};
+
+	format!(
+		r#"<div class="side_content">
+			<div class="content">
+				<header><img style="width: 112px; height: 112px;" src="{base}/img/redwood_observatory_medium.png" alt="Redwood Observatory"> {}</header>
+				<form action="{base}/search/article" method="post" role="search" class="menu_section">
+					<label for="search_term_plain">Search:</label>
+					<input type="search" id="search_term_plain" name="search_term_plain" value=""><input type="submit" class="editor_submit" value="Search">
+				</form>
+				<nav aria-label="Wiki">
+				<div class="menu_section">
+					Navigation:
+					<ul>
+						{}
+						{}{}{}
+					</ul>
+				</div>
+				<div class="menu_section">
+					Wiki:
+					<ul>
+						{}
+						{}
+						{}
+						{}{}
+					</ul>
+				</div>{}{}{}
+				</nav>
+			</div>
+		</div>"#,
+		html_escape(&config.wiki.name),
+		menu_link(MenuItem::Home, "/", "Home"),
+		menu_link(
+			MenuItem::AllArticles,
+			"/articles",
+			&match new_articles {
+				Some(count) if count > 0 => format!("All articles ({} new)", count),
+				_ => "All articles".to_string(),
+			}
+		),
+		today,
+		nav_links("Navigation"),
+		menu_link(MenuItem::CreateArticle, "/create/article", "Create article"),
+		menu_link(MenuItem::Categories, "/categories", "Categories"),
+		menu_link(MenuItem::Trash, "/trash", "Trash"),
+		menu_link(MenuItem::About, "/about", "About"),
+		nav_links("Wiki"),
+		current_article,
+		custom_sections,
+		account
+	)
+}
+
+/// Target of a redirect to `path` on this site
+fn site_uri(config: &Config, path: &str) -> warp::http::Uri {
+	warp::http::Uri::from_maybe_shared(config.url(path)).unwrap()
+}
+
+/// Escape text for use in HTML content and attribute values
+fn html_escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	pulldown_cmark::escape::escape_html(&mut escaped, text).unwrap();
+	escaped
+}
+
+/// Text as the content of a `<textarea>`, so that the browser submits
+/// it unchanged. Escaping `<` defuses `</textarea>`, and escaping `&`
+/// keeps entities in the text from being decoded. Browsers drop a
+/// newline right after the start tag, so a leading newline is doubled.
+fn textarea_escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len() + 1);
+	if text.starts_with('\n') || text.starts_with("\r\n") {
+		escaped.push('\n');
+	}
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Text as the value of an attribute in double or single quotes
+fn attribute_escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Render a list of articles as HTML list with links to the articles
+fn generate_articles_list(
+	config: &Config,
+	articles: &[ArticleSummary],
+	last_visit: Option<UtcTimestamp>,
+) -> String {
+	use std::fmt::Write;
+	let mut accumulator = String::from("<ul class=\"article_list\">\n");
+	for article in articles {
+		let badge = if visits::is_new(article.date_modified, last_visit) {
+			r#" <span class="badge_changed">changed</span>"#
+		} else {
+			""
+		};
+		writeln!(
+			accumulator,
+			"<li><a href=\"{}\">{}</a> <span style=\"color: #BBBBBB;\">#{}</span>{}</li>",
+			html_escape(&config.url(&urls::article_path(article.id, &article.slug))),
+			html_escape(&article.title),
+			article.id,
+			badge
+		)
+		.unwrap();
+	}
+	accumulator.push_str("</ul>");
+	accumulator
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use regex::Regex;
+
+	fn test_db_path(name: &str) -> std::path::PathBuf {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-main-{}-{}.sqlite",
+			name,
+			std::process::id()
+		));
+		path
+	}
+
+	async fn test_db(name: &str) -> Arc<DbLock> {
+		let path = test_db_path(name);
+		let _ = std::fs::remove_file(&path);
+		let db = DatabaseConnection::new(&path, database::OpenMode::CreateNew)
+			.unwrap()
+			.init()
+			.unwrap();
+		Arc::new(DbLock::new(db, std::time::Duration::from_millis(100)))
+	}
+
+	fn test_config() -> Arc<Config> {
+		Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[database]
+				storage_location = "."
+				"#,
+			)
+			.unwrap(),
+		)
+	}
+
+	fn test_config_with_server_header() -> Arc<Config> {
+		Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				server_header = true
+
+				[database]
+				storage_location = "."
+				"#,
+			)
+			.unwrap(),
+		)
+	}
+
+	fn test_dictionary() -> Arc<Mutex<Dictionary>> {
+		Arc::new(Mutex::new(Dictionary::default()))
+	}
+
+	async fn body_of(reply: impl warp::Reply) -> String {
+		let body = warp::hyper::body::to_bytes(reply.into_response().into_body())
+			.await
+			.unwrap();
+		String::from_utf8(body.to_vec()).unwrap()
+	}
+
+	fn assert_landmarks(html: &str) {
+		let body = &html[html.find("<body>").unwrap() + "<body>".len()..];
+		assert!(body
+			.trim_start()
+			.starts_with(r##"<a href="#main_content" class="skip_link">"##));
+		assert!(html.contains(r#"<main id="main_content""#));
+		assert!(html.contains("<header>"));
+		assert!(html.contains("<nav "));
+		assert!(html.contains(r#"role="search""#));
+	}
+
+	fn assert_inputs_labelled(html: &str) {
+		// Only look at the body, the inlined scripts in the head contain HTML snippets
+		let html = html.find("<body>").map_or(html, |start| &html[start..]);
+		let input_regex = Regex::new(r#"<(input|textarea)([^>]*)>"#).unwrap();
+		let id_regex = Regex::new(r#"id="([^"]+)""#).unwrap();
+		for input in input_regex.captures_iter(html) {
+			let attributes = &input[2];
+			if attributes.contains(r#"type="submit""#) || attributes.contains(r#"type="hidden""#) {
+				continue;
+			}
+			if attributes.contains("aria-label=") {
+				continue;
+			}
+			let id = &id_regex
+				.captures(attributes)
+				.unwrap_or_else(|| panic!("Input without id or aria-label: {}", &input[0]))[1];
+			assert!(
+				html.contains(&format!(r#"<label for="{}">"#, id)),
+				"Input without label: {}",
+				&input[0]
+			);
+		}
+	}
+
+	#[test]
+	fn test_menu_current_item() {
+		let menu = generate_menu(&test_config(), None, None, None);
+		assert!(!menu.contains("aria-current"));
+		assert!(!menu.contains("Current article"));
+
+		let menu = generate_menu(
+			&test_config(),
+			None,
+			Some(5.into()),
+			Some(MenuItem::EditArticle),
+		);
+		assert_eq!(menu.matches("aria-current").count(), 1);
+		assert!(menu.contains(r#"<a href="/edit/article/5" aria-current="page">Edit</a>"#));
+
+		let menu = generate_menu(&test_config(), None, None, Some(MenuItem::AllArticles));
+		assert!(menu.contains(r#"<a href="/articles" aria-current="page">All articles</a>"#));
+		assert_inputs_labelled(&menu);
+	}
+
+	#[test]
+	fn test_articles_list_is_list() {
+		let article = ArticleSummary {
+			id: 3.into(),
+			title: "Tree".to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			slug: "tree".to_string(),
+		};
+		let list = generate_articles_list(&test_config(), &[article], None);
+		assert!(list.starts_with(r#"<ul class="article_list">"#));
+		assert!(list.contains(r#"<li><a href="/article/3/tree">Tree</a>"#));
+		assert!(list.ends_with("</ul>"));
+		assert!(!list.contains("<br>"));
+	}
+
+	#[test]
+	fn test_articles_list_escapes_titles() {
+		let article = ArticleSummary {
+			id: 4.into(),
+			title: r#"<img src=x onerror="alert(1)">"#.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			slug: "img".to_string(),
+		};
+		let list = generate_articles_list(&test_config(), &[article], None);
+		assert!(list.contains(
+			r#"<li><a href="/article/4/img">&lt;img src=x onerror=&quot;alert(1)&quot;&gt;</a>"#
+		));
+		assert!(!list.contains("<img"));
+	}
+
+	#[tokio::test]
+	async fn test_pages_accessible() {
+		let db = test_db("accessible").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tree".to_string(),
+				text: "Redwoods are tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+
+		let pages = vec![
+			body_of(
+				index_page(db.clone(), test_config(), None, None)
+					.await
+					.unwrap(),
+			)
+			.await,
+			body_of(
+				articles_page(db.clone(), test_config(), None, HashMap::new(), None)
+					.await
+					.unwrap(),
+			)
+			.await,
+			body_of(article_page(db.clone(), test_config(), id).await.unwrap()).await,
+			body_of(
+				article_edit_page(db.clone(), test_config(), test_dictionary(), id, None, None)
+					.await
+					.unwrap(),
+			)
+			.await,
+			body_of(
+				article_create_page(db.clone(), test_config(), None)
+					.await
+					.unwrap(),
+			)
+			.await,
+		];
+		for page in &pages {
+			assert_landmarks(page);
+			assert_inputs_labelled(page);
+		}
+		assert!(pages[1].contains(r#"<ul class="article_list">"#));
+		assert!(pages[2].contains(r#"aria-label="Edit this article""#));
+	}
+
+	#[tokio::test]
+	async fn test_url_normalization() {
+		let db = test_db("normalization").await;
+		db.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tree".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db, test_config(), test_dictionary());
+
+		for (path, target) in [
+			("/articles/", "/articles"),
+			("/article/1/", "/article/1"),
+			("/edit/article/1/", "/edit/article/1"),
+			("/create/article/", "/create/article"),
+			("/search/article/", "/search/article"),
+			("//article//1", "/article/1"),
+			(
+				"/search//article?search_term_plain=x",
+				"/search/article?search_term_plain=x",
+			),
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 301, "{}", path);
+			assert_eq!(response.headers()["location"], target, "{}", path);
+		}
+
+		for path in [
+			"/",
+			"/articles",
+			"/article/1",
+			"/edit/article/1",
+			"/create/article",
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 200, "{}", path);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_setup() {
+		let db = test_db("setup").await;
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let index = || async {
+			let response = warp::test::request().path("/").reply(&routes).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let setup = |form: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path("/setup")
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(form)
+				.reply(&routes)
+		};
+		assert!(index()
+			.await
+			.contains(r#"<form action="/setup" method="post">"#));
+
+		let response = setup("examples=on").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/article/3");
+		assert_eq!(db.lock().await.count_articles().unwrap(), 3);
+		let response = warp::test::request()
+			.path("/article/3")
+			.reply(&routes)
+			.await;
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(r#"<h1>Home <span"#));
+		assert!(body.contains(r#"<a href="/article/1" title="Coast redwood">Coast redwood</a>"#));
+		assert!(body.contains(r#"<a href="/article/2" title="Giant sequoia">Giant sequoia</a>"#));
+
+		// Only once, afterwards the index is back to normal
+		assert!(!index().await.contains("/setup"));
+		let response = setup("examples=on").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/");
+		assert_eq!(db.lock().await.count_articles().unwrap(), 3);
+
+		let db = test_db("setup-without-examples").await;
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request()
+			.method("POST")
+			.path("/setup")
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.headers()["location"], "/article/1");
+		assert_eq!(db.lock().await.count_articles().unwrap(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_auth() {
+		let db = test_db("auth").await;
+		// Hash of `correct horse`, see the tests of `auth`
+		let config = |protect_reads: bool| -> Arc<Config> {
+			Arc::new(
+				toml::from_str(&format!(
+					r#"
+					[network]
+					ip = "127.0.0.1"
+					port = 0
+
+					[auth]
+					username = "editor"
+					password_hash = "$2b$04$akTib07tXAzyXVLya0Dqb.GY6BdVRMtqzhYavCxE/q.9kUWesvRZ6"
+					protect_reads = {}
+					"#,
+					protect_reads
+				))
+				.unwrap(),
+			)
+		};
+		let credentials = "Basic ZWRpdG9yOmNvcnJlY3QgaG9yc2U=";
+		let routes = routes(db.clone(), config(false), test_dictionary());
+		let setup = |authorization: Option<&'static str>| {
+			let mut request = warp::test::request()
+				.method("POST")
+				.path("/setup")
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body("examples=on");
+			if let Some(authorization) = authorization {
+				request = request.header("authorization", authorization);
+			}
+			request.reply(&routes)
+		};
+		let response = setup(None).await;
+		assert_eq!(response.status(), 401);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains(r#"<a href="/login?next=%2Fsetup">log in</a>"#));
+		assert_eq!(setup(Some("Basic ZWRpdG9yOndyb25n")).await.status(), 401);
+		assert_eq!(setup(Some(credentials)).await.status(), 303);
+
+		// Reading is open, editing is not
+		for path in [
+			"/",
+			"/articles",
+			"/article/1",
+			"/search/article",
+			"/api/article/1",
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 200, "{}", path);
+		}
+		for path in [
+			"/edit/article/1",
+			"/create/article",
+			"/delete/article/1",
+			"/trash",
+			"/admin/tags",
+			"/admin/export.json",
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 401, "{}", path);
+			// Browsers show the login page instead of asking themselves
+			assert!(
+				!response.headers().contains_key("www-authenticate"),
+				"{}",
+				path
+			);
+			let response = warp::test::request()
+				.path(path)
+				.header("authorization", credentials)
+				.reply(&routes)
+				.await;
+			assert_eq!(response.status(), 200, "{}", path);
+		}
+		let response = warp::test::request()
+			.method("PUT")
+			.path("/api/article/1")
+			.header("content-type", "application/json")
+			.body(r#"{"title": "Coast redwood", "text": "", "revision": 1}"#)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 401);
+		assert_eq!(
+			response.headers()["www-authenticate"],
+			r#"Basic realm="Redwood Wiki", charset="UTF-8""#
+		);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains(r#""code":"unauthorized""#));
+
+		let routes = super::routes(db, config(true), test_dictionary());
+		let response = warp::test::request().path("/articles").reply(&routes).await;
+		assert_eq!(response.status(), 401);
+		let response = warp::test::request()
+			.path("/articles")
+			.header("authorization", credentials)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		// The login page stays reachable
+		let response = warp::test::request().path("/login").reply(&routes).await;
+		assert_eq!(response.status(), 200);
+	}
+
+	#[tokio::test]
+	async fn test_login() {
+		let db = test_db("login").await;
+		// Hash of `correct horse`, see the tests of `auth`
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[auth]
+				username = "editor"
+				password_hash = "$2b$04$akTib07tXAzyXVLya0Dqb.GY6BdVRMtqzhYavCxE/q.9kUWesvRZ6"
+				"#,
+			)
+			.unwrap(),
+		);
+		let routes = routes(db.clone(), config, test_dictionary());
+		let body = |response: &warp::http::Response<warp::hyper::body::Bytes>| {
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let login = |form: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path("/login")
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(form)
+				.reply(&routes)
+		};
+		let get = |path: &'static str, cookie: Option<String>| {
+			let mut request = warp::test::request().path(path);
+			if let Some(cookie) = cookie {
+				request = request.header("cookie", cookie);
+			}
+			request.reply(&routes)
+		};
+
+		let response = get("/login?next=%2Fcreate%2Farticle", None).await;
+		assert_eq!(response.status(), 200);
+		assert!(body(&response)
+			.contains(r#"<input type="hidden" name="next" value="/create/article">"#));
+		assert!(body(&response).contains(r#"<a href="/login" aria-current="page">Login</a>"#));
+
+		let response = login("username=editor&password=wrong+horse&next=%2Fcreate%2Farticle").await;
+		assert_eq!(response.status(), 401);
+		assert!(!response.headers().contains_key("set-cookie"));
+		assert!(body(&response).contains("Wrong username or password."));
+
+		// Only paths on this wiki
+		let response = login("username=editor&password=correct+horse&next=%2F%2Fexample.com").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/");
+
+		let response =
+			login("username=editor&password=correct+horse&next=%2Fcreate%2Farticle").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/create/article");
+		let set_cookie = response.headers()["set-cookie"].to_str().unwrap();
+		assert!(set_cookie.starts_with("redwood_session="), "{}", set_cookie);
+		assert!(
+			set_cookie.ends_with("; Max-Age=2592000; Path=/; HttpOnly; SameSite=Lax"),
+			"{}",
+			set_cookie
+		);
+		let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+		let response = get("/create/article", Some(cookie.clone())).await;
+		assert_eq!(response.status(), 200);
+		assert!(body(&response).contains("<li>Logged in as editor</li>"));
+		assert!(body(&response).contains(r#"<form action="/logout" method="post">"#));
+		let response = get("/create/article", None).await;
+		assert_eq!(response.status(), 401);
+		assert!(body(&response).contains(r#"<a href="/login">Login</a>"#));
+		assert_eq!(response.headers()["cache-control"], "private");
+
+		// Logging in or out changes the menu, the ETag must change too
+		let response = warp::test::request()
+			.method("POST")
+			.path("/setup")
+			.header("cookie", &cookie)
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("examples=on")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		let conditional = |cookie: Option<&String>, etag: &str| {
+			let mut request = warp::test::request()
+				.path("/article/1")
+				.header("if-none-match", etag);
+			if let Some(cookie) = cookie {
+				request = request.header("cookie", cookie);
+			}
+			request.reply(&routes)
+		};
+		let anonymous = get("/article/1", None).await;
+		assert_eq!(anonymous.headers()["cache-control"], "private, no-cache");
+		let anonymous = anonymous.headers()["etag"].to_str().unwrap().to_string();
+		let logged_in = get("/article/1", Some(cookie.clone())).await;
+		let logged_in = logged_in.headers()["etag"].to_str().unwrap().to_string();
+		assert_eq!(conditional(None, &anonymous).await.status(), 304);
+		let response = conditional(Some(&cookie), &anonymous).await;
+		assert_eq!(response.status(), 200);
+		assert!(body(&response).contains("<li>Logged in as editor</li>"));
+		let response = conditional(None, &logged_in).await;
+		assert_eq!(response.status(), 200);
+		assert!(body(&response).contains(r#"<a href="/login">Login</a>"#));
+
+		// Tampered cookies are anonymous, not errors
+		let mut tampered = cookie.clone();
+		tampered.replace_range(16..17, if &cookie[16..17] == "9" { "8" } else { "9" });
+		for cookie in [tampered, "redwood_session=garbage".to_string()] {
+			let response = get("/articles", Some(cookie.clone())).await;
+			assert_eq!(response.status(), 200);
+			assert!(body(&response).contains(r#"<a href="/login">Login</a>"#));
+			let response = get("/create/article", Some(cookie)).await;
+			assert_eq!(response.status(), 401);
+		}
+
+		let response = warp::test::request()
+			.method("POST")
+			.path("/logout")
+			.header("cookie", &cookie)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["set-cookie"],
+			"redwood_session=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax"
+		);
+
+		// Without `[auth]`, there is nothing to log in to
+		let routes = super::routes(db, test_config(), test_dictionary());
+		let response = warp::test::request().path("/login").reply(&routes).await;
+		assert_eq!(response.status(), 404);
+		let response = warp::test::request().path("/articles").reply(&routes).await;
+		assert!(!String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("Login"));
+	}
+
+	#[tokio::test]
+	async fn test_base_path() {
+		let db = test_db("base-path").await;
+		let config = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				base_path = "/wiki"
+
+				[database]
+				storage_location = "."
+				"#,
+			)
+			.unwrap(),
+		);
+		let routes = routes(db.clone(), config, test_dictionary());
+		let response = warp::test::request()
+			.method("POST")
+			.path("/wiki/setup")
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("examples=on")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/wiki/article/3");
+
+		let response = warp::test::request()
+			.path("/wiki/article/3")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(r#"<html lang="en" data-base-path="/wiki">"#));
+		assert!(body.contains(r#"<a href="/wiki/article/1" title="Coast redwood">"#));
+		assert!(body.contains(r#"href="/wiki/articles""#));
+		assert!(!body.contains(r#"href="/articles""#));
+
+		// Redirects to the stored slug stay under the base path
+		let response = warp::test::request()
+			.path("/wiki/article/3/outdated-slug")
+			.reply(&routes)
+			.await;
+		assert!(response.status().is_redirection());
+		let location = response.headers()["location"].to_str().unwrap();
+		assert!(location.starts_with("/wiki/article/3"), "{}", location);
+
+		for (path, target) in [("/wiki/", "/wiki"), ("/wiki/articles/", "/wiki/articles")] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 301, "{}", path);
+			assert_eq!(response.headers()["location"], target, "{}", path);
+		}
+		for path in [
+			"/wiki",
+			"/wiki/articles",
+			"/wiki/search/article?search_term_plain=redwood",
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 200, "{}", path);
+		}
+		for path in ["/", "/articles", "/wikiarticles", "/article/3"] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 404, "{}", path);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_articles_pagination() {
+		let db = test_db("articles-pagination").await;
+		for title in ["Cedar", "Alder", "Beech", "Elm", "Dogwood"] {
+			db.lock()
+				.await
+				.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: "Bark".to_string(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap();
+		}
+		let routes = routes(db, test_config(), test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: &'static str| async move {
+			let response = warp::test::request().path(path).reply(routes_ref).await;
+			assert_eq!(response.status(), 200);
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let titles = |body: &str| -> Vec<String> {
+			Regex::new(r#"<li><a href="/article/\d+/[^"]+">([^<]*)</a>"#)
+				.unwrap()
+				.captures_iter(body)
+				.map(|captures| captures[1].to_string())
+				.collect()
+		};
+
+		// All on one page by default
+		let body = get("/articles").await;
+		assert_eq!(titles(&body), ["Cedar", "Alder", "Beech", "Elm", "Dogwood"]);
+		assert!(!body.contains(r#"<nav aria-label="Pages""#));
+		assert!(body.contains(
+			r#"<p class="list_order">Order: <b>by ID</b> | <a href="/articles?page=1&amp;order=title">by title</a> | <a href="/articles?page=1&amp;order=modified">recently changed</a></p>"#
+		));
+
+		let body = get("/articles?page=2&per_page=2&order=title").await;
+		assert_eq!(titles(&body), ["Cedar", "Dogwood"]);
+		assert!(body.contains(
+			r#"<nav aria-label="Pages" class="pagination"><a href="/articles?page=1&amp;per_page=2&amp;order=title" rel="prev">&larr; Previous</a> Page 2 of 3 <a href="/articles?page=3&amp;per_page=2&amp;order=title" rel="next">Next &rarr;</a></nav>"#
+		));
+		let body = get("/articles?page=3&per_page=2&order=title").await;
+		assert_eq!(titles(&body), ["Elm"]);
+		assert!(!body.contains("Next &rarr;"));
+
+		// Out of range or invalid parameters do not fail
+		let body = get("/articles?page=9&per_page=2").await;
+		assert!(titles(&body).is_empty());
+		assert!(body.contains(
+			r#"<p>There are no articles on page 9. <a href="/articles?page=1&amp;per_page=2">Go to the first page</a></p>"#
+		));
+		assert!(body.contains(r#"<a href="/articles?page=3&amp;per_page=2" rel="prev">"#));
+		let body = get("/articles?page=4294967295&per_page=4294967295").await;
+		assert!(body.contains("There are no articles on page 4294967295."));
+		let body = get("/articles?page=0&per_page=-1&order=x").await;
+		assert_eq!(titles(&body).len(), 5);
+		let body = get("/articles?per_page=0").await;
+		assert_eq!(titles(&body), ["Cedar"]);
+	}
+
+	#[tokio::test]
+	async fn test_new_since_last_visit() {
+		let db = test_db("new-since-visit").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tree".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		// Stored with more than millisecond precision
+		let modified = db
+			.lock()
+			.await
+			.get_article(id)
+			.unwrap()
+			.unwrap()
+			.date_modified;
+		let before = modified - chrono::Duration::seconds(10);
+		let routes = routes(db, test_config(), test_dictionary());
+		let get = |path: &'static str, cookie: Option<String>| {
+			let mut request = warp::test::request().path(path);
+			if let Some(cookie) = cookie {
+				request = request.header("cookie", cookie);
+			}
+			request.reply(&routes)
+		};
+
+		// First visit: nothing is marked, the visit is recorded
+		let response = get("/articles", None).await;
+		let set_cookie = response.headers()["set-cookie"]
+			.to_str()
+			.unwrap()
+			.to_string();
+		assert!(set_cookie.starts_with("last_listing_visit="));
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(!body.contains(r#"<span class="badge_changed">"#));
+		assert!(body.contains(">All articles</a>"));
+
+		let visit = set_cookie.split(';').next().unwrap().to_string();
+		let response = get("/articles", Some(visit)).await;
+		assert!(!String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains(r#"<span class="badge_changed">"#));
+		// Visited in the same millisecond, after the change
+		let same_millisecond = Some(
+			visits::cookie(modified)
+				.split(';')
+				.next()
+				.unwrap()
+				.to_string(),
+		);
+		let body =
+			String::from_utf8(get("/articles", same_millisecond).await.body().to_vec()).unwrap();
+		assert!(!body.contains(r#"<span class="badge_changed">"#));
+
+		// Visited before the article was created
+		let old_visit = Some(
+			visits::cookie(before)
+				.split(';')
+				.next()
+				.unwrap()
+				.to_string(),
+		);
+		let body =
+			String::from_utf8(get("/articles", old_visit.clone()).await.body().to_vec()).unwrap();
+		assert!(body.contains(r#"<span class="badge_changed">changed</span>"#));
+		assert!(body.contains("All articles (1 new)"));
+		let body = String::from_utf8(get("/", old_visit.clone()).await.body().to_vec()).unwrap();
+		assert!(body.contains("All articles (1 new)"));
+
+		// Malformed cookies are ignored
+		let body = String::from_utf8(
+			get("/articles", Some("last_listing_visit=-1".to_string()))
+				.await
+				.body()
+				.to_vec(),
+		)
+		.unwrap();
+		assert!(!body.contains(r#"<span class="badge_changed">"#));
+	}
+
+	#[tokio::test]
+	async fn test_delete_article() {
+		let db = test_db("delete").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Old <tree>".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let path = format!("/delete/article/{}", id);
+
+		let response = warp::test::request().path(&path).reply(&routes).await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains("Delete \"Old &lt;tree&gt;\"?"));
+		assert!(body.contains(&format!(r#"<form action="{}" method="post">"#, path)));
+		assert!(db.lock().await.get_article(id).unwrap().is_some());
+
+		let response = warp::test::request()
+			.method("POST")
+			.path(&path)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/articles");
+		assert!(db.lock().await.get_article(id).unwrap().is_none());
+
+		// Already gone
+		for method in ["GET", "POST"] {
+			let response = warp::test::request()
+				.method(method)
+				.path(&path)
+				.reply(&routes)
+				.await;
+			assert_eq!(response.status(), 200, "{}", method);
+			assert!(String::from_utf8(response.body().to_vec())
+				.unwrap()
+				.contains(&format!("Could not find article #{}!", id)));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_delete_and_restore_referenced_article() {
+		let db = test_db("restore").await;
+		let new_article = |title: &str, text: String| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text,
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let target = db
+			.lock()
+			.await
+			.create_article(&new_article("Sequoia", String::new()))
+			.unwrap();
+		let referrer = db
+			.lock()
+			.await
+			.create_article(&new_article("Redwood", format!("See [article:{}]", target)))
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let referrer_body = || async {
+			let response = warp::test::request()
+				.path(&format!("/article/{}", referrer))
+				.reply(&routes)
+				.await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let link = format!(
+			r#"<a href="/article/{}" title="Sequoia">Sequoia</a>"#,
+			target
+		);
+		let deleted = r#"<del class="ref_deleted" title="deleted">Sequoia</del>"#;
+		assert!(referrer_body().await.contains(&link));
+
+		let response = warp::test::request()
+			.method("POST")
+			.path(&format!("/delete/article/{}", target))
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		let body = referrer_body().await;
+		assert!(body.contains(deleted));
+		assert!(!body.contains(&link));
+
+		let response = warp::test::request().path("/trash").reply(&routes).await;
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(&format!(
+			r#"<form action="/restore/article/{}" method="post""#,
+			target
+		)));
+
+		let restore_path = format!("/restore/article/{}", target);
+		let response = warp::test::request()
+			.method("POST")
+			.path(&restore_path)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}", target).as_str()
+		);
+		// The referring article was not saved again in between
+		let body = referrer_body().await;
+		assert!(body.contains(&link));
+		assert!(!body.contains(deleted));
+
+		let response = warp::test::request().path("/trash").reply(&routes).await;
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("The trash is empty."));
+		let response = warp::test::request()
+			.method("POST")
+			.path(&restore_path)
+			.reply(&routes)
+			.await;
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains(&format!("Could not find article #{}!", target)));
+	}
+
+	#[tokio::test]
+	async fn test_invalid_article_id() {
+		let db = test_db("invalid-id").await;
+		let routes = routes(db, test_config(), test_dictionary());
+
+		for (path, segment) in [
+			("/article/abc", "abc"),
+			("/article/+5", "+5"),
+			("/article/05", "05"),
+			("/article/4294967296", "4294967296"),
+			("/article/abc/some-slug", "abc"),
+			("/edit/article/x%3Cb%3E", "x&lt;b&gt;"),
+		] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 400, "{}", path);
+			let body = String::from_utf8(response.body().to_vec()).unwrap();
+			assert!(
+				body.contains(&format!("'{}' is not a valid article id", segment)),
+				"{}",
+				path
+			);
+		}
+		let response = warp::test::request()
+			.method("POST")
+			.path("/article/abc")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 400);
+
+		// Valid but absent IDs get the not-found page
+		let response = warp::test::request()
+			.path("/article/4294967295")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("Could not find article #4294967295"));
+
+		// Other rejections are not affected
+		let response = warp::test::request().path("/nowhere").reply(&routes).await;
+		assert!(response.status().is_client_error());
+		assert_ne!(response.status(), 400);
+	}
+
+	#[tokio::test]
+	async fn test_about_page() {
+		let db = test_db("about").await;
+
+		let response = warp::test::request()
+			.path("/about")
+			.reply(&routes(db.clone(), test_config(), test_dictionary()))
+			.await;
+		assert_eq!(response.status(), 200);
+		assert!(response.headers().get("server").is_none());
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(env!("CARGO_PKG_VERSION")));
+		assert!(body.contains(rusqlite::version()));
+		assert!(body.contains("<td>0.1.0</td>"));
+		assert!(body.contains("<h3>Database lock</h3>"));
+		assert!(body.contains("<th>≤ 1 ms</th>"));
+		assert!(body.contains("Held longer than 100 ms: 0"));
+		assert_landmarks(&body);
+
+		let response = warp::test::request()
+			.path("/articles")
+			.reply(&routes(
+				db,
+				test_config_with_server_header(),
+				test_dictionary(),
+			))
+			.await;
+		assert_eq!(
+			response.headers()["server"],
+			format!("redwood-wiki/{}", env!("CARGO_PKG_VERSION"))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_startup_problems() {
+		let db = test_db("startup-problems").await;
+		let body = body_of(about_page(db.clone(), test_config(), None).await.unwrap()).await;
+		assert!(!body.contains("Startup problems"));
+
+		let mut config: Config = toml::from_str(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+
+			[journal]
+			date_format = "%Q <b>"
+			"#,
+		)
+		.unwrap();
+		let dictionary = startup::init_optional(&mut config);
+		let routes = routes(db, Arc::new(config), Arc::new(Mutex::new(dictionary)));
+
+		// The wiki runs without the broken feature
+		let response = warp::test::request().path("/about").reply(&routes).await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains("<h3>Startup problems</h3>"));
+		assert!(body
+			.contains("<li>Journal disabled: invalid date format &quot;%Q &lt;b&gt;&quot;</li>"));
+		assert!(!body.contains("/today"));
+		let response = warp::test::request().path("/today").reply(&routes).await;
+		assert!(response.status().is_client_error());
+		let response = warp::test::request().path("/articles").reply(&routes).await;
+		assert_eq!(response.status(), 200);
+	}
+
+	#[tokio::test]
+	async fn test_about_page_broken_database() {
+		let db = test_db("about-broken").await;
+		rusqlite::Connection::open(test_db_path("about-broken"))
+			.unwrap()
+			.execute("DROP TABLE table_layout", [])
+			.unwrap();
+
+		let body = body_of(about_page(db, test_config(), None).await.unwrap()).await;
+		assert!(body.contains(env!("CARGO_PKG_VERSION")));
+		assert!(body.contains("unreadable (no such table: table_layout)"));
+	}
+
+	#[tokio::test]
+	async fn test_editor_spellcheck_off() {
+		let db = test_db("spellcheck-off").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tree".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let body = body_of(
+			article_edit_page(db, test_config(), test_dictionary(), id, None, None)
+				.await
+				.unwrap(),
+		)
+		.await;
+		assert!(body.contains(&format!(
+			r#"<script>
+				var easyMDE = new EasyMDE({{
+					autoDownloadFontAwesome: false,
+					lineNumbers: true,
+					spellChecker: false,
+					toolbar: false,
+					sideBySideFullscreen: false,
+					previewClass: ["editor-preview", "markdown"],
+					previewRender: redwoodPreviewRender({}),
+					element: document.getElementById('article_text')
+				}});
+				EasyMDE.toggleSideBySide(easyMDE);
+				</script>"#,
+			id
+		)));
+		assert!(!body.contains("redwoodEditorDictionary"));
+	}
+
+	#[tokio::test]
+	async fn test_editor_preview() {
+		let db = test_db("preview").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Giant sequoia".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let body = body_of(
+			article_edit_page(db.clone(), test_config(), test_dictionary(), id, None, None)
+				.await
+				.unwrap(),
+		)
+		.await;
+		assert!(body.contains(&format!("previewRender: redwoodPreviewRender({}),", id)));
+		assert!(body.contains("function redwoodPreviewRender("));
+		// Highlighted code in the preview needs the theme
+		assert!(body.contains(test_config().appearance.code_theme_css()));
+
+		let routes = routes(db, test_config(), test_dictionary());
+		let response = warp::test::request()
+			.method("POST")
+			.path("/api/preview")
+			.header("content-type", "application/json")
+			.body(format!(
+				r#"{{"text": "See [article:{}]", "article": {}}}"#,
+				id, id
+			))
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		let preview: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(
+			preview["html"],
+			format!(
+				"<p>See <a href=\"/article/{}\" title=\"Giant sequoia\">Giant sequoia</a></p>\n",
+				id
+			)
+		);
+		assert_eq!(preview["warnings"], serde_json::json!([]));
+	}
+
+	#[tokio::test]
+	async fn test_section_edit_links() {
+		let db = test_db("sections").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tree".to_string(),
+				text: "Intro\n\n## Habitat\n\nText\n\n> ## Habitat\n".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let body = body_of(article_page(db.clone(), test_config(), id).await.unwrap()).await;
+		assert!(body.contains(&format!(
+			r#"<h2 id="section-habitat">Habitat <a class="section_edit" href="/edit/article/{}#section-habitat" aria-label="Edit section">edit</a></h2>"#,
+			id
+		)));
+		assert!(body.contains(&format!(
+			r#"<h2 id="section-habitat-2">Habitat <a class="section_edit" href="/edit/article/{}#section-habitat-2""#,
+			id
+		)));
+
+		let body = body_of(
+			article_edit_page(db, test_config(), test_dictionary(), id, None, None)
+				.await
+				.unwrap(),
+		)
+		.await;
+		assert!(body.contains(
+			r#"<script type="application/json" id="section_lines">{"section-habitat":3,"section-habitat-2":7}</script>"#
+		));
+		assert!(body.contains(r#"redwoodEditorSections(easyMDE, "section_lines");"#));
+	}
+
+	#[tokio::test]
+	async fn test_dictionary_endpoints() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-main-dictionary-{}.txt",
+			std::process::id()
+		));
+		std::fs::write(
+			&path,
+			"rusqlite
+",
+		)
+		.unwrap();
+		let dictionary = Arc::new(Mutex::new(Dictionary::load(&path).unwrap()));
+		let routes = routes(
+			test_db("dictionary").await,
+			test_config(),
+			dictionary.clone(),
+		);
+
+		let response = warp::test::request()
+			.path("/api/dictionary")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.body(), r#"["rusqlite"]"#);
+
+		let post = |body: &str| {
+			warp::test::request()
+				.method("POST")
+				.path("/api/dictionary")
+				.header("content-type", "application/json")
+				.body(body)
+				.reply(&routes)
+		};
+		assert_eq!(post(r#"{"word": "syntect"}"#).await.status(), 201);
+		assert_eq!(post(r#"{"word": "syntect"}"#).await.status(), 200);
+		let code = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+			let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+			(response.status().as_u16(), body["code"].clone())
+		};
+		assert_eq!(
+			code(post(r#"{"word": "</script>"}"#).await),
+			(400, serde_json::json!("validation"))
+		);
+		assert_eq!(
+			code(post(&format!(r#"{{"word": "{}"}}"#, "a".repeat(2000))).await),
+			(413, serde_json::json!("too_large"))
+		);
+
+		let response = warp::test::request()
+			.path("/api/dictionary")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.body(), r#"["rusqlite","syntect"]"#);
+		assert_eq!(
+			std::fs::read_to_string(&path).unwrap(),
+			"rusqlite\nsyntect\n"
+		);
+		std::fs::remove_file(&path).unwrap();
+
+		let editor = config::Editor {
+			spellcheck: true,
+			..Default::default()
+		};
+		let script = editor_script(&editor, &*dictionary.lock().await, 1.into());
+		assert!(script.contains("nativeSpellcheck: true"));
+		assert!(script.contains(r#"redwoodEditorDictionary(easyMDE, ["rusqlite","syntect"]);"#));
+	}
+
+	#[tokio::test]
+	async fn test_article_metadata() {
+		let db = test_db("metadata").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: r#"The "tallest" <tree>"#.to_string(),
+				text: format!("# Heading\n\n**Redwoods** are {}", "very ".repeat(100)),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				public_url = "https://wiki.example.com/"
+
+				[database]
+				storage_location = "."
+				"#,
+			)
+			.unwrap(),
+		);
+
+		let html = body_of(article_page(db.clone(), config.clone(), id).await.unwrap()).await;
+		let head = &html[..html.find("<body>").unwrap()];
+		assert!(head.contains(r#"<meta property="og:type" content="article">"#));
+		assert!(head.contains(
+			r#"<meta property="og:title" content="The &quot;tallest&quot; &lt;tree&gt;">"#
+		));
+		assert!(head.contains(
+			r#"<meta property="og:description" content="Heading Redwoods are very very"#
+		));
+		assert!(head.contains(&format!(
+			r#"<meta property="og:url" content="https://wiki.example.com/article/{}/the-tallest-tree">"#,
+			id
+		)));
+		assert!(head.contains(r#"<meta property="article:modified_time" content=""#));
+		assert!(head.contains(
+			r#"<script type="application/ld+json">{"@context":"https://schema.org","@type":"Article""#
+		));
+		assert!(head.contains(r#""headline":"The \"tallest\" \u003ctree>""#));
+		let description_start = head.find(r#"og:description" content=""#).unwrap();
+		let description = &head[description_start..];
+		let description = &description[..description.find("\">").unwrap()];
+		assert!(description.ends_with('…'));
+		// The heading of the page as well
+		let body = &html[html.find("<body>").unwrap()..];
+		assert!(body.contains("<h1>The &quot;tallest&quot; &lt;tree&gt; <span"));
+		assert!(!body.contains("<tree>"));
+
+		// Without public URL, the absolute URL is unknown
+		let html = body_of(article_page(db.clone(), test_config(), id).await.unwrap()).await;
+		assert!(!html.contains("og:url"));
+		assert!(html.contains("og:title"));
+
+		let html = body_of(article_page(db, config, 1000.into()).await.unwrap()).await;
+		assert!(!html.contains("og:"));
+		assert!(!html.contains("application/ld+json"));
+	}
+
+	#[tokio::test]
+	async fn test_article_slug_urls() {
+		let db = test_db("slug").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Große Bäume".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String| warp::test::request().path(&path).reply(&routes);
+
+		assert_eq!(get(format!("/article/{}", id)).await.status(), 200);
+		let response = get(format!("/article/{}/grosse-baume", id)).await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(&format!(
+			r#"<link rel="canonical" href="/article/{}/grosse-baume">"#,
+			id
+		)));
+
+		let response = get(format!("/article/{}/old-title", id)).await;
+		assert_eq!(response.status(), 301);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/grosse-baume", id)
+		);
+
+		// Renaming keeps the slug unless regeneration is requested
+		let post = |body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/article/{}", id))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+		let response = post("article_title=Redwoods").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/grosse-baume", id)
+		);
+		assert_eq!(
+			get(format!("/article/{}/grosse-baume", id)).await.status(),
+			200
+		);
+		let response = post("article_title=Redwoods&regenerate_slug=1").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/redwoods", id)
+		);
+		let response = get(format!("/article/{}/grosse-baume", id)).await;
+		assert_eq!(response.status(), 301);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/redwoods", id)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_title_redirect() {
+		let db = test_db("title-redirect").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Old <title>".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: &str| warp::test::request().path(path).reply(&routes);
+
+		let response = get("/wiki/Old%20%3Ctitle%3E").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/old-title", id)
+		);
+		let response = get("/wiki/old%20%3CTITLE%3E").await;
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/old-title", id)
+		);
+
+		let response = warp::test::request()
+			.method("POST")
+			.path(&format!("/article/{}", id))
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("article_title=New+title")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+
+		// The slug stays what it was
+		let response = get("/wiki/New%20title").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/old-title", id)
+		);
+		let response = get("/wiki/Old%20%3Ctitle%3E").await;
+		assert_eq!(response.status(), 303);
+		let location = response.headers()["location"].to_str().unwrap().to_string();
+		assert_eq!(
+			location,
+			format!(
+				"/article/{}/old-title?redirected_from=Old%20%3Ctitle%3E",
+				id
+			)
+		);
+
+		let body = String::from_utf8(get(&location).await.body().to_vec()).unwrap();
+		assert!(
+			body.contains(r#"<p class="redirect_note">(redirected from Old &lt;title&gt;)</p>"#)
+		);
+		let body =
+			String::from_utf8(get(&format!("/article/{}", id)).await.body().to_vec()).unwrap();
+		assert!(!body.contains(r#"<p class="redirect_note">"#));
+
+		let response = get("/wiki/Missing").await;
+		assert_eq!(response.status(), 404);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("There is no article titled \"Missing\"."));
+
+		// A slash in the title may be encoded or not, the missing page
+		// offers to create the article
+		let slashed = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "TCP/IP".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		for path in ["/wiki/TCP/IP", "/wiki/tcp%2Fip"] {
+			let response = get(path).await;
+			assert_eq!(response.status(), 303);
+			assert_eq!(
+				response.headers()["location"],
+				format!("/article/{}/tcp-ip", slashed)
+			);
+		}
+		let response = get("/wiki/UDP/IP%20%22v6%22").await;
+		assert_eq!(response.status(), 404);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains(r#"<form action="/create/article" method="post">"#));
+		assert!(body.contains(
+			r#"name="article_title" class="editor_input" value="UDP/IP &quot;v6&quot;">"#
+		));
+	}
+
+	#[tokio::test]
+	async fn test_tags() {
+		let db = test_db("tags").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: String| async move {
+			let response = warp::test::request().path(&path).reply(routes_ref).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let post = |body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/article/{}", id))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+
+		post("article_title=Redwood&article_tags=Trees%2C+Coast+%3Cred%3E+%2Ctrees").await;
+		let body = get(format!("/article/{}", id)).await;
+		assert!(body.contains(
+			r#"<p class="article_tags">Tags: <a href="/tag/coast%20%3Cred%3E" class="tag">coast &lt;red&gt;</a> <a href="/tag/trees" class="tag">trees</a></p>"#
+		));
+		assert!(get(format!("/edit/article/{}", id))
+			.await
+			.contains(r#"value="coast &lt;red&gt;, trees""#));
+
+		let body = get("/tag/Trees".to_string()).await;
+		assert!(body.contains(r#"<h2 style="margin-top: 0px;">Tag "trees"</h2>"#));
+		assert!(body.contains(&format!(
+			r#"<li><a href="/article/{}/redwood">Redwood</a>"#,
+			id
+		)));
+		assert!(get("/tag/coast%20%3Cred%3E".to_string())
+			.await
+			.contains(&format!(r#"<a href="/article/{}/redwood">Redwood</a>"#, id)));
+
+		// Saving without the tags field keeps the tags
+		assert_eq!(post("article_text=Taller").await.status(), 303);
+		assert_eq!(db.lock().await.get_tags(id).unwrap().len(), 2);
+
+		assert_eq!(post("article_tags=conifer").await.status(), 303);
+		assert_eq!(db.lock().await.get_tags(id).unwrap(), ["conifer"]);
+		assert!(get("/tag/trees".to_string())
+			.await
+			.contains(r#"No articles are tagged "trees"."#));
+
+		post("article_tags=+").await;
+		assert!(!get(format!("/article/{}", id))
+			.await
+			.contains(r#"<p class="article_tags">"#));
+	}
+
+	#[tokio::test]
+	async fn test_admin_tags() {
+		let db = test_db("admin-tags").await;
+		let mut ids = Vec::new();
+		for (title, tags) in [
+			("First", &["linux"][..]),
+			("Second", &["gnu-linux", "linux"][..]),
+			("Third", &["gnu-linux", "a&b"][..]),
+		] {
+			let mut db = db.lock().await;
+			let id = db
+				.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: String::new(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap();
+			for tag in tags {
+				db.add_tag(id, tag).unwrap();
+			}
+			ids.push(id);
+		}
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let post = |path: &'static str, body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(path)
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+		let body = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+
+		let page = body(
+			warp::test::request()
+				.path("/admin/tags")
+				.reply(&routes)
+				.await,
+		);
+		assert!(
+			page.contains("<td><a href=\"/tag/a%26b\">a&amp;b</a></td>\n\t\t\t\t\t\t<td>1</td>")
+		);
+		assert!(page
+			.contains("<td><a href=\"/tag/gnu-linux\">gnu-linux</a></td>\n\t\t\t\t\t\t<td>2</td>"));
+		assert!(page.contains(r#"<input type="hidden" name="from" value="a&amp;b">"#));
+
+		let response = post("/admin/tags/rename", "from=a%26b&to=Both").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(response.headers()["location"], "/admin/tags");
+		assert_eq!(
+			db.lock().await.get_tags(ids[2]).unwrap(),
+			["both", "gnu-linux"]
+		);
+
+		// Renaming to an existing tag offers to merge instead
+		let response = post("/admin/tags/rename", "from=gnu-linux&to=Linux").await;
+		assert_eq!(response.status(), 409);
+		let page = body(response);
+		assert!(page.contains("The tag &quot;linux&quot; already exists."));
+		assert!(page.contains(r#"<input type="hidden" name="into" value="linux">"#));
+		assert_eq!(
+			db.lock().await.get_tags(ids[1]).unwrap(),
+			["gnu-linux", "linux"]
+		);
+
+		let response = post("/admin/tags/merge", "from=gnu-linux&into=linux").await;
+		assert_eq!(response.status(), 303);
+		let mut db_guard = db.lock().await;
+		assert_eq!(db_guard.get_tags(ids[1]).unwrap(), ["linux"]);
+		assert_eq!(db_guard.get_tags(ids[2]).unwrap(), ["both", "linux"]);
+		assert_eq!(
+			db_guard.get_tag_counts().unwrap(),
+			[("both".to_string(), 1), ("linux".to_string(), 3)]
+		);
+		drop(db_guard);
+
+		// A case-only rename is accepted and changes nothing
+		let response = post("/admin/tags/rename", "from=linux&to=LINUX").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			db.lock().await.get_articles_by_tag("linux").unwrap().len(),
+			3
+		);
+
+		assert_eq!(
+			post("/admin/tags/merge", "from=gnu-linux&into=linux")
+				.await
+				.status(),
+			404
+		);
+		assert_eq!(
+			post("/admin/tags/rename", "from=both&to=+").await.status(),
+			404
+		);
+	}
+
+	#[tokio::test]
+	async fn test_categories() {
+		let db = test_db("categories").await;
+		let article = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Tomatoes".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let post = |path: String, body: String| {
+			warp::test::request()
+				.method("POST")
+				.path(&path)
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+		let get = |path: String| warp::test::request().path(&path).reply(&routes);
+		let body = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let create = |name: &'static str, parent: String| async move {
+			let response = post(
+				"/create/category".to_string(),
+				format!("name={}&parent={}", name, parent),
+			)
+			.await;
+			assert_eq!(response.status(), 303);
+			let location = response.headers()["location"].to_str().unwrap();
+			location
+				.strip_prefix("/category/")
+				.unwrap()
+				.parse::<ItemId>()
+				.unwrap()
+		};
+
+		let projects = create("Projects", String::new()).await;
+		let home = create("Home+%26+yard", projects.to_string()).await;
+		let garden = create("Garden", home.to_string()).await;
+		assert_eq!(
+			post("/create/category".to_string(), "name=+&parent=".to_string())
+				.await
+				.status(),
+			400
+		);
+		assert_eq!(
+			post(
+				"/create/category".to_string(),
+				"name=X&parent=99".to_string()
+			)
+			.await
+			.status(),
+			404
+		);
+		assert!(
+			body(get("/categories".to_string()).await).contains(&format!(
+				r#"<li><a href="/category/{}">Projects</a></li>"#,
+				projects
+			))
+		);
+
+		// The edit form offers all categories with their path
+		let page = body(get(format!("/edit/article/{}", article)).await);
+		assert!(page.contains(&format!(
+			r#"<option value="{}">Projects / Home &amp; yard / Garden</option>"#,
+			garden
+		)));
+		post(
+			format!("/article/{}", article),
+			format!("article_category={}", garden),
+		)
+		.await;
+		let response = get(format!("/article/{}", article)).await;
+		let breadcrumb = format!(
+			r#"<nav aria-label="Category" class="breadcrumb"><a href="/categories">Categories</a> › <a href="/category/{}">Projects</a> › <a href="/category/{}">Home &amp; yard</a> › <a href="/category/{}">Garden</a></nav>"#,
+			projects, home, garden
+		);
+		assert!(body(response).contains(&breadcrumb));
+		assert!(body(get(format!("/edit/article/{}", article)).await)
+			.contains(&format!(r#"<option value="{}" selected>"#, garden)));
+
+		let page = body(get(format!("/category/{}", home)).await);
+		assert!(page.contains(&format!(
+			r#"<a href="/category/{}">Projects</a> › <span aria-current="page">Home &amp; yard</span>"#,
+			projects
+		)));
+		assert!(page.contains(&format!(
+			r#"<li><a href="/category/{}">Garden</a></li>"#,
+			garden
+		)));
+		// Neither the category itself nor its descendants are offered
+		// as new parent
+		assert!(page.contains(&format!(
+			r#"<option value="{}" selected>Projects</option>"#,
+			projects
+		)));
+		assert!(!page.contains(&format!(r#"<option value="{}""#, garden)));
+		let page = body(get(format!("/category/{}", garden)).await);
+		assert!(page.contains(&format!(
+			r#"<li><a href="/article/{}/tomatoes">Tomatoes</a>"#,
+			article
+		)));
+
+		let response = post(
+			format!("/move/category/{}", projects),
+			format!("parent={}", garden),
+		)
+		.await;
+		assert_eq!(response.status(), 409);
+		assert!(body(response).contains("a category cannot be moved into itself"));
+		let response = post(format!("/move/category/{}", home), "parent=".to_string()).await;
+		assert_eq!(response.status(), 303);
+		let page = body(get(format!("/article/{}", article)).await);
+		assert!(page.contains(&format!(
+			r#"<a href="/categories">Categories</a> › <a href="/category/{}">Home &amp; yard</a> › <a href="/category/{}">Garden</a></nav>"#,
+			home, garden
+		)));
+		assert_eq!(get("/category/99".to_string()).await.status(), 404);
+
+		// Taking the article out of its category removes the breadcrumb
+		let response = post(
+			format!("/article/{}", article),
+			"article_category=".to_string(),
+		)
+		.await;
+		assert!(!body(response).contains(r#"class="breadcrumb""#));
+	}
+
+	#[test]
+	fn test_nav_links() {
+		let config: Config = toml::from_str(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+
+			[[site.nav_links]]
+			label = "Issues <open>"
+			url = "https://issues.example.com/?state=open&sort=new"
+
+			[[site.nav_links]]
+			label = "Monitoring"
+			url = "https://monitoring.example.com"
+			section = "Operations"
+
+			[[site.nav_links]]
+			label = "Help"
+			url = "/wiki/Help"
+			section = "Wiki"
+
+			[[site.nav_links]]
+			label = "Runbooks"
+			url = "/wiki/Runbooks"
+			section = "Operations"
+
+			[[site.nav_links]]
+			label = "Status"
+			url = "https://status.example.com"
+			section = "Links & more"
+			"#,
+		)
+		.unwrap();
+		let menu = generate_menu(&config, None, Some(3.into()), None);
+		// Position of each part in the menu, which must be increasing
+		let positions: Vec<usize> = [
+			r#"<li><a href="/articles">All articles</a></li>"#,
+			r#"<li><a href="https://issues.example.com/?state=open&amp;sort=new" rel="noopener">Issues &lt;open&gt;</a></li>"#,
+			"Wiki:",
+			r#"<li><a href="/about">About</a></li>"#,
+			r#"<li><a href="/wiki/Help">Help</a></li>"#,
+			"Current article:",
+			"Operations:",
+			r#"<li><a href="https://monitoring.example.com" rel="noopener">Monitoring</a></li>"#,
+			r#"<li><a href="/wiki/Runbooks">Runbooks</a></li>"#,
+			"Links &amp; more:",
+			r#"<li><a href="https://status.example.com" rel="noopener">Status</a></li>"#,
+			"</nav>",
+		]
+		.iter()
+		.map(|part| menu.find(part).unwrap_or_else(|| panic!("{} missing", part)))
+		.collect();
+		assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+		assert_eq!(menu.matches("Operations:").count(), 1);
+		assert!(!menu.contains("<open>"));
+
+		// Without links, the menu is the same as before they existed
+		assert_eq!(
+			generate_menu(&test_config(), None, Some(3.into()), None),
+			generate_menu(
+				&Config {
+					site: config::Site::default(),
+					..config
+				},
+				None,
+				Some(3.into()),
+				None
+			)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_backlinks() {
+		let db = test_db("backlinks").await;
+		let mut ids = Vec::new();
+		for title in ["Redwood", "Forest <old>"] {
+			ids.push(
+				db.lock()
+					.await
+					.create_article(&Article {
+						id: 0.into(),
+						title: title.to_string(),
+						text: String::new(),
+						date_created: UtcTimestamp::now(),
+						date_modified: UtcTimestamp::now(),
+						revision: 0,
+						slug: String::new(),
+					})
+					.unwrap(),
+			);
+		}
+		let (redwood, forest) = (ids[0], ids[1]);
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: String| async move {
+			let response = warp::test::request().path(&path).reply(routes_ref).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let save = |text: String| async move {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/article/{}", forest))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(format!("article_text={}", text))
+				.reply(routes_ref)
+				.await
+		};
+
+		let body = get(format!("/article/{}", redwood)).await;
+		assert!(body.contains(&format!(
+			r#"[edit]</a> <a href="/links/article/{}" class="backlink_count" aria-label="What links here">[0 links here]</a></h1>"#,
+			redwood
+		)));
+		assert!(get(format!("/links/article/{}", redwood))
+			.await
+			.contains("<p>No article links here.</p>"));
+
+		save(format!(
+			"See+%5Barticle%3A{0}%5D+and+%5Barticle%3A{0}%7Ctall%5D",
+			redwood
+		))
+		.await;
+		assert!(get(format!("/article/{}", redwood))
+			.await
+			.contains("[1 link here]</a></h1>"));
+		let body = get(format!("/links/article/{}", redwood)).await;
+		assert!(body.contains(&format!(
+			r#"<p>Articles that reference <a href="/article/{}">Redwood</a>:</p>"#,
+			redwood
+		)));
+		assert!(body.contains(&format!(
+			r#"<li><a href="/article/{}/forest-old">Forest &lt;old&gt;</a>"#,
+			forest
+		)));
+
+		// Saving without the reference removes the link
+		save("No+more+links".to_string()).await;
+		assert!(get(format!("/links/article/{}", redwood))
+			.await
+			.contains("<p>No article links here.</p>"));
+		// A title change alone keeps the links
+		save(format!("%5Barticle%3A{}%5D", redwood)).await;
+		warp::test::request()
+			.method("POST")
+			.path(&format!("/article/{}", forest))
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("article_title=Forest")
+			.reply(&routes)
+			.await;
+		assert_eq!(db.lock().await.count_backlinks(redwood), Some(1));
+
+		assert!(get("/links/article/1000".to_string())
+			.await
+			.contains("Could not find article #1000!"));
+	}
+
+	#[tokio::test]
+	async fn test_article_etag() {
+		let db = test_db("article-etag").await;
+		let new_article = |title: &str, text: String| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text,
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let sequoia = db
+			.lock()
+			.await
+			.create_article(&new_article("Sequoia", String::new()))
+			.unwrap();
+		let redwood = db
+			.lock()
+			.await
+			.create_article(&new_article(
+				"Redwood",
+				format!("See [article:{}]", sequoia),
+			))
+			.unwrap();
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String, if_none_match: Option<String>| {
+			let mut request = warp::test::request().path(&path);
+			if let Some(etag) = if_none_match {
+				request = request.header("if-none-match", etag);
+			}
+			request.reply(&routes)
+		};
+		let path = format!("/article/{}", redwood);
+
+		let response = get(path.clone(), None).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["cache-control"], "no-cache");
+		let etag = response.headers()["etag"].to_str().unwrap().to_string();
+		let response = get(path.clone(), Some(etag.clone())).await;
+		assert_eq!(response.status(), 304);
+		assert!(response.body().is_empty());
+		assert_eq!(response.headers()["etag"], etag.as_str());
+		// Other pages and representations have other tags
+		let sequoia_etag = get(format!("/article/{}", sequoia), None).await.headers()["etag"]
+			.to_str()
+			.unwrap()
+			.to_string();
+		assert_ne!(sequoia_etag, etag);
+		let response = get(format!("{}?redirected_from=Old", path), Some(etag.clone())).await;
+		assert_eq!(response.status(), 200);
+
+		// Renaming the referenced article changes the text of the link
+		let response = warp::test::request()
+			.method("POST")
+			.path(&format!("/article/{}", sequoia))
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body("article_title=Giant+sequoia")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		let response = get(path.clone(), Some(etag.clone())).await;
+		assert_eq!(response.status(), 200);
+		assert_ne!(response.headers()["etag"], etag.as_str());
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains(">Giant sequoia</a>"));
+
+		// Writes through another connection, e.g. an import from the
+		// command line while the server runs
+		let etag = response.headers()["etag"].to_str().unwrap().to_string();
+		let mut other = DatabaseConnection::new(
+			&test_db_path("article-etag"),
+			database::OpenMode::OpenExisting,
+		)
+		.unwrap()
+		.init()
+		.unwrap();
+		other
+			.update_article(redwood, None, Some("Tall"), None, None)
+			.unwrap();
+		drop(other);
+		let response = get(path.clone(), Some(etag.clone())).await;
+		assert_eq!(response.status(), 200);
+		assert_ne!(response.headers()["etag"], etag.as_str());
+	}
+
+	#[tokio::test]
+	async fn test_search_fts() {
+		let db = test_db("search_fts").await;
+		for (title, text) in [("Forest", "A redwood grows here"), ("Redwood", "Tall")] {
+			db.lock()
+				.await
+				.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: text.to_string(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap();
+		}
+		let routes = routes(db.clone(), test_config(), test_dictionary());
+		let search = |term: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path("/search/article")
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(format!("search_term_plain={}", term))
+				.reply(&routes)
+		};
+
+		let response = search("redwood").await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains("<b style=\"color:red;\">Redwood</b>"));
+		assert!(body.contains("Forest"));
+
+		// FTS syntax in the term is taken literally
+		for term in ["%22redwood", "redwood+OR+%28", "%2A"] {
+			let response = search(term).await;
+			assert_eq!(response.status(), 200);
+			let body = String::from_utf8(response.body().to_vec()).unwrap();
+			assert!(!body.contains("Could not fetch the articles."));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_reads_busy() {
+		let path = test_db_path("reads-busy");
+		let _ = std::fs::remove_file(&path);
+		// Readers never wait in WAL mode
+		let settings = database::ConnectionSettings {
+			journal_mode: database::JournalMode::Delete,
+			busy_timeout: std::time::Duration::ZERO,
+			..database::ConnectionSettings::default()
+		};
+		let mut db =
+			DatabaseConnection::with_settings(&path, database::OpenMode::CreateNew, &settings)
+				.unwrap()
+				.init()
+				.unwrap();
+		db.create_article(&Article {
+			id: 0.into(),
+			title: "Redwood".to_string(),
+			text: "Tall".to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		})
+		.unwrap();
+		let db = Arc::new(DbLock::new(db, std::time::Duration::from_millis(100)));
+		let routes = routes(db, test_config(), test_dictionary());
+		let paths = [
+			"/articles",
+			"/search/article?search_term_plain=redwood",
+			"/search/article?search_term_plain=%2A",
+			"/wiki/Redwood",
+		];
+
+		// Another process holds the database
+		let other = rusqlite::Connection::open(&path).unwrap();
+		other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+		for path in paths {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 503, "{}", path);
+		}
+		other.execute_batch("ROLLBACK").unwrap();
+		for path in paths {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_ne!(response.status(), 503, "{}", path);
+			assert!(response.status().as_u16() < 400, "{}", path);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_favicon_api() {
+		let db = test_db("favicon").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Links".to_string(),
+				text: "See https://example.com/redwoods".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+
+		// Disabled by default
+		let disabled = routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request()
+			.path("/api/favicon?host=example.com")
+			.reply(&disabled)
+			.await;
+		assert!(response.status().is_client_error());
+		let page = body_of(article_page(db.clone(), test_config(), id).await.unwrap()).await;
+		assert!(!page.contains(r#"<img class="extlink-icon""#));
+
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				outbound_requests = true
+
+				[database]
+				storage_location = "."
+
+				[render]
+				external_link_icons = true
+				"#,
+			)
+			.unwrap(),
+		);
+		let page = body_of(article_page(db.clone(), config.clone(), id).await.unwrap()).await;
+		assert!(page.contains(
+			r#"<img class="extlink-icon" src="/api/favicon?host=example.com:443" alt="">"#
+		));
+
+		let enabled = routes(db.clone(), config, test_dictionary());
+		// Invalid hosts get the generic icon without any request
+		for host in [
+			"127.0.0.1",
+			"localhost",
+			"example.com:8080",
+			"%5B%3A%3A1%5D",
+		] {
+			let response = warp::test::request()
+				.path(&format!("/api/favicon?host={}", host))
+				.reply(&enabled)
+				.await;
+			assert_eq!(response.status(), 200);
+			assert_eq!(response.headers()["content-type"], "image/svg+xml");
+			assert_eq!(response.body(), favicon::GENERIC_ICON.as_bytes());
+		}
+
+		// Cached icons are served from the database
+		let icon = favicon::Icon {
+			content_type: "image/png".to_string(),
+			data: vec![0x89, b'P', b'N', b'G'],
+		};
+		db.lock()
+			.await
+			.store_favicon("example.com", Some(&icon), 10);
+		let response = warp::test::request()
+			.path("/api/favicon?host=Example.com")
+			.reply(&enabled)
+			.await;
+		assert_eq!(response.headers()["content-type"], "image/png");
+		assert_eq!(response.body(), &icon.data[..]);
+		// HTTPS hosts are cached separately
+		db.lock().await.store_favicon("example.com:443", None, 10);
+		let response = warp::test::request()
+			.path("/api/favicon?host=example.com:443")
+			.reply(&enabled)
+			.await;
+		assert_eq!(response.body(), favicon::GENERIC_ICON.as_bytes());
+	}
+
+	#[tokio::test]
+	async fn test_config_refs_warnings() {
+		let db = test_db("config-refs").await;
+		let mut ids = Vec::new();
+		for title in ["Journal template", "Sequoia", "Oak"] {
+			ids.push(
+				db.lock()
+					.await
+					.create_article(&Article {
+						id: 0.into(),
+						title: title.to_string(),
+						text: String::new(),
+						date_created: UtcTimestamp::now(),
+						date_modified: UtcTimestamp::now(),
+						revision: 0,
+						slug: String::new(),
+					})
+					.unwrap(),
+			);
+		}
+		let (template, sequoia, oak) = (ids[0], ids[1], ids[2]);
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(&format!(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[database]
+				storage_location = "."
+
+				[journal]
+				template_article = {}
+
+				[[site.nav_links]]
+				label = "Trees"
+				url = "/wiki/Sequoia"
+				"#,
+				template
+			))
+			.unwrap(),
+		);
+		let routes = routes(db.clone(), config, test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: String| async move {
+			let response = warp::test::request().path(&path).reply(routes_ref).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+
+		assert!(get(format!("/delete/article/{}", template)).await.contains(
+			r#"<p class="config_note">This article is configured as <code>journal.template_article</code>. Features using it stop working while it is deleted.</p>"#
+		));
+		assert!(get(format!("/delete/article/{}", sequoia))
+			.await
+			.contains("This article is configured as <code>site.nav_links[0].url</code>."));
+		assert!(!get(format!("/delete/article/{}", oak))
+			.await
+			.contains(r#"<p class="config_note">"#));
+
+		// Only references by title are affected by renaming
+		assert!(get(format!("/edit/article/{}", sequoia)).await.contains(
+			r#"<p class="config_note">This article is configured as <code>site.nav_links[0].url</code>. After renaming"#
+		));
+		assert!(!get(format!("/edit/article/{}", template))
+			.await
+			.contains(r#"<p class="config_note">"#));
+	}
+
+	#[tokio::test]
+	async fn test_rotate_logs() {
+		let db = test_db("rotate-logs").await;
+		let post = |config: Arc<Config>| {
+			let routes = super::routes(db.clone(), config, test_dictionary());
+			async move {
+				warp::test::request()
+					.method("POST")
+					.path("/admin/rotate-logs")
+					.reply(&routes)
+					.await
+			}
+		};
+		// Without a log file there is nothing to rotate
+		assert_eq!(post(test_config()).await.status(), 404);
+
+		let mut path = std::env::temp_dir();
+		path.push(format!("redwood-wiki-test-main-{}.log", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(&format!(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[database]
+				storage_location = "."
+
+				[log]
+				file = {:?}
+				"#,
+				path
+			))
+			.unwrap(),
+		);
+		log_file::LOG_FILE.open(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		let response = post(config).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.body(), "Log file reopened\n");
+		// Created again
+		assert!(std::fs::read_to_string(&path)
+			.unwrap()
+			.contains("Log file reopened"));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_feed() {
+		let db = test_db("feed").await;
+		let mut ids = Vec::new();
+		for title in ["Trees & <shrubs>", "Old", "Deleted"] {
+			ids.push(
+				db.lock()
+					.await
+					.create_article(&Article {
+						id: 0.into(),
+						title: title.to_string(),
+						text: format!("About *{}*.", title),
+						date_created: UtcTimestamp::now(),
+						date_modified: UtcTimestamp::now(),
+						revision: 0,
+						slug: String::new(),
+					})
+					.unwrap(),
+			);
+		}
+		db.lock()
+			.await
+			.update_article(ids[0], None, Some("About *trees* & shrubs."), None, None)
+			.unwrap();
+		db.lock().await.delete_article(ids[2]).unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request().path("/feed.xml").reply(&routes).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/atom+xml");
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		let new = body
+			.find("<title>Trees &amp; &lt;shrubs&gt;</title>")
+			.unwrap();
+		let old = body.find("<title>Old</title>").unwrap();
+		assert!(new < old, "most recently modified first");
+		assert!(body.contains("<summary>About trees &amp; shrubs.</summary>"));
+		assert!(!body.contains("Deleted"));
+
+		// Discoverable from every page
+		let response = warp::test::request().path("/about").reply(&routes).await;
+		assert!(String::from_utf8(response.body().to_vec()).unwrap().contains(
+			r#"<link rel="alternate" type="application/atom+xml" title="Recently changed articles" href="/feed.xml">"#
+		));
+	}
+
+	#[tokio::test]
+	async fn test_custom_css() {
+		let db = test_db("custom-css").await;
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-custom-css-route-{}.css",
+			std::process::id()
+		));
+		std::fs::write(&path, "body { color: #c0ffee; }").unwrap();
+		let config: Config = toml::from_str(&format!(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[appearance]
+			custom_css = {:?}
+			"#,
+			path
+		))
+		.unwrap();
+		let routes = super::routes(db.clone(), Arc::new(config), test_dictionary());
+
+		let response = warp::test::request().path("/about").reply(&routes).await;
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		let link_start = body
+			.find(r#"<link rel="stylesheet" href="/css/custom.css?v="#)
+			.unwrap();
+		assert!(
+			body.find("</style>").unwrap() < link_start,
+			"after the built-in styles"
+		);
+		assert!(!body.contains("#c0ffee"), "not inlined");
+		let href = body[link_start..].split('"').nth(3).unwrap().to_string();
+
+		let response = warp::test::request().path(&href).reply(&routes).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.headers()["content-type"],
+			"text/css; charset=utf-8"
+		);
+		assert_eq!(response.headers()["cache-control"], "public, max-age=86400");
+		assert_eq!(&response.body()[..], b"body { color: #c0ffee; }");
+		let etag = response.headers()["etag"].to_str().unwrap().to_string();
+		let response = warp::test::request()
+			.path(&href)
+			.header("if-none-match", etag)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 304);
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let article_path = format!("/article/{}", id);
+		let response = warp::test::request()
+			.path(&article_path)
+			.reply(&routes)
+			.await;
+		let article_etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+		// Without the file, the built-in styles remain
+		std::fs::remove_file(&path).unwrap();
+		let response = warp::test::request().path("/about").reply(&routes).await;
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(!body.contains("/css/custom.css"));
+		// Cached article pages would still link the stylesheet
+		let response = warp::test::request()
+			.path(&article_path)
+			.header("if-none-match", article_etag)
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		let response = warp::test::request()
+			.path("/css/custom.css")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 404);
+
+		let routes = super::routes(db, test_config(), test_dictionary());
+		let response = warp::test::request()
+			.path("/css/custom.css")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 404);
+	}
+
+	#[tokio::test]
+	async fn test_wiki_name() {
+		let db = test_db("wiki-name").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood <tall>".to_string(),
+				text: "Tall.".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let get = |routes, path: String| async move {
+			let response = warp::test::request().path(&path).reply(&routes).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let body = get(routes.clone(), format!("/article/{}", id)).await;
+		assert!(body.contains("<title>Redwood &lt;tall&gt; — Redwood Wiki</title>"));
+		let body = get(routes, "/about".to_string()).await;
+		assert!(body.contains("<title>Redwood Wiki</title>"));
+
+		let config: Config = toml::from_str(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[wiki]
+			name = "Grove & Co"
+			"#,
+		)
+		.unwrap();
+		let routes = super::routes(db, Arc::new(config), test_dictionary());
+		let body = get(routes.clone(), format!("/article/{}", id)).await;
+		assert!(body.contains("<title>Redwood &lt;tall&gt; — Grove &amp; Co</title>"));
+		assert!(body.contains(" Grove &amp; Co</header>"));
+		let body = get(routes.clone(), "/".to_string()).await;
+		assert!(body.contains("<title>Grove &amp; Co</title>"));
+		assert!(body.contains(r#"<h2 style="margin-top: 0px;">Grove &amp; Co</h2>"#));
+		assert!(!body.contains("Redwood Wiki"));
+		let body = get(routes, "/feed.xml".to_string()).await;
+		assert!(body.contains("\t<title>Grove &amp; Co</title>\n"));
+	}
+
+	#[tokio::test]
+	async fn test_edit_summary() {
+		let db = test_db("edit-summary").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: String| async move {
+			let response = warp::test::request().path(&path).reply(routes_ref).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let post = |body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/article/{}", id))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+
+		assert!(get(format!("/edit/article/{}", id)).await.contains(
+			r#"<input type="text" id="edit_summary" name="edit_summary" class="editor_input" maxlength="200" placeholder="optional">"#
+		));
+		let response = post("article_text=Taller&edit_summary=Fix+%3Cheight%3E").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(post("article_text=Tallest").await.status(), 303);
+
+		let body = get(format!("/history/article/{}", id)).await;
+		let revision = |n: i64| format!("<li><a href=\"/article/{}?rev={}\">", id, n);
+		let newest = body.find(&revision(2)).unwrap();
+		let older = body.find(&revision(1)).unwrap();
+		let created = body.find(&revision(0)).unwrap();
+		assert!(newest < older && older < created, "newest first");
+		assert!(body[older..].starts_with(&format!(
+			"{}Revision 1</a> <span class=\"history_date\">",
+			revision(1)
+		)));
+		assert!(body[older..]
+			.contains("</span> <span class=\"size_delta\">(+2 bytes)</span> <span class=\"badge_edited\">edited</span> Fix &lt;height&gt; <form action="));
+		assert!(body[newest..older].contains(
+			"(+1 bytes)</span> <span class=\"badge_edited\">edited</span> \u{2014}</li>"
+		));
+		assert!(body[created..].starts_with(&format!(
+			"{}Created</a> <span class=\"history_date\">",
+			revision(0)
+		)));
+		assert!(body[created..]
+			.contains("</span> <span class=\"size_delta\">(+4 bytes)</span> <form action="));
+		// The current revision cannot be restored
+		assert!(body[newest..older].contains("\u{2014}</li>"));
+		assert!(body.contains(&format!(
+			r#"<a href="/history/article/{}" aria-current="page">History</a>"#,
+			id
+		)));
+		assert!(get("/history/article/99".to_string())
+			.await
+			.contains("Could not find article #99!"));
+
+		// The feed shows the summary of the last edit only
+		post("article_text=Tallest+tree&edit_summary=Wording").await;
+		let feed = get("/feed.xml".to_string()).await;
+		assert!(feed.contains("<summary>Wording \u{2014} Tallest tree</summary>"));
+	}
+
+	#[tokio::test]
+	async fn test_article_revision_view() {
+		let db = test_db("article-revision-view").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "# Tall\n\nThe first text".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		db.lock()
+			.await
+			.update_article(id, None, Some("# Taller\n\nThe second text"), None, None)
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String| {
+			let routes = &routes;
+			async move { warp::test::request().path(&path).reply(routes).await }
+		};
+
+		let response = get(format!("/article/{}?rev=0", id)).await;
+		assert_eq!(response.status(), 200);
+		let etag = response.headers()["etag"].clone();
+		let old = body_of(response).await;
+		assert_landmarks(&old);
+		assert!(old.contains(&format!(
+			"<p class=\"revision_note\">You are viewing revision 0. <a href=\"/article/{}/redwood\">View the current version</a>.</p>",
+			id
+		)));
+		assert!(old.contains("<p>The first text</p>"));
+		assert!(!old.contains("The second text"));
+		assert!(!old.contains("class=\"section_edit\""));
+		assert!(old.contains("<meta name=\"robots\" content=\"noindex\">"));
+
+		// Unknown revisions fall back to the current text
+		let current = get(format!("/article/{}", id)).await;
+		assert_ne!(current.headers()["etag"], etag);
+		let current = body_of(current).await;
+		for query in ["?rev=7", "?rev=first", "?rev="] {
+			let body = body_of(get(format!("/article/{}{}", id, query)).await).await;
+			assert!(body.contains("<p>The second text</p>"));
+			assert!(!body.contains("class=\"revision_note\""));
+		}
+		assert!(current.contains("class=\"section_edit\""));
+		assert!(!current.contains("noindex"));
+	}
+
+	#[tokio::test]
+	async fn test_revision_restore() {
+		let db = test_db("revision-restore").await;
+		let new_article = |title: &str, text: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: text.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let id = db
+			.lock()
+			.await
+			.create_article(&new_article("Redwood", "Tall"))
+			.unwrap();
+		db.lock()
+			.await
+			.update_article(id, Some("Coast redwood"), Some("Taller"), None, None)
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let restore = |body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/restore/article/{}", id))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+
+		// The current revision and unknown ones are refused
+		let response = restore("revision=1").await;
+		assert_eq!(response.status(), 409);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("Revision 1 is the current one already."));
+		assert_eq!(restore("revision=7").await.status(), 404);
+		assert_eq!(restore("revision=first").await.status(), 400);
+
+		let response = restore("revision=0").await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}/redwood", id).as_str()
+		);
+		let article = db.lock().await.get_article(id).unwrap().unwrap();
+		assert_eq!(
+			(
+				article.title.as_str(),
+				article.text.as_str(),
+				article.revision
+			),
+			("Redwood", "Tall", 2)
+		);
+		let revisions = db.lock().await.get_revisions(id).unwrap();
+		assert_eq!(revisions.len(), 3);
+		assert_eq!(revisions[0].summary, "Restored revision 0");
+
+		// Another article took the title of revision 1 in the meantime
+		db.lock()
+			.await
+			.create_article(&new_article("Coast redwood", "Other"))
+			.unwrap();
+		let response = restore("revision=1").await;
+		assert_eq!(response.status(), 409);
+		assert!(String::from_utf8(response.body().to_vec())
+			.unwrap()
+			.contains("The title &quot;Coast redwood&quot; of revision 1 belongs to article"));
+		assert_eq!(
+			db.lock().await.get_article(id).unwrap().unwrap().revision,
+			2
+		);
+	}
+
+	#[tokio::test]
+	async fn test_edit_conflict() {
+		let db = test_db("edit-conflict").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let post = |body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&format!("/article/{}", id))
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+
+		let edit = warp::test::request()
+			.path(&format!("/edit/article/{}", id))
+			.reply(&routes)
+			.await;
+		assert!(body_of(edit)
+			.await
+			.contains(r#"<input type="hidden" name="base_revision" value="0">"#));
+
+		// Two tabs started from revision 0, the first one saves
+		let first = post("article_text=Taller&base_revision=0").await;
+		assert_eq!(first.status(), 303);
+		let second =
+			post("article_text=Tallest+%3Ctree%3E&article_tags=conifer&base_revision=0").await;
+		assert_eq!(second.status(), 409);
+		let page = body_of(second).await;
+		assert_landmarks(&page);
+		assert!(page.contains("Your changes were not saved."));
+		assert!(page.contains(r#"<input type="hidden" name="base_revision" value="1">"#));
+		assert!(page.contains(r#"<input type="hidden" name="article_tags" value="conifer">"#));
+		assert!(page.contains(r#"class="editor_textarea">Tallest &lt;tree&gt;</textarea>"#));
+		assert!(page.contains(r#"readonly aria-label="Current text">Taller</textarea>"#));
+		assert!(page.contains(&format!(
+			r#"<a href="/edit/article/{}">Back to editing</a>"#,
+			id
+		)));
+		{
+			let mut db = db.lock().await;
+			let article = db.get_article(id).unwrap().unwrap();
+			assert_eq!((article.text.as_str(), article.revision), ("Taller", 1));
+			assert!(db.get_tags(id).unwrap().is_empty());
+		}
+
+		// Overwriting sends the form again with the current revision
+		let overwrite =
+			post("article_text=Tallest+%3Ctree%3E&article_tags=conifer&base_revision=1").await;
+		assert_eq!(overwrite.status(), 303);
+		let mut db = db.lock().await;
+		let article = db.get_article(id).unwrap().unwrap();
+		assert_eq!(
+			(article.text.as_str(), article.revision),
+			("Tallest <tree>", 2)
+		);
+		assert_eq!(db.get_tags(id).unwrap(), ["conifer"]);
+		drop(db);
+
+		// Forms without the field save as before
+		assert_eq!(post("article_text=Short").await.status(), 303);
+	}
+
+	#[tokio::test]
+	async fn test_missing_form_fields() {
+		let db = test_db("missing-form-fields").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let post = |path: String, body: &'static str| {
+			warp::test::request()
+				.method("POST")
+				.path(&path)
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+
+		for (path, body, message) in [
+			(
+				"/create/article".to_string(),
+				"",
+				"The form lacks the field article_title",
+			),
+			(
+				"/create/article".to_string(),
+				"article_text=Tall",
+				"The form lacks the field article_title",
+			),
+			(
+				"/create/article".to_string(),
+				"article_title=",
+				"The title of the new article is empty.",
+			),
+			(format!("/article/{}", id), "", "The form changes nothing"),
+			(
+				format!("/article/{}", id),
+				"edit_summary=Nothing&base_revision=0",
+				"The form changes nothing",
+			),
+			(
+				format!("/article/{}", id),
+				"article_title=&article_text=Taller",
+				"The title must not be empty.",
+			),
+			(
+				format!("/article/{}", id),
+				"article_title=+%09+",
+				"The title must not be empty.",
+			),
+			(
+				"/create/article".to_string(),
+				"article_title=+",
+				"The title of the new article is empty.",
+			),
+		] {
+			let response = post(path.clone(), body).await;
+			assert_eq!(response.status(), 400, "{} {}", path, body);
+			let page = body_of(response).await;
+			assert_landmarks(&page);
+			assert!(page.contains(message), "{} {}", path, body);
+		}
+		let article = db.lock().await.get_article(id).unwrap().unwrap();
+		assert_eq!(
+			(article.title.as_str(), article.text.as_str()),
+			("Redwood", "Tall")
+		);
+		assert_eq!(db.lock().await.count_articles().unwrap(), 1);
+
+		// A title that differs from another one only in case
+		let response = post("/create/article".to_string(), "article_title=Sequoia").await;
+		let other: ItemId = response.headers()["location"].to_str().unwrap()[9..]
+			.parse()
+			.unwrap();
+		let response = post(format!("/article/{}", other), "article_title=+REDWOOD").await;
+		assert_eq!(response.status(), 400);
+		assert!(body_of(response)
+			.await
+			.contains("Another article already has this title"));
+		assert_eq!(
+			db.lock().await.get_article(other).unwrap().unwrap().title,
+			"Sequoia"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_create_similar_title() {
+		let db = test_db("similar-title").await;
+		let existing = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Server setup".to_string(),
+				text: String::new(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let post = |body: String| {
+			warp::test::request()
+				.method("POST")
+				.path("/create/article")
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(&routes)
+		};
+		let count = || async { db.lock().await.get_all_titles().unwrap().len() };
+
+		// Nothing similar, created right away
+		let response = post("article_title=Backup+%3Cdaily%3E".to_string()).await;
+		assert_eq!(response.status(), 301);
+		assert_eq!(count().await, 2);
+
+		let response = post("article_title=Server+Setup+%3Cnotes%3E".to_string()).await;
+		assert_eq!(response.status(), 200);
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(body.contains("Did you mean one of these existing articles?"));
+		assert!(body.contains(&format!(
+			"<li><a href=\"/article/{}/server-setup\">Server setup</a></li>",
+			existing
+		)));
+		assert!(body.contains(
+			r#"<input type="hidden" name="article_title" value="Server Setup &lt;notes&gt;">"#
+		));
+		assert!(body.contains(r#"value="Create &quot;Server Setup &lt;notes&gt;&quot; anyway">"#));
+		assert_inputs_labelled(&body);
+		assert_eq!(count().await, 2);
+		let token = Regex::new(r#"name="confirm" value="([0-9a-f]{40})""#)
+			.unwrap()
+			.captures(&body)
+			.unwrap()[1]
+			.to_string();
+
+		// A forged token, or one for another title, asks again
+		for body in [
+			"article_title=Server+Setup+%3Cnotes%3E&confirm=0000".to_string(),
+			format!("article_title=Server+setups&confirm={}", token),
+		] {
+			let response = post(body).await;
+			assert_eq!(response.status(), 200);
+			assert_eq!(count().await, 2);
+		}
+
+		let response = post(format!(
+			"article_title=Server+Setup+%3Cnotes%3E&confirm={}",
+			token
+		))
+		.await;
+		assert_eq!(response.status(), 301);
+		let id = db
+			.lock()
+			.await
+			.get_article_id_by_title("Server Setup <notes>")
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			response.headers()["location"],
+			format!("/article/{}", id).as_str()
+		);
+
+		// The same title again, confirmed or not
+		for body in [
+			"article_title=Server+setup".to_string(),
+			format!(
+				"article_title=Server+setup&confirm={}",
+				similar_titles::ConfirmKey::random().token("Server setup")
+			),
+		] {
+			let response = post(body).await;
+			assert_eq!(response.status(), 409);
+			let body = body_of(response).await;
+			assert_landmarks(&body);
+			assert!(body.contains(&format!(
+				"The article <a href=\"/article/{}/server-setup\">Server setup</a> already has this title.",
+				existing
+			)));
+			assert!(
+				body.contains(r#"name="article_title" class="editor_input" value="Server setup">"#)
+			);
+		}
+		db.lock().await.delete_article(existing).unwrap();
+		let body = body_of(post("article_title=Server+setup".to_string()).await).await;
+		assert!(body.contains(
+			"The article <a href=\"/trash\">Server setup</a> in the trash already has this title."
+		));
+		assert_eq!(count().await, 2);
+	}
+
+	#[tokio::test]
+	async fn test_export() {
+		let db = test_db("export").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Coast redwood".to_string(),
+				text: "Tall.".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request()
+			.path("/admin/export.json")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/json");
+		assert_eq!(
+			response.headers()["content-disposition"],
+			r#"attachment; filename="redwood-wiki-export.json""#
+		);
+		let export: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(export["format_version"], database::EXPORT_FORMAT_VERSION);
+		assert_eq!(
+			export["articles"][0]["id"],
+			serde_json::to_value(id).unwrap()
+		);
+		assert_eq!(export["articles"][0]["text"], "Tall.");
+	}
+
+	#[tokio::test]
+	async fn test_raw_article() {
+		let db = test_db("raw-article").await;
+		let text = "# Coast redwood\n\n<script>alert(1)</script>\r\nTall.";
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Coast redwood".to_string(),
+				text: text.to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request()
+			.path(&format!("/raw/article/{}", id))
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.headers()["content-type"],
+			"text/plain; charset=utf-8"
+		);
+		assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+		assert_eq!(
+			response.headers()["content-disposition"],
+			r#"inline; filename="coast-redwood.md""#
+		);
+		// Verbatim, as stored (carriage returns are filtered on save)
+		assert_eq!(
+			response.body(),
+			&db.lock().await.get_article(id).unwrap().unwrap().text
+		);
+		assert!(response.body().starts_with(b"# Coast redwood\n\n<script>"));
+
+		// Revalidated with the revision of the article
+		let etag = response.headers()["etag"].to_str().unwrap().to_string();
+		assert_eq!(etag, format!("\"{}-0\"", id));
+		assert_eq!(response.headers()["cache-control"], "no-cache");
+		let get = |etag: String| {
+			warp::test::request()
+				.path(&format!("/raw/article/{}", id))
+				.header("if-none-match", etag)
+				.reply(&routes)
+		};
+		let response = get(etag.clone()).await;
+		assert_eq!(response.status(), 304);
+		assert_eq!(response.headers()["etag"], etag.as_str());
+		assert!(response.body().is_empty());
+		assert_eq!(get(format!("W/{}", etag)).await.status(), 304);
+		db.lock()
+			.await
+			.update_article(id, None, Some("Taller."), None, None)
+			.unwrap();
+		let response = get(etag.clone()).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["etag"], format!("\"{}-1\"", id).as_str());
+		assert_eq!(response.body(), "Taller.");
+
+		let response = warp::test::request()
+			.path("/raw/article/1000")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 404);
+		assert_eq!(
+			response.headers()["content-type"],
+			"text/plain; charset=utf-8"
+		);
+		assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+		assert_eq!(response.body(), "Article #1000 does not exist\n");
+	}
+
+	#[tokio::test]
+	async fn test_error_pages() {
+		let db = test_db("error-pages").await;
+		let routes = routes(db, test_config(), test_dictionary());
+
+		for (method, path) in [
+			("GET", "/no/such/page"),
+			("POST", "/no/such/page"),
+			("GET", "/article"),
+			("DELETE", "/article/1"),
+		] {
+			let response = warp::test::request()
+				.method(method)
+				.path(path)
+				.reply(&routes)
+				.await;
+			assert_eq!(response.status(), 404, "{} {}", method, path);
+			assert_eq!(
+				response.headers()["content-type"],
+				"text/html; charset=utf-8"
+			);
+			let body = String::from_utf8(response.body().to_vec()).unwrap();
+			assert!(body.contains("404 Not Found"), "{} {}", method, path);
+			assert!(body.contains(r#"<a href="/">index</a>"#));
+			assert!(body.contains(r#"<a href="/search/article">search</a>"#));
+			assert_landmarks(&body);
+		}
+
+		// The API answers in JSON
+		for path in ["/api/no-such-endpoint", "/api"] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 404, "{}", path);
+			assert_eq!(response.headers()["content-type"], "application/json");
+			assert_eq!(
+				response.body(),
+				r#"{"code":"not_found","message":"not found"}"#
+			);
+		}
+
+		// Errors of the server do not tell what went wrong
+		#[derive(Debug)]
+		struct SecretFailure;
+		impl warp::reject::Reject for SecretFailure {}
+		for (path, expected) in [
+			(
+				"/article/1",
+				"Something went wrong. The error has been logged.",
+			),
+			(
+				"/api/article/1",
+				r#"{"code":"internal","message":"internal error"}"#,
+			),
+		] {
+			let response = handle_rejection(
+				&test_config(),
+				None,
+				path,
+				warp::reject::custom(SecretFailure),
+			);
+			assert_eq!(response.status(), 500);
+			let body = warp::hyper::body::to_bytes(response.into_body())
+				.await
+				.unwrap();
+			let body = String::from_utf8(body.to_vec()).unwrap();
+			assert!(body.contains(expected), "{}", body);
+			assert!(!body.contains("SecretFailure"));
+		}
+
+		// A busy database can be retried, unlike other database errors
+		for (error, path, status, expected) in [
+			(
+				database::DbError::Busy,
+				"/article/1",
+				503,
+				"The database is busy, try again in a moment.",
+			),
+			(
+				database::DbError::Busy,
+				"/api/article/1",
+				503,
+				r#"{"code":"unavailable","message":"the database is busy"}"#,
+			),
+			(
+				database::DbError::Corrupt,
+				"/article/1",
+				500,
+				"Something went wrong. The error has been logged.",
+			),
+		] {
+			let response =
+				handle_rejection(&test_config(), None, path, warp::reject::custom(error));
+			assert_eq!(response.status(), status);
+			let body = warp::hyper::body::to_bytes(response.into_body())
+				.await
+				.unwrap();
+			let body = String::from_utf8(body.to_vec()).unwrap();
+			assert!(body.contains(expected), "{}", body);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_body_limits() {
+		let db = test_db("body-limits").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Redwood".to_string(),
+				text: "Tall".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let config: Config = toml::from_str(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+
+			[limits]
+			article_body_bytes = 2048
+			search_body_bytes = 100
+			"#,
+		)
+		.unwrap();
+		let routes = super::routes(db.clone(), Arc::new(config), test_dictionary());
+		let routes_ref = &routes;
+		let post = |path: String, body: String| async move {
+			warp::test::request()
+				.method("POST")
+				.path(&path)
+				.header("content-type", "application/x-www-form-urlencoded")
+				.body(body)
+				.reply(routes_ref)
+				.await
+		};
+
+		let long_text = format!("article_title=Redwood&article_text={}", "a".repeat(2048));
+		for (path, body, maximum) in [
+			(format!("/article/{}", id), long_text.clone(), "2.0 KiB"),
+			(
+				format!("/preview/article/{}", id),
+				long_text.clone(),
+				"2.0 KiB",
+			),
+			("/create/article".to_string(), long_text, "2.0 KiB"),
+			(
+				"/search/article".to_string(),
+				format!("search_term={}", "a".repeat(100)),
+				"100 bytes",
+			),
+		] {
+			let response = post(path.clone(), body).await;
+			assert_eq!(response.status(), 413, "{}", path);
+			let body = String::from_utf8(response.body().to_vec()).unwrap();
+			assert!(body.contains("413 Payload Too Large"), "{}", path);
+			assert!(
+				body.contains(&format!("larger than the maximum of {} set", maximum)),
+				"{}",
+				path
+			);
+		}
+		// The preview of the editor takes the text as well
+		let response = warp::test::request()
+			.method("POST")
+			.path("/api/preview")
+			.header("content-type", "application/json")
+			.body(format!(r#"{{"text": "{}"}}"#, "a".repeat(2048)))
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 413);
+		assert_eq!(
+			db.lock().await.get_article(id).unwrap().unwrap().text,
+			"Tall"
+		);
+
+		// Within the limits
+		let response = post(
+			format!("/article/{}", id),
+			format!("article_title=Redwood&article_text={}", "a".repeat(1900)),
+		)
+		.await;
+		assert_eq!(response.status(), 303);
+		assert_eq!(
+			db.lock().await.get_article(id).unwrap().unwrap().text.len(),
+			1900
+		);
+		let response = post(
+			"/search/article".to_string(),
+			"search_term=Redwood".to_string(),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+	}
+
+	#[tokio::test]
+	async fn test_edit_page_escaping() {
+		let db = test_db("edit-escaping").await;
+		let title = r#"Quotes "double" & 'single' <b>"#;
+		let text = "\n</textarea><script>alert(1)</script>\n&amp; &lt;tag&gt; \"x\"\n";
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: title.to_string(),
+				text: text.to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+
+		let response = warp::test::request()
+			.path(&format!("/edit/article/{}", id))
+			.reply(&routes)
+			.await;
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(!body.contains("<script>alert(1)</script>"));
+		assert_eq!(body.matches("</textarea>").count(), 1);
+		assert!(
+			body.contains(r#"value="Quotes &quot;double&quot; &amp; &#39;single&#39; &lt;b&gt;">"#)
+		);
+
+		// What the browser submits: the textarea content decoded, without
+		// the newline after the start tag
+		let start = body.find("class=\"editor_textarea\">").unwrap() + 24;
+		let end = body.find("</textarea>").unwrap();
+		let submitted = body[start..end]
+			.strip_prefix('\n')
+			.unwrap()
+			.replace("&lt;", "<")
+			.replace("&gt;", ">")
+			.replace("&amp;", "&");
+		assert_eq!(submitted, text);
+
+		let encode = |value: &str| {
+			percent_encoding::utf8_percent_encode(value, percent_encoding::NON_ALPHANUMERIC)
+				.to_string()
+		};
+		let response = warp::test::request()
+			.method("POST")
+			.path(&format!("/article/{}", id))
+			.header("content-type", "application/x-www-form-urlencoded")
+			.body(format!(
+				"article_title={}&article_text={}",
+				encode(title),
+				encode(&submitted)
+			))
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 303);
+		let article = db.lock().await.get_article(id).unwrap().unwrap();
+		assert_eq!(article.title, title);
+		assert_eq!(article.text, text);
+	}
+
+	#[test]
+	fn test_textarea_and_attribute_escape() {
+		assert_eq!(
+			textarea_escape("a </textarea> & \"b\""),
+			"a &lt;/textarea&gt; &amp; \"b\""
+		);
+		assert_eq!(textarea_escape("\nx"), "\n\nx");
+		assert_eq!(textarea_escape("\r\nx"), "\n\r\nx");
+		assert_eq!(textarea_escape("x\n"), "x\n");
+		assert_eq!(
+			attribute_escape(r#"a "b" 'c' <d> &"#),
+			"a &quot;b&quot; &#39;c&#39; &lt;d&gt; &amp;"
+		);
+	}
+
+	/// Serve the wiki on a port picked by the system, like the `main`
+	async fn spawn_server(db: Arc<DbLock>) -> std::net::SocketAddr {
+		let addresses = ["127.0.0.1:0".parse().unwrap()];
+		let mut servers =
+			bind_servers(db, test_config(), test_dictionary(), &addresses, None).unwrap();
+		let (address, server) = servers.remove(0);
+		tokio::spawn(server);
+		address
+	}
+
+	async fn http_get(address: std::net::SocketAddr, path: &str) -> (u16, String) {
+		let request = warp::hyper::Request::get(path)
+			.body(warp::hyper::Body::empty())
+			.unwrap();
+		let response: warp::hyper::Response<warp::hyper::Body> = outbound::send::<
+			Box<dyn std::error::Error + Send + Sync>,
+		>(address, "localhost", request)
+		.await
+		.unwrap();
+		let status = response.status().as_u16();
+		let body = warp::hyper::body::to_bytes(response.into_body())
+			.await
+			.unwrap();
+		(status, String::from_utf8(body.to_vec()).unwrap())
+	}
+
+	#[tokio::test]
+	async fn test_bind_servers() {
+		let db = DatabaseConnection::new(std::path::Path::new(""), database::OpenMode::InMemory)
+			.unwrap()
+			.init()
+			.unwrap();
+		let db = Arc::new(DbLock::new(db, std::time::Duration::from_millis(100)));
+		let address = spawn_server(db.clone()).await;
+		assert_ne!(address.port(), 0);
+		let (status, body) = http_get(address, "/").await;
+		assert_eq!(status, 200);
+		assert!(body.contains(r#"<form action="/setup" method="post">"#));
+
+		// All addresses or none
+		let addresses = ["127.0.0.1:0".parse().unwrap(), address];
+		let err = bind_servers(
+			db.clone(),
+			test_config(),
+			test_dictionary(),
+			&addresses,
+			None,
+		)
+		.err()
+		.unwrap();
+		assert_eq!(err.0, address);
+
+		let identity = tls::Identity::load(
+			std::path::Path::new("src/testdata/tls/cert.pem"),
+			std::path::Path::new("src/testdata/tls/key.pem"),
+		)
+		.unwrap();
+		let addresses = ["127.0.0.1:0".parse().unwrap()];
+		let servers = bind_servers(
+			db,
+			test_config(),
+			test_dictionary(),
+			&addresses,
+			Some(&identity),
+		)
+		.unwrap();
+		assert_ne!(servers[0].0.port(), 0);
+	}
+
+	#[tokio::test]
+	async fn test_compression() {
+		let db = test_db("compression").await;
+		let address = spawn_server(db).await;
+		let get = |path: &'static str, accept_encoding: Option<&'static str>| async move {
+			let mut request = warp::hyper::Request::get(path);
+			if let Some(accept_encoding) = accept_encoding {
+				request = request.header("accept-encoding", accept_encoding);
+			}
+			let response: warp::hyper::Response<warp::hyper::Body> =
+				outbound::send::<Box<dyn std::error::Error + Send + Sync>>(
+					address,
+					"localhost",
+					request.body(warp::hyper::Body::empty()).unwrap(),
+				)
+				.await
+				.unwrap();
+			let (parts, body) = response.into_parts();
+			let body = warp::hyper::body::to_bytes(body).await.unwrap();
+			assert!(parts.headers.get("transfer-encoding").is_none(), "{}", path);
+			assert_eq!(
+				parts.headers["content-length"],
+				body.len().to_string().as_str(),
+				"{}",
+				path
+			);
+			(parts.headers, body)
+		};
+
+		let (headers, compressed) = get("/about", Some("gzip, deflate")).await;
+		assert_eq!(headers["content-encoding"], "gzip");
+		assert_eq!(headers["vary"], "accept-encoding");
+		let mut about = String::new();
+		std::io::Read::read_to_string(
+			&mut flate2::read::GzDecoder::new(&compressed[..]),
+			&mut about,
+		)
+		.unwrap();
+		assert!(about.contains("<h2 style=\"margin-top: 0px;\">About</h2>"));
+		assert!(compressed.len() < about.len() / 3);
+
+		let (headers, body) = get("/about", None).await;
+		assert!(headers.get("content-encoding").is_none());
+		assert_eq!(body.len(), about.len());
+
+		// Brotli when the client accepts both
+		let (headers, compressed) = get("/about", Some("gzip, deflate, br")).await;
+		assert_eq!(headers["content-encoding"], "br");
+		assert_eq!(headers["vary"], "accept-encoding");
+		let mut decoded = String::new();
+		std::io::Read::read_to_string(
+			&mut brotli::Decompressor::new(&compressed[..], 4096),
+			&mut decoded,
+		)
+		.unwrap();
+		assert_eq!(decoded.len(), about.len());
+
+		// Already compressed images are sent as they are
+		let (headers, body) = get("/favicon.ico", Some("gzip")).await;
+		assert!(headers.get("content-encoding").is_none());
+		assert_eq!(&body[..], assets::FAVICON_ICO.data);
+	}
+
+	#[tokio::test]
+	async fn test_rename_notice() {
+		let db = test_db("rename-notice").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Alpha".to_string(),
+				text: "Text".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let routes_ref = &routes;
+		let get = |path: String| async move {
+			let response = warp::test::request().path(&path).reply(routes_ref).await;
+			String::from_utf8(response.body().to_vec()).unwrap()
+		};
+		let rename = |title: &'static str| {
+			let db = db.clone();
+			async move {
+				db.lock()
+					.await
+					.update_article(id, Some(title), None, None, None)
+					.unwrap();
+			}
+		};
+		let today = test_config()
+			.appearance
+			.timezone
+			.local_time(UtcTimestamp::now())
+			.format("%Y-%m-%d")
+			.to_string();
+
+		assert!(!get(format!("/article/{}", id))
+			.await
+			.contains("class=\"rename_note\""));
+		rename("Beta").await;
+		let page = get(format!("/article/{}", id)).await;
+		assert!(page.contains(&format!(
+			"<p class=\"rename_note\">Renamed from \u{201c}Alpha\u{201d} on {} ",
+			today
+		)));
+		// Arriving through the redirect of the old title
+		let page = get(format!("/article/{}?redirected_from=Alpha", id)).await;
+		assert!(page.contains("(redirected from Alpha)"));
+		assert!(page.contains("Renamed from \u{201c}Alpha\u{201d}"));
+
+		// Renamed back, nothing happened as far as readers are concerned
+		rename("Alpha").await;
+		assert!(!get(format!("/article/{}", id))
+			.await
+			.contains("class=\"rename_note\""));
+		// Renamed again, the notice names the title before all renames
+		rename("Beta").await;
+		rename("Gamma").await;
+		assert!(get(format!("/article/{}", id))
+			.await
+			.contains("Renamed from \u{201c}Alpha\u{201d}"));
+
+		let history = get(format!("/history/article/{}", id)).await;
+		assert!(history.contains(
+			"<span class=\"badge_renamed\">renamed</span> \u{201c}Beta\u{201d} \u{2192} \u{201c}Gamma\u{201d}</li>"
+		));
+		assert_eq!(history.matches("badge_renamed\">").count(), 4);
+		assert!(!history.contains("badge_edited\">"));
+		db.lock()
+			.await
+			.update_article(id, None, Some("Edited"), Some("Fix"), None)
+			.unwrap();
+		let history = get(format!("/history/article/{}", id)).await;
+		assert!(history.contains("<span class=\"badge_edited\">edited</span> Fix</li>"));
+
+		// The notice expires
+		let mut db = db.lock().await;
+		let config = test_config();
+		let now = UtcTimestamp::now();
+		assert!(rename_notice(&mut db, &config, id, now).is_some());
+		let later = now + chrono::Duration::days(15);
+		assert_eq!(rename_notice(&mut db, &config, id, later), None);
+		let config: Config = toml::from_str(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+
+			[site]
+			rename_notice_days = 0
+			"#,
+		)
+		.unwrap();
+		assert_eq!(rename_notice(&mut db, &config, id, now), None);
+	}
+
+	#[tokio::test]
+	async fn test_article_preview() {
+		let db = test_db("article-preview").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Coast <redwood>".to_string(),
+				text: "# Habitat\n\nFog.\n\n```rust\nfn main() {}\n```".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String| {
+			let routes = &routes;
+			async move { warp::test::request().path(&path).reply(routes).await }
+		};
+
+		let article = body_of(get(format!("/article/{}", id)).await).await;
+		let response = get(format!("/preview/article/{}", id)).await;
+		assert_eq!(response.status(), 200);
+		let preview = body_of(response).await;
+		assert_landmarks(&preview);
+		assert!(preview.contains("class=\"preview_note\""));
+		assert!(preview.contains("<meta name=\"robots\" content=\"noindex\">"));
+		assert!(preview.contains("<h1>Coast &lt;redwood&gt; <span"));
+		// Rendered like the article page, but without edit links
+		assert!(preview.contains("<h1>Habitat</h1>"));
+		assert!(preview.contains("<span class=\"storage type function rust\">fn</span>"));
+		assert!(preview.contains(test_config().appearance.code_theme_css()));
+		assert!(article.contains("<p>Fog.</p>") && preview.contains("<p>Fog.</p>"));
+		assert!(!preview.contains("class=\"section_edit\""));
+		assert!(!preview.contains("aria-label=\"Edit this article\""));
+
+		// The link on the edit page leads to it
+		let edit = body_of(get(format!("/edit/article/{}", id)).await).await;
+		assert!(edit.contains(&format!("href=\"/preview/article/{}\"", id)));
+
+		// The title slug is accepted like on the article page, a stale one
+		// redirects to the current one
+		for page in ["/edit", "/preview"] {
+			let response = get(format!("{}/article/{}/coast-redwood", page, id)).await;
+			assert_eq!(response.status(), 200);
+			let response = get(format!("{}/article/{}/giant-sequoia", page, id)).await;
+			assert_eq!(response.status(), 301);
+			assert_eq!(
+				response.headers()["location"],
+				format!("{}/article/{}/coast-redwood", page, id).as_str()
+			);
+		}
+
+		let missing = body_of(get("/preview/article/999".to_string()).await).await;
+		assert!(missing.contains("Could not find article #999!"));
+	}
+
+	#[tokio::test]
+	async fn test_article_preview_post() {
+		let db = test_db("article-preview-post").await;
+		let mut ids = Vec::new();
+		for (title, text) in [("Coast redwood", "Tall"), ("Giant sequoia", "Wide")] {
+			ids.push(
+				db.lock()
+					.await
+					.create_article(&Article {
+						id: 0.into(),
+						title: title.to_string(),
+						text: text.to_string(),
+						date_created: UtcTimestamp::now(),
+						date_modified: UtcTimestamp::now(),
+						revision: 0,
+						slug: String::new(),
+					})
+					.unwrap(),
+			);
+		}
+		let id = ids[0];
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let post = |path: String, body: String| {
+			let routes = &routes;
+			async move {
+				warp::test::request()
+					.method("POST")
+					.path(&path)
+					.header("content-type", "application/x-www-form-urlencoded")
+					.body(body)
+					.reply(routes)
+					.await
+			}
+		};
+
+		let response = post(
+			format!("/preview/article/{}", id),
+			format!(
+				"article_title=Coast+%3Credwood%3E&article_text=%23+Habitat%0A%0ASee+%5Barticle%3A{}%5D+and+%5Barticle%3A999%5D.",
+				ids[1]
+			),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["cache-control"], "no-store");
+		let fragment = String::from_utf8(response.body().to_vec()).unwrap();
+		assert!(
+			fragment.starts_with("<h1>Coast &lt;redwood&gt; <span"),
+			"{}",
+			fragment
+		);
+		assert!(!fragment.contains("<html"));
+		assert!(fragment.contains("<h1>Habitat</h1>"));
+		assert!(fragment.contains(&format!(
+			"<a href=\"/article/{}\" title=\"Giant sequoia\">Giant sequoia</a>",
+			ids[1]
+		)));
+		assert!(fragment.contains("[article:999]"));
+		// Nothing is saved
+		let article = db.lock().await.get_article(id).unwrap().unwrap();
+		assert_eq!(
+			(article.title.as_str(), article.text.as_str()),
+			("Coast redwood", "Tall")
+		);
+		assert_eq!(article.revision, 0);
+
+		let response = post(
+			format!("/preview/article/{}", id),
+			"article_title=x".to_string(),
+		)
+		.await;
+		assert_eq!(response.status(), 400);
+		let response = post(
+			"/preview/article/999".to_string(),
+			"article_title=x&article_text=y".to_string(),
+		)
+		.await;
+		assert_eq!(response.status(), 404);
+	}
+
+	async fn article_page(
+		db: Arc<DbLock>,
+		config: Arc<Config>,
+		article_number: ItemId,
+	) -> Result<impl warp::Reply, warp::Rejection> {
+		article_page_redirected(db, config, article_number, None, None, None).await
+	}
+
+	#[tokio::test]
+	async fn test_sitemap() {
+		let db = test_db("sitemap").await;
+		let mut ids = Vec::new();
+		for title in ["Coast redwood", "Giant sequoia", "Deleted"] {
+			ids.push(
+				db.lock()
+					.await
+					.create_article(&Article {
+						id: 0.into(),
+						title: title.to_string(),
+						text: String::new(),
+						date_created: UtcTimestamp::now(),
+						date_modified: UtcTimestamp::now(),
+						revision: 0,
+						slug: String::new(),
+					})
+					.unwrap(),
+			);
+		}
+		db.lock().await.delete_article(ids[2]).unwrap();
+
+		// Without a public URL, there are no absolute URLs to list
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let response = warp::test::request()
+			.path("/sitemap.xml")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 404);
+
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+				public_url = "https://wiki.example.com/"
+
+				[database]
+				storage_location = "."
+				"#,
+			)
+			.unwrap(),
+		);
+		let routes = super::routes(db.clone(), config, test_dictionary());
+		let response = warp::test::request()
+			.path("/sitemap.xml")
+			.reply(&routes)
+			.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/xml");
+		let body = String::from_utf8(response.body().to_vec()).unwrap();
+		let locs: Vec<&str> = body
+			.lines()
+			.filter_map(|line| line.split("<loc>").nth(1)?.split("</loc>").next())
+			.collect();
+		assert_eq!(
+			locs,
+			[
+				format!("https://wiki.example.com/article/{}/coast-redwood", ids[0]),
+				format!("https://wiki.example.com/article/{}/giant-sequoia", ids[1]),
+			]
+		);
+		assert!(body.contains("</loc><lastmod>"));
+
+		// The single sitemap is also the first part
+		let response = warp::test::request()
+			.path("/sitemap/1.xml")
+			.reply(&routes)
+			.await;
+		assert_eq!(String::from_utf8(response.body().to_vec()).unwrap(), body);
+		for path in ["/sitemap/2.xml", "/sitemap/0.xml", "/sitemap/x"] {
+			let response = warp::test::request().path(path).reply(&routes).await;
+			assert_eq!(response.status(), 404, "{}", path);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_api_article() {
+		let db = test_db("api-article").await;
+		let id = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Coast \"redwood\"".to_string(),
+				text: "Tall.\n".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+		let article = db.lock().await.get_article(id).unwrap().unwrap();
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String| {
+			let routes = routes.clone();
+			async move { warp::test::request().path(&path).reply(&routes).await }
+		};
+
+		let response = get(format!("/api/article/{}", id)).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/json");
+		let json: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"id": id.to_string().parse::<u32>().unwrap(),
+				"title": "Coast \"redwood\"",
+				"text": "Tall.\n",
+				"date_created": article.date_created.rfc3339(),
+				"date_modified": article.date_modified.rfc3339(),
+				"revision": 0,
+				"slug": "coast-redwood",
+			})
+		);
+
+		let response = get("/api/article/1000".to_string()).await;
+		assert_eq!(response.status(), 404);
+		assert_eq!(response.headers()["content-type"], "application/json");
+		assert_eq!(
+			response.body(),
+			r#"{"code":"not_found","message":"not found"}"#
+		);
+		db.lock().await.delete_article(id).unwrap();
+		assert_eq!(get(format!("/api/article/{}", id)).await.status(), 404);
+		let response = get("/api/article/abc".to_string()).await;
+		assert_eq!(response.status(), 400);
+		assert_eq!(
+			response.body(),
+			r#"{"code":"validation","message":"invalid id","details":{"field":"id"}}"#
+		);
+	}
+
+	#[tokio::test]
+	async fn test_api_links() {
+		let db = test_db("api-links").await;
+		let mut ids = Vec::new();
+		for (title, text) in [
+			("Redwood", "[article:2] [article:3]"),
+			("Sequoia", "[article:3]"),
+			("Oak", "[article:1000]"),
+			("Fir", ""),
+			("Pine", "[article:1]"),
+		] {
+			let mut db = db.lock().await;
+			let id = db
+				.create_article(&Article {
+					id: 0.into(),
+					title: title.to_string(),
+					text: text.to_string(),
+					date_created: UtcTimestamp::now(),
+					date_modified: UtcTimestamp::now(),
+					revision: 0,
+					slug: String::new(),
+				})
+				.unwrap();
+			save_links(&mut db, &test_config(), id, text);
+			ids.push(id);
+		}
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let get = |path: String| {
+			let routes = routes.clone();
+			async move { warp::test::request().path(&path).reply(&routes).await }
+		};
+
+		// Two sources per page, the next page is linked until one is empty
+		let mut path = "/api/links?limit=2".to_string();
+		let mut pages = Vec::new();
+		loop {
+			let response = get(path.clone()).await;
+			assert_eq!(response.status(), 200);
+			assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+			pages.push(std::str::from_utf8(response.body()).unwrap().to_string());
+			let Some(link) = response.headers().get("link") else {
+				break;
+			};
+			path = link
+				.to_str()
+				.unwrap()
+				.strip_prefix('<')
+				.and_then(|link| link.strip_suffix(">; rel=\"next\""))
+				.unwrap()
+				.to_string();
+		}
+		assert_eq!(
+			pages,
+			[
+				"{\"from\":1,\"to\":2,\"kind\":\"article_ref\"}\n\
+				{\"from\":1,\"to\":3,\"kind\":\"article_ref\"}\n\
+				{\"from\":2,\"to\":3,\"kind\":\"article_ref\"}\n",
+				"{\"from\":3,\"to\":1000,\"kind\":\"article_ref\"}\n\
+				{\"from\":5,\"to\":1,\"kind\":\"article_ref\"}\n",
+				"",
+			]
+		);
+		let response = get("/api/links?after=3".to_string()).await;
+		assert_eq!(
+			response.body(),
+			"{\"from\":5,\"to\":1,\"kind\":\"article_ref\"}\n"
+		);
+		assert!(response.headers().get("link").is_none());
+		for query in ["after=x", "after=-1", "limit=0", "limit=1001"] {
+			let response = get(format!("/api/links?{}", query)).await;
+			assert_eq!(response.status(), 400, "{}", query);
+		}
+
+		let response = get(format!("/api/links/article/{}", ids[2])).await;
+		assert_eq!(response.status(), 200);
+		let json: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"outgoing": [{ "from": 3, "to": 1000, "kind": "article_ref" }],
+				"incoming": [
+					{ "from": 1, "to": 3, "kind": "article_ref" },
+					{ "from": 2, "to": 3, "kind": "article_ref" },
+				],
+			})
+		);
+		// An article without any links
+		let response = get(format!("/api/links/article/{}", ids[3])).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.body(), r#"{"outgoing":[],"incoming":[]}"#);
+		assert_eq!(
+			get("/api/links/article/1000".to_string()).await.status(),
+			404
+		);
+		assert_eq!(get("/api/links/article/x".to_string()).await.status(), 400);
+	}
+
+	#[tokio::test]
+	async fn test_api_article_write() {
+		let db = test_db("api-article-write").await;
+		let routes = super::routes(db.clone(), test_config(), test_dictionary());
+		let send = |method: &str, path: String, body: &str| {
+			warp::test::request()
+				.method(method)
+				.path(&path)
+				.header("content-type", "application/json")
+				.body(body)
+				.reply(&routes)
+		};
+		let json = |response: &warp::http::Response<warp::hyper::body::Bytes>| {
+			serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()
+		};
+
+		let response = send(
+			"POST",
+			"/api/article".to_string(),
+			r#"{"title": "Coast redwood", "text": "Tall."}"#,
+		)
+		.await;
+		assert_eq!(response.status(), 201);
+		let created = json(&response);
+		let id = created["id"].as_u64().unwrap();
+		assert_eq!(
+			response.headers()["location"],
+			format!("/api/article/{}", id).as_str()
+		);
+		assert_eq!(created["title"], "Coast redwood");
+		assert_eq!(created["text"], "Tall.");
+		assert_eq!(created["slug"], "coast-redwood");
+		let other = json(
+			&send(
+				"POST",
+				"/api/article".to_string(),
+				&format!(
+					r#"{{"title": "Giant sequoia", "text": "[article:{}]"}}"#,
+					id
+				),
+			)
+			.await,
+		)["id"]
+			.as_u64()
+			.unwrap();
+		// The links of the text are saved as by the edit form
+		assert_eq!(db.lock().await.count_backlinks((id as u32).into()), Some(1));
+
+		let response = send(
+			"PUT",
+			format!("/api/article/{}", id),
+			r#"{"text": "Taller.", "summary": "Height"}"#,
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		let updated = json(&response);
+		assert_eq!(updated["title"], "Coast redwood");
+		assert_eq!(updated["text"], "Taller.");
+		assert_eq!(updated["revision"], 1);
+		assert_eq!(
+			db.lock()
+				.await
+				.get_edit_summary((id as u32).into(), 1)
+				.as_deref(),
+			Some("Height")
+		);
+		// Renaming to its own title is no conflict
+		let response = send(
+			"PUT",
+			format!("/api/article/{}", id),
+			r#"{"title": "Coast redwood"}"#,
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+
+		// Status, code and message of the error, the details are checked
+		// below
+		let error = |status: u16, code: &str, message: &str| {
+			(status, code.to_string(), message.to_string())
+		};
+		let result = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+			assert_eq!(response.headers()["content-type"], "application/json");
+			let body = json(&response);
+			(
+				response.status().as_u16(),
+				body["code"].as_str().unwrap().to_string(),
+				body["message"].as_str().unwrap().to_string(),
+			)
+		};
+		let cases = [
+			(
+				"POST",
+				"/api/article".to_string(),
+				r#"{"title": "Coast redwood"}"#,
+				error(409, "conflict", "title already exists"),
+			),
+			(
+				"POST",
+				"/api/article".to_string(),
+				r#"{"title": ""}"#,
+				error(400, "validation", "empty title"),
+			),
+			(
+				"POST",
+				"/api/article".to_string(),
+				r#"{"title": 5}"#,
+				error(400, "validation", "invalid body"),
+			),
+			(
+				"POST",
+				"/api/article".to_string(),
+				r#"{"title": "A", "tags": []}"#,
+				error(400, "validation", "invalid body"),
+			),
+			(
+				"POST",
+				"/api/article".to_string(),
+				"not json",
+				error(400, "validation", "invalid body"),
+			),
+			(
+				"PUT",
+				format!("/api/article/{}", id),
+				r#"{"title": "Giant sequoia"}"#,
+				error(409, "conflict", "title already exists"),
+			),
+			(
+				"PUT",
+				format!("/api/article/{}", id),
+				r#"{"summary": "Nothing"}"#,
+				error(400, "validation", "nothing to update"),
+			),
+			(
+				"PUT",
+				format!("/api/article/{}", id),
+				r#"{"title": ""}"#,
+				error(400, "validation", "empty title"),
+			),
+			(
+				"PUT",
+				"/api/article/1000".to_string(),
+				r#"{"text": "x"}"#,
+				error(404, "not_found", "not found"),
+			),
+			(
+				"PUT",
+				"/api/article/abc".to_string(),
+				r#"{"text": "x"}"#,
+				error(400, "validation", "invalid id"),
+			),
+		];
+		for (method, path, body, expected) in cases {
+			assert_eq!(
+				result(send(method, path.clone(), body).await),
+				expected,
+				"{} {} {}",
+				method,
+				path,
+				body
+			);
+		}
+
+		// Deleted articles cannot be updated and keep their title
+		db.lock()
+			.await
+			.delete_article((other as u32).into())
+			.unwrap();
+		assert_eq!(
+			result(send("PUT", format!("/api/article/{}", other), r#"{"text": "x"}"#).await),
+			error(404, "not_found", "not found")
+		);
+		assert_eq!(
+			result(
+				send(
+					"POST",
+					"/api/article".to_string(),
+					r#"{"title": "Giant sequoia"}"#
+				)
+				.await
+			),
+			error(409, "conflict", "title already exists")
+		);
+
+		let large = format!(
+			r#"{{"title": "Large", "text": "{}"}}"#,
+			"x".repeat(1024 * 1024)
+		);
+		assert_eq!(
+			result(send("POST", "/api/article".to_string(), &large).await),
+			error(413, "too_large", "body too large")
+		);
+		let response = warp::test::request()
+			.method("POST")
+			.path("/api/article")
+			.header("content-type", "text/plain")
+			.body(r#"{"title": "Plain"}"#)
+			.reply(&routes)
+			.await;
+		assert_eq!(result(response), error(415, "validation", "expected JSON"));
+
+		// Details for programs
+		let details = |response: warp::http::Response<warp::hyper::body::Bytes>| {
+			json(&response)["details"].clone()
+		};
+		assert_eq!(
+			details(send("POST", "/api/article".to_string(), r#"{"title": ""}"#).await),
+			serde_json::json!({ "field": "title" })
+		);
+		assert_eq!(
+			details(
+				send(
+					"PUT",
+					format!("/api/article/{}", id),
+					r#"{"title": "Giant sequoia"}"#
+				)
+				.await
+			),
+			serde_json::json!({ "field": "title" })
+		);
+		assert_eq!(
+			details(send("POST", "/api/article".to_string(), &large).await),
+			serde_json::json!({ "limit": 1024 * 1024 })
+		);
+		assert!(
+			details(send("POST", "/api/article".to_string(), r#"{"title": 5}"#).await)["reason"]
+				.as_str()
+				.unwrap()
+				.contains("invalid type")
+		);
+		// No details where there are none
+		let response = send("PUT", "/api/article/1000".to_string(), r#"{"text": "x"}"#).await;
+		assert!(json(&response).get("details").is_none());
+		let response = warp::test::request()
+			.path("/api/no-such-endpoint")
+			.reply(&routes)
+			.await;
+		assert_eq!(result(response), error(404, "not_found", "not found"));
+	}
+	#[tokio::test]
+	async fn test_api_article_full() {
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[api]
+				article_sync = true
+				"#,
+			)
+			.unwrap(),
+		);
+		let source = test_db("api-article-full-source").await;
+		let target = test_db("api-article-full-target").await;
+		let create = |title: &str, text: &str| Article {
+			id: 0.into(),
+			title: title.to_string(),
+			text: text.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		};
+		let (redwood, fir) = {
+			let mut db = source.lock().await;
+			let redwood = db.create_article(&create("Redwood", "Tall")).unwrap();
+			db.update_article(redwood, None, Some("Taller"), Some("Height"), None)
+				.unwrap();
+			db.update_article(redwood, Some("Coast redwood"), None, Some("Rename"), None)
+				.unwrap();
+			db.add_tag(redwood, "conifer").unwrap();
+			let fir = db.create_article(&create("Fir", "[article:1]")).unwrap();
+			(redwood, fir)
+		};
+		let placeholder = {
+			let mut db = target.lock().await;
+			db.create_article(&create("Unrelated", "")).unwrap();
+			db.create_article(&create("Placeholder", "Old")).unwrap()
+		};
+		let source_routes = super::routes(source.clone(), config.clone(), test_dictionary());
+		let target_routes = super::routes(target.clone(), config.clone(), test_dictionary());
+		let get = |routes, path: String| async move {
+			warp::test::request().path(&path).reply(routes).await
+		};
+		let put = |routes, path: String, content_type: &'static str, body: Vec<u8>| async move {
+			warp::test::request()
+				.method("PUT")
+				.path(&path)
+				.header("content-type", content_type)
+				.body(body)
+				.reply(routes)
+				.await
+		};
+		let json = |body: &[u8]| serde_json::from_slice::<serde_json::Value>(body).unwrap();
+		// Everything but the ID and slug, which stay in the target
+		let comparable = |mut article: serde_json::Value| {
+			let object = article.as_object_mut().unwrap();
+			object.remove("id");
+			object.remove("slug");
+			article
+		};
+
+		let response = get(&source_routes, format!("/api/article/{}/full", redwood)).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/json");
+		let exported = json(response.body());
+		assert_eq!(exported["title"], "Coast redwood");
+		assert_eq!(exported["tags"], serde_json::json!(["conifer"]));
+		let revisions = exported["revisions"].as_array().unwrap();
+		assert_eq!(revisions.len(), 3);
+		assert_eq!(revisions[0]["text"], "Tall");
+		assert_eq!(revisions[1]["summary"], "Height");
+		assert_eq!(revisions[2]["new_title"], "Coast redwood");
+
+		let path = format!("/api/article/{}/full", placeholder);
+		let response = put(
+			&target_routes,
+			path.clone(),
+			"application/json",
+			response.body().to_vec(),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		let replaced = json(response.body());
+		assert_eq!(replaced["id"].to_string(), placeholder.to_string());
+		assert_eq!(replaced["title"], "Coast redwood");
+		assert_eq!(replaced["slug"], "placeholder");
+		let copy = json(get(&target_routes, path.clone()).await.body());
+		assert_eq!(comparable(copy), comparable(exported.clone()));
+
+		// Inconsistent histories are refused with the first problem, the
+		// article stays as it was
+		let mut swapped = exported.clone();
+		swapped["revisions"][1]["revision"] = serde_json::json!(0);
+		let response = put(
+			&target_routes,
+			path.clone(),
+			"application/json",
+			swapped.to_string().into_bytes(),
+		)
+		.await;
+		assert_eq!(response.status(), 422);
+		assert_eq!(
+			json(response.body()),
+			serde_json::json!({
+				"code": "inconsistent",
+				"message": "revision numbers must increase",
+				"details": { "index": 1 },
+			})
+		);
+		let mut stale = exported.clone();
+		stale["revision"] = serde_json::json!(1);
+		let response = put(
+			&target_routes,
+			path.clone(),
+			"application/json",
+			stale.to_string().into_bytes(),
+		)
+		.await;
+		assert_eq!(response.status(), 422);
+		assert_eq!(
+			json(response.body())["details"],
+			serde_json::json!({ "index": null })
+		);
+		let unchanged = json(get(&target_routes, path.clone()).await.body());
+		assert_eq!(comparable(unchanged), comparable(exported.clone()));
+
+		// A large history comes as JSON lines, which are accepted as well
+		{
+			let mut db = source.lock().await;
+			let text = format!("[article:1] {}", "Fir ".repeat(200 * 1024));
+			db.update_article(fir, None, Some(&text), None, None)
+				.unwrap();
+			db.update_article(fir, None, Some(&text.repeat(2)), None, None)
+				.unwrap();
+		}
+		let response = get(&source_routes, format!("/api/article/{}/full", fir)).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+		let body = std::str::from_utf8(response.body()).unwrap();
+		assert_eq!(body.lines().count(), 4);
+		let head = json(body.lines().next().unwrap().as_bytes());
+		assert_eq!(head["title"], "Fir");
+		assert_eq!(head["tags"], serde_json::json!([]));
+		assert!(head.get("revisions").is_none());
+		let response = put(
+			&target_routes,
+			path.clone(),
+			"application/x-ndjson",
+			response.body().to_vec(),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		let copy = get(&target_routes, path.clone()).await;
+		assert_eq!(copy.headers()["content-type"], "application/x-ndjson");
+		assert_eq!(
+			copy.body(),
+			body.replace(
+				&format!("\"id\":{},", fir),
+				&format!("\"id\":{},", placeholder),
+			)
+			.replace("\"slug\":\"fir\"", "\"slug\":\"placeholder\"")
+			.as_bytes()
+		);
+		// The references of the text are saved
+		assert_eq!(target.lock().await.count_backlinks(1.into()), Some(1));
+
+		// Title conflicts, unknown articles and bad bodies
+		let response = put(
+			&target_routes,
+			format!("/api/article/{}/full", placeholder),
+			"application/json",
+			serde_json::json!({
+				"title": "Unrelated",
+				"text": "",
+				"date_created": "2024-01-01T00:00:00Z",
+				"date_modified": "2024-01-01T00:00:00Z",
+				"revision": 0,
+			})
+			.to_string()
+			.into_bytes(),
+		)
+		.await;
+		assert_eq!(response.status(), 409);
+		let response = put(
+			&target_routes,
+			"/api/article/1000/full".to_string(),
+			"application/json",
+			exported.to_string().into_bytes(),
+		)
+		.await;
+		assert_eq!(response.status(), 404);
+		assert_eq!(
+			get(&source_routes, "/api/article/1000/full".to_string())
+				.await
+				.status(),
+			404
+		);
+		let response = put(
+			&target_routes,
+			path.clone(),
+			"application/json",
+			b"{\"title\": 5}".to_vec(),
+		)
+		.await;
+		assert_eq!(response.status(), 400);
+		assert!(json(response.body())["details"]["reason"]
+			.as_str()
+			.unwrap()
+			.contains("invalid type"));
+
+		// Only if enabled in the configuration
+		let disabled = super::routes(target.clone(), test_config(), test_dictionary());
+		let response = put(
+			&disabled,
+			path.clone(),
+			"application/json",
+			exported.to_string().into_bytes(),
+		)
+		.await;
+		assert_eq!(response.status(), 403);
+		assert_eq!(json(response.body())["code"], "read_only");
+	}
+
+	#[tokio::test]
+	async fn test_today_page() {
+		let db = test_db("today").await;
+		let template = db
+			.lock()
+			.await
+			.create_article(&Article {
+				id: 0.into(),
+				title: "Journal template".to_string(),
+				text: "## Done\n\n## Planned".to_string(),
+				date_created: UtcTimestamp::now(),
+				date_modified: UtcTimestamp::now(),
+				revision: 0,
+				slug: String::new(),
+			})
+			.unwrap();
+
+		// Disabled without `[journal]` section
+		assert!(today_page(db.clone(), test_config()).await.is_err());
+		let menu = generate_menu(&test_config(), None, None, None);
+		assert!(!menu.contains("/today"));
+
+		let config: Arc<Config> = Arc::new(
+			toml::from_str(&format!(
+				r#"
+				[network]
+				ip = "127.0.0.1"
+				port = 0
+
+				[database]
+				storage_location = "."
+
+				[journal]
+				title_prefix = "Journal "
+				template_article = {}
+				"#,
+				template
+			))
+			.unwrap(),
+		);
+		assert!(generate_menu(&config, None, None, None)
+			.contains(r#"<a href="/today">Today's note</a>"#));
+
+		let response = today_page(db.clone(), config.clone())
+			.await
+			.unwrap()
+			.into_response();
+		assert_eq!(response.status(), 303);
+		let location = response.headers()["location"].to_str().unwrap().to_string();
+		let title = format!("Journal {}", chrono::Local::now().format("%Y-%m-%d"));
+		let id = db
+			.lock()
+			.await
+			.get_article_id_by_title(&title)
+			.unwrap()
+			.unwrap();
+		assert_eq!(location, format!("/article/{}", id));
+		assert_eq!(
+			db.lock().await.get_article(id).unwrap().unwrap().text,
+			"## Done\n\n## Planned"
+		);
+
+		// The second request finds the existing entry
+		let response = today_page(db.clone(), config.clone())
+			.await
+			.unwrap()
+			.into_response();
+		assert_eq!(response.headers()["location"], location.as_str());
+		assert_eq!(db.lock().await.get_all_articles().unwrap().len(), 2);
+
+		// A deleted entry keeps the title, the error is not a missing page
+		db.lock().await.delete_article(id).unwrap();
+		let Err(rejection) = today_page(db.clone(), config).await else {
+			panic!("The entry in the trash was not detected");
+		};
+		assert!(!rejection.is_not_found());
+		let response = handle_rejection(&test_config(), None, "/today", rejection);
+		assert_eq!(response.status(), 500);
+	}
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+
+use super::database::{Article, CreateArticleError, Database, ItemId};
+use super::render::{self, RenderOptions};
+use super::timestamp::UtcTimestamp;
+
+/// Edit summary of the second pass, which turns links between the
+/// files into references
+const LINKS_SUMMARY: &str = "Links between the imported files converted";
+
+/// What `import_dir` did with the files, paths are relative to the
+/// imported directory
+#[derive(Debug, Default)]
+pub struct Report {
+	pub imported: Vec<(PathBuf, ItemId)>,
+	/// Files that were left out, with the reason
+	pub skipped: Vec<(PathBuf, String)>,
+	/// Files whose title was taken, with the title they got instead
+	pub renamed: Vec<(PathBuf, String)>,
+}
+
+/// Create an article from every Markdown file (`.md`, `.markdown`) in
+/// `dir` and its subdirectories, hidden ones left out. The title is the
+/// leading `# Heading` of the file or its name, a taken title gets a
+/// suffix like `Title (2)`. Once all articles exist, relative links
+/// between the files become `[article:5]` references.
+pub fn import_dir(db: &mut Database, dir: &Path) -> io::Result<Report> {
+	let mut files = Vec::new();
+	collect_files(dir, Path::new(""), &mut files)?;
+	files.sort();
+
+	let mut report = Report::default();
+	let mut texts = Vec::new();
+	for file in files {
+		let text = match std::fs::read(dir.join(&file)).map(String::from_utf8) {
+			Ok(Ok(text)) if text.contains('\0') => {
+				report.skipped.push((file, "binary file".to_string()));
+				continue;
+			}
+			Ok(Ok(text)) => text,
+			Ok(Err(_)) => {
+				report
+					.skipped
+					.push((file, "binary file, not UTF-8".to_string()));
+				continue;
+			}
+			Err(err) => {
+				report.skipped.push((file, format!("cannot read: {}", err)));
+				continue;
+			}
+		};
+		if text.trim().is_empty() {
+			report.skipped.push((file, "empty".to_string()));
+			continue;
+		}
+		let title = title_of(&file, &text);
+		match create_article(db, &title, &text) {
+			Ok((id, created_title)) => {
+				if created_title != title {
+					report.renamed.push((file.clone(), created_title));
+				}
+				report.imported.push((file, id));
+				texts.push(text);
+			}
+			Err(err) => report.skipped.push((file, err.to_string())),
+		}
+	}
+
+	let ids: HashMap<&Path, ItemId> = report
+		.imported
+		.iter()
+		.map(|(file, id)| (file.as_path(), *id))
+		.collect();
+	for ((file, id), text) in report.imported.iter().zip(&texts) {
+		let converted = convert_links(text, file, &ids);
+		if converted != *text {
+			db.update_article(*id, None, Some(&converted), Some(LINKS_SUMMARY), None)
+				.map_err(|err| io::Error::other(err.to_string()))?;
+		}
+		let _ = db.set_article_links(
+			*id,
+			&render::article_refs(&converted, RenderOptions::default()),
+		);
+	}
+	Ok(report)
+}
+
+/// Markdown files below `dir.join(prefix)`, relative to `dir`
+fn collect_files(dir: &Path, prefix: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	for entry in std::fs::read_dir(dir.join(prefix))? {
+		let entry = entry?;
+		let name = entry.file_name();
+		if name.to_string_lossy().starts_with('.') {
+			continue;
+		}
+		let path = prefix.join(&name);
+		if entry.file_type()?.is_dir() {
+			collect_files(dir, &path, files)?;
+		} else if is_markdown(&path) {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
+
+fn is_markdown(path: &Path) -> bool {
+	path.extension()
+		.is_some_and(|ext| ext == "md" || ext == "markdown")
+}
+
+/// The leading `# Heading` of the text, otherwise the file name
+fn title_of(file: &Path, text: &str) -> String {
+	let heading = text
+		.lines()
+		.find(|line| !line.trim().is_empty())
+		.and_then(|line| line.strip_prefix("# "))
+		.map(|heading| heading.trim().trim_end_matches('#').trim())
+		.filter(|heading| !heading.is_empty());
+	match heading {
+		Some(heading) => heading.to_string(),
+		None => file
+			.file_stem()
+			.map(|stem| stem.to_string_lossy().into_owned())
+			.unwrap_or_default(),
+	}
+}
+
+/// Create the article, with a suffix if the title is taken. Returns
+/// the ID and the title it got.
+fn create_article(
+	db: &mut Database,
+	title: &str,
+	text: &str,
+) -> Result<(ItemId, String), CreateArticleError> {
+	let mut candidate = title.to_string();
+	for suffix in 2.. {
+		let result = db.create_article(&Article {
+			id: 0.into(),
+			title: candidate.clone(),
+			text: text.to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: String::new(),
+		});
+		match result {
+			Ok(id) => return Ok((id, candidate)),
+			Err(CreateArticleError::DuplicateTitle) => {
+				candidate = format!("{} ({})", title, suffix);
+			}
+			Err(err) => return Err(err),
+		}
+	}
+	unreachable!("The suffixes are endless")
+}
+
+/// Replace inline links to imported files, like `[the tree](trees/redwood.md)`,
+/// by references like `[article:5|the tree]`. Other links are kept.
+fn convert_links(text: &str, file: &Path, ids: &HashMap<&Path, ItemId>) -> String {
+	let mut converted = String::with_capacity(text.len());
+	let mut copied = 0;
+	for (event, range) in Parser::new(text).into_offset_iter() {
+		let Event::Start(Tag::Link(LinkType::Inline, dest, _)) = event else {
+			continue;
+		};
+		let Some(id) = link_target(file, &dest).and_then(|target| ids.get(target.as_path())) else {
+			continue;
+		};
+		let source = &text[range.clone()];
+		let label = source
+			.rfind("](")
+			.map(|end| &source[1..end])
+			.filter(|label| !label.contains(['[', ']', '|', '\n']));
+		converted.push_str(&text[copied..range.start]);
+		match label {
+			Some(label) if !label.trim().is_empty() => {
+				converted.push_str(&format!("[article:{}|{}]", id, label))
+			}
+			_ => converted.push_str(&format!("[article:{}]", id)),
+		}
+		copied = range.end;
+	}
+	converted.push_str(&text[copied..]);
+	converted
+}
+
+/// The file a link in `file` points to, relative to the imported
+/// directory. `None` for absolute paths and URLs.
+fn link_target(file: &Path, dest: &str) -> Option<PathBuf> {
+	let dest = dest.split('#').next().unwrap_or_default();
+	if dest.is_empty() || dest.starts_with('/') || dest.contains(':') {
+		return None;
+	}
+	let dest = percent_encoding::percent_decode_str(dest)
+		.decode_utf8()
+		.ok()?;
+	let mut target = PathBuf::new();
+	for component in file.parent()?.join(dest.as_ref()).components() {
+		match component {
+			Component::Normal(part) => target.push(part),
+			Component::ParentDir => {
+				if !target.pop() {
+					// Outside of the imported directory
+					return None;
+				}
+			}
+			Component::CurDir => {}
+			Component::RootDir | Component::Prefix(_) => return None,
+		}
+	}
+	// Links to notes often leave out the extension
+	if !is_markdown(&target) {
+		target.set_extension("md");
+	}
+	Some(target)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use super::super::database::{DatabaseConnection, OpenMode};
+
+	#[test]
+	fn test_title_of() {
+		let file = Path::new("notes/coast redwood.md");
+		assert_eq!(title_of(file, "# Redwood #\n\nTall."), "Redwood");
+		assert_eq!(title_of(file, "\n\n# Redwood\nTall."), "Redwood");
+		assert_eq!(title_of(file, "Tall.\n\n# Redwood"), "coast redwood");
+		assert_eq!(title_of(file, "## Redwood"), "coast redwood");
+		assert_eq!(title_of(file, "# \nTall."), "coast redwood");
+	}
+
+	#[test]
+	fn test_link_target() {
+		let file = Path::new("trees/redwood.md");
+		assert_eq!(
+			link_target(file, "sequoia.md"),
+			Some(PathBuf::from("trees/sequoia.md"))
+		);
+		assert_eq!(
+			link_target(file, "./sequoia#size"),
+			Some(PathBuf::from("trees/sequoia.md"))
+		);
+		assert_eq!(
+			link_target(file, "../fog%20belt.md"),
+			Some(PathBuf::from("fog belt.md"))
+		);
+		assert_eq!(link_target(file, "../../outside.md"), None);
+		assert_eq!(link_target(file, "/etc/passwd"), None);
+		assert_eq!(link_target(file, "https://example.com/a.md"), None);
+		assert_eq!(link_target(file, "#size"), None);
+	}
+
+	#[test]
+	fn test_import_dir() {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!(
+			"redwood-wiki-test-markdown-import-{}",
+			std::process::id()
+		));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(dir.join("trees")).unwrap();
+		std::fs::create_dir_all(dir.join(".obsidian")).unwrap();
+		std::fs::write(
+			dir.join("index.md"),
+			"# Forest\n\nSee [the tallest](trees/redwood.md), [](trees/fir.md), [home](https://example.com) and ![](trees/redwood.md).\n",
+		)
+		.unwrap();
+		std::fs::write(
+			dir.join("trees/redwood.md"),
+			"Tallest tree, back to [the forest](../index.md#top).",
+		)
+		.unwrap();
+		std::fs::write(dir.join("trees/fir.md"), "# Forest\n\nA fir.").unwrap();
+		std::fs::write(dir.join("trees/empty.md"), " \n\n").unwrap();
+		std::fs::write(dir.join("trees/photo.md"), [0x89, 0x50, 0x4e, 0x47, 0xff]).unwrap();
+		std::fs::write(dir.join("trees/notes.txt"), "Not Markdown").unwrap();
+		std::fs::write(dir.join(".obsidian/workspace.md"), "Hidden").unwrap();
+
+		let mut db = DatabaseConnection::new(Path::new(""), OpenMode::InMemory)
+			.unwrap()
+			.init()
+			.unwrap();
+		let report = import_dir(&mut db, &dir).unwrap();
+		let files: Vec<&Path> = report
+			.imported
+			.iter()
+			.map(|(file, _)| file.as_path())
+			.collect();
+		assert_eq!(
+			files,
+			[
+				Path::new("index.md"),
+				Path::new("trees/fir.md"),
+				Path::new("trees/redwood.md")
+			]
+		);
+		let index = report.imported[0].1;
+		let fir = report.imported[1].1;
+		let redwood = report.imported[2].1;
+		assert_eq!(
+			report.skipped,
+			[
+				(PathBuf::from("trees/empty.md"), "empty".to_string()),
+				(
+					PathBuf::from("trees/photo.md"),
+					"binary file, not UTF-8".to_string()
+				)
+			]
+		);
+		assert_eq!(
+			report.renamed,
+			[(PathBuf::from("trees/fir.md"), "Forest (2)".to_string())]
+		);
+
+		let article = db.get_article(index).unwrap().unwrap();
+		assert_eq!(article.title, "Forest");
+		assert_eq!(
+			article.text,
+			format!(
+				"# Forest\n\nSee [article:{}|the tallest], [article:{}], [home](https://example.com) and ![](trees/redwood.md).\n",
+				redwood, fir
+			)
+		);
+		assert_eq!(article.revision, 1);
+		assert_eq!(db.get_revisions(index).unwrap()[0].summary, LINKS_SUMMARY);
+		let article = db.get_article(redwood).unwrap().unwrap();
+		assert_eq!(article.title, "redwood");
+		assert_eq!(
+			article.text,
+			format!("Tallest tree, back to [article:{}|the forest].", index)
+		);
+		// Without links, the text stays as it was
+		assert_eq!(db.get_article(fir).unwrap().unwrap().revision, 0);
+		assert_eq!(db.get_backlinks(redwood).unwrap()[0].id, index);
+		assert_eq!(db.get_backlinks(index).unwrap()[0].id, redwood);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
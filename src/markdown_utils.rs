@@ -1,12 +1,54 @@
+use std::cell::Cell;
 use std::collections::VecDeque;
+use std::rc::Rc;
 
 use regex::Regex;
 use std::sync::OnceLock;
 
 use pulldown_cmark::{CowStr, Event, LinkType, Tag};
 
+use super::infobox;
 use super::regex_utils::{DoPartition, Part};
 
+/// Upper bound for the number of events a stream stage may inject
+/// over a whole document. It is shared with the caller of the stage,
+/// who can check afterwards whether the limit was hit.
+pub struct InjectionLimit {
+	remaining: Cell<usize>,
+	exceeded: Cell<bool>,
+}
+
+impl InjectionLimit {
+	pub fn new(max: usize) -> Rc<InjectionLimit> {
+		Rc::new(InjectionLimit {
+			remaining: Cell::new(max),
+			exceeded: Cell::new(false),
+		})
+	}
+
+	/// Number of events that may still be injected
+	fn remaining(&self) -> usize {
+		self.remaining.get()
+	}
+
+	/// Account for `count` injected events, returns `false` and marks
+	/// the limit as exceeded if there are not that many left
+	fn take(&self, count: usize) -> bool {
+		if count > self.remaining.get() {
+			self.remaining.set(0);
+			self.exceeded.set(true);
+			false
+		} else {
+			self.remaining.set(self.remaining.get() - count);
+			true
+		}
+	}
+
+	pub fn exceeded(&self) -> bool {
+		self.exceeded.get()
+	}
+}
+
 // Merge text events together. More information at:
 // https://github.com/raphlinus/pulldown-cmark/issues/507
 
@@ -118,6 +160,7 @@ pub struct LinkHighlightStream<'a, I> {
 	inject_event: VecDeque<Event<'a>>,
 	inside_link: bool,
 	inside_codeblock: bool,
+	limit: Option<Rc<InjectionLimit>>,
 }
 
 impl<'a, I> LinkHighlightStream<'a, I>
@@ -130,8 +173,15 @@ where
 			inject_event: VecDeque::new(),
 			inside_link: false,
 			inside_codeblock: false,
+			limit: None,
 		}
 	}
+
+	/// Stop detecting links once `limit` is exhausted
+	pub fn with_limit(mut self, limit: Rc<InjectionLimit>) -> Self {
+		self.limit = Some(limit);
+		self
+	}
 }
 
 impl<'a, I> Iterator for LinkHighlightStream<'a, I>
@@ -175,6 +225,14 @@ where
 						.unwrap()
 					});
 
+					if self.limit.is_some() && !link_regex.is_match(&next_text) {
+						// Plain text does not count towards the limit
+						return Some(Event::Text(next_text));
+					}
+					let max_events = self
+						.limit
+						.as_ref()
+						.map_or(usize::MAX, |limit| limit.remaining());
 					self.inject_event = link_regex
 						.partition(&next_text)
 						.flat_map(|mat| match mat {
@@ -197,7 +255,16 @@ where
 							]
 							.into_iter(),
 						})
+						// One more than allowed, to detect exceeding the limit
+						.take(max_events.saturating_add(1))
 						.collect();
+					if let Some(limit) = &self.limit {
+						if !limit.take(self.inject_event.len()) {
+							// Too many links, leave the text as it is
+							self.inject_event.clear();
+							return Some(Event::Text(next_text));
+						}
+					}
 					self.next()
 				}
 			}
@@ -228,9 +295,10 @@ pub struct UnknownRefHandlingStream<'a, 'b, I> {
 	iter: I,
 	inject_event: VecDeque<Event<'a>>,
 	ref_handler: UnknownRefCallback<'a, 'b>,
+	limit: Option<Rc<InjectionLimit>>,
 }
 
-impl<'a, 'b, 'c, I> UnknownRefHandlingStream<'a, 'b, I>
+impl<'a, 'b, I> UnknownRefHandlingStream<'a, 'b, I>
 where
 	I: Iterator<Item = Event<'a>>,
 {
@@ -239,8 +307,16 @@ where
 			iter,
 			inject_event: VecDeque::new(),
 			ref_handler,
+			limit: None,
 		}
 	}
+
+	/// Stop calling the handler once `limit` is exhausted, the
+	/// remaining references are emitted as text
+	pub fn with_limit(mut self, limit: Rc<InjectionLimit>) -> Self {
+		self.limit = Some(limit);
+		self
+	}
 }
 
 impl<'a, 'b, I> Iterator for UnknownRefHandlingStream<'a, 'b, I>
@@ -259,7 +335,25 @@ where
 				match self.iter.next() {
 					Some(Event::Text(text)) => {
 						// Link text found
-						(self.ref_handler)(&mut self.inject_event, &link_url, &link_title, &text);
+						match &self.limit {
+							Some(limit) if limit.remaining() == 0 => {
+								limit.take(1);
+								self.inject_event.push_back(Event::Text(CowStr::Boxed(
+									format!("[{}]", text).into_boxed_str(),
+								)));
+							}
+							_ => {
+								(self.ref_handler)(
+									&mut self.inject_event,
+									&link_url,
+									&link_title,
+									&text,
+								);
+								if let Some(limit) = &self.limit {
+									limit.take(self.inject_event.len());
+								}
+							}
+						}
 					}
 					Some(Event::End(Tag::Link(LinkType::ShortcutUnknown, _, _))) => {
 						// No link text? Link end without any contents??
@@ -289,6 +383,164 @@ where
 	}
 }
 
+/// Tokens shorter than this many characters are never broken
+pub const WORD_BREAK_MIN_LENGTH: usize = 30;
+/// Longest run of characters without break opportunity in a long token
+pub const WORD_BREAK_MAX_RUN: usize = 20;
+
+fn is_word_break_char(c: char) -> bool {
+	matches!(c, '/' | '-' | '_' | '.')
+}
+
+/// Characters that must stay attached to the preceding character
+fn is_joined(c: char) -> bool {
+	unicode_normalization::char::is_combining_mark(c) || c == '\u{200D}' || c == '\u{FE0F}'
+}
+
+/// Split `text` at the places where long tokens may be broken across
+/// lines. Only tokens (runs without whitespace) of at least
+/// `min_length` characters are split, after `/`, `-`, `_` and `.`, and
+/// otherwise after at most `max_run` characters. The pieces always
+/// concatenate to `text`.
+pub fn word_break_pieces(text: &str, min_length: usize, max_run: usize) -> Vec<&str> {
+	let mut breaks = Vec::new();
+	let mut token_start = None;
+	let mut chars = text.char_indices().peekable();
+	while let Some((i, c)) = chars.next() {
+		if c.is_whitespace() {
+			continue;
+		}
+		let start = *token_start.get_or_insert(i);
+		let token_ended = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+		if !token_ended {
+			continue;
+		}
+		token_start = None;
+		let end = i + c.len_utf8();
+		let token = &text[start..end];
+		if token.chars().count() < min_length {
+			continue;
+		}
+		let mut run = 0;
+		let mut token_chars = token.char_indices().peekable();
+		while let Some((_, c)) = token_chars.next() {
+			run += 1;
+			let (j, next) = match token_chars.peek() {
+				Some(next) => *next,
+				None => break,
+			};
+			if is_joined(next) || c == '\u{200D}' {
+				continue;
+			}
+			if (is_word_break_char(c) && !is_word_break_char(next)) || run >= max_run {
+				breaks.push(start + j);
+				run = 0;
+			}
+		}
+	}
+
+	let mut pieces = Vec::with_capacity(breaks.len() + 1);
+	let mut last = 0;
+	for position in breaks {
+		pieces.push(&text[last..position]);
+		last = position;
+	}
+	pieces.push(&text[last..]);
+	pieces
+}
+
+/// Inserts `<wbr>` into long tokens like URLs or hashes, so they do not
+/// overflow narrow screens. Code, headings and image descriptions are
+/// left alone. Link destinations are untouched, only their text is broken.
+pub struct WordBreakStream<'a, I> {
+	iter: I,
+	/// Depth of elements whose text must not be broken
+	skip_depth: usize,
+	_marker: std::marker::PhantomData<Event<'a>>,
+}
+
+impl<'a, I> WordBreakStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	pub fn new(iter: I) -> Self {
+		Self {
+			iter,
+			skip_depth: 0,
+			_marker: std::marker::PhantomData,
+		}
+	}
+}
+
+impl<'a, I> Iterator for WordBreakStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	type Item = Event<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let event = self.iter.next()?;
+		match &event {
+			Event::Start(Tag::CodeBlock(_) | Tag::Heading(..) | Tag::Image(..)) => {
+				self.skip_depth += 1;
+			}
+			Event::End(Tag::CodeBlock(_) | Tag::Heading(..) | Tag::Image(..)) => {
+				self.skip_depth -= 1;
+			}
+			Event::Text(text) if self.skip_depth == 0 => {
+				let pieces = word_break_pieces(text, WORD_BREAK_MIN_LENGTH, WORD_BREAK_MAX_RUN);
+				if pieces.len() > 1 {
+					let mut html = String::with_capacity(text.len() + pieces.len() * 5);
+					for (i, piece) in pieces.into_iter().enumerate() {
+						if i > 0 {
+							html.push_str("<wbr>");
+						}
+						pulldown_cmark::escape::escape_html(&mut html, piece).unwrap();
+					}
+					return Some(Event::Html(CowStr::Boxed(html.into_boxed_str())));
+				}
+			}
+			_ => {}
+		}
+		Some(event)
+	}
+}
+
+/// Extract the plain text of a Markdown document for use as summary,
+/// e.g. in `<meta name="description">`. Whitespace is collapsed and the
+/// text is truncated to at most `max_chars` characters (not bytes),
+/// in which case it ends with an ellipsis. Infoboxes are skipped.
+pub fn plain_text_summary(markdown: &str, max_chars: usize) -> String {
+	let mut text = String::new();
+	let mut inside_infobox = false;
+	for event in pulldown_cmark::Parser::new(markdown) {
+		match event {
+			Event::Start(Tag::CodeBlock(kind)) if infobox::is_infobox(&kind) => {
+				inside_infobox = true;
+			}
+			Event::End(Tag::CodeBlock(_)) if inside_infobox => inside_infobox = false,
+			_ if inside_infobox => {}
+			Event::Text(part) | Event::Code(part) => text.push_str(&part),
+			Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+			_ => {}
+		}
+	}
+	let mut summary = String::with_capacity(text.len().min(max_chars * 4));
+	for (count, word) in text.split_whitespace().enumerate() {
+		if count > 0 {
+			summary.push(' ');
+		}
+		summary.push_str(word);
+	}
+	if summary.chars().count() <= max_chars {
+		return summary;
+	}
+	let mut truncated: String = summary.chars().take(max_chars.saturating_sub(1)).collect();
+	truncated.truncate(truncated.trim_end().len());
+	truncated.push('…');
+	truncated
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -595,7 +847,6 @@ mod tests {
 				special_urls
 					.iter()
 					.map(|text| Event::Text(CowStr::Borrowed(text)))
-					.into_iter()
 			)
 			.collect::<Vec<Event<'_>>>(),
 			special_urls
@@ -618,13 +869,12 @@ mod tests {
 		);
 
 		// Make sure that the following URLs with different protocols are all recognized
-		let protocol_urls = vec!["http://www.example.com", "https://www.example.com"];
+		let protocol_urls = ["http://www.example.com", "https://www.example.com"];
 		assert_eq!(
 			LinkHighlightStream::new(
 				protocol_urls
 					.iter()
 					.map(|text| Event::Text(CowStr::Borrowed(text)))
-					.into_iter()
 			)
 			.collect::<Vec<Event<'_>>>(),
 			protocol_urls
@@ -646,4 +896,106 @@ mod tests {
 				.collect::<Vec<Event<'_>>>()
 		);
 	}
+
+	#[test]
+	fn test_word_break_pieces() {
+		fn check(text: &str) -> Vec<&str> {
+			let pieces = word_break_pieces(text, 30, 20);
+			assert_eq!(pieces.concat(), text);
+			assert!(pieces.iter().all(|piece| !piece.is_empty()) || text.is_empty());
+			pieces
+		}
+		assert_eq!(
+			check("Short words stay as they are"),
+			vec!["Short words stay as they are"]
+		);
+		assert_eq!(check(""), vec![""]);
+		assert_eq!(
+			check("see https://example.com/some/long-path_to.file now"),
+			vec![
+				"see https://",
+				"example.",
+				"com/",
+				"some/",
+				"long-",
+				"path_",
+				"to.",
+				"file now"
+			]
+		);
+		// Tokens without punctuation are broken after `max_run` characters
+		let digest = "0123456789abcdef".repeat(4);
+		assert_eq!(
+			check(&digest),
+			vec![
+				&digest[..20],
+				&digest[20..40],
+				&digest[40..60],
+				&digest[60..]
+			]
+		);
+		// Multi-byte characters and combining marks are never split
+		let text =
+			"ä\u{0308}".repeat(40) + " " + &"紅杉".repeat(20) + " " + &"👩\u{200D}🌾".repeat(15);
+		let pieces = check(&text);
+		assert!(pieces.len() > 3);
+		for piece in &pieces[1..] {
+			assert!(!piece.starts_with('\u{0308}'));
+			assert!(!piece.starts_with('\u{200D}'));
+			assert!(!piece.starts_with('🌾'));
+		}
+		// Whitespace between tokens is kept as it is
+		check("  a\t\n b  ");
+		check(&format!("{}\n{}", "x/".repeat(30), "y".repeat(50)));
+	}
+
+	#[test]
+	fn test_word_break_stream() {
+		let render = |text: &str, breaks: bool| {
+			let mut html = String::new();
+			let parser = TextMergeStream::new(pulldown_cmark::Parser::new(text));
+			if breaks {
+				pulldown_cmark::html::push_html(&mut html, WordBreakStream::new(parser));
+			} else {
+				pulldown_cmark::html::push_html(&mut html, parser);
+			}
+			html
+		};
+		let long = "https://registry.example.com/image@sha256:0123456789abcdef0123456789abcdef";
+		let text = format!(
+			"{long} & <{long}>\n\n[{long}]({long})\n\n`{long}`\n\n# {long}\n\n![{long}]({long})\n\n```\n{long}\n```\n\n**{long}**",
+			long = long
+		);
+		let with_breaks = render(&text, true);
+		assert_eq!(with_breaks.replace("<wbr>", ""), render(&text, false));
+		// Paragraph, autolink text, link text and strong text
+		assert_eq!(with_breaks.matches("registry.<wbr>example").count(), 4);
+		// Link destinations, code, headings and images are untouched
+		assert_eq!(
+			with_breaks.matches(&format!("href=\"{}\"", long)).count(),
+			2
+		);
+		assert!(with_breaks.contains(&format!("<code>{}</code>", long)));
+		assert!(with_breaks.contains(&format!("<h1>{}</h1>", long)));
+		assert!(with_breaks.contains(&format!("alt=\"{}\"", long)));
+		assert!(with_breaks.contains(&format!("<code>{}\n</code>", long)));
+		// Text is still escaped
+		assert!(with_breaks.contains(" &amp; "));
+	}
+
+	#[test]
+	fn test_plain_text_summary() {
+		assert_eq!(
+			plain_text_summary("# Redwoods\n\nThey are *very*\ntall. `code`", 100),
+			"Redwoods They are very tall. code"
+		);
+		assert_eq!(plain_text_summary("", 10), "");
+		assert_eq!(plain_text_summary("Redwood trees", 8), "Redwood…");
+		// Truncation must respect char boundaries
+		assert_eq!(plain_text_summary("ääääää", 4), "äää…");
+		assert_eq!(
+			plain_text_summary("```infobox\nStatus: active\n```\n\nRedwoods are tall.", 100),
+			"Redwoods are tall."
+		);
+	}
 }
@@ -0,0 +1,143 @@
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use warp::hyper;
+
+/// Send `request` to `address` over a new plain HTTP/1.1 connection.
+/// The `Host` header is set to `host` and the `User-Agent` to the wiki
+/// version.
+///
+/// This does not check where `address` points to, requests to targets
+/// taken from user content must be checked before (see `favicon`).
+pub async fn send<E>(
+	address: SocketAddr,
+	host: &str,
+	request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, E>
+where
+	E: From<std::io::Error> + From<hyper::Error>,
+{
+	let stream = tokio::net::TcpStream::connect(address).await?;
+	send_on(stream, host, request).await
+}
+
+/// Like `send`, but over TLS. The certificate must be valid for `host`
+/// without the port, if it has one, and issued by one of the roots of
+/// the web PKI (`webpki-roots`).
+pub async fn send_tls<E>(
+	address: SocketAddr,
+	host: &str,
+	request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, E>
+where
+	E: From<std::io::Error> + From<hyper::Error>,
+{
+	let authority = host.parse::<hyper::http::uri::Authority>();
+	let name = authority
+		.as_ref()
+		.map_or(host, |authority| authority.host());
+	let server_name = rustls::ServerName::try_from(name)
+		.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+	let stream = tokio::net::TcpStream::connect(address).await?;
+	let stream = tokio_rustls::TlsConnector::from(client_config())
+		.connect(server_name, stream)
+		.await?;
+	send_on(stream, host, request).await
+}
+
+/// TLS settings of outbound connections, built once
+fn client_config() -> Arc<rustls::ClientConfig> {
+	static CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
+	CONFIG
+		.get_or_init(|| {
+			let mut roots = rustls::RootCertStore::empty();
+			roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+				rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+					anchor.subject,
+					anchor.spki,
+					anchor.name_constraints,
+				)
+			}));
+			Arc::new(
+				rustls::ClientConfig::builder()
+					.with_safe_defaults()
+					.with_root_certificates(roots)
+					.with_no_client_auth(),
+			)
+		})
+		.clone()
+}
+
+async fn send_on<S, E>(
+	stream: S,
+	host: &str,
+	mut request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, E>
+where
+	S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+	E: From<std::io::Error> + From<hyper::Error>,
+{
+	let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+	tokio::spawn(async move {
+		if let Err(err) = connection.await {
+			log::debug!("Outbound connection error: {:?}", err);
+		}
+	});
+
+	let headers = request.headers_mut();
+	if let Ok(value) = hyper::header::HeaderValue::from_str(host) {
+		headers.insert(hyper::header::HOST, value);
+	}
+	if let Ok(value) =
+		hyper::header::HeaderValue::from_str(&super::version::VersionInfo::get().short())
+	{
+		headers.insert(hyper::header::USER_AGENT, value);
+	}
+	Ok(sender.send_request(request).await?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use warp::Filter;
+
+	#[tokio::test]
+	async fn test_send_tls_checks_the_certificate() {
+		let routes = warp::path("hello").map(|| "Hello");
+		let (address, server) = warp::serve(routes)
+			.tls()
+			.cert_path("src/testdata/tls/cert.pem")
+			.key_path("src/testdata/tls/key.pem")
+			.bind_ephemeral(([127, 0, 0, 1], 0));
+		tokio::spawn(server);
+		let request = || {
+			hyper::Request::get("/hello")
+				.body(hyper::Body::empty())
+				.unwrap()
+		};
+
+		// The certificate of the tests is self-signed, not from the web PKI
+		let err =
+			send_tls::<Box<dyn std::error::Error + Send + Sync>>(address, "localhost", request())
+				.await
+				.unwrap_err();
+		assert!(err.to_string().contains("certificate"), "{}", err);
+		// The port is not part of the name
+		let err = send_tls::<Box<dyn std::error::Error + Send + Sync>>(
+			address,
+			&format!("localhost:{}", address.port()),
+			request(),
+		)
+		.await
+		.unwrap_err();
+		assert!(err.to_string().contains("certificate"), "{}", err);
+		let err =
+			send_tls::<Box<dyn std::error::Error + Send + Sync>>(address, "not a name", request())
+				.await
+				.unwrap_err();
+		assert!(err.to_string().contains("invalid dns name"), "{}", err);
+	}
+}
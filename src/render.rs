@@ -0,0 +1,981 @@
+use std::collections::VecDeque;
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag};
+use syntect::parsing::SyntaxSet;
+
+use super::codeblock_syntax_highlight::SyntaxHighlightStream;
+use super::database::{Database, ItemId};
+use super::favicon::LinkIconStream;
+use super::infobox::{self, InfoboxStream};
+use super::markdown_utils::{
+	InjectionLimit, LinkHighlightStream, TextMergeStream, UnknownRefHandlingStream, WordBreakStream,
+};
+use super::sections::{self, HeadingAnchorStream};
+
+/// Looks up the targets of wiki references like `[article:5]`
+pub trait RefResolver {
+	/// Title of the article with the given ID, `None` if it does not exist
+	/// or is deleted
+	fn article_title(&mut self, id: ItemId) -> Option<String>;
+
+	/// Title of the deleted article with the given ID, `None` if there
+	/// is no deleted article with it
+	fn deleted_article_title(&mut self, _id: ItemId) -> Option<String> {
+		None
+	}
+}
+
+impl RefResolver for Database {
+	fn article_title(&mut self, id: ItemId) -> Option<String> {
+		self.get_article_title(id)
+	}
+
+	fn deleted_article_title(&mut self, id: ItemId) -> Option<String> {
+		match self.get_article_title_include_deleted(id) {
+			Some((title, true)) => Some(title),
+			_ => None,
+		}
+	}
+}
+
+pub struct RenderOptions {
+	pub tables: bool,
+	pub strikethrough: bool,
+	pub tasklists: bool,
+	pub footnotes: bool,
+	pub smart_punctuation: bool,
+	/// Pass raw HTML in the Markdown through, otherwise it is shown as text
+	pub raw_html: bool,
+	/// Prefix of the link target of article references, the ID is appended
+	pub article_link_prefix: String,
+	/// Rendering stops with an error box after this many events
+	pub max_events: usize,
+	/// Maximum number of events each injecting stage (link highlighting,
+	/// reference resolution) may add to the document
+	pub max_injected_events: usize,
+	/// Containers nested deeper than this are flattened
+	pub max_nesting_depth: usize,
+}
+
+impl Default for RenderOptions {
+	fn default() -> Self {
+		RenderOptions {
+			tables: true,        // https://www.tablesgenerator.com/markdown_tables
+			strikethrough: true, // `~~strikethrough~~`
+			tasklists: true,     // `- [ ]` or `- [x]` or `- [X]`
+			footnotes: false,    // https://www.markdownguide.org/extended-syntax/#footnotes
+			// Em-dashes for `--` and nice quotes for `"Hello."` or `'thing'`
+			smart_punctuation: false,
+			raw_html: true,
+			article_link_prefix: "/article/".to_string(),
+			max_events: 200_000,
+			max_injected_events: 30_000,
+			max_nesting_depth: 32,
+		}
+	}
+}
+
+impl RenderOptions {
+	/// Options of the Markdown parser
+	pub fn parser_options(&self) -> Options {
+		let mut options = Options::empty();
+		if self.tables {
+			options.insert(Options::ENABLE_TABLES);
+		}
+		if self.strikethrough {
+			options.insert(Options::ENABLE_STRIKETHROUGH);
+		}
+		if self.tasklists {
+			options.insert(Options::ENABLE_TASKLISTS);
+		}
+		if self.footnotes {
+			options.insert(Options::ENABLE_FOOTNOTES);
+		}
+		if self.smart_punctuation {
+			options.insert(Options::ENABLE_SMART_PUNCTUATION);
+		}
+		options
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+	pub level: u32,
+	pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RenderResult {
+	pub html: String,
+	/// CSS of the syntax highlighting theme, empty without highlighting
+	pub style: String,
+	/// The document contains a `mermaid` code block
+	pub needs_mermaid: bool,
+	/// Targets of all `http` and `https` links, in document order
+	pub external_links: Vec<String>,
+	/// IDs of all `[article:5]` references, in document order, whether
+	/// the articles exist or not
+	pub article_refs: Vec<ItemId>,
+	/// All headings, in document order
+	pub headings: Vec<Heading>,
+	/// Problems found while rendering, e.g. references to missing articles
+	pub warnings: Vec<String>,
+}
+
+/// Markdown to HTML renderer, assembled from the event streams each
+/// caller needs, e.g. `Pipeline::new(options).with_db_refs(&mut db)
+/// .with_link_highlight().render_html(&article.text)`
+pub struct Pipeline<'r> {
+	options: RenderOptions,
+	resolver: Option<&'r mut dyn RefResolver>,
+	theme: Option<(&'r str, &'r SyntaxSet)>,
+	link_highlight: bool,
+	/// URL of the icon API, see `with_link_icons`
+	link_icons: Option<String>,
+	word_breaks: bool,
+	heading_anchors: bool,
+	section_edit_url: Option<String>,
+}
+
+impl<'r> Pipeline<'r> {
+	pub fn new(options: RenderOptions) -> Self {
+		Pipeline {
+			options,
+			resolver: None,
+			theme: None,
+			link_highlight: false,
+			link_icons: None,
+			word_breaks: false,
+			heading_anchors: false,
+			section_edit_url: None,
+		}
+	}
+
+	/// Resolve wiki references like `[article:5]` into links.
+	/// Without a resolver, they are shown as text.
+	pub fn with_db_refs(mut self, resolver: &'r mut dyn RefResolver) -> Self {
+		self.resolver = Some(resolver);
+		self
+	}
+
+	/// Highlight code blocks with the syntaxes of `syntax_set`, the CSS
+	/// of the theme (see `ThemeCss`) is returned in the result
+	pub fn with_syntax_highlight(mut self, theme_css: &'r str, syntax_set: &'r SyntaxSet) -> Self {
+		self.theme = Some((theme_css, syntax_set));
+		self
+	}
+
+	/// Turn URLs in the text into links
+	pub fn with_link_highlight(mut self) -> Self {
+		self.link_highlight = true;
+		self
+	}
+
+	/// Show the icon of the target site in front of external links,
+	/// served by `icon_url` (e.g. `/api/favicon`)
+	pub fn with_link_icons(mut self, icon_url: String) -> Self {
+		self.link_icons = Some(icon_url);
+		self
+	}
+
+	/// Allow long tokens like URLs to break across lines
+	pub fn with_word_breaks(mut self) -> Self {
+		self.word_breaks = true;
+		self
+	}
+
+	/// Give headings IDs to link to (see `sections::heading_anchors`),
+	/// and an edit link to the section if `edit_url` is given
+	pub fn with_heading_anchors(mut self, edit_url: Option<String>) -> Self {
+		self.heading_anchors = true;
+		self.section_edit_url = edit_url;
+		self
+	}
+
+	pub fn render_html(&mut self, text: &str) -> RenderResult {
+		let mut result = RenderResult::default();
+
+		let options = self.options.parser_options();
+
+		if let Some((theme_css, _)) = self.theme {
+			result.style = theme_css.to_string();
+		}
+
+		let infobox_html = self.render_infobox(text, options, &mut result);
+		let anchors = if self.heading_anchors {
+			sections::heading_anchors(text, options)
+		} else {
+			Vec::new()
+		};
+		let section_edit_url = self.section_edit_url.clone();
+
+		let mut broken_link_callback = |_link: pulldown_cmark::BrokenLink<'_>| {
+			// Returns Option<link_url, hover_description>
+			// Because we need deeper modifications (in particular, the
+			// text of the link itself, we just return empty strings here
+			// and modify the ShortcutUnknown Link events.
+			Some((CowStr::Borrowed(""), CowStr::Borrowed("")))
+		};
+
+		let raw_html = self.options.raw_html;
+		let article_link_prefix = &self.options.article_link_prefix;
+		let resolver = &mut self.resolver;
+		let warnings = &mut result.warnings;
+		let article_refs = &mut result.article_refs;
+		let mut unknown_ref_callback = |inject_event: &mut VecDeque<Event>,
+		                                _link_url: &str,
+		                                _link_title: &str,
+		                                link_text: &str| {
+			article_refs.extend(handle_unknown_ref(
+				resolver.as_deref_mut(),
+				article_link_prefix,
+				warnings,
+				inject_event,
+				link_text,
+			));
+		};
+
+		let parser = InfoboxStream::new(
+			TextMergeStream::new(
+				Parser::new_with_broken_link_callback(
+					text,
+					options,
+					Some(&mut broken_link_callback),
+				)
+				.map(|event| match event {
+					Event::Html(html) if !raw_html => Event::Text(html),
+					event => event,
+				}),
+			),
+			infobox_html,
+		);
+		let ref_limit = InjectionLimit::new(self.options.max_injected_events);
+		let link_limit = InjectionLimit::new(self.options.max_injected_events);
+		let mut stream: Box<dyn Iterator<Item = Event<'_>> + '_> = Box::new(
+			UnknownRefHandlingStream::new(parser, &mut unknown_ref_callback)
+				.with_limit(ref_limit.clone()),
+		);
+		if self.heading_anchors {
+			stream = Box::new(HeadingAnchorStream::new(
+				stream,
+				&anchors,
+				section_edit_url.as_deref(),
+			));
+		}
+		if let Some((_, syntax_set)) = self.theme {
+			stream = Box::new(SyntaxHighlightStream::new(stream, syntax_set));
+		}
+		if self.link_highlight {
+			stream = Box::new(LinkHighlightStream::new(stream).with_limit(link_limit.clone()));
+		}
+		if let Some(icon_url) = &self.link_icons {
+			stream = Box::new(LinkIconStream::new(stream, icon_url.clone()));
+		}
+		if self.word_breaks {
+			// After link highlighting, which needs the unbroken text
+			stream = Box::new(WordBreakStream::new(stream));
+		}
+		let mut limit_stream = LimitStream::new(
+			stream,
+			self.options.max_events,
+			self.options.max_nesting_depth,
+		);
+
+		let mut needs_mermaid = false;
+		let mut external_links = Vec::new();
+		let mut headings = Vec::new();
+		let mut current_heading: Option<Heading> = None;
+		let stream = limit_stream.by_ref().inspect(|event| match event {
+			Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang)))
+				if lang.as_ref() == "mermaid" =>
+			{
+				needs_mermaid = true;
+			}
+			Event::Start(Tag::Link(_, url, _))
+				if url.starts_with("http://") || url.starts_with("https://") =>
+			{
+				external_links.push(url.to_string());
+			}
+			Event::Start(Tag::Heading(level, _, _)) => {
+				current_heading = Some(Heading {
+					level: *level as u32,
+					text: String::new(),
+				});
+			}
+			Event::Text(text) | Event::Code(text) => {
+				if let Some(heading) = &mut current_heading {
+					heading.text.push_str(text);
+				}
+			}
+			Event::End(Tag::Heading(..)) => {
+				headings.extend(current_heading.take());
+			}
+			_ => {}
+		});
+
+		html::push_html(&mut result.html, stream);
+		let (truncated, flattened) = (limit_stream.truncated, limit_stream.flattened);
+		// Release the borrow of the warnings by the reference handler
+		drop(limit_stream);
+		if truncated {
+			result.warnings.push(format!(
+				"Rendering stopped after {} elements",
+				self.options.max_events
+			));
+		}
+		if flattened {
+			result.warnings.push(format!(
+				"Elements nested deeper than {} levels were flattened",
+				self.options.max_nesting_depth
+			));
+		}
+		if ref_limit.exceeded() {
+			result
+				.warnings
+				.push("Too many references, some were not resolved".to_string());
+		}
+		if link_limit.exceeded() {
+			result
+				.warnings
+				.push("Too many links, some were not highlighted".to_string());
+		}
+		result.needs_mermaid = needs_mermaid;
+		result.external_links.extend(external_links);
+		result.headings = headings;
+		result
+	}
+
+	/// Render the first ` ```infobox ` block of the document as table.
+	/// The values are rendered like the rest of the document, so links
+	/// and references work in them.
+	fn render_infobox(
+		&mut self,
+		text: &str,
+		options: Options,
+		result: &mut RenderResult,
+	) -> Option<String> {
+		let blocks = infobox::extract(text, options);
+		let first = blocks.first()?;
+		if blocks.len() > 1 {
+			result.warnings.push(format!(
+				"Only one infobox is allowed, {} more were ignored",
+				blocks.len() - 1
+			));
+		}
+		let parsed = infobox::parse(first);
+		result.warnings.extend(parsed.warnings);
+
+		let mut html = String::from("<aside class=\"infobox\"><table>\n");
+		for (key, value) in parsed.entries {
+			let value = self.render_html(&value);
+			result.warnings.extend(value.warnings);
+			result.external_links.extend(value.external_links);
+			result.article_refs.extend(value.article_refs);
+			let value_html = value.html.trim_end();
+			let value_html = value_html
+				.strip_prefix("<p>")
+				.and_then(|html| html.strip_suffix("</p>"))
+				.unwrap_or(value_html);
+			html.push_str("<tr><th>");
+			pulldown_cmark::escape::escape_html(&mut html, &key).unwrap();
+			html.push_str("</th><td>");
+			html.push_str(value_html);
+			html.push_str("</td></tr>\n");
+		}
+		html.push_str("</table></aside>\n");
+		Some(html)
+	}
+}
+
+/// Guards against pathological documents: Stops the stream after
+/// `max_events` events, closing all open elements and appending an
+/// error box, and drops elements nested deeper than `max_depth`
+/// (their contents are kept).
+struct LimitStream<'a, I> {
+	iter: I,
+	remaining_events: usize,
+	max_depth: usize,
+	/// Elements that are open at the moment, up to `max_depth`
+	open_tags: Vec<Tag<'a>>,
+	/// Depth including the dropped elements
+	depth: usize,
+	inject_event: VecDeque<Event<'a>>,
+	done: bool,
+	truncated: bool,
+	flattened: bool,
+}
+
+impl<'a, I> LimitStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	fn new(iter: I, max_events: usize, max_depth: usize) -> Self {
+		LimitStream {
+			iter,
+			remaining_events: max_events,
+			max_depth,
+			open_tags: Vec::new(),
+			depth: 0,
+			inject_event: VecDeque::new(),
+			done: false,
+			truncated: false,
+			flattened: false,
+		}
+	}
+}
+
+impl<'a, I> Iterator for LimitStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	type Item = Event<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if !self.inject_event.is_empty() {
+			return self.inject_event.pop_front();
+		}
+		if self.done {
+			return None;
+		}
+		loop {
+			let event = match self.iter.next() {
+				Some(event) => event,
+				None => {
+					self.done = true;
+					return None;
+				}
+			};
+			if self.remaining_events == 0 {
+				self.done = true;
+				self.truncated = true;
+				while let Some(tag) = self.open_tags.pop() {
+					self.inject_event.push_back(Event::End(tag));
+				}
+				self.inject_event.push_back(Event::Html(CowStr::Borrowed(
+					"<div class=\"render_error\">The article is too complex, rendering stopped here.</div>\n",
+				)));
+				return self.inject_event.pop_front();
+			}
+			self.remaining_events -= 1;
+			match event {
+				Event::Start(tag) => {
+					self.depth += 1;
+					if self.depth > self.max_depth {
+						self.flattened = true;
+						continue;
+					}
+					self.open_tags.push(tag.clone());
+					return Some(Event::Start(tag));
+				}
+				Event::End(tag) => {
+					self.depth -= 1;
+					if self.depth >= self.max_depth {
+						continue;
+					}
+					self.open_tags.pop();
+					return Some(Event::End(tag));
+				}
+				event => return Some(event),
+			}
+		}
+	}
+}
+
+/// Emit the link for a wiki reference, returns the ID of the article
+/// if `link_text` references one
+fn handle_unknown_ref(
+	resolver: Option<&mut (dyn RefResolver + '_)>,
+	article_link_prefix: &str,
+	warnings: &mut Vec<String>,
+	inject_event: &mut VecDeque<Event<'_>>,
+	link_text: &str,
+) -> Option<ItemId> {
+	if let (Some(article_str), Some(resolver)) = (link_text.strip_prefix("article:"), resolver) {
+		let mut article_iter = article_str.split('|');
+		// `split` always yields at least one item
+		let id_str = article_iter.next().unwrap_or_default();
+		if let Ok(id) = id_str.parse::<ItemId>() {
+			let dest_url = format!("{}{}", article_link_prefix, id);
+			if let Some(title) = resolver.article_title(id) {
+				let displayed_title = article_iter
+					.next()
+					.map_or_else(|| title.to_string(), |s| s.to_string());
+				inject_event.push_back(Event::Start(Tag::Link(
+					LinkType::Autolink,
+					CowStr::Boxed(dest_url.clone().into_boxed_str()),
+					CowStr::Boxed(title.clone().into_boxed_str()),
+				)));
+				inject_event
+					.push_back(Event::Text(CowStr::Boxed(displayed_title.into_boxed_str())));
+				inject_event.push_back(Event::End(Tag::Link(
+					LinkType::Autolink,
+					CowStr::Boxed(dest_url.into_boxed_str()),
+					CowStr::Boxed(title.into_boxed_str()),
+				)));
+				return Some(id);
+			}
+			// Looked up on every render, so restoring the article
+			// brings back the link without touching this article
+			if let Some(title) = resolver.deleted_article_title(id) {
+				let displayed_title = article_iter.next().unwrap_or(&title).to_string();
+				inject_event.push_back(Event::Html(CowStr::Borrowed(
+					r#"<del class="ref_deleted" title="deleted">"#,
+				)));
+				inject_event
+					.push_back(Event::Text(CowStr::Boxed(displayed_title.into_boxed_str())));
+				inject_event.push_back(Event::Html(CowStr::Borrowed("</del>")));
+				warnings.push(format!("Article #{} is deleted", id));
+				return Some(id);
+			}
+			warnings.push(format!("Article #{} does not exist", id));
+			inject_event.push_back(Event::Text(CowStr::Boxed(
+				format!("[{}]", link_text).into_boxed_str(),
+			)));
+			return Some(id);
+		}
+	}
+
+	// Does not match any wiki commands... Just emit as text.
+	inject_event.push_back(Event::Text(CowStr::Boxed(
+		format!("[{}]", link_text).into_boxed_str(),
+	)));
+	None
+}
+
+/// IDs of the articles `text` references, in document order and
+/// without duplicates. Only parses the references, their targets are
+/// not looked up.
+pub fn article_refs(text: &str, options: RenderOptions) -> Vec<ItemId> {
+	struct Unresolved;
+	impl RefResolver for Unresolved {
+		fn article_title(&mut self, _id: ItemId) -> Option<String> {
+			None
+		}
+	}
+	let mut refs = Pipeline::new(options)
+		.with_db_refs(&mut Unresolved)
+		.render_html(text)
+		.article_refs;
+	let mut seen = std::collections::HashSet::new();
+	refs.retain(|id| seen.insert(*id));
+	refs
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::collections::HashMap;
+
+	use crate::codeblock_syntax_highlight::Syntaxes;
+
+	impl RefResolver for HashMap<u32, String> {
+		fn article_title(&mut self, id: ItemId) -> Option<String> {
+			self.get(&id.to_string().parse::<u32>().unwrap()).cloned()
+		}
+	}
+
+	fn resolver() -> HashMap<u32, String> {
+		HashMap::from([(1, "Redwood".to_string()), (2, "Sequoia".to_string())])
+	}
+
+	/// The stream stack `article_page` wired by hand before the pipeline existed
+	fn legacy_render(
+		text: &str,
+		resolver: &mut HashMap<u32, String>,
+		syntax_set: &SyntaxSet,
+	) -> String {
+		let mut options = Options::empty();
+		options.insert(Options::ENABLE_TABLES);
+		options.insert(Options::ENABLE_STRIKETHROUGH);
+		options.insert(Options::ENABLE_TASKLISTS);
+		let mut broken_link_callback = |_link: pulldown_cmark::BrokenLink<'_>| {
+			Some((CowStr::Borrowed(""), CowStr::Borrowed("")))
+		};
+		let mut warnings = Vec::new();
+		let mut unknown_ref_callback =
+			|inject_event: &mut VecDeque<Event>, _: &str, _: &str, link_text: &str| {
+				handle_unknown_ref(
+					Some(&mut *resolver),
+					"/article/",
+					&mut warnings,
+					inject_event,
+					link_text,
+				);
+			};
+		let parser = UnknownRefHandlingStream::new(
+			TextMergeStream::new(Parser::new_with_broken_link_callback(
+				text,
+				options,
+				Some(&mut broken_link_callback),
+			)),
+			&mut unknown_ref_callback,
+		);
+		let parser = LinkHighlightStream::new(SyntaxHighlightStream::new(parser, syntax_set));
+		let mut html_output = String::new();
+		html::push_html(&mut html_output, parser);
+		html_output
+	}
+
+	const CORPUS: &[&str] = &[
+		"",
+		"# Redwoods\n\nThe *tallest* trees are [article:1].",
+		"See [article:2|the giant] and [article:3] and [unknown] and [article:x].",
+		"Visit https://example.com/a?b=c#d or <https://example.org>.",
+		"```rust\nfn main() {\n\tprintln!(\"https://not.a.link\");\n}\n```\n\n    indented code\n",
+		"| a | b |\n|---|---|\n| ~~c~~ | d |\n\n- [ ] todo\n- [x] done\n",
+		"<div class=\"raw\">raw html</div>\n\ninline <b>bold</b> html",
+		"```mermaid\ngraph TD;\n  A-->B;\n```",
+		"Text with [a link](https://example.com/x) and ![image](/img/a.png)",
+	];
+
+	#[test]
+	fn test_same_output_as_legacy() {
+		let syntaxes = Syntaxes::default();
+		for text in CORPUS {
+			let mut legacy_resolver = resolver();
+			let mut pipeline_resolver = resolver();
+			let result = Pipeline::new(RenderOptions::default())
+				.with_db_refs(&mut pipeline_resolver)
+				.with_syntax_highlight("", syntaxes.get())
+				.with_link_highlight()
+				.render_html(text);
+			assert_eq!(
+				result.html,
+				legacy_render(text, &mut legacy_resolver, syntaxes.get()),
+				"{}",
+				text
+			);
+		}
+	}
+
+	/// Resolves references with fixed titles, so the goldens do not
+	/// depend on a database
+	struct FixtureResolver;
+
+	impl RefResolver for FixtureResolver {
+		fn article_title(&mut self, id: ItemId) -> Option<String> {
+			match id.to_string().as_str() {
+				"1" => Some("Coast redwood".to_string()),
+				"2" => Some("Giant sequoia".to_string()),
+				_ => None,
+			}
+		}
+
+		fn deleted_article_title(&mut self, id: ItemId) -> Option<String> {
+			(id.to_string() == "4").then(|| "Dawn redwood".to_string())
+		}
+	}
+
+	/// Renders every `src/testdata/markdown/*.md` with the stages of the
+	/// article page and compares the result with the `.html` next to it.
+	/// With `REDWOOD_BLESS=1` the goldens are written instead, review the
+	/// diff before committing them.
+	#[test]
+	fn test_golden_corpus() {
+		let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata/markdown");
+		let bless = std::env::var_os("REDWOOD_BLESS").is_some_and(|value| value == "1");
+		let syntaxes = Syntaxes::default();
+
+		let mut fixtures: Vec<_> = std::fs::read_dir(&dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+			.collect();
+		fixtures.sort();
+		assert!(fixtures.len() >= 20, "{} fixtures", fixtures.len());
+
+		let mut failures = Vec::new();
+		for fixture in &fixtures {
+			let text = std::fs::read_to_string(fixture).unwrap();
+			let html = Pipeline::new(RenderOptions::default())
+				.with_link_highlight()
+				.with_word_breaks()
+				.with_heading_anchors(Some("/edit/article/1".to_string()))
+				.with_syntax_highlight("", syntaxes.get())
+				.with_db_refs(&mut FixtureResolver)
+				.render_html(&text)
+				.html;
+			let golden = fixture.with_extension("html");
+			if bless {
+				std::fs::write(&golden, &html).unwrap();
+				continue;
+			}
+			let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+			if html != expected {
+				let line = html
+					.lines()
+					.zip(expected.lines())
+					.position(|(a, b)| a != b)
+					.unwrap_or_else(|| html.lines().count().min(expected.lines().count()));
+				failures.push(format!(
+					"{}, line {}:\n  expected: {:?}\n  rendered: {:?}",
+					golden.display(),
+					line + 1,
+					expected.lines().nth(line).unwrap_or(""),
+					html.lines().nth(line).unwrap_or("")
+				));
+			}
+		}
+		assert!(
+			failures.is_empty(),
+			"Rendering differs from the goldens, rerun with REDWOOD_BLESS=1 if intended:\n{}",
+			failures.join("\n")
+		);
+	}
+
+	#[test]
+	fn test_word_breaks() {
+		let url = "https://example.com/a/very/long/path/to/some/resource.html";
+		let result = Pipeline::new(RenderOptions::default())
+			.with_link_highlight()
+			.with_word_breaks()
+			.render_html(url);
+		// Links are detected before the text is broken
+		assert_eq!(result.external_links, vec![url]);
+		assert!(result
+			.html
+			.starts_with(&format!("<p><a href=\"{}\">https://<wbr>", url)));
+		assert_eq!(
+			result.html.replace("<wbr>", ""),
+			format!("<p><a href=\"{0}\">{0}</a></p>\n", url)
+		);
+	}
+
+	#[test]
+	fn test_article_refs() {
+		let text = "[article:2] and [article:1|the tree] [article:2] [article:x] [other]\n\n`[article:3]`\n\n```infobox\nGenus: [article:4]\n```\n";
+		let ids: Vec<String> = article_refs(text, RenderOptions::default())
+			.iter()
+			.map(ItemId::to_string)
+			.collect();
+		// The infobox is rendered before the rest of the document
+		assert_eq!(ids, ["4", "2", "1"]);
+
+		let result = Pipeline::new(RenderOptions::default())
+			.with_db_refs(&mut resolver())
+			.render_html("[article:1] [article:9] [article:1]");
+		assert_eq!(result.article_refs.len(), 3);
+		assert!(article_refs("no refs", RenderOptions::default()).is_empty());
+	}
+
+	#[test]
+	fn test_side_data() {
+		let mut resolver = resolver();
+		let result = Pipeline::new(RenderOptions::default())
+			.with_db_refs(&mut resolver)
+			.with_link_highlight()
+			.render_html(
+				"# Top `code`\n\n## Sub\n\nhttps://example.com and [article:9]\n\n```mermaid\nA-->B\n```",
+			);
+		assert!(result.needs_mermaid);
+		assert!(result.style.is_empty());
+		assert_eq!(result.external_links, vec!["https://example.com"]);
+		assert_eq!(
+			result.headings,
+			vec![
+				Heading {
+					level: 1,
+					text: "Top code".to_string()
+				},
+				Heading {
+					level: 2,
+					text: "Sub".to_string()
+				},
+			]
+		);
+		assert_eq!(result.warnings, vec!["Article #9 does not exist"]);
+	}
+
+	#[test]
+	fn test_deleted_ref() {
+		/// Article 1 exists, article 2 is in the trash
+		struct Trash;
+		impl RefResolver for Trash {
+			fn article_title(&mut self, id: ItemId) -> Option<String> {
+				(id.to_string() == "1").then(|| "Redwood".to_string())
+			}
+			fn deleted_article_title(&mut self, id: ItemId) -> Option<String> {
+				(id.to_string() == "2").then(|| "Sequoia <old>".to_string())
+			}
+		}
+		let result = Pipeline::new(RenderOptions::default())
+			.with_db_refs(&mut Trash)
+			.render_html("[article:1] [article:2] [article:2|the sequoia] [article:3]");
+		assert_eq!(
+			result.html,
+			"<p><a href=\"/article/1\" title=\"Redwood\">Redwood</a> <del class=\"ref_deleted\" title=\"deleted\">Sequoia &lt;old&gt;</del> <del class=\"ref_deleted\" title=\"deleted\">the sequoia</del> [article:3]</p>\n"
+		);
+		assert_eq!(
+			result.warnings,
+			vec![
+				"Article #2 is deleted",
+				"Article #2 is deleted",
+				"Article #3 does not exist"
+			]
+		);
+	}
+
+	#[test]
+	fn test_infobox() {
+		let mut resolver = resolver();
+		let result = Pipeline::new(RenderOptions::default())
+			.with_db_refs(&mut resolver)
+			.with_link_highlight()
+			.render_html(
+				"# Redwood\n\n```infobox\nStatus: *active*\nGenus: [article:2]\nWebsite: https://example.com/a:b\nOwner <b>: x\n```\n\nTall trees.\n\n```infobox\nIgnored: yes\n```\n",
+			);
+		assert_eq!(
+			result.html,
+			concat!(
+				"<h1>Redwood</h1>\n",
+				"<aside class=\"infobox\"><table>\n",
+				"<tr><th>Status</th><td><em>active</em></td></tr>\n",
+				"<tr><th>Genus</th><td><a href=\"/article/2\" title=\"Sequoia\">Sequoia</a></td></tr>\n",
+				"<tr><th>Website</th><td><a href=\"https://example.com/a:b\">https://example.com/a:b</a></td></tr>\n",
+				"<tr><th>Owner &lt;b&gt;</th><td>x</td></tr>\n",
+				"</table></aside>\n",
+				"<p>Tall trees.</p>\n",
+			)
+		);
+		assert_eq!(result.external_links, vec!["https://example.com/a:b"]);
+		assert_eq!(
+			result.warnings,
+			vec!["Only one infobox is allowed, 1 more were ignored"]
+		);
+	}
+
+	#[test]
+	fn test_options() {
+		let options = RenderOptions {
+			raw_html: false,
+			article_link_prefix: "/article/".to_string(),
+			..RenderOptions::default()
+		};
+		let mut resolver = resolver();
+		let result = Pipeline::new(options)
+			.with_db_refs(&mut resolver)
+			.render_html("<script>x</script>\n\ninline <b>b</b> [article:1] https://example.com");
+		assert!(!result.html.contains("<script>"));
+		assert!(result.html.contains("&lt;script&gt;"));
+		assert!(result.html.contains("&lt;b&gt;"));
+		assert!(result
+			.html
+			.contains(r#"<a href="/article/1" title="Redwood">Redwood</a>"#));
+		// No link highlighting requested
+		assert!(!result.html.contains(r#"<a href="https://example.com""#));
+
+		// Without resolver, references stay text
+		let result = Pipeline::new(RenderOptions::default()).render_html("[article:1]");
+		assert_eq!(result.html, "<p>[article:1]</p>\n");
+		assert!(result.warnings.is_empty());
+	}
+
+	#[test]
+	fn test_markdown_extensions() {
+		let text = "| a |\n|---|\n| b |\n\n~~gone~~\n\n- [x] done\n\nNote[^1] -- \"quoted\"\n\n[^1]: Footnote\n";
+		for combination in 0..32 {
+			let enabled = |bit: u32| combination & (1 << bit) != 0;
+			let options = RenderOptions {
+				tables: enabled(0),
+				strikethrough: enabled(1),
+				tasklists: enabled(2),
+				footnotes: enabled(3),
+				smart_punctuation: enabled(4),
+				..RenderOptions::default()
+			};
+			let html = Pipeline::new(options).render_html(text).html;
+			let check = |bit: u32, marker: &str| {
+				assert_eq!(html.contains(marker), enabled(bit), "{}: {}", marker, html);
+			};
+			check(0, "<table>");
+			check(1, "<del>gone</del>");
+			check(2, "<input disabled=\"\" type=\"checkbox\" checked=\"\"/>");
+			check(3, "<sup class=\"footnote-reference\">");
+			check(4, "\u{201c}quoted\u{201d}");
+			check(4, "\u{2013} \u{201c}");
+		}
+	}
+
+	fn render_limited(text: &str, options: RenderOptions) -> RenderResult {
+		let mut resolver = resolver();
+		Pipeline::new(options)
+			.with_db_refs(&mut resolver)
+			.with_link_highlight()
+			.render_html(text)
+	}
+
+	#[test]
+	fn test_adversarial_unclosed_brackets() {
+		let text = "[".repeat(100_000);
+		let result = render_limited(&text, RenderOptions::default());
+		assert!(result.html.len() < 2 * text.len());
+		assert!(result.warnings.is_empty());
+	}
+
+	#[test]
+	fn test_adversarial_nesting() {
+		let text = "> ".repeat(10_000) + "deep";
+		let result = render_limited(&text, RenderOptions::default());
+		assert_eq!(result.html.matches("<blockquote>").count(), 32);
+		assert_eq!(result.html.matches("</blockquote>").count(), 32);
+		assert!(result.html.contains("deep"));
+		assert!(result.warnings.iter().any(|w| w.contains("flattened")));
+	}
+
+	#[test]
+	fn test_adversarial_many_lines() {
+		let text = "a\n\n".repeat(1_000_000);
+		let result = render_limited(&text, RenderOptions::default());
+		// Each paragraph is three events
+		assert_eq!(result.html.matches("<p>").count(), 200_000 / 3 + 1);
+		assert_eq!(result.html.matches("</p>").count(), 200_000 / 3 + 1);
+		assert!(result.html.ends_with("rendering stopped here.</div>\n"));
+		assert_eq!(
+			result.warnings,
+			vec!["Rendering stopped after 200000 elements"]
+		);
+	}
+
+	#[test]
+	fn test_adversarial_links_and_refs() {
+		let options = || RenderOptions {
+			max_injected_events: 300,
+			..RenderOptions::default()
+		};
+		let result = render_limited(&"https://example.com ".repeat(1000), options());
+		assert!(result.external_links.len() <= 100);
+		assert!(result
+			.html
+			.contains("https://example.com https://example.com"));
+		assert_eq!(
+			result.warnings,
+			vec!["Too many links, some were not highlighted"]
+		);
+
+		let result = render_limited(&"[article:1] ".repeat(1000), options());
+		assert_eq!(result.html.matches("<a href").count(), 100);
+		assert_eq!(result.html.matches("[article:1]").count(), 900);
+		assert_eq!(
+			result.warnings,
+			vec!["Too many references, some were not resolved"]
+		);
+	}
+
+	#[test]
+	fn test_limits_do_not_affect_normal_articles() {
+		let text: String = CORPUS.join("\n\n").repeat(50);
+		let result = render_limited(&text, RenderOptions::default());
+		let unlimited = render_limited(
+			&text,
+			RenderOptions {
+				max_events: usize::MAX,
+				max_injected_events: usize::MAX,
+				max_nesting_depth: usize::MAX,
+				..RenderOptions::default()
+			},
+		);
+		assert_eq!(result.html, unlimited.html);
+		assert_eq!(result.warnings, unlimited.warnings);
+	}
+}
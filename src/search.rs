@@ -0,0 +1,400 @@
+use std::fmt::Write;
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use pulldown_cmark::escape::escape_html;
+use regex::{Regex, RegexBuilder};
+
+use super::database::Article;
+use super::regex_utils::{DoPartition, Part};
+use super::urls::{self, BasePath};
+
+/// Search results are grouped into these categories,
+/// in order of decreasing relevance
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MatchCategory {
+	Exact,
+	Title,
+	Text,
+}
+
+impl MatchCategory {
+	const ALL: [MatchCategory; 3] = [
+		MatchCategory::Exact,
+		MatchCategory::Title,
+		MatchCategory::Text,
+	];
+
+	fn label(&self) -> &'static str {
+		match self {
+			MatchCategory::Exact => "Exact matches",
+			MatchCategory::Title => "Title matches",
+			MatchCategory::Text => "Text matches",
+		}
+	}
+
+	/// Name of the category in query strings
+	pub fn query_name(&self) -> &'static str {
+		match self {
+			MatchCategory::Exact => "exact",
+			MatchCategory::Title => "title",
+			MatchCategory::Text => "text",
+		}
+	}
+
+	pub fn from_query_name(name: &str) -> Option<MatchCategory> {
+		MatchCategory::ALL
+			.iter()
+			.find(|category| category.query_name() == name)
+			.copied()
+	}
+}
+
+/// Which part of the search results to render
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SearchView {
+	/// All categories, each showing at most `limit` results (at
+	/// least one, like the pages of `Category`)
+	Overview { limit: usize },
+	/// A single category, paginated with `limit` results per page
+	/// (pages are counted from 1)
+	Category {
+		category: MatchCategory,
+		page: usize,
+		limit: usize,
+	},
+}
+
+pub struct CategorizedResults<'a> {
+	exact: Vec<&'a Article>,
+	title: Vec<&'a Article>,
+	text: Vec<&'a Article>,
+}
+
+impl<'a> CategorizedResults<'a> {
+	pub fn get(&self, category: MatchCategory) -> &[&'a Article] {
+		match category {
+			MatchCategory::Exact => &self.exact,
+			MatchCategory::Title => &self.title,
+			MatchCategory::Text => &self.text,
+		}
+	}
+}
+
+fn search_regex(search_term: &str) -> Regex {
+	RegexBuilder::new(&regex::escape(search_term))
+		.case_insensitive(true)
+		.build()
+		.expect("Invalid Regex")
+}
+
+/// Sort the articles returned by the database into the match categories
+pub fn categorize<'a>(articles: &'a [Article], search_term: &str) -> CategorizedResults<'a> {
+	let search_regex = search_regex(search_term);
+	let search_term_lowercase = search_term.to_lowercase();
+	let mut results = CategorizedResults {
+		exact: Vec::new(),
+		title: Vec::new(),
+		text: Vec::new(),
+	};
+	for article in articles {
+		if article.title.to_lowercase() == search_term_lowercase {
+			results.exact.push(article);
+		} else if search_regex.is_match(&article.title) {
+			results.title.push(article);
+		} else {
+			results.text.push(article);
+		}
+	}
+	results
+}
+
+/// Link to a page of a single result category
+fn category_url(
+	base_path: &BasePath,
+	search_term: &str,
+	category: MatchCategory,
+	page: usize,
+) -> String {
+	format!(
+		"{}/search/article?search_term_plain={}&amp;category={}&amp;page={}",
+		base_path.as_str(),
+		utf8_percent_encode(search_term, NON_ALPHANUMERIC),
+		category.query_name(),
+		page
+	)
+}
+
+fn write_article_entry(
+	out: &mut String,
+	base_path: &BasePath,
+	search_regex: &Regex,
+	article: &Article,
+) {
+	out.push_str("<li><a href=\"");
+	escape_html(
+		&mut *out,
+		&base_path.url(&urls::article_path(article.id, &article.slug)),
+	)
+	.unwrap();
+	out.push_str("\">");
+	for part in search_regex.partition(&article.title) {
+		match part {
+			Part::NoMatch(text) => {
+				escape_html(&mut *out, text).unwrap();
+			}
+			Part::Match(text) => {
+				out.push_str("<b style=\"color:red;\">");
+				escape_html(&mut *out, text).unwrap();
+				out.push_str("</b>");
+			}
+		}
+	}
+	writeln!(
+		out,
+		"</a> <span style=\"color: #BBBBBB;\">#{}</span></li>",
+		article.id
+	)
+	.unwrap();
+}
+
+// Rough size of one rendered result entry, used to pre-size the output
+const ENTRY_SIZE_ESTIMATE: usize = 128;
+
+/// Render the search results as HTML list, linking to the pages under
+/// `base_path`. Empty categories are omitted.
+pub fn render_results(
+	base_path: &BasePath,
+	results: &CategorizedResults<'_>,
+	search_term: &str,
+	view: SearchView,
+) -> String {
+	let search_regex = search_regex(search_term);
+	match view {
+		SearchView::Overview { limit } => {
+			let limit = limit.max(1);
+			let shown_total: usize = MatchCategory::ALL
+				.iter()
+				.map(|category| results.get(*category).len().min(limit))
+				.sum();
+			let mut out = String::with_capacity(shown_total * ENTRY_SIZE_ESTIMATE);
+			for category in MatchCategory::ALL {
+				let articles = results.get(category);
+				if articles.is_empty() {
+					continue;
+				}
+				writeln!(
+					out,
+					"<h3>{}</h3>\n<ul class=\"article_list\">",
+					category.label()
+				)
+				.unwrap();
+				for article in articles.iter().take(limit) {
+					write_article_entry(&mut out, base_path, &search_regex, article);
+				}
+				out.push_str("</ul>\n");
+				if articles.len() > limit {
+					writeln!(
+						out,
+						"<p><i>Showing first {} of {} &mdash; refine your search or <a href=\"{}\">view all</a></i></p>",
+						limit,
+						articles.len(),
+						category_url(base_path, search_term, category, 1)
+					)
+					.unwrap();
+				}
+			}
+			out
+		}
+		SearchView::Category {
+			category,
+			page,
+			limit,
+		} => {
+			let articles = results.get(category);
+			let limit = limit.max(1);
+			let page_count = articles.len().div_ceil(limit).max(1);
+			let page = page.clamp(1, page_count);
+			let mut out = String::with_capacity(limit * ENTRY_SIZE_ESTIMATE);
+			write!(
+				out,
+				"<h3>{} (page {} of {}, {} in total)</h3>\n<ul class=\"article_list\">\n",
+				category.label(),
+				page,
+				page_count,
+				articles.len()
+			)
+			.unwrap();
+			for article in articles.iter().skip((page - 1) * limit).take(limit) {
+				write_article_entry(&mut out, base_path, &search_regex, article);
+			}
+			out.push_str("</ul>\n");
+			if page > 1 {
+				write!(
+					out,
+					"<a href=\"{}\">&larr; Previous</a> ",
+					category_url(base_path, search_term, category, page - 1)
+				)
+				.unwrap();
+			}
+			if page < page_count {
+				write!(
+					out,
+					"<a href=\"{}\">Next &rarr;</a>",
+					category_url(base_path, search_term, category, page + 1)
+				)
+				.unwrap();
+			}
+			out
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::convert::TryFrom;
+
+	use crate::timestamp::UtcTimestamp;
+
+	fn article(id: u32, title: &str) -> Article {
+		Article {
+			id: id.into(),
+			title: title.to_string(),
+			text: "e".to_string(),
+			date_created: UtcTimestamp::now(),
+			date_modified: UtcTimestamp::now(),
+			revision: 0,
+			slug: crate::slug::slugify(title),
+		}
+	}
+
+	fn entry_count(html: &str) -> usize {
+		html.matches("<li><a href=\"/article/").count()
+	}
+
+	#[test]
+	fn test_categorize() {
+		let articles = vec![
+			article(1, "Tree"),
+			article(2, "tree"),
+			article(3, "Redwood tree"),
+			article(4, "Observatory"),
+		];
+		let results = categorize(&articles, "TREE");
+		let ids = |category| {
+			results
+				.get(category)
+				.iter()
+				.map(|a| a.id.to_string())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(ids(MatchCategory::Exact), vec!["1", "2"]);
+		assert_eq!(ids(MatchCategory::Title), vec!["3"]);
+		assert_eq!(ids(MatchCategory::Text), vec!["4"]);
+	}
+
+	#[test]
+	fn test_overview_cap() {
+		let articles: Vec<Article> = (1..=25)
+			.map(|id| article(id, &format!("Plain {}", id)))
+			.chain((26..=28).map(|id| article(id, &format!("e {}", id))))
+			.collect();
+		let results = categorize(&articles, "e");
+
+		let html = render_results(
+			&BasePath::default(),
+			&results,
+			"e",
+			SearchView::Overview { limit: 10 },
+		);
+		assert_eq!(entry_count(&html), 10 + 3);
+		assert!(html.contains("Showing first 10 of 25"));
+		assert!(html.contains("/search/article?search_term_plain=e&amp;category=text&amp;page=1"));
+		// The category below the limit must not show a notice
+		assert_eq!(html.matches("Showing first").count(), 1);
+		assert!(!html.contains("Exact matches"));
+
+		// Exactly at the limit there is nothing to truncate
+		let html = render_results(
+			&BasePath::default(),
+			&results,
+			"e",
+			SearchView::Overview { limit: 25 },
+		);
+		assert_eq!(entry_count(&html), 28);
+		assert!(!html.contains("Showing first"));
+
+		// No limit below one result per category, as on the category pages
+		let html = render_results(
+			&BasePath::default(),
+			&results,
+			"e",
+			SearchView::Overview { limit: 0 },
+		);
+		assert_eq!(entry_count(&html), 2);
+		assert!(html.contains("Showing first 1 of 25"));
+	}
+
+	#[test]
+	fn test_category_pages() {
+		let articles: Vec<Article> = (1..=25).map(|id| article(id, "Plain")).collect();
+		let results = categorize(&articles, "e");
+		let page = |page| {
+			render_results(
+				&BasePath::default(),
+				&results,
+				"e",
+				SearchView::Category {
+					category: MatchCategory::Text,
+					page,
+					limit: 10,
+				},
+			)
+		};
+
+		let first = page(1);
+		assert_eq!(entry_count(&first), 10);
+		assert!(first.contains("page 1 of 3, 25 in total"));
+		assert!(first.contains("page=2\">Next"));
+		assert!(!first.contains("Previous"));
+
+		let last = page(3);
+		assert_eq!(entry_count(&last), 5);
+		assert!(last.contains("/article/21/plain\""));
+		assert!(last.contains("page=2\">&larr; Previous"));
+		assert!(!last.contains("Next"));
+
+		// Out of range pages are clamped to the last page
+		assert_eq!(page(100), last);
+	}
+
+	#[test]
+	fn test_titles_escaped() {
+		let articles = vec![article(1, r#"<script>"tree"</script>"#)];
+		let results = categorize(&articles, "tree");
+		let html = render_results(
+			&BasePath::default(),
+			&results,
+			"tree",
+			SearchView::Overview { limit: 10 },
+		);
+		assert!(html.contains(
+			r#"&lt;script&gt;&quot;<b style="color:red;">tree</b>&quot;&lt;/script&gt;</a>"#
+		));
+		assert!(!html.contains("<script>"));
+	}
+
+	#[test]
+	fn test_search_term_encoding() {
+		assert_eq!(
+			category_url(&BasePath::default(), "a&b c", MatchCategory::Title, 2),
+			"/search/article?search_term_plain=a%26b%20c&amp;category=title&amp;page=2"
+		);
+		let base_path = BasePath::try_from("/wiki".to_string()).unwrap();
+		assert_eq!(
+			category_url(&base_path, "e", MatchCategory::Text, 1),
+			"/wiki/search/article?search_term_plain=e&amp;category=text&amp;page=1"
+		);
+	}
+}
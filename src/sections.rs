@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+use super::slug;
+
+/// Prefix of the anchors of headings, so they cannot collide with the
+/// other IDs of the page
+pub const ANCHOR_PREFIX: &str = "section-";
+
+#[derive(Debug, PartialEq)]
+pub struct HeadingAnchor {
+	/// `section-` followed by the slug of the heading text
+	pub id: String,
+	/// Line of the heading in the Markdown source, starting at 1
+	pub line: usize,
+}
+
+/// Anchors of all headings, in document order. A heading text that
+/// occurs more than once gets a numeric suffix (`-2`, `-3`, ...) from
+/// its second occurrence on, so every anchor is unique. The anchors
+/// only depend on the text, so links to sections stay stable as long
+/// as the headings do not change.
+pub fn heading_anchors(text: &str, options: Options) -> Vec<HeadingAnchor> {
+	let mut anchors = Vec::new();
+	let mut used = HashSet::new();
+	let mut current: Option<(usize, String)> = None;
+	// Line counting continues where the previous heading left off
+	let (mut counted_to, mut line) = (0, 1);
+	for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+		match event {
+			Event::Start(Tag::Heading(..)) => {
+				line += text[counted_to..range.start].matches('\n').count();
+				counted_to = range.start;
+				current = Some((line, String::new()));
+			}
+			Event::Text(text) | Event::Code(text) => {
+				if let Some((_, heading)) = &mut current {
+					heading.push_str(&text);
+				}
+			}
+			Event::End(Tag::Heading(..)) => {
+				if let Some((line, heading)) = current.take() {
+					let id = slug::candidates(&slug::slugify(&heading))
+						.map(|slug| format!("{}{}", ANCHOR_PREFIX, slug))
+						.find(|id| !used.contains(id))
+						.expect("The slug candidates are endless");
+					used.insert(id.clone());
+					anchors.push(HeadingAnchor { id, line });
+				}
+			}
+			_ => {}
+		}
+	}
+	anchors
+}
+
+/// Gives the headings their anchor as ID, in document order, and adds
+/// an edit link to the section after the heading text if `edit_url`
+/// is given
+pub struct HeadingAnchorStream<'a, I> {
+	iter: I,
+	anchors: std::slice::Iter<'a, HeadingAnchor>,
+	edit_url: Option<&'a str>,
+	current: Option<&'a HeadingAnchor>,
+	inject_event: Option<Event<'a>>,
+}
+
+impl<'a, I> HeadingAnchorStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	pub fn new(iter: I, anchors: &'a [HeadingAnchor], edit_url: Option<&'a str>) -> Self {
+		Self {
+			iter,
+			anchors: anchors.iter(),
+			edit_url,
+			current: None,
+			inject_event: None,
+		}
+	}
+}
+
+impl<'a, I> Iterator for HeadingAnchorStream<'a, I>
+where
+	I: Iterator<Item = Event<'a>>,
+{
+	type Item = Event<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(event) = self.inject_event.take() {
+			return Some(event);
+		}
+		match self.iter.next()? {
+			Event::Start(Tag::Heading(level, id, classes)) => {
+				self.current = self.anchors.next();
+				let id = self.current.map(|anchor| anchor.id.as_str()).or(id);
+				Some(Event::Start(Tag::Heading(level, id, classes)))
+			}
+			event @ Event::End(Tag::Heading(..)) => match (self.current.take(), self.edit_url) {
+				(Some(anchor), Some(edit_url)) => {
+					self.inject_event = Some(event);
+					Some(Event::Html(CowStr::Boxed(
+						format!(
+							r##" <a class="section_edit" href="{}#{}" aria-label="Edit section">edit</a>"##,
+							edit_url, anchor.id
+						)
+						.into_boxed_str(),
+					)))
+				}
+				_ => Some(event),
+			},
+			event => Some(event),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use pulldown_cmark::html;
+
+	fn anchor(id: &str, line: usize) -> HeadingAnchor {
+		HeadingAnchor {
+			id: id.to_string(),
+			line,
+		}
+	}
+
+	#[test]
+	fn test_heading_anchors() {
+		let text = "# Redwoods\n\nText\n\n## Habitat\n\nMore\ntext\n\nSetext heading\n---\n\n## Habitat\n\n## Habitat\n\n## `Code` and *more*\n";
+		assert_eq!(
+			heading_anchors(text, Options::empty()),
+			vec![
+				anchor("section-redwoods", 1),
+				anchor("section-habitat", 5),
+				anchor("section-setext-heading", 10),
+				anchor("section-habitat-2", 13),
+				anchor("section-habitat-3", 15),
+				anchor("section-code-and-more", 17),
+			]
+		);
+	}
+
+	#[test]
+	fn test_heading_anchors_nested() {
+		let text = "Intro\n\n> Quote\n>\n> ## Quoted\n\n- item\n\n  ### In list\n\n```\n# Not a heading\n```\n\n## Quoted\n";
+		assert_eq!(
+			heading_anchors(text, Options::empty()),
+			vec![
+				anchor("section-quoted", 5),
+				anchor("section-in-list", 9),
+				anchor("section-quoted-2", 15),
+			]
+		);
+		assert!(heading_anchors("No headings\n", Options::empty()).is_empty());
+	}
+
+	#[test]
+	fn test_stream() {
+		let text = "# Tree\n\nText\n\n> ## Tree";
+		let anchors = heading_anchors(text, Options::empty());
+		let render = |edit_url| {
+			let mut out = String::new();
+			html::push_html(
+				&mut out,
+				HeadingAnchorStream::new(Parser::new(text), &anchors, edit_url),
+			);
+			out
+		};
+		assert_eq!(
+			render(None),
+			"<h1 id=\"section-tree\">Tree</h1>\n<p>Text</p>\n<blockquote>\n<h2 id=\"section-tree-2\">Tree</h2>\n</blockquote>\n"
+		);
+		assert_eq!(
+			render(Some("/edit/article/5")),
+			"<h1 id=\"section-tree\">Tree <a class=\"section_edit\" href=\"/edit/article/5#section-tree\" aria-label=\"Edit section\">edit</a></h1>\n<p>Text</p>\n<blockquote>\n<h2 id=\"section-tree-2\">Tree <a class=\"section_edit\" href=\"/edit/article/5#section-tree-2\" aria-label=\"Edit section\">edit</a></h2>\n</blockquote>\n"
+		);
+	}
+}
@@ -0,0 +1,195 @@
+use rand::RngCore;
+
+use super::database::ItemId;
+use super::hmac::hmac_sha1;
+use super::slug;
+
+/// Most similar articles shown before creating an article
+pub const MAX_CANDIDATES: usize = 10;
+
+/// A title that is a prefix of another one only counts as similar if it
+/// has at least this many characters, otherwise `Tree` would find every
+/// article about trees
+const MIN_PREFIX_LENGTH: usize = 6;
+
+/// Existing articles with titles so close to `title` that creating an
+/// article with it is probably a mistake, the most similar first: titles
+/// that only differ in case, accents or punctuation, titles a few typos
+/// apart, and titles that extend the other one by whole words (`Server
+/// setup` and `Server Setup notes`).
+pub fn candidates<'a>(title: &str, titles: &'a [(ItemId, String)]) -> Vec<&'a (ItemId, String)> {
+	let title = words(title);
+	let mut found: Vec<(usize, &(ItemId, String))> = titles
+		.iter()
+		.filter_map(|entry| Some((distance(&title, &words(&entry.1))?, entry)))
+		.collect();
+	found.sort_by(|(a, (_, a_title)), (b, (_, b_title))| a.cmp(b).then(a_title.cmp(b_title)));
+	found.truncate(MAX_CANDIDATES);
+	found.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The title as lowercase words without accents, separated by `-`
+fn words(title: &str) -> Vec<char> {
+	slug::slugify(title).chars().collect()
+}
+
+/// How far apart two normalized titles are, `None` if they are not
+/// similar at all. Prefix matches rank after typos.
+fn distance(a: &[char], b: &[char]) -> Option<usize> {
+	let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+	let max_edits = match short.len() {
+		0..=4 => 0,
+		5..=11 => 1,
+		_ => 2,
+	};
+	if let Some(edits) = edit_distance(short, long, max_edits) {
+		return Some(edits);
+	}
+	let whole_words = long.starts_with(short) && long.get(short.len()) == Some(&'-');
+	if short.len() >= MIN_PREFIX_LENGTH && whole_words {
+		Some(max_edits + 1 + long.len() - short.len())
+	} else {
+		None
+	}
+}
+
+/// Levenshtein distance, `None` if it is larger than `max`
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+	if b.len() - a.len().min(b.len()) > max || a.len() - b.len().min(a.len()) > max {
+		return None;
+	}
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	let mut current = vec![0; b.len() + 1];
+	for (i, &ca) in a.iter().enumerate() {
+		current[0] = i + 1;
+		for (j, &cb) in b.iter().enumerate() {
+			let substitution = previous[j] + usize::from(ca != cb);
+			current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+		}
+		std::mem::swap(&mut previous, &mut current);
+	}
+	Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+/// Signs the tokens with which the create form confirms that the
+/// article is to be created despite similar titles. The key is random
+/// and only kept in memory, so after a restart the similar titles are
+/// shown once more.
+pub struct ConfirmKey([u8; 32]);
+
+impl ConfirmKey {
+	pub fn random() -> ConfirmKey {
+		let mut key = [0; 32];
+		rand::thread_rng().fill_bytes(&mut key);
+		ConfirmKey(key)
+	}
+
+	/// Token confirming the creation of an article titled `title`, it
+	/// is not valid for any other title
+	pub fn token(&self, title: &str) -> String {
+		hmac_sha1(&self.0, format!("create-article\0{}", title).as_bytes())
+			.iter()
+			.map(|byte| format!("{:02x}", byte))
+			.collect()
+	}
+
+	pub fn verify(&self, title: &str, token: &str) -> bool {
+		let expected = self.token(title);
+		// Compare in constant time, the time must not tell how much of
+		// a guessed token is right
+		expected.len() == token.len()
+			&& expected
+				.bytes()
+				.zip(token.bytes())
+				.fold(0, |diff, (a, b)| diff | (a ^ b))
+				== 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn titles(titles: &[&str]) -> Vec<(ItemId, String)> {
+		titles
+			.iter()
+			.enumerate()
+			.map(|(i, title)| ((i as u32 + 1).into(), title.to_string()))
+			.collect()
+	}
+
+	fn found(title: &str, existing: &[(ItemId, String)]) -> Vec<String> {
+		candidates(title, existing)
+			.into_iter()
+			.map(|(_, title)| title.clone())
+			.collect()
+	}
+
+	#[test]
+	fn test_candidates() {
+		let existing = titles(&[
+			"Server setup",
+			"Server Setup notes",
+			"Servers",
+			"Backup",
+			"Tree",
+			"Tree care",
+			"Coast redwood",
+		]);
+		assert_eq!(
+			found("Server setup notes", &existing),
+			["Server Setup notes", "Server setup"]
+		);
+		assert_eq!(
+			found("server-setup", &existing),
+			["Server setup", "Server Setup notes"]
+		);
+		assert_eq!(found("Sever setup", &existing), ["Server setup"]);
+		assert_eq!(found("Coast redwod", &existing), ["Coast redwood"]);
+		assert_eq!(found("Backups", &existing), ["Backup"]);
+		// Short titles must match exactly, and are no prefix of others
+		assert!(found("Trees", &existing).is_empty());
+		assert_eq!(found("Trée", &existing), ["Tree"]);
+		assert!(found("Free", &existing).is_empty());
+		// Nothing similar, the article is created right away
+		assert!(found("Giant sequoia", &existing).is_empty());
+		assert!(found("Coast", &existing).is_empty());
+
+		let many: Vec<String> = (0..20).map(|i| format!("Server setup {}", i)).collect();
+		let many = titles(&many.iter().map(String::as_str).collect::<Vec<_>>());
+		assert_eq!(candidates("Server setup", &many).len(), MAX_CANDIDATES);
+	}
+
+	#[test]
+	fn test_edit_distance() {
+		let chars = |s: &str| s.chars().collect::<Vec<_>>();
+		assert_eq!(
+			edit_distance(&chars("kitten"), &chars("sitting"), 3),
+			Some(3)
+		);
+		assert_eq!(edit_distance(&chars("kitten"), &chars("sitting"), 2), None);
+		assert_eq!(edit_distance(&chars(""), &chars("ab"), 2), Some(2));
+		assert_eq!(edit_distance(&chars("ab"), &chars("ab"), 0), Some(0));
+	}
+
+	#[test]
+	fn test_confirm_token() {
+		// RFC 2202, test case 2
+		assert_eq!(
+			hmac_sha1(b"Jefe", b"what do ya want for nothing?"),
+			[
+				0xef, 0xfc, 0xdf, 0x6a, 0xe5, 0xeb, 0x2f, 0xa2, 0xd2, 0x74, 0x16, 0xd5, 0xf1, 0x84,
+				0xdf, 0x9c, 0x25, 0x9a, 0x7c, 0x79
+			]
+		);
+		let key = ConfirmKey::random();
+		let token = key.token("Server setup notes");
+		assert_eq!(token.len(), 40);
+		assert!(key.verify("Server setup notes", &token));
+		assert!(!key.verify("Server setup", &token));
+		assert!(!key.verify("Server setup notes", &token[1..]));
+		assert!(!key.verify("Server setup notes", ""));
+		// Another key, as after a restart
+		assert!(!ConfirmKey::random().verify("Server setup notes", &token));
+	}
+}
@@ -0,0 +1,124 @@
+use super::database::ArticleLocation;
+use super::feed::escape_xml;
+use super::urls;
+
+/// Most URLs in one sitemap, the limit of the sitemap protocol. Larger
+/// wikis get a sitemap index at `/sitemap.xml` that lists several
+/// sitemaps.
+pub const MAX_URLS: u32 = 50_000;
+
+/// Number of sitemaps needed for `articles` articles
+pub fn parts(articles: u32) -> u32 {
+	articles.div_ceil(MAX_URLS).max(1)
+}
+
+/// Path of a sitemap listed in the sitemap index, counting from 1
+pub fn part_path(part: u32) -> String {
+	format!("/sitemap/{}.xml", part)
+}
+
+/// Number of the sitemap in the last segment of its path (`3.xml`)
+pub fn parse_part(segment: &str) -> Option<u32> {
+	let part: u32 = segment.strip_suffix(".xml")?.parse().ok()?;
+	// No `01.xml`, every sitemap has exactly one URL
+	if part == 0 || part.to_string().len() + ".xml".len() != segment.len() {
+		return None;
+	}
+	Some(part)
+}
+
+/// Sitemap with the URLs of the articles
+pub fn urlset(articles: &[ArticleLocation], public_url: &str) -> String {
+	// About 120 bytes per URL
+	let mut out = String::with_capacity(200 + articles.len() * 120);
+	out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+	out.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+	for article in articles {
+		let path = urls::article_path(article.id, &article.slug);
+		out.push_str("\t<url><loc>");
+		escape_xml(&mut out, &absolute(public_url, &path));
+		out.push_str("</loc><lastmod>");
+		out.push_str(&article.date_modified.rfc3339());
+		out.push_str("</lastmod></url>\n");
+	}
+	out.push_str("</urlset>\n");
+	out
+}
+
+/// Sitemap index listing `parts` sitemaps
+pub fn index(parts: u32, public_url: &str) -> String {
+	let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+	out.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+	for part in 1..=parts {
+		out.push_str("\t<sitemap><loc>");
+		escape_xml(&mut out, &absolute(public_url, &part_path(part)));
+		out.push_str("</loc></sitemap>\n");
+	}
+	out.push_str("</sitemapindex>\n");
+	out
+}
+
+fn absolute(public_url: &str, path: &str) -> String {
+	urls::canonical_url(Some(public_url), path).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use chrono::NaiveDateTime;
+
+	use crate::timestamp::UtcTimestamp;
+
+	#[test]
+	fn test_urlset() {
+		let articles = [
+			ArticleLocation {
+				id: 1.into(),
+				slug: "coast-redwood".to_string(),
+				date_modified: UtcTimestamp::from_naive_utc(
+					NaiveDateTime::parse_from_str("2024-03-02 10:20:30", "%Y-%m-%d %H:%M:%S")
+						.unwrap(),
+				),
+			},
+			ArticleLocation {
+				id: 2.into(),
+				slug: "tom-&-jerry".to_string(),
+				date_modified: UtcTimestamp::from_naive_utc(
+					NaiveDateTime::parse_from_str("2024-03-03 00:00:00", "%Y-%m-%d %H:%M:%S")
+						.unwrap(),
+				),
+			},
+		];
+		assert_eq!(
+			urlset(&articles, "https://example.com/wiki?a=1&b=2/"),
+			"<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">
+	<url><loc>https://example.com/wiki?a=1&amp;b=2/article/1/coast-redwood</loc><lastmod>2024-03-02T10:20:30Z</lastmod></url>
+	<url><loc>https://example.com/wiki?a=1&amp;b=2/article/2/tom-%26-jerry</loc><lastmod>2024-03-03T00:00:00Z</lastmod></url>
+</urlset>
+"
+		);
+	}
+
+	#[test]
+	fn test_index() {
+		assert_eq!(parts(0), 1);
+		assert_eq!(parts(MAX_URLS), 1);
+		assert_eq!(parts(MAX_URLS + 1), 2);
+		assert_eq!(
+			index(2, "https://example.com/"),
+			"<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">
+	<sitemap><loc>https://example.com/sitemap/1.xml</loc></sitemap>
+	<sitemap><loc>https://example.com/sitemap/2.xml</loc></sitemap>
+</sitemapindex>
+"
+		);
+		assert_eq!(parse_part("2.xml"), Some(2));
+		assert_eq!(parse_part("0.xml"), None);
+		assert_eq!(parse_part("02.xml"), None);
+		assert_eq!(parse_part("+2.xml"), None);
+		assert_eq!(parse_part("2"), None);
+	}
+}
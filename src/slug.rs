@@ -0,0 +1,140 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum length of a slug in characters
+pub const MAX_SLUG_LENGTH: usize = 80;
+
+/// Slug used if a title does not contain a single letter or digit
+pub const FALLBACK_SLUG: &str = "article";
+
+/// Letters that do not decompose into a base letter and combining marks
+fn transliterate(c: char) -> Option<&'static str> {
+	Some(match c {
+		'ß' | 'ẞ' => "ss",
+		'æ' | 'Æ' => "ae",
+		'œ' | 'Œ' => "oe",
+		'ø' | 'Ø' => "o",
+		'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+		'þ' | 'Þ' => "th",
+		'ł' | 'Ł' => "l",
+		'ı' => "i",
+		_ => return None,
+	})
+}
+
+/// Turn an article title into the cosmetic part of its URL, e.g.
+/// `Über große Bäume` into `uber-grosse-baume`.
+///
+/// Accents are stripped from Latin letters, other letters and digits
+/// are kept as they are (lowercased), everything else becomes a
+/// separator. The slug is at most `MAX_SLUG_LENGTH` characters long
+/// and never empty.
+pub fn slugify(title: &str) -> String {
+	let mut slug = String::with_capacity(title.len());
+	let mut pending_separator = false;
+	for c in title.chars() {
+		let decomposed: String;
+		let text = if let Some(replacement) = transliterate(c) {
+			replacement
+		} else if !c.is_alphanumeric() {
+			pending_separator = true;
+			continue;
+		} else {
+			// Strip accents from Latin letters only, in other scripts
+			// the combining marks are an essential part of the letter
+			let decomposition = std::iter::once(c).nfkd().filter(|c| !is_combining_mark(*c));
+			decomposed = if decomposition.clone().all(|c| c.is_ascii_alphanumeric()) {
+				decomposition.map(|c| c.to_ascii_lowercase()).collect()
+			} else {
+				c.to_lowercase().collect()
+			};
+			&decomposed
+		};
+		if pending_separator && !slug.is_empty() {
+			slug.push('-');
+		}
+		pending_separator = false;
+		slug.push_str(text);
+	}
+
+	if slug.chars().count() > MAX_SLUG_LENGTH {
+		let cut: String = slug.chars().take(MAX_SLUG_LENGTH).collect();
+		// Prefer cutting at a word boundary if that does not lose too much
+		slug = match cut.rfind('-') {
+			Some(boundary) if boundary >= cut.len() / 2 => cut[..boundary].to_string(),
+			_ => cut,
+		};
+	}
+
+	if slug.is_empty() {
+		FALLBACK_SLUG.to_string()
+	} else {
+		slug
+	}
+}
+
+/// Candidates for a unique slug: The slug itself, then with
+/// numeric suffixes `-2`, `-3`, ...
+pub fn candidates(slug: &str) -> impl Iterator<Item = String> + '_ {
+	std::iter::once(slug.to_string()).chain((2..).map(move |n| format!("{}-{}", slug, n)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_basic() {
+		assert_eq!(slugify("Redwood"), "redwood");
+		assert_eq!(slugify("Title of first article"), "title-of-first-article");
+		assert_eq!(slugify("  Leading and trailing  "), "leading-and-trailing");
+		assert_eq!(slugify("C++ & Rust: a comparison!"), "c-rust-a-comparison");
+		assert_eq!(slugify("snake_case / kebab-case"), "snake-case-kebab-case");
+		assert_eq!(slugify("Version 1.2.3"), "version-1-2-3");
+	}
+
+	#[test]
+	fn test_transliteration() {
+		assert_eq!(slugify("Über große Bäume"), "uber-grosse-baume");
+		assert_eq!(slugify("Crème brûlée"), "creme-brulee");
+		assert_eq!(slugify("Ærøskøbing"), "aeroskobing");
+		assert_eq!(slugify("Łódź"), "lodz");
+		assert_eq!(slugify("ﬁligree"), "filigree");
+		assert_eq!(slugify("Þórr"), "thorr");
+		// Non-Latin scripts are kept
+		assert_eq!(slugify("Секвойя Дерево"), "секвойя-дерево");
+		assert_eq!(slugify("紅杉 wiki"), "紅杉-wiki");
+	}
+
+	#[test]
+	fn test_fallback() {
+		assert_eq!(slugify(""), FALLBACK_SLUG);
+		assert_eq!(slugify("!!! ??? ---"), FALLBACK_SLUG);
+		assert_eq!(slugify("🌲🌲🌲"), FALLBACK_SLUG);
+	}
+
+	#[test]
+	fn test_length_cap() {
+		let slug = slugify(&"redwood ".repeat(30));
+		assert!(slug.chars().count() <= MAX_SLUG_LENGTH);
+		assert!(slug.ends_with("redwood"));
+		assert!(!slug.ends_with('-'));
+
+		// Without word boundaries, the slug is cut hard
+		let slug = slugify(&"ä".repeat(200));
+		assert_eq!(slug, "a".repeat(MAX_SLUG_LENGTH));
+
+		// Multi-byte characters are counted as characters
+		let slug = slugify(&"дерево ".repeat(20));
+		assert!(slug.chars().count() <= MAX_SLUG_LENGTH);
+		assert!(slug.ends_with("дерево"));
+	}
+
+	#[test]
+	fn test_candidates() {
+		assert_eq!(
+			candidates("tree").take(3).collect::<Vec<_>>(),
+			vec!["tree", "tree-2", "tree-3"]
+		);
+	}
+}
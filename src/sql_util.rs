@@ -0,0 +1,215 @@
+use std::fmt;
+
+/// Escape character of the patterns from `escape_like`, every `LIKE`
+/// using them needs `ESCAPE '^'` (see `like_any`)
+pub const LIKE_ESCAPE: char = '^';
+
+/// Search term with the `LIKE` wildcards `%` and `_` and the escape
+/// character escaped, so it only matches itself. It is a separate type
+/// so that an unescaped term cannot be passed where a pattern is
+/// expected by accident.
+///
+/// `Display` gives the escaped term, which matches the whole value
+/// only. The alternate form (`{:#}`) surrounds it with `%`, so it
+/// matches the term anywhere in the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapedLike {
+	escaped: String,
+}
+
+pub fn escape_like(term: &str) -> EscapedLike {
+	let mut escaped = String::with_capacity(term.len());
+	for c in term.chars() {
+		if c == LIKE_ESCAPE || c == '%' || c == '_' {
+			escaped.push(LIKE_ESCAPE);
+		}
+		escaped.push(c);
+	}
+	EscapedLike { escaped }
+}
+
+impl EscapedLike {
+	/// Pattern matching values that contain the term
+	pub fn contains(&self) -> String {
+		format!("{:#}", self)
+	}
+}
+
+impl fmt::Display for EscapedLike {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if f.alternate() {
+			write!(f, "%{}%", self.escaped)
+		} else {
+			f.write_str(&self.escaped)
+		}
+	}
+}
+
+/// SQL condition that is true if any of `columns` matches the pattern
+/// bound to the parameter `?{param}`, e.g. `(title LIKE ?1 ESCAPE '^'
+/// OR text LIKE ?1 ESCAPE '^')`. The pattern only needs to be bound
+/// once, however many columns there are.
+pub fn like_any(columns: &[&str], param: usize) -> String {
+	let conditions: Vec<String> = columns
+		.iter()
+		.map(|column| format!("{} LIKE ?{} ESCAPE '{}'", column, param, LIKE_ESCAPE))
+		.collect();
+	format!("({})", conditions.join(" OR "))
+}
+
+/// FTS5 query for a search term as the user typed it. Every word is
+/// quoted, so characters with a meaning in the FTS5 query syntax
+/// (quotes, `*`, `-`, `AND`, ...) are searched for as text instead of
+/// causing a syntax error. Words match as prefixes, like the search
+/// with `LIKE` did, and all words must occur. `None` if the term has
+/// no words.
+pub fn fts_query(term: &str) -> Option<String> {
+	let words: Vec<String> = term
+		.split_whitespace()
+		.map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+		.collect();
+	if words.is_empty() {
+		None
+	} else {
+		Some(words.join(" "))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rusqlite::{params, Connection};
+
+	#[test]
+	fn test_escape_like() {
+		let escaped = escape_like("100%_^a");
+		assert_eq!(escaped.to_string(), "100^%^_^^a");
+		assert_eq!(format!("{:#}", escaped), "%100^%^_^^a%");
+		assert_eq!(escaped.contains(), "%100^%^_^^a%");
+		assert_eq!(escape_like("紅杉").to_string(), "紅杉");
+		assert_eq!(escape_like("").contains(), "%%");
+	}
+
+	#[test]
+	fn test_like_any() {
+		assert_eq!(
+			like_any(&["title", "text"], 1),
+			"(title LIKE ?1 ESCAPE '^' OR text LIKE ?1 ESCAPE '^')"
+		);
+		assert_eq!(like_any(&["name"], 3), "(name LIKE ?3 ESCAPE '^')");
+	}
+
+	#[test]
+	fn test_fts_query() {
+		assert_eq!(fts_query("red wood").unwrap(), r#""red"* "wood"*"#);
+		assert_eq!(fts_query(r#"say "hi"#).unwrap(), r#""say"* """hi"*"#);
+		assert_eq!(fts_query(" \t"), None);
+
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(
+			r#"CREATE VIRTUAL TABLE doc USING fts5(text);
+			INSERT INTO doc (text) VALUES ('Coast redwood trees'), ('The "giant" sequoia'), ('C++ and C#');"#,
+		)
+		.unwrap();
+		let search = |term: &str| -> Vec<i64> {
+			let mut stmt = conn
+				.prepare("SELECT rowid FROM doc WHERE doc MATCH ? ORDER BY rowid")
+				.unwrap();
+			stmt.query_map(params![fts_query(term).unwrap()], |row| row.get(0))
+				.unwrap()
+				.collect::<rusqlite::Result<_>>()
+				.unwrap()
+		};
+		assert_eq!(search("redwood"), [1]);
+		assert_eq!(search("RED tree"), [1]);
+		assert!(search("redwood sequoia").is_empty());
+		// Input that is invalid FTS5 syntax on its own
+		for term in [
+			r#""giant"#,
+			"giant)",
+			"sequoia*",
+			"-giant",
+			"AND",
+			"NEAR(giant",
+			"c++",
+			"^",
+			"col:giant",
+			r#"""#,
+		] {
+			search(term);
+		}
+		assert_eq!(search(r#""giant"#), [2]);
+		// Punctuation is not indexed, this is a search for `c`
+		assert_eq!(search("c++"), [1, 3]);
+	}
+
+	/// Strings made of the characters that are special to `LIKE`, some
+	/// letters in both cases and some non-ASCII characters, from a
+	/// fixed seed so failures can be reproduced
+	fn generated_strings(count: usize, max_len: usize) -> Vec<String> {
+		const ALPHABET: &[char] = &['%', '_', '^', '\\', 'a', 'A', 'b', ' ', 'ä', 'Ä', '紅'];
+		let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+		let mut next = move |bound: usize| {
+			// xorshift64
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			(state % bound as u64) as usize
+		};
+		(0..count)
+			.map(|_| {
+				let len = next(max_len + 1);
+				(0..len).map(|_| ALPHABET[next(ALPHABET.len())]).collect()
+			})
+			.collect()
+	}
+
+	/// What `LIKE` is expected to do with an escaped term: substring
+	/// search that ignores the case of ASCII letters only, like SQLite
+	fn reference_contains(value: &str, term: &str) -> bool {
+		value
+			.to_ascii_lowercase()
+			.contains(&term.to_ascii_lowercase())
+	}
+
+	fn reference_equals(value: &str, term: &str) -> bool {
+		value.eq_ignore_ascii_case(term)
+	}
+
+	#[test]
+	fn test_against_sqlite() {
+		let conn = Connection::open_in_memory().unwrap();
+		let mut contains = conn
+			.prepare(&format!("SELECT {}", like_any(&["?2"], 1)))
+			.unwrap();
+		let mut equals = conn.prepare("SELECT ?2 LIKE ?1 ESCAPE '^'").unwrap();
+		let terms = generated_strings(150, 3);
+		let values = generated_strings(150, 8);
+		for term in &terms {
+			let escaped = escape_like(term);
+			for value in values.iter().chain(terms.iter()) {
+				let matched: bool = contains
+					.query_row(params![escaped.contains(), value], |row| row.get(0))
+					.unwrap();
+				assert_eq!(
+					matched,
+					reference_contains(value, term),
+					"{:?} contains {:?}",
+					value,
+					term
+				);
+				let matched: bool = equals
+					.query_row(params![escaped.to_string(), value], |row| row.get(0))
+					.unwrap();
+				assert_eq!(
+					matched,
+					reference_equals(value, term),
+					"{:?} equals {:?}",
+					value,
+					term
+				);
+			}
+		}
+	}
+}
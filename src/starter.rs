@@ -0,0 +1,83 @@
+use super::assets::{self, Asset};
+use super::database::{Article, Database, ItemId};
+use super::render::{self, RenderOptions};
+use super::timestamp::UtcTimestamp;
+
+/// Title of the home article created by the setup
+pub const HOME_TITLE: &str = "Home";
+
+/// Example articles the setup can create, with their title
+static EXAMPLES: &[(&str, &Asset)] = &[
+	("Coast redwood", &assets::STARTER_COAST_REDWOOD),
+	("Giant sequoia", &assets::STARTER_GIANT_SEQUOIA),
+];
+
+/// Text of the home article. The `{examples}` placeholder of the
+/// template becomes a list of references to the example articles.
+pub fn home_text(examples: &[ItemId]) -> String {
+	let list = if examples.is_empty() {
+		"Once you have created a few articles, try it out here.".to_string()
+	} else {
+		let refs: Vec<String> = examples
+			.iter()
+			.map(|id| format!("- [article:{}]", id))
+			.collect();
+		format!(
+			"For example, here are the example articles:\n\n{}",
+			refs.join("\n")
+		)
+	};
+	assets::STARTER_HOME.text().replace("{examples}", &list)
+}
+
+fn create_article(db: &mut Database, title: &str, text: String) -> Option<ItemId> {
+	let refs = render::article_refs(&text, RenderOptions::default());
+	let id = db.create_article(&Article {
+		id: 0.into(),
+		title: title.to_string(),
+		text,
+		date_created: UtcTimestamp::now(),
+		date_modified: UtcTimestamp::now(),
+		revision: 0,
+		slug: String::new(), // Generated from the title by the database
+	});
+	match id {
+		Ok(id) => {
+			let _ = db.set_article_links(id, &refs);
+			Some(id)
+		}
+		Err(err) => {
+			log::error!("Could not create starter article {:?}: {}", title, err);
+			None
+		}
+	}
+}
+
+/// Create the home article, and the example articles first if
+/// requested so the home article can reference them. Returns the ID of
+/// the home article.
+pub fn create(db: &mut Database, with_examples: bool) -> Option<ItemId> {
+	let mut examples = Vec::new();
+	if with_examples {
+		for (title, asset) in EXAMPLES {
+			examples.extend(create_article(db, title, asset.text().to_string()));
+		}
+	}
+	create_article(db, HOME_TITLE, home_text(&examples))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_home_text() {
+		let text = home_text(&[3.into(), 4.into()]);
+		assert!(text.contains("- [article:3]\n- [article:4]"));
+		assert!(!text.contains("{examples}"));
+
+		let text = home_text(&[]);
+		assert!(!text.contains("- [article:"));
+		assert!(!text.contains("{examples}"));
+	}
+}
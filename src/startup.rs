@@ -0,0 +1,359 @@
+use super::codeblock_syntax_highlight::{CodeTheme, Syntaxes, ThemeCss};
+use super::config::Config;
+use super::config_refs;
+use super::database::Database;
+use super::dictionary::Dictionary;
+use super::hooks;
+use super::journal;
+use super::log_file;
+use super::timestamp::UtcTimestamp;
+
+/// Disable a feature whose initialization failed, recording the problem
+fn disable(config: &mut Config, feature: &str, problem: String) {
+	let message = format!("{} disabled: {}", feature, problem);
+	log::error!("{}", message);
+	config.startup_problems.push(message);
+}
+
+/// Initialize the optional features. A misconfigured feature does not
+/// keep the wiki from starting: it is disabled in `config` and the
+/// problem is recorded in `config.startup_problems` so the about page
+/// can show it. Only the configuration itself, the database and the
+/// network address are hard requirements, they are handled by `main`.
+pub fn init_optional(config: &mut Config) -> Dictionary {
+	// First, so the problems of the other features are logged to it
+	if let Some(path) = &config.log.file {
+		if let Err(err) = log_file::LOG_FILE.open(path) {
+			let problem = format!("cannot open {}: {}", path.display(), err);
+			config.log.file = None;
+			disable(config, "Log file", problem);
+		}
+	}
+
+	let dictionary = match &config.editor.dictionary {
+		Some(path) => match Dictionary::load(path) {
+			Ok(dictionary) => dictionary,
+			Err(err) => {
+				let problem = format!("cannot read {}: {}", path.display(), err);
+				config.editor.dictionary = None;
+				disable(config, "Spell checker dictionary", problem);
+				Dictionary::default()
+			}
+		},
+		None => Dictionary::default(),
+	};
+
+	if let Some(journal) = &config.journal {
+		let today = journal::date_in(config.appearance.timezone.local_time(UtcTimestamp::now()));
+		if journal::entry_title(journal, today).is_none() {
+			let problem = format!("invalid date format {:?}", journal.date_format);
+			config.journal = None;
+			disable(config, "Journal", problem);
+		}
+	}
+
+	if let Some(backup) = &config.backup {
+		let problem = if backup.interval_hours == 0 || backup.keep_count == 0 {
+			Some("`interval_hours` and `keep_count` must be at least 1".to_string())
+		} else {
+			std::fs::create_dir_all(&backup.directory)
+				.err()
+				.map(|err| format!("cannot create {}: {}", backup.directory.display(), err))
+		};
+		if let Some(problem) = problem {
+			config.backup = None;
+			disable(config, "Backups", problem);
+		}
+	}
+
+	for (name, url) in [
+		("purge_url", config.hooks.purge_url.clone()),
+		("ping_url", config.hooks.ping_url.clone()),
+	] {
+		let Some(url) = url else {
+			continue;
+		};
+		if let Err(err) = hooks::check_url_template(&url) {
+			match name {
+				"purge_url" => config.hooks.purge_url = None,
+				_ => config.hooks.ping_url = None,
+			}
+			disable(
+				config,
+				&format!("Hook `{}`", name),
+				format!("{} ({})", err, url),
+			);
+		}
+	}
+
+	for link in std::mem::take(&mut config.site.nav_links) {
+		match link.check() {
+			Ok(()) => config.site.nav_links.push(link),
+			Err(err) => disable(
+				config,
+				&format!("Navigation link {:?}", link.label),
+				format!("{} ({})", err, link.url),
+			),
+		}
+	}
+
+	if let Some(dir) = config.render.extra_syntaxes_dir.clone() {
+		match Syntaxes::load(&dir) {
+			Ok((syntaxes, problems)) => {
+				config.render.syntaxes = syntaxes;
+				for (file, problem) in problems {
+					disable(config, &format!("Syntax definition {}", file), problem);
+				}
+			}
+			Err(err) => {
+				// Code blocks are still highlighted with the defaults
+				let message = format!(
+					"Extra syntaxes not loaded: cannot read {}: {}",
+					dir.display(),
+					err
+				);
+				log::warn!("{}", message);
+				config.render.extra_syntaxes_dir = None;
+				config.startup_problems.push(message);
+			}
+		}
+	}
+
+	if let Some(path) = &config.appearance.custom_css {
+		if let Err(err) = config.appearance.stylesheet.reload(path) {
+			// The pages keep the built-in styles, the file is read
+			// again once it changes
+			let message = format!(
+				"Custom stylesheet not loaded: cannot read {}: {}",
+				path.display(),
+				err
+			);
+			log::warn!("{}", message);
+			config.startup_problems.push(message);
+		}
+	}
+
+	if let CodeTheme::File(path) = &config.appearance.code_theme {
+		match ThemeCss::load(path) {
+			Ok(theme_css) => config.appearance.theme_css = theme_css,
+			Err(err) => {
+				// Code is still highlighted, in the default colors
+				let message = format!(
+					"Code theme not loaded: cannot read {}: {}",
+					path.display(),
+					err
+				);
+				log::warn!("{}", message);
+				config.appearance.code_theme = CodeTheme::default();
+				config.startup_problems.push(message);
+			}
+		}
+	}
+
+	if config.render.external_link_icons && !config.network.outbound_requests {
+		log::warn!("`external_link_icons` has no effect without `outbound_requests`");
+	}
+
+	dictionary
+}
+
+/// Check that the articles the configuration refers to exist. Broken
+/// references are recorded like the problems of `init_optional`, but
+/// nothing is disabled: the features cope with missing articles and
+/// the article may be restored.
+pub fn check_article_refs(config: &mut Config, db: &mut Database) {
+	for problem in config_refs::Registry::resolve(config, db).problems() {
+		let message = format!("Article reference: {}", problem);
+		log::warn!("{}", message);
+		config.startup_problems.push(message);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config(extra: &str) -> Config {
+		toml::from_str(&format!(
+			r#"
+			[network]
+			ip = "127.0.0.1"
+			port = 0
+
+			[database]
+			storage_location = "."
+			{}
+			"#,
+			extra
+		))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_valid() {
+		let mut config = config(
+			r#"
+			[journal]
+			[hooks]
+			purge_url = "http://cache.local{path}"
+			"#,
+		);
+		init_optional(&mut config);
+		assert!(config.startup_problems.is_empty());
+		assert!(config.journal.is_some());
+		assert!(config.hooks.purge_url.is_some());
+	}
+
+	#[test]
+	fn test_failures_disable_features() {
+		// A directory cannot be read as dictionary
+		let mut config = config(&format!(
+			r#"
+			[editor]
+			spellcheck = true
+			dictionary = {:?}
+
+			[journal]
+			date_format = "%Q"
+
+			[backup]
+			keep_count = 0
+
+			[hooks]
+			purge_url = "ftp://cache.local{{path}}"
+			ping_url = "http://search.example/ping?url={{url}}"
+			"#,
+			std::env::temp_dir()
+		));
+		let dictionary = init_optional(&mut config);
+		assert_eq!(dictionary.words().count(), 0);
+		assert!(config.editor.dictionary.is_none());
+		assert!(config.journal.is_none());
+		assert!(config.backup.is_none());
+		assert!(config.hooks.purge_url.is_none());
+		assert!(config.hooks.ping_url.is_some());
+		assert_eq!(config.startup_problems.len(), 4);
+		assert!(config.startup_problems[0]
+			.starts_with("Spell checker dictionary disabled: cannot read"));
+		assert_eq!(
+			config.startup_problems[1],
+			r#"Journal disabled: invalid date format "%Q""#
+		);
+		assert_eq!(
+			config.startup_problems[2],
+			"Backups disabled: `interval_hours` and `keep_count` must be at least 1"
+		);
+		assert!(config.startup_problems[3].starts_with("Hook `purge_url` disabled"));
+	}
+
+	#[test]
+	fn test_extra_syntaxes() {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!("redwood-wiki-test-syntaxes-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir(&dir).unwrap();
+		std::fs::copy(
+			concat!(
+				env!("CARGO_MANIFEST_DIR"),
+				"/src/testdata/syntaxes/redwood-test.sublime-syntax"
+			),
+			dir.join("redwood-test.sublime-syntax"),
+		)
+		.unwrap();
+		std::fs::write(dir.join("broken.sublime-syntax"), "name: [unclosed").unwrap();
+		std::fs::write(dir.join("notes.txt"), "not a syntax").unwrap();
+
+		let mut loaded = config(&format!("[render]\nextra_syntaxes_dir = {:?}", dir));
+		init_optional(&mut loaded);
+		assert!(loaded
+			.render
+			.syntaxes
+			.get()
+			.find_syntax_by_token("rwtest")
+			.is_some());
+		assert_eq!(loaded.startup_problems.len(), 1);
+		assert!(loaded.startup_problems[0]
+			.starts_with("Syntax definition broken.sublime-syntax disabled:"));
+
+		// The defaults are used without the folder
+		std::fs::remove_dir_all(&dir).unwrap();
+		let mut missing = config(&format!("[render]\nextra_syntaxes_dir = {:?}", dir));
+		init_optional(&mut missing);
+		assert!(missing.render.extra_syntaxes_dir.is_none());
+		assert!(missing
+			.render
+			.syntaxes
+			.get()
+			.find_syntax_by_token("rust")
+			.is_some());
+		assert!(missing
+			.render
+			.syntaxes
+			.get()
+			.find_syntax_by_token("rwtest")
+			.is_none());
+		assert_eq!(missing.startup_problems.len(), 1);
+		assert!(missing.startup_problems[0].starts_with("Extra syntaxes not loaded: cannot read"));
+	}
+
+	#[test]
+	fn test_code_theme_file() {
+		let mut path = std::env::temp_dir();
+		path.push(format!(
+			"redwood-wiki-test-theme-{}.tmTheme",
+			std::process::id()
+		));
+		std::fs::write(&path, "not a theme").unwrap();
+		let mut broken = config(&format!("[appearance]\ncode_theme = {:?}", path));
+		init_optional(&mut broken);
+		assert_eq!(broken.appearance.code_theme, CodeTheme::default());
+		assert!(broken.appearance.code_theme_css().contains(".code"));
+		assert_eq!(broken.startup_problems.len(), 1);
+		assert!(broken.startup_problems[0].starts_with("Code theme not loaded: cannot read"));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_nav_links() {
+		let mut config = config(
+			r#"
+			[[site.nav_links]]
+			label = "Issues"
+			url = "https://issues.example.com"
+
+			[[site.nav_links]]
+			label = " "
+			url = "/articles"
+
+			[[site.nav_links]]
+			label = "Tracker"
+			url = "issues.example.com"
+
+			[[site.nav_links]]
+			label = "Protocol relative"
+			url = "//issues.example.com"
+
+			[[site.nav_links]]
+			label = "Start"
+			url = "/"
+			section = "Wiki"
+			"#,
+		);
+		init_optional(&mut config);
+		let labels: Vec<&str> = config
+			.site
+			.nav_links
+			.iter()
+			.map(|link| link.label.as_str())
+			.collect();
+		assert_eq!(labels, ["Issues", "Start"]);
+		assert_eq!(
+			config.startup_problems,
+			[
+				r#"Navigation link " " disabled: the label is empty (/articles)"#,
+				r#"Navigation link "Tracker" disabled: the URL is neither an http(s) URL nor a path starting with `/` (issues.example.com)"#,
+				r#"Navigation link "Protocol relative" disabled: the URL is neither an http(s) URL nor a path starting with `/` (//issues.example.com)"#,
+			]
+		);
+	}
+}
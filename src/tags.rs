@@ -0,0 +1,49 @@
+/// Form in which tags are stored and compared: trimmed and case-folded,
+/// so `Redwood ` and `redwood` are the same tag. `None` for a tag that
+/// is empty after trimming.
+pub fn normalize(tag: &str) -> Option<String> {
+	let tag = tag.trim();
+	if tag.is_empty() {
+		None
+	} else {
+		Some(tag.to_lowercase())
+	}
+}
+
+/// Normalized tags of a comma-separated list as entered in the editor,
+/// in the order of their first occurrence. Empty entries are skipped.
+pub fn parse_list(list: &str) -> Vec<String> {
+	let mut tags: Vec<String> = Vec::new();
+	for tag in list.split(',').filter_map(normalize) {
+		if !tags.contains(&tag) {
+			tags.push(tag);
+		}
+	}
+	tags
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_normalize() {
+		assert_eq!(normalize("  Redwood "), Some("redwood".to_string()));
+		assert_eq!(
+			normalize("Coast Redwood"),
+			Some("coast redwood".to_string())
+		);
+		assert_eq!(normalize("ÄSTE"), Some("äste".to_string()));
+		assert_eq!(normalize(" \t"), None);
+	}
+
+	#[test]
+	fn test_parse_list() {
+		assert_eq!(
+			parse_list("Trees, redwood,, TREES ,conifer,"),
+			["trees", "redwood", "conifer"]
+		);
+		assert!(parse_list("").is_empty());
+		assert!(parse_list(" , ").is_empty());
+	}
+}
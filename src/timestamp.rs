@@ -0,0 +1,468 @@
+use std::convert::TryFrom;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+
+/// Point in time as stored in the database: UTC without offset, as text
+/// like `2024-05-12 08:30:00.123`. Only converted to the time zone of
+/// the site for display, see `SiteTimezone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcTimestamp(NaiveDateTime);
+
+impl UtcTimestamp {
+	pub fn now() -> UtcTimestamp {
+		UtcTimestamp(Utc::now().naive_utc())
+	}
+
+	pub fn from_naive_utc(time: NaiveDateTime) -> UtcTimestamp {
+		UtcTimestamp(time)
+	}
+
+	pub fn naive_utc(self) -> NaiveDateTime {
+		self.0
+	}
+
+	/// In the time zone `tz`. Times beyond the years 0 to 9999 are
+	/// clamped to them first, see `clamped`.
+	pub fn with_timezone<Tz: TimeZone>(self, tz: &Tz) -> DateTime<Tz> {
+		tz.from_utc_datetime(&self.clamped().0)
+	}
+
+	/// RFC 3339, e.g. `2024-05-12T08:30:00Z`. RFC 3339 only has the
+	/// years 0 to 9999, other times are clamped to them.
+	pub fn rfc3339(self) -> String {
+		self.clamped().0.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+	}
+
+	/// Within the years 0 to 9999, which every time zone and format can
+	/// show. Only a corrupt database has other times, showing them
+	/// wrong is better than failing (chrono panics when an offset
+	/// moves a time beyond the years it supports).
+	fn clamped(self) -> UtcTimestamp {
+		let earliest = NaiveDate::from_ymd_opt(0, 1, 1)
+			.unwrap()
+			.and_hms_opt(0, 0, 0)
+			.unwrap();
+		let latest = NaiveDate::from_ymd_opt(9999, 12, 31)
+			.unwrap()
+			.and_hms_opt(23, 59, 59)
+			.unwrap();
+		UtcTimestamp(self.0.clamp(earliest, latest))
+	}
+}
+
+/// Dates the wiki considers plausible: not before `earliest` and at most
+/// `max_ahead` after the current time. Dates outside of it come from a
+/// wrong clock or a bad import and break the order of the articles,
+/// the markers of changes and the feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateWindow {
+	pub earliest: UtcTimestamp,
+	/// Allowance for clocks of different machines not agreeing
+	pub max_ahead: chrono::Duration,
+}
+
+impl Default for DateWindow {
+	fn default() -> DateWindow {
+		DateWindow::new(2000, 1)
+	}
+}
+
+impl DateWindow {
+	/// From the start of `earliest_year` to `max_future_days` ahead
+	pub fn new(earliest_year: i32, max_future_days: u32) -> DateWindow {
+		let earliest = NaiveDate::from_ymd_opt(earliest_year, 1, 1)
+			.unwrap_or(NaiveDate::MIN)
+			.and_hms_opt(0, 0, 0)
+			.unwrap();
+		DateWindow {
+			earliest: UtcTimestamp(earliest),
+			max_ahead: chrono::Duration::days(max_future_days.into()),
+		}
+	}
+
+	pub fn contains(&self, time: UtcTimestamp, now: UtcTimestamp) -> bool {
+		self.earliest <= time && time <= now + self.max_ahead
+	}
+}
+
+impl std::fmt::Display for UtcTimestamp {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} UTC", self.0.format("%Y-%m-%d %H:%M:%S"))
+	}
+}
+
+impl std::ops::Add<chrono::Duration> for UtcTimestamp {
+	type Output = UtcTimestamp;
+
+	fn add(self, duration: chrono::Duration) -> UtcTimestamp {
+		UtcTimestamp(self.0 + duration)
+	}
+}
+
+impl std::ops::Sub<chrono::Duration> for UtcTimestamp {
+	type Output = UtcTimestamp;
+
+	fn sub(self, duration: chrono::Duration) -> UtcTimestamp {
+		UtcTimestamp(self.0 - duration)
+	}
+}
+
+/// As RFC 3339 string, see `rfc3339`
+impl serde::Serialize for UtcTimestamp {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.rfc3339())
+	}
+}
+
+/// From RFC 3339 as written by `rfc3339`, times with an offset are
+/// converted to UTC
+impl<'de> Deserialize<'de> for UtcTimestamp {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let text = String::deserialize(deserializer)?;
+		DateTime::parse_from_rfc3339(&text)
+			.map(|time| UtcTimestamp(time.naive_utc()))
+			.map_err(|err| serde::de::Error::custom(format!("invalid time {:?}: {}", text, err)))
+	}
+}
+
+impl ToSql for UtcTimestamp {
+	fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+		self.0.to_sql()
+	}
+}
+
+/// Timestamps with an offset were not written by this version of the
+/// wiki. Rather than guess what they mean, reading them fails.
+#[derive(Debug)]
+struct OffsetNotSupported(String);
+
+impl std::fmt::Display for OffsetNotSupported {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "timestamp {:?} has a time zone, expected UTC", self.0)
+	}
+}
+
+impl std::error::Error for OffsetNotSupported {}
+
+impl FromSql for UtcTimestamp {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+		let text = value.as_str()?;
+		// The time starts after `YYYY-MM-DD`, a `-` after it is an offset
+		let time = text.get(10..).unwrap_or_default();
+		if time.ends_with('Z') || time.contains(['+', '-']) {
+			return Err(FromSqlError::Other(Box::new(OffsetNotSupported(
+				text.to_string(),
+			))));
+		}
+		NaiveDateTime::column_result(value).map(UtcTimestamp)
+	}
+}
+
+/// Time zone in which the wiki shows times and decides which day it
+/// is: a name of the tz database like `Europe/Zurich` or `UTC` (the
+/// default), `local` for the time zone of the server, or a fixed offset
+/// like `+02:00`. Names and `local` follow daylight saving time, a
+/// fixed offset does not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SiteTimezone {
+	Named(Tz),
+	Local,
+	Fixed(FixedOffset),
+}
+
+impl Default for SiteTimezone {
+	fn default() -> SiteTimezone {
+		SiteTimezone::Named(Tz::UTC)
+	}
+}
+
+impl TryFrom<String> for SiteTimezone {
+	type Error = String;
+
+	fn try_from(name: String) -> Result<SiteTimezone, String> {
+		if name == "local" {
+			return Ok(SiteTimezone::Local);
+		}
+		if let Ok(offset) = name.parse() {
+			return Ok(SiteTimezone::Fixed(offset));
+		}
+		name.parse().map(SiteTimezone::Named).map_err(|_| {
+			format!(
+				"invalid time zone {:?}, expected a name like `Europe/Zurich` or `UTC`, \
+				 `local` or an offset like `+02:00`",
+				name
+			)
+		})
+	}
+}
+
+impl From<SiteTimezone> for String {
+	fn from(timezone: SiteTimezone) -> String {
+		match timezone {
+			SiteTimezone::Named(tz) => tz.name().to_string(),
+			SiteTimezone::Local => "local".to_string(),
+			SiteTimezone::Fixed(offset) => offset.to_string(),
+		}
+	}
+}
+
+impl SiteTimezone {
+	/// The time in this time zone, with the offset in effect at that time
+	pub fn local_time(self, time: UtcTimestamp) -> DateTime<FixedOffset> {
+		match self {
+			SiteTimezone::Named(tz) => local_time(time, &tz),
+			SiteTimezone::Local => local_time(time, &Local),
+			SiteTimezone::Fixed(offset) => local_time(time, &offset),
+		}
+	}
+}
+
+/// How times are shown, a chrono format string like `%Y-%m-%d %H:%M`
+/// (see https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+/// Checked when the configuration is read, formatting a time with an
+/// invalid string would panic.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DateFormat(String);
+
+impl Default for DateFormat {
+	fn default() -> DateFormat {
+		DateFormat("%Y-%m-%d %H:%M".to_string())
+	}
+}
+
+impl TryFrom<String> for DateFormat {
+	type Error = String;
+
+	fn try_from(format: String) -> Result<DateFormat, String> {
+		if StrftimeItems::new(&format).any(|item| item == Item::Error) {
+			return Err(format!("invalid date format {:?}", format));
+		}
+		Ok(DateFormat(format))
+	}
+}
+
+impl From<DateFormat> for String {
+	fn from(format: DateFormat) -> String {
+		format.0
+	}
+}
+
+impl DateFormat {
+	/// `time` in the time zone `timezone`
+	pub fn format(&self, time: UtcTimestamp, timezone: SiteTimezone) -> String {
+		timezone.local_time(time).format(&self.0).to_string()
+	}
+}
+
+/// `time` in the time zone `tz`. The offset is looked up for every
+/// time, so times on both sides of a daylight saving time change are
+/// shown with their own offset.
+pub fn local_time<Tz: TimeZone>(time: UtcTimestamp, tz: &Tz) -> DateTime<FixedOffset> {
+	let local = time.with_timezone(tz);
+	local.with_timezone(&local.offset().fix())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use chrono::{LocalResult, NaiveDate};
+	use chrono_tz::Europe::Zurich;
+
+	fn utc(text: &str) -> UtcTimestamp {
+		UtcTimestamp::from_naive_utc(
+			NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap(),
+		)
+	}
+
+	#[test]
+	fn test_sql() {
+		let conn = rusqlite::Connection::open_in_memory().unwrap();
+		let time = utc("2024-05-12 08:30:00") + chrono::Duration::milliseconds(123);
+		let stored: String = conn
+			.query_row("SELECT ?", [time], |row| row.get(0))
+			.unwrap();
+		assert_eq!(stored, "2024-05-12 08:30:00.123");
+		let read =
+			|text: &str| conn.query_row("SELECT ?", [text], |row| row.get::<_, UtcTimestamp>(0));
+		assert_eq!(read(&stored).unwrap(), time);
+		// Written by SQLite's `datetime()` or by hand
+		assert_eq!(
+			read("2024-05-12T08:30:00").unwrap(),
+			utc("2024-05-12 08:30:00")
+		);
+		for text in [
+			"2024-05-12 08:30:00Z",
+			"2024-05-12 08:30:00+02:00",
+			"2024-05-12T08:30:00.123-05:00",
+		] {
+			let err = read(text).unwrap_err();
+			assert!(err.to_string().contains("has a time zone"), "{}", err);
+		}
+		assert!(read("yesterday").is_err());
+	}
+
+	#[test]
+	fn test_extreme_dates() {
+		let time = |year: i32| {
+			UtcTimestamp::from_naive_utc(
+				NaiveDate::from_ymd_opt(year, 6, 1)
+					.unwrap()
+					.and_hms_opt(12, 0, 0)
+					.unwrap(),
+			)
+		};
+		assert_eq!(time(2999).rfc3339(), "2999-06-01T12:00:00Z");
+		assert_eq!(time(12345).rfc3339(), "9999-12-31T23:59:59Z");
+		assert_eq!(time(-50).rfc3339(), "0000-01-01T00:00:00Z");
+		// The offset would move these beyond what chrono supports
+		let max = UtcTimestamp::from_naive_utc(NaiveDateTime::MAX);
+		let min = UtcTimestamp::from_naive_utc(NaiveDateTime::MIN);
+		let east = SiteTimezone::Fixed(FixedOffset::east_opt(14 * 3600).unwrap());
+		let west = SiteTimezone::Fixed(FixedOffset::west_opt(12 * 3600).unwrap());
+		assert_eq!(
+			east.local_time(max).to_rfc3339(),
+			"+10000-01-01T13:59:59+14:00"
+		);
+		assert_eq!(
+			west.local_time(min).to_rfc3339(),
+			"-0001-12-31T12:00:00-12:00"
+		);
+		assert!(!max.to_string().is_empty());
+
+		let window = DateWindow::new(2000, 1);
+		let now = utc("2024-05-12 08:30:00");
+		assert!(window.contains(utc("2000-01-01 00:00:00"), now));
+		assert!(window.contains(now + chrono::Duration::days(1), now));
+		assert!(!window.contains(utc("1999-12-31 23:59:59"), now));
+		assert!(!window.contains(utc("1970-01-01 00:00:00"), now));
+		assert!(!window.contains(now + chrono::Duration::hours(25), now));
+		assert!(!window.contains(time(2999), now));
+		assert_eq!(DateWindow::default(), window);
+	}
+
+	#[test]
+	fn test_site_timezone() {
+		let parse = |name: &str| SiteTimezone::try_from(name.to_string());
+		assert_eq!(parse("local"), Ok(SiteTimezone::Local));
+		assert_eq!(parse("UTC"), Ok(SiteTimezone::Named(Tz::UTC)));
+		assert_eq!(SiteTimezone::default(), SiteTimezone::Named(Tz::UTC));
+		assert_eq!(parse("Europe/Zurich"), Ok(SiteTimezone::Named(Zurich)));
+		assert_eq!(
+			parse("-05:30"),
+			Ok(SiteTimezone::Fixed(
+				FixedOffset::west_opt(5 * 3600 + 1800).unwrap()
+			))
+		);
+		for name in ["Europe/Zurich", "UTC", "local", "-05:30"] {
+			assert_eq!(String::from(parse(name).unwrap()), name);
+		}
+		for name in ["Europe/Atlantis", "europe/zurich", "CEST", ""] {
+			let err = parse(name).unwrap_err();
+			assert!(err.starts_with("invalid time zone"), "{}", err);
+		}
+
+		let time = utc("2024-05-12 22:30:00");
+		assert_eq!(
+			parse("UTC").unwrap().local_time(time).date_naive(),
+			NaiveDate::from_ymd_opt(2024, 5, 12).unwrap()
+		);
+		assert_eq!(
+			parse("+02:00").unwrap().local_time(time).date_naive(),
+			NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()
+		);
+		assert_eq!(
+			parse("+02:00").unwrap().local_time(time).to_rfc3339(),
+			"2024-05-13T00:30:00+02:00"
+		);
+	}
+
+	#[test]
+	fn test_date_format() {
+		let parse = |format: &str| DateFormat::try_from(format.to_string());
+		let time = utc("2024-05-12 22:30:00");
+		let utc_zone = SiteTimezone::default();
+		let zurich = SiteTimezone::Named(Zurich);
+		assert_eq!(
+			DateFormat::default().format(time, utc_zone),
+			"2024-05-12 22:30"
+		);
+		assert_eq!(
+			DateFormat::default().format(time, zurich),
+			"2024-05-13 00:30"
+		);
+		assert_eq!(
+			parse("%d.%m.%Y %H:%M %:z").unwrap().format(time, zurich),
+			"13.05.2024 00:30 +02:00"
+		);
+		assert!(parse("%Y-%m-%d %Q").is_err());
+		assert!(parse("%").is_err());
+	}
+
+	#[test]
+	fn test_daylight_saving_time() {
+		let format = |text: &str| {
+			SiteTimezone::Named(Zurich)
+				.local_time(utc(text))
+				.format("%Y-%m-%d %H:%M %:z")
+				.to_string()
+		};
+		// Spring forward: 02:00 local does not exist
+		assert_eq!(format("2024-03-31 00:59:00"), "2024-03-31 01:59 +01:00");
+		assert_eq!(format("2024-03-31 01:00:00"), "2024-03-31 03:00 +02:00");
+		// Fall back: 02:30 local happens twice, the offsets tell them apart
+		assert_eq!(format("2024-10-27 00:30:00"), "2024-10-27 02:30 +02:00");
+		assert_eq!(format("2024-10-27 01:30:00"), "2024-10-27 02:30 +01:00");
+	}
+
+	#[test]
+	fn test_day_boundary() {
+		let day = |text: &str| {
+			SiteTimezone::Named(Zurich)
+				.local_time(utc(text))
+				.date_naive()
+		};
+		let date = |d| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + chrono::Duration::days(d);
+		// Midnight is at 23:00 UTC in winter ...
+		assert_eq!(day("2024-01-15 22:59:59"), date(14));
+		assert_eq!(day("2024-01-15 23:00:00"), date(15));
+		// ... and at 22:00 UTC in summer
+		assert_eq!(day("2024-07-15 21:59:59"), date(196));
+		assert_eq!(day("2024-07-15 22:00:00"), date(197));
+		// The day of the change has 23 hours
+		assert_eq!(
+			day("2024-03-30 23:00:00"),
+			NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+		);
+		assert_eq!(
+			day("2024-03-31 21:59:59"),
+			NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+		);
+		assert_eq!(
+			day("2024-03-31 22:00:00"),
+			NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()
+		);
+		assert!(matches!(
+			Zurich.offset_from_local_datetime(
+				&NaiveDate::from_ymd_opt(2024, 3, 31)
+					.unwrap()
+					.and_hms_opt(2, 30, 0)
+					.unwrap()
+			),
+			LocalResult::None
+		));
+		assert_eq!(
+			Zurich
+				.offset_from_utc_date(&date(0))
+				.fix()
+				.local_minus_utc(),
+			3600
+		);
+	}
+}
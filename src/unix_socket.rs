@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+
+/// Permissions of the socket file of `network.unix_socket`, written in
+/// octal like `660`, so that e.g. only the group of the reverse proxy
+/// can connect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SocketMode(u32);
+
+impl TryFrom<String> for SocketMode {
+	type Error = String;
+
+	fn try_from(mode: String) -> Result<SocketMode, String> {
+		let digits = mode.strip_prefix("0o").unwrap_or(&mode);
+		match u32::from_str_radix(digits, 8) {
+			Ok(bits) if !digits.starts_with('+') && bits <= 0o777 => Ok(SocketMode(bits)),
+			_ => Err(format!(
+				"invalid socket mode {:?}, expected octal permissions like `660`",
+				mode
+			)),
+		}
+	}
+}
+
+impl From<SocketMode> for String {
+	fn from(mode: SocketMode) -> String {
+		format!("{:03o}", mode.0)
+	}
+}
+
+#[cfg(unix)]
+pub use listen::{bind, shutdown_signal};
+
+#[cfg(unix)]
+mod listen {
+	use std::io;
+	use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+	use std::path::{Path, PathBuf};
+
+	use tokio::net::UnixListener;
+	use tokio_stream::wrappers::UnixListenerStream;
+
+	use super::SocketMode;
+
+	/// The socket file of a running server, removed when dropped
+	#[derive(Debug)]
+	pub struct SocketFile {
+		path: PathBuf,
+	}
+
+	impl Drop for SocketFile {
+		fn drop(&mut self) {
+			if let Err(err) = std::fs::remove_file(&self.path) {
+				log::warn!(
+					"Could not remove the socket {}: {}",
+					self.path.display(),
+					err
+				);
+			}
+		}
+	}
+
+	/// Listen on the Unix domain socket at `path`. A socket file left
+	/// behind by a server that is gone is replaced, one that a server
+	/// still listens on or any other file is not.
+	///
+	/// The socket is created in a directory only this process can
+	/// enter and moved to `path` once it has its permissions, so that
+	/// nobody can connect while it still has the ones of the umask.
+	pub fn bind(
+		path: &Path,
+		mode: Option<SocketMode>,
+	) -> io::Result<(UnixListenerStream, SocketFile)> {
+		remove_stale(path)?;
+		let private = private_dir(path)?;
+		let staged = private.join("socket");
+		let listener = UnixListener::bind(&staged).and_then(|listener| {
+			if let Some(SocketMode(bits)) = mode {
+				std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(bits))?;
+			}
+			std::fs::rename(&staged, path)?;
+			Ok(listener)
+		});
+		if listener.is_err() {
+			let _ = std::fs::remove_file(&staged);
+		}
+		if let Err(err) = std::fs::remove_dir(&private) {
+			log::warn!(
+				"Could not remove the directory {}: {}",
+				private.display(),
+				err
+			);
+		}
+		let listener = listener?;
+		let socket_file = SocketFile {
+			path: path.to_path_buf(),
+		};
+		Ok((UnixListenerStream::new(listener), socket_file))
+	}
+
+	/// New directory next to `path` that only the owner can enter. The
+	/// name is short, the path of a socket is limited to about 100 bytes.
+	fn private_dir(path: &Path) -> io::Result<PathBuf> {
+		use std::os::unix::fs::DirBuilderExt;
+		let parent = match path.parent() {
+			Some(parent) if !parent.as_os_str().is_empty() => parent,
+			_ => Path::new("."),
+		};
+		let private = parent.join(format!(".{}", std::process::id()));
+		std::fs::DirBuilder::new().mode(0o700).create(&private)?;
+		Ok(private)
+	}
+
+	fn remove_stale(path: &Path) -> io::Result<()> {
+		let metadata = match std::fs::symlink_metadata(path) {
+			Ok(metadata) => metadata,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+			Err(err) => return Err(err),
+		};
+		if !metadata.file_type().is_socket() {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				"the file exists and is not a socket",
+			));
+		}
+		if std::os::unix::net::UnixStream::connect(path).is_ok() {
+			return Err(io::Error::new(
+				io::ErrorKind::AddrInUse,
+				"another server listens on the socket",
+			));
+		}
+		log::info!("Removing the stale socket {}", path.display());
+		std::fs::remove_file(path)
+	}
+
+	/// Completes on `SIGINT` or `SIGTERM`, so that the server can stop
+	/// and remove its socket file
+	pub async fn shutdown_signal() {
+		use tokio::signal::unix::{signal, SignalKind};
+		let mut terminate = match signal(SignalKind::terminate()) {
+			Ok(terminate) => terminate,
+			Err(err) => {
+				log::error!("Cannot handle SIGTERM: {}", err);
+				let _ = tokio::signal::ctrl_c().await;
+				return;
+			}
+		};
+		tokio::select! {
+			_ = tokio::signal::ctrl_c() => {}
+			_ = terminate.recv() => {}
+		}
+		log::info!("Shutting down");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_socket_mode() {
+		let mode = |mode: &str| SocketMode::try_from(mode.to_string());
+		assert_eq!(mode("660"), Ok(SocketMode(0o660)));
+		assert_eq!(mode("0660"), Ok(SocketMode(0o660)));
+		assert_eq!(mode("0o600"), Ok(SocketMode(0o600)));
+		assert_eq!(String::from(SocketMode(0o60)), "060");
+		for invalid in ["", "888", "1777", "+660", "rw-rw----"] {
+			assert_eq!(
+				mode(invalid),
+				Err(format!(
+					"invalid socket mode {:?}, expected octal permissions like `660`",
+					invalid
+				))
+			);
+		}
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_bind() {
+		use std::os::unix::fs::PermissionsExt;
+		use tokio::io::{AsyncReadExt, AsyncWriteExt};
+		use warp::Filter;
+
+		let mut dir = std::env::temp_dir();
+		dir.push(format!("redwood-wiki-test-socket-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("wiki.sock");
+
+		// Left behind by a server that was killed
+		drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+		assert!(path.exists());
+
+		let (incoming, socket_file) = bind(&path, Some(SocketMode(0o600))).unwrap();
+		let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+		// Only the socket is left in the directory
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		let err = bind(&path, None).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+
+		let routes = warp::path("hello").map(|| "Hello");
+		tokio::spawn(warp::serve(routes).serve_incoming(incoming));
+		let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+		stream
+			.write_all(b"GET /hello HTTP/1.0\r\n\r\n")
+			.await
+			.unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).await.unwrap();
+		assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+		assert!(response.ends_with("Hello"), "{}", response);
+
+		drop(socket_file);
+		assert!(!path.exists());
+
+		// Other files are not replaced
+		std::fs::write(&path, "").unwrap();
+		let err = bind(&path, None).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,349 @@
+use std::convert::{Infallible, TryFrom};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use warp::filters::BoxedFilter;
+use warp::http::Uri;
+use warp::path::FullPath;
+use warp::{Filter, Rejection, Reply};
+
+use super::database::ItemId;
+
+/// Bring a URL path into its canonical form: Runs of slashes are
+/// collapsed into one and the trailing slash is removed, except for
+/// the root path `/` itself.
+///
+/// All URLs generated by the wiki must be in this form, otherwise
+/// following them leads to a redirect.
+pub fn normalize_path(path: &str) -> String {
+	let mut normalized = String::with_capacity(path.len() + 1);
+	for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+		normalized.push('/');
+		normalized.push_str(segment);
+	}
+	if normalized.is_empty() {
+		normalized.push('/');
+	}
+	normalized
+}
+
+/// Path the wiki is served under (`network.base_path`), e.g. `/wiki`
+/// behind a reverse proxy that forwards `https://example.com/wiki/...`.
+/// Empty if the wiki is served at the root. The paths built in this
+/// module do not include it, every link and redirect to one of them
+/// goes through `url`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BasePath(String);
+
+impl TryFrom<String> for BasePath {
+	type Error = String;
+
+	fn try_from(path: String) -> Result<BasePath, String> {
+		let allowed = |c: char| c.is_ascii_alphanumeric() || "/-._~".contains(c);
+		if !(path.is_empty() || path.starts_with('/')) || !path.chars().all(allowed) {
+			return Err(format!(
+				"invalid base path {:?}, expected a path like `/wiki`",
+				path
+			));
+		}
+		match normalize_path(&path).as_str() {
+			"/" => Ok(BasePath(String::new())),
+			normalized => Ok(BasePath(normalized.to_string())),
+		}
+	}
+}
+
+impl From<BasePath> for String {
+	fn from(base_path: BasePath) -> String {
+		base_path.0
+	}
+}
+
+impl BasePath {
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	/// Whether the wiki is served at the root of the site
+	pub fn is_root(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// URL of `path` on this site, e.g. `/wiki/articles` for
+	/// `/articles`. The root `/` is the base path itself.
+	pub fn url(&self, path: &str) -> String {
+		if path == "/" && !self.0.is_empty() {
+			self.0.clone()
+		} else {
+			format!("{}{}", self.0, path)
+		}
+	}
+
+	/// The path of a request without the base path, `None` if the
+	/// request is not for the wiki
+	pub fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+		match path.strip_prefix(self.0.as_str())? {
+			"" => Some("/"),
+			rest if rest.starts_with('/') => Some(rest),
+			_ => None,
+		}
+	}
+
+	/// Matches the segments of the base path, so the routes after it
+	/// see the rest of the path
+	pub fn filter(&self) -> BoxedFilter<()> {
+		self.0
+			.split('/')
+			.filter(|segment| !segment.is_empty())
+			.fold(warp::any().boxed(), |filter, segment| {
+				filter.and(warp::path(segment.to_string())).boxed()
+			})
+	}
+}
+
+/// Characters of a slug that are percent-encoded in URLs
+const SLUG_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+
+/// Characters of a file name that are percent-encoded in headers
+const FILENAME_ENCODE_SET: &AsciiSet = &SLUG_ENCODE_SET.remove(b'.');
+
+/// Canonical path of an article, `/article/{id}/{slug}`
+pub fn article_path(id: impl std::fmt::Display, slug: &str) -> String {
+	format!(
+		"/article/{}/{}",
+		id,
+		utf8_percent_encode(slug, SLUG_ENCODE_SET)
+	)
+}
+
+/// Path of a page about an article, e.g. `/edit/article/{id}/{slug}`
+/// for `page` `/edit`
+pub fn article_page_path(page: &str, id: impl std::fmt::Display, slug: &str) -> String {
+	format!("{}{}", page, article_path(id, slug))
+}
+
+/// `Content-Disposition` value suggesting `name` as file name. Names
+/// that are not plain ASCII are percent-encoded (RFC 6266), with
+/// `fallback` for clients that do not understand that.
+pub fn content_disposition(name: &str, fallback: &str) -> String {
+	let plain = |c: char| c.is_ascii_graphic() && c != '"' && c != '\\';
+	if name.chars().all(plain) {
+		format!("inline; filename=\"{}\"", name)
+	} else {
+		format!(
+			"inline; filename=\"{}\"; filename*=UTF-8''{}",
+			fallback,
+			utf8_percent_encode(name, FILENAME_ENCODE_SET)
+		)
+	}
+}
+
+/// Path of the list of articles with a tag, `/tag/{tag}`
+pub fn tag_path(tag: &str) -> String {
+	format!("/tag/{}", utf8_percent_encode(tag, SLUG_ENCODE_SET))
+}
+
+/// Absolute URL of a canonical path under the configured public URL of
+/// the wiki (e.g. `https://www.example.com/wiki`). Without a public URL,
+/// the absolute URL is unknown.
+pub fn canonical_url(public_url: Option<&str>, path: &str) -> Option<String> {
+	public_url.map(|public_url| format!("{}{}", public_url.trim_end_matches('/'), path))
+}
+
+/// Redirect requests to paths that are not in canonical form
+/// (see `normalize_path`) permanently to the canonical path,
+/// keeping the query string. Requests to canonical paths are
+/// rejected so the actual routes can handle them.
+pub fn normalize_path_filter() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path::full()
+		.and(warp::query::raw().or(warp::any().map(String::new)).unify())
+		.and_then(|path: FullPath, query: String| async move {
+			let normalized = normalize_path(path.as_str());
+			if normalized == path.as_str() {
+				return Err(warp::reject::not_found());
+			}
+			let target = if query.is_empty() {
+				normalized
+			} else {
+				format!("{}?{}", normalized, query)
+			};
+			match Uri::from_maybe_shared(target) {
+				Ok(uri) => Ok(warp::redirect(uri)),
+				Err(_) => Err(warp::reject::not_found()),
+			}
+		})
+}
+
+/// Rejection of a path segment that should be an article ID but is not
+#[derive(Debug)]
+pub struct InvalidItemId {
+	/// The segment as given in the request
+	pub segment: String,
+}
+
+impl warp::reject::Reject for InvalidItemId {}
+
+/// Extracts an `ItemId` from the next path segment. Unlike
+/// `warp::path::param`, a segment that is not a valid ID is rejected
+/// with `InvalidItemId` instead of "not found", so that the user can
+/// be told what is wrong.
+pub fn item_id() -> impl Filter<Extract = (ItemId,), Error = Rejection> + Copy {
+	warp::path::param::<String>().and_then(|segment: String| async move {
+		let decoded = percent_encoding::percent_decode_str(&segment)
+			.decode_utf8_lossy()
+			.into_owned();
+		decoded
+			.parse::<ItemId>()
+			.map_err(|_| warp::reject::custom(InvalidItemId { segment: decoded }))
+	})
+}
+
+/// The cosmetic slug segment after the ID of an article, if there is one
+pub fn optional_slug() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Copy {
+	warp::path::param::<String>()
+		.map(Some)
+		.or(warp::any().map(|| None))
+		.unify()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_normalize_path() {
+		assert_eq!(normalize_path("/"), "/");
+		assert_eq!(normalize_path(""), "/");
+		assert_eq!(normalize_path("//"), "/");
+		assert_eq!(normalize_path("/articles"), "/articles");
+		assert_eq!(normalize_path("/articles/"), "/articles");
+		assert_eq!(normalize_path("/article/5/"), "/article/5");
+		assert_eq!(normalize_path("//article//5"), "/article/5");
+		assert_eq!(normalize_path("/search/article///"), "/search/article");
+	}
+
+	#[test]
+	fn test_article_path() {
+		assert_eq!(article_path(5, "redwood-tree"), "/article/5/redwood-tree");
+		assert_eq!(article_path(7, "紅杉"), "/article/7/%E7%B4%85%E6%9D%89");
+	}
+
+	#[test]
+	fn test_content_disposition() {
+		assert_eq!(
+			content_disposition("redwood-tree.md", "article-5.md"),
+			r#"inline; filename="redwood-tree.md""#
+		);
+		assert_eq!(
+			content_disposition("紅杉.md", "article-7.md"),
+			r#"inline; filename="article-7.md"; filename*=UTF-8''%E7%B4%85%E6%9D%89.md"#
+		);
+		assert_eq!(
+			content_disposition("a \"b\".md", "article-8.md"),
+			r#"inline; filename="article-8.md"; filename*=UTF-8''a%20%22b%22.md"#
+		);
+	}
+
+	#[test]
+	fn test_tag_path() {
+		assert_eq!(tag_path("trees"), "/tag/trees");
+		assert_eq!(tag_path("coast redwood/α"), "/tag/coast%20redwood%2F%CE%B1");
+	}
+
+	#[test]
+	fn test_canonical_url() {
+		assert_eq!(canonical_url(None, "/article/5"), None);
+		assert_eq!(
+			canonical_url(Some("https://example.com/"), "/article/5").unwrap(),
+			"https://example.com/article/5"
+		);
+		assert_eq!(
+			canonical_url(Some("https://example.com/wiki"), "/article/5").unwrap(),
+			"https://example.com/wiki/article/5"
+		);
+	}
+
+	#[test]
+	fn test_base_path() {
+		let parse = |path: &str| BasePath::try_from(path.to_string());
+		assert_eq!(parse(""), Ok(BasePath::default()));
+		assert_eq!(parse("/"), Ok(BasePath::default()));
+		assert_eq!(parse("/wiki/"), Ok(BasePath("/wiki".to_string())));
+		assert_eq!(
+			parse("//team//wiki"),
+			Ok(BasePath("/team/wiki".to_string()))
+		);
+		for invalid in [
+			"wiki",
+			"/wiki?x",
+			"/my wiki",
+			"/wiki\"",
+			"https://example.com/wiki",
+		] {
+			assert!(parse(invalid).is_err(), "{}", invalid);
+		}
+
+		let base = parse("/wiki").unwrap();
+		assert_eq!(base.url("/"), "/wiki");
+		assert_eq!(base.url("/article/5/redwood"), "/wiki/article/5/redwood");
+		assert_eq!(base.strip("/wiki"), Some("/"));
+		assert_eq!(base.strip("/wiki/api/article"), Some("/api/article"));
+		assert_eq!(base.strip("/wikis/api"), None);
+		assert_eq!(base.strip("/api"), None);
+		let root = BasePath::default();
+		assert_eq!(root.url("/"), "/");
+		assert_eq!(root.url("/articles"), "/articles");
+		assert_eq!(root.strip("/api"), Some("/api"));
+	}
+
+	#[tokio::test]
+	async fn test_base_path_filter() {
+		let filter = BasePath::try_from("/team/wiki".to_string())
+			.unwrap()
+			.filter()
+			.and(warp::path("articles"))
+			.and(warp::path::end())
+			.map(|| "articles");
+		let matches = |path: &'static str| warp::test::request().path(path).matches(&filter);
+		assert!(matches("/team/wiki/articles").await);
+		assert!(!matches("/articles").await);
+		assert!(!matches("/team/articles").await);
+		assert!(
+			warp::test::request()
+				.path("/articles")
+				.matches(&BasePath::default().filter().and(warp::path("articles")))
+				.await
+		);
+	}
+
+	#[tokio::test]
+	async fn test_redirect() {
+		let filter = normalize_path_filter();
+
+		let response = warp::test::request()
+			.path("/article/5/")
+			.reply(&filter)
+			.await;
+		assert_eq!(response.status(), 301);
+		assert_eq!(response.headers()["location"], "/article/5");
+
+		let response = warp::test::request()
+			.path("//search//article/?search_term_plain=a%20b")
+			.reply(&filter)
+			.await;
+		assert_eq!(response.status(), 301);
+		assert_eq!(
+			response.headers()["location"],
+			"/search/article?search_term_plain=a%20b"
+		);
+
+		assert!(
+			!warp::test::request()
+				.path("/article/5")
+				.matches(&filter)
+				.await
+		);
+		assert!(!warp::test::request().path("/").matches(&filter).await);
+	}
+}
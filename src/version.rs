@@ -0,0 +1,69 @@
+use chrono::NaiveDateTime;
+
+/// Information about the running build of redwood-wiki,
+/// collected at compile time by the build script
+pub struct VersionInfo {
+	pub version: &'static str,
+	pub git_commit: &'static str,
+	pub build_date: Option<NaiveDateTime>,
+	pub features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+	pub fn get() -> VersionInfo {
+		VersionInfo {
+			version: env!("CARGO_PKG_VERSION"),
+			git_commit: env!("REDWOOD_GIT_COMMIT"),
+			build_date: env!("REDWOOD_BUILD_TIMESTAMP")
+				.parse::<i64>()
+				.ok()
+				.and_then(|timestamp| NaiveDateTime::from_timestamp_opt(timestamp, 0)),
+			features: env!("REDWOOD_FEATURES")
+				.split(',')
+				.filter(|feature| !feature.is_empty())
+				.collect(),
+		}
+	}
+
+	/// Abbreviated form for the `Server` response header
+	pub fn short(&self) -> String {
+		format!("redwood-wiki/{}", self.version)
+	}
+}
+
+impl std::fmt::Display for VersionInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+		write!(
+			f,
+			"redwood-wiki {} (commit {}",
+			self.version, self.git_commit
+		)?;
+		if let Some(build_date) = self.build_date {
+			write!(f, ", built {} UTC", build_date.format("%Y-%m-%d %H:%M:%S"))?;
+		}
+		if self.features.is_empty() {
+			write!(f, ", no optional features)")
+		} else {
+			write!(f, ", features: {})", self.features.join(", "))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_version_info() {
+		let info = VersionInfo::get();
+		assert_eq!(
+			info.short(),
+			format!("redwood-wiki/{}", env!("CARGO_PKG_VERSION"))
+		);
+		assert!(!info.git_commit.is_empty());
+		assert!(info.build_date.is_some());
+		assert!(info
+			.to_string()
+			.starts_with(&format!("redwood-wiki {} (commit ", info.version)));
+	}
+}
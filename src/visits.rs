@@ -0,0 +1,109 @@
+use chrono::NaiveDateTime;
+
+use super::timestamp::UtcTimestamp;
+
+/// Cookie with the time the article list was last shown, in
+/// milliseconds since the Unix epoch
+pub const COOKIE_NAME: &str = "last_listing_visit";
+
+/// The cookie is kept for a year after the last visit
+const COOKIE_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+/// Time of the last visit stored in the cookie. Malformed values are
+/// ignored, times in the future (clock skew, or a tampered cookie) are
+/// clamped to `now` so they cannot hide changes indefinitely.
+pub fn parse_cookie(value: &str, now: UtcTimestamp) -> Option<UtcTimestamp> {
+	let value = value.trim();
+	// Strict, `parse` would accept a sign
+	if value.is_empty() || value.len() > 15 || !value.bytes().all(|b| b.is_ascii_digit()) {
+		return None;
+	}
+	let millis = value.parse::<i64>().ok()?;
+	let visit = UtcTimestamp::from_naive_utc(NaiveDateTime::from_timestamp_millis(millis)?);
+	Some(visit.min(now))
+}
+
+/// `Set-Cookie` header value recording a visit at `now`. The time is
+/// rounded up to the next millisecond, rounded down a change shortly
+/// before the visit would count as new.
+pub fn cookie(now: UtcTimestamp) -> String {
+	let now = now.naive_utc().and_utc();
+	let mut millis = now.timestamp_millis();
+	if !now.timestamp_subsec_nanos().is_multiple_of(1_000_000) {
+		millis += 1;
+	}
+	format!(
+		"{}={}; Path=/; Max-Age={}; SameSite=Lax; HttpOnly",
+		COOKIE_NAME, millis, COOKIE_MAX_AGE
+	)
+}
+
+/// Whether an article was changed after the last visit. A change at
+/// exactly the time of the visit was already seen.
+pub fn is_new(date_modified: UtcTimestamp, last_visit: Option<UtcTimestamp>) -> bool {
+	last_visit.is_some_and(|visit| date_modified > visit)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use chrono::Duration;
+
+	fn time(millis: i64) -> UtcTimestamp {
+		UtcTimestamp::from_naive_utc(NaiveDateTime::from_timestamp_millis(millis).unwrap())
+	}
+
+	#[test]
+	fn test_parse_cookie() {
+		let now = time(1_700_000_000_000);
+		assert_eq!(
+			parse_cookie("1600000000123", now),
+			Some(time(1_600_000_000_123))
+		);
+		assert_eq!(parse_cookie(" 0 ", now), Some(time(0)));
+		// In the future
+		assert_eq!(parse_cookie("1800000000000", now), Some(now));
+		for malformed in ["", "abc", "-5", "+5", "1.5", "1e12", "99999999999999999999"] {
+			assert_eq!(parse_cookie(malformed, now), None, "{:?}", malformed);
+		}
+	}
+
+	#[test]
+	fn test_cookie_roundtrip() {
+		let now = time(1_700_000_000_456);
+		let cookie = cookie(now);
+		assert!(cookie.starts_with("last_listing_visit=1700000000456;"));
+		let value = cookie
+			.trim_start_matches("last_listing_visit=")
+			.split(';')
+			.next()
+			.unwrap();
+		assert_eq!(parse_cookie(value, now), Some(now));
+	}
+
+	#[test]
+	fn test_cookie_rounds_up() {
+		let now = time(1_700_000_000_456) + Duration::nanoseconds(1);
+		let cookie = cookie(now);
+		assert!(cookie.starts_with("last_listing_visit=1700000000457;"));
+		let value = cookie
+			.trim_start_matches("last_listing_visit=")
+			.split(';')
+			.next()
+			.unwrap();
+		let visit = parse_cookie(value, now + Duration::seconds(1));
+		// A change at the time of the visit was already seen
+		assert!(!is_new(now, visit));
+	}
+
+	#[test]
+	fn test_is_new() {
+		let visit = time(1_700_000_000_000);
+		assert!(!is_new(visit, Some(visit)));
+		assert!(is_new(visit + Duration::milliseconds(1), Some(visit)));
+		assert!(!is_new(visit - Duration::milliseconds(1), Some(visit)));
+		// Without a visit, nothing is new
+		assert!(!is_new(visit, None));
+	}
+}